@@ -0,0 +1,85 @@
+//! Times building a [`RepoIndex`] over a synthetic endpoint folder of 5000 object files and
+//! looking every object up, run with `cargo bench --bench repo_index`.
+
+use std::path::Path;
+use std::time::Instant;
+
+use shepherd::models::ObjectType;
+use shepherd::utils::ignore::ShepherdIgnore;
+use shepherd::utils::index::RepoIndex;
+
+const CLUSTERS: usize = 5;
+const PROJECTS_PER_CLUSTER: usize = 20;
+const BINDINGS_PER_PROJECT: usize = 49;
+
+fn write(path: &Path, contents: &str) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, contents).unwrap();
+}
+
+/// Lay out `CLUSTERS` clusters with `PROJECTS_PER_CLUSTER` projects of `BINDINGS_PER_PROJECT` bindings each.
+fn synthetic_repo(endpoint: &Path) -> usize {
+    let mut files = 0;
+    for c in 0..CLUSTERS {
+        let cluster_id = format!("c-{c:05}");
+        let cluster_folder = endpoint.join(&cluster_id);
+        write(
+            &cluster_folder.join(format!("{cluster_id}.cluster.yaml")),
+            &format!("id: {cluster_id}\ndisplay_name: cluster-{c}\n"),
+        );
+        files += 1;
+        for p in 0..PROJECTS_PER_CLUSTER {
+            let project_id = format!("p-{c:02}{p:03}");
+            let project_folder = cluster_folder.join(&project_id);
+            write(
+                &project_folder.join(format!("{project_id}.project.yaml")),
+                &format!("id: {project_id}\nnamespace: {cluster_id}\ncluster_name: {cluster_id}\ndisplay_name: project-{p}\n"),
+            );
+            files += 1;
+            for b in 0..BINDINGS_PER_PROJECT {
+                let id = format!("prtb-{b:04}");
+                write(
+                    &project_folder.join(format!("{id}.prtb.yaml")),
+                    &format!(
+                        "id: {id}\nnamespace: {project_id}\nproject_name: {cluster_id}:{project_id}\nrole_template_name: project-member\nuser_name: u-{b:04}\n"
+                    ),
+                );
+                files += 1;
+            }
+        }
+    }
+    files
+}
+
+#[tokio::main]
+async fn main() {
+    let dir = tempfile::tempdir().unwrap();
+    let endpoint = dir.path().join("rancher.example.com");
+    let files = synthetic_repo(&endpoint);
+
+    let started = Instant::now();
+    let index = RepoIndex::build(dir.path(), &endpoint, &ShepherdIgnore::default()).await;
+    let build = started.elapsed();
+    assert_eq!(index.len(), files);
+
+    let started = Instant::now();
+    let mut found = 0;
+    for c in 0..CLUSTERS {
+        for p in 0..PROJECTS_PER_CLUSTER {
+            let project_id = format!("p-{c:02}{p:03}");
+            for b in 0..BINDINGS_PER_PROJECT {
+                let id = format!("prtb-{b:04}");
+                found += usize::from(
+                    index
+                        .path(ObjectType::ProjectRoleTemplateBinding, Some(&project_id), &id)
+                        .is_some(),
+                );
+            }
+        }
+    }
+    let lookups = started.elapsed();
+    assert_eq!(found, CLUSTERS * PROJECTS_PER_CLUSTER * BINDINGS_PER_PROJECT);
+
+    println!("indexed {files} files in {build:?}");
+    println!("looked up {found} bindings in {lookups:?} ({:?} each)", lookups / found as u32);
+}
@@ -0,0 +1,89 @@
+//! Times loading the stored configuration of 20 clusters one cluster at a time, once for all of
+//! them with [`StoredConfigs`], and again from the [`ParseCache`], run with
+//! `cargo bench --bench stored_configs`.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use shepherd::utils::file::{endpoint_folder, FileFormat};
+use shepherd::utils::ignore::ShepherdIgnore;
+use shepherd::utils::index::RepoIndex;
+use shepherd::utils::parse_cache::ParseCache;
+use shepherd::{load_configuration, StoredConfigs};
+
+const ENDPOINT_URL: &str = "https://rancher.example.com";
+const CLUSTERS: usize = 20;
+const PROJECTS_PER_CLUSTER: usize = 5;
+const BINDINGS_PER_PROJECT: usize = 4;
+const ROLE_TEMPLATES: usize = 100;
+
+fn write(path: &Path, contents: &str) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, contents).unwrap();
+}
+
+/// Lay out `ROLE_TEMPLATES` role templates and `CLUSTERS` clusters with `PROJECTS_PER_CLUSTER`
+/// projects of `BINDINGS_PER_PROJECT` bindings each.
+fn synthetic_repo(endpoint: &Path) {
+    let rules = "rules:\n- apiGroups:\n  - ''\n  resources:\n  - pods\n  - services\n  verbs:\n  - get\n  - list\n  - watch\n";
+    for rt in 0..ROLE_TEMPLATES {
+        write(
+            &endpoint.join("roles").join(format!("rt-{rt}.rt.yaml")),
+            &format!("id: rt-{rt}\ndisplay_name: Role {rt}\ncontext: project\nrole_template_names:\n- view\n{rules}labels:\n  team: t-{rt}\n"),
+        );
+    }
+    for c in 0..CLUSTERS {
+        let cluster_id = format!("c-{c}");
+        let cluster_folder = endpoint.join(&cluster_id);
+        write(
+            &cluster_folder.join(format!("{cluster_id}.cluster.yaml")),
+            &format!("id: {cluster_id}\ndisplay_name: cluster {c}\n"),
+        );
+        for p in 0..PROJECTS_PER_CLUSTER {
+            let project_id = format!("p-{c}-{p}");
+            let project_folder = cluster_folder.join(&project_id);
+            write(
+                &project_folder.join(format!("{project_id}.project.yaml")),
+                &format!("id: {project_id}\ndisplay_name: team {p}\ncluster_name: {cluster_id}\nnamespace: {cluster_id}\n"),
+            );
+            for b in 0..BINDINGS_PER_PROJECT {
+                write(
+                    &project_folder.join(format!("prtb-{b}.prtb.yaml")),
+                    &format!(
+                        "id: prtb-{b}\nnamespace: {project_id}\nproject_name: {cluster_id}:{project_id}\nrole_template_name: rt-{b}\nuser_name: u-{b}\n"
+                    ),
+                );
+            }
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let dir = tempfile::tempdir().unwrap();
+    let endpoint = endpoint_folder(dir.path(), ENDPOINT_URL);
+    synthetic_repo(&endpoint);
+    let index = RepoIndex::build(dir.path(), &endpoint, &ShepherdIgnore::default()).await;
+    let cluster_ids: Vec<String> = (0..CLUSTERS).map(|c| format!("c-{c}")).collect();
+
+    // every cluster loading the whole repository on its own, as the compare used to
+    let started = Instant::now();
+    for cluster_id in &cluster_ids {
+        load_configuration(dir.path(), ENDPOINT_URL, cluster_id, &FileFormat::Yaml, false).await.unwrap();
+    }
+    let per_cluster = started.elapsed();
+
+    let cache = Arc::new(ParseCache::default());
+    let started = Instant::now();
+    StoredConfigs::load(dir.path(), ENDPOINT_URL, &index, &cluster_ids, &FileFormat::Yaml, false, cache.clone()).await;
+    let once = started.elapsed();
+
+    let started = Instant::now();
+    StoredConfigs::load(dir.path(), ENDPOINT_URL, &index, &cluster_ids, &FileFormat::Yaml, false, cache.clone()).await;
+    let cached = started.elapsed();
+
+    println!("loaded {CLUSTERS} clusters one at a time in {per_cluster:?}");
+    println!("loaded them at once in {once:?}");
+    println!("loaded them again from the cache in {cached:?}");
+}
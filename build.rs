@@ -1,21 +1,88 @@
 use std::env;
 use std::fs;
 use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The commit being built, `unknown` outside a git checkout or without git.
+fn git_sha() -> String {
+    Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|sha| sha.trim().to_string())
+        .filter(|sha| !sha.is_empty())
+        .unwrap_or_else(|| "unknown".into())
+}
+
+/// The build time as an RFC 3339 UTC timestamp, taken from `SOURCE_DATE_EPOCH` when set so
+/// reproducible builds embed the same value.
+fn build_timestamp() -> String {
+    let seconds = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<i64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs() as i64)
+                .unwrap_or_default()
+        });
+    let (days, time) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+
+    // civil date of a day count since 1970-01-01, after Howard Hinnant's days_from_civil inverse
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
 
 fn main() {
-    let client_name = "sheperd";
+    let client_name = "shepherd";
     let client_version = env::var("CARGO_PKG_VERSION").unwrap_or_else(|_| "unknown".into());
 
+    let full_client_id = format!("{}/{}", client_name, client_version);
+
     let contents = format!(
         r#"pub const CLIENT_NAME: &str = "{}";
 pub const CLIENT_VERSION: &str = "{}";
-pub const FULL_CLIENT_ID: &str = "{}";"#,
+pub const FULL_CLIENT_ID: &str = "{}";
+pub const GIT_SHA: &str = "{}";
+pub const BUILD_TIMESTAMP: &str = "{}";"#,
         client_name,
         client_version,
-        format!("{}/{}", client_name, client_version)
+        full_client_id,
+        git_sha(),
+        build_timestamp()
     );
 
     let out_dir = env::var("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("client_info.rs");
     fs::write(dest_path, contents).unwrap();
+
+    // pick up a change of the sources, a new commit or checkout, and a new SOURCE_DATE_EPOCH
+    println!("cargo:rerun-if-changed=src");
+    for git_file in [".git/HEAD", ".git/refs", ".git/packed-refs"] {
+        if Path::new(git_file).exists() {
+            println!("cargo:rerun-if-changed={}", git_file);
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
 }
@@ -0,0 +1,134 @@
+use std::time::{Duration, Instant};
+
+/// Whether the API phases of a run may talk to the endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// The endpoint is healthy, run normally
+    Closed,
+    /// The endpoint failed too often, skip the API phases for the remaining cool-down
+    Open(Duration),
+    /// The cool-down is over, a single probe request decides whether to close the circuit
+    HalfOpen,
+}
+
+/// Stops a sync loop from hammering an endpoint that is down.
+///
+/// After `threshold` consecutive failed runs the circuit opens and the API phases are
+/// skipped for `cooldown`. Every failed probe after a cool-down doubles the cool-down,
+/// up to `max_cooldown`. A successful probe or run closes the circuit again. A
+/// `threshold` of 0 disables the breaker.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    base_cooldown: Duration,
+    max_cooldown: Duration,
+    cooldown: Duration,
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    /// Keep pulling and pushing the repository while the circuit is open
+    pub sync_git_when_open: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration, max_cooldown: Duration, sync_git_when_open: bool) -> Self {
+        Self {
+            threshold,
+            base_cooldown: cooldown,
+            max_cooldown: max_cooldown.max(cooldown),
+            cooldown,
+            consecutive_failures: 0,
+            open_until: None,
+            sync_git_when_open,
+        }
+    }
+
+    /// The state of the circuit at `now`.
+    pub fn state(&self, now: Instant) -> CircuitState {
+        match self.open_until {
+            None => CircuitState::Closed,
+            Some(until) if now < until => CircuitState::Open(until - now),
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Record a run or probe that reached the endpoint, closing the circuit.
+    pub fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.open_until = None;
+        self.cooldown = self.base_cooldown;
+    }
+
+    /// Record a run or probe that could not reach the endpoint.
+    ///
+    /// # Returns
+    /// The cool-down if this failure opened the circuit
+    pub fn record_failure(&mut self, now: Instant) -> Option<Duration> {
+        if self.threshold == 0 {
+            return None;
+        }
+
+        if self.open_until.is_some() {
+            // a failed probe, back off further
+            self.cooldown = (self.cooldown * 2).min(self.max_cooldown);
+        } else {
+            self.consecutive_failures += 1;
+            if self.consecutive_failures < self.threshold {
+                return None;
+            }
+        }
+
+        self.open_until = Some(now + self.cooldown);
+        Some(self.cooldown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(2, Duration::from_secs(60), Duration::from_secs(150), true)
+    }
+
+    #[test]
+    fn test_opens_after_threshold_and_half_opens_after_cooldown() {
+        let mut breaker = breaker();
+        let now = Instant::now();
+
+        assert_eq!(breaker.record_failure(now), None);
+        assert_eq!(breaker.state(now), CircuitState::Closed);
+        assert_eq!(breaker.record_failure(now), Some(Duration::from_secs(60)));
+        assert_eq!(
+            breaker.state(now + Duration::from_secs(20)),
+            CircuitState::Open(Duration::from_secs(40))
+        );
+        assert_eq!(breaker.state(now + Duration::from_secs(60)), CircuitState::HalfOpen);
+    }
+
+    #[test]
+    fn test_failed_probes_back_off_up_to_the_cap() {
+        let mut breaker = breaker();
+        let now = Instant::now();
+        breaker.record_failure(now);
+        breaker.record_failure(now);
+
+        assert_eq!(breaker.record_failure(now), Some(Duration::from_secs(120)));
+        assert_eq!(breaker.record_failure(now), Some(Duration::from_secs(150)));
+
+        breaker.record_success();
+        assert_eq!(breaker.state(now), CircuitState::Closed);
+        assert_eq!(breaker.record_failure(now), None);
+        assert_eq!(breaker.record_failure(now), Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_zero_threshold_never_opens() {
+        let mut breaker = CircuitBreaker::new(0, Duration::from_secs(60), Duration::from_secs(60), true);
+        let now = Instant::now();
+
+        for _ in 0..10 {
+            assert_eq!(breaker.record_failure(now), None);
+        }
+        assert_eq!(breaker.state(now), CircuitState::Closed);
+    }
+}
@@ -1,22 +1,129 @@
-use std::sync::Arc;
+use std::fmt::Display;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
 
+use anyhow::{bail, Context};
 use rancher_client::apis::configuration::{ApiKey, Configuration};
+use reqwest::Url;
 use reqwest_middleware::ClientBuilder;
 
+use crate::api::client_info::field_manager;
+use crate::api::kubeconfig::{load_kubeconfig, Credential, ExecAuth, KubeconfigAuth, KubeconfigCredentials, EXEC_TIMEOUT};
+
+/// `endpoint_url` as the base path of the API, which `rancher_client` appends `/apis/...` to.
+///
+/// The path prefix of a Rancher behind a reverse proxy, such as `https://portal.example.com/rancher/`,
+/// is kept without empty segments or a trailing slash, so no request URL has a double slash.
+/// A query or fragment is dropped, it would end up in the middle of every request URL.
+pub fn base_path(endpoint_url: &str) -> String {
+    let endpoint_url = endpoint_url.trim();
+    let Some(mut url) = Url::parse(endpoint_url).ok().filter(|url| url.has_host()) else {
+        return endpoint_url.trim_end_matches('/').to_string();
+    };
+    let path: Vec<String> = url
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect();
+    url.set_path(&path.join("/"));
+    url.set_query(None);
+    url.set_fragment(None);
+    url.as_str().trim_end_matches('/').to_string()
+}
+
 fn rancher_config_init(endpoint_url: &str, token: &str) -> Configuration {
     let mut config = Configuration::new();
-    config.base_path = endpoint_url.to_string();
+    config.base_path = base_path(endpoint_url);
 
     config.api_key = Some(ApiKey {
         prefix: Some("Bearer".to_string()),
         key: token.to_string(),
     });
+    // Delete endpoints take no fieldManager, so the user agent is what identifies us there
+    config.user_agent = Some(field_manager().to_string());
     config
 }
 
 
 
 
+/// The Rancher setting holding the version of the server.
+pub const SERVER_VERSION_SETTING: &str = "server-version";
+
+/// The version each server reported last by the base path of its configuration, see
+/// [`record_server_version`]. `Configuration` belongs to `rancher_client` and has no room for it.
+static SERVER_VERSIONS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Remember `version` as the version of the server of `configuration`, for the errors of
+/// [`with_server_version`].
+pub fn record_server_version(configuration: &Configuration, version: &str) {
+    SERVER_VERSIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(configuration.base_path.clone(), version.to_string());
+}
+
+/// The version of the server of `configuration` recorded last, `None` before it was probed.
+pub fn recorded_server_version(configuration: &Configuration) -> Option<String> {
+    SERVER_VERSIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&configuration.base_path)
+        .cloned()
+}
+
+/// `message` about a response of the server of `configuration` that does not parse, naming
+/// the version of the server if it is known, as such failures usually come with a Rancher upgrade.
+pub fn with_server_version(configuration: &Configuration, message: impl Display) -> String {
+    match recorded_server_version(configuration) {
+        Some(version) => format!("{} (Rancher {})", message, version),
+        None => message.to_string(),
+    }
+}
+
+/// Read the version of the server, such as `v2.8.5`, from its `server-version` setting.
+///
+/// # Errors
+/// If the setting cannot be read or holds no version
+pub async fn server_version(configuration: &Configuration) -> anyhow::Result<String> {
+    let url = format!("{}/apis/management.cattle.io/v3/settings/{}", configuration.base_path, SERVER_VERSION_SETTING);
+    let mut request = configuration.client.get(&url);
+    if let Some(user_agent) = &configuration.user_agent {
+        request = request.header(reqwest::header::USER_AGENT, user_agent);
+    }
+    if let Some(api_key) = &configuration.api_key {
+        let value = match &api_key.prefix {
+            Some(prefix) => format!("{} {}", prefix, api_key.key),
+            None => api_key.key.clone(),
+        };
+        request = request.header(reqwest::header::AUTHORIZATION, value);
+    }
+    let response = request.send().await.with_context(|| format!("Failed to read the {} setting", SERVER_VERSION_SETTING))?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("Rancher answered {} reading the {} setting", status, SERVER_VERSION_SETTING);
+    }
+    let setting: serde_json::Value = response
+        .json()
+        .await
+        .with_context(|| format!("The {} setting is not JSON", SERVER_VERSION_SETTING))?;
+    // an unset setting has only its default
+    ["value", "default"]
+        .iter()
+        .filter_map(|field| setting.get(field)?.as_str())
+        .find(|version| !version.is_empty())
+        .map(str::to_string)
+        .with_context(|| format!("The {} setting holds no version", SERVER_VERSION_SETTING))
+}
+
+/// Map a dry-run flag onto the `dryRun` query parameter of mutating calls.
+pub fn dry_run_param(dry_run: bool) -> Option<&'static str> {
+    dry_run.then_some("All")
+}
+
+
 pub struct ShepherdClient {
     pub config: Arc<Configuration>,
 }
@@ -40,4 +147,138 @@ impl ShepherdClient {
         }
     }
 
-}
\ No newline at end of file
+    /// Build a client from the server, CA and credential of a kubeconfig context.
+    ///
+    /// A static token is sent like the `token` of the configuration, an exec plugin is run
+    /// for the first request and again whenever its token expires or is refused.
+    ///
+    /// # Errors
+    /// If the kubeconfig cannot be read or its CA is not PEM
+    pub fn from_kubeconfig(auth: &KubeconfigAuth, allow_insecure: bool) -> anyhow::Result<(Self, KubeconfigCredentials)> {
+        let credentials = load_kubeconfig(auth)?;
+
+        let mut builder = reqwest::Client::builder()
+            .danger_accept_invalid_certs(allow_insecure || credentials.insecure_skip_tls_verify);
+        if let Some(ca_pem) = &credentials.ca_pem {
+            for certificate in reqwest::Certificate::from_pem_bundle(ca_pem)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        let client = builder.build()?;
+
+        let config = match &credentials.credential {
+            Credential::Token(token) => {
+                let mut config = rancher_config_init(&credentials.server, token);
+                config.client = ClientBuilder::new(client).build();
+                config
+            }
+            Credential::Exec(plugin) => {
+                // the middleware sets the header, so there is no api key to send
+                let mut config = rancher_config_init(&credentials.server, "");
+                config.api_key = None;
+                let server = Url::parse(&config.base_path)
+                    .with_context(|| format!("Server `{}` of the kubeconfig is not a URL", credentials.server))?;
+                config.client = ClientBuilder::new(client)
+                    .with(ExecAuth::new(plugin.clone(), server, EXEC_TIMEOUT))
+                    .build();
+                config
+            }
+        };
+
+        Ok((
+            Self {
+                config: Arc::new(config),
+            },
+            credentials,
+        ))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::cluster::get_clusters;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_base_paths_keep_the_path_prefix() {
+        let cases = [
+            ("https://rancher.example.com", "https://rancher.example.com"),
+            ("https://rancher.example.com/", "https://rancher.example.com"),
+            ("https://portal.example.com/rancher", "https://portal.example.com/rancher"),
+            ("https://portal.example.com/rancher/", "https://portal.example.com/rancher"),
+            ("https://portal.example.com//rancher//api/", "https://portal.example.com/rancher/api"),
+            ("https://portal.example.com:8443/rancher/?team=a#top", "https://portal.example.com:8443/rancher"),
+            (" https://rancher.example.com:443/ ", "https://rancher.example.com"),
+            ("rancher.example.com/", "rancher.example.com"),
+        ];
+
+        for (endpoint_url, expected) in cases {
+            assert_eq!(base_path(endpoint_url), expected, "{}", endpoint_url);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_version_is_read_from_its_setting() {
+        let server = MockServer::start().await;
+        let setting = |value: &str| {
+            serde_json::json!({
+                "apiVersion": "management.cattle.io/v3",
+                "kind": "Setting",
+                "metadata": { "name": "server-version" },
+                "value": value,
+                "default": "v2.7.0"
+            })
+        };
+        Mock::given(method("GET"))
+            .and(path("/rancher/apis/management.cattle.io/v3/settings/server-version"))
+            .and(header("Authorization", "Bearer token-abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(setting("v2.8.5")))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rancher/apis/management.cattle.io/v3/settings/server-version"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(setting("")))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        let client = ShepherdClient::new(&format!("{}/rancher", server.uri()), "token-abc", false);
+
+        assert_eq!(server_version(&client.config).await.unwrap(), "v2.8.5");
+        assert_eq!(server_version(&client.config).await.unwrap(), "v2.7.0");
+        let error = server_version(&client.config).await.unwrap_err();
+        assert_eq!(error.to_string(), "Rancher answered 404 Not Found reading the server-version setting");
+    }
+
+    #[test]
+    fn test_errors_name_the_recorded_server_version() {
+        let upgraded = ShepherdClient::new("https://upgraded.example.com", "token-abc", false);
+        let other = ShepherdClient::new("https://other.example.com", "token-abc", false);
+        record_server_version(&upgraded.config, "v2.9.1");
+
+        assert_eq!(
+            with_server_version(&upgraded.config, "Failed to deserialize projects response: missing field `x`"),
+            "Failed to deserialize projects response: missing field `x` (Rancher v2.9.1)"
+        );
+        assert_eq!(with_server_version(&other.config, "Failed"), "Failed");
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_below_the_path_prefix() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rancher/apis/management.cattle.io/v3/clusters"))
+            .and(header("Authorization", "Bearer token-abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "metadata": {}, "items": [] })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        for endpoint_url in [format!("{}/rancher/", server.uri()), format!("{}//rancher", server.uri())] {
+            let client = ShepherdClient::new(&endpoint_url, "token-abc", false);
+            assert_eq!(client.config.base_path, format!("{}/rancher", server.uri()));
+            get_clusters(&client.config, None, None, None, None, None, None).await.unwrap();
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use std::sync::OnceLock;
+
+use crate::{BUILD_TIMESTAMP, CLIENT_VERSION, FULL_CLIENT_ID, GIT_SHA};
+
+static FIELD_MANAGER: OnceLock<String> = OnceLock::new();
+static VERSION_INFO: OnceLock<String> = OnceLock::new();
+
+/// Override the client name used as the field manager on mutating API calls.
+///
+/// The version is always appended, so `"shepherd-prod"` becomes
+/// `"shepherd-prod/<version>"`. Requests sent before the call go out with the
+/// default name, a second call is ignored.
+///
+/// # Arguments
+/// * `client_name` - The name to identify this client as
+///
+pub fn set_client_name(client_name: &str) {
+    let _ = FIELD_MANAGER.set(format!("{}/{}", client_name, CLIENT_VERSION));
+}
+
+/// The field manager sent with every create and patch request.
+///
+/// Defaults to `FULL_CLIENT_ID` unless overridden with [`set_client_name`].
+pub fn field_manager() -> &'static str {
+    FIELD_MANAGER
+        .get()
+        .map(String::as_str)
+        .unwrap_or(FULL_CLIENT_ID)
+}
+
+/// The version of the binary with the commit and time it was built from and the client ID
+/// it identifies as, such as `0.1.0 (git 1a2b3c4d5e6f, built 2026-10-17T09:00:00Z, shepherd/0.1.0)`.
+///
+/// Printed by `--version` and logged at startup.
+pub fn version_info() -> &'static str {
+    VERSION_INFO.get_or_init(|| format!("{} (git {}, built {}, {})", CLIENT_VERSION, GIT_SHA, BUILD_TIMESTAMP, FULL_CLIENT_ID))
+}
@@ -0,0 +1,120 @@
+//! The clock of this host compared to the one of the endpoint.
+//!
+//! A drifted clock gets tokens refused with `401 Unauthorized` and SSH signatures rejected by
+//! the git remote, which look like wrong credentials; the `Date` header of the endpoint tells.
+
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, TimeDelta, Utc};
+use rancher_client::apis::configuration::Configuration;
+use tracing::debug;
+
+use crate::error::ClockSkewError;
+
+/// The longest a clock check may take, it never holds up a run for longer.
+pub const CLOCK_CHECK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Authentication failures in a run, refused API calls and git remotes, after which the clock is checked again.
+pub const AUTH_FAILURES_BEFORE_CLOCK_CHECK: usize = 2;
+
+/// How far the clock of this host is ahead of the endpoint, negative when it is behind.
+///
+/// The endpoint is asked with a `HEAD` request, its `Date` header is compared to the local
+/// time halfway through the request. The header has whole seconds only.
+///
+/// # Errors
+/// If the endpoint does not answer within [`CLOCK_CHECK_TIMEOUT`] or has no `Date` header
+pub async fn clock_skew(configuration: &Configuration) -> anyhow::Result<TimeDelta> {
+    let sent = Utc::now();
+    let response = tokio::time::timeout(CLOCK_CHECK_TIMEOUT, configuration.client.head(&configuration.base_path).send())
+        .await
+        .with_context(|| format!("{} did not answer within {:?}", configuration.base_path, CLOCK_CHECK_TIMEOUT))?
+        .with_context(|| format!("Failed to reach {}", configuration.base_path))?;
+    let received = Utc::now();
+    let date = response
+        .headers()
+        .get(reqwest::header::DATE)
+        .with_context(|| format!("{} answered without a Date header", configuration.base_path))?
+        .to_str()?;
+    let server_time = DateTime::parse_from_rfc2822(date)
+        .with_context(|| format!("{} answered with the Date header `{}`", configuration.base_path, date))?;
+    Ok(sent + (received - sent) / 2 - server_time.with_timezone(&Utc))
+}
+
+/// The skew of the clock of this host if it is more than `max_skew` off the endpoint.
+///
+/// A check that fails is logged and taken for a clock that is right, it is only a hint.
+pub async fn check_clock(configuration: &Configuration, max_skew: Duration) -> Option<ClockSkewError> {
+    match clock_skew(configuration).await {
+        Ok(skew) if skew.abs().to_std().unwrap_or_default() > max_skew => Some(ClockSkewError {
+            endpoint: configuration.base_path.clone(),
+            skew_seconds: skew.num_seconds(),
+        }),
+        Ok(skew) => {
+            debug!("Clock is {}ms off {}", skew.num_milliseconds(), configuration.base_path);
+            None
+        }
+        Err(e) => {
+            debug!("Cannot check the clock: {:#}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn endpoint(response: ResponseTemplate) -> (MockServer, Configuration) {
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD")).respond_with(response).mount(&server).await;
+        let mut configuration = Configuration::new();
+        configuration.base_path = server.uri();
+        (server, configuration)
+    }
+
+    fn dated(offset: TimeDelta) -> ResponseTemplate {
+        ResponseTemplate::new(401).insert_header("Date", (Utc::now() + offset).to_rfc2822().as_str())
+    }
+
+    #[tokio::test]
+    async fn test_a_skewed_date_header_is_reported() {
+        let (_server, configuration) = endpoint(dated(TimeDelta::minutes(-10))).await;
+
+        let skew = clock_skew(&configuration).await.unwrap();
+        assert!((598..=602).contains(&skew.num_seconds()), "{:?}", skew);
+
+        let error = check_clock(&configuration, Duration::from_secs(30)).await.unwrap();
+        assert!((598..=602).contains(&error.skew_seconds), "{:?}", error);
+        assert!(error.to_string().contains("ahead of http://"), "{}", error);
+        assert!(check_clock(&configuration, Duration::from_secs(900)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_a_clock_behind_the_endpoint_is_reported() {
+        let (_server, configuration) = endpoint(dated(TimeDelta::minutes(5))).await;
+
+        let error = check_clock(&configuration, Duration::from_secs(30)).await.unwrap();
+        assert!((-302..=-298).contains(&error.skew_seconds), "{:?}", error);
+        assert!(error.to_string().contains("behind"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_a_right_clock_is_not_reported() {
+        let (_server, configuration) = endpoint(dated(TimeDelta::zero())).await;
+
+        assert!(check_clock(&configuration, Duration::from_secs(5)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_a_slow_endpoint_does_not_hold_up_the_check() {
+        let (_server, configuration) = endpoint(dated(TimeDelta::hours(1)).set_delay(Duration::from_secs(10))).await;
+
+        let started = std::time::Instant::now();
+        assert!(check_clock(&configuration, Duration::from_secs(30)).await.is_none());
+        assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+    }
+}
@@ -1,14 +1,24 @@
 use std::fmt;
 use std::env;
-use std::{collections::HashMap, fmt::Display, path::PathBuf};
+use std::time::Duration;
+use std::{collections::{BTreeMap, HashMap}, fmt::Display, path::PathBuf};
 
-use rancher_client::models::{IoCattleManagementv3Cluster, IoCattleManagementv3Project, IoCattleManagementv3ProjectRoleTemplateBinding, IoCattleManagementv3RoleTemplate};
+use rancher_client::models::{IoCattleManagementv3Cluster, IoCattleManagementv3ClusterRoleTemplateBinding, IoCattleManagementv3Project, IoCattleManagementv3ProjectRoleTemplateBinding, IoCattleManagementv3RoleTemplate};
 use serde::{Deserialize, Serialize};
-use anyhow::{Result, Context};
+use anyhow::{bail, Result, Context};
 use tracing::info;
 
-use crate::utils::git::GitAuth;
-use crate::{cluster::Cluster, utils::file::FileFormat, resources::project::Project, resources::prtb::ProjectRoleTemplateBinding, resources::rt::RoleTemplate};
+use crate::api::kubeconfig::KubeconfigAuth;
+use crate::error::ErrorBodies;
+use crate::events::EventSinkConfig;
+use crate::utils::doctor::RepoDoctorConfig;
+use crate::models::{Backoff, ChangeLimits, ConversionError, DeletionOptions, ObjectType, FileLimits, ManagementMode, PhaseTimeouts, SyncMode, PropagationPolicy, Readiness, ReadyPolicy, UpdateStrategy, DEFAULT_PROJECT_READY_CONDITIONS};
+use crate::notify::NotificationConfig;
+use crate::plan::ApprovalPolicy;
+use crate::utils::config_validator::Severity;
+use crate::utils::principal::PrincipalNormalization;
+use crate::utils::git::{default_git_author_email, CommitGranularity, GitAuth, TokenCredentials};
+use crate::{cluster::{Cluster, ClusterFolderNaming, ClusterSelector, UNREDACTABLE_FIELDS}, utils::file::FileFormat, resources::crtb::ClusterRoleTemplateBinding, resources::project::{Project, ProjectMatch}, resources::prtb::{PrtbListing, ProjectRoleTemplateBinding}, resources::rt::RoleTemplate};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ClusterConfig {
@@ -16,6 +26,12 @@ pub struct ClusterConfig {
     pub role_templates: Vec<RoleTemplate>,
     /// Map from project ID → (project, its role‐template‐bindings)
     pub projects: HashMap<String, (Project, Vec<ProjectRoleTemplateBinding>)>,
+    /// The cluster role template bindings of the cluster
+    #[serde(default)]
+    pub crtbs: Vec<ClusterRoleTemplateBinding>,
+    /// The object files that could not be parsed and were left out
+    #[serde(skip)]
+    pub malformed: Vec<ConversionError>,
 }
 
 impl Display for ClusterConfig {
@@ -32,6 +48,10 @@ impl Display for ClusterConfig {
                 writeln!(f, "    - Binding: {}", binding.id)?;
             }
         }
+        writeln!(f, "Cluster Bindings:")?;
+        for binding in &self.crtbs {
+            writeln!(f, "  - {}", binding.id)?;
+        }
         Ok(())
     }
 }
@@ -43,6 +63,8 @@ pub struct RancherClusterConfig {
     pub role_templates: Vec<IoCattleManagementv3RoleTemplate>,
     /// Map from project ID → (project, its role‐template‐bindings)
     pub projects: HashMap<String, (IoCattleManagementv3Project, Vec<IoCattleManagementv3ProjectRoleTemplateBinding>)>,
+    #[serde(default)]
+    pub crtbs: Vec<IoCattleManagementv3ClusterRoleTemplateBinding>,
 }
 
 
@@ -93,11 +115,22 @@ impl TryFrom<ClusterConfig> for RancherClusterConfig {
             &'static str,
         >>()?;
 
+        // 4. Cluster role-template-bindings
+        let rancher_crtbs = value
+            .crtbs
+            .into_iter()
+            .map(|b| {
+                IoCattleManagementv3ClusterRoleTemplateBinding::try_from(b)
+                    .map_err(|_| "cluster binding conversion failed")
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
 
         Ok(RancherClusterConfig {
             cluster: rancher_cluster,
             role_templates: rancher_role_templates,
             projects: rancher_projects,
+            crtbs: rancher_crtbs,
         })
     }
 }
@@ -106,13 +139,41 @@ impl TryFrom<ClusterConfig> for RancherClusterConfig {
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ShepherdConfig {
     pub rancher_config_path: PathBuf,
+    /// The Rancher URL, unused when `auth` is set
+    #[serde(default)]
     pub endpoint_url: String,
     pub file_format: FileFormat,
+    /// The Rancher API token, unused when `auth` is set
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub token: String,
+    /// Take the Rancher URL, CA and token or exec plugin from a kubeconfig context instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<KubeconfigAuth>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub remote_git_url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cluster_names: Option<Vec<String>>,
+    /// Discovers the clusters every run when `cluster_names` is unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster_selector: Option<ClusterSelector>,
+    /// Download and sync Rancher's own `local` cluster even when it is not in `cluster_names`
+    #[serde(default)]
+    pub manage_local_cluster: bool,
+    /// Name cluster folders after the cluster ID or its display name
+    #[serde(default)]
+    pub cluster_folder_naming: ClusterFolderNaming,
+    /// Match project files to projects by ID only, or by display name when the ID is gone
+    #[serde(default)]
+    pub match_projects_by: ProjectMatch,
+    /// List the project role template bindings of a download per project or across all namespaces
+    #[serde(default)]
+    pub prtb_listing: PrtbListing,
+    /// Download each project and its bindings into one `<project-id>.bundle.yaml`, YAML only
+    #[serde(default)]
+    pub bundle_projects: bool,
+    /// Fields left out of downloaded cluster files, as dot-separated paths such as `description`
+    #[serde(default)]
+    pub cluster_redact_paths: Vec<String>,
     #[serde(default = "default_loop_interval")]
     pub loop_interval: u64,
     #[serde(default = "default_retry_delay")]
@@ -120,15 +181,400 @@ pub struct ShepherdConfig {
     pub auth_method: GitAuth,
     #[serde(default = "default_branch")]
     pub branch: String,
+    /// Commits of history to clone and fetch, the whole history when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clone_depth: Option<u32>,
+    /// Folders to check out besides the endpoint folder, a full checkout when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sparse_paths: Option<Vec<String>>,
+    /// Clone, pull, commit and push the config folder, or only read it as another tool keeps it
+    #[serde(default = "default_git_enabled")]
+    pub git_enabled: bool,
     #[serde(default = "default_insecure")]
-    pub insecure: bool
+    pub insecure: bool,
+    /// Overrides the client name sent as the field manager on create and patch calls
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    /// Author name of the commits Shepherd creates, the client ID when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_author_name: Option<String>,
+    /// Author email of the commits Shepherd creates, `shepherd@<hostname>` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_author_email: Option<String>,
+    #[serde(default)]
+    pub update_strategy: UpdateStrategy,
+    /// Apply the repository to Rancher, or only mirror Rancher into the repository
+    #[serde(default)]
+    pub mode: SyncMode,
+    /// Write the changes of a run to a plan a person approves before they are applied, off when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval: Option<ApprovalConfig>,
+    /// Compare every object in Rancher or only the objects with a file
+    #[serde(default)]
+    pub management_mode: ManagementMode,
+    /// Delete bindings without a file from Rancher, with `management_mode = "repo-is-source"`
+    #[serde(default)]
+    pub prune: bool,
+    /// Extra annotation and label key patterns to ignore, on top of the Rancher defaults
+    #[serde(default)]
+    pub ignored_metadata_patterns: Vec<String>,
+    /// Status conditions a new project must report `True` before bindings are created in it
+    #[serde(default = "default_project_ready_conditions")]
+    pub project_ready_conditions: Vec<String>,
+    /// Seconds to wait for the project conditions before settling for the project existing
+    #[serde(default = "default_project_ready_timeout")]
+    pub project_ready_timeout: u64,
+    /// How long new objects of each type are polled for before they count as not ready
+    #[serde(default)]
+    pub readiness: ReadinessConfig,
+    /// Seconds each phase of a run may take before it is cut short and the run goes on without it
+    #[serde(default)]
+    pub phase_timeouts: PhaseTimeoutsConfig,
+    /// The most objects a run creates, updates and deletes, the rest are left to the next runs
+    #[serde(default)]
+    pub max_changes_per_run: ChangeLimits,
+    /// Leave the bindings of a deleted project to Rancher instead of deleting them one by one
+    #[serde(default)]
+    pub skip_cascaded_deletes: bool,
+    /// Propagation policy and grace period of deletions, and whether to wait for the objects to be gone
+    #[serde(default)]
+    pub deletion_options: DeletionOptionsConfig,
+    /// How stale git locks and a repository that cannot be read are dealt with before each run
+    #[serde(default)]
+    pub repo_doctor: RepoDoctorConfig,
+    /// How the principal names of bindings are compared per auth provider prefix, such as
+    /// `activedirectory_group`, on top of the defaults of
+    /// [`crate::utils::principal::DEFAULT_PRINCIPAL_NORMALIZATION`]
+    #[serde(default)]
+    pub principal_normalization: BTreeMap<String, PrincipalNormalization>,
+    /// Types whose objects are deleted from Rancher when their files are removed or pruned, never clusters
+    #[serde(default = "default_deletable_types")]
+    pub deletable_types: Vec<ObjectType>,
+    /// Download and compare role templates marked `hidden`
+    #[serde(default = "default_download_role_templates")]
+    pub download_hidden_role_templates: bool,
+    /// Download and compare role templates marked `builtin`
+    #[serde(default = "default_download_role_templates")]
+    pub download_builtin_role_templates: bool,
+    /// Consecutive runs failing to reach the endpoint before API calls are paused, 0 disables
+    #[serde(default = "default_circuit_breaker_threshold")]
+    pub circuit_breaker_threshold: u32,
+    /// Seconds API calls are paused for once the circuit opens
+    #[serde(default = "default_circuit_breaker_cooldown")]
+    pub circuit_breaker_cooldown: u64,
+    /// Upper bound in seconds for the pause, which doubles every time the endpoint is still down
+    #[serde(default = "default_circuit_breaker_max_cooldown")]
+    pub circuit_breaker_max_cooldown: u64,
+    /// Keep pulling and pushing the repository while API calls are paused
+    #[serde(default = "default_circuit_breaker_sync_git")]
+    pub circuit_breaker_sync_git: bool,
+    /// Commit each run as a whole or every changed object on its own
+    #[serde(default)]
+    pub commit_granularity: CommitGranularity,
+    /// Whether projects of a cluster sharing a display name are a warning or skip the cluster
+    #[serde(default)]
+    pub duplicate_project_display_names: Severity,
+    /// Whether new bindings breaking `.shepherd/policy.toml` are a warning or are not created
+    #[serde(default)]
+    pub binding_policy_violations: Severity,
+    /// Whether projects lowering their resource quota below what their namespaces use are a warning or are not updated
+    #[serde(default)]
+    pub quota_below_usage: Severity,
+    /// Fail the run when any object file does not parse instead of skipping it with a warning
+    #[serde(default)]
+    pub strict_parsing: bool,
+    /// Move projects and bindings naming another cluster than their folder to the cluster of
+    /// the folder instead of failing them
+    #[serde(default)]
+    pub correct_misplaced_objects: bool,
+    /// Undo every change of a run failing more often than this, never rolls back when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollback_threshold: Option<usize>,
+    /// Folder for the backups of a run's changes, `~/.local/state/shepherd` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_dir: Option<PathBuf>,
+    /// Runs in a row an object may fail before it is skipped until its file changes, 0 never skips
+    #[serde(default = "default_quarantine_after")]
+    pub quarantine_after: u32,
+    /// Minutes a deleted object may stay terminating before it is logged as an error and notified about
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escalate_terminating_after: Option<u64>,
+    /// Runs between reads of the Rancher version, which is always read by the first run, 0 never reads it again
+    #[serde(default = "default_server_version_interval")]
+    pub server_version_interval: u32,
+    /// Seconds the clock may be off the endpoint before it is named as the cause of refused tokens, 0 never checks it
+    #[serde(default = "default_max_clock_skew")]
+    pub max_clock_skew: u64,
+    /// Webhook notified about failed runs, deletions, drift and the circuit breaker opening
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notifications: Option<NotificationConfig>,
+    /// Stream of the actions of every run, a JSON object per line written to a file, FIFO or TCP address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<EventSinkConfig>,
+    /// Annotate created and updated objects with the commit and path of the file they were applied from
+    #[serde(default = "default_stamp_git_commit")]
+    pub stamp_git_commit: bool,
+    /// Record the configuration applied to created and updated objects in the
+    /// `shepherd.cattle.io/last-applied` annotation, so fields added in Rancher are kept
+    #[serde(default)]
+    pub record_last_applied: bool,
+    /// Largest object file in bytes read from the repository, larger files are skipped
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+    /// Most files a project folder may hold, the folder is skipped beyond that
+    #[serde(default = "default_max_files_per_project")]
+    pub max_files_per_project: usize,
+    /// Bytes of a response body kept in errors and logs, longer bodies are cut
+    #[serde(default = "default_max_error_body")]
+    pub max_error_body: usize,
+    /// Log the running tasks when a run takes longer than this many loop intervals, 0 never does
+    #[serde(default = "default_stuck_run_intervals")]
+    pub stuck_run_intervals: u32,
+    /// Seconds between two task dumps, whether triggered by a stuck run or `SIGUSR1`
+    #[serde(default = "default_task_dump_min_interval")]
+    pub task_dump_min_interval: u64,
+    /// Seconds a repeated warning, such as a missing project file, is logged once and then counted, 0 logs every one
+    #[serde(default = "default_repeated_warning_window")]
+    pub repeated_warning_window: u64,
+}
+
+/// How long new objects of one type are polled for after they are created.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadyPolicyConfig {
+    /// Seconds to poll for before the object counts as not ready, 0 turns polling off for bindings
+    pub max_wait: u64,
+    /// Milliseconds between two polls
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+    /// Double the milliseconds between two polls after each one, up to this many
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backoff_max_interval: Option<u64>,
+    /// Stop polling at the first `401` or `403` instead of waiting out `max_wait`
+    #[serde(default)]
+    pub treat_forbidden_as_fatal: bool,
+}
+
+impl From<ReadyPolicyConfig> for ReadyPolicy {
+    fn from(config: ReadyPolicyConfig) -> Self {
+        ReadyPolicy {
+            max_wait: Duration::from_secs(config.max_wait),
+            interval: Duration::from_millis(config.poll_interval),
+            backoff: match config.backoff_max_interval {
+                Some(max_interval) => Backoff::Exponential { max_interval: Duration::from_millis(max_interval) },
+                None => Backoff::Fixed,
+            },
+            treat_forbidden_as_fatal: config.treat_forbidden_as_fatal,
+        }
+    }
+}
+
+/// The `[approval]` table.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApprovalConfig {
+    /// Minutes a plan waits for its approval before it expires and is planned again
+    #[serde(default = "default_plan_ttl")]
+    pub plan_ttl: u64,
+    /// Also commit each plan with its approval marker to a `shepherd/plans/<run-id>` branch, approved by merging it
+    #[serde(default)]
+    pub plan_branch: bool,
+}
+
+impl From<ApprovalConfig> for ApprovalPolicy {
+    fn from(config: ApprovalConfig) -> Self {
+        ApprovalPolicy {
+            ttl: Duration::from_secs(config.plan_ttl * 60),
+            plan_branch: config.plan_branch,
+        }
+    }
+}
+
+/// The `[readiness]` table, object types left out keep their default wait.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadinessConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role_template: Option<ReadyPolicyConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<ReadyPolicyConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prtb: Option<ReadyPolicyConfig>,
+}
+
+impl ReadinessConfig {
+    pub fn readiness(&self) -> Readiness {
+        let defaults = Readiness::default();
+        Readiness {
+            role_template: self.role_template.map_or(defaults.role_template, ReadyPolicy::from),
+            project: self.project.map_or(defaults.project, ReadyPolicy::from),
+            project_role_template_binding: self
+                .prtb
+                .map_or(defaults.project_role_template_binding, ReadyPolicy::from),
+        }
+    }
+}
+
+impl Display for ReadinessConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let readiness = self.readiness();
+        write!(
+            f,
+            "role templates {}; projects {}; bindings {}",
+            readiness.role_template, readiness.project, readiness.project_role_template_binding
+        )
+    }
+}
+
+/// The `[phase_timeouts]` table in seconds, phases left out never time out.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseTimeoutsConfig {
+    /// Downloading a cluster, at bootstrap or once it matches the cluster selector
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download: Option<u64>,
+    /// Waiting on the remote to answer while fetching or pushing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<u64>,
+    /// Comparing and updating the objects of a cluster
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compare: Option<u64>,
+    /// Creating the objects of new files in a cluster
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub create: Option<u64>,
+    /// Deleting the objects of deleted files in a cluster
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delete: Option<u64>,
+}
+
+impl PhaseTimeoutsConfig {
+    pub fn phase_timeouts(&self) -> PhaseTimeouts {
+        PhaseTimeouts {
+            download: self.download.map(Duration::from_secs),
+            git: self.git.map(Duration::from_secs),
+            compare: self.compare.map(Duration::from_secs),
+            create: self.create.map(Duration::from_secs),
+            delete: self.delete.map(Duration::from_secs),
+        }
+    }
+}
 
+impl Display for PhaseTimeoutsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phases = [
+            ("download", self.download),
+            ("git", self.git),
+            ("compare", self.compare),
+            ("create", self.create),
+            ("delete", self.delete),
+        ];
+        let limited: Vec<String> = phases
+            .iter()
+            .filter_map(|(phase, seconds)| seconds.map(|seconds| format!("{} {} seconds", phase, seconds)))
+            .collect();
+        match limited.is_empty() {
+            true => write!(f, "none"),
+            false => write!(f, "{}", limited.join(", ")),
+        }
+    }
+}
+
+/// The propagation policy of deletions for each object type, left to the server when unset.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PropagationPolicyConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role_template: Option<PropagationPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<PropagationPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prtb: Option<PropagationPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crtb: Option<PropagationPolicy>,
+}
+
+/// The `[deletion_options]` table.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeletionOptionsConfig {
+    #[serde(default)]
+    pub propagation_policy: PropagationPolicyConfig,
+    /// Seconds Kubernetes gives the objects to terminate, the server default when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grace_period_seconds: Option<i32>,
+    /// Poll deleted objects until they are gone before the deletion counts as done
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_for_deletion: Option<ReadyPolicyConfig>,
+}
+
+impl DeletionOptionsConfig {
+    pub fn deletion_options(&self) -> DeletionOptions {
+        DeletionOptions {
+            role_template: self.propagation_policy.role_template,
+            project: self.propagation_policy.project,
+            project_role_template_binding: self.propagation_policy.prtb,
+            cluster_role_template_binding: self.propagation_policy.crtb,
+            grace_period_seconds: self.grace_period_seconds,
+            wait_for_deletion: self.wait_for_deletion.map(ReadyPolicy::from),
+        }
+    }
+}
+
+impl Display for DeletionOptionsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let policy = |policy: Option<PropagationPolicy>| policy.map_or_else(|| "server default".to_string(), |p| p.to_string());
+        write!(
+            f,
+            "propagation role templates {}, projects {}, prtbs {}, crtbs {}",
+            policy(self.propagation_policy.role_template),
+            policy(self.propagation_policy.project),
+            policy(self.propagation_policy.prtb),
+            policy(self.propagation_policy.crtb)
+        )?;
+        match self.grace_period_seconds {
+            Some(seconds) => write!(f, ", grace period {} seconds", seconds)?,
+            None => write!(f, ", server default grace period")?,
+        }
+        match self.wait_for_deletion {
+            Some(wait) => write!(f, ", waiting up to {} seconds for objects to be gone", wait.max_wait),
+            None => write!(f, ", not waiting for objects to be gone"),
+        }
+    }
 }
 
 impl ShepherdConfig {
     pub fn from_file(path: &str) -> Result<Self> {
         let file = std::fs::read_to_string(path).context("Failed to read config file")?;
         let mut config: ShepherdConfig = toml::from_str(&file).context("Failed to parse config file")?;
+        if let Some(selector) = &config.cluster_selector {
+            selector.name_regex()?;
+        }
+        if config.bundle_projects && config.file_format != FileFormat::Yaml {
+            bail!("bundle_projects needs file_format = \"yaml\", bundles are multi-document YAML files");
+        }
+        if config.approval.is_some() && config.mode == SyncMode::Mirror {
+            bail!("approval needs mode = \"gitops\", a mirror run never changes Rancher");
+        }
+        if !config.git_enabled && config.approval.is_some() {
+            bail!("approval needs git_enabled = true, plans are approved with commits");
+        }
+        if !config.git_enabled && config.mode == SyncMode::Mirror {
+            bail!("mode = \"mirror\" needs git_enabled = true, it commits Rancher into the repository");
+        }
+        if let Some(path) = config
+            .cluster_redact_paths
+            .iter()
+            .find(|path| UNREDACTABLE_FIELDS.contains(&path.split('.').next().unwrap_or_default()))
+        {
+            bail!("cluster_redact_paths cannot leave `{}` out of cluster files", path);
+        }
+        if let Some(prefix) = config.principal_normalization.keys().find(|prefix| prefix.is_empty() || prefix.contains("://")) {
+            bail!("principal_normalization names auth provider prefixes such as `activedirectory_group`, without `://`, not `{}`", prefix);
+        }
+        let limits = config.max_changes_per_run;
+        if [limits.creates, limits.updates, limits.deletes].contains(&Some(0)) {
+            bail!("max_changes_per_run cannot be 0, leave a kind out to not limit it");
+        }
+        if let Some(object_type) = config.deletable_types.iter().find(|object_type| !object_type.deletable()) {
+            bail!("deletable_types cannot include {:?}, those objects are never deleted", object_type);
+        }
+        if config.auth.is_none() && (config.endpoint_url.is_empty() || config.token.is_empty()) {
+            bail!("Set endpoint_url and token, or auth to read them from a kubeconfig context");
+        }
 
         // Handle Git authentication method
         config.auth_method = match (env::var("GIT_AUTH_METHOD"), env::var("GIT_SSH_KEY"), env::var("GIT_TOKEN")) {
@@ -136,8 +582,8 @@ impl ShepherdConfig {
                 info!("Using SSH key: {}", key);
                 GitAuth::SshKey(PathBuf::from(key))},
             (Ok(method), _, Ok(token)) if method == "https_token" => {
-                info!("Using HTTPS token: {}", token);
-                GitAuth::HttpsToken(token)
+                info!("Using HTTPS token");
+                GitAuth::HttpsToken(TokenCredentials::new(token, env::var("GIT_USERNAME").ok()))
             },
             (Ok(method), _, _) if method == "ssh_agent" => {
                 info!("Using SSH agent");
@@ -157,7 +603,9 @@ impl ShepherdConfig {
     pub fn get_git_auth(&self) -> GitAuth {
         match (env::var("GIT_AUTH_METHOD"), env::var("GIT_SSH_KEY"), env::var("GIT_TOKEN")) {
             (Ok(method), Ok(key), _) if method == "ssh_key" => GitAuth::SshKey(PathBuf::from(key)),
-            (Ok(method), _, Ok(token)) if method == "https_token" => GitAuth::HttpsToken(token),
+            (Ok(method), _, Ok(token)) if method == "https_token" => {
+                GitAuth::HttpsToken(TokenCredentials::new(token, env::var("GIT_USERNAME").ok()))
+            }
             (Ok(method), _, _) if method == "ssh_agent" => GitAuth::SshAgent,
             (Ok(method), _, _) if method == "git_credential_helper" => GitAuth::GitCredentialHelper,
             _ => self.auth_method.clone(), // Use the value from the config file if environment variables are not set
@@ -178,15 +626,102 @@ fn default_branch() -> String {
     "main".to_string()
 }
 
+fn default_git_enabled() -> bool {
+    true
+}
+
 fn default_insecure() -> bool {
     false
 }
 
+fn default_project_ready_conditions() -> Vec<String> {
+    DEFAULT_PROJECT_READY_CONDITIONS.iter().map(|c| c.to_string()).collect()
+}
+
+fn default_deletable_types() -> Vec<ObjectType> {
+    ObjectType::DEFAULT_DELETABLE.to_vec()
+}
+
+fn default_project_ready_timeout() -> u64 {
+    60
+}
+
+fn default_poll_interval() -> u64 {
+    1000
+}
+
+fn default_download_role_templates() -> bool {
+    true
+}
+
+fn default_circuit_breaker_threshold() -> u32 {
+    3
+}
+
+fn default_circuit_breaker_cooldown() -> u64 {
+    60
+}
+
+fn default_circuit_breaker_max_cooldown() -> u64 {
+    900
+}
+
+fn default_circuit_breaker_sync_git() -> bool {
+    true
+}
+
+fn default_quarantine_after() -> u32 {
+    5
+}
+
+fn default_server_version_interval() -> u32 {
+    10
+}
+
+fn default_max_clock_skew() -> u64 {
+    30
+}
+
+fn default_stamp_git_commit() -> bool {
+    true
+}
+
+fn default_max_file_size() -> u64 {
+    FileLimits::default().max_file_size
+}
+
+fn default_max_files_per_project() -> usize {
+    FileLimits::default().max_files_per_project
+}
+
+fn default_max_error_body() -> usize {
+    ErrorBodies::default().max_len
+}
+
+fn default_stuck_run_intervals() -> u32 {
+    3
+}
+
+fn default_task_dump_min_interval() -> u64 {
+    300
+}
+
+fn default_repeated_warning_window() -> u64 {
+    3600
+}
+
+fn default_plan_ttl() -> u64 {
+    1440
+}
+
 
 impl Display for ShepherdConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Rancher config path: {}", self.rancher_config_path.display())?;
         writeln!(f, "Endpoint URL: {}", self.endpoint_url)?;
+        if let Some(auth) = &self.auth {
+            writeln!(f, "Rancher credentials: {}", auth)?;
+        }
         writeln!(f, "File format: {}", self.file_format)?;
         writeln!(
             f,
@@ -203,11 +738,119 @@ impl Display for ShepherdConfig {
                 .map(|v| v.join(", "))
                 .unwrap_or_else(|| "<none>".into())
         )?;
+        if let Some(selector) = &self.cluster_selector {
+            writeln!(
+                f,
+                "Cluster selector: labels {}, name pattern {}, missing clusters {:?}",
+                selector.labels.as_deref().unwrap_or("<none>"),
+                selector.name_pattern.as_deref().unwrap_or("<none>"),
+                selector.missing
+            )?;
+        }
+        writeln!(f, "Manage local cluster: {}", self.manage_local_cluster)?;
+        writeln!(f, "Cluster folder naming: {}", self.cluster_folder_naming)?;
+        writeln!(f, "Match projects by: {}", self.match_projects_by)?;
+        writeln!(f, "PRTB listing: {}", self.prtb_listing)?;
+        writeln!(f, "Bundle projects: {}", self.bundle_projects)?;
+        writeln!(f, "Cluster redact paths: {:?}", self.cluster_redact_paths)?;
         writeln!(f, "Loop interval: {} seconds", self.loop_interval)?;
         writeln!(f, "Retry delay: {} milliseconds", self.retry_delay)?;
         writeln!(f, "Auth method: {:#?}", self.auth_method)?;
         writeln!(f, "Branch: {}", self.branch)?;
+        match self.clone_depth {
+            Some(depth) => writeln!(f, "Clone depth: {} commits", depth)?,
+            None => writeln!(f, "Clone depth: unlimited")?,
+        }
+        match &self.sparse_paths {
+            Some(paths) => writeln!(f, "Sparse paths: {:?}", paths)?,
+            None => writeln!(f, "Sparse paths: full checkout")?,
+        }
+        writeln!(f, "Git enabled: {}", self.git_enabled)?;
         writeln!(f, "Insecure: {}", self.insecure)?;
+        writeln!(
+            f,
+            "Client name: {}",
+            self.client_name.as_deref().unwrap_or(crate::CLIENT_NAME)
+        )?;
+        writeln!(
+            f,
+            "Git author: {} <{}>",
+            self.git_author_name.as_deref().unwrap_or(crate::FULL_CLIENT_ID),
+            self.git_author_email.clone().unwrap_or_else(default_git_author_email)
+        )?;
+        writeln!(f, "Update strategy: {}", self.update_strategy)?;
+        writeln!(f, "Mode: {}", self.mode)?;
+        match self.approval {
+            Some(approval) if approval.plan_branch => {
+                writeln!(f, "Approval: plans expire after {} minutes, committed to a branch each", approval.plan_ttl)?
+            }
+            Some(approval) => writeln!(f, "Approval: plans expire after {} minutes", approval.plan_ttl)?,
+            None => writeln!(f, "Approval: off")?,
+        }
+        writeln!(f, "Management mode: {}", self.management_mode)?;
+        writeln!(f, "Prune: {}", self.prune)?;
+        writeln!(f, "Ignored metadata patterns: {:?}", self.ignored_metadata_patterns)?;
+        writeln!(f, "Project ready conditions: {:?}", self.project_ready_conditions)?;
+        writeln!(f, "Project ready timeout: {} seconds", self.project_ready_timeout)?;
+        writeln!(f, "Readiness: {}", self.readiness)?;
+        writeln!(f, "Phase timeouts: {}", self.phase_timeouts)?;
+        writeln!(f, "Max changes per run: {}", self.max_changes_per_run)?;
+        writeln!(f, "Skip cascaded deletes: {}", self.skip_cascaded_deletes)?;
+        writeln!(f, "Deletion options: {}", self.deletion_options)?;
+        writeln!(f, "Deletable types: {:?}", self.deletable_types)?;
+        writeln!(
+            f,
+            "Repo doctor: stale locks after {}s, reclone {}",
+            self.repo_doctor.stale_lock_after, self.repo_doctor.reclone
+        )?;
+        writeln!(f, "Principal normalization: {:?}", self.principal_normalization)?;
+        writeln!(f, "Download hidden role templates: {}", self.download_hidden_role_templates)?;
+        writeln!(f, "Download builtin role templates: {}", self.download_builtin_role_templates)?;
+        writeln!(f, "Circuit breaker threshold: {}", self.circuit_breaker_threshold)?;
+        writeln!(f, "Circuit breaker cooldown: {} seconds", self.circuit_breaker_cooldown)?;
+        writeln!(f, "Circuit breaker max cooldown: {} seconds", self.circuit_breaker_max_cooldown)?;
+        writeln!(f, "Circuit breaker sync git: {}", self.circuit_breaker_sync_git)?;
+        writeln!(f, "Commit granularity: {}", self.commit_granularity)?;
+        writeln!(f, "Duplicate project display names: {}", self.duplicate_project_display_names)?;
+        writeln!(f, "Binding policy violations: {}", self.binding_policy_violations)?;
+        writeln!(f, "Quota below usage: {}", self.quota_below_usage)?;
+        writeln!(f, "Strict parsing: {}", self.strict_parsing)?;
+        writeln!(f, "Correct misplaced objects: {}", self.correct_misplaced_objects)?;
+        match self.rollback_threshold {
+            Some(threshold) => writeln!(f, "Rollback threshold: {} failures", threshold)?,
+            None => writeln!(f, "Rollback threshold: never")?,
+        }
+        match &self.state_dir {
+            Some(state_dir) => writeln!(f, "State dir: {}", state_dir.display())?,
+            None => writeln!(f, "State dir: ~/.local/state/shepherd")?,
+        }
+        writeln!(f, "Quarantine after: {} failed runs", self.quarantine_after)?;
+        match self.escalate_terminating_after {
+            Some(minutes) => writeln!(f, "Escalate terminating after: {} minutes", minutes)?,
+            None => writeln!(f, "Escalate terminating after: never")?,
+        }
+        writeln!(f, "Server version interval: {} runs", self.server_version_interval)?;
+        match self.max_clock_skew {
+            0 => writeln!(f, "Max clock skew: not checked")?,
+            seconds => writeln!(f, "Max clock skew: {} seconds", seconds)?,
+        }
+        match &self.notifications {
+            // the URL of a webhook is usually its secret
+            Some(notifications) => writeln!(f, "Notifications: {:?} via {:?} preset", notifications.events, notifications.preset)?,
+            None => writeln!(f, "Notifications: off")?,
+        }
+        match &self.events {
+            Some(events) => writeln!(f, "Events: {} (capacity {})", events.sink, events.capacity)?,
+            None => writeln!(f, "Events: off")?,
+        }
+        writeln!(f, "Stamp git commit: {}", self.stamp_git_commit)?;
+        writeln!(f, "Record last applied: {}", self.record_last_applied)?;
+        writeln!(f, "Max file size: {} bytes", self.max_file_size)?;
+        writeln!(f, "Max files per project: {}", self.max_files_per_project)?;
+        writeln!(f, "Max error body: {} bytes", self.max_error_body)?;
+        writeln!(f, "Stuck run intervals: {}", self.stuck_run_intervals)?;
+        writeln!(f, "Task dump min interval: {} seconds", self.task_dump_min_interval)?;
+        writeln!(f, "Repeated warning window: {} seconds", self.repeated_warning_window)?;
         Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,648 @@
+//! Rancher credentials taken from a kubeconfig context instead of `endpoint_url` and `token`.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use http::Extensions;
+use reqwest::header::{HeaderValue, AUTHORIZATION};
+use reqwest::{Request, Response, StatusCode, Url};
+use reqwest_middleware::{Middleware, Next};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+/// How long an exec plugin may take to print a credential before it is killed.
+pub const EXEC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The `ExecCredential` version asked of plugins that do not name one.
+const DEFAULT_EXEC_API_VERSION: &str = "client.authentication.k8s.io/v1";
+
+/// A context of a kubeconfig to take the Rancher URL, CA and credentials from,
+/// `auth = { kubeconfig = "~/.kube/config", context = "rancher-mgmt" }`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KubeconfigAuth {
+    /// The kubeconfig file, a leading `~/` is the home folder
+    pub kubeconfig: PathBuf,
+    /// The context to use, the `current-context` of the kubeconfig when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+impl std::fmt::Display for KubeconfigAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.context {
+            Some(context) => write!(f, "kubeconfig {} context {}", self.kubeconfig.display(), context),
+            None => write!(f, "kubeconfig {} current context", self.kubeconfig.display()),
+        }
+    }
+}
+
+/// What a kubeconfig context says about reaching the cluster.
+#[derive(Clone, PartialEq)]
+pub struct KubeconfigCredentials {
+    pub server: String,
+    /// The PEM certificates of the CA the server's certificate is checked against
+    pub ca_pem: Option<Vec<u8>>,
+    pub insecure_skip_tls_verify: bool,
+    pub credential: Credential,
+}
+
+/// Keeps the token out of logs.
+impl std::fmt::Debug for KubeconfigCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KubeconfigCredentials")
+            .field("server", &self.server)
+            .field("ca_pem", &self.ca_pem.as_ref().map(|_| "<pem>"))
+            .field("insecure_skip_tls_verify", &self.insecure_skip_tls_verify)
+            .field("credential", &self.credential)
+            .finish()
+    }
+}
+
+/// How the user of a context authenticates.
+#[derive(Clone, PartialEq)]
+pub enum Credential {
+    /// A bearer token, from `token` or `tokenFile`
+    Token(String),
+    /// A command printing an `ExecCredential`
+    Exec(ExecPlugin),
+}
+
+/// Keeps the token out of logs.
+impl std::fmt::Debug for Credential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Credential::Token(_) => write!(f, "Token(<redacted>)"),
+            Credential::Exec(plugin) => f.debug_tuple("Exec").field(plugin).finish(),
+        }
+    }
+}
+
+/// The `exec` section of a kubeconfig user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecPlugin {
+    pub command: PathBuf,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    pub api_version: String,
+}
+
+/// A token printed by an exec plugin.
+#[derive(Clone, PartialEq)]
+pub struct ExecToken {
+    pub token: String,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Keeps the token out of logs.
+impl std::fmt::Debug for ExecToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExecToken")
+            .field("token", &"<redacted>")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+impl ExecToken {
+    /// Whether the token expired at `now`, tokens without an expiry are kept until refused.
+    pub fn expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+impl ExecPlugin {
+    /// Run the plugin for a fresh token, killing it after `timeout`.
+    ///
+    /// # Errors
+    /// If the plugin cannot be started, times out, fails or prints no token
+    pub async fn token(&self, timeout: Duration) -> Result<ExecToken> {
+        let exec_info = serde_json::json!({
+            "apiVersion": self.api_version,
+            "kind": "ExecCredential",
+            "spec": { "interactive": false },
+        });
+        let output = tokio::process::Command::new(&self.command)
+            .args(&self.args)
+            .envs(self.env.iter().map(|(name, value)| (name, value)))
+            .env("KUBERNETES_EXEC_INFO", exec_info.to_string())
+            .stdin(std::process::Stdio::null())
+            .kill_on_drop(true)
+            .output();
+        let output = tokio::time::timeout(timeout, output)
+            .await
+            .map_err(|_| anyhow!("Exec plugin {} printed no credential within {:?}", self.command.display(), timeout))?
+            .with_context(|| format!("Failed to run exec plugin {}", self.command.display()))?;
+        if !output.status.success() {
+            bail!(
+                "Exec plugin {} failed with {}: {}",
+                self.command.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let credential: ExecCredentialOutput = serde_json::from_slice(&output.stdout)
+            .with_context(|| format!("Exec plugin {} printed no ExecCredential", self.command.display()))?;
+        let status = credential.status.unwrap_or_default();
+        let token = status
+            .token
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| anyhow!("Exec plugin {} printed no token", self.command.display()))?;
+        debug!("Exec plugin {} printed a token expiring at {:?}", self.command.display(), status.expiration_timestamp);
+        Ok(ExecToken {
+            token,
+            expires_at: status.expiration_timestamp,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct ExecCredentialOutput {
+    #[serde(default)]
+    status: Option<ExecCredentialStatus>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct ExecCredentialStatus {
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    expiration_timestamp: Option<DateTime<Utc>>,
+}
+
+/// The parts of a kubeconfig file Shepherd reads.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Kubeconfig {
+    #[serde(default)]
+    clusters: Vec<NamedCluster>,
+    #[serde(default)]
+    contexts: Vec<NamedContext>,
+    #[serde(default)]
+    users: Vec<NamedUser>,
+    #[serde(default)]
+    current_context: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NamedCluster {
+    name: String,
+    cluster: ClusterEntry,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ClusterEntry {
+    server: String,
+    #[serde(default)]
+    certificate_authority_data: Option<String>,
+    #[serde(default)]
+    certificate_authority: Option<PathBuf>,
+    #[serde(default)]
+    insecure_skip_tls_verify: bool,
+}
+
+#[derive(Deserialize)]
+struct NamedContext {
+    name: String,
+    context: ContextEntry,
+}
+
+#[derive(Deserialize)]
+struct ContextEntry {
+    cluster: String,
+    user: String,
+}
+
+#[derive(Deserialize)]
+struct NamedUser {
+    name: String,
+    #[serde(default)]
+    user: UserEntry,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct UserEntry {
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    token_file: Option<PathBuf>,
+    #[serde(default)]
+    exec: Option<ExecEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExecEntry {
+    command: PathBuf,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: Option<Vec<ExecEnv>>,
+    #[serde(default)]
+    api_version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ExecEnv {
+    name: String,
+    value: String,
+}
+
+/// `path` with a leading `~/` replaced by the home folder.
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Read the server, CA and credential of the context of `auth`.
+///
+/// Relative paths in the kubeconfig, such as a `certificate-authority` file, are relative
+/// to the kubeconfig's folder as they are for kubectl.
+///
+/// # Errors
+/// If the kubeconfig cannot be read, the context, its cluster or its user is missing, or
+/// the user has neither a token nor an exec plugin
+pub fn load_kubeconfig(auth: &KubeconfigAuth) -> Result<KubeconfigCredentials> {
+    let path = expand_home(&auth.kubeconfig);
+    let contents = std::fs::read_to_string(&path).with_context(|| format!("Failed to read kubeconfig {}", path.display()))?;
+    let kubeconfig: Kubeconfig =
+        serde_yaml::from_str(&contents).with_context(|| format!("Failed to parse kubeconfig {}", path.display()))?;
+    let base = path.parent().unwrap_or(Path::new("."));
+
+    let context_name = auth
+        .context
+        .as_deref()
+        .or(kubeconfig.current_context.as_deref())
+        .ok_or_else(|| anyhow!("Set a context, {} has no current-context", path.display()))?;
+    let context = kubeconfig
+        .contexts
+        .iter()
+        .find(|context| context.name == context_name)
+        .map(|context| &context.context)
+        .ok_or_else(|| anyhow!("No context `{}` in {}", context_name, path.display()))?;
+    let cluster = kubeconfig
+        .clusters
+        .iter()
+        .find(|cluster| cluster.name == context.cluster)
+        .map(|cluster| &cluster.cluster)
+        .ok_or_else(|| anyhow!("No cluster `{}` in {}", context.cluster, path.display()))?;
+    let user = kubeconfig
+        .users
+        .iter()
+        .find(|user| user.name == context.user)
+        .map(|user| &user.user)
+        .ok_or_else(|| anyhow!("No user `{}` in {}", context.user, path.display()))?;
+
+    let ca_pem = match (&cluster.certificate_authority_data, &cluster.certificate_authority) {
+        (Some(data), _) => Some(
+            base64::engine::general_purpose::STANDARD
+                .decode(data.trim())
+                .with_context(|| format!("certificate-authority-data of cluster `{}` is not base64", context.cluster))?,
+        ),
+        (None, Some(file)) => {
+            let file = base.join(expand_home(file));
+            Some(std::fs::read(&file).with_context(|| format!("Failed to read CA {}", file.display()))?)
+        }
+        (None, None) => None,
+    };
+
+    let credential = match (&user.token, &user.token_file, &user.exec) {
+        (Some(token), _, _) => Credential::Token(token.clone()),
+        (None, Some(file), _) => {
+            let file = base.join(expand_home(file));
+            let token = std::fs::read_to_string(&file).with_context(|| format!("Failed to read token {}", file.display()))?;
+            Credential::Token(token.trim().to_string())
+        }
+        (None, None, Some(exec)) => {
+            // like kubectl, a command naming a path is relative to the kubeconfig
+            let command = if exec.command.components().count() > 1 {
+                base.join(expand_home(&exec.command))
+            } else {
+                exec.command.clone()
+            };
+            Credential::Exec(ExecPlugin {
+                command,
+                args: exec.args.clone(),
+                env: exec
+                    .env
+                    .iter()
+                    .flatten()
+                    .map(|env| (env.name.clone(), env.value.clone()))
+                    .collect(),
+                api_version: exec.api_version.clone().unwrap_or_else(|| DEFAULT_EXEC_API_VERSION.to_string()),
+            })
+        }
+        (None, None, None) => bail!("User `{}` in {} has neither a token nor an exec plugin", context.user, path.display()),
+    };
+
+    Ok(KubeconfigCredentials {
+        server: cluster.server.trim_end_matches('/').to_string(),
+        ca_pem,
+        insecure_skip_tls_verify: cluster.insecure_skip_tls_verify,
+        credential,
+    })
+}
+
+/// Sends the token of an exec plugin with every request to the Rancher `server`, running the
+/// plugin again when the token expires or Rancher answers `401 Unauthorized`.
+///
+/// Requests to any other scheme, host or port are sent as they are.
+pub struct ExecAuth {
+    plugin: ExecPlugin,
+    server: Url,
+    timeout: Duration,
+    token: Mutex<Option<ExecToken>>,
+}
+
+impl ExecAuth {
+    pub fn new(plugin: ExecPlugin, server: Url, timeout: Duration) -> Self {
+        Self {
+            plugin,
+            server,
+            timeout,
+            token: Mutex::new(None),
+        }
+    }
+
+    /// The cached token unless it expired or is `rejected`, a fresh one otherwise.
+    ///
+    /// Requests refused together refresh the token once, the others find it replaced.
+    async fn token(&self, rejected: Option<&str>) -> Result<String> {
+        let mut cached = self.token.lock().await;
+        if let Some(token) = cached.as_ref() {
+            if !token.expired(Utc::now()) && rejected != Some(token.token.as_str()) {
+                return Ok(token.token.clone());
+            }
+        }
+        let fresh = self.plugin.token(self.timeout).await?;
+        let token = fresh.token.clone();
+        *cached = Some(fresh);
+        Ok(token)
+    }
+}
+
+fn authorize(request: &mut Request, token: &str) -> reqwest_middleware::Result<()> {
+    let mut value = HeaderValue::from_str(&format!("Bearer {}", token))
+        .map_err(|e| anyhow!("Exec plugin token is not a valid header value: {}", e))?;
+    value.set_sensitive(true);
+    request.headers_mut().insert(AUTHORIZATION, value);
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl Middleware for ExecAuth {
+    async fn handle(
+        &self,
+        mut request: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        if request.url().origin() != self.server.origin() {
+            return next.run(request, extensions).await;
+        }
+        let retry = request.try_clone();
+        let token = self.token(None).await?;
+        authorize(&mut request, &token)?;
+        let response = next.clone().run(request, extensions).await?;
+        let (StatusCode::UNAUTHORIZED, Some(mut retry)) = (response.status(), retry) else {
+            return Ok(response);
+        };
+
+        info!("Rancher refused the exec plugin token, running {} again", self.plugin.command.display());
+        let token = self.token(Some(&token)).await?;
+        authorize(&mut retry, &token)?;
+        next.run(retry, extensions).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, Request as MockRequest, ResponseTemplate};
+
+    fn write_kubeconfig(dir: &Path, user: &str) -> PathBuf {
+        let path = dir.join("config");
+        std::fs::write(dir.join("ca.pem"), "-----BEGIN CERTIFICATE-----\n").unwrap();
+        std::fs::write(
+            &path,
+            format!(
+                "apiVersion: v1
+kind: Config
+current-context: downstream
+clusters:
+- name: rancher
+  cluster:
+    server: https://rancher.example.com/k8s/clusters/local/
+    certificate-authority: ca.pem
+- name: downstream
+  cluster:
+    server: https://rancher.example.com/k8s/clusters/c-abc
+    certificate-authority-data: {}
+contexts:
+- name: rancher-mgmt
+  context:
+    cluster: rancher
+    user: rancher
+- name: downstream
+  context:
+    cluster: downstream
+    user: other
+users:
+- name: rancher
+  user:
+{}
+- name: other
+  user:
+    token: other-token
+",
+                base64::engine::general_purpose::STANDARD.encode("-----BEGIN CERTIFICATE-----\n"),
+                user
+            ),
+        )
+        .unwrap();
+        path
+    }
+
+    /// An exec plugin printing `token`, counting its runs in `runs`.
+    fn exec_script(dir: &Path, token: &str) -> PathBuf {
+        let script = dir.join("credential.sh");
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho run >> \"$(dirname \"$0\")/runs\"\necho '{{\"kind\":\"ExecCredential\",\"status\":{{\"token\":\"{}\"}}}}'\n",
+                token
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+        script
+    }
+
+    #[test]
+    fn test_token_context_reads_server_ca_and_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let kubeconfig = write_kubeconfig(dir.path(), "    token: kubeconfig-u-abc:secret");
+
+        let credentials = load_kubeconfig(&KubeconfigAuth {
+            kubeconfig: kubeconfig.clone(),
+            context: Some("rancher-mgmt".to_string()),
+        })
+        .unwrap();
+
+        assert_eq!(credentials.server, "https://rancher.example.com/k8s/clusters/local");
+        assert_eq!(credentials.ca_pem.as_deref(), Some(b"-----BEGIN CERTIFICATE-----\n".as_slice()));
+        assert_eq!(credentials.credential, Credential::Token("kubeconfig-u-abc:secret".to_string()));
+        assert!(!format!("{:?}", credentials).contains("secret"));
+
+        // without a context the current one is used
+        let current = load_kubeconfig(&KubeconfigAuth { kubeconfig, context: None }).unwrap();
+        assert_eq!(current.server, "https://rancher.example.com/k8s/clusters/c-abc");
+        assert_eq!(current.ca_pem.as_deref(), Some(b"-----BEGIN CERTIFICATE-----\n".as_slice()));
+        assert_eq!(current.credential, Credential::Token("other-token".to_string()));
+    }
+
+    #[test]
+    fn test_missing_context_and_credential_are_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let kubeconfig = write_kubeconfig(dir.path(), "    username: admin");
+
+        let missing = load_kubeconfig(&KubeconfigAuth {
+            kubeconfig: kubeconfig.clone(),
+            context: Some("nope".to_string()),
+        });
+        let no_credential = load_kubeconfig(&KubeconfigAuth {
+            kubeconfig,
+            context: Some("rancher-mgmt".to_string()),
+        });
+
+        assert!(missing.unwrap_err().to_string().contains("No context `nope`"));
+        assert!(no_credential.unwrap_err().to_string().contains("neither a token nor an exec plugin"));
+    }
+
+    #[tokio::test]
+    async fn test_exec_context_runs_the_plugin_relative_to_the_kubeconfig() {
+        let dir = tempfile::tempdir().unwrap();
+        exec_script(dir.path(), "exec-token");
+        let kubeconfig = write_kubeconfig(
+            dir.path(),
+            "    exec:\n      apiVersion: client.authentication.k8s.io/v1beta1\n      command: ./credential.sh\n      env:\n      - name: RANCHER_SERVER\n        value: rancher.example.com\n",
+        );
+
+        let credentials = load_kubeconfig(&KubeconfigAuth {
+            kubeconfig,
+            context: Some("rancher-mgmt".to_string()),
+        })
+        .unwrap();
+
+        let Credential::Exec(plugin) = credentials.credential else {
+            panic!("expected an exec plugin, got {:?}", credentials.credential);
+        };
+        assert_eq!(plugin.command, dir.path().join("./credential.sh"));
+        assert_eq!(plugin.api_version, "client.authentication.k8s.io/v1beta1");
+        assert_eq!(plugin.env, [("RANCHER_SERVER".to_string(), "rancher.example.com".to_string())]);
+        let token = plugin.token(EXEC_TIMEOUT).await.unwrap();
+        assert_eq!(token.token, "exec-token");
+        assert_eq!(token.expires_at, None);
+    }
+
+    #[tokio::test]
+    async fn test_exec_plugin_is_killed_after_the_timeout() {
+        let plugin = ExecPlugin {
+            command: PathBuf::from("sleep"),
+            args: vec!["5".to_string()],
+            env: Vec::new(),
+            api_version: DEFAULT_EXEC_API_VERSION.to_string(),
+        };
+
+        let started = std::time::Instant::now();
+        let result = plugin.token(Duration::from_millis(100)).await;
+
+        assert!(result.unwrap_err().to_string().contains("printed no credential within"));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_exec_auth_refreshes_the_token_on_401() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = exec_script(dir.path(), "fresh-token");
+        let server = MockServer::start().await;
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        // the first token is refused, as after Rancher expired it
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/clusters"))
+            .and(header("authorization", "Bearer fresh-token"))
+            .respond_with(move |_: &MockRequest| match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => ResponseTemplate::new(401),
+                _ => ResponseTemplate::new(200).set_body_json(serde_json::json!({ "metadata": {}, "items": [] })),
+            })
+            .mount(&server)
+            .await;
+        let plugin = ExecPlugin {
+            command: script,
+            args: Vec::new(),
+            env: Vec::new(),
+            api_version: DEFAULT_EXEC_API_VERSION.to_string(),
+        };
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+            .with(ExecAuth::new(plugin, Url::parse(&server.uri()).unwrap(), EXEC_TIMEOUT))
+            .build();
+
+        let response = client
+            .get(format!("{}/apis/management.cattle.io/v3/clusters", server.uri()))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        let runs = std::fs::read_to_string(dir.path().join("runs")).unwrap();
+        assert_eq!(runs.lines().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_exec_auth_sends_the_token_to_the_rancher_server_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = exec_script(dir.path(), "exec-token");
+        let rancher = MockServer::start().await;
+        let webhook = MockServer::start().await;
+        for server in [&rancher, &webhook] {
+            Mock::given(method("POST"))
+                .respond_with(ResponseTemplate::new(200))
+                .mount(server)
+                .await;
+        }
+        let plugin = ExecPlugin {
+            command: script,
+            args: Vec::new(),
+            env: Vec::new(),
+            api_version: DEFAULT_EXEC_API_VERSION.to_string(),
+        };
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+            .with(ExecAuth::new(plugin, Url::parse(&rancher.uri()).unwrap(), EXEC_TIMEOUT))
+            .build();
+
+        for server in [&rancher, &webhook] {
+            client.post(format!("{}/hook", server.uri())).send().await.unwrap();
+        }
+
+        let authorization = |requests: Vec<MockRequest>| requests[0].headers.get(AUTHORIZATION).cloned();
+        assert_eq!(
+            authorization(rancher.received_requests().await.unwrap()),
+            Some(HeaderValue::from_static("Bearer exec-token"))
+        );
+        assert_eq!(authorization(webhook.received_requests().await.unwrap()), None);
+    }
+}
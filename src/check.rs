@@ -0,0 +1,191 @@
+use std::fmt;
+use std::path::Path;
+
+use rancher_client::apis::configuration::Configuration;
+
+use crate::resources::cluster::probe_clusters;
+use crate::resources::project::probe_projects;
+use crate::resources::rt::probe_role_templates;
+use crate::utils::file::probe_write_access;
+use crate::utils::git::{probe_remote, GitAuth};
+
+/// The outcome of one probe of `shepherd check`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Probe {
+    pub name: String,
+    /// What the probe saw when it passed, why it failed otherwise
+    pub outcome: Result<String, String>,
+}
+
+/// The probes of `shepherd check`, in the order they ran.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CheckReport {
+    pub probes: Vec<Probe>,
+}
+
+impl CheckReport {
+    /// Add the outcome of the probe `name`, errors keep their whole chain on one line.
+    pub fn record<E: fmt::Display>(&mut self, name: impl Into<String>, outcome: Result<String, E>) {
+        let outcome = outcome.map_err(|e| format!("{:#}", e).split_whitespace().collect::<Vec<_>>().join(" "));
+        self.probes.push(Probe {
+            name: name.into(),
+            outcome,
+        });
+    }
+
+    /// Whether every probe passed.
+    pub fn passed(&self) -> bool {
+        self.probes.iter().all(|probe| probe.outcome.is_ok())
+    }
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.probes.iter().map(|probe| probe.name.len()).max().unwrap_or(0);
+        for probe in &self.probes {
+            let (mark, detail) = match &probe.outcome {
+                Ok(detail) => ("✅", detail),
+                Err(reason) => ("❌", reason),
+            };
+            writeln!(f, "{} {:<width$}  {}", mark, probe.name, detail, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+/// Probe everything a sync needs without changing anything.
+///
+/// Lists the clusters, the role templates and the projects of every cluster in
+/// `cluster_ids`, connects to `remote_url` with `auth_method` and writes a probe file
+/// to `config_folder_path`, or to its closest existing parent if it was not created yet.
+pub async fn run_checks(
+    configuration: &Configuration,
+    cluster_ids: &[String],
+    remote_url: &str,
+    auth_method: &GitAuth,
+    config_folder_path: &Path,
+) -> CheckReport {
+    let mut report = CheckReport::default();
+
+    report.record(
+        "List clusters",
+        probe_clusters(configuration).await.map(|count| format!("{} clusters", count)),
+    );
+    report.record(
+        "List role templates",
+        probe_role_templates(configuration)
+            .await
+            .map(|count| format!("{} role templates", count)),
+    );
+    for cluster_id in cluster_ids {
+        report.record(
+            format!("List projects in `{}`", cluster_id),
+            probe_projects(configuration, cluster_id)
+                .await
+                .map(|count| format!("{} projects", count)),
+        );
+    }
+
+    let remote = {
+        let remote_url = remote_url.to_string();
+        let auth_method = auth_method.clone();
+        tokio::task::spawn_blocking(move || probe_remote(&remote_url, &auth_method))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|result| result.map_err(|e| e.to_string()))
+    };
+    report.record(
+        format!("Read {}", remote_url),
+        remote.map(|_| "readable".to_string()),
+    );
+
+    let folder = config_folder_path
+        .ancestors()
+        .find(|folder| folder.is_dir())
+        .unwrap_or(config_folder_path);
+    report.record(
+        format!("Write to {}", config_folder_path.display()),
+        probe_write_access(folder).await.map(|_| {
+            if folder == config_folder_path {
+                "writable".to_string()
+            } else {
+                format!("{} is writable, the folder will be created", folder.display())
+            }
+        }),
+    );
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_report_marks_each_probe_and_fails_on_any_failure() {
+        let mut report = CheckReport::default();
+        report.record("List clusters", Ok::<_, String>("2 clusters".to_string()));
+        assert!(report.passed());
+
+        report.record("List role templates", Err::<String, _>(anyhow::anyhow!("forbidden\n  by RBAC")));
+
+        assert!(!report.passed());
+        assert_eq!(
+            report.to_string(),
+            "✅ List clusters        2 clusters\n❌ List role templates  forbidden by RBAC\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_checks_probes_rancher_git_and_the_folder() {
+        let server = MockServer::start().await;
+        let list = |items: serde_json::Value| serde_json::json!({ "metadata": {}, "items": items });
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/clusters"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(list(serde_json::json!([
+                { "metadata": { "name": "c-123" }, "spec": { "displayName": "prod" } },
+            ]))))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/roletemplates"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(serde_json::json!({ "reason": "Forbidden" })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/c-123/projects"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(list(serde_json::json!([]))))
+            .mount(&server)
+            .await;
+        let configuration = Configuration {
+            base_path: server.uri(),
+            ..Default::default()
+        };
+        let remote = tempfile::tempdir().unwrap();
+        git2::Repository::init_bare(remote.path()).unwrap();
+        let folder = tempfile::tempdir().unwrap();
+        let config_folder_path = folder.path().join("config");
+
+        let report = run_checks(
+            &configuration,
+            &["c-123".to_string()],
+            remote.path().to_str().unwrap(),
+            &GitAuth::SshAgent,
+            &config_folder_path,
+        )
+        .await;
+
+        let failed: Vec<_> = report
+            .probes
+            .iter()
+            .filter(|probe| probe.outcome.is_err())
+            .map(|probe| probe.name.as_str())
+            .collect();
+        assert_eq!(failed, ["List role templates"], "{}", report);
+        assert_eq!(report.probes.len(), 5);
+        assert_eq!(report.probes[2].outcome, Ok("0 projects".to_string()));
+        assert!(!config_folder_path.exists());
+    }
+}
@@ -1,4 +1,15 @@
-use crate::models::ConversionError;
+use std::borrow::Cow;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use rancher_client::apis::ResponseContent;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::models::{ConversionError, ObjectType, ReadyPolicy};
 
 
 #[derive(Debug, thiserror::Error)]
@@ -14,6 +25,9 @@ pub enum AppError {
     
     #[error("Object conversion error: {0}")]
     ConversionError(#[from] ConversionError),
+
+    #[error("Timed out waiting for `{object_id}` to become ready after {elapsed:?}, polling {policy}")]
+    TimedOutWaitingReady { object_id: String, elapsed: Duration, policy: ReadyPolicy },
     
     #[error("{0}")]
     Other(String),
@@ -26,6 +40,270 @@ impl AppError {
 }
 
 
+/// The deserialization failure behind `error`, if it was raised for a file that does not parse.
+pub fn parse_error(error: &anyhow::Error) -> Option<&ConversionError> {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<ConversionError>())
+        .find(|conversion| conversion.path().is_some())
+}
+
+/// Why the file of a deleted object does not name an object to delete.
+#[derive(Debug, thiserror::Error)]
+pub enum DeletionError {
+    #[error("Cannot delete the {object_type:?}, its last committed contents are malformed: {source}")]
+    Malformed {
+        object_type: ObjectType,
+        source: ConversionError,
+    },
+
+    #[error("Deletion is unsupported for {object_type:?} objects, {path} was removed but Rancher is left unchanged")]
+    Unsupported { object_type: ObjectType, path: PathBuf },
+
+    #[error("{object_type:?} is not in deletable_types, {path} was removed but Rancher is left unchanged")]
+    NotAllowed { object_type: ObjectType, path: PathBuf },
+}
+
+
+/// The object an update or deletion failed for, attached as context to the error.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("Failed to apply {object_type:?} `{object_id}`")]
+pub struct FailedObject {
+    pub object_type: ObjectType,
+    pub object_id: String,
+    /// The namespace of the object, `None` for role templates
+    pub namespace: Option<String>,
+    /// The file of the object, if it is known where the error is raised
+    pub path: Option<PathBuf>,
+}
+
+/// The object `error` was raised for, if it is known.
+pub fn failed_object(error: &anyhow::Error) -> Option<&FailedObject> {
+    error.downcast_ref::<FailedObject>()
+}
+
+/// An API call that failed in a way that may succeed when simply tried again,
+/// such as a timeout or a connection reset.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct TransientError(pub String);
+
+/// Whether a failed API call never got a response because of a network problem.
+///
+/// Responses with an error status are not transient, the server made a decision.
+pub fn is_transient_api_error<T>(error: &rancher_client::apis::Error<T>) -> bool {
+    use rancher_client::apis::Error;
+
+    match error {
+        Error::Reqwest(e) | Error::ReqwestMiddleware(reqwest_middleware::Error::Reqwest(e)) => {
+            e.is_timeout() || e.is_connect() || e.is_request()
+        }
+        Error::Io(_) => true,
+        _ => false,
+    }
+}
+
+/// Whether `error` was raised by a resource function for a transient API failure.
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<TransientError>().is_some()
+}
+
+/// An API call the account is not allowed to make, answered with `403 Forbidden`.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct ForbiddenError(pub String);
+
+/// Whether `error` was raised by a resource function for a call the account may not make.
+pub fn is_forbidden(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.downcast_ref::<ForbiddenError>().is_some())
+}
+
+/// Whether a refused API call would only have read objects or would have changed them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl std::fmt::Display for Access {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Access::Read => write!(f, "read"),
+            Access::Write => write!(f, "write"),
+        }
+    }
+}
+
+/// An API call answered with `401 Unauthorized` or `403 Forbidden`, naming what the call
+/// needed access to so the refusals of a run can be summed up.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{message}")]
+pub struct PermissionError {
+    /// `401` or `403`
+    pub status: u16,
+    pub access: Access,
+    pub object_type: ObjectType,
+    /// The cluster ID of projects and cluster role template bindings, the project ID of
+    /// project role template bindings
+    pub namespace: Option<String>,
+    pub message: String,
+}
+
+/// The refusal behind `error`, if the account was not allowed to make the call.
+pub fn permission_error(error: &anyhow::Error) -> Option<&PermissionError> {
+    error.downcast_ref::<PermissionError>()
+}
+
+/// A file path built from a name Rancher or the repository handed us that leads out of the
+/// folder it belongs in, through `..` or a symbolic link.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Refusing to touch {path:?}, it leads outside of {root:?}; the name it was built from may be malicious or corrupted")]
+pub struct UnsafePathError {
+    pub path: PathBuf,
+    pub root: PathBuf,
+}
+
+/// Whether `error` was raised for a path leading outside of its folder.
+pub fn is_unsafe_path(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.downcast_ref::<UnsafePathError>().is_some())
+}
+
+/// The clock of this host is too far off the endpoint, whose tokens and the signatures of
+/// the git remote are then refused as if the credentials were wrong.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "The clock of this host is {}s {} {endpoint}, tokens and git signatures are refused as unauthorized until it is synchronized",
+    skew_seconds.abs(),
+    if *skew_seconds > 0 { "ahead of" } else { "behind" }
+)]
+pub struct ClockSkewError {
+    pub endpoint: String,
+    /// Positive when this host is ahead
+    pub skew_seconds: i64,
+}
+
+/// Turn the message for an API call answered with `status` into an error, marking it as
+/// [`PermissionError`] when the status refuses the account.
+pub fn status_error(
+    status: StatusCode,
+    access: Access,
+    object_type: ObjectType,
+    namespace: Option<&str>,
+    msg: String,
+) -> anyhow::Error {
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => PermissionError {
+            status: status.as_u16(),
+            access,
+            object_type,
+            namespace: namespace.map(str::to_string),
+            message: msg,
+        }
+        .into(),
+        _ => anyhow::anyhow!(msg),
+    }
+}
+
+/// Turn the message for a failed API call into an error, marking it as
+/// [`TransientError`] when the call is worth retrying.
+pub fn api_error<T>(error: &rancher_client::apis::Error<T>, msg: String) -> anyhow::Error {
+    if is_transient_api_error(error) {
+        TransientError(msg).into()
+    } else {
+        anyhow::anyhow!(msg)
+    }
+}
+
+
+
+
+/// How much of a response body goes into an error, see [`set_error_bodies`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorBodies {
+    /// Bytes of a body kept in error messages, longer bodies are cut
+    pub max_len: usize,
+    /// Folder the full bodies that were cut are written to, `None` drops them
+    pub spool_dir: Option<PathBuf>,
+}
+
+impl Default for ErrorBodies {
+    fn default() -> Self {
+        Self { max_len: 4096, spool_dir: None }
+    }
+}
+
+static ERROR_BODIES: OnceLock<ErrorBodies> = OnceLock::new();
+
+/// The folder of the state dir `--verbose` keeps the full response bodies in.
+pub const ERROR_BODIES_FOLDER: &str = "error_bodies";
+
+/// Set how much of a response body goes into errors and where the full bodies are kept.
+///
+/// Errors are built in every API call, far from the config, so the setting is kept for the
+/// process; set it before the first request.
+pub fn set_error_bodies(bodies: ErrorBodies) {
+    let _ = ERROR_BODIES.set(bodies);
+}
+
+/// Write `body` to a new file in `spool_dir`, returning its path.
+fn spool_body(spool_dir: &std::path::Path, body: &str) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(spool_dir)?;
+    let name = format!(
+        "{}-{}.body",
+        chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+        crate::utils::logging::generate_operation_id()
+    );
+    let path = spool_dir.join(name);
+    std::fs::write(&path, body)?;
+    Ok(path)
+}
+
+/// `body` cut to the configured length, such as an HTML error page or a huge status, so
+/// errors and the log lines built from them stay small.
+///
+/// A cut body ends with an ellipsis and its full length, and the path of the full body
+/// when a spool folder is set.
+pub fn bounded_body(body: &str) -> Cow<'_, str> {
+    bound(ERROR_BODIES.get_or_init(ErrorBodies::default), body, true)
+}
+
+fn bound<'a>(bodies: &ErrorBodies, body: &'a str, spool: bool) -> Cow<'a, str> {
+    if body.len() <= bodies.max_len {
+        return Cow::Borrowed(body);
+    }
+    let mut end = bodies.max_len;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    let spool_dir = bodies.spool_dir.as_deref().filter(|_| spool);
+    let spooled = spool_dir.and_then(|spool_dir| match spool_body(spool_dir, body) {
+        Ok(path) => Some(path),
+        Err(e) => {
+            warn!("Failed to keep a response body in {}: {}", spool_dir.display(), e);
+            None
+        }
+    });
+    Cow::Owned(match spooled {
+        Some(path) => format!("{}… ({} bytes, in full at {})", &body[..end], body.len(), path.display()),
+        None => format!("{}… ({} bytes)", &body[..end], body.len()),
+    })
+}
+
+/// Formats a response like its `{:#?}`, with the body bounded by [`bounded_body`] and the
+/// entity parsed from it cut the same way.
+pub struct BoundedResponse<'a, T>(pub &'a ResponseContent<T>);
+
+impl<T: fmt::Debug> fmt::Debug for BoundedResponse<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entity = format!("{:?}", self.0.entity);
+        f.debug_struct("ResponseContent")
+            .field("status", &self.0.status)
+            .field("content", &bounded_body(&self.0.content))
+            .field("entity", &format_args!("{}", bound(ERROR_BODIES.get_or_init(ErrorBodies::default), &entity, false)))
+            .finish()
+    }
+}
 
 
 pub fn handle_result_collection<T, E>(results: Vec<Result<T, E>>) -> (Vec<T>, Vec<E>) 
@@ -43,4 +321,47 @@ where
     }
     
     (successes, errors)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_bodies_are_cut_with_their_length() {
+        let bodies = ErrorBodies { max_len: 16, spool_dir: None };
+
+        assert_eq!(bound(&bodies, "{\"code\":\"Gone\"}", true), "{\"code\":\"Gone\"}");
+        let page = format!("<html>{}</html>", "x".repeat(10_000));
+        assert_eq!(bound(&bodies, &page, true), "<html>xxxxxxxxxx… (10013 bytes)");
+        // never cut inside a character
+        assert_eq!(bound(&bodies, &"é".repeat(20), true), format!("{}… (40 bytes)", "é".repeat(8)));
+        assert_eq!(bound(&bodies, &format!("a{}", "é".repeat(20)), true), format!("a{}… (41 bytes)", "é".repeat(7)));
+    }
+
+    #[test]
+    fn test_cut_bodies_are_kept_in_full_in_the_spool_folder() {
+        let spool = tempfile::tempdir().unwrap();
+        let bodies = ErrorBodies { max_len: 4, spool_dir: Some(spool.path().join(ERROR_BODIES_FOLDER)) };
+
+        let bounded = bound(&bodies, "<html>Bad Gateway</html>", true);
+
+        let path = bounded.split("in full at ").nth(1).unwrap().trim_end_matches(')');
+        assert!(bounded.starts_with("<htm… (24 bytes, in full at "));
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "<html>Bad Gateway</html>");
+    }
+
+    #[test]
+    fn test_responses_are_formatted_with_a_bounded_body() {
+        let response: ResponseContent<()> = ResponseContent {
+            status: StatusCode::BAD_GATEWAY,
+            content: "x".repeat(100_000),
+            entity: None,
+        };
+
+        let formatted = format!("{:#?}", BoundedResponse(&response));
+
+        assert!(formatted.len() < 5_000, "{}", formatted.len());
+        assert!(formatted.contains("(100000 bytes)"));
+        assert!(formatted.contains("502"));
+    }
+}
@@ -0,0 +1,361 @@
+//! A stream of the actions of every run, one JSON object per line, for other systems to ingest.
+//!
+//! Events are queued in a bounded channel and written by a background task, a sink that is
+//! slow or gone drops events, counted in `run_completed`, instead of holding up the run.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::models::ObjectType;
+use crate::report::RunStatus;
+
+/// The `[events]` section of the config.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EventSinkConfig {
+    /// A file appended to, a FIFO, or `tcp://host:port`
+    pub sink: String,
+    /// Events queued for the sink before more are dropped
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+}
+
+fn default_capacity() -> usize {
+    1024
+}
+
+/// Where the events are written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkTarget {
+    /// A file or FIFO, opened for appending
+    File(PathBuf),
+    /// A `host:port` connected to over TCP
+    Tcp(String),
+}
+
+impl EventSinkConfig {
+    pub fn target(&self) -> SinkTarget {
+        match self.sink.strip_prefix("tcp://") {
+            Some(address) => SinkTarget::Tcp(address.to_string()),
+            None => SinkTarget::File(PathBuf::from(&self.sink)),
+        }
+    }
+}
+
+/// What was done, or is about to be done, to an object.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Create,
+    Update,
+    Delete,
+}
+
+/// The object an event is about, as far as it is known.
+///
+/// Objects about to be created have no ID yet, objects without a file have no path.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ObjectRef {
+    pub object_type: ObjectType,
+    pub cluster_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+/// The event of a line of the stream, named by its `event` field.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    RunStarted {
+        endpoint: String,
+        dry_run: bool,
+    },
+    /// The object is about to be changed, or the change is written to a plan
+    ObjectPlanned {
+        action: Action,
+        object: ObjectRef,
+    },
+    ObjectApplied {
+        action: Action,
+        object: ObjectRef,
+    },
+    ObjectFailed {
+        action: Action,
+        object: ObjectRef,
+        error: String,
+    },
+    RunCompleted {
+        status: RunStatus,
+        created: usize,
+        updated: usize,
+        deleted: usize,
+        failed: usize,
+        /// The events dropped since the sink was started, a gap in the stream if it grew
+        dropped_events: u64,
+    },
+}
+
+/// A line of the stream.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SyncEvent {
+    pub run_id: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// Queues events for the background task writing them to the sink.
+#[derive(Debug, Clone)]
+pub struct EventSink {
+    sender: mpsc::Sender<SyncEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl EventSink {
+    /// Start writing events to the sink of `config`, which is opened with the first event.
+    pub fn start(config: &EventSinkConfig) -> Self {
+        let (sink, receiver) = Self::new(config.capacity);
+        tokio::spawn(write_events(config.target(), receiver, sink.dropped.clone()));
+        sink
+    }
+
+    fn new(capacity: usize) -> (Self, mpsc::Receiver<SyncEvent>) {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        let sink = Self {
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+        };
+        (sink, receiver)
+    }
+
+    /// The events of the run `run_id`.
+    pub fn for_run(&self, run_id: &str) -> RunEvents {
+        RunEvents {
+            sink: self.clone(),
+            run_id: run_id.to_string(),
+        }
+    }
+
+    /// Queue `event`, or drop and count it if the queue is full.
+    pub fn emit(&self, event: SyncEvent) {
+        match self.sender.try_send(event) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(event)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                debug!("Event queue full, dropped {:?}", event.kind);
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// The events dropped since the sink was started.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// The events of one run, stamped with its ID and the time they are emitted.
+#[derive(Debug, Clone)]
+pub struct RunEvents {
+    sink: EventSink,
+    run_id: String,
+}
+
+impl RunEvents {
+    pub fn emit(&self, kind: EventKind) {
+        self.sink.emit(SyncEvent {
+            run_id: self.run_id.clone(),
+            timestamp: Utc::now(),
+            kind,
+        });
+    }
+
+    /// The events dropped since the sink was started.
+    pub fn dropped(&self) -> u64 {
+        self.sink.dropped()
+    }
+}
+
+type Writer = Box<dyn AsyncWrite + Send + Unpin>;
+
+async fn open(target: &SinkTarget) -> std::io::Result<Writer> {
+    match target {
+        // opening a FIFO waits for a reader, in the background task only
+        SinkTarget::File(path) => {
+            let file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+            Ok(Box::new(file))
+        }
+        SinkTarget::Tcp(address) => Ok(Box::new(tokio::net::TcpStream::connect(address).await?)),
+    }
+}
+
+/// Write the events of `receiver` to `target` a line each, reopening it after a failed write.
+///
+/// An event that cannot be written is dropped and counted in `dropped`.
+async fn write_events(target: SinkTarget, mut receiver: mpsc::Receiver<SyncEvent>, dropped: Arc<AtomicU64>) {
+    let mut writer: Option<Writer> = None;
+    while let Some(event) = receiver.recv().await {
+        let mut line = match serde_json::to_vec(&event) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Cannot serialize the {:?} event: {}", event.kind, e);
+                dropped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+        };
+        line.push(b'\n');
+        if writer.is_none() {
+            match open(&target).await {
+                Ok(opened) => writer = Some(opened),
+                Err(e) => {
+                    warn!("Cannot open the event sink {:?}: {}", target, e);
+                    dropped.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            }
+        }
+        if let Some(open_writer) = writer.as_mut() {
+            let written = match open_writer.write_all(&line).await {
+                Ok(()) => open_writer.flush().await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = written {
+                warn!("Failed to write to the event sink {:?}, reopening it: {}", target, e);
+                dropped.fetch_add(1, Ordering::Relaxed);
+                writer = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(seconds: u32) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2026-10-16T10:00:{:02}Z", seconds)).unwrap().with_timezone(&Utc)
+    }
+
+    fn event(seconds: u32, kind: EventKind) -> SyncEvent {
+        SyncEvent {
+            run_id: "20261016T100000.000Z".to_string(),
+            timestamp: at(seconds),
+            kind,
+        }
+    }
+
+    fn binding(object_id: Option<&str>) -> ObjectRef {
+        ObjectRef {
+            object_type: ObjectType::ProjectRoleTemplateBinding,
+            cluster_id: "c-123".to_string(),
+            object_id: object_id.map(str::to_string),
+            namespace: object_id.map(|_| "p-abc".to_string()),
+            path: Some(PathBuf::from("rancher.example.com/c-123/p-abc/prtbs/dev.prtb.yaml")),
+        }
+    }
+
+    /// One event of every kind, in the order of `tests/golden/events.ndjson`.
+    fn samples() -> Vec<SyncEvent> {
+        vec![
+            event(0, EventKind::RunStarted { endpoint: "https://rancher.example.com".to_string(), dry_run: false }),
+            event(1, EventKind::ObjectPlanned { action: Action::Create, object: binding(None) }),
+            event(2, EventKind::ObjectApplied { action: Action::Create, object: binding(Some("prtb-xyz")) }),
+            event(
+                3,
+                EventKind::ObjectFailed {
+                    action: Action::Update,
+                    object: ObjectRef {
+                        object_type: ObjectType::Project,
+                        cluster_id: "c-123".to_string(),
+                        object_id: Some("p-abc".to_string()),
+                        namespace: Some("c-123".to_string()),
+                        path: None,
+                    },
+                    error: "422 Unprocessable Entity".to_string(),
+                },
+            ),
+            event(
+                4,
+                EventKind::RunCompleted {
+                    status: RunStatus::Completed,
+                    created: 1,
+                    updated: 0,
+                    deleted: 0,
+                    failed: 1,
+                    dropped_events: 0,
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_events_match_the_golden_samples() {
+        let golden = include_str!("../tests/golden/events.ndjson");
+        let samples = samples();
+
+        assert_eq!(golden.lines().count(), samples.len());
+        for (line, sample) in golden.lines().zip(&samples) {
+            assert_eq!(serde_json::to_string(sample).unwrap(), line);
+            assert_eq!(&serde_json::from_str::<SyncEvent>(line).unwrap(), sample);
+        }
+    }
+
+    #[test]
+    fn test_sink_targets() {
+        let config = |sink: &str| EventSinkConfig { sink: sink.to_string(), capacity: default_capacity() };
+
+        assert_eq!(config("/var/run/shepherd/events").target(), SinkTarget::File(PathBuf::from("/var/run/shepherd/events")));
+        assert_eq!(config("tcp://audit.example.com:5170").target(), SinkTarget::Tcp("audit.example.com:5170".to_string()));
+    }
+
+    #[test]
+    fn test_events_beyond_the_capacity_are_dropped_and_counted() {
+        let (sink, mut receiver) = EventSink::new(2);
+        let run = sink.for_run("run-1");
+
+        for _ in 0..5 {
+            run.emit(EventKind::RunStarted { endpoint: "https://rancher.example.com".to_string(), dry_run: true });
+        }
+
+        assert_eq!(run.dropped(), 3);
+        assert_eq!(receiver.try_recv().unwrap().run_id, "run-1");
+        assert!(receiver.try_recv().is_ok());
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_events_are_appended_to_a_file_a_line_each() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.ndjson");
+        std::fs::write(&path, "earlier\n").unwrap();
+        let sink = EventSink::start(&EventSinkConfig { sink: path.to_string_lossy().to_string(), capacity: 8 });
+
+        for sample in samples() {
+            sink.emit(sample);
+        }
+
+        let golden = include_str!("../tests/golden/events.ndjson");
+        let expected = format!("earlier\n{}", golden);
+        for _ in 0..100 {
+            if std::fs::read_to_string(&path).unwrap() == expected {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+        assert_eq!(sink.dropped(), 0);
+    }
+}
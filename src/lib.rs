@@ -1,12 +1,30 @@
 // This file will contain all the functions that will be used to interact and extract from the Rancher API
 pub mod utils{
+    pub mod bundle;
+    pub mod canonical;
+    pub mod config_validator;
+    pub mod convert;
     pub mod diff;
+    pub mod doctor;
+    pub mod fields;
     pub mod file;
     pub mod git;
+    pub mod ignore;
+    pub mod index;
     pub mod logging;
+    pub mod parse_cache;
+    pub mod policy;
+    pub mod principal;
+    pub mod quota;
+    pub mod rbac;
+    pub mod safe_path;
+    pub mod snapshot;
+    pub mod taskdump;
+    pub mod yaml_edit;
 }
 
 pub mod resources {
+    pub mod crtb;
     pub mod project;
     pub mod cluster;
     pub mod prtb;
@@ -14,43 +32,77 @@ pub mod resources {
 }
 
 pub mod api {
+    pub mod circuit_breaker;
+    pub mod clock;
     pub mod config;
     pub mod client_info;
     pub mod client;
+    pub mod kubeconfig;
 }
 
+pub mod check;
 pub mod error;
+pub mod events;
 
 
 pub mod models;
 pub mod modify;
+pub mod notify;
+pub mod plan;
+pub mod report;
+pub mod reporting;
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod state;
 pub mod traits;
-
-use anyhow::{bail, Context, Result};
-
-use traits::RancherResource;
-use utils::file::{file_extension_from_format, file_format_from_path, get_file_name_for_object, FileFormat};
-use utils::logging::log_api_error;
-
-use models::{ConversionError, CreatedObject, ObjectType};
+pub mod transaction;
+
+pub use utils::git::is_repo_effectively_empty;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use traits::{DownloadTarget, RancherResource};
+use utils::safe_path::ensure_within;
+use utils::file::{endpoint_folder, file_extension_from_format, file_format_from_path, get_file_name_for_object, parse_object_file_name, FileFormat};
+use utils::canonical::canonical_value;
+use utils::bundle::{bundle_file_name, bundle_project_id, is_bundle, project_document, read_bundle, write_bundle};
+use utils::logging::{log_api_error, warn_repeated};
+use utils::diff::MetadataFilter;
+use utils::policy::{Binding, BindingPolicy, BoundProject};
+use utils::principal::PrincipalRules;
+use utils::rbac::{role_template_errors, InheritanceGraph};
+use utils::config_validator::{check_cluster_placement, duplicate_bindings, duplicate_project_display_names, ClusterScoped, Violation};
+use utils::ignore::file_has_ignore_directive;
+use utils::index::RepoIndex;
+use utils::parse_cache::ParseCache;
+use utils::yaml_edit::keep_comments;
+
+use models::{ConversionError, CreatedObject, DownloadOptions, FileLimits, ObjectType, ProjectReadiness, ReadyPolicy, RetryPolicy};
+use report::{DownloadReport, SyncReport};
+use state::write_atomically;
+use error::{is_forbidden, is_transient, is_transient_api_error, is_unsafe_path, permission_error, AppError};
 
 
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::future::Future;
 use std::option::Option;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::fs::{create_dir_all, read_dir, read_to_string, write};
+use tokio::fs::{create_dir_all, read_dir, read_to_string};
 use tokio::time::sleep;
 use tracing::{debug, trace, error, info, warn};
 
 use api::config::{ClusterConfig, RancherClusterConfig};
-use resources::cluster::{self, Cluster};
-use resources::project::{find_project, get_projects, Project};
-use resources::prtb::{get_namespaced_project_role_template_bindings, ProjectRoleTemplateBinding};
-use resources::rt::{find_role_template, get_role_templates, RoleTemplate};
+use resources::cluster::{self, cluster_folder, place_cluster_folder, Cluster, ClusterFolderMap, ClusterFolderNaming};
+use resources::project::{find_project, get_projects, match_regenerated_projects, move_project, Project};
+use resources::crtb::{get_namespaced_cluster_role_template_bindings, ClusterRoleTemplateBinding};
+use resources::prtb::{
+    find_project_role_template_binding, get_all_project_role_template_bindings,
+    get_namespaced_project_role_template_bindings, PrtbListing, ProjectRoleTemplateBinding,
+};
+use resources::rt::{find_role_template, get_role_templates, RoleTemplate, RoleTemplateFilter, RoleTemplateIndex};
 
 use rancher_client::apis::configuration::Configuration;
 use rancher_client::models::{
@@ -60,14 +112,29 @@ use rancher_client::models::{
 
 include!(concat!(env!("OUT_DIR"), "/client_info.rs"));
 
+/// Name of the folder inside a cluster folder holding its cluster role template bindings.
+pub const CRTB_FOLDER: &str = "crtbs";
+
 
 
 
 /// Downloads the current configuration from the Rancher API and stores it in a file.
 ///
 /// This will create a folder structure mirroring the Rancher API, with clusters as subfolders
-/// containing projects and project role template bindings as subfolders, and role templates as
-/// files in a 'roles' folder. Each object is serialized as a file in the specified format.
+/// containing projects and project role template bindings as subfolders, cluster role template
+/// bindings in a 'crtbs' subfolder, and role templates as files in a 'roles' folder. Each object is serialized as a file in the specified format.
+///
+/// Annotations and labels matched by `metadata_filter` are managed by Rancher and are left
+/// out of the written files. Role templates rejected by `role_template_filter` are not written.
+/// List calls failing with a transient network error are retried according to `retry_policy`.
+/// Rancher's own `local` cluster is only downloaded with `include_local_cluster`.
+/// Cluster folders are named after the cluster ID or display name as set by `naming`.
+/// Project role template bindings are listed as set by `prtb_listing`, see [`list_prtbs`].
+/// The files are written as set by `options`.
+///
+/// # Returns
+///
+/// The files written and skipped, per cluster
 ///
 /// # Errors
 ///
@@ -77,28 +144,32 @@ include!(concat!(env!("OUT_DIR"), "/client_info.rs"));
 /// * The folder structure cannot be created
 /// * The objects cannot be converted to the specified format
 /// * The objects cannot be written to the file system
+#[allow(clippy::too_many_arguments)]
 #[async_backtrace::framed]
 pub async fn download_current_configuration(
     configuration: &Configuration,
     path: &Path,
     file_format: &FileFormat,
-) -> Result<()> {
-    let rancher_cluster = cluster::get_clusters(configuration)
-        .await
-        .context("Failed to get clusters")?;
+    metadata_filter: &MetadataFilter,
+    role_template_filter: &RoleTemplateFilter,
+    retry_policy: &RetryPolicy,
+    include_local_cluster: bool,
+    naming: ClusterFolderNaming,
+    prtb_listing: PrtbListing,
+    options: &DownloadOptions,
+) -> Result<DownloadReport> {
+    let mut report = DownloadReport::default();
+    let rancher_cluster = retry_async(
+        "get_clusters",
+        retry_policy.max_retries.max(1),
+        retry_policy.delay,
+        || cluster::get_clusters(configuration, None, None, None, None, None, None),
+        is_transient_api_error,
+    )
+    .await
+    .context("Failed to get clusters")?;
 
-    let rancher_role_templates =
-        get_role_templates(configuration, None, None, None, None, None, None)
-            .await
-            .context("Failed to get role templates")?;
-
-    let base_path = path.join(
-        configuration
-            .base_path
-            .trim_end_matches('/')
-            .replace("https://", "")
-            .replace('/', "_"),
-    );
+    let base_path = endpoint_folder(path, &configuration.base_path);
     if !base_path.exists() {
         create_dir_all(&base_path)
             .await
@@ -112,112 +183,492 @@ pub async fn download_current_configuration(
             .context("Failed to create role templates folder")?;
     }
 
-    let role_templates: Vec<RoleTemplate> = rancher_role_templates
-        .items
-        .into_iter()
-        .map(|item| item.try_into().context("Failed to convert role template"))
-        .collect::<Result<_>>()?;
-
-    for role_template in &role_templates {
-        let role_template_file = role_template_path.join(get_file_name_for_object(&role_template.id, &ObjectType::RoleTemplate, file_format));
-        write(
-            &role_template_file,
-            serialize_object(role_template, file_format)?,
-        )
-        .await
-        .with_context(|| format!("Failed to write file {:?}", role_template_file))?;
+    let target = DownloadTarget { dir: &role_template_path, file_format, metadata_filter, cluster_id: None };
+    let mut role_templates =
+        RoleTemplate::fetch_all(configuration, None, retry_policy, |role_template| role_template_filter.includes(role_template)).await?;
+    for role_template in &mut role_templates {
+        let (annotations, labels) = role_template.metadata_mut();
+        metadata_filter.retain(annotations);
+        metadata_filter.retain(labels);
+        role_template.save_to(target, &mut report).await?;
+    }
+    if let Some(index) = RoleTemplateIndex::new(&role_templates).write(&role_template_path, file_format)? {
+        // not a role template, only committed along with them
+        report.written.push(index);
     }
 
     let clusters: Vec<Cluster> = rancher_cluster
         .items
         .into_iter()
         .map(|item| item.try_into().context("Failed to convert cluster"))
-        .collect::<Result<_>>()?;
+        .collect::<Result<Vec<Cluster>>>()?
+        .into_iter()
+        .filter(|cluster| include_local_cluster || cluster.id != cluster::LOCAL_CLUSTER_ID)
+        .collect();
 
+    let prtbs = list_prtbs(configuration, prtb_listing, retry_policy).await?;
     for cluster in &clusters {
-        let cluster_path = base_path.join(&cluster.id);
-        if !cluster_path.exists() {
-            create_dir_all(&cluster_path)
-                .await
-                .context("Failed to create cluster folder")?;
-        }
+        download_cluster_configuration(
+            configuration,
+            &base_path,
+            cluster,
+            file_format,
+            naming,
+            metadata_filter,
+            retry_policy,
+            &prtbs,
+            options,
+            &mut report,
+        )
+        .await?;
+    }
+
+    Ok(report)
+}
 
-        let cluster_file = cluster_path.join(get_file_name_for_object(&cluster.id, &ObjectType::Cluster, file_format));
-        write(&cluster_file, serialize_object(cluster, file_format)?)
+/// Downloads the projects and role template bindings of `cluster` into its folder in
+/// `endpoint_path`, creating the folder if needed.
+///
+/// With [`ClusterFolderNaming::DisplayName`] the folder is named after the display name
+/// and recorded in the cluster folder map, see [`place_cluster_folder`].
+///
+/// Annotations and labels matched by `metadata_filter` are left out of the written files.
+/// List calls failing with a transient network error are retried according to `retry_policy`.
+/// The project role template bindings are taken from `prtbs`, or listed per project.
+/// The files are written as set by `options`, the written and skipped ones are recorded in `report`.
+///
+/// # Errors
+///
+/// Returns an error if the objects cannot be listed, converted or written
+#[allow(clippy::too_many_arguments)]
+#[async_backtrace::framed]
+pub async fn download_cluster_configuration(
+    configuration: &Configuration,
+    endpoint_path: &Path,
+    cluster: &Cluster,
+    file_format: &FileFormat,
+    naming: ClusterFolderNaming,
+    metadata_filter: &MetadataFilter,
+    retry_policy: &RetryPolicy,
+    prtbs: &PrtbSource,
+    options: &DownloadOptions,
+    report: &mut DownloadReport,
+) -> Result<()> {
+    let cluster_path = match naming {
+        ClusterFolderNaming::Id => endpoint_path.join(&cluster.id),
+        ClusterFolderNaming::DisplayName => {
+            let (cluster_path, moved) = match place_cluster_folder(endpoint_path, cluster, file_format) {
+                Ok(placed) => placed,
+                Err(e) if is_unsafe_path(&e) => {
+                    error!("Skipping cluster `{}`: {:#}", cluster.id, e);
+                    return Ok(());
+                }
+                Err(e) => return Err(e),
+            };
+            report.moved.extend(moved);
+            cluster_path
+        }
+    };
+    let cluster_file = cluster_path.join(get_file_name_for_object(&cluster.id, &ObjectType::Cluster, file_format));
+    if let Some(e) = [&cluster_path, &cluster_file].iter().find_map(|path| ensure_within(endpoint_path, path).err()) {
+        error!("Skipping cluster `{}`: {}", cluster.id, e);
+        return Ok(());
+    }
+    if !cluster_path.exists() {
+        create_dir_all(&cluster_path)
             .await
-            .with_context(|| format!("Failed to write cluster file {:?}", cluster_file))?;
+            .context("Failed to create cluster folder")?;
+    }
 
-        let rancher_projects = get_projects(
-            configuration,
-            &cluster.id,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-        )
-        .await
-        .context("Failed to get projects")?;
+    let stored = cluster.redacted(&options.cluster_redact_paths)?;
+    write_atomically(&cluster_file, &keep_comments(&cluster_file, serialize_object(&stored, file_format)?).await)
+        .with_context(|| format!("Failed to write cluster file {:?}", cluster_file))?;
+    report.record(Some(&cluster.id), &cluster_file, false);
 
-        let projects: Vec<Project> = rancher_projects
-            .items
-            .into_iter()
-            .map(|item| item.try_into().context("Failed to convert project"))
-            .collect::<Result<_>>()?;
-
-        for project in &projects {
-            let project_path = cluster_path.join(&project.id.clone().unwrap());
-            if !project_path.exists() {
-                create_dir_all(&project_path)
-                    .await
-                    .context("Failed to create project folder")?;
+    let projects = Project::fetch_all(configuration, Some(&cluster.id), retry_policy, |_| true).await?;
+    for mut project in projects {
+        let (annotations, labels) = project.metadata_mut();
+        metadata_filter.retain(annotations);
+        metadata_filter.retain(labels);
+        let project_id = project
+            .id
+            .clone()
+            .ok_or(ConversionError::MissingField("metadata.name".into()))
+            .context("Failed to read project ID")?;
+        // the bundle and the folder of the project are named after its ID
+        let outside = [cluster_path.join(bundle_file_name(&project_id)), cluster_path.join(&project_id)]
+            .iter()
+            .find_map(|path| ensure_within(&cluster_path, path).err());
+        if let Some(e) = outside {
+            error!("Skipping project `{}`: {}", project_id, e);
+            continue;
+        }
+        let mut prtbs = match prtbs {
+            PrtbSource::PerProject => {
+                ProjectRoleTemplateBinding::fetch_all(configuration, Some(&project_id), retry_policy, |_| true).await?
             }
+            PrtbSource::Listed(by_project) => by_project
+                .get(&project_id)
+                .cloned()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|item| {
+                    ProjectRoleTemplateBinding::try_from_api(item)
+                        .context("Failed to convert project role template binding")
+                })
+                .collect::<Result<_>>()?,
+        };
+        for prtb in &mut prtbs {
+            let (annotations, labels) = prtb.metadata_mut();
+            metadata_filter.retain(annotations);
+            metadata_filter.retain(labels);
+        }
+
+        if options.bundle_projects {
+            let bundle_file = cluster_path.join(bundle_file_name(&project_id));
+            let prtbs = prtbs.iter().map(RancherResource::for_file).collect::<Result<Vec<_>>>()?;
+            write_downloaded(&bundle_file, write_bundle(&project, &prtbs)?, report, Some(&cluster.id))
+                .await
+                .with_context(|| format!("Failed to write project bundle {:?}", bundle_file))?;
+            continue;
+        }
 
-            let project_file = project_path.join(get_file_name_for_object(&project.id.clone().unwrap(), &ObjectType::Project, file_format));
-            write(&project_file, serialize_object(project, file_format)?)
+        let project_path = cluster_path.join(&project_id);
+        if !project_path.exists() {
+            create_dir_all(&project_path)
                 .await
-                .with_context(|| format!("Failed to write project file {:?}", project_file))?;
+                .context("Failed to create project folder")?;
+        }
+
+        let target = DownloadTarget { dir: &project_path, file_format, metadata_filter, cluster_id: Some(&cluster.id) };
+        project.save_to(target, report).await?;
+        for prtb in &prtbs {
+            prtb.save_to(target, report).await?;
+        }
+    }
+
+    // only creates the folder when there is something to put in it
+    let crtb_path = cluster_path.join(CRTB_FOLDER);
+    let crtbs = DownloadTarget { dir: &crtb_path, file_format, metadata_filter, cluster_id: Some(&cluster.id) };
+    ClusterRoleTemplateBinding::download_all(configuration, Some(&cluster.id), retry_policy, |_| true, crtbs, report).await?;
+
+    Ok(())
+}
+
+/// The project role template bindings of an endpoint a download writes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrtbSource {
+    /// Listed per project while downloading it
+    PerProject,
+    /// Listed up front across all namespaces, keyed by project ID
+    Listed(HashMap<String, Vec<IoCattleManagementv3ProjectRoleTemplateBinding>>),
+}
+
+/// The number of bindings requested per page when listing them across all namespaces.
+const PRTB_PAGE_SIZE: i32 = 500;
 
-            let rancher_prtbs = get_namespaced_project_role_template_bindings(
+/// List the project role template bindings of the endpoint up front if `listing` is
+/// [`PrtbListing::AllNamespaces`], in pages of [`PRTB_PAGE_SIZE`].
+///
+/// Falls back to [`PrtbSource::PerProject`] when the account may not list bindings across
+/// all namespaces.
+///
+/// # Errors
+/// Returns an error if a page cannot be listed for any other reason
+#[async_backtrace::framed]
+pub async fn list_prtbs(
+    configuration: &Configuration,
+    listing: PrtbListing,
+    retry_policy: &RetryPolicy,
+) -> Result<PrtbSource> {
+    if listing == PrtbListing::PerProject {
+        return Ok(PrtbSource::PerProject);
+    }
+
+    let mut by_project: HashMap<String, Vec<_>> = HashMap::new();
+    let mut continue_token: Option<String> = None;
+    loop {
+        let page = retry_transient("get_all_project_role_template_bindings", retry_policy, || {
+            get_all_project_role_template_bindings(
                 configuration,
-                &project.id.clone().unwrap(),
-                None,
-                None,
                 None,
                 None,
+                Some(PRTB_PAGE_SIZE),
                 None,
                 None,
+                continue_token.as_deref(),
             )
-            .await
-            .context("Failed to get project role template bindings")?;
+        })
+        .await;
+        let page = match page {
+            Ok(page) => page,
+            Err(e) if is_forbidden(&e) => {
+                warn!("Cannot list project role template bindings across all namespaces, listing them per project");
+                return Ok(PrtbSource::PerProject);
+            }
+            Err(e) => return Err(e.context("Failed to get project role template bindings")),
+        };
+        for prtb in page.items {
+            match prtb.metadata.as_ref().and_then(|metadata| metadata.namespace.clone()) {
+                Some(project_id) => by_project.entry(project_id).or_default().push(prtb),
+                None => debug!("Skipping project role template binding without a namespace"),
+            }
+        }
+        continue_token = page
+            .metadata
+            .and_then(|metadata| metadata.r#continue)
+            .filter(|token| !token.is_empty());
+        if continue_token.is_none() {
+            break;
+        }
+    }
+    Ok(PrtbSource::Listed(by_project))
+}
 
-            let prtbs: Vec<ProjectRoleTemplateBinding> = rancher_prtbs
-                .items
-                .into_iter()
-                .map(|item| {
-                    item.try_into()
-                        .context("Failed to convert project role template binding")
-                })
-                .collect::<Result<_>>()?;
+/// The IDs of the cluster folders in `endpoint_path`, the folders holding a cluster file
+/// named after the folder or the cluster the folder is mapped to.
+///
+/// # Errors
+///
+/// Returns an error if `endpoint_path` exists but cannot be read
+pub async fn cluster_folders(endpoint_path: &Path, file_format: &FileFormat) -> Result<Vec<String>> {
+    if !endpoint_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let map = ClusterFolderMap::load(endpoint_path, file_format)?;
+    let mut entries = read_dir(endpoint_path)
+        .await
+        .with_context(|| format!("Failed to read endpoint folder {:?}", endpoint_path))?;
+    let mut cluster_ids = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let Ok(folder) = entry.file_name().into_string() else {
+            continue;
+        };
+        let cluster_id = map.cluster_id(&folder).unwrap_or(&folder).to_string();
+        // a folder holding the cluster file in any format, a file reads as no folder
+        let is_cluster = std::fs::read_dir(entry.path()).into_iter().flatten().flatten().any(|file| {
+            file.file_name()
+                .to_str()
+                .and_then(parse_object_file_name)
+                .is_some_and(|(object_type, object_id, _)| object_type == ObjectType::Cluster && object_id == cluster_id)
+        });
+        if is_cluster {
+            cluster_ids.push(cluster_id);
+        }
+    }
+    cluster_ids.sort();
+    Ok(cluster_ids)
+}
 
-            for prtb in &prtbs {
-                let prtb_file = project_path.join(get_file_name_for_object(&prtb.id, &ObjectType::ProjectRoleTemplateBinding, file_format));
-                write(&prtb_file, serialize_object(prtb, file_format)?)
-                    .await
-                    .with_context(|| format!("Failed to write PRTB file {:?}", prtb_file))?;
+/// Move the folders of the clusters whose display name changed in Rancher, see [`place_cluster_folder`].
+///
+/// Only clusters with a folder in `endpoint_path` are looked at.
+///
+/// # Returns
+/// The moved folders and the map file, to commit
+///
+/// # Errors
+/// Returns an error if the clusters cannot be listed or a folder cannot be moved
+#[async_backtrace::framed]
+pub async fn rename_cluster_folders(
+    configuration: &Configuration,
+    endpoint_path: &Path,
+    file_format: &FileFormat,
+    retry_policy: &RetryPolicy,
+) -> Result<Vec<PathBuf>> {
+    let clusters = retry_async(
+        "get_clusters",
+        retry_policy.max_retries.max(1),
+        retry_policy.delay,
+        || cluster::get_clusters(configuration, None, None, None, None, None, None),
+        is_transient_api_error,
+    )
+    .await
+    .context("Failed to get clusters")?;
+
+    let mut changed = Vec::new();
+    for item in clusters.items {
+        let cluster = Cluster::try_from(item).context("Failed to convert cluster")?;
+        if cluster_folder(endpoint_path, &cluster.id, file_format).exists() {
+            match place_cluster_folder(endpoint_path, &cluster, file_format) {
+                Ok((_, moved)) => changed.extend(moved),
+                Err(e) if is_unsafe_path(&e) => error!("Not moving the folder of cluster `{}`: {:#}", cluster.id, e),
+                Err(e) => return Err(e),
             }
         }
     }
+    Ok(changed)
+}
 
+/// Write a downloaded object, unless the file already there carries the ignore directive.
+///
+/// The file is recorded in `report` under `cluster_id`, or the role templates if `None`.
+pub(crate) async fn write_downloaded(
+    path: &Path,
+    contents: String,
+    report: &mut DownloadReport,
+    cluster_id: Option<&str>,
+) -> Result<()> {
+    if file_has_ignore_directive(path).await {
+        info!("Keeping {:?}, it carries the ignore directive", path);
+        report.record(cluster_id, path, true);
+        return Ok(());
+    }
+    // written in one go, a download cut short by its timeout never leaves half a file
+    write_atomically(path, &keep_comments(path, contents).await)?;
+    report.record(cluster_id, path, false);
     Ok(())
 }
 
+/// Remove the files a download cut short wrote, and the folders it leaves empty up to
+/// `endpoint_path`, so a half downloaded cluster is not taken for a complete one.
+pub async fn discard_download(endpoint_path: &Path, download: &DownloadReport) {
+    for path in &download.written {
+        if let Err(e) = ensure_within(endpoint_path, path) {
+            error!("Not removing {:?} of an unfinished download: {}", path, e);
+            continue;
+        }
+        if let Err(e) = tokio::fs::remove_file(path).await {
+            warn!("Failed to remove {:?} of an unfinished download: {}", path, e);
+        }
+        remove_empty_folders(endpoint_path, path).await;
+    }
+}
+
+/// Remove the object files of `index` a finished `download` of the whole endpoint neither
+/// wrote nor kept for the ignore directive, whose objects Rancher no longer has, and the
+/// folders they leave empty up to `endpoint_path`. The removed files are recorded in `download`.
+///
+/// `index` is built after the download, files it could not read an object from are left alone.
+pub async fn prune_download(endpoint_path: &Path, index: &RepoIndex, download: &mut DownloadReport) {
+    let mut stale: Vec<&Path> = index
+        .iter()
+        .map(|(_, path)| path)
+        .filter(|path| !download.written.iter().chain(&download.kept).any(|kept| kept == path))
+        .collect();
+    // a bundle holds several objects
+    stale.sort();
+    stale.dedup();
+    for path in stale {
+        if let Err(e) = ensure_within(endpoint_path, path) {
+            error!("Not removing {:?} of an object Rancher no longer has: {}", path, e);
+            continue;
+        }
+        match tokio::fs::remove_file(path).await {
+            Ok(()) => {
+                info!("Removed {:?}, Rancher no longer has its object", path);
+                download.pruned.push(path.to_path_buf());
+            }
+            Err(e) => warn!("Failed to remove {:?} of an object Rancher no longer has: {}", path, e),
+        }
+        remove_empty_folders(endpoint_path, path).await;
+    }
+}
+
+/// Remove the folders of the removed file at `path` that are left empty, up to `endpoint_path`.
+async fn remove_empty_folders(endpoint_path: &Path, path: &Path) {
+    let mut folder = path.parent();
+    while let Some(dir) = folder.filter(|dir| dir.starts_with(endpoint_path) && *dir != endpoint_path) {
+        // only succeeds once the folder is empty
+        if tokio::fs::remove_dir(dir).await.is_err() {
+            break;
+        }
+        folder = dir.parent();
+    }
+}
+
+/// Run `future` as the phase `phase` of a run, cut short once it takes longer than `limit`.
+///
+/// Returns `None` if it was cut short, which is logged and recorded in `report`. A phase is
+/// only cut at one of its `.await`s, files it writes are written atomically.
+pub async fn within_timeout<T>(
+    phase: &str,
+    limit: Option<Duration>,
+    report: &mut SyncReport,
+    future: impl Future<Output = T>,
+) -> Option<T> {
+    let Some(limit) = limit else {
+        return Some(future.await);
+    };
+    match tokio::time::timeout(limit, future).await {
+        Ok(output) => Some(output),
+        Err(_) => {
+            error!("The {} phase timed out after {:?}, the run goes on without it", phase, limit);
+            report.record_timeout(phase);
+            None
+        }
+    }
+}
+
+/// The objects of an endpoint that are not tied to a cluster, listed once per run and
+/// shared by the clusters of the run.
+#[derive(Debug, Default)]
+pub struct RemoteCache {
+    role_templates: tokio::sync::OnceCell<Vec<IoCattleManagementv3RoleTemplate>>,
+}
+
+impl RemoteCache {
+    /// The role templates of the endpoint, listed on first use.
+    ///
+    /// # Errors
+    /// Returns an error if the role templates cannot be listed, the next call tries again
+    pub async fn role_templates(
+        &self,
+        configuration: &Configuration,
+        retry_policy: &RetryPolicy,
+    ) -> Result<&[IoCattleManagementv3RoleTemplate]> {
+        self.role_templates
+            .get_or_try_init(|| async {
+                retry_transient("get_role_templates", retry_policy, || {
+                    get_role_templates(configuration, None, None, None, None, None, None)
+                })
+                .await
+                .map(|list| list.items)
+                .context("Failed to get role templates")
+            })
+            .await
+            .map(Vec::as_slice)
+    }
+}
+
+/// Write the role template index of the endpoint in `endpoint_path` from the role templates
+/// `role_template_filter` accepts, unless it is unchanged or the endpoint has no `roles` folder.
+///
+/// # Returns
+/// The path of the index if it was written
+///
+/// # Errors
+/// Returns an error if the role templates cannot be listed or converted, or the index cannot be written
+pub async fn refresh_role_template_index(
+    configuration: &Configuration,
+    endpoint_path: &Path,
+    file_format: &FileFormat,
+    role_template_filter: &RoleTemplateFilter,
+    remote_cache: &RemoteCache,
+    retry_policy: &RetryPolicy,
+) -> Result<Option<PathBuf>> {
+    let role_template_path = endpoint_path.join("roles");
+    if !role_template_path.is_dir() {
+        return Ok(None);
+    }
+    let role_templates = remote_cache
+        .role_templates(configuration, retry_policy)
+        .await?
+        .iter()
+        .filter(|role_template| role_template_filter.includes(role_template))
+        .map(|role_template| RoleTemplate::try_from_api(role_template.clone()))
+        .collect::<Result<Vec<_>>>()?;
+    RoleTemplateIndex::new(&role_templates).write(&role_template_path, file_format)
+}
+
     /// Loads the current configuration of the specified cluster from the Rancher API.
     ///
     /// # Arguments
     /// * `configuration`: The configuration object to use for connecting to Rancher
     /// * `cluster_id`: The ID of the cluster to load the configuration for
+    /// * `remote_cache`: The role templates of the endpoint, listed once per run
+    /// * `retry_policy`: How list calls failing with a transient network error are retried
     ///
     /// # Returns
     /// `RancherClusterConfig`: The loaded configuration
@@ -225,11 +676,19 @@ pub async fn download_current_configuration(
 pub async fn load_configuration_from_rancher(
     configuration: &Configuration,
     cluster_id: &str,
+    remote_cache: &RemoteCache,
+    retry_policy: &RetryPolicy,
 ) -> Result<RancherClusterConfig> {
     // Get the current configuration from the Rancher API
-    let rancher_clusters = cluster::get_clusters(configuration)
-        .await
-        .context("Failed to get clusters")?;
+    let rancher_clusters = retry_async(
+        "get_clusters",
+        retry_policy.max_retries.max(1),
+        retry_policy.delay,
+        || cluster::get_clusters(configuration, None, None, None, None, None, None),
+        is_transient_api_error,
+    )
+    .await
+    .context("Failed to get clusters")?;
 
     let rancher_cluster = rancher_clusters
         .items
@@ -239,30 +698,35 @@ pub async fn load_configuration_from_rancher(
         })
         .ok_or_else(|| anyhow::anyhow!("Cluster with id '{}' not found", cluster_id))?;
 
-    let rancher_role_templates =
-        get_role_templates(configuration, None, None, None, None, None, None)
-            .await
-            .context("Failed to get role templates")?;
-
-    let rrt: Vec<IoCattleManagementv3RoleTemplate> = rancher_role_templates.items.clone();
-
-    let rancher_projects = get_projects(
-        configuration,
-        cluster_id,
-        None,
-        None,
-        None,
-        None,
-        None,
-        None,
-    )
+    let rrt: Vec<IoCattleManagementv3RoleTemplate> =
+        remote_cache.role_templates(configuration, retry_policy).await?.to_vec();
+
+    let rancher_projects = retry_transient("get_projects", retry_policy, || {
+        get_projects(configuration, cluster_id, None, None, None, None, None, None)
+    })
     .await
     .context("Failed to get projects")?;
 
+    let rancher_crtbs = retry_transient("get_cluster_role_template_bindings", retry_policy, || {
+        get_namespaced_cluster_role_template_bindings(
+            configuration,
+            cluster_id,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    })
+    .await
+    .context("Failed to get cluster role template bindings")?;
+
     let mut rancher_cluster_config = RancherClusterConfig {
         cluster: rancher_cluster,
         role_templates: rrt,
         projects: HashMap::new(),
+        crtbs: rancher_crtbs.items,
     };
 
     let rprojects: Vec<IoCattleManagementv3Project> = rancher_projects.items.clone();
@@ -276,16 +740,18 @@ pub async fn load_configuration_from_rancher(
             .ok_or_else(|| anyhow::anyhow!("Project missing metadata name"))?;
 
         let rancher_project_role_template_bindings =
-            get_namespaced_project_role_template_bindings(
-                configuration,
-                project_id,
-                None,
-                None,
-                None,
-                None,
-                None,
-                None,
-            )
+            retry_transient("get_project_role_template_bindings", retry_policy, || {
+                get_namespaced_project_role_template_bindings(
+                    configuration,
+                    project_id,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+            })
             .await
             .context(format!(
                 "Failed to get project role template bindings for project '{}'",
@@ -321,6 +787,8 @@ pub async fn load_configuration_from_rancher(
     /// * `endpoint_url`: The URL of the Rancher server
     /// * `cluster_id`: The ID of the cluster to load the configuration for
     /// * `file_format`: The file format of the configuration file
+    /// * `correct_misplaced_objects`: Move projects and bindings naming another cluster than
+    ///   their folder to the cluster of the folder instead of failing them
     ///
     /// # Examples
     ///
@@ -330,121 +798,666 @@ pub async fn load_configuration(
     endpoint_url: &str,
     cluster_id: &str,
     file_format: &FileFormat,
+    correct_misplaced_objects: bool,
 ) -> Result<Option<ClusterConfig>> {
-    let endpoint_path = path.join(endpoint_url.replace("https://", "").replace("/", "_"));
+    load_cached_configuration(path, endpoint_url, cluster_id, file_format, correct_misplaced_objects, &ParseCache::default()).await
+}
+
+/// [`load_configuration`] taking the objects of files unchanged since they were last parsed from `cache`.
+pub async fn load_cached_configuration(
+    path: &Path,
+    endpoint_url: &str,
+    cluster_id: &str,
+    file_format: &FileFormat,
+    correct_misplaced_objects: bool,
+    cache: &ParseCache,
+) -> Result<Option<ClusterConfig>> {
+    let endpoint_path = endpoint_folder(path, endpoint_url);
     if !endpoint_path.exists() {
         bail!("Configuration path does not exist: {:?}", endpoint_path);
     }
 
-    let cluster_folder_path = endpoint_path.join(cluster_id);
+    let cluster_folder_path = cluster_folder(&endpoint_path, cluster_id, file_format);
     if !cluster_folder_path.exists() {
         bail!("Cluster path does not exist: {:?}", cluster_folder_path);
     }
 
+    let role_templates = load_role_templates(&endpoint_path, file_format, cache).await?;
+    let cluster_config =
+        load_cluster_folder(&cluster_folder_path, cluster_id, role_templates, file_format, correct_misplaced_objects, cache).await?;
+    Ok(Some(cluster_config))
+}
+
+/// The role templates in the `roles` folder of `endpoint_path` and the errors of the files
+/// that could not be read.
+async fn load_role_templates(
+    endpoint_path: &Path,
+    file_format: &FileFormat,
+    cache: &ParseCache,
+) -> Result<(Vec<RoleTemplate>, Vec<ConversionError>)> {
+    let role_template_path = endpoint_path.join("roles");
+    if !role_template_path.exists() {
+        bail!("Role template path does not exist: {:?}", role_template_path);
+    }
+
+    let mut role_templates = Vec::new();
+    let mut malformed = Vec::new();
+    let mut rd = read_dir(&role_template_path).await?;
+    while let Some(entry) = rd.next_entry().await? {
+        if entry.file_type().await?.is_file() && is_object_file(&entry.path(), ObjectType::RoleTemplate, file_format) {
+            let Some(content) = read_or_record(&entry.path(), &mut malformed).await? else {
+                continue;
+            };
+            role_templates.extend(parse_or_record::<RoleTemplate>(
+                &content,
+                &entry.path(),
+                file_format,
+                cache,
+                &mut malformed,
+            ));
+        }
+    }
+    Ok((role_templates, malformed))
+}
+
+/// The configuration of the cluster in `cluster_folder_path` with the role templates of its
+/// endpoint, loaded by [`load_role_templates`].
+async fn load_cluster_folder(
+    cluster_folder_path: &Path,
+    cluster_id: &str,
+    (role_templates, malformed): (Vec<RoleTemplate>, Vec<ConversionError>),
+    file_format: &FileFormat,
+    correct_misplaced_objects: bool,
+    cache: &ParseCache,
+) -> Result<ClusterConfig> {
     let extension = file_extension_from_format(file_format);
     let cluster_file = cluster_folder_path.join(format!("{}.cluster.{}", cluster_id, extension));
     if !cluster_file.exists() {
         bail!("Cluster file does not exist: {:?}", cluster_file);
     }
 
-    // info!("Loading cluster configuration from file: {:?}", cluster_file);
     info!(path = %cluster_file.display(), "Reading cluster file");
+    let cluster_file_size = tokio::fs::metadata(&cluster_file).await?.len();
+    file_limits().check_size(&cluster_file, cluster_file_size)?;
     let cluster_file_content = read_to_string(&cluster_file)
         .await
         .with_context(|| format!("Failed to read cluster file: {:?}", cluster_file))?;
-    let cluster: Cluster = deserialize_object(&cluster_file_content, file_format)
-        .with_context(|| format!("Failed to deserialize cluster file: {:?}", cluster_file))?;
+    let cluster: Cluster = cache.parse(&cluster_file_content, file_format, || {
+        deserialize_object(&cluster_file_content, file_format, Some(&cluster_file))
+    })?;
 
     let mut cluster_config = ClusterConfig {
-        cluster: cluster.clone(),
-        role_templates: Vec::new(),
+        cluster,
+        role_templates,
         projects: std::collections::HashMap::new(),
+        crtbs: Vec::new(),
+        malformed,
     };
 
-    // Read role templates
-    let role_template_path = endpoint_path.join("roles");
-    if !role_template_path.exists() {
-        bail!("Role template path does not exist: {:?}", role_template_path);
-    }
-
-    let mut role_templates = Vec::new();
-    let mut rd = read_dir(&role_template_path).await?;
-    while let Some(entry) = rd.next_entry().await? {
-        if entry.file_type().await?.is_file() {
-            let rt_file_name = entry.file_name();
-            let file_name = rt_file_name.to_string_lossy();
-            if file_name.ends_with(&format!(".rt.{}", extension)) {
-                let content = read_to_string(entry.path()).await?;
-                let role_template: RoleTemplate = deserialize_object(&content, file_format)?;
-                role_templates.push(role_template);
+    // Read cluster role template bindings, the folder only exists if the cluster has any
+    let crtb_folder_path = cluster_folder_path.join(CRTB_FOLDER);
+    if crtb_folder_path.exists() {
+        let mut rd = read_dir(&crtb_folder_path).await?;
+        while let Some(entry) = rd.next_entry().await? {
+            if entry.file_type().await?.is_file()
+                && is_object_file(&entry.path(), ObjectType::ClusterRoleTemplateBinding, file_format)
+            {
+                let Some(content) = read_or_record(&entry.path(), &mut cluster_config.malformed).await? else {
+                    continue;
+                };
+                let crtb = parse_or_record::<ClusterRoleTemplateBinding>(
+                    &content,
+                    &entry.path(),
+                    file_format,
+                    cache,
+                    &mut cluster_config.malformed,
+                );
+                cluster_config.crtbs.extend(
+                    crtb.and_then(|crtb| placed_or_record(crtb, &entry.path(), cluster_id, correct_misplaced_objects, &mut cluster_config.malformed)),
+                );
             }
         }
     }
-    cluster_config.role_templates = role_templates;
 
-    // Read projects
+    // Read projects, from their folder or their bundle
     let mut rd = read_dir(&cluster_folder_path).await?;
     while let Some(entry) = rd.next_entry().await? {
-        if entry.file_type().await?.is_dir() {
+        let bundle_file = entry.path();
+        if let (true, Some(project_id)) = (entry.file_type().await?.is_file(), bundle_project_id(&bundle_file)) {
+            info!("Loading project bundle from file: {:?}", bundle_file);
+            let Some(content) = read_or_record(&bundle_file, &mut cluster_config.malformed).await? else {
+                continue;
+            };
+            match cache.parse(&content, file_format, || read_bundle(&content, &bundle_file)) {
+                Ok((project, prtbs)) => {
+                    let malformed = &mut cluster_config.malformed;
+                    let Some(project) = placed_or_record(project, &bundle_file, cluster_id, correct_misplaced_objects, malformed) else {
+                        continue;
+                    };
+                    let prtbs = prtbs
+                        .into_iter()
+                        .filter_map(|prtb| placed_or_record(prtb, &bundle_file, cluster_id, correct_misplaced_objects, malformed))
+                        .collect();
+                    cluster_config.projects.insert(project_id.to_string(), (project, prtbs));
+                }
+                Err(e) => cluster_config.malformed.push(e),
+            }
+            continue;
+        }
+        if entry.file_type().await?.is_dir() && entry.file_name() != CRTB_FOLDER {
             let project_folder_path = entry.path();
             let project_id = entry.file_name().to_string_lossy().to_string();
 
             // Look for project file with new naming convention
             let project_file = project_folder_path.join(format!("{}.project.{}", project_id, extension));
             if project_file.exists() {
+                // a folder flooded with files is skipped whole, its bindings would be incomplete
+                let mut file_count = 0;
+                let mut prd = read_dir(&project_folder_path).await?;
+                while prd.next_entry().await?.is_some() {
+                    file_count += 1;
+                }
+                if let Err(e) = file_limits().check_count(&project_folder_path, file_count) {
+                    cluster_config.malformed.push(e);
+                    continue;
+                }
+
                 info!("Loading project configuration from file: {:?}", project_file);
-                let content = read_to_string(&project_file).await
-                    .with_context(|| format!("Failed to read project file: {:?}", project_file))?;
-                let project: Project = deserialize_object(&content, file_format)
-                    .with_context(|| format!("Failed to deserialize project file: {:?}", project_file))?;
+                let Some(content) = read_or_record(&project_file, &mut cluster_config.malformed).await? else {
+                    continue;
+                };
+                // the bindings are left out with their project, the project is not compared
+                let Some(project) =
+                    parse_or_record::<Project>(&content, &project_file, file_format, cache, &mut cluster_config.malformed)
+                        .and_then(|project| {
+                            placed_or_record(project, &project_file, cluster_id, correct_misplaced_objects, &mut cluster_config.malformed)
+                        })
+                else {
+                    continue;
+                };
 
                 // Read PRTBs
                 let mut prtbs = Vec::new();
                 let mut prd = read_dir(&project_folder_path).await?;
                 while let Some(prtb_entry) = prd.next_entry().await? {
-                    if prtb_entry.file_type().await?.is_file() {
-                        let prtb_file_name = prtb_entry.file_name();
-                        let file_name = prtb_file_name.to_string_lossy();
-                        if file_name.ends_with(&format!(".prtb.{}", extension)) {
-                            let content = read_to_string(prtb_entry.path()).await
-                                .with_context(|| format!("Failed to read PRTB file: {:?}", prtb_entry.path()))?;
-                            let prtb: ProjectRoleTemplateBinding = deserialize_object(&content, file_format)
-                                .with_context(|| format!("Failed to deserialize PRTB file: {:?}", prtb_entry.path()))?;
-                            prtbs.push(prtb);
-                        }
+                    if prtb_entry.file_type().await?.is_file()
+                        && is_object_file(&prtb_entry.path(), ObjectType::ProjectRoleTemplateBinding, file_format)
+                    {
+                        let Some(content) = read_or_record(&prtb_entry.path(), &mut cluster_config.malformed).await?
+                        else {
+                            continue;
+                        };
+                        let prtb = parse_or_record::<ProjectRoleTemplateBinding>(
+                            &content,
+                            &prtb_entry.path(),
+                            file_format,
+                            cache,
+                            &mut cluster_config.malformed,
+                        );
+                        prtbs.extend(prtb.and_then(|prtb| {
+                            placed_or_record(prtb, &prtb_entry.path(), cluster_id, correct_misplaced_objects, &mut cluster_config.malformed)
+                        }));
                     }
                 }
 
                 cluster_config.projects.insert(project_id, (project, prtbs));
             } else {
-                warn!("Project file not found: {:?}", project_file);
+                warn_repeated(
+                    "Project file not found",
+                    project_file.display(),
+                    format_args!("Project file not found: {:?}", project_file),
+                );
             }
         }
     }
 
-    Ok(Some(cluster_config))
+    Ok(cluster_config)
 }
 
+/// The stored configuration of the clusters of a run, loaded once at its start and handed
+/// to the compare of each cluster.
+///
+/// The role templates are read once for every cluster, and files unchanged since the last
+/// run are taken from the [`ParseCache`] rather than parsed again. A cluster whose files are
+/// written during the run, see [`StoredConfigs::invalidate`], is loaded again when taken.
+#[derive(Debug)]
+pub struct StoredConfigs {
+    config_folder_path: PathBuf,
+    endpoint_url: String,
+    file_format: FileFormat,
+    cache: Arc<ParseCache>,
+    role_template_path: PathBuf,
+    correct_misplaced_objects: bool,
+    /// The loaded clusters by ID, with their folder.
+    clusters: HashMap<String, (PathBuf, ClusterConfig)>,
+}
 
-/// Recursively remove fields from a JSON Value based on a list of dot-separated paths.
-/// # Arguments
-/// * `value` - The mutable JSON object to clean
-/// * `exclude_paths` - A list of dot-separated paths to remove (e.g., ["status", "metadata.creationTimestamp"])
-pub fn clean_up_value(value: &mut Value, exclude_paths: &[&str]) {
-    for path in exclude_paths {
-        let parts: Vec<&str> = path.split('.').collect();
-        remove_path_and_return(value, &parts);
+impl StoredConfigs {
+    /// Load the stored configuration of `cluster_ids`, the clusters `index` holds a cluster
+    /// file for.
+    ///
+    /// A cluster that fails to load is left out, taking it loads it again and returns the error.
+    ///
+    /// # Arguments
+    /// * `config_folder_path` - The path to the folder containing the stored configuration
+    /// * `endpoint_url` - The URL of the Rancher server
+    /// * `index` - The object files of the endpoint folder
+    /// * `cluster_ids` - The clusters of the run
+    /// * `file_format` - The file format of the configuration files
+    /// * `correct_misplaced_objects` - Move projects and bindings naming another cluster than
+    ///   their folder to the cluster of the folder instead of failing them
+    /// * `cache` - The objects parsed by earlier runs, kept across runs
+    pub async fn load(
+        config_folder_path: &Path,
+        endpoint_url: &str,
+        index: &RepoIndex,
+        cluster_ids: &[String],
+        file_format: &FileFormat,
+        correct_misplaced_objects: bool,
+        cache: Arc<ParseCache>,
+    ) -> Self {
+        cache.start_run();
+        let endpoint_path = endpoint_folder(config_folder_path, endpoint_url);
+        let mut stored = Self {
+            config_folder_path: config_folder_path.to_path_buf(),
+            endpoint_url: endpoint_url.to_string(),
+            file_format: *file_format,
+            cache,
+            role_template_path: endpoint_path.join("roles"),
+            correct_misplaced_objects,
+            clusters: HashMap::new(),
+        };
+        let role_templates = match load_role_templates(&endpoint_path, file_format, &stored.cache).await {
+            Ok(role_templates) => role_templates,
+            Err(e) => {
+                debug!("Not loading the stored configurations up front: {:#}", e);
+                return stored;
+            }
+        };
+        let parsed = stored.cache.parsed();
+        for cluster_id in cluster_ids {
+            if index.path(ObjectType::Cluster, None, cluster_id).is_none() {
+                continue;
+            }
+            let cluster_folder_path = cluster_folder(&endpoint_path, cluster_id, file_format);
+            let loaded = load_cluster_folder(
+                &cluster_folder_path,
+                cluster_id,
+                role_templates.clone(),
+                file_format,
+                correct_misplaced_objects,
+                &stored.cache,
+            );
+            match loaded.await {
+                Ok(cluster_config) => {
+                    stored.clusters.insert(cluster_id.clone(), (cluster_folder_path, cluster_config));
+                }
+                Err(e) => debug!("Not loading cluster `{}` up front: {:#}", cluster_id, e),
+            }
+        }
+        debug!(
+            "Loaded the stored configuration of {} clusters, parsing {} files",
+            stored.clusters.len(),
+            stored.cache.parsed() - parsed
+        );
+        stored
     }
-}
 
-/// Remove a deeply nested field from a JSON object and remove it.
-/// Traverses objects by key. Returns `None` if any key is missing or the path is invalid.
-fn remove_path_and_return(value: &mut Value, path: &[&str]) -> Option<Value> {
-    if path.is_empty() {
-        return None;
+    /// Take the stored configuration of `cluster_id`, loading it again if it was not loaded
+    /// up front or its files were written since.
+    ///
+    /// # Errors
+    /// If the configuration of the cluster cannot be loaded
+    pub async fn take(&mut self, cluster_id: &str) -> Result<Option<ClusterConfig>> {
+        if let Some((_, cluster_config)) = self.clusters.remove(cluster_id) {
+            return Ok(Some(cluster_config));
+        }
+        load_cached_configuration(
+            &self.config_folder_path,
+            &self.endpoint_url,
+            cluster_id,
+            &self.file_format,
+            self.correct_misplaced_objects,
+            &self.cache,
+        )
+        .await
+            .context("Failed to load stored configuration")
     }
 
-    let mut current = value;
+    /// Drop the clusters holding one of `paths` after they were written, every cluster if one
+    /// is a role template.
+    pub fn invalidate(&mut self, paths: &[PathBuf]) {
+        if paths.iter().any(|path| path.starts_with(&self.role_template_path)) {
+            self.clusters.clear();
+            return;
+        }
+        self.clusters
+            .retain(|_, (cluster_folder_path, _)| !paths.iter().any(|path| path.starts_with(&*cluster_folder_path)));
+    }
+
+    /// Whether the configuration of `cluster_id` is loaded and can be taken as is.
+    pub fn is_loaded(&self, cluster_id: &str) -> bool {
+        self.clusters.contains_key(cluster_id)
+    }
+}
+
+/// Checks that no two projects of `cluster_id` share a display name, counting the project
+/// files in the repository and the projects Rancher lists for the cluster.
+///
+/// Project files that cannot be parsed are left out, loading them reports the error.
+///
+/// # Arguments
+/// * `configuration`: The configuration object to use for connecting to Rancher
+/// * `index`: The object files of the endpoint folder holding the cluster folder
+/// * `cluster_id`: The ID of the cluster to check
+/// * `retry_policy`: How the project list call is retried on transient network errors
+///
+/// # Returns
+/// The violations found, one per shared display name
+#[async_backtrace::framed]
+pub async fn check_project_display_names(
+    configuration: &Configuration,
+    index: &RepoIndex,
+    cluster_id: &str,
+    retry_policy: &RetryPolicy,
+) -> Result<Vec<Violation>> {
+    let mut files = Vec::new();
+    for path in index.files(ObjectType::Project, Some(cluster_id)) {
+        match load_object::<Project>(path).await {
+            Ok(project) => files.push((path.to_path_buf(), project)),
+            Err(e) => debug!("Not checking {:?}: {}", path, e),
+        }
+    }
+
+    let remote: Vec<Project> = retry_transient("get_projects", retry_policy, || {
+        get_projects(configuration, cluster_id, None, None, None, None, None, None)
+    })
+    .await
+    .context("Failed to get projects")?
+    .items
+    .into_iter()
+    .map(|item| item.try_into().context("Failed to convert project"))
+    .collect::<Result<_>>()?;
+
+    Ok(duplicate_project_display_names(&files, &remote))
+}
+
+/// Checks the project role template bindings in `new_files`, the ones this run would create,
+/// for bindings granting the same role template to the same subject in their project, in
+/// Rancher or in another new file.
+///
+/// Binding files that cannot be parsed are left out, creating them reports the error.
+///
+/// # Arguments
+/// * `configuration`: The configuration object to use for connecting to Rancher
+/// * `new_files`: The new object files of the cluster
+/// * `principal_rules`: How the principal names of the bindings are compared
+/// * `retry_policy`: How the binding list calls are retried on transient network errors
+///
+/// # Returns
+/// The violations found, one per duplicate file
+#[async_backtrace::framed]
+pub async fn check_duplicate_bindings(
+    configuration: &Configuration,
+    new_files: &[(ObjectType, PathBuf)],
+    principal_rules: &PrincipalRules,
+    retry_policy: &RetryPolicy,
+) -> Result<Vec<Violation>> {
+    let mut files = Vec::new();
+    for (object_type, path) in new_files {
+        if *object_type != ObjectType::ProjectRoleTemplateBinding {
+            continue;
+        }
+        match load_object::<ProjectRoleTemplateBinding>(path).await {
+            Ok(prtb) => files.push((path.clone(), prtb)),
+            Err(e) => debug!("Not checking {:?}: {}", path, e),
+        }
+    }
+
+    let project_ids: BTreeSet<&str> = files.iter().map(|(_, prtb)| prtb.namespace.as_str()).collect();
+    let mut remote = Vec::new();
+    for project_id in project_ids {
+        let prtbs = retry_transient("get_project_role_template_bindings", retry_policy, || {
+            get_namespaced_project_role_template_bindings(configuration, project_id, None, None, None, None, None, None)
+        })
+        .await
+        .with_context(|| format!("Failed to get project role template bindings for project '{}'", project_id))?;
+        for prtb in prtbs.items {
+            remote.push(ProjectRoleTemplateBinding::try_from(prtb).context("Failed to convert project role template binding")?);
+        }
+    }
+
+    Ok(duplicate_bindings(&files, &remote, principal_rules))
+}
+
+/// Checks the bindings of `cluster_id` in `new_files`, the ones this run would create, against
+/// `policy`.
+///
+/// Binding files that cannot be parsed are left out, creating them reports the error. The
+/// project of a binding is looked up in `index` for its display name.
+///
+/// # Returns
+/// The violations found, one per binding
+pub async fn check_binding_policy(
+    policy: &BindingPolicy,
+    index: &RepoIndex,
+    cluster_id: &str,
+    new_files: &[(ObjectType, PathBuf)],
+) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for (object_type, path) in new_files {
+        let (project, role_template_name, subjects) = match object_type {
+            ObjectType::ProjectRoleTemplateBinding => match load_object::<ProjectRoleTemplateBinding>(path).await {
+                Ok(prtb) => {
+                    let project = match index.path(ObjectType::Project, Some(cluster_id), &prtb.namespace) {
+                        Some(project_path) => load_object::<Project>(project_path).await.ok(),
+                        None => None,
+                    };
+                    let subjects = [prtb.user_name, prtb.user_principal_name, prtb.group_name, prtb.group_principal_name, prtb.service_account];
+                    (Some((prtb.namespace, project.map(|project| project.display_name))), prtb.role_template_name, subjects)
+                }
+                Err(e) => {
+                    debug!("Not checking {:?}: {}", path, e);
+                    continue;
+                }
+            },
+            ObjectType::ClusterRoleTemplateBinding => match load_object::<ClusterRoleTemplateBinding>(path).await {
+                Ok(crtb) => {
+                    let subjects = [crtb.user_name, crtb.user_principal_name, crtb.group_name, crtb.group_principal_name, None];
+                    (None, crtb.role_template_name, subjects)
+                }
+                Err(e) => {
+                    debug!("Not checking {:?}: {}", path, e);
+                    continue;
+                }
+            },
+            _ => continue,
+        };
+        let binding = Binding {
+            cluster_id,
+            // a project without a file has no display name to match
+            project: project.as_ref().map(|(id, display_name)| BoundProject {
+                id,
+                display_name: display_name.as_deref().unwrap_or_default(),
+            }),
+            role_template_name: &role_template_name,
+            subjects: subjects.iter().flatten().map(String::as_str).collect(),
+        };
+        if let Some(reason) = policy.check(&binding) {
+            violations.push(Violation {
+                rule: "binding-policy",
+                message: format!("{}: {}", path.display(), reason),
+                paths: vec![path.clone()],
+            });
+        }
+    }
+    violations
+}
+
+/// Checks the rules of the role templates in `new_files`, the ones this run would create, and
+/// the inheritance of every role template file in `index`.
+///
+/// The role templates inherited from must exist in `index` or in Rancher, builtin ones included,
+/// and must not inherit back from the one inheriting them. Role template files that cannot be
+/// parsed are left out, creating them reports the error. If the role templates of Rancher cannot
+/// be listed only the files in `index` count as existing.
+///
+/// # Returns
+/// The violations found, one per problem
+pub async fn check_role_templates(
+    configuration: &Configuration,
+    index: &RepoIndex,
+    new_files: &[(ObjectType, PathBuf)],
+    remote_cache: &RemoteCache,
+    retry_policy: &RetryPolicy,
+) -> Vec<Violation> {
+    let mut graph = InheritanceGraph::default();
+    match remote_cache.role_templates(configuration, retry_policy).await {
+        Ok(remote) => {
+            for rt in remote {
+                if let Some(name) = rt.metadata.as_ref().and_then(|m| m.name.as_deref()) {
+                    graph.insert(name, rt.role_template_names.as_deref().unwrap_or_default());
+                }
+            }
+        }
+        Err(e) => warn!("Checking inherited role templates against the repository only: {:#}", e),
+    }
+
+    let mut violations = Vec::new();
+    let mut files = HashMap::new();
+    for path in index.files(ObjectType::RoleTemplate, None) {
+        let role_template = match load_object::<RoleTemplate>(path).await {
+            Ok(role_template) => role_template,
+            Err(e) => {
+                debug!("Not checking {:?}: {}", path, e);
+                continue;
+            }
+        };
+        graph.insert(role_template.id.as_str(), role_template.role_template_names.as_deref().unwrap_or_default());
+        if new_files.iter().any(|(object_type, new)| *object_type == ObjectType::RoleTemplate && new == path) {
+            violations.extend(role_template_errors(&role_template).into_iter().map(|error| Violation {
+                rule: "role-template-rules",
+                message: format!("{}: {}", path.display(), error),
+                paths: vec![path.to_path_buf()],
+            }));
+        }
+        files.insert(role_template.id, path.to_path_buf());
+    }
+
+    for (id, name) in graph.missing() {
+        if let Some(path) = files.get(id) {
+            violations.push(Violation {
+                rule: "role-template-inheritance",
+                message: format!("{}: role_template_names: role template `{}` does not exist", path.display(), name),
+                paths: vec![path.clone()],
+            });
+        }
+    }
+    for cycle in graph.cycles() {
+        let paths: Vec<PathBuf> = cycle.iter().filter_map(|id| files.get(*id).cloned()).collect();
+        if !paths.is_empty() {
+            violations.push(Violation {
+                rule: "role-template-inheritance",
+                message: format!("role templates inherit from each other: {}", cycle.join(" -> ")),
+                paths,
+            });
+        }
+    }
+    violations
+}
+
+
+/// Move the project files of `cluster_id` whose project Rancher recreated under a new ID,
+/// matched by display name, see [`match_regenerated_projects`].
+///
+/// `index` is updated with the moved files.
+///
+/// # Arguments
+/// * `configuration`: The configuration object to use for connecting to Rancher
+/// * `index`: The object files of the endpoint folder holding the cluster folder
+/// * `cluster_id`: The ID of the cluster to reconcile
+/// * `file_format`: The format the moved files are written in
+/// * `retry_policy`: How the project list call is retried on transient network errors
+///
+/// # Returns
+/// The old and the new folders of the moved projects, to commit
+#[async_backtrace::framed]
+pub async fn reconcile_project_ids(
+    configuration: &Configuration,
+    index: &mut RepoIndex,
+    cluster_id: &str,
+    file_format: &FileFormat,
+    retry_policy: &RetryPolicy,
+) -> Result<Vec<PathBuf>> {
+    let mut stored = Vec::new();
+    let mut files = HashMap::new();
+    for path in index.files(ObjectType::Project, Some(cluster_id)) {
+        if is_bundle(path) {
+            debug!("Not matching {:?}, bundles are not moved", path);
+            continue;
+        }
+        match load_object::<Project>(path).await {
+            Ok(project) => {
+                if let Some(id) = &project.id {
+                    files.insert(id.clone(), path.to_path_buf());
+                }
+                stored.push(project);
+            }
+            Err(e) => debug!("Not matching {:?}: {}", path, e),
+        }
+    }
+
+    let live: Vec<Project> = retry_transient("get_projects", retry_policy, || {
+        get_projects(configuration, cluster_id, None, None, None, None, None, None)
+    })
+    .await
+    .context("Failed to get projects")?
+    .items
+    .into_iter()
+    .map(|item| item.try_into().context("Failed to convert project"))
+    .collect::<Result<_>>()?;
+
+    let mut moved = Vec::new();
+    for (old_id, new_id) in match_regenerated_projects(&stored, &live) {
+        let project_file = &files[&old_id];
+        match move_project(project_file, &new_id, file_format).await {
+            Ok(folders) => {
+                for folder in &folders {
+                    index.remove_under(folder);
+                }
+                if let Some(new_folder) = folders.last() {
+                    for entry in std::fs::read_dir(new_folder)?.flatten() {
+                        index.refresh(&entry.path()).await;
+                    }
+                }
+                moved.extend(folders);
+            }
+            Err(e) => error!("Failed to move project `{}` to `{}`: {:#}", old_id, new_id, e),
+        }
+    }
+    Ok(moved)
+}
+
+/// Recursively remove fields from a JSON Value based on a list of dot-separated paths.
+/// # Arguments
+/// * `value` - The mutable JSON object to clean
+/// * `exclude_paths` - A list of dot-separated paths to remove (e.g., ["status", "metadata.creationTimestamp"])
+pub fn clean_up_value(value: &mut Value, exclude_paths: &[&str]) {
+    for path in exclude_paths {
+        let parts: Vec<&str> = path.split('.').collect();
+        remove_path_and_return(value, &parts);
+    }
+}
+
+/// Remove a deeply nested field from a JSON object and remove it.
+/// Traverses objects by key. Returns `None` if any key is missing or the path runs through
+/// something else than an object.
+fn remove_path_and_return(value: &mut Value, path: &[&str]) -> Option<Value> {
+    if path.is_empty() {
+        return None;
+    }
+
+    let mut current = value;
 
     // Traverse to the parent of the key to remove
     for &key in &path[..path.len() - 1] {
@@ -454,7 +1467,52 @@ fn remove_path_and_return(value: &mut Value, path: &[&str]) -> Option<Value> {
     let last_key = *path.last().unwrap();
 
     // Remove the target key
-    current.as_object_mut().unwrap().remove(last_key)
+    current.as_object_mut()?.remove(last_key)
+}
+
+/// Copy fields at dot-separated paths from `source` into `target`.
+///
+/// Missing intermediate objects are created in `target`. Paths that do not
+/// exist in `source` are removed from `target`, so afterwards both values agree
+/// on every listed path.
+/// # Arguments
+/// * `source` - The JSON object to copy the fields from
+/// * `target` - The mutable JSON object to copy the fields into
+/// * `paths` - A list of dot-separated paths to copy (e.g., ["status", "metadata.resourceVersion"])
+pub fn copy_paths(source: &Value, target: &mut Value, paths: &[&str]) {
+    for path in paths {
+        let parts: Vec<&str> = path.split('.').collect();
+        match get_path(source, &parts) {
+            Some(value) => set_path(target, &parts, value.clone()),
+            None => {
+                remove_path_and_return(target, &parts);
+            }
+        }
+    }
+}
+
+fn get_path<'a>(value: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |current, key| current.get(key))
+}
+
+fn set_path(value: &mut Value, path: &[&str], new_value: Value) {
+    let Some((last_key, parents)) = path.split_last() else {
+        return;
+    };
+
+    let mut current = value;
+    for &key in parents {
+        let Some(object) = current.as_object_mut() else {
+            return;
+        };
+        current = object
+            .entry(key)
+            .or_insert_with(|| Value::Object(serde_json::Map::new()));
+    }
+
+    if let Some(object) = current.as_object_mut() {
+        object.insert(last_key.to_string(), new_value);
+    }
 }
 
 // load an object from the file path specified
@@ -472,21 +1530,73 @@ fn remove_path_and_return(value: &mut Value, path: &[&str]) -> Option<Value> {
 
 pub async fn load_object<T: RancherResource>(path: &Path) -> Result<T> {
     let file_format = file_format_from_path(path);
-    let content = std::fs::read_to_string(path)?;
+    file_limits().check_size(path, std::fs::metadata(path)?.len())?;
+    let mut content = std::fs::read_to_string(path)?;
+    if is_bundle(path) {
+        // bundles are only ever read as the project they hold
+        content = project_document(&content).ok_or_else(|| anyhow!("{} has no project document", path.display()))?;
+    }
     
-    match file_format {
-        FileFormat::Yaml => {
-            serde_yaml::from_str(&content)
-                .map_err(|e| anyhow::anyhow!("Failed to parse YAML: {}", e))
-        },
-        FileFormat::Json => {
-            serde_json::from_str(&content)
-                .map_err(|e| anyhow::anyhow!("Failed to parse JSON: {}", e))
-        },
-        FileFormat::Toml => {
-            toml::from_str(&content)
-                .map_err(|e| anyhow::anyhow!("Failed to parse TOML: {}", e))
-        },
+    Ok(deserialize_object(&content, &file_format, Some(path))?)
+}
+
+/// Whether the file at `path` holds an object of `object_type` in `file_format`, by its name.
+fn is_object_file(path: &Path, object_type: ObjectType, file_format: &FileFormat) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(parse_object_file_name)
+        .is_some_and(|(found_type, _, found_format)| found_type == object_type && found_format == *file_format)
+}
+
+/// Read the object file at `path`, recording it in `malformed` if it is too large to read.
+async fn read_or_record(path: &Path, malformed: &mut Vec<ConversionError>) -> Result<Option<String>> {
+    let size = tokio::fs::metadata(path)
+        .await
+        .with_context(|| format!("Failed to read file: {:?}", path))?
+        .len();
+    if let Err(e) = file_limits().check_size(path, size) {
+        malformed.push(e);
+        return Ok(None);
+    }
+    let content = read_to_string(path)
+        .await
+        .with_context(|| format!("Failed to read file: {:?}", path))?;
+    Ok(Some(content))
+}
+
+/// Deserialize the object file at `path`, recording it in `malformed` if it does not parse.
+fn parse_or_record<T: serde::de::DeserializeOwned + Clone + Send + Sync + 'static>(
+    content: &str,
+    path: &Path,
+    file_format: &FileFormat,
+    cache: &ParseCache,
+    malformed: &mut Vec<ConversionError>,
+) -> Option<T> {
+    match cache.parse(content, file_format, || deserialize_object(content, file_format, Some(path))) {
+        Ok(object) => Some(object),
+        Err(e) => {
+            malformed.push(e);
+            None
+        }
+    }
+}
+
+/// `object` of the file at `path` in the folder of `cluster_id`, recording it in `malformed`
+/// if it names another cluster, unless it is moved to the cluster with `correct`, see
+/// [`check_cluster_placement`].
+fn placed_or_record<T: ClusterScoped>(
+    mut object: T,
+    path: &Path,
+    cluster_id: &str,
+    correct: bool,
+    malformed: &mut Vec<ConversionError>,
+) -> Option<T> {
+    match check_cluster_placement(path, cluster_id, &mut object, correct) {
+        Ok(()) => Some(object),
+        Err(e) => {
+            malformed.push(e);
+            None
+        }
     }
 }
 
@@ -494,18 +1604,19 @@ pub async fn load_object<T: RancherResource>(path: &Path) -> Result<T> {
 /// Polls until a Rancher object becomes available or a timeout occurs.
 ///
 /// # Arguments
-/// * `max_retries` - Number of times to retry
-/// * `delay` - Duration between retries
+/// * `object_id` - The ID of the object, reported when it does not become ready
+/// * `policy` - How long to poll for and how long to sleep between polls
 /// * `fetch_fn` - An async closure that attempts to fetch the object and returns `Ok(T)` if found or `Err(anyhow::Error)` on failure
 /// * `operation_name` - Name of the operation for logging purposes
 ///
 /// # Returns
 /// * `Ok(T)` - If the object was eventually found
-/// * `Err(anyhow::Error)` - If polling fails or times out
+/// * `Err(anyhow::Error)` - [`AppError::TimedOutWaitingReady`] if every poll failed, or a poll
+///   was refused with `policy.treat_forbidden_as_fatal`
 ///
 pub async fn wait_for_object_ready<T, F, Fut>(
-    max_retries: usize,
-    delay: Duration,
+    object_id: &str,
+    policy: &ReadyPolicy,
     mut fetch_fn: F,
     operation_name: &str,
 ) -> Result<T, anyhow::Error>
@@ -513,8 +1624,14 @@ where
     F: FnMut() -> Fut,
     Fut: std::future::Future<Output = Result<T, anyhow::Error>>
 {
-
-    for attempt in 0..max_retries {
+    let started = tokio::time::Instant::now();
+    let intervals = policy.intervals();
+    let max_retries = intervals.len() + 1;
+    let mut last_error = None;
+
+    // the wait after each poll, none after the last one
+    let waits = intervals.iter().copied().map(Some).chain(std::iter::once(None));
+    for (attempt, wait) in waits.enumerate() {
         trace!("Attempt {}/{} for {}", attempt + 1, max_retries, operation_name);
         
         match fetch_fn().await {
@@ -525,12 +1642,12 @@ where
             Err(e) => {
                 // Check if this is a "not found" error that we should retry
                 let is_not_found = e.to_string().contains("not found");
+                let forbidden = permission_error(&e).is_some() || format!("{:#}", e).contains("403 Forbidden");
                 
-                if attempt + 1 == max_retries {
-                    let err = anyhow::anyhow!("Timed out waiting for object: {}", e);
-                    log_api_error(&format!("wait_for_object_ready:{}", operation_name), &err);
-                    return Err(err);
-                }
+                let Some(wait) = wait.filter(|_| !(forbidden && policy.treat_forbidden_as_fatal)) else {
+                    last_error = Some(e);
+                    break;
+                };
                 
                 if is_not_found {
                     trace!("Object not found on attempt {}/{}, waiting to retry...", attempt + 1, max_retries);
@@ -538,12 +1655,21 @@ where
                     debug!("Error on attempt {}/{}: {}", attempt + 1, max_retries, e);
                 }
                 
-                tokio::time::sleep(delay).await;
+                tokio::time::sleep(wait).await;
             }
         }
     }
     
-    let err = anyhow::anyhow!("Timed out waiting for object to become ready after {} attempts", max_retries);
+    let timed_out = AppError::TimedOutWaitingReady {
+        object_id: object_id.to_string(),
+        elapsed: started.elapsed(),
+        policy: *policy,
+    };
+    // The failure of the last poll stays in the chain, it tells why the object is not ready
+    let err = match last_error {
+        Some(e) => e.context(timed_out),
+        None => anyhow::Error::new(timed_out),
+    };
     log_api_error(&format!("wait_for_object_ready:{}", operation_name), &err);
     Err(err)
 }
@@ -582,6 +1708,8 @@ async fn await_handles(
 /// * `created`: The created role template that we want to poll. The `metadata` field of
 ///   `created` must contain a valid `name` field.
 ///
+/// * `wait`: How long to poll the role template for
+///
 /// # Errors
 ///
 /// If the polling fails for any reason, or if the object is not created successfully,
@@ -590,6 +1718,7 @@ async fn await_handles(
 async fn poll_role_template_ready(
     config: Arc<Configuration>,
     created: &IoCattleManagementv3RoleTemplate,
+    wait: &ReadyPolicy,
 ) -> Result<IoCattleManagementv3RoleTemplate, anyhow::Error> {
     let rt_name = created
         .metadata
@@ -603,8 +1732,8 @@ async fn poll_role_template_ready(
         .and_then(|m| m.resource_version.as_deref());
 
     wait_for_object_ready(
-        10, 
-        Duration::from_secs(1), 
+        rt_name,
+        wait,
         || {
             let rt_name = rt_name.to_string();
             let resource_version = resource_version.map(|s| s.to_string());
@@ -619,13 +1748,105 @@ async fn poll_role_template_ready(
     .await
 }
 
+/// Poll a project role template binding until Rancher serves it.
+///
+/// # Arguments
+///
+/// * `config`: The configuration to use for the request
+/// * `created`: The created binding, its `metadata` must hold the `name` and `namespace`
+/// * `wait`: How long to poll the binding for
+///
+/// # Errors
+///
+/// * `anyhow::Error` - If the binding is not found within `wait.max_wait`
+///
+#[async_backtrace::framed]
+pub async fn poll_project_role_template_binding_ready(
+    config: Arc<Configuration>,
+    created: &IoCattleManagementv3ProjectRoleTemplateBinding,
+    wait: &ReadyPolicy,
+) -> Result<IoCattleManagementv3ProjectRoleTemplateBinding, anyhow::Error> {
+    let metadata = created
+        .metadata
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Missing metadata in created project role template binding"))?;
+    let prtb_name = metadata
+        .name
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("Missing metadata.name in created project role template binding"))?;
+    let project_id = metadata
+        .namespace
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("Missing metadata.namespace in created project role template binding"))?;
+    let resource_version = metadata.resource_version.as_deref();
+
+    wait_for_object_ready(
+        prtb_name,
+        wait,
+        || {
+            let prtb_name = prtb_name.to_string();
+            let project_id = project_id.to_string();
+            let resource_version = resource_version.map(|s| s.to_string());
+            let config = config.clone();
+
+            async move {
+                find_project_role_template_binding(&config, &project_id, &prtb_name, resource_version.as_deref()).await
+            }
+        },
+        "project_role_template_binding",
+    )
+    .await
+}
+
+/// Check whether every condition in `conditions` is `True` in the project's status.
+///
+/// # Arguments
+/// * `project`: The project as returned by the Rancher API
+/// * `conditions`: The condition types that must be satisfied
+///
+/// # Returns
+/// * `Ok(())` - If all conditions are satisfied
+/// * `Err(Vec<String>)` - The condition types that are missing or not `True`
+pub fn project_conditions_met(
+    project: &IoCattleManagementv3Project,
+    conditions: &[String],
+) -> Result<(), Vec<String>> {
+    let status_conditions = project
+        .status
+        .as_ref()
+        .and_then(|status| status.conditions.as_deref())
+        .unwrap_or_default();
+
+    let pending: Vec<String> = conditions
+        .iter()
+        .filter(|wanted| {
+            !status_conditions
+                .iter()
+                .any(|c| &c.r#type == *wanted && c.status.eq_ignore_ascii_case("true"))
+        })
+        .cloned()
+        .collect();
+
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        Err(pending)
+    }
+}
+
 /// Poll a project until it is ready. This function is used to block until
 /// a project is created successfully.
 ///
+/// The project is ready once the status conditions in `readiness` are all `True`.
+/// If they are not within `readiness.timeout`, the project is accepted as long as it
+/// exists. Without conditions, the project is polled until it exists for `wait.max_wait`.
+///
 /// # Arguments
 ///
 /// * `config`: The configuration to use for the request
 /// * `created`: The created project that we want to poll.
+/// * `readiness`: The conditions to wait for and how long to wait for them
+/// * `wait`: How long to poll for the project to exist and how often to poll it
 ///
 /// # Returns
 ///
@@ -636,9 +1857,11 @@ async fn poll_role_template_ready(
 /// * `anyhow::Error` - If the polling fails or times out
 ///
 #[async_backtrace::framed]
-async fn poll_project_ready(
+pub async fn poll_project_ready(
     config: Arc<Configuration>,
     created: &IoCattleManagementv3Project,
+    readiness: &ProjectReadiness,
+    wait: &ReadyPolicy,
 ) -> Result<IoCattleManagementv3Project, anyhow::Error> {
     let p_name = created
         .metadata
@@ -658,22 +1881,54 @@ async fn poll_project_ready(
         .cluster_name
         .clone();
 
-    wait_for_object_ready(
-        10, 
-        Duration::from_secs(1), 
-        || {
-            let p_name = p_name.to_string();
-            let c_name = c_name.to_string();
-            let resource_version = resource_version.map(|s| s.to_string());
-            let config = config.clone();
+    let fetch = || {
+        let p_name = p_name.to_string();
+        let c_name = c_name.to_string();
+        let resource_version = resource_version.map(|s| s.to_string());
+        let config = config.clone();
+
+        async move { find_project(&config, &c_name, &p_name, resource_version.as_deref()).await }
+    };
+
+    if readiness.conditions.is_empty() {
+        return wait_for_object_ready(p_name, wait, fetch, "project").await;
+    }
 
+    let conditions_wait = ReadyPolicy {
+        max_wait: readiness.timeout,
+        ..*wait
+    };
+    let conditions_met = wait_for_object_ready(
+        p_name,
+        &conditions_wait,
+        || {
+            let fetched = fetch();
             async move {
-                find_project(&config, &c_name, &p_name, resource_version.as_deref()).await
+                let project = fetched.await?;
+                match project_conditions_met(&project, &readiness.conditions) {
+                    Ok(()) => Ok(project),
+                    Err(pending) => bail!("conditions {:?} are not yet true", pending),
+                }
             }
         },
-        "project"
+        "project_conditions",
     )
-    .await
+    .await;
+
+    match conditions_met {
+        Ok(project) => Ok(project),
+        Err(e) => {
+            warn!(
+                "Project {} did not report ready within {:?} ({:#}), continuing because it exists",
+                p_name, readiness.timeout, e
+            );
+            let exists = ReadyPolicy {
+                max_wait: Duration::ZERO,
+                ..*wait
+            };
+            wait_for_object_ready(p_name, &exists, fetch, "project").await
+        }
+    }
 }
 
 /// Retries an async operation up to `max_retries` times with a delay between attempts.
@@ -744,18 +1999,35 @@ where
     unreachable!("retry_async: loop should return on final attempt")
 }
 
+/// Retries an API call that is safe to repeat while it fails with a
+/// [`TransientError`](error::TransientError).
+///
+/// # Arguments
+/// * `label` - A string label for logging (e.g. "get_projects")
+/// * `policy` - How many attempts are made and how long to wait between them
+/// * `op` - Async closure performing the call
+pub async fn retry_transient<T, F, Fut>(label: &str, policy: &RetryPolicy, op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    retry_async(label, policy.max_retries.max(1), policy.delay, op, is_transient).await
+}
+
 
 /// serialize the object to the file format specified
+///
+/// YAML and JSON are written in the fixed field order of [`canonical_value`]
 pub fn serialize_object<T: serde::Serialize>(
     object: &T,
     file_format: &FileFormat,
 ) -> Result<String> {
     match file_format {
         FileFormat::Yaml => {
-            serde_yaml::to_string(object).context("Failed to serialize object to YAML")
+            serde_yaml::to_string(&canonical_value(object)?).context("Failed to serialize object to YAML")
         }
         FileFormat::Json => {
-            serde_json::to_string_pretty(object).context("Failed to serialize object to JSON")
+            serde_json::to_string_pretty(&canonical_value(object)?).context("Failed to serialize object to JSON")
         }
         FileFormat::Toml => {
             toml::to_string_pretty(object).context("Failed to serialize object to TOML")
@@ -763,18 +2035,1316 @@ pub fn serialize_object<T: serde::Serialize>(
     }
 }
 
+/// Whether unknown fields in object files are errors, see [`set_strict_parsing`].
+static STRICT_PARSING: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+
+/// Make unknown fields in object files an error instead of a warning.
+///
+/// [`deserialize_object`] applies it to every object file the process parses, from the index,
+/// the sync and the git history alike, so it is set before the first one is read.
+pub fn set_strict_parsing(strict: bool) {
+    let _ = STRICT_PARSING.set(strict);
+}
+
+/// The bounds on object files read from the repository, see [`set_file_limits`].
+static FILE_LIMITS: std::sync::OnceLock<FileLimits> = std::sync::OnceLock::new();
+
+/// Set the bounds on the size of object files and the number of files per project folder.
+///
+/// They hold for every read of an object file, the index and the files of earlier commits
+/// included. The bounds set first are kept.
+pub fn set_file_limits(limits: FileLimits) {
+    let _ = FILE_LIMITS.set(limits);
+}
+
+/// The bounds on object files set with [`set_file_limits`], or the defaults.
+pub fn file_limits() -> FileLimits {
+    FILE_LIMITS.get().copied().unwrap_or_default()
+}
+
 // deserialize the project from the format specified
 ///
+/// Fields serde would ignore, usually typos, are logged as a warning naming the closest
+/// known field, or rejected under [`set_strict_parsing`].
+///
 /// # Arguments
 /// FileFormat: The format of the file to be deserialized
 /// object: The object to be deserialized
+/// path: The file `object` was read from, named in the error
+///
+/// # Errors
+/// [`ConversionError::Deserialize`] with the line and column the parser stopped at
 pub fn deserialize_object<T: serde::de::DeserializeOwned>(
     object: &str,
     file_format: &FileFormat,
+    path: Option<&Path>,
 ) -> Result<T, ConversionError> {
-    match file_format {
-        FileFormat::Yaml => serde_yaml::from_str(object).map_err(|e| e.into()),
-        FileFormat::Json => serde_json::from_str(object).map_err(|e| e.into()),
-        FileFormat::Toml => toml::from_str(object).map_err(|e| e.into()),
+    deserialize_checked(object, file_format, path, STRICT_PARSING.get().copied().unwrap_or(false))
+}
+
+fn deserialize_checked<T: serde::de::DeserializeOwned>(
+    object: &str,
+    file_format: &FileFormat,
+    path: Option<&Path>,
+    strict: bool,
+) -> Result<T, ConversionError> {
+    let mut unknown: Vec<String> = Vec::new();
+    let located = match file_format {
+        FileFormat::Yaml => {
+            let deserializer = serde_yaml::Deserializer::from_str(object);
+            serde_ignored::deserialize(deserializer, |field| unknown.push(field.to_string())).map_err(|e| {
+                let location = e.location().map(|location| (location.line(), location.column()));
+                (location, without_location(e.to_string(), location.is_some()))
+            })
+        }
+        FileFormat::Json => {
+            let mut deserializer = serde_json::Deserializer::from_str(object);
+            serde_ignored::deserialize(&mut deserializer, |field| unknown.push(field.to_string()))
+                .and_then(|value| deserializer.end().map(|_| value))
+                .map_err(|e| {
+                    // line 0 means the error has no position, such as a failed read
+                    let location = (e.line() > 0).then(|| (e.line(), e.column()));
+                    (location, without_location(e.to_string(), location.is_some()))
+                })
+        }
+        FileFormat::Toml => {
+            let deserializer = toml::Deserializer::new(object);
+            serde_ignored::deserialize(deserializer, |field| unknown.push(field.to_string())).map_err(|e| {
+                let location = e.span().map(|span| line_and_column(object, span.start));
+                (location, e.message().to_string())
+            })
+        }
+    };
+    let error = |location: Option<(usize, usize)>, message: String| ConversionError::Deserialize {
+        format: *file_format,
+        path: path.map(Path::to_path_buf),
+        line: location.map(|(line, _)| line),
+        column: location.map(|(_, column)| column),
+        message,
+    };
+    let value = located.map_err(|(location, message)| error(location, message))?;
+
+    for field in &unknown {
+        let unknown_field = error(
+            utils::fields::key_location(object, field),
+            utils::fields::describe_unknown_field(field, utils::fields::struct_fields::<T>()),
+        );
+        if strict {
+            return Err(unknown_field);
+        }
+        warn!("{}, the field is ignored", unknown_field);
+    }
+    Ok(value)
+}
+
+/// Drop the ` at line L column C` serde_yaml and serde_json append, the location is reported apart.
+fn without_location(mut message: String, located: bool) -> String {
+    if let Some(index) = message.rfind(" at line ").filter(|_| located) {
+        message.truncate(index);
+    }
+    message
+}
+
+/// The 1-based line and column of the byte at `offset` in `contents`.
+fn line_and_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::parse_error;
+    use crate::models::Backoff;
+    use crate::report::DownloadCounts;
+    use rancher_client::models::{
+        IoCattleManagementv3ProjectSpec, IoCattleManagementv3ProjectStatus,
+        IoCattleManagementv3ProjectStatusConditionsInner, IoK8sApimachineryPkgApisMetaV1ObjectMeta,
+    };
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const PROJECT_PATH: &str = "/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc";
+
+    fn project_with_conditions(conditions: &[(&str, &str)]) -> IoCattleManagementv3Project {
+        IoCattleManagementv3Project {
+            metadata: Some(IoK8sApimachineryPkgApisMetaV1ObjectMeta {
+                name: Some("p-abc".to_string()),
+                namespace: Some("c-123".to_string()),
+                ..Default::default()
+            }),
+            spec: Some(IoCattleManagementv3ProjectSpec::new("c-123".to_string(), "Project".to_string())),
+            status: Some(Box::new(IoCattleManagementv3ProjectStatus {
+                conditions: Some(
+                    conditions
+                        .iter()
+                        .map(|(kind, status)| {
+                            IoCattleManagementv3ProjectStatusConditionsInner::new(status.to_string(), kind.to_string())
+                        })
+                        .collect(),
+                ),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn fast_readiness(timeout_ms: u64) -> ProjectReadiness {
+        ProjectReadiness {
+            timeout: Duration::from_millis(timeout_ms),
+            ..ProjectReadiness::default()
+        }
+    }
+
+    fn fast_wait(max_wait_ms: u64) -> ReadyPolicy {
+        ReadyPolicy {
+            max_wait: Duration::from_millis(max_wait_ms),
+            interval: Duration::from_millis(10),
+            ..ReadyPolicy::default()
+        }
+    }
+
+    async fn mock_config(server: &MockServer) -> Arc<Configuration> {
+        let mut config = Configuration::new();
+        config.base_path = server.uri();
+        Arc::new(config)
+    }
+
+    #[test]
+    fn test_project_conditions_met_reports_pending_conditions() {
+        let readiness = ProjectReadiness::default();
+        let project = project_with_conditions(&[("BackingNamespaceCreated", "True"), ("InitialRolesPopulated", "Unknown")]);
+
+        assert_eq!(
+            project_conditions_met(&project, &readiness.conditions),
+            Err(vec!["InitialRolesPopulated".to_string()])
+        );
+
+        let project = project_with_conditions(&[("BackingNamespaceCreated", "True"), ("InitialRolesPopulated", "True")]);
+        assert_eq!(project_conditions_met(&project, &readiness.conditions), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_poll_project_ready_waits_for_conditions() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(PROJECT_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(project_with_conditions(&[])))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(PROJECT_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(project_with_conditions(&[
+                ("BackingNamespaceCreated", "True"),
+                ("InitialRolesPopulated", "True"),
+            ])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let created = project_with_conditions(&[]);
+        let ready = poll_project_ready(mock_config(&server).await, &created, &fast_readiness(1000), &fast_wait(100))
+            .await
+            .unwrap();
+
+        assert!(project_conditions_met(&ready, &ProjectReadiness::default().conditions).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_poll_project_ready_falls_back_to_existence() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(PROJECT_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(project_with_conditions(&[])))
+            .mount(&server)
+            .await;
+
+        let created = project_with_conditions(&[]);
+        let result = poll_project_ready(mock_config(&server).await, &created, &fast_readiness(50), &fast_wait(100)).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_poll_project_ready_times_out_with_object_id_and_elapsed() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(PROJECT_PATH))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(5)
+            .mount(&server)
+            .await;
+        let readiness = ProjectReadiness {
+            conditions: Vec::new(),
+            ..ProjectReadiness::default()
+        };
+
+        let created = project_with_conditions(&[]);
+        let error = poll_project_ready(mock_config(&server).await, &created, &readiness, &fast_wait(50))
+            .await
+            .unwrap_err();
+
+        match error.downcast_ref::<AppError>() {
+            Some(AppError::TimedOutWaitingReady { object_id, elapsed, .. }) => {
+                assert_eq!(object_id, "p-abc");
+                assert!(*elapsed >= Duration::from_millis(40), "{:?}", elapsed);
+            }
+            other => panic!("expected a readiness timeout, got {:?}", other),
+        }
+        assert!(format!("{:#}", error).contains("not found"), "{:#}", error);
+    }
+
+    #[test]
+    fn test_ready_policy_intervals() {
+        let fixed = ReadyPolicy {
+            max_wait: Duration::from_secs(5),
+            interval: Duration::from_secs(1),
+            ..ReadyPolicy::default()
+        };
+        assert_eq!(fixed.intervals(), vec![Duration::from_secs(1); 4]);
+
+        let exponential = ReadyPolicy {
+            max_wait: Duration::from_secs(18),
+            backoff: Backoff::Exponential { max_interval: Duration::from_secs(4) },
+            ..fixed
+        };
+        let seconds: Vec<u64> = exponential.intervals().iter().map(Duration::as_secs).collect();
+        assert_eq!(seconds, [1, 2, 4, 4, 4]);
+        assert_eq!(exponential.to_string(), "up to 18s every 1s doubling to 4s");
+
+        let once = ReadyPolicy { max_wait: Duration::ZERO, ..fixed };
+        assert!(once.intervals().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_for_object_ready_sleeps_the_policy_intervals() {
+        let policy = ReadyPolicy {
+            max_wait: Duration::from_secs(30),
+            interval: Duration::from_secs(2),
+            backoff: Backoff::Exponential { max_interval: Duration::from_secs(8) },
+            treat_forbidden_as_fatal: false,
+        };
+        let polls = std::sync::atomic::AtomicUsize::new(0);
+        let started = tokio::time::Instant::now();
+
+        let error = wait_for_object_ready(
+            "rt-abc",
+            &policy,
+            || async {
+                polls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                Err::<(), _>(anyhow::anyhow!("403 Forbidden: cannot get roletemplates"))
+            },
+            "role_template",
+        )
+        .await
+        .unwrap_err();
+
+        // 2 + 4 + 8 + 8 seconds, another 8 would reach max_wait
+        assert_eq!(polls.into_inner(), 5);
+        assert_eq!(started.elapsed(), Duration::from_secs(22));
+        assert!(
+            format!("{:#}", error).contains("polling up to 30s every 2s doubling to 8s"),
+            "{:#}",
+            error
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_for_object_ready_stops_when_forbidden_is_fatal() {
+        let policy = ReadyPolicy {
+            max_wait: Duration::from_secs(30),
+            treat_forbidden_as_fatal: true,
+            ..ReadyPolicy::default()
+        };
+        let polls = std::sync::atomic::AtomicUsize::new(0);
+        let started = tokio::time::Instant::now();
+        let poll = |message: &'static str| {
+            polls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            async move { Err::<(), _>(anyhow::anyhow!(message)) }
+        };
+
+        let error = wait_for_object_ready("rt-abc", &policy, || poll("403 Forbidden"), "role_template")
+            .await
+            .unwrap_err();
+        assert_eq!(polls.swap(0, std::sync::atomic::Ordering::Relaxed), 1);
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert!(format!("{:#}", error).contains("stopping when forbidden"), "{:#}", error);
+
+        // other errors are still polled until max_wait
+        wait_for_object_ready("rt-abc", &policy, || poll("not found"), "role_template")
+            .await
+            .unwrap_err();
+        assert_eq!(polls.into_inner(), 30);
+    }
+
+    #[tokio::test]
+    async fn test_poll_prtb_ready_waits_for_the_binding() {
+        let server = MockServer::start().await;
+        let prtb_path = "/apis/management.cattle.io/v3/namespaces/p-abc/projectroletemplatebindings/prtb-1";
+        let created = IoCattleManagementv3ProjectRoleTemplateBinding {
+            metadata: Some(IoK8sApimachineryPkgApisMetaV1ObjectMeta {
+                name: Some("prtb-1".to_string()),
+                namespace: Some("p-abc".to_string()),
+                ..Default::default()
+            }),
+            project_name: "c-123:p-abc".to_string(),
+            role_template_name: "project-member".to_string(),
+            ..Default::default()
+        };
+        Mock::given(method("GET"))
+            .and(path(prtb_path))
+            .respond_with(ResponseTemplate::new(404))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(prtb_path))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&created))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let ready = poll_project_role_template_binding_ready(mock_config(&server).await, &created, &fast_wait(100))
+            .await
+            .unwrap();
+
+        assert_eq!(ready.metadata.and_then(|m| m.name).as_deref(), Some("prtb-1"));
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient_recovers_from_dropped_connection() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut request = [0u8; 4096];
+            // the first connection is closed without an answer
+            let (mut dropped, _) = listener.accept().await.unwrap();
+            let _ = dropped.read(&mut request).await;
+            drop(dropped);
+
+            let (mut answered, _) = listener.accept().await.unwrap();
+            let _ = answered.read(&mut request).await;
+            let body = r#"{"apiVersion":"management.cattle.io/v3","kind":"ProjectList","items":[]}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            answered.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let mut config = Configuration::new();
+        config.base_path = format!("http://{}", address);
+        let policy = RetryPolicy {
+            max_retries: 3,
+            delay: Duration::from_millis(10),
+        };
+
+        let projects = retry_transient("get_projects", &policy, || {
+            get_projects(&config, "c-123", None, None, None, None, None, None)
+        })
+        .await
+        .unwrap();
+
+        assert!(projects.items.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_a_phase_hanging_on_a_silent_server_is_cut_short() {
+        // accepts the connection and never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (_silent, _) = listener.accept().await.unwrap();
+            sleep(Duration::from_secs(60)).await;
+        });
+        let mut config = Configuration::new();
+        config.base_path = format!("http://{}", address);
+        let mut report = SyncReport::new("run-1");
+
+        let listed = within_timeout(
+            "compare_c-123",
+            Some(Duration::from_millis(200)),
+            &mut report,
+            get_projects(&config, "c-123", None, None, None, None, None, None),
+        )
+        .await;
+
+        assert!(listed.is_none());
+        assert_eq!(report.timed_out, vec!["compare_c-123".to_string()]);
+        assert!(within_timeout("delete_c-123", None, &mut report, async { 1 }).await.is_some());
+        assert_eq!(report.timed_out.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_discarding_a_download_removes_its_files_and_empty_folders() {
+        let endpoint = tempfile::tempdir().unwrap();
+        let project_file = endpoint.path().join("c-1/p-1/p-1.project.yaml");
+        let cluster_file = endpoint.path().join("c-1/c-1.cluster.yaml");
+        let role_template_file = endpoint.path().join("roles/rt-1.rt.yaml");
+        for file in [&project_file, &cluster_file, &role_template_file] {
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, "id: x\n").unwrap();
+        }
+        let download = DownloadReport { written: vec![project_file, cluster_file], ..Default::default() };
+
+        discard_download(endpoint.path(), &download).await;
+
+        assert!(!endpoint.path().join("c-1").exists());
+        assert!(role_template_file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_pruning_a_download_removes_the_files_it_neither_wrote_nor_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = dir.path();
+        write_synthetic_repo(endpoint, 1, 2, 1, 1);
+        let gone = endpoint.join("c-0/p-0-1");
+        let kept = endpoint.join("c-0/p-0-0/prtb-0.prtb.yaml");
+        let index = RepoIndex::build(endpoint, endpoint, &utils::ignore::ShepherdIgnore::default()).await;
+        let written = index
+            .iter()
+            .map(|(_, path)| path.to_path_buf())
+            .filter(|path| !path.starts_with(&gone) && *path != kept)
+            .collect();
+        let mut download = DownloadReport { written, kept: vec![kept.clone()], ..Default::default() };
+
+        prune_download(endpoint, &index, &mut download).await;
+
+        assert_eq!(download.pruned, [gone.join("p-0-1.project.yaml"), gone.join("prtb-0.prtb.yaml")]);
+        assert!(!gone.exists());
+        assert!(kept.exists());
+        assert!(endpoint.join("c-0/p-0-0/p-0-0.project.yaml").exists());
+        assert!(endpoint.join("roles/rt-0.rt.yaml").exists());
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient_does_not_retry_error_responses() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/c-123/projects"))
+            .respond_with(ResponseTemplate::new(403))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = mock_config(&server).await;
+        let policy = RetryPolicy {
+            max_retries: 3,
+            delay: Duration::from_millis(10),
+        };
+        let result = retry_transient("get_projects", &policy, || {
+            get_projects(&config, "c-123", None, None, None, None, None, None)
+        })
+        .await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_download_writes_crtbs_only_for_clusters_that_have_them() {
+        use rancher_client::models::{IoCattleManagementv3Cluster, IoCattleManagementv3ClusterSpec};
+
+        let cluster = |id: &str| IoCattleManagementv3Cluster {
+            metadata: Some(IoK8sApimachineryPkgApisMetaV1ObjectMeta {
+                name: Some(id.to_string()),
+                ..Default::default()
+            }),
+            spec: Box::new(IoCattleManagementv3ClusterSpec {
+                display_name: id.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let crtb = serde_json::json!({
+            "metadata": {
+                "name": "crtb-abc",
+                "namespace": "c-123",
+                "annotations": { "lifecycle.cattle.io/create.cluster-crtb-sync": "true" }
+            },
+            "clusterName": "c-123",
+            "roleTemplateName": "cluster-owner",
+            "userName": "u-xyz"
+        });
+        let list = |items: Vec<serde_json::Value>| serde_json::json!({ "metadata": {}, "items": items });
+
+        let server = MockServer::start().await;
+        let mocks = [
+            ("/apis/management.cattle.io/v3/clusters", list(vec![
+                serde_json::to_value(cluster("c-123")).unwrap(),
+                serde_json::to_value(cluster("c-456")).unwrap(),
+                serde_json::to_value(cluster("local")).unwrap(),
+            ])),
+            ("/apis/management.cattle.io/v3/roletemplates", list(vec![])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects", list(vec![])),
+            ("/apis/management.cattle.io/v3/namespaces/c-456/projects", list(vec![])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/clusterroletemplatebindings", list(vec![crtb])),
+            ("/apis/management.cattle.io/v3/namespaces/c-456/clusterroletemplatebindings", list(vec![])),
+        ];
+        for (mock_path, body) in mocks {
+            Mock::given(method("GET"))
+                .and(path(mock_path))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&server)
+                .await;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = mock_config(&server).await;
+        download_current_configuration(
+            &config,
+            dir.path(),
+            &FileFormat::Yaml,
+            &MetadataFilter::default(),
+            &RoleTemplateFilter::default(),
+            &RetryPolicy::default(),
+            false,
+            ClusterFolderNaming::Id,
+            PrtbListing::PerProject,
+            &DownloadOptions::default(),
+        )
+            .await
+            .unwrap();
+
+        let endpoint_path = endpoint_folder(dir.path(), &config.base_path);
+        let crtb_file = endpoint_path.join("c-123").join(CRTB_FOLDER).join("crtb-abc.crtb.yaml");
+        let contents = std::fs::read_to_string(&crtb_file).unwrap();
+        assert!(!contents.contains("lifecycle.cattle.io"));
+        assert!(!endpoint_path.join("c-456").join(CRTB_FOLDER).exists());
+        assert!(!endpoint_path.join("local").exists());
+
+        let loaded = load_configuration(dir.path(), &config.base_path, "c-123", &FileFormat::Yaml, false)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.crtbs.len(), 1);
+        assert_eq!(loaded.crtbs[0].role_template_name, "cluster-owner");
+        assert!(loaded.projects.is_empty());
+
+        std::fs::write(&crtb_file, "annotations:\n  shepherd.cattle.io/ignore: \"true\"\n").unwrap();
+        let report = download_current_configuration(
+            &config,
+            dir.path(),
+            &FileFormat::Yaml,
+            &MetadataFilter::default(),
+            &RoleTemplateFilter::default(),
+            &RetryPolicy::default(),
+            false,
+            ClusterFolderNaming::Id,
+            PrtbListing::PerProject,
+            &DownloadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report.written.len(), 2);
+        assert_eq!(report.clusters["c-123"], DownloadCounts { written: 1, skipped: 1 });
+        assert_eq!(report.clusters["c-456"], DownloadCounts { written: 1, skipped: 0 });
+        assert_eq!(report.role_templates, DownloadCounts::default());
+    }
+
+    #[tokio::test]
+    async fn test_download_writes_a_bundle_per_project_when_bundling() {
+        let list = |items: Vec<serde_json::Value>| serde_json::json!({ "metadata": {}, "items": items });
+        let server = MockServer::start().await;
+        let mocks = [
+            ("/apis/management.cattle.io/v3/clusters", list(vec![serde_json::json!({ "metadata": { "name": "c-123" }, "spec": { "displayName": "prod" } })])),
+            ("/apis/management.cattle.io/v3/roletemplates", list(vec![])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects", list(vec![serde_json::json!({
+                "metadata": { "name": "p-abc", "namespace": "c-123" },
+                "spec": { "clusterName": "c-123", "displayName": "web" }
+            })])),
+            ("/apis/management.cattle.io/v3/namespaces/p-abc/projectroletemplatebindings", list(vec![serde_json::json!({
+                "metadata": { "name": "prtb-abc", "namespace": "p-abc" },
+                "projectName": "c-123:p-abc",
+                "roleTemplateName": "project-member",
+                "userName": "u-abc"
+            })])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/clusterroletemplatebindings", list(vec![])),
+        ];
+        for (mock_path, body) in mocks {
+            Mock::given(method("GET"))
+                .and(path(mock_path))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&server)
+                .await;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = mock_config(&server).await;
+        download_current_configuration(
+            &config,
+            dir.path(),
+            &FileFormat::Yaml,
+            &MetadataFilter::default(),
+            &RoleTemplateFilter::default(),
+            &RetryPolicy::default(),
+            false,
+            ClusterFolderNaming::Id,
+            PrtbListing::PerProject,
+            &DownloadOptions { bundle_projects: true, ..Default::default() },
+        )
+        .await
+        .unwrap();
+
+        let cluster_path = endpoint_folder(dir.path(), &config.base_path).join("c-123");
+        assert!(cluster_path.join("p-abc.bundle.yaml").exists());
+        assert!(!cluster_path.join("p-abc").exists());
+        let loaded = load_configuration(dir.path(), &config.base_path, "c-123", &FileFormat::Yaml, false)
+            .await
+            .unwrap()
+            .unwrap();
+        let (_, prtbs) = &loaded.projects["p-abc"];
+        assert_eq!(prtbs.len(), 1);
+        assert_eq!(prtbs[0].user_name.as_deref(), Some("u-abc"));
+    }
+
+    #[tokio::test]
+    async fn test_download_skips_objects_named_to_lead_out_of_the_config_folder() {
+        let list = |items: Vec<serde_json::Value>| serde_json::json!({ "metadata": {}, "items": items });
+        let crtb = |name: &str| {
+            serde_json::json!({
+                "metadata": { "name": name, "namespace": "c-123" },
+                "clusterName": "c-123",
+                "roleTemplateName": "cluster-owner",
+                "userName": "u-xyz"
+            })
+        };
+        let role_template = |name: &str| serde_json::json!({ "metadata": { "name": name }, "context": "project" });
+        let server = MockServer::start().await;
+        let mocks = [
+            ("/apis/management.cattle.io/v3/clusters", list(vec![
+                serde_json::json!({ "metadata": { "name": "c-123" }, "spec": { "displayName": "c-123" } }),
+            ])),
+            ("/apis/management.cattle.io/v3/roletemplates", list(vec![
+                role_template("project-member"),
+                role_template("../../etc/passwd"),
+            ])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects", list(vec![])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/clusterroletemplatebindings", list(vec![
+                crtb("crtb-abc"),
+                crtb("../../../../etc/passwd"),
+            ])),
+        ];
+        for (mock_path, body) in mocks {
+            Mock::given(method("GET"))
+                .and(path(mock_path))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&server)
+                .await;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("rancher_config");
+        let config = mock_config(&server).await;
+        download_current_configuration(
+            &config,
+            &root,
+            &FileFormat::Yaml,
+            &MetadataFilter::default(),
+            &RoleTemplateFilter::default(),
+            &RetryPolicy::default(),
+            false,
+            ClusterFolderNaming::Id,
+            PrtbListing::PerProject,
+            &DownloadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let endpoint_path = endpoint_folder(&root, &config.base_path);
+        assert!(endpoint_path.join("roles/project-member.rt.yaml").exists());
+        assert!(endpoint_path.join("c-123").join(CRTB_FOLDER).join("crtb-abc.crtb.yaml").exists());
+        let outside: Vec<_> = walkdir::WalkDir::new(dir.path())
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.file_type().is_file() && !entry.path().starts_with(&endpoint_path))
+            .map(|entry| entry.into_path())
+            .collect();
+        assert!(outside.is_empty(), "{:?}", outside);
+    }
+
+    #[tokio::test]
+    async fn test_role_templates_are_listed_once_for_every_cluster_of_a_run() {
+        let list = |items: Vec<serde_json::Value>| serde_json::json!({ "metadata": {}, "items": items });
+        let cluster_ids = ["c-1", "c-2", "c-3"];
+        let server = MockServer::start().await;
+        let clusters = cluster_ids
+            .iter()
+            .map(|id| serde_json::json!({ "metadata": { "name": id }, "spec": { "displayName": id } }))
+            .collect();
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/clusters"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(list(clusters)))
+            .mount(&server)
+            .await;
+        let role_template = serde_json::json!({ "metadata": { "name": "project-member" }, "context": "project" });
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/roletemplates"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(list(vec![role_template])))
+            .expect(1)
+            .mount(&server)
+            .await;
+        for cluster_id in cluster_ids {
+            for kind in ["projects", "clusterroletemplatebindings"] {
+                Mock::given(method("GET"))
+                    .and(path(format!("/apis/management.cattle.io/v3/namespaces/{cluster_id}/{kind}")))
+                    .respond_with(ResponseTemplate::new(200).set_body_json(list(vec![])))
+                    .mount(&server)
+                    .await;
+            }
+        }
+
+        let config = mock_config(&server).await;
+        let remote_cache = RemoteCache::default();
+        for cluster_id in cluster_ids {
+            let live = load_configuration_from_rancher(&config, cluster_id, &remote_cache, &RetryPolicy::default())
+                .await
+                .unwrap();
+            assert_eq!(live.role_templates.len(), 1);
+        }
+
+        server.verify().await;
+    }
+
+    /// A server with cluster `c-123` holding `projects` projects with one binding each, listed
+    /// across all namespaces in two pages, or answering `403` to that with `forbidden`.
+    async fn mock_prtb_server(projects: usize, forbidden: bool) -> MockServer {
+        use wiremock::matchers::query_param;
+
+        let list = |items: Vec<serde_json::Value>| serde_json::json!({ "metadata": {}, "items": items });
+        let project_ids: Vec<String> = (0..projects).map(|i| format!("p-{i:02}")).collect();
+        let prtb = |project_id: &str| {
+            serde_json::json!({
+                "metadata": { "name": format!("prtb-{project_id}"), "namespace": project_id },
+                "projectName": format!("c-123:{project_id}"),
+                "roleTemplateName": "project-member",
+                "userName": "u-abc"
+            })
+        };
+
+        let server = MockServer::start().await;
+        let cluster = serde_json::json!({ "metadata": { "name": "c-123" }, "spec": { "displayName": "prod" } });
+        let project_list = list(
+            project_ids
+                .iter()
+                .map(|id| {
+                    serde_json::json!({
+                        "metadata": { "name": id, "namespace": "c-123" },
+                        "spec": { "clusterName": "c-123", "displayName": id }
+                    })
+                })
+                .collect(),
+        );
+        let mut mocks = vec![
+            ("/apis/management.cattle.io/v3/clusters".to_string(), list(vec![cluster])),
+            ("/apis/management.cattle.io/v3/roletemplates".to_string(), list(vec![])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects".to_string(), project_list),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/clusterroletemplatebindings".to_string(), list(vec![])),
+        ];
+        for project_id in &project_ids {
+            mocks.push((
+                format!("/apis/management.cattle.io/v3/namespaces/{project_id}/projectroletemplatebindings"),
+                list(vec![prtb(project_id)]),
+            ));
+        }
+        for (mock_path, body) in mocks {
+            Mock::given(method("GET"))
+                .and(path(mock_path))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&server)
+                .await;
+        }
+
+        let all_namespaces = "/apis/management.cattle.io/v3/projectroletemplatebindings";
+        if forbidden {
+            Mock::given(method("GET"))
+                .and(path(all_namespaces))
+                .respond_with(ResponseTemplate::new(403).set_body_json(serde_json::json!({ "reason": "Forbidden" })))
+                .mount(&server)
+                .await;
+            return server;
+        }
+        let (first, second) = project_ids.split_at(projects / 2);
+        Mock::given(method("GET"))
+            .and(path(all_namespaces))
+            .and(query_param("continue", "page-2"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(list(second.iter().map(|id| prtb(id)).collect())),
+            )
+            .mount(&server)
+            .await;
+        let mut first_page = list(first.iter().map(|id| prtb(id)).collect());
+        first_page["metadata"]["continue"] = "page-2".into();
+        Mock::given(method("GET"))
+            .and(path(all_namespaces))
+            .respond_with(ResponseTemplate::new(200).set_body_json(first_page))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    /// Download the endpoint of `server` listing bindings as set by `listing`, returning the
+    /// written files relative to the endpoint folder and the number of requests made.
+    async fn download_counting_requests(server: &MockServer, listing: PrtbListing) -> (Vec<PathBuf>, usize) {
+        let dir = tempfile::tempdir().unwrap();
+        let config = mock_config(server).await;
+        let before = server.received_requests().await.unwrap().len();
+        let report = download_current_configuration(
+            &config,
+            dir.path(),
+            &FileFormat::Yaml,
+            &MetadataFilter::default(),
+            &RoleTemplateFilter::default(),
+            &RetryPolicy::default(),
+            false,
+            ClusterFolderNaming::Id,
+            listing,
+            &DownloadOptions::default(),
+        )
+        .await
+        .unwrap();
+        let requests = server.received_requests().await.unwrap().len() - before;
+
+        let endpoint_path = endpoint_folder(dir.path(), &config.base_path);
+        let mut written: Vec<PathBuf> = report
+            .written
+            .iter()
+            .map(|file| file.strip_prefix(&endpoint_path).unwrap().to_path_buf())
+            .collect();
+        written.sort();
+        let binding = endpoint_path.join("c-123/p-07/prtb-p-07.prtb.yaml");
+        assert!(std::fs::read_to_string(binding).unwrap().contains("project_name: c-123:p-07"));
+        (written, requests)
+    }
+
+    #[tokio::test]
+    async fn test_prtbs_listed_across_all_namespaces_take_fewer_calls() {
+        let server = mock_prtb_server(20, false).await;
+
+        let (per_project, per_project_calls) = download_counting_requests(&server, PrtbListing::PerProject).await;
+        let (all_namespaces, all_namespaces_calls) =
+            download_counting_requests(&server, PrtbListing::AllNamespaces).await;
+
+        // the cluster, 20 projects and their bindings, and the role template index
+        assert_eq!(per_project.len(), 42);
+        assert_eq!(all_namespaces, per_project);
+        // clusters, role templates, projects and crtbs, then one call per project or one per page
+        assert_eq!(per_project_calls, 4 + 20);
+        assert_eq!(all_namespaces_calls, 4 + 2);
+    }
+
+    #[tokio::test]
+    async fn test_prtbs_are_listed_per_project_when_all_namespaces_are_forbidden() {
+        let server = mock_prtb_server(10, true).await;
+
+        let (written, calls) = download_counting_requests(&server, PrtbListing::AllNamespaces).await;
+
+        assert_eq!(written.len(), 22);
+        assert_eq!(calls, 4 + 1 + 10);
+    }
+
+    #[tokio::test]
+    async fn test_files_that_do_not_parse_are_left_out_and_listed() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint_url = "https://rancher.example.com";
+        let endpoint_path = endpoint_folder(dir.path(), endpoint_url);
+        let cluster_path = endpoint_path.join("c-123");
+        std::fs::create_dir_all(cluster_path.join("p-abc")).unwrap();
+        std::fs::create_dir_all(cluster_path.join("p-bad")).unwrap();
+        std::fs::create_dir_all(endpoint_path.join("roles")).unwrap();
+        std::fs::write(cluster_path.join("c-123.cluster.yaml"), "id: c-123\ndisplay_name: prod\n").unwrap();
+        let project = Project::new(
+            None,
+            "c-123".to_string(),
+            None,
+            None,
+            "team-a".to_string(),
+            None,
+            Some("p-abc".to_string()),
+            None,
+            None,
+            "c-123".to_string(),
+            None,
+            None,
+            None,
+        );
+        std::fs::write(
+            cluster_path.join("p-abc").join("p-abc.project.yaml"),
+            serde_yaml::to_string(&project).unwrap(),
+        )
+        .unwrap();
+        let bad_project = cluster_path.join("p-bad").join("p-bad.project.yaml");
+        std::fs::write(&bad_project, "id: p-bad\n  display_name: [\n").unwrap();
+        let bad_role = endpoint_path.join("roles").join("admin.rt.yaml");
+        std::fs::write(&bad_role, "{").unwrap();
+
+        let loaded = load_configuration(dir.path(), endpoint_url, "c-123", &FileFormat::Yaml, false)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(loaded.projects.keys().collect::<Vec<_>>(), ["p-abc"]);
+        assert!(loaded.role_templates.is_empty());
+        let mut malformed: Vec<_> = loaded.malformed.iter().filter_map(|e| e.path()).collect();
+        malformed.sort();
+        assert_eq!(malformed, [bad_project.as_path(), bad_role.as_path()]);
+
+        let error = load_object::<Project>(&bad_project).await.unwrap_err();
+        assert_eq!(parse_error(&error).and_then(ConversionError::path), Some(bad_project.as_path()));
+    }
+
+    #[tokio::test]
+    async fn test_objects_naming_another_cluster_than_their_folder_are_left_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint_url = "https://rancher.example.com";
+        let endpoint_path = endpoint_folder(dir.path(), endpoint_url);
+        let cluster_path = endpoint_path.join("c-aaa");
+        std::fs::create_dir_all(cluster_path.join("p-abc")).unwrap();
+        std::fs::create_dir_all(cluster_path.join("p-def")).unwrap();
+        std::fs::create_dir_all(endpoint_path.join("roles")).unwrap();
+        std::fs::write(cluster_path.join("c-aaa.cluster.yaml"), "id: c-aaa\ndisplay_name: prod\n").unwrap();
+        let project = |id: &str, cluster_id: &str| {
+            format!("id: {id}\ndisplay_name: team-{id}\ncluster_name: {cluster_id}\nnamespace: {cluster_id}\n")
+        };
+        std::fs::write(cluster_path.join("p-abc/p-abc.project.yaml"), project("p-abc", "c-aaa")).unwrap();
+        let copied_binding = cluster_path.join("p-abc/prtb-1.prtb.yaml");
+        std::fs::write(
+            &copied_binding,
+            "id: prtb-1\nnamespace: p-abc\nproject_name: c-bbb:p-abc\nrole_template_name: project-member\nuser_name: u-abc\n",
+        )
+        .unwrap();
+        let copied_project = cluster_path.join("p-def/p-def.project.yaml");
+        std::fs::write(&copied_project, project("p-def", "c-bbb")).unwrap();
+
+        let loaded = load_configuration(dir.path(), endpoint_url, "c-aaa", &FileFormat::Yaml, false)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(loaded.projects.keys().collect::<Vec<_>>(), ["p-abc"]);
+        assert!(loaded.projects["p-abc"].1.is_empty());
+        let mut misplaced: Vec<_> = loaded
+            .malformed
+            .iter()
+            .filter(|e| matches!(e, ConversionError::Misplaced { .. }))
+            .filter_map(|e| e.path())
+            .collect();
+        misplaced.sort();
+        assert_eq!(misplaced, [copied_binding.as_path(), copied_project.as_path()]);
+
+        // corrected, they take the cluster of their folder
+        let loaded = load_configuration(dir.path(), endpoint_url, "c-aaa", &FileFormat::Yaml, true)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.projects["p-def"].0.cluster_name, "c-aaa");
+        assert_eq!(loaded.projects["p-abc"].1[0].project_name, "c-aaa:p-abc");
+    }
+
+    #[tokio::test]
+    async fn test_new_bindings_are_checked_against_the_policy_of_their_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint_path = endpoint_folder(dir.path(), "https://rancher.example.com");
+        let cluster_path = endpoint_path.join("c-aaa");
+        std::fs::create_dir_all(cluster_path.join("p-abc")).unwrap();
+        std::fs::write(cluster_path.join("c-aaa.cluster.yaml"), "id: c-aaa\ndisplay_name: prod\n").unwrap();
+        std::fs::write(
+            cluster_path.join("p-abc/p-abc.project.yaml"),
+            "id: p-abc\ndisplay_name: team-a-web\ncluster_name: c-aaa\nnamespace: c-aaa\n",
+        )
+        .unwrap();
+        let binding = |role_template_name: &str, user_name: &str| {
+            format!("id: ''\nnamespace: p-abc\nproject_name: c-aaa:p-abc\nrole_template_name: {role_template_name}\nuser_name: {user_name}\n")
+        };
+        let member = cluster_path.join("p-abc/member.prtb.yaml");
+        std::fs::write(&member, binding("project-member", "u-dev")).unwrap();
+        let owner = cluster_path.join("p-abc/owner.prtb.yaml");
+        std::fs::write(&owner, binding("project-owner", "u-dev")).unwrap();
+        let cluster_owner = cluster_path.join("owner.crtb.yaml");
+        std::fs::write(
+            &cluster_owner,
+            "id: ''\ncluster_name: c-aaa\nnamespace: c-aaa\nrole_template_name: cluster-owner\nuser_name: u-dev\n",
+        )
+        .unwrap();
+        let index = RepoIndex::build(dir.path(), &endpoint_path, &utils::ignore::ShepherdIgnore::default()).await;
+        let policy: BindingPolicy = toml::from_str(
+            "[[rule]]\nprojects = [\"team-a-*\"]\nrole_templates = [\"project-member\"]\n\n\
+             [[rule]]\nproject_ids = [\"p-other\"]\n",
+        )
+        .unwrap();
+        let new_files = vec![
+            (ObjectType::ProjectRoleTemplateBinding, member),
+            (ObjectType::ProjectRoleTemplateBinding, owner.clone()),
+            (ObjectType::ClusterRoleTemplateBinding, cluster_owner),
+        ];
+
+        let violations = check_binding_policy(&policy, &index, "c-aaa", &new_files).await;
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].paths, [owner]);
+        assert!(violations[0].message.contains("binding of `project-owner` to [\"u-dev\"] in project `team-a-web` (p-abc)"));
+    }
+
+    #[tokio::test]
+    async fn test_project_bundles_are_loaded_next_to_project_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint_url = "https://rancher.example.com";
+        let endpoint_path = endpoint_folder(dir.path(), endpoint_url);
+        let cluster_path = endpoint_path.join("c-123");
+        std::fs::create_dir_all(cluster_path.join("p-abc")).unwrap();
+        std::fs::create_dir_all(endpoint_path.join("roles")).unwrap();
+        std::fs::write(cluster_path.join("c-123.cluster.yaml"), "id: c-123\ndisplay_name: prod\n").unwrap();
+        let project = |id: &str| {
+            Project::new(
+                None,
+                "c-123".to_string(),
+                None,
+                None,
+                format!("team-{}", id),
+                None,
+                Some(id.to_string()),
+                None,
+                None,
+                "c-123".to_string(),
+                None,
+                None,
+                None,
+            )
+        };
+        std::fs::write(
+            cluster_path.join("p-abc").join("p-abc.project.yaml"),
+            serde_yaml::to_string(&project("p-abc")).unwrap(),
+        )
+        .unwrap();
+        let prtb: ProjectRoleTemplateBinding = deserialize_object(
+            "id: prtb-1\nnamespace: p-def\nproject_name: c-123:p-def\nrole_template_name: project-member\nuser_name: u-abc\n",
+            &FileFormat::Yaml,
+            None,
+        )
+        .unwrap();
+        let bundle = write_bundle(&project("p-def"), std::slice::from_ref(&prtb)).unwrap();
+        std::fs::write(cluster_path.join("p-def.bundle.yaml"), bundle).unwrap();
+        let bad_bundle = cluster_path.join("p-bad.bundle.yaml");
+        std::fs::write(&bad_bundle, "kind: ProjectRoleTemplateBinding\nid: prtb-2\n").unwrap();
+
+        let loaded = load_configuration(dir.path(), endpoint_url, "c-123", &FileFormat::Yaml, false)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let mut project_ids: Vec<_> = loaded.projects.keys().collect();
+        project_ids.sort();
+        assert_eq!(project_ids, ["p-abc", "p-def"]);
+        assert_eq!(loaded.projects["p-def"], (project("p-def"), vec![prtb]));
+        assert_eq!(loaded.malformed.iter().filter_map(|e| e.path()).collect::<Vec<_>>(), [bad_bundle.as_path()]);
+        // the bundle is read as its project where a project file is expected
+        let loaded_project = load_object::<Project>(&cluster_path.join("p-def.bundle.yaml")).await.unwrap();
+        assert_eq!(loaded_project, project("p-def"));
+    }
+
+    #[tokio::test]
+    async fn test_files_over_the_size_limit_are_left_out_unread() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint_url = "https://rancher.example.com";
+        let endpoint_path = endpoint_folder(dir.path(), endpoint_url);
+        let cluster_path = endpoint_path.join("c-123");
+        std::fs::create_dir_all(&cluster_path).unwrap();
+        std::fs::create_dir_all(endpoint_path.join("roles")).unwrap();
+        std::fs::write(cluster_path.join("c-123.cluster.yaml"), "id: c-123\ndisplay_name: prod\n").unwrap();
+        let big_role = endpoint_path.join("roles").join("generated.rt.yaml");
+        let size = FileLimits::default().max_file_size as usize + 1;
+        std::fs::write(&big_role, "#".repeat(size)).unwrap();
+
+        let loaded = load_configuration(dir.path(), endpoint_url, "c-123", &FileFormat::Yaml, false)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(loaded.role_templates.is_empty());
+        assert!(matches!(
+            loaded.malformed.as_slice(),
+            [ConversionError::OverLimit { path, .. }] if *path == big_role
+        ));
+        assert!(load_object::<RoleTemplate>(&big_role).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_clusters_in_mapped_folders_are_found_and_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint_url = "https://rancher.example.com";
+        let endpoint_path = endpoint_folder(dir.path(), endpoint_url);
+        std::fs::create_dir_all(endpoint_path.join("prod")).unwrap();
+        std::fs::create_dir_all(endpoint_path.join("c-456")).unwrap();
+        std::fs::create_dir_all(endpoint_path.join("roles")).unwrap();
+        std::fs::write(endpoint_path.join("prod/c-123.cluster.yaml"), "id: c-123\ndisplay_name: prod\n").unwrap();
+        std::fs::write(endpoint_path.join("c-456/c-456.cluster.yaml"), "id: c-456\ndisplay_name: dev\n").unwrap();
+        std::fs::write(endpoint_path.join("clusters.map.yaml"), "prod: c-123\n").unwrap();
+
+        let cluster_ids = cluster_folders(&endpoint_path, &FileFormat::Yaml).await.unwrap();
+        let loaded = load_configuration(dir.path(), endpoint_url, "c-123", &FileFormat::Yaml, false)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(cluster_ids, ["c-123", "c-456"]);
+        assert_eq!(loaded.cluster.display_name, "prod");
+    }
+
+    /// Write an endpoint folder with `clusters` clusters of `projects` projects holding
+    /// `bindings` bindings each, and `role_templates` role templates, returning the file count.
+    fn write_synthetic_repo(endpoint_path: &Path, clusters: usize, projects: usize, bindings: usize, role_templates: usize) -> usize {
+        let roles = endpoint_path.join("roles");
+        std::fs::create_dir_all(&roles).unwrap();
+        let rules = "rules:\n- apiGroups:\n  - ''\n  resources:\n  - pods\n  - services\n  verbs:\n  - get\n  - list\n  - watch\n";
+        for rt in 0..role_templates {
+            let contents = format!("id: rt-{rt}\ndisplay_name: Role {rt}\ncontext: project\nrole_template_names:\n- view\n{rules}labels:\n  team: t-{rt}\n");
+            std::fs::write(roles.join(format!("rt-{rt}.rt.yaml")), contents).unwrap();
+        }
+        for cluster in 0..clusters {
+            let cluster_id = format!("c-{cluster}");
+            let cluster_path = endpoint_path.join(&cluster_id);
+            std::fs::create_dir_all(&cluster_path).unwrap();
+            std::fs::write(
+                cluster_path.join(format!("{cluster_id}.cluster.yaml")),
+                format!("id: {cluster_id}\ndisplay_name: cluster {cluster}\n"),
+            )
+            .unwrap();
+            for project in 0..projects {
+                let project_id = format!("p-{cluster}-{project}");
+                let project_path = cluster_path.join(&project_id);
+                std::fs::create_dir_all(&project_path).unwrap();
+                std::fs::write(
+                    project_path.join(format!("{project_id}.project.yaml")),
+                    format!("id: {project_id}\ndisplay_name: team {project}\ncluster_name: {cluster_id}\nnamespace: {cluster_id}\n"),
+                )
+                .unwrap();
+                for binding in 0..bindings {
+                    let contents = format!(
+                        "id: prtb-{binding}\nnamespace: {project_id}\nproject_name: {cluster_id}:{project_id}\nrole_template_name: rt-{binding}\nuser_name: u-{binding}\n"
+                    );
+                    std::fs::write(project_path.join(format!("prtb-{binding}.prtb.yaml")), contents).unwrap();
+                }
+            }
+        }
+        role_templates + clusters * (1 + projects * (1 + bindings))
+    }
+
+    #[tokio::test]
+    async fn test_stored_configs_are_loaded_once_and_unchanged_files_are_not_parsed_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint_url = "https://rancher.example.com";
+        let endpoint_path = endpoint_folder(dir.path(), endpoint_url);
+        let files = write_synthetic_repo(&endpoint_path, 20, 5, 4, 100);
+        let index = RepoIndex::build(dir.path(), &endpoint_path, &utils::ignore::ShepherdIgnore::default()).await;
+        let cluster_ids: Vec<String> = (0..20).map(|cluster| format!("c-{cluster}")).collect();
+
+        let mut expected = HashMap::new();
+        for cluster_id in &cluster_ids {
+            let loaded = load_configuration(dir.path(), endpoint_url, cluster_id, &FileFormat::Yaml, false).await.unwrap();
+            expected.insert(cluster_id.clone(), loaded.unwrap());
+        }
+
+        let cache = Arc::new(ParseCache::default());
+        let mut stored =
+            StoredConfigs::load(dir.path(), endpoint_url, &index, &cluster_ids, &FileFormat::Yaml, false, cache.clone()).await;
+        assert_eq!(cache.parsed(), files);
+        for cluster_id in &cluster_ids {
+            assert!(stored.is_loaded(cluster_id));
+            assert_eq!(stored.take(cluster_id).await.unwrap().as_ref(), expected.get(cluster_id));
+        }
+
+        let stored =
+            StoredConfigs::load(dir.path(), endpoint_url, &index, &cluster_ids, &FileFormat::Yaml, false, cache.clone()).await;
+        assert_eq!(cache.parsed(), files, "unchanged files were parsed again");
+        assert!(cluster_ids.iter().all(|cluster_id| stored.is_loaded(cluster_id)));
+
+        // parses are keyed by the contents, a file written again unchanged is not parsed again
+        let rewritten = endpoint_path.join("c-0/p-0-0/prtb-0.prtb.yaml");
+        std::fs::write(&rewritten, std::fs::read(&rewritten).unwrap()).unwrap();
+        let changed = endpoint_path.join("c-0/p-0-0/prtb-1.prtb.yaml");
+        let contents = std::fs::read_to_string(&changed).unwrap().replace("u-1", "u-changed");
+        std::fs::write(&changed, contents).unwrap();
+        StoredConfigs::load(dir.path(), endpoint_url, &index, &cluster_ids, &FileFormat::Yaml, false, cache.clone()).await;
+        assert_eq!(cache.parsed(), files + 1);
+    }
+
+    #[tokio::test]
+    async fn test_clusters_with_written_files_are_loaded_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint_url = "https://rancher.example.com";
+        let endpoint_path = endpoint_folder(dir.path(), endpoint_url);
+        write_synthetic_repo(&endpoint_path, 2, 1, 1, 1);
+        let index = RepoIndex::build(dir.path(), &endpoint_path, &utils::ignore::ShepherdIgnore::default()).await;
+        let cluster_ids = vec!["c-0".to_string(), "c-1".to_string()];
+        let cache = Arc::new(ParseCache::default());
+        let mut stored =
+            StoredConfigs::load(dir.path(), endpoint_url, &index, &cluster_ids, &FileFormat::Yaml, false, cache.clone()).await;
+
+        let project_file = endpoint_path.join("c-0/p-0-0/p-0-0.project.yaml");
+        std::fs::write(&project_file, "id: p-0-0\ndisplay_name: renamed\ncluster_name: c-0\nnamespace: c-0\n").unwrap();
+        stored.invalidate(std::slice::from_ref(&project_file));
+        assert!(!stored.is_loaded("c-0"));
+        assert!(stored.is_loaded("c-1"));
+        let loaded = stored.take("c-0").await.unwrap().unwrap();
+        assert_eq!(loaded.projects["p-0-0"].0.display_name, "renamed");
+
+        stored.invalidate(&[endpoint_path.join("roles/rt-new.rt.yaml")]);
+        assert!(!stored.is_loaded("c-1"));
+    }
+
+    #[test]
+    fn test_deserialize_errors_name_the_file_line_and_column() {
+        let path = Path::new("p-abc.project.yaml");
+        let yaml = "id: p-abc\ncluster_name: c-123\nnamespace: c-123\ndisplay_name: [team-a]\n";
+        let error = deserialize_object::<Project>(yaml, &FileFormat::Yaml, Some(path)).unwrap_err();
+        assert_eq!(error.path(), Some(path));
+        assert!(
+            error.to_string().starts_with("p-abc.project.yaml:4:15: display_name: invalid type: sequence"),
+            "{}",
+            error
+        );
+
+        let json = "{\n  \"id\": \"p-abc\",\n  \"display_name\": 7\n}";
+        let error = deserialize_object::<Project>(json, &FileFormat::Json, Some(Path::new("p-abc.project.json"))).unwrap_err();
+        assert!(
+            error.to_string().starts_with("p-abc.project.json:3:19: invalid type: integer `7`"),
+            "{}",
+            error
+        );
+
+        let toml = "id = \"p-abc\"\ndisplay_name = 7\n";
+        let error = deserialize_object::<Project>(toml, &FileFormat::Toml, Some(Path::new("p-abc.project.toml"))).unwrap_err();
+        assert!(
+            error.to_string().starts_with("p-abc.project.toml:2:16: invalid type: integer `7`"),
+            "{}",
+            error
+        );
+
+        let error = deserialize_object::<Project>("id: [", &FileFormat::Yaml, None).unwrap_err();
+        assert_eq!(error.path(), None);
+        assert!(error.to_string().starts_with("<yaml>:1:"), "{}", error);
+    }
+
+    #[test]
+    fn test_unknown_fields_are_rejected_only_when_strict() {
+        let path = Path::new("c-123/c-123.cluster.yaml");
+        let yaml = "id: c-123\ndisplayname: prod\ndisplay_name: prod\n";
+
+        let cluster: Cluster = deserialize_checked(yaml, &FileFormat::Yaml, Some(path), false).unwrap();
+        assert_eq!(cluster.display_name, "prod");
+
+        let error = deserialize_checked::<Cluster>(yaml, &FileFormat::Yaml, Some(path), true).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "c-123/c-123.cluster.yaml:2:1: unknown field `displayname`, did you mean `display_name`?"
+        );
     }
 }
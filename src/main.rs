@@ -1,29 +1,167 @@
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use shepherd::api::client::ShepherdClient;
+use shepherd::api::client::{record_server_version, server_version, ShepherdClient};
+use shepherd::api::clock::{check_clock, AUTH_FAILURES_BEFORE_CLOCK_CHECK};
+use shepherd::api::client_info::{set_client_name, version_info};
 use shepherd::api::config::ShepherdConfig;
-use shepherd::error::{handle_result_collection, AppError};
-use shepherd::models::{MinimalObject, ObjectType};
+use shepherd::api::circuit_breaker::{CircuitBreaker, CircuitState};
+use shepherd::check::{run_checks, CheckReport};
+use shepherd::error::{failed_object, handle_result_collection, is_transient, parse_error, set_error_bodies, AppError, DeletionError, ErrorBodies, FailedObject, ERROR_BODIES_FOLDER};
+use shepherd::events::{Action, EventKind, EventSink, ObjectRef, RunEvents};
+use shepherd::notify::{Event, Notification, Notifier};
+use shepherd::plan::{apply_plan, drifted, live_resource_version, unplanned, ApprovalPolicy, Plan, PlannedChange, PLAN_BRANCH_PREFIX};
+use shepherd::reporting::{live_config, RbacReport};
+use shepherd::report::{count_ignored, new_run_id, DownloadReport, MirroredChanges, RunStatus, StuckObject, SyncReport};
+use shepherd::state::{write_atomically, ChangeBacklog, ObjectFailure, PendingDeletion, Status, SyncState, UnpersistedCreation, METRICS_FILE};
+use shepherd::transaction::{Mutation, Transaction};
+use shepherd::utils::config_validator::{Severity, Violation, ALLOW_DUPLICATE_DISPLAY_NAME_ANNOTATION};
+use shepherd::utils::policy::{BindingPolicy, POLICY_FILE};
+use shepherd::utils::principal::PrincipalRules;
+use shepherd::utils::convert::{convert_format, find_mixed_formats};
+use shepherd::utils::ignore::{contents_have_ignore_directive, ShepherdIgnore};
+use shepherd::utils::index::RepoIndex;
+use shepherd::utils::snapshot::{snapshot_object_to_delete, DirectorySnapshot};
+use shepherd::utils::doctor::{examine, healing_metrics, RepoDoctorConfig};
+use shepherd::utils::logging::{repeated_warning_metrics, set_repeated_warning_window, summarize_repeated_warnings, warn_repeated};
+use shepherd::utils::parse_cache::ParseCache;
+use shepherd::utils::safe_path::ensure_within;
+#[cfg(unix)]
+use shepherd::utils::taskdump::dump_on_sigusr1;
+use shepherd::utils::taskdump::{RunWatchdog, TaskDumper};
+use shepherd::models::{ChangeBudget, ChangeLimits, ConversionError, CreatedObject, DeletionOptions, DownloadOptions, FileLimits, ManagementMode, MinimalObject, ObjectType, PhaseTimeouts, ProjectReadiness, Readiness, RetryPolicy, SyncMode, UpdateStrategy};
+use shepherd::resources::cluster::{
+    cluster_folder, get_clusters, ClusterFolderMap, ClusterFolderNaming, ClusterSelector, MissingClusters, LOCAL_CLUSTER_ID,
+};
+use shepherd::resources::project::ProjectMatch;
+use shepherd::resources::prtb::PrtbListing;
+use shepherd::resources::rt::RoleTemplateFilter;
+use shepherd::utils::diff::MetadataFilter;
 use shepherd::utils::file::{
-    get_minimal_object_from_contents, is_directory_empty, write_back_objects, FileFormat,
+    endpoint_folder, endpoint_folder_name, get_object_to_delete, is_directory_empty, write_back_objects,
+    FileFormat,
 };
 use shepherd::utils::git::{
-    commit_changes, get_deleted_files_and_contents, get_modified_files, get_new_uncommited_files,
-    init_git_repo_with_main_branch, pull_changes, push_changes, resolve_conflicts, safe_clone_repository, GitAuth, GitError,
+    commit_changes, commit_changes_except, commit_each_object, commit_to_branch, with_run_id, get_deleted_files, get_deleted_files_and_contents, get_modified_files, get_new_uncommited_files,
+    apply_sparse_checkout, init_git_repo_with_main_branch, pull_changes, push_changes, push_changes_with_retry, repair_repository_state, resolve_conflicts, safe_clone_repository, CommitGranularity, GitAuth,
+    GitError, default_git_author_email, set_git_author, set_network_timeout,
+};
+use shepherd::modify::{
+    compare_and_update_configurations, compare_cluster, create_objects, delete_objects, fetch_created_object, object_exists,
+};
+use shepherd::{
+    retry_transient, check_binding_policy, check_duplicate_bindings, check_role_templates, check_project_display_names, cluster_folders, reconcile_project_ids, rename_cluster_folders, set_file_limits, set_strict_parsing, download_cluster_configuration, download_current_configuration, is_repo_effectively_empty, load_configuration, load_configuration_from_rancher, list_prtbs, discard_download, refresh_role_template_index, prune_download, within_timeout, RemoteCache, StoredConfigs,
 };
-use shepherd::modify::{compare_and_update_configurations, create_objects, delete_objects};
-use shepherd::download_current_configuration;
 use rancher_client::apis::configuration::Configuration;
 
 
 use anyhow::Result;
+use clap::Parser;
 use git2::Repository;
 use tokio::time::interval;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 use tracing_subscriber::EnvFilter;
-use walkdir::WalkDir;
+
+/// Keep Rancher projects, role templates and bindings in sync with a git repository
+#[derive(Parser, Debug)]
+#[command(name = "shepherd", version = version_info())]
+struct Cli {
+    /// Send every create, update and delete with dryRun=All and never write the results back
+    #[arg(long)]
+    dry_run: bool,
+    /// Fail the run when any object file does not parse, overrides `strict_parsing` in the config
+    #[arg(long)]
+    strict_parsing: bool,
+    /// Run a single sync and exit, non-zero under strict parsing if any object file did not parse
+    #[arg(long)]
+    once: bool,
+    /// Keep the full response bodies cut from errors in the `error_bodies` folder of the state dir
+    #[arg(long)]
+    verbose: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Show the outcome of the last run and the objects quarantined after failing too many runs in a row
+    Status {
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Show how the repository differs from Rancher without changing either
+    Diff {
+        /// The clusters to compare, all synchronized clusters by default
+        #[arg(long = "cluster")]
+        clusters: Vec<String>,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Probe Rancher, the git remote and the configuration folder without changing anything
+    ///
+    /// Prints a line per probe and exits non-zero if any of them failed.
+    Check,
+    /// Rewrite every object file in another file format and commit the change
+    ///
+    /// Deletes the files it converted, set `file_format` to the same format before the next run.
+    ConvertFormat {
+        /// The format to convert the object files to
+        #[arg(long, value_enum)]
+        to: FileFormat,
+    },
+    /// Print a report of the configuration in the repository
+    Report {
+        #[command(subcommand)]
+        report: ReportCommand,
+    },
+    /// Release a quarantined object so the next run applies it again
+    Unquarantine {
+        /// The ID of the object or the path of its file
+        id: String,
+    },
+    /// Approve the changes a run planned and commit the approval, so the next run applies them
+    Approve {
+        /// The ID of the run that planned the changes
+        run_id: String,
+    },
+    /// Write a JSON Schema per object type and a catalog mapping file names to them
+    #[cfg(feature = "schema")]
+    Schema {
+        /// The folder to write the schemas to, created if missing
+        #[arg(long)]
+        out_dir: PathBuf,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum ReportCommand {
+    /// Who is granted which role template in a cluster and the verbs it allows beyond reading
+    Rbac {
+        /// The cluster to report on
+        #[arg(long)]
+        cluster: String,
+        #[arg(long, value_enum, default_value_t = ReportFormat::Md)]
+        format: ReportFormat,
+        /// Compare the bindings with the ones in Rancher, and resolve the builtin role templates
+        #[arg(long)]
+        live: bool,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ReportFormat {
+    Md,
+    Csv,
+    Json,
+}
 
 // const RETRY_DELAY: Duration = Duration::from_millis(200);
 // const LOOP_INTERVAL: Duration = Duration::from_secs(60);
@@ -54,7 +192,7 @@ fn init_tracing() {
 /// 5. Create new objects in the Rancher API if new files have been added
 /// 6. Delete objects in the Rancher API if files have been deleted
 ///
-/// The function will run indefinitely until it is stopped.
+/// The function will run indefinitely until it is stopped, or return after one run with `once`.
 ///
 /// It takes the following parameters:
 ///
@@ -62,286 +200,3350 @@ fn init_tracing() {
 /// - `config_folder_path`: The path to the folder where the configuration files are stored
 /// - `remote_url`: The URL of the remote git repository
 /// - `file_format`: The file format of the configuration files
-/// - `cluster_ids`: A vector of cluster IDs to synchronize the configuration for
+/// - `clusters`: The configured clusters, or the selector discovering them every run
+/// - `include_local_cluster`: Download and sync Rancher's `local` cluster, invisible otherwise
+/// - `cluster_folder_naming`: Whether cluster folders are named after the cluster ID or display name
+/// - `match_projects_by`: Whether project files whose ID is gone are matched to recreated projects by display name
+/// - `prtb_listing`: Whether downloads list project role template bindings per project or across all namespaces
+/// - `download_options`: How downloads write the objects of Rancher to the repository
 /// - `loop_interval`: The interval in seconds to wait between each run
 /// - `retry_delay`: The delay in milliseconds to wait before retrying an operation
 /// - `branch`: The branch to use in the remote repository
 /// - `auth_method`: The authentication method to use for the remote repository
+/// - `clone_depth`: How many commits of history to clone and fetch, `None` for all of them
+/// - `sparse_paths`: Folders to check out besides the endpoint folder, `None` for a full checkout
+/// - `git_enabled`: Whether the config folder is a git repository, or a folder kept by another tool compared to the last run
+/// - `update_strategy`: Whether drifted objects are patched or replaced
+/// - `management_mode`: Whether objects in Rancher without a file are compared
+/// - `mode`: Whether the repository is applied to Rancher or Rancher is only mirrored into the repository
+/// - `approval`: Write the changes of a run to a plan applied once approved, `None` applies them right away
+/// - `prune`: Delete bindings without a file from Rancher when they are not compared
+/// - `project_readiness`: The status conditions new projects are polled for
+/// - `readiness`: How long new objects of each type are polled for before they count as not ready
+/// - `skip_cascaded_deletes`: Leave the bindings of deleted projects to Rancher's cascade
+/// - `deletion_options`: The propagation policy and grace period of deletions, and how long to wait for them
+/// - `deletable_types`: The types whose objects are deleted when their files are removed or pruned
+/// - `repo_doctor`: How stale git locks and a repository that cannot be read are dealt with before each run
+/// - `duplicate_display_names`: Whether projects sharing a display name only warn or skip the cluster
+/// - `binding_policy_violations`: Whether new bindings breaking the binding policy only warn or are not created
+/// - `quota_below_usage`: Whether projects lowering their resource quota below what is used only warn or are not updated
+/// - `metadata_filter`: Annotation and label keys managed by Rancher, ignored when comparing and downloading
+/// - `principal_rules`: How the principal names of bindings are compared, when diffing and looking for duplicates
+/// - `role_template_filter`: The role templates downloaded and compared
+/// - `circuit_breaker`: Pauses the API phases while the endpoint keeps failing
+/// - `commit_granularity`: Whether a run is committed as a whole or one object at a time
+/// - `rollback_threshold`: Undo the changes of a run with more failures than this, `None` never does
+/// - `state_dir`: The folder the changes of a run are backed up to while they can be rolled back
+/// - `quarantine_after`: Skip objects failing this many runs in a row until their file changes, 0 never does
+/// - `escalate_terminating_after`: Log an error and notify once a deleted object is still terminating after this long
+/// - `server_version_interval`: Runs between reads of the Rancher version after the first, 0 never reads it again
+/// - `max_clock_skew`: How far the clock may be off the endpoint before refused tokens are blamed on it, `None` never checks
+/// - `notifier`: Posts failed runs, deletions, drift and the circuit opening to a webhook
+/// - `events`: Streams the start, the planned, applied and failed changes and the end of every run
+/// - `stamp_git_commit`: Annotate created and updated objects with the commit and path of their file
+/// - `record_last_applied`: Record the configuration applied to created and updated objects for three-way merges
+/// - `correct_misplaced_objects`: Move projects and bindings naming another cluster than their folder to the cluster of the folder instead of failing them
+/// - `strict_parsing`: Log object files that do not parse as errors that fail the run instead of warnings
+/// - `once`: Return after a single run, with an error if strict parsing found files that do not parse
+/// - `dry_run`: Send every write as a server-side dry run and leave the repository untouched
+/// - `task_dumper`: Logs the running tasks, rate limited
+/// - `stuck_run_after`: Dump the tasks when a run takes longer than this, `None` never does
+/// - `phase_timeouts`: How long each phase may take before the run goes on without it
+/// - `max_changes_per_run`: The most objects a run creates, updates and deletes, the rest are left to the next runs
+#[allow(clippy::too_many_arguments)]
 async fn run_sync(
     client_config: Arc<Configuration>,
     config_folder_path: &Path,
     remote_url: &str,
     file_format: FileFormat,
-    cluster_ids: Vec<String>,
+    clusters: ClusterScope,
+    include_local_cluster: bool,
+    cluster_folder_naming: ClusterFolderNaming,
+    match_projects_by: ProjectMatch,
+    prtb_listing: PrtbListing,
+    download_options: DownloadOptions,
     loop_interval: u64,
     retry_delay: u64,
     branch: &str,
     auth_method: GitAuth,
+    clone_depth: Option<u32>,
+    sparse_paths: Option<Vec<String>>,
+    git_enabled: bool,
+    update_strategy: UpdateStrategy,
+    management_mode: ManagementMode,
+    mode: SyncMode,
+    approval: Option<ApprovalPolicy>,
+    prune: bool,
+    project_readiness: ProjectReadiness,
+    readiness: Readiness,
+    skip_cascaded_deletes: bool,
+    deletion_options: DeletionOptions,
+    deletable_types: Vec<ObjectType>,
+    repo_doctor: RepoDoctorConfig,
+    duplicate_display_names: Severity,
+    binding_policy_violations: Severity,
+    quota_below_usage: Severity,
+    metadata_filter: MetadataFilter,
+    principal_rules: PrincipalRules,
+    role_template_filter: RoleTemplateFilter,
+    mut circuit_breaker: CircuitBreaker,
+    commit_granularity: CommitGranularity,
+    rollback_threshold: Option<usize>,
+    state_dir: PathBuf,
+    quarantine_after: u32,
+    escalate_terminating_after: Option<Duration>,
+    server_version_interval: u32,
+    max_clock_skew: Option<Duration>,
+    notifier: Option<Notifier>,
+    events: Option<EventSink>,
+    stamp_git_commit: bool,
+    record_last_applied: bool,
+    correct_misplaced_objects: bool,
+    strict_parsing: bool,
+    once: bool,
+    dry_run: bool,
+    task_dumper: Arc<TaskDumper>,
+    stuck_run_after: Option<Duration>,
+    phase_timeouts: PhaseTimeouts,
+    max_changes_per_run: ChangeLimits,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Create a interval ticker
     let mut interval_timer = interval(Duration::from_secs(loop_interval));
 
     let retry_delay = Duration::from_millis(retry_delay);
+    let retry_policy = RetryPolicy {
+        delay: retry_delay,
+        ..RetryPolicy::default()
+    };
 
-    let download_required = download_required(config_folder_path, remote_url, &auth_method).await;
+    // everything Shepherd commits lives in the endpoint folder, or was written by it
+    let endpoint_folder = endpoint_folder(config_folder_path, &client_config.base_path);
+    if endpoint_folder != config_folder_path.join(endpoint_folder_name(&client_config.base_path)) {
+        warn!(
+            "Using endpoint folder {} named by an older version, rename it to {} with git mv",
+            endpoint_folder.display(),
+            endpoint_folder_name(&client_config.base_path)
+        );
+    }
+    // keeps the files of the local cluster out of every phase
+    let local_cluster_pattern = endpoint_folder
+        .strip_prefix(config_folder_path)
+        .ok()
+        .filter(|_| !include_local_cluster)
+        .map(|endpoint| format!("/{}/{}/", endpoint.to_string_lossy(), LOCAL_CLUSTER_ID));
+    let mut written_files: Vec<PathBuf> = Vec::new();
+    // described in the body of the first commit
+    let mut bootstrap_download: Option<DownloadReport> = None;
+    // the endpoint folder is always checked out, an empty cone restores the full checkout
+    let sparse_cone: Vec<String> = match sparse_paths {
+        Some(mut paths) => {
+            if let Ok(endpoint) = endpoint_folder.strip_prefix(config_folder_path) {
+                paths.push(endpoint.to_string_lossy().to_string());
+            }
+            paths
+        }
+        None => Vec::new(),
+    };
 
-    match download_required {
-        Ok(true) => {
+    let decision = match git_enabled {
+        true => download_required(config_folder_path, remote_url, &auth_method, clone_depth).await,
+        // another tool fills the folder
+        false => DownloadDecision::UseExisting,
+    };
+    match decision {
+        DownloadDecision::Bootstrap => {
             info!("Downloading required");
 
-            let _ = init_git_repo_with_main_branch(&config_folder_path, &remote_url, branch)
+            let _ = init_git_repo_with_main_branch(config_folder_path, remote_url, branch)
                 .map_err(|e| {
                     error!("Failed to initialize git repo: {}", e);
                     e
                 });
 
-            let _ =
-                download_current_configuration(&client_config, config_folder_path, &file_format)
-                    .await;
+            let download = download_current_configuration(
+                &client_config,
+                config_folder_path,
+                &file_format,
+                &metadata_filter,
+                &role_template_filter,
+                &retry_policy,
+                include_local_cluster,
+                cluster_folder_naming,
+                prtb_listing,
+                &download_options,
+            );
+            let download = match phase_timeouts.download {
+                Some(limit) => tokio::time::timeout(limit, download)
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out after {:?}", limit))),
+                None => download.await,
+            };
+            match download {
+                Ok(download) => {
+                    info!("{}", download);
+                    written_files.extend(download.written.iter().chain(&download.moved).cloned());
+                    bootstrap_download = Some(download);
+                }
+                Err(e) => error!("Failed to download current configuration: {:#}", e),
+            }
             // init git repo
         }
-        Ok(false) => {
+        DownloadDecision::UseExisting => {
             info!("Downloading not required");
         }
-        Err(e) => {
-            error!("Failed to check if download is required: {}", e);
+        DownloadDecision::Error(e) => {
+            error!("Cannot use {} as the config repository: {}", config_folder_path.display(), e);
+            return Err(e.into());
         }
     }
 
+    // the files that did not parse in the last run, for the exit status of a single run
+    let mut ran = false;
+    let mut parse_failures: Vec<PathBuf> = Vec::new();
+    // the objects of the files parsed by the last run, unchanged files are not parsed again
+    let parse_cache = Arc::new(ParseCache::default());
+    // the version of the last run before a restart, to tell an upgrade in between
+    let mut rancher_version = SyncState::load(&state_dir).ok().and_then(|state| state.server_version);
+    if let Some(version) = &rancher_version {
+        record_server_version(&client_config, version);
+    }
+    let mut runs_since_version_check: Option<u32> = None;
+    // the files of the changes left to the next runs, kept out of the commits until they are applied
+    let mut held_back: Vec<PathBuf> = SyncState::load(&state_dir)
+        .ok()
+        .and_then(|state| state.backlog)
+        .map(|backlog| backlog.held_back)
+        .unwrap_or_default();
+    // a drifted clock gets every token refused, which looks like wrong credentials
+    if let Some(max_skew) = max_clock_skew {
+        if let Some(skew) = check_clock(&client_config, max_skew).await {
+            warn!("{}", skew);
+        }
+    }
     loop {
+        if once && ran {
+            if strict_parsing && !parse_failures.is_empty() {
+                return Err(format!("{} object files do not parse: {:?}", parse_failures.len(), parse_failures).into());
+            }
+            return Ok(());
+        }
+        ran = true;
         interval_timer.tick().await;
 
-        info!("Starting scheduled run at {}", chrono::Utc::now());
+        let run_id = new_run_id();
+        let _watchdog = stuck_run_after.map(|limit| RunWatchdog::start(task_dumper.clone(), limit));
+        // every event of the run carries the run ID, the frame shows it in task dumps
+        async_backtrace::location!().frame(async {
+            let mut report = SyncReport::new(run_id.clone());
+            info!("Starting scheduled run at {}", chrono::Utc::now());
+            // a dry run changes nothing that would need undoing
+            let mut transaction = rollback_threshold
+                .filter(|_| !dry_run)
+                .map(|_| Transaction::new(&state_dir, &report.run_id));
 
-        // Initialize repository if it doesn't exist
-        let repo = match Repository::open(&config_folder_path) {
-            Ok(repo) => repo,
-            Err(_) => {
-                info!("Repository not found, initializing...");
-                init_git_repo_with_main_branch(&config_folder_path, &remote_url, branch)?;
-                Repository::open(&config_folder_path).map_err(|e| {
-                    error!("Failed to open repository: {}", e);
-                    e
-                })?
+            let circuit = circuit_breaker.state(Instant::now());
+            if let CircuitState::Open(remaining) = circuit {
+                if !circuit_breaker.sync_git_when_open {
+                    warn!(
+                        "Endpoint {} is unavailable, skipping run, next attempt in {:?}",
+                        client_config.base_path, remaining
+                    );
+                    return Ok(());
+                }
             }
-        };
+            let run_events = events.as_ref().map(|events| events.for_run(&report.run_id));
+            emit(
+                run_events.as_ref(),
+                EventKind::RunStarted {
+                    endpoint: client_config.base_path.clone(),
+                    dry_run,
+                },
+            );
 
-        info!("Repository found");
-        info!("Pulling changes...");
-        // Pull changes
-        match pull_changes(&repo, branch, &auth_method) {
-            Ok(_) => info!("Successfully pulled changes"),
-            Err(e) => {
-                error!("Failed to pull changes: {}", e);
-                // Handle merge conflicts
-                resolve_conflicts(&repo, branch)?;
-            }
-        }
+            // held until the run is done, so another process does not take its git locks for stale ones
+            let _run_lock = match git_enabled {
+                true => match examine(config_folder_path, &repo_doctor, remote_url, &auth_method, clone_depth).await {
+                    Ok(lock) => lock,
+                    Err(e) => {
+                        error!("Skipping run, the repository needs attention: {}", e);
+                        return Ok(());
+                    }
+                },
+                false => None,
+            };
 
-        // Commit local changes
-        let now = chrono::Utc::now();
-        let datetime = now.format("%Y-%m-%d %H:%M:%S").to_string();
-        let message = format!("Updated configuration at {}", datetime);
-        commit_changes(&config_folder_path, &message)?;
+            let mut git_auth_failed = false;
+            // without git the folder is read as another tool left it
+            let repo = if git_enabled {
+                // Initialize repository if it doesn't exist
+                let repo = match Repository::open(config_folder_path) {
+                    Ok(repo) => repo,
+                    Err(_) => {
+                        info!("Repository not found, initializing...");
+                        init_git_repo_with_main_branch(config_folder_path, remote_url, branch)?;
+                        Repository::open(config_folder_path).map_err(|e| {
+                            error!("Failed to open repository: {}", e);
+                            e
+                        })?
+                    }
+                };
 
-        // Push changes
-        match push_changes(&repo, branch, &auth_method) {
-            Ok(_) => info!("Successfully pushed changes"),
-            Err(e) => error!("Failed to push changes: {}", e),
-        }
+                info!("Repository found");
+                if let Err(e) = repair_repository_state(&repo, branch) {
+                    error!("Skipping run, the repository needs attention: {}", e);
+                    return Ok(());
+                }
 
-        // let cluster_id = cluster_ids[0].clone();
+                info!("Pulling changes...");
+                // Pull changes
+                let started = Instant::now();
+                match pull_changes(&repo, branch, &auth_method, clone_depth) {
+                    Ok(_) => info!("Successfully pulled changes"),
+                    Err(e) => {
+                        error!("Failed to pull changes: {}", e);
+                        git_auth_failed = matches!(e, GitError::AuthenticationFailed { .. });
+                        if e.is_timeout() {
+                            report.record_timeout("pull");
+                        }
+                        // Handle merge conflicts
+                        resolve_conflicts(&repo, branch)?;
+                    }
+                }
+                report.record_phase("pull", started.elapsed());
 
-        for cluster_id in cluster_ids.iter() {
-            let new_files = get_new_uncommited_files(&config_folder_path).await?;
+                // the checkout above brings back every file, hide the unmanaged ones again
+                if let Err(e) = apply_sparse_checkout(&repo, &sparse_cone) {
+                    error!("Failed to apply sparse checkout: {}", e);
+                }
+                Some(repo)
+            } else {
+                None
+            };
 
-            let modified_files = get_modified_files(&config_folder_path).await?;
+            // read after pulling so changes to the patterns apply to this run
+            let mut shepherd_ignore = ShepherdIgnore::load(config_folder_path);
+            if let Some(pattern) = &local_cluster_pattern {
+                shepherd_ignore.add(pattern);
+            }
 
-            let deleted_files_and_contents =
-                get_deleted_files_and_contents(&config_folder_path).await?;
+            // files of the old format would be taken for deleted objects and written again next to them
+            if let Some(mixed) = find_mixed_formats(config_folder_path, &endpoint_folder, &file_format, &shepherd_ignore) {
+                error!("Skipping run, {}", mixed);
+                if once {
+                    return Err(format!("Mixed file formats in {}", endpoint_folder.display()).into());
+                }
+                return Ok(());
+            }
 
-            info!("New files: {:?}", new_files);
+            if dry_run {
+                info!("Dry run, not committing or pushing local changes");
+            } else if let Some(repo) = &repo {
+                // Commit local changes
+                commit_run(
+                    config_folder_path,
+                    &endpoint_folder,
+                    &mut written_files,
+                    bootstrap_download.take(),
+                    &held_back,
+                    commit_granularity,
+                    &shepherd_ignore,
+                    &mut report,
+                )?;
 
-            info!("Modified files: {:?}", modified_files);
+                // In object mode the write-back commits of this run go out in the same push
+                if commit_granularity == CommitGranularity::Run {
+                    push_run(repo, branch, &auth_method, clone_depth, &mut report);
+                }
+            }
 
-            info!(
-                "Deleted files: {:?}",
-                deleted_files_and_contents
-                    .iter()
-                    .map(|(object_type, path, _)| (object_type, path))
-                    .collect::<Vec<_>>()
-            );
+            // the repository of the run, if its commits go out at the end
+            let push_at_end = repo.as_ref().filter(|_| !dry_run && commit_granularity == CommitGranularity::Object);
+            match circuit {
+                CircuitState::Closed => {}
+                CircuitState::Open(remaining) => {
+                    warn!(
+                        "Endpoint {} is unavailable, skipping API phases, next attempt in {:?}",
+                        client_config.base_path, remaining
+                    );
+                    if let Some(repo) = push_at_end {
+                        push_run(repo, branch, &auth_method, clone_depth, &mut report);
+                    }
+                    return Ok(());
+                }
+                CircuitState::HalfOpen => match get_clusters(&client_config, None, None, None, None, None, None).await {
+                    Ok(_) => {
+                        info!("Endpoint {} is reachable again, resuming", client_config.base_path);
+                        circuit_breaker.record_success();
+                    }
+                    Err(e) => {
+                        let cooldown = circuit_breaker.record_failure(Instant::now());
+                        warn!(
+                            "Endpoint {} is still unavailable ({}), skipping API phases for {:?}",
+                            client_config.base_path, e, cooldown.unwrap_or_default()
+                        );
+                        if let Some(cooldown) = cooldown {
+                            let message = format!("Endpoint is still unavailable, pausing API calls for {:?}", cooldown);
+                            notify(notifier.as_ref(), Event::CircuitOpened, &report, &client_config.base_path, &message);
+                        }
+                        if let Some(repo) = push_at_end {
+                            push_run(repo, branch, &auth_method, clone_depth, &mut report);
+                        }
+                        return Ok(());
+                    }
+                },
+            }
+            if let Some(runs) = runs_since_version_check.as_mut() {
+                *runs += 1;
+            }
+            let check_version = match runs_since_version_check {
+                None => true,
+                Some(runs) => server_version_interval > 0 && runs >= server_version_interval,
+            };
+            if check_version {
+                match server_version(&client_config).await {
+                    Ok(version) => {
+                        if let Some(previous) = rancher_version.as_ref().filter(|previous| **previous != version) {
+                            warn!(
+                                "Rancher changed from {} to {} since it was last checked, creates and updates may need fields it added",
+                                previous, version
+                            );
+                        }
+                        record_server_version(&client_config, &version);
+                        rancher_version = Some(version);
+                        runs_since_version_check = Some(0);
+                    }
+                    Err(e) => warn!("Failed to read the version of Rancher: {:#}", e),
+                }
+            }
+            report.server_version = rancher_version.clone();
+            let mut endpoint_failed = false;
+            report.mode = mode;
+            if mode == SyncMode::Mirror {
+                if dry_run {
+                    info!("Dry run, not mirroring Rancher into the repository");
+                } else if let Some(repo) = &repo {
+                    let mirror = mirror_rancher(
+                        &client_config,
+                        config_folder_path,
+                        &endpoint_folder,
+                        &file_format,
+                        &metadata_filter,
+                        &role_template_filter,
+                        &retry_policy,
+                        include_local_cluster,
+                        cluster_folder_naming,
+                        prtb_listing,
+                        &download_options,
+                        &shepherd_ignore,
+                        phase_timeouts.download,
+                        &mut report,
+                    )
+                    .await;
+                    match mirror {
+                        Some(_) if report.mirrored.is_empty() => info!("Rancher is unchanged since the last commit"),
+                        Some(download) => {
+                            written_files.extend(download.written.iter().chain(&download.moved).cloned());
+                            commit_run(
+                                config_folder_path,
+                                &endpoint_folder,
+                                &mut written_files,
+                                Some(download),
+                                &[],
+                                commit_granularity,
+                                &shepherd_ignore,
+                                &mut report,
+                            )?;
+                            if push_at_end.is_none() {
+                                push_run(repo, branch, &auth_method, clone_depth, &mut report);
+                            }
+                        }
+                        None => endpoint_failed = true,
+                    }
+                }
+            }
+            let mut state = load_state(&state_dir, quarantine_after, dry_run);
+            // without git the changes of the folder are the ones since the last run
+            let mut previous_snapshot = state.snapshot.take().filter(|_| !git_enabled);
+            // the first run without git has no previous revisions
+            let no_snapshot = DirectorySnapshot::default();
+            // the new files found and created, and the removed files whose object was deleted
+            let mut found_new: Vec<PathBuf> = Vec::new();
+            let mut created_files: Vec<PathBuf> = Vec::new();
+            let mut deleted_files: Vec<PathBuf> = Vec::new();
+            let mut moved: Vec<PathBuf> = Vec::new();
+            let mut failures: Vec<ObjectFailure> = Vec::new();
+            check_pending_deletions(
+                &client_config,
+                &mut state.pending_deletions,
+                escalate_terminating_after,
+                &retry_policy,
+                notifier.as_ref(),
+                &mut report,
+            )
+            .await;
+            if approval.is_some() && !dry_run {
+                settle_plans(
+                    &client_config,
+                    config_folder_path,
+                    &project_readiness,
+                    &readiness,
+                    &deletion_options,
+                    &retry_policy,
+                    &mut state,
+                    &mut written_files,
+                    &mut report,
+                )
+                .await;
+            }
 
-            let _update_objects = compare_and_update_configurations(
-                client_config.clone(),
-                &config_folder_path,
-                &cluster_id,
+            if cluster_folder_naming == ClusterFolderNaming::DisplayName && !dry_run {
+                match rename_cluster_folders(&client_config, &endpoint_folder, &file_format, &retry_policy).await {
+                    Ok(folders) if folders.is_empty() => {}
+                    // known to the snapshot once the index is built
+                    Ok(folders) if !git_enabled => moved = folders,
+                    Ok(moved) => {
+                        // committed right away, so the moved files are not taken for new and deleted objects
+                        if let Err(e) = commit_changes(config_folder_path, &moved, &with_run_id("Move cluster folders after their display names", &run_id)) {
+                            error!("Failed to commit the moved cluster folders: {}", e);
+                        }
+                    }
+                    Err(e) => warn!("Failed to move renamed cluster folders: {:#}", e),
+                }
+            }
+
+            let started = Instant::now();
+            let mut index = RepoIndex::build(config_folder_path, &endpoint_folder, &shepherd_ignore).await;
+            report.record_phase("index", started.elapsed());
+            if let Some(snapshot) = previous_snapshot.as_mut() {
+                snapshot.rebase(&index, &moved);
+            }
+
+            let cluster_ids = match &clusters {
+                // a mirror run never changes Rancher, there is nothing to compare
+                _ if mode == SyncMode::Mirror => Vec::new(),
+                ClusterScope::Names(names) => names.clone(),
+                ClusterScope::Selector(selector) => match discover_clusters(
+                    &client_config,
+                    config_folder_path,
+                    &endpoint_folder,
+                    &mut index,
+                    selector,
+                    include_local_cluster,
+                    &file_format,
+                    cluster_folder_naming,
+                    &metadata_filter,
+                    &retry_policy,
+                    prtb_listing,
+                    &download_options,
+                    &run_id,
+                    phase_timeouts.download,
+                    &mut report,
+                    dry_run,
+                )
+                .await
+                {
+                    Ok(cluster_ids) => cluster_ids,
+                    Err(e) => {
+                        error!("Failed to discover clusters: {:#}", e);
+                        endpoint_failed = true;
+                        Vec::new()
+                    }
+                },
+            };
+
+            let started = Instant::now();
+            let mut stored_configs = StoredConfigs::load(
+                config_folder_path,
+                &client_config.base_path,
+                &index,
+                &cluster_ids,
                 &file_format,
+                correct_misplaced_objects,
+                parse_cache.clone(),
             )
             .await;
-            let created_objects =
-                create_objects(client_config.clone(), new_files, 10, 5, retry_delay).await;
+            report.record_phase("load", started.elapsed());
 
-            let (successes, mut errors) = handle_result_collection(created_objects);
+            // role templates are the same for every cluster, listed once per run
+            let remote_cache = RemoteCache::default();
+            // read after pulling, like the ignore patterns
+            let binding_policy = BindingPolicy::load(config_folder_path);
+            if let Err(e) = &binding_policy {
+                error!("{:#}", e);
+            }
+            // with approval the changes are planned instead of sent
+            let planning = approval.is_some() && !dry_run;
+            let mut planned: Vec<PlannedChange> = Vec::new();
+            // a plan is approved as a whole, it is not cut short
+            let mut budget = ChangeBudget::new(max_changes_per_run);
+            held_back.clear();
+            for cluster_id in cluster_ids.iter() {
+                let counts_before = report.counts();
+                if match_projects_by == ProjectMatch::DisplayName && !dry_run {
+                    match reconcile_project_ids(&client_config, &mut index, cluster_id, &file_format, &retry_policy).await {
+                        Ok(moved) if moved.is_empty() => {}
+                        Ok(moved) => {
+                            stored_configs.invalidate(&moved);
+                            // committed right away, so the moved files are not taken for new and deleted objects
+                            let message = format!("Move the projects of cluster {} recreated under new IDs", cluster_id);
+                            if !git_enabled {
+                                // moved, not new and deleted
+                                if let Some(snapshot) = previous_snapshot.as_mut() {
+                                    snapshot.rebase(&index, &moved);
+                                }
+                            } else if let Err(e) = commit_changes(config_folder_path, &moved, &with_run_id(&message, &run_id)) {
+                                error!("Failed to commit the moved projects: {}", e);
+                            }
+                        }
+                        Err(e) => warn!("Failed to match the projects of cluster {} by display name: {:#}", cluster_id, e),
+                    }
+                }
+                let mut changes = (!git_enabled).then(|| DirectorySnapshot::changes(previous_snapshot.as_ref(), &index));
+                if let Some(changes) = changes.as_mut() {
+                    // an earlier cluster of the run created or deleted them
+                    changes.new.retain(|(_, path)| !created_files.contains(path));
+                    changes.deleted.retain(|(path, _)| !deleted_files.contains(path));
+                    for (_, path) in &changes.new {
+                        if !found_new.contains(path) {
+                            found_new.push(path.clone());
+                        }
+                    }
+                }
+                let mut new_files = match changes.as_mut() {
+                    Some(changes) => std::mem::take(&mut changes.new),
+                    None => get_new_uncommited_files(config_folder_path, &shepherd_ignore).await?,
+                };
 
-            // Write back the successfully created objects
-            write_back_objects(successes, file_format).await?;
+                match check_project_display_names(&client_config, &index, cluster_id, &retry_policy).await {
+                    Ok(violations) if violations.is_empty() => {}
+                    Ok(violations) => {
+                        for violation in &violations {
+                            match duplicate_display_names {
+                                Severity::Warning => warn!("{}", violation),
+                                Severity::Error => error!("{}", violation),
+                            }
+                        }
+                        if duplicate_display_names == Severity::Error {
+                            error!("Skipping cluster {} until its projects have unique display names", cluster_id);
+                            report.failed += violations.len();
+                            report.record_cluster(cluster_id, counts_before);
+                            continue;
+                        }
+                        // never add another project with a name in use
+                        new_files.retain(|(object_type, path)| {
+                            let colliding = *object_type == ObjectType::Project
+                                && violations.iter().any(|violation| violation.paths.contains(path));
+                            if colliding {
+                                error!(
+                                    "Not creating the project in {:?}, its display name is in use. Add the {} annotation to allow it",
+                                    path, ALLOW_DUPLICATE_DISPLAY_NAME_ANNOTATION
+                                );
+                                report.failed += 1;
+                            }
+                            !colliding
+                        });
+                    }
+                    Err(e) => warn!("Failed to check the project display names of cluster {}: {:#}", cluster_id, e),
+                }
 
-            let mut objects_to_delete: Vec<(ObjectType, MinimalObject)> = Vec::new();
+                let violations = match &binding_policy {
+                    Ok(Some(policy)) => check_binding_policy(policy, &index, cluster_id, &new_files).await,
+                    Ok(None) => Vec::new(),
+                    // a broken policy holds back every new binding
+                    Err(_) => new_files
+                        .iter()
+                        .filter(|(object_type, _)| {
+                            matches!(object_type, ObjectType::ProjectRoleTemplateBinding | ObjectType::ClusterRoleTemplateBinding)
+                        })
+                        .map(|(_, path)| Violation {
+                            rule: "binding-policy",
+                            message: format!("{}: {} does not parse", path.display(), POLICY_FILE),
+                            paths: vec![path.clone()],
+                        })
+                        .collect(),
+                };
+                for violation in &violations {
+                    match binding_policy_violations {
+                        Severity::Warning => warn!("{}", violation),
+                        Severity::Error => error!("{}, not creating it", violation),
+                    }
+                }
+                if binding_policy_violations == Severity::Error && !violations.is_empty() {
+                    new_files.retain(|(_, path)| !violations.iter().any(|violation| violation.paths.contains(path)));
+                    report.failed += violations.len();
+                }
 
-            for (object_type, _path, contents) in deleted_files_and_contents {
-                let minimal_object =
-                    get_minimal_object_from_contents(object_type, &contents, &file_format)
-                        .await
-                        .unwrap();
-                objects_to_delete.push((object_type, minimal_object));
-            }
-            let deleted_objects = delete_objects(client_config.clone(), objects_to_delete).await;
-            let (_, delete_errors) = handle_result_collection(deleted_objects);
+                // Rancher would refuse them with a 422
+                let violations = check_role_templates(&client_config, &index, &new_files, &remote_cache, &retry_policy).await;
+                for violation in &violations {
+                    error!("{}", violation);
+                }
+                new_files.retain(|(_, path)| {
+                    let invalid = violations.iter().any(|violation| violation.paths.contains(path));
+                    if invalid {
+                        error!("Not creating the role template of {:?}", path);
+                        report.failed += 1;
+                    }
+                    !invalid
+                });
 
-            errors.extend(delete_errors);
-        }
-        info!("Run complete at {}", chrono::Utc::now());
-    }
-}
+                let (modified_files, mut deleted_files_and_contents, mut deleted_from_snapshot) = match changes {
+                    Some(changes) => (changes.modified, Vec::new(), changes.deleted),
+                    None => (
+                        get_modified_files(config_folder_path, &shepherd_ignore).await?,
+                        get_deleted_files_and_contents(config_folder_path, &shepherd_ignore).await?,
+                        Vec::new(),
+                    ),
+                };
 
-pub async fn is_repo_effectively_empty(repo: &Repository) -> Result<bool, GitError> {
-    let workdir = repo.workdir().ok_or_else(|| {
-        GitError::Other("Repository has no working directory (bare repo?)".to_string())
-    })?;
+                new_files.retain(|(_, path)| !skip_quarantined(&state, path));
+                // recreating an object under the ID of one still terminating fails
+                new_files.retain(|(_, path)| !skip_terminating(&state, &index, path));
+                deleted_files_and_contents.retain(|(_, path, _)| !skip_quarantined(&state, path));
+                deleted_from_snapshot.retain(|(path, _)| !skip_quarantined(&state, path));
+                // an earlier cluster of the run left them to the next runs
+                new_files.retain(|(_, path)| !held_back.contains(path));
+                deleted_files_and_contents.retain(|(_, path, _)| !held_back.contains(path));
+                deleted_from_snapshot.retain(|(path, _)| !held_back.contains(path));
+                // created by an earlier run that failed to write back their file
+                let adopted =
+                    adopt_unpersisted(&client_config, &mut state, &mut new_files, config_folder_path, file_format, dry_run, &mut report).await;
+                for path in &adopted {
+                    index.refresh(path).await;
+                }
+                stored_configs.invalidate(&adopted);
+                created_files.extend(adopted.iter().cloned());
+                written_files.extend(adopted);
 
-    let root = workdir.to_path_buf();
+                // Rancher would bind the subject twice, such as a directory group written in another case
+                match check_duplicate_bindings(&client_config, &new_files, &principal_rules, &retry_policy).await {
+                    Ok(violations) => {
+                        for violation in &violations {
+                            error!("{}, not creating it", violation);
+                        }
+                        new_files.retain(|(_, path)| !violations.iter().any(|violation| violation.paths.contains(path)));
+                        report.failed += violations.len();
+                    }
+                    Err(e) => warn!("Failed to check the new bindings of cluster {} for duplicates: {:#}", cluster_id, e),
+                }
 
-    for entry in WalkDir::new(&root)
-        .into_iter()
-        .filter_entry(|e| e.file_name() != ".git")
-        .filter_map(Result::ok)
-        .filter(|e| e.file_type().is_file())
-    {
-        let path: PathBuf = entry.path().to_path_buf();
+                info!("New files: {:?}", new_files);
 
-        // Check if file is ignored
-        let is_ignored = repo.status_should_ignore(&path).unwrap_or(false);
-        if !is_ignored {
-            return Ok(false);
-        }
-    }
+                info!("Modified files: {:?}", modified_files);
 
-    Ok(true)
-}
+                info!(
+                    "Deleted files: {:?}",
+                    deleted_files_and_contents
+                        .iter()
+                        .map(|(object_type, path, _)| (object_type, path))
+                        .chain(deleted_from_snapshot.iter().map(|(path, file)| (&file.object.object_type, path)))
+                        .collect::<Vec<_>>()
+                );
 
+                let started = Instant::now();
+                let mut out_of_scope = Vec::new();
+                let compare = compare_and_update_configurations(
+                    client_config.clone(),
+                    config_folder_path,
+                    &mut stored_configs,
+                    cluster_id,
+                    &file_format,
+                    update_strategy,
+                    &metadata_filter,
+                    &principal_rules,
+                    &role_template_filter,
+                    &retry_policy,
+                    &remote_cache,
+                    management_mode,
+                    quota_below_usage,
+                    &mut out_of_scope,
+                    &index,
+                    &state,
+                    (!git_enabled).then(|| previous_snapshot.as_ref().unwrap_or(&no_snapshot)),
+                    transaction.as_mut(),
+                    planning.then_some(&mut planned),
+                    &mut budget,
+                    run_events.as_ref(),
+                    stamp_git_commit,
+                    record_last_applied,
+                    dry_run,
+                );
+                let phase = format!("compare_{}", cluster_id);
+                let updated_objects = within_timeout(&phase, phase_timeouts.compare, &mut report, compare).await;
+                endpoint_failed |= updated_objects.is_none();
+                let (updated, update_errors) = handle_result_collection(updated_objects.unwrap_or_default());
+                for object in &updated {
+                    let path = object
+                        .metadata()
+                        .and_then(|(object_type, metadata)| {
+                            index.path(object_type, metadata.namespace.as_deref(), metadata.name.as_deref()?)
+                        })
+                        .map(Path::to_path_buf);
+                    if let Some(object) = returned_object(cluster_id, object, path) {
+                        emit(run_events.as_ref(), EventKind::ObjectApplied { action: Action::Update, object });
+                    }
+                }
+                let failed_before = failures.len();
+                failures.extend(update_errors.iter().filter_map(|e| {
+                    let failed = failed_object(e)?;
+                    Some(ObjectFailure {
+                        path: failed.path.clone()?,
+                        object_type: failed.object_type,
+                        object_id: Some(failed.object_id.clone()),
+                        error: format!("{:#}", e),
+                    })
+                }));
+                emit_failures(run_events.as_ref(), cluster_id, Action::Update, &failures[failed_before..]);
+                record_parse_failures(&update_errors, strict_parsing, &mut report);
+                report.record_permission_errors(&update_errors);
+                endpoint_failed |= update_errors.iter().any(is_transient);
+                report.record_updated(&updated);
+                report.failed += update_errors.len();
+                report.record_phase(&format!("compare_{}", cluster_id), started.elapsed());
 
-    /// Checks if a download of the remote repository is required by checking if the local config
-    /// folder is empty. If the folder is empty, it clones the remote repository into the folder
-    /// and checks if the repository is empty after cloning. If the repository is empty, it returns
-    /// true, indicating that a download is required. If the folder is not empty, it returns false.
-    /// If an error occurs during the check, it returns true.
-    ///
-    /// # Arguments
-    /// * `config_folder_path` - Path to the local config folder
-    /// * `remote_url` - URL of the remote repository
-    /// * `auth_method` - Authentication method to use when cloning the repository
-    ///
-    /// # Returns
-    /// * `Result<bool, Box<dyn std::error::Error>>` - Result indicating whether a download is required
-async fn download_required(
-    config_folder_path: &Path,
-    remote_url: &str,
-    auth_method: &GitAuth,
-) -> Result<bool, Box<dyn std::error::Error>> {
-    match is_directory_empty(config_folder_path).await {
-        Ok(true) => {
-            info!("Directory is empty: {}", config_folder_path.display());
-            // Clone the remote repository into the empty directory
-            let cloned = safe_clone_repository(config_folder_path, remote_url, auth_method).await;
-            // handle error
-            match cloned {
-                Ok(repo) => {
-                    info!("Repository cloned successfully: {}", repo.path().display());
+                // roles and projects before their bindings, then by path, so each run takes and plans the same files
+                new_files.sort_by(|(a_type, a), (b_type, b)| (a_type.priority(), a).cmp(&(b_type.priority(), b)));
+                if !planning {
+                    let (applied, deferred) = budget.take(Action::Create, std::mem::take(&mut new_files));
+                    new_files = applied;
+                    for (object_type, path) in deferred {
+                        debug!("Leaving the {:?} of {} to the next runs", object_type, path.display());
+                        held_back.push(path);
+                    }
+                }
+                for (object_type, path) in &new_files {
+                    let object = ObjectRef {
+                        object_type: *object_type,
+                        cluster_id: cluster_id.clone(),
+                        object_id: None,
+                        namespace: None,
+                        path: Some(path.clone()),
+                    };
+                    emit(run_events.as_ref(), EventKind::ObjectPlanned { action: Action::Create, object });
+                }
+                if planning {
+                    for (object_type, path) in std::mem::take(&mut new_files) {
+                        match tokio::fs::read_to_string(&path).await {
+                            Ok(contents) => planned.push(PlannedChange::Create { object_type, path, contents }),
+                            Err(e) => {
+                                error!("Failed to read {} to plan its creation: {}", path.display(), e);
+                                report.failed += 1;
+                            }
+                        }
+                    }
+                }
+
+                let started = Instant::now();
+                let attempted = new_files.clone();
+                let create = create_objects(
+                    client_config.clone(),
+                    new_files,
+                    10,
+                    5,
+                    retry_delay,
+                    &project_readiness,
+                    &readiness,
+                    stamp_git_commit,
+                    record_last_applied,
+                    correct_misplaced_objects,
+                    dry_run,
+                );
+                let created_objects =
+                    within_timeout(&format!("create_{}", cluster_id), phase_timeouts.create, &mut report, create).await;
+                let create_timed_out = created_objects.is_none();
+                endpoint_failed |= create_timed_out;
+
+                let (successes, mut errors) = handle_result_collection(created_objects.unwrap_or_default());
+                record_parse_failures(&errors, strict_parsing, &mut report);
+                let failed_before = failures.len();
+                // every file either created an object or failed
+                for (object_type, path) in attempted {
+                    if successes.iter().any(|(created, _)| *created == path) {
+                        continue;
+                    }
+                    let error = errors
+                        .iter()
+                        .map(|e| format!("{:#}", e))
+                        .find(|e| e.contains(&path.display().to_string()))
+                        .unwrap_or_else(|| match create_timed_out {
+                            true => "The create phase timed out".to_string(),
+                            false => "Failed to create the object".to_string(),
+                        });
+                    failures.push(ObjectFailure { path, object_type, object_id: None, error });
+                }
+                emit_failures(run_events.as_ref(), cluster_id, Action::Create, &failures[failed_before..]);
+                report.record_phase("create", started.elapsed());
+                let ignored = count_ignored(successes.iter().map(|(_, object)| object));
+                let successes: Vec<_> = successes
+                    .into_iter()
+                    .filter(|(_, object)| !matches!(object, CreatedObject::Ignored(..)))
+                    .collect();
+                report.ignored += ignored;
+                report.created += successes.len();
+                created_files.extend(successes.iter().map(|(path, _)| path.clone()));
+                for (path, object) in &successes {
+                    if let Some(object) = returned_object(cluster_id, object, Some(path.clone())) {
+                        emit(run_events.as_ref(), EventKind::ObjectApplied { action: Action::Create, object });
+                    }
+                }
 
-                    let repo = Repository::open(config_folder_path)?;
-                    if is_repo_effectively_empty(&repo).await? {
-                        info!("Repository is empty after cloning (ignoring .git and .gitignored files)");
-                        return Ok(true);
+                // Dry-run responses describe objects that were never persisted
+                if dry_run {
+                    info!("Dry run, {} objects would have been created", successes.len());
+                } else {
+                    if let Some(transaction) = transaction.as_mut() {
+                        record_creations(transaction, &successes).await;
                     }
+                    // Write back the successfully created objects
+                    let started = Instant::now();
+                    let write_back = write_back_objects(successes, config_folder_path, file_format).await?;
+                    for path in &write_back.written {
+                        index.refresh(path).await;
+                    }
+                    for (object_id, creation) in &write_back.failed {
+                        error!(
+                            "Created {:?} `{}` but failed to write back {}, the next run adopts it",
+                            creation.object_type,
+                            object_id,
+                            creation.path.display()
+                        );
+                        report.unpersisted.push(creation.path.clone());
+                    }
+                    state.record_write_back(&write_back.written, write_back.failed);
+                    // role templates are written to the folder every cluster loads
+                    stored_configs.invalidate(&write_back.written);
+                    written_files.extend(write_back.written);
+                    report.record_phase("write_back", started.elapsed());
+                }
+
+                let mut objects_to_delete: Vec<(ObjectType, MinimalObject)> = Vec::new();
+                let mut deletions: Vec<(ObjectType, Mutation)> = Vec::new();
+                // the file of each object to delete by its type, ID and namespace
+                let mut deleted_paths: Vec<(ObjectType, Option<String>, Option<String>, PathBuf)> = Vec::new();
 
-                    Ok(false)
+                for (object_type, path, contents) in deleted_files_and_contents {
+                    if contents_have_ignore_directive(&contents, &file_format) {
+                        info!("Not deleting {:?}, its file carried the ignore directive", path);
+                        report.ignored += 1;
+                        continue;
+                    }
+                    match get_object_to_delete(object_type, &path, &contents, &file_format, &deletable_types).await {
+                        Ok(minimal_object) => {
+                            deleted_paths.push((
+                                object_type,
+                                minimal_object.object_id.clone(),
+                                minimal_object.namespace.clone(),
+                                path.clone(),
+                            ));
+                            objects_to_delete.push((object_type, minimal_object));
+                            let mutation = Mutation::Deleted {
+                                object_type,
+                                path: path.clone(),
+                                contents: contents.clone(),
+                                file_format,
+                            };
+                            deletions.push((object_type, mutation));
+                        }
+                        Err(DeletionError::Malformed { object_type, source }) => {
+                            note_parse_failure(&path, &source, strict_parsing, &mut report);
+                            if !strict_parsing {
+                                warn!("Not deleting the {:?} of {}", object_type, path.display());
+                            }
+                            report.failed += 1;
+                        }
+                        Err(e @ (DeletionError::Unsupported { .. } | DeletionError::NotAllowed { .. })) => {
+                            warn!("{}", e);
+                            report.ignored += 1;
+                        }
+                    }
                 }
-                Err(e) => {
-                    error!("Failed to clone repository: {}", e);
-                    Ok(true)
+                for (path, file) in deleted_from_snapshot {
+                    if file.ignored {
+                        info!("Not deleting {:?}, its file carried the ignore directive", path);
+                        report.ignored += 1;
+                        continue;
+                    }
+                    match snapshot_object_to_delete(&path, &file, &deletable_types) {
+                        // without git there are no contents to restore the object from on rollback
+                        Ok(minimal_object) => {
+                            deleted_paths.push((
+                                file.object.object_type,
+                                minimal_object.object_id.clone(),
+                                minimal_object.namespace.clone(),
+                                path,
+                            ));
+                            objects_to_delete.push((file.object.object_type, minimal_object));
+                        }
+                        Err(e) => {
+                            warn!("{}", e);
+                            report.ignored += 1;
+                        }
+                    }
+                }
+                if prune {
+                    out_of_scope.sort_by(|(a_type, a), (b_type, b)| (a_type, &a.object_id).cmp(&(b_type, &b.object_id)));
+                    // without a file there is nothing to restore them from on rollback
+                    for (object_type, minimal_object) in out_of_scope {
+                        if !deletable_types.contains(&object_type) {
+                            debug!(
+                                "Not pruning {:?} `{}`, {:?} is not in deletable_types",
+                                object_type,
+                                minimal_object.object_id.as_deref().unwrap_or_default(),
+                                object_type
+                            );
+                            continue;
+                        }
+                        info!(
+                            "Pruning {:?} `{}`, it has no file",
+                            object_type,
+                            minimal_object.object_id.as_deref().unwrap_or_default()
+                        );
+                        objects_to_delete.push((object_type, minimal_object));
+                    }
+                } else if !out_of_scope.is_empty() {
+                    debug!("Leaving {} bindings without a file alone", out_of_scope.len());
+                }
+                if !planning {
+                    // bindings before their projects and roles, then by ID, so each run takes the same objects
+                    objects_to_delete.sort_by(|(a_type, a), (b_type, b)| {
+                        (std::cmp::Reverse(a_type.priority()), &a.namespace, &a.object_id)
+                            .cmp(&(std::cmp::Reverse(b_type.priority()), &b.namespace, &b.object_id))
+                    });
+                    let (applied, deferred) = budget.take(Action::Delete, std::mem::take(&mut objects_to_delete));
+                    objects_to_delete = applied;
+                    for (object_type, object) in deferred {
+                        debug!(
+                            "Leaving the deletion of {:?} `{}` to the next runs",
+                            object_type,
+                            object.object_id.as_deref().unwrap_or_default()
+                        );
+                        if let Some(index) = deleted_paths.iter().position(|(deleted_type, object_id, namespace, _)| {
+                            (*deleted_type, object_id, namespace) == (object_type, &object.object_id, &object.namespace)
+                        }) {
+                            held_back.push(deleted_paths.remove(index).3);
+                        }
+                    }
+                    deletions.retain(|(_, mutation)| match mutation {
+                        Mutation::Deleted { path, .. } => !held_back.contains(path),
+                        _ => true,
+                    });
+                }
+
+                for (object_type, object) in &objects_to_delete {
+                    let path = deleted_path(&deleted_paths, *object_type, object);
+                    let object = ObjectRef {
+                        object_type: *object_type,
+                        cluster_id: cluster_id.clone(),
+                        object_id: object.object_id.clone(),
+                        namespace: object.namespace.clone(),
+                        path,
+                    };
+                    emit(run_events.as_ref(), EventKind::ObjectPlanned { action: Action::Delete, object });
+                }
+                if planning {
+                    for (object_type, object) in std::mem::take(&mut objects_to_delete) {
+                        let path = deleted_path(&deleted_paths, object_type, &object);
+                        let Some(object_id) = object.object_id else {
+                            continue;
+                        };
+                        let resource_version =
+                            match live_resource_version(&client_config, object_type, &object_id, object.namespace.as_deref()).await {
+                                Ok(resource_version) => resource_version,
+                                Err(e) => {
+                                    debug!("Not planning the deletion of {:?} `{}`: {:#}", object_type, object_id, e);
+                                    continue;
+                                }
+                            };
+                        planned.push(PlannedChange::Delete {
+                            object_type,
+                            object_id,
+                            namespace: object.namespace,
+                            resource_version,
+                            path,
+                        });
+                    }
+                    deletions.clear();
+                    deleted_paths.clear();
+                }
+
+                let started = Instant::now();
+                let requested_deletions = objects_to_delete.clone();
+                let delete = delete_objects(
+                    client_config.clone(),
+                    objects_to_delete,
+                    skip_cascaded_deletes,
+                    &deletion_options,
+                    &retry_policy,
+                    dry_run,
+                );
+                let deleted_objects =
+                    within_timeout(&format!("delete_{}", cluster_id), phase_timeouts.delete, &mut report, delete).await;
+                let delete_timed_out = deleted_objects.is_none();
+                endpoint_failed |= delete_timed_out;
+                let failed_before = failures.len();
+                if delete_timed_out {
+                    // which deletions went through is unknown, their files stay pending drift
+                    failures.extend(deleted_paths.iter().map(|(object_type, object_id, _, path)| ObjectFailure {
+                        path: path.clone(),
+                        object_type: *object_type,
+                        object_id: object_id.clone(),
+                        error: "The delete phase timed out".to_string(),
+                    }));
+                }
+                let (deleted, delete_errors) = handle_result_collection(deleted_objects.unwrap_or_default());
+                report.record_phase("delete", started.elapsed());
+                failures.extend(delete_errors.iter().filter_map(|e| {
+                    let failed = failed_object(e)?;
+                    let (_, _, _, path) = deleted_paths.iter().find(|(object_type, object_id, namespace, _)| {
+                        (*object_type, object_id.as_deref(), namespace)
+                            == (failed.object_type, Some(failed.object_id.as_str()), &failed.namespace)
+                    })?;
+                    Some(ObjectFailure {
+                        path: path.clone(),
+                        object_type: failed.object_type,
+                        object_id: Some(failed.object_id.clone()),
+                        error: format!("{:#}", e),
+                    })
+                }));
+                emit_failures(run_events.as_ref(), cluster_id, Action::Delete, &failures[failed_before..]);
+                if let Some(transaction) = transaction.as_mut() {
+                    // a failed deletion is recorded as well, recreating an object that exists is a no-op
+                    // undone in reverse, so projects are recreated before their bindings
+                    deletions.sort_by_key(|(object_type, _)| std::cmp::Reverse(object_type.priority()));
+                    for (_, mutation) in deletions {
+                        if let Err(e) = transaction.record(mutation) {
+                            error!("Failed to back up a deletion: {:#}", e);
+                        }
+                    }
+                }
+                report.record_deleted(&deleted);
+                let failed_deletions: Vec<&FailedObject> = delete_errors.iter().filter_map(failed_object).collect();
+                let deletion_failed = |object_type: ObjectType, object: &MinimalObject| {
+                    failed_deletions.iter().any(|failed| {
+                        (failed.object_type, Some(failed.object_id.as_str()), &failed.namespace)
+                            == (object_type, object.object_id.as_deref(), &object.namespace)
+                    })
+                };
+                if !delete_timed_out {
+                    for (object_type, object) in &requested_deletions {
+                        if object.object_id.is_none() || deletion_failed(*object_type, object) {
+                            continue;
+                        }
+                        let path = deleted_path(&deleted_paths, *object_type, object);
+                        deleted_files.extend(path.iter().cloned());
+                        let object = ObjectRef {
+                            object_type: *object_type,
+                            cluster_id: cluster_id.clone(),
+                            object_id: object.object_id.clone(),
+                            namespace: object.namespace.clone(),
+                            path,
+                        };
+                        emit(run_events.as_ref(), EventKind::ObjectApplied { action: Action::Delete, object });
+                    }
+                }
+                // objects waited for are known to be gone
+                if !dry_run && deletion_options.wait_for_deletion.is_none() && !delete_timed_out {
+                    let issued: Vec<_> = requested_deletions
+                        .into_iter()
+                        .filter(|(object_type, object)| !deletion_failed(*object_type, object))
+                        .collect();
+                    state.record_deletions(&issued, chrono::Utc::now());
                 }
+
+                errors.extend(delete_errors);
+                report.record_permission_errors(&errors);
+                endpoint_failed |= errors.iter().any(is_transient);
+                report.failed += errors.len();
+                report.record_cluster(cluster_id, counts_before);
             }
-        }
-        Ok(false) => {
-            info!("Directory is not empty: {}", config_folder_path.display());
-            // Handle non-empty directory case
-            Ok(false)
-        }
-        Err(e) => {
-            error!("Error checking directory: {}", e);
-            // Handle error
-            Ok(true)
-        }
+
+            // the mirror writes the index with its download
+            if mode != SyncMode::Mirror && !dry_run {
+                let index = refresh_role_template_index(
+                    &client_config,
+                    &endpoint_folder,
+                    &file_format,
+                    &role_template_filter,
+                    &remote_cache,
+                    &retry_policy,
+                )
+                .await;
+                match index {
+                    Ok(Some(path)) => {
+                        info!("Updated the role template index {}", path.display());
+                        written_files.push(path);
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("Failed to update the role template index: {:#}", e),
+                }
+            }
+
+            if let (Some(approval), true, Some(repo)) = (approval, planning, &repo) {
+                plan_changes(
+                    config_folder_path,
+                    repo,
+                    &client_config.base_path,
+                    file_format,
+                    approval,
+                    planned,
+                    branch,
+                    &auth_method,
+                    &mut written_files,
+                    &report,
+                );
+            }
+
+            for failure in &failures {
+                if !report.pending_drift.contains(&failure.path) {
+                    report.pending_drift.push(failure.path.clone());
+                }
+            }
+            let snapshot = (!git_enabled && !dry_run).then(|| {
+                let uncreated: Vec<PathBuf> = found_new.into_iter().filter(|path| !created_files.contains(path)).collect();
+                let mut snapshot = DirectorySnapshot::of(&index);
+                snapshot.carry_over(previous_snapshot.as_ref(), &uncreated, &deleted_files);
+                snapshot
+            });
+            report.deferred = budget.deferred();
+            let resumed = state.backlog.is_some();
+            let backlog = ChangeBacklog::advance(
+                state.backlog.take(),
+                &report.run_id,
+                budget.applied(),
+                budget.deferred(),
+                held_back.clone(),
+            );
+            match &backlog {
+                Some(backlog) => info!("{}, continuing next run", backlog),
+                None if resumed => info!("Applied the last changes left by earlier runs"),
+                None => {}
+            }
+            report.quarantined = update_state(
+                &state_dir,
+                &report,
+                &failures,
+                std::mem::take(&mut state.pending_deletions),
+                std::mem::take(&mut state.unpersisted),
+                snapshot,
+                backlog,
+                quarantine_after,
+                dry_run,
+            );
+
+            if let (Some(threshold), Some(transaction)) = (rollback_threshold, transaction) {
+                settle_transaction(transaction, threshold, &client_config, &retry_policy, &mut written_files, &mut report)
+                    .await;
+            }
+
+            if !git_enabled {
+                // nothing commits them
+                written_files.clear();
+            }
+            if let Some(repo) = push_at_end {
+                // the ids Rancher assigned to created objects, committed before the single push
+                commit_run(
+                    config_folder_path,
+                    &endpoint_folder,
+                    &mut written_files,
+                    None,
+                    &held_back,
+                    commit_granularity,
+                    &shepherd_ignore,
+                    &mut report,
+                )?;
+                push_run(repo, branch, &auth_method, clone_depth, &mut report);
+            }
+
+            if !endpoint_failed {
+                circuit_breaker.record_success();
+            } else if let Some(cooldown) = circuit_breaker.record_failure(Instant::now()) {
+                warn!(
+                    "Endpoint {} keeps failing, pausing API phases for {:?}",
+                    client_config.base_path, cooldown
+                );
+                let message = format!("Endpoint keeps failing, pausing API calls for {:?}", cooldown);
+                notify(notifier.as_ref(), Event::CircuitOpened, &report, &client_config.base_path, &message);
+            }
+            if strict_parsing && !report.parse_failures.is_empty() {
+                error!(
+                    "Run failed, {} object files do not parse: {:?}",
+                    report.parse_failures.len(),
+                    report.parse_failures
+                );
+            }
+            let auth_failures = report
+                .permission_denials
+                .iter()
+                .filter(|denial| denial.status == 401)
+                .map(|denial| denial.calls)
+                .sum::<usize>()
+                + usize::from(git_auth_failed);
+            let clock_skew = match max_clock_skew {
+                Some(max_skew) if auth_failures >= AUTH_FAILURES_BEFORE_CLOCK_CHECK => check_clock(&client_config, max_skew).await,
+                _ => None,
+            };
+            if let Some(skew) = &clock_skew {
+                error!("{}", skew);
+            }
+            // with a skewed clock, refused tokens say nothing about the permissions of the account
+            let denials = report
+                .permission_denials
+                .iter()
+                .filter(|denial| clock_skew.is_none() || denial.status != 401);
+            for denial in denials {
+                error!("Permission problem: {}", denial);
+            }
+            summarize_repeated_warnings();
+            report.sort();
+            report.finished_at = Some(chrono::Utc::now());
+            notify_run(notifier.as_ref(), &report, &client_config.base_path, dry_run);
+            if let Some(run_events) = &run_events {
+                run_events.emit(EventKind::RunCompleted {
+                    status: report.status,
+                    created: report.created,
+                    updated: report.updated,
+                    deleted: report.deleted,
+                    failed: report.failed,
+                    dropped_events: run_events.dropped(),
+                });
+            }
+            info!("Run complete at {}", chrono::Utc::now());
+            info!("Run summary: {}", report);
+            if !dry_run {
+                if let Err(e) = report.save(&state_dir) {
+                    warn!("Failed to save the report of the run: {:#}", e);
+                }
+            }
+            parse_failures = report.parse_failures;
+            Ok::<(), Box<dyn std::error::Error>>(())
+        })
+        .instrument(info_span!("run", %run_id))
+        .await?;
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    //Setup logging
+/// Notify about `event` of the run of `report`, if notifications are configured.
+fn notify(notifier: Option<&Notifier>, event: Event, report: &SyncReport, endpoint: &str, message: &str) {
+    if let Some(notifier) = notifier {
+        notifier.notify(&Notification {
+            event,
+            run_id: &report.run_id,
+            endpoint,
+            message,
+        });
+    }
+}
 
-    init_tracing();
+/// Emit `kind` to the event stream of the run, if one is configured.
+fn emit(events: Option<&RunEvents>, kind: EventKind) {
+    if let Some(events) = events {
+        events.emit(kind);
+    }
+}
 
-    // get home path and concatenate with .config/shepherd/config.toml
-    let home_path = std::env::var("HOME")
-        .map_err(|_| AppError::Other("HOME environment variable not set".to_string()))?;
-    let app_config_path = home_path + "/.config/shepherd/config.toml";
-    let app_config = ShepherdConfig::from_file(&app_config_path).map_err(|e| {
-        error!("Failed to load config: {}", e);
-        AppError::Other("Failed to load config".to_string())
+/// Emit an `object_failed` event for each of `failures`, which failed to be changed by `action`.
+fn emit_failures(events: Option<&RunEvents>, cluster_id: &str, action: Action, failures: &[ObjectFailure]) {
+    for failure in failures {
+        let object = ObjectRef {
+            object_type: failure.object_type,
+            cluster_id: cluster_id.to_string(),
+            object_id: failure.object_id.clone(),
+            namespace: None,
+            path: Some(failure.path.clone()),
+        };
+        emit(events, EventKind::ObjectFailed { action, object, error: failure.error.clone() });
+    }
+}
+
+/// The object of the file at `path` as Rancher returned it, `None` for statuses and skipped objects.
+fn returned_object(cluster_id: &str, object: &CreatedObject, path: Option<PathBuf>) -> Option<ObjectRef> {
+    let (object_type, metadata) = object.metadata()?;
+    Some(ObjectRef {
+        object_type,
+        cluster_id: cluster_id.to_string(),
+        object_id: metadata.name.clone(),
+        namespace: metadata.namespace.clone(),
+        path,
+    })
+}
+
+/// The file of the deletion of `object`, matched on its type, ID and namespace.
+fn deleted_path(
+    deleted_paths: &[(ObjectType, Option<String>, Option<String>, PathBuf)],
+    object_type: ObjectType,
+    object: &MinimalObject,
+) -> Option<PathBuf> {
+    deleted_paths
+        .iter()
+        .find(|(deleted_type, object_id, namespace, _)| {
+            (*deleted_type, object_id, namespace) == (object_type, &object.object_id, &object.namespace)
+        })
+        .map(|(_, _, _, path)| path.clone())
+}
+
+/// Notify about the failures, deletions and drift of the finished run of `report`.
+fn notify_run(notifier: Option<&Notifier>, report: &SyncReport, endpoint: &str, dry_run: bool) {
+    let prefix = if dry_run { "Dry run: " } else { "" };
+    if report.failed > 0 || report.status != RunStatus::Completed {
+        let message = format!("{}{} failures, {}", prefix, report.failed, report);
+        notify(notifier, Event::RunFailed, report, endpoint, &message);
+    }
+    if report.deleted + report.cascade_deleted > 0 {
+        let message = format!(
+            "{}Deleted {} objects, {} more with their project",
+            prefix, report.deleted, report.cascade_deleted
+        );
+        notify(notifier, Event::Deletions, report, endpoint, &message);
+    }
+    if report.updated > 0 {
+        let message = format!("{}Updated {} objects that drifted from their files", prefix, report.updated);
+        notify(notifier, Event::Drift, report, endpoint, &message);
+    }
+    if !report.mirrored.is_empty() {
+        let message = format!("Rancher changed since the last commit, mirrored {}", report.mirrored);
+        notify(notifier, Event::Drift, report, endpoint, &message);
+    }
+}
+
+/// The state of the objects failing in earlier runs, with the quarantined objects whose file
+/// changed released, and of the objects deleted in earlier runs. Without a `quarantine_after`
+/// no object is failing.
+fn load_state(state_dir: &Path, quarantine_after: u32, dry_run: bool) -> SyncState {
+    let mut state = SyncState::load(state_dir).unwrap_or_else(|e| {
+        warn!("Starting without the failures and deletions of earlier runs: {:#}", e);
+        SyncState::default()
     });
+    if quarantine_after == 0 {
+        state.objects.clear();
+        return state;
+    }
+    let released = state.release_changed();
+    for path in &released {
+        info!("Releasing {} from quarantine, the file changed", path.display());
+    }
+    if !released.is_empty() && !dry_run {
+        if let Err(e) = state.save(state_dir) {
+            warn!("{:#}", e);
+        }
+    }
+    state
+}
 
-    // if we have an error spit out the error and exit
-    let app_config = match app_config {
-        Ok(config) => config,
+/// Whether the object of the file at `path` is quarantined and left out of the run.
+fn skip_quarantined(state: &SyncState, path: &Path) -> bool {
+    let quarantined = state.is_quarantined(path);
+    if quarantined {
+        debug!("Skipping quarantined {}", path.display());
+    }
+    quarantined
+}
+
+/// Count the `failures` of the run of `report` in the state file and quarantine the objects failing
+/// too often, and store the deletions that may still be terminating, the creations not written
+/// back, the refused API calls, the `snapshot` of the folder when git is off and the `backlog`
+/// of changes left to the next runs.
+///
+/// The state is read again so objects released while the run was going stay released. A dry
+/// run counts nothing, without a `quarantine_after` only the deletions are stored. Returns the
+/// number of quarantined objects.
+#[allow(clippy::too_many_arguments)]
+fn update_state(
+    state_dir: &Path,
+    report: &SyncReport,
+    failures: &[ObjectFailure],
+    pending_deletions: Vec<PendingDeletion>,
+    unpersisted: BTreeMap<String, UnpersistedCreation>,
+    snapshot: Option<DirectorySnapshot>,
+    backlog: Option<ChangeBacklog>,
+    quarantine_after: u32,
+    dry_run: bool,
+) -> usize {
+    let mut state = match SyncState::load(state_dir) {
+        Ok(state) => state,
         Err(e) => {
-            error!("{}", e);
-            std::process::exit(1);
+            warn!("Not counting the failures of the run: {:#}", e);
+            return 0;
         }
     };
+    if dry_run {
+        return state.quarantined().count();
+    }
 
-    debug!("App config: {}", app_config);
+    if quarantine_after > 0 {
+        for path in state.record_run(failures, quarantine_after) {
+            warn!(
+                "Quarantined {} until the file changes or `shepherd unquarantine` is run: {}",
+                path.display(),
+                state.objects[&path]
+            );
+        }
+    }
+    state.pending_deletions = pending_deletions;
+    state.unpersisted = unpersisted;
+    if snapshot.is_some() {
+        state.snapshot = snapshot;
+    }
+    state.backlog = backlog;
+    state.last_run_id = Some(report.run_id.clone());
+    state.mode = report.mode;
+    state.permission_denials = report.permission_denials.clone();
+    if report.server_version.is_some() {
+        state.server_version = report.server_version.clone();
+    }
+    if let Err(e) = state.save(state_dir) {
+        warn!("{:#}", e);
+    }
+    let metrics = state.metrics() + &repeated_warning_metrics() + &healing_metrics();
+    if let Err(e) = write_atomically(&state_dir.join(METRICS_FILE), &metrics) {
+        warn!("{:#}", e);
+    }
+    if quarantine_after == 0 {
+        return 0;
+    }
+    state.quarantined().count()
+}
 
+/// Check which objects deleted in earlier runs Rancher still has, forgetting the ones that are gone.
+///
+/// The objects still there are reported as stuck terminating. Past `escalate_after`, an object is
+/// logged as an error and notified about, once.
+async fn check_pending_deletions(
+    configuration: &Configuration,
+    pending_deletions: &mut Vec<PendingDeletion>,
+    escalate_after: Option<Duration>,
+    retry_policy: &RetryPolicy,
+    notifier: Option<&Notifier>,
+    report: &mut SyncReport,
+) {
+    let now = chrono::Utc::now();
+    let mut terminating = Vec::with_capacity(pending_deletions.len());
+    for mut pending in std::mem::take(pending_deletions) {
+        let namespace = pending.namespace.clone().unwrap_or_default();
+        let exists = retry_transient("object_exists", retry_policy, || {
+            object_exists(configuration, pending.object_type, &namespace, &pending.object_id)
+        })
+        .await;
+        match exists {
+            Ok(false) => {
+                debug!("{} is gone", pending);
+                continue;
+            }
+            Ok(true) => {}
+            Err(e) => debug!("Failed to check whether {} is gone: {:#}", pending, e),
+        }
+        let terminating_for = pending.terminating_for(now);
+        let minutes = terminating_for.as_secs() / 60;
+        report.stuck_terminating.push(StuckObject {
+            object_type: pending.object_type,
+            object_id: pending.object_id.clone(),
+            minutes,
+        });
+        match escalate_after {
+            Some(threshold) if terminating_for >= threshold && !pending.escalated => {
+                error!("{} is stuck terminating for {} minutes", pending, minutes);
+                let message = format!("{} is stuck terminating for {} minutes", pending, minutes);
+                notify(notifier, Event::StuckTerminating, report, &configuration.base_path, &message);
+                pending.escalated = true;
+            }
+            _ => warn_repeated(
+                "Stuck terminating",
+                &pending,
+                format_args!("{} is stuck terminating for {} minutes", pending, minutes),
+            ),
+        }
+        terminating.push(pending);
+    }
+    *pending_deletions = terminating;
+}
 
-    let auth_method = app_config.auth_method;
-    let branch = app_config.branch;
-    let cluster_ids = app_config.cluster_names.unwrap();
-    let config_folder_path = app_config.rancher_config_path;
-    let endpoint_url = app_config.endpoint_url;
-    let file_format = app_config.file_format;
-    let insecure = app_config.insecure;
+/// Whether the file at `path` is for an object whose deletion is still terminating, and is
+/// left out of the run's creations.
+fn skip_terminating(state: &SyncState, index: &RepoIndex, path: &Path) -> bool {
+    let Some(key) = index.key(path) else {
+        return false;
+    };
+    let terminating = state.pending_deletion(key.object_type, key.namespace.as_deref(), &key.object_id);
+    if let Some(pending) = terminating {
+        warn_repeated(
+            "Not creating, still terminating",
+            path.display(),
+            format_args!("Not creating {}, {} is still terminating", path.display(), pending),
+        );
+    }
+    terminating.is_some()
+}
+
+/// Write the objects created by an earlier run that failed to write back their file to those
+/// files of `new_files`, instead of creating them again.
+///
+/// An object gone from Rancher is created again from its file, one that cannot be read is
+/// left for a later run. Returns the files written.
+async fn adopt_unpersisted(
+    configuration: &Configuration,
+    state: &mut SyncState,
+    new_files: &mut Vec<(ObjectType, PathBuf)>,
+    config_folder_path: &Path,
+    file_format: FileFormat,
+    dry_run: bool,
+    report: &mut SyncReport,
+) -> Vec<PathBuf> {
+    let adoptions: Vec<(String, UnpersistedCreation)> = new_files
+        .iter()
+        .filter_map(|(_, path)| state.unpersisted_at(path))
+        .map(|(object_id, creation)| (object_id.clone(), creation.clone()))
+        .collect();
+    let mut adopted = Vec::new();
+    for (object_id, creation) in adoptions {
+        let object_type = creation.object_type;
+        let fetched = fetch_created_object(configuration, object_type, &object_id, creation.namespace.as_deref()).await;
+        let object = match fetched {
+            Ok(object) => object,
+            Err(e) if e.to_string().contains("not found") => {
+                info!(
+                    "{:?} `{}` created from {} by an earlier run is gone, creating it again",
+                    object_type,
+                    object_id,
+                    creation.path.display()
+                );
+                state.unpersisted.remove(&object_id);
+                continue;
+            }
+            Err(e) => {
+                warn!(
+                    "Not creating {} again, failed to get the {:?} `{}` created from it: {:#}",
+                    creation.path.display(),
+                    object_type,
+                    object_id,
+                    e
+                );
+                new_files.retain(|(_, path)| *path != creation.path);
+                report.unpersisted.push(creation.path);
+                continue;
+            }
+        };
+        new_files.retain(|(_, path)| *path != creation.path);
+        if dry_run {
+            info!("Dry run, {:?} `{}` would be adopted into {}", object_type, object_id, creation.path.display());
+            continue;
+        }
+        info!("Adopting {:?} `{}` created by an earlier run into {}", object_type, object_id, creation.path.display());
+        match write_back_objects(vec![(creation.path.clone(), object)], config_folder_path, file_format).await {
+            Ok(write_back) => {
+                report.adopted += write_back.written.len();
+                report.unpersisted.extend(write_back.failed.iter().map(|(_, creation)| creation.path.clone()));
+                state.record_write_back(&write_back.written, write_back.failed);
+                adopted.extend(write_back.written);
+            }
+            Err(e) => {
+                error!("Failed to adopt {:?} `{}`: {:#}", object_type, object_id, e);
+                report.unpersisted.push(creation.path);
+            }
+        }
+    }
+    adopted
+}
+
+/// Record the objects created from the files in `created`, which must not be written back yet.
+async fn record_creations(transaction: &mut Transaction, created: &[(PathBuf, CreatedObject)]) {
+    for (path, object) in created {
+        let Some((object_type, metadata)) = object.metadata() else {
+            continue;
+        };
+        let original = match tokio::fs::read_to_string(path).await {
+            Ok(original) => original,
+            Err(e) => {
+                error!("Failed to back up {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let mutation = Mutation::Created {
+            object_type,
+            object_id: metadata.name.clone().unwrap_or_default(),
+            namespace: metadata.namespace.clone(),
+            path: path.clone(),
+            original,
+        };
+        if let Err(e) = transaction.record(mutation) {
+            error!("Failed to back up a creation: {:#}", e);
+        }
+    }
+}
+
+/// Keep the changes of the run, or undo them if it failed more than `threshold` times.
+///
+/// The files of undone creations get their contents back and are no longer committed.
+async fn settle_transaction(
+    transaction: Transaction,
+    threshold: usize,
+    client_config: &Arc<Configuration>,
+    retry_policy: &RetryPolicy,
+    written_files: &mut Vec<PathBuf>,
+    report: &mut SyncReport,
+) {
+    if report.failed <= threshold {
+        if let Err(e) = transaction.commit() {
+            warn!("{:#}", e);
+        }
+        return;
+    }
+
+    error!(
+        "Run failed {} times, more than the rollback threshold of {}, rolling back {} changes",
+        report.failed,
+        threshold,
+        transaction.mutations().len()
+    );
+    let backup_dir = transaction.backup_dir().to_path_buf();
+    let rollback = transaction.roll_back(client_config.clone(), retry_policy).await;
+    written_files.retain(|path| !rollback.restored_files.contains(path));
+    if rollback.errors.is_empty() {
+        info!("Rolled back run {}", report.run_id);
+        report.status = RunStatus::RolledBack;
+        return;
+    }
+    for e in &rollback.errors {
+        error!("{:#}", e);
+    }
+    error!(
+        "ROLLBACK INCOMPLETE: {} changes of run {} could not be undone, their backups are kept in {}",
+        rollback.errors.len(),
+        report.run_id,
+        backup_dir.display()
+    );
+    report.status = RunStatus::RollbackFailed;
+}
+
+/// Commit the local changes, as one commit or one per object depending on `granularity`.
+///
+/// Only the endpoint folder and the files in `written_files` are staged, the latter
+/// is emptied in the process, and the `held_back` files are left out. A `download` is
+/// summarized in the message body.
+#[allow(clippy::too_many_arguments)]
+fn commit_run(
+    config_folder_path: &Path,
+    endpoint_folder: &Path,
+    written_files: &mut Vec<PathBuf>,
+    download: Option<DownloadReport>,
+    held_back: &[PathBuf],
+    granularity: CommitGranularity,
+    shepherd_ignore: &ShepherdIgnore,
+    report: &mut SyncReport,
+) -> Result<(), String> {
+    let datetime = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let mut message = format!("Updated configuration at {}", datetime);
+    if let Some(download) = download {
+        report.downloaded += download.written.len();
+        message = format!("{}\n\n{}", message, download);
+    }
+
+    let mut managed = vec![endpoint_folder.to_path_buf()];
+    managed.append(written_files);
+
+    let started = Instant::now();
+    match granularity {
+        CommitGranularity::Run => {
+            commit_changes_except(config_folder_path, &managed, held_back, &with_run_id(&message, &report.run_id))?
+        }
+        CommitGranularity::Object => {
+            let commits = commit_each_object(config_folder_path, &managed, held_back, shepherd_ignore, &message, &report.run_id)?;
+            debug!("Created {} commits", commits);
+        }
+    }
+    report.record_phase("commit", started.elapsed());
+    Ok(())
+}
+
+/// Download the whole endpoint into `endpoint_folder`, removing the files of the objects
+/// Rancher no longer has, and record the object files this changed since the last commit in
+/// `report`, what changed in Rancher meanwhile.
+///
+/// Returns the download to commit, `None` if it failed or took longer than `download_timeout`.
+/// The files written before are left to the next run, which downloads them again.
+#[allow(clippy::too_many_arguments)]
+async fn mirror_rancher(
+    client_config: &Configuration,
+    config_folder_path: &Path,
+    endpoint_folder: &Path,
+    file_format: &FileFormat,
+    metadata_filter: &MetadataFilter,
+    role_template_filter: &RoleTemplateFilter,
+    retry_policy: &RetryPolicy,
+    include_local_cluster: bool,
+    naming: ClusterFolderNaming,
+    prtb_listing: PrtbListing,
+    download_options: &DownloadOptions,
+    shepherd_ignore: &ShepherdIgnore,
+    download_timeout: Option<Duration>,
+    report: &mut SyncReport,
+) -> Option<DownloadReport> {
+    let started = Instant::now();
+    let download = download_current_configuration(
+        client_config,
+        config_folder_path,
+        file_format,
+        metadata_filter,
+        role_template_filter,
+        retry_policy,
+        include_local_cluster,
+        naming,
+        prtb_listing,
+        download_options,
+    );
+    let download = within_timeout("download", download_timeout, report, download).await;
+    report.record_phase("download", started.elapsed());
+    let mut download = match download? {
+        Ok(download) => download,
+        Err(e) => {
+            error!("Failed to mirror Rancher: {:#}", e);
+            return None;
+        }
+    };
+
+    let index = RepoIndex::build(config_folder_path, endpoint_folder, shepherd_ignore).await;
+    prune_download(endpoint_folder, &index, &mut download).await;
+
+    let mut mirrored = MirroredChanges::default();
+    match get_new_uncommited_files(endpoint_folder, shepherd_ignore).await {
+        Ok(added) => mirrored.added = added.into_iter().map(|(_, path)| path).collect(),
+        Err(e) => warn!("Failed to list the files added by the mirror: {}", e),
+    }
+    match get_modified_files(endpoint_folder, shepherd_ignore).await {
+        Ok(modified) => mirrored.modified = modified,
+        Err(e) => warn!("Failed to list the files changed by the mirror: {}", e),
+    }
+    match get_deleted_files(endpoint_folder, shepherd_ignore).await {
+        Ok(removed) => mirrored.removed = removed.into_iter().map(|(_, path)| path).collect(),
+        Err(e) => warn!("Failed to list the files removed by the mirror: {}", e),
+    }
+    info!("Mirrored Rancher: {}", mirrored);
+    for path in mirrored.added.iter().chain(&mirrored.modified).chain(&mirrored.removed) {
+        debug!("Mirrored {}", path.display());
+    }
+    report.mirrored = mirrored;
+    Some(download)
+}
+
+/// The clusters a run works on.
+enum ClusterScope {
+    /// The configured `cluster_names`
+    Names(Vec<String>),
+    /// The clusters matching the selector, discovered every run
+    Selector(ClusterSelector),
+}
+
+/// The IDs of the clusters matching `selector`, keeping the cluster folders in line with them.
+///
+/// Newly matched clusters are downloaded and, depending on the selector, folders of clusters
+/// that no longer match are pruned. Both are committed right away, so the later phases do not
+/// take the files for objects to create in or delete from Rancher. A dry run changes nothing
+/// and leaves newly matched clusters out. Without `include_local_cluster` Rancher's `local`
+/// cluster is neither matched nor pruned. A download taking longer than `download_timeout` is
+/// recorded in `report` and discarded, the cluster is left out until a later run downloads it.
+#[allow(clippy::too_many_arguments)]
+async fn discover_clusters(
+    client_config: &Configuration,
+    config_folder_path: &Path,
+    endpoint_folder: &Path,
+    index: &mut RepoIndex,
+    selector: &ClusterSelector,
+    include_local_cluster: bool,
+    file_format: &FileFormat,
+    naming: ClusterFolderNaming,
+    metadata_filter: &MetadataFilter,
+    retry_policy: &RetryPolicy,
+    prtb_listing: PrtbListing,
+    download_options: &DownloadOptions,
+    run_id: &str,
+    download_timeout: Option<Duration>,
+    report: &mut SyncReport,
+    dry_run: bool,
+) -> Result<Vec<String>> {
+    let visible = |cluster_id: &str| include_local_cluster || cluster_id != LOCAL_CLUSTER_ID;
+    let selected: Vec<_> = selector
+        .select(client_config)
+        .await?
+        .into_iter()
+        .filter(|cluster| visible(&cluster.id))
+        .collect();
+    let known: Vec<_> = cluster_folders(endpoint_folder, file_format)
+        .await?
+        .into_iter()
+        .filter(|cluster_id| visible(cluster_id))
+        .collect();
+
+    // listed once the first new cluster is downloaded
+    let mut listed_prtbs = None;
+    let mut cluster_ids = Vec::new();
+    for cluster in &selected {
+        if known.contains(&cluster.id) {
+            cluster_ids.push(cluster.id.clone());
+            continue;
+        }
+        if dry_run {
+            info!("Dry run, not downloading newly matched cluster {}", cluster.id);
+            continue;
+        }
+
+        info!("Cluster {} matches the cluster selector, downloading it", cluster.id);
+        if listed_prtbs.is_none() {
+            listed_prtbs = Some(list_prtbs(client_config, prtb_listing, retry_policy).await?);
+        }
+        let mut download = DownloadReport::default();
+        let downloaded = within_timeout(
+            &format!("download_{}", cluster.id),
+            download_timeout,
+            report,
+            download_cluster_configuration(
+                client_config,
+                endpoint_folder,
+                cluster,
+                file_format,
+                naming,
+                metadata_filter,
+                retry_policy,
+                listed_prtbs.as_ref().expect("listed above"),
+                download_options,
+                &mut download,
+            ),
+        )
+        .await;
+        let Some(downloaded) = downloaded else {
+            // tried again next run, a partial folder would be taken for the whole cluster
+            discard_download(endpoint_folder, &download).await;
+            continue;
+        };
+        downloaded?;
+        let message = with_run_id(&format!("Add cluster {}\n\n{}", cluster.id, download), run_id);
+        let changed: Vec<PathBuf> = download.written.iter().chain(&download.moved).cloned().collect();
+        commit_changes(config_folder_path, &changed, &message).map_err(anyhow::Error::msg)?;
+        for path in &download.written {
+            index.refresh(path).await;
+        }
+        cluster_ids.push(cluster.id.clone());
+    }
+
+    for cluster_id in known.iter().filter(|id| !selected.iter().any(|cluster| &cluster.id == *id)) {
+        match selector.missing {
+            MissingClusters::Warn => {
+                warn_repeated(
+                    "Cluster no longer matches the cluster selector",
+                    cluster_id,
+                    format_args!("Cluster {} no longer matches the cluster selector, not syncing it", cluster_id),
+                )
+            }
+            MissingClusters::Prune if dry_run => {
+                info!("Dry run, not pruning cluster {}", cluster_id)
+            }
+            MissingClusters::Prune => {
+                info!("Cluster {} no longer matches the cluster selector, pruning its folder", cluster_id);
+                let cluster_path = cluster_folder(endpoint_folder, cluster_id, file_format);
+                if let Err(e) = ensure_within(endpoint_folder, &cluster_path) {
+                    error!("Not pruning cluster {}: {}", cluster_id, e);
+                    continue;
+                }
+                tokio::fs::remove_dir_all(&cluster_path).await?;
+                index.remove_under(&cluster_path);
+                let mut pruned = vec![cluster_path];
+                let mut map = ClusterFolderMap::load(endpoint_folder, file_format)?;
+                if map.folder(cluster_id).is_some() {
+                    map.remove(cluster_id);
+                    pruned.push(map.save(endpoint_folder, file_format)?);
+                }
+                let message = with_run_id(
+                    &format!("Remove cluster {}, it no longer matches the cluster selector", cluster_id),
+                    run_id,
+                );
+                commit_changes(config_folder_path, &pruned, &message).map_err(anyhow::Error::msg)?;
+            }
+        }
+    }
+
+    Ok(cluster_ids)
+}
+
+/// Push the commits of this run, merging in commits pushed by others meanwhile.
+fn push_run(
+    repo: &Repository,
+    branch: &str,
+    auth_method: &GitAuth,
+    clone_depth: Option<u32>,
+    report: &mut SyncReport,
+) {
+    let started = Instant::now();
+    match push_changes_with_retry(repo, branch, auth_method, clone_depth) {
+        Ok(_) => {
+            info!("Successfully pushed changes");
+            report.pushed_commit = repo.head().ok().and_then(|head| head.target()).map(|oid| oid.to_string());
+        }
+        Err(e) => {
+            error!("Failed to push changes: {}", e);
+            if e.is_timeout() {
+                report.record_timeout("push");
+            }
+        }
+    }
+    report.record_phase("push", started.elapsed());
+}
+
+/// Apply the approved plans for the endpoint, refusing the ones whose objects changed in
+/// Rancher since they were planned, and discard them along with the plans that expired.
+///
+/// The discarded plans and the files written back for created objects are added to
+/// `written_files`, committed with the rest of the run.
+#[allow(clippy::too_many_arguments)]
+async fn settle_plans(
+    client_config: &Arc<Configuration>,
+    config_folder_path: &Path,
+    project_readiness: &ProjectReadiness,
+    readiness: &Readiness,
+    deletion_options: &DeletionOptions,
+    retry_policy: &RetryPolicy,
+    state: &mut SyncState,
+    written_files: &mut Vec<PathBuf>,
+    report: &mut SyncReport,
+) {
+    let plans = match Plan::pending(config_folder_path) {
+        Ok(plans) => plans,
+        Err(e) => {
+            error!("Failed to read the pending plans: {:#}", e);
+            return;
+        }
+    };
+    let now = chrono::Utc::now();
+    for plan in plans.into_iter().filter(|plan| plan.endpoint == client_config.base_path) {
+        if plan.is_expired(now) {
+            warn!("Discarding {}, it was not approved in time", plan);
+            written_files.extend(plan.discard(config_folder_path));
+            continue;
+        }
+        if !plan.is_approved(config_folder_path) {
+            info!("Waiting for the approval of {}", plan);
+            continue;
+        }
+        let drift = drifted(client_config, &plan).await;
+        if !drift.is_empty() {
+            for change in &drift {
+                error!("Refusing plan {}, {}", plan.run_id, change);
+            }
+            report.failed += drift.len();
+            written_files.extend(plan.discard(config_folder_path));
+            continue;
+        }
+
+        info!("Applying approved {}", plan);
+        let applied = apply_plan(client_config.clone(), &plan, project_readiness, readiness, deletion_options, retry_policy).await;
+        for e in &applied.errors {
+            error!("{:#}", e);
+        }
+        report.failed += applied.errors.len();
+        report.record_updated(&applied.updated);
+        report.record_deleted(&applied.deleted);
+        report.created += applied.created.len();
+        match write_back_objects(applied.created, config_folder_path, plan.file_format).await {
+            Ok(write_back) => {
+                for (object_id, creation) in &write_back.failed {
+                    error!(
+                        "Created {:?} `{}` but failed to write back {}, the next run adopts it",
+                        creation.object_type,
+                        object_id,
+                        creation.path.display()
+                    );
+                    report.unpersisted.push(creation.path.clone());
+                }
+                state.record_write_back(&write_back.written, write_back.failed);
+                written_files.extend(write_back.written);
+            }
+            Err(e) => error!("Failed to write back the objects created by plan {}: {:#}", plan.run_id, e),
+        }
+        written_files.extend(plan.discard(config_folder_path));
+    }
+}
+
+/// Write the `planned` changes not already waiting in a plan to a plan of this run, and with
+/// `plan_branch` also commit it with its approval marker to a branch of its own and push it.
+///
+/// The plan is added to `written_files`, committed with the rest of the run.
+#[allow(clippy::too_many_arguments)]
+fn plan_changes(
+    config_folder_path: &Path,
+    repo: &Repository,
+    endpoint: &str,
+    file_format: FileFormat,
+    approval: ApprovalPolicy,
+    planned: Vec<PlannedChange>,
+    branch: &str,
+    auth_method: &GitAuth,
+    written_files: &mut Vec<PathBuf>,
+    report: &SyncReport,
+) {
+    let pending = match Plan::pending(config_folder_path) {
+        Ok(pending) => pending,
+        Err(e) => {
+            error!("Failed to read the pending plans, not planning: {:#}", e);
+            return;
+        }
+    };
+    let pending: Vec<Plan> = pending.into_iter().filter(|plan| plan.endpoint == endpoint).collect();
+    let changes = unplanned(planned, &pending, chrono::Utc::now());
+    if changes.is_empty() {
+        return;
+    }
+    let plan = Plan::new(&report.run_id, endpoint, file_format, approval.ttl, changes);
+    let path = match plan.save(config_folder_path) {
+        Ok(path) => path,
+        Err(e) => {
+            error!("Failed to write {}: {:#}", plan, e);
+            return;
+        }
+    };
+    info!("Wrote {}, approve it with `shepherd approve {}`", plan, plan.run_id);
+    written_files.push(path.clone());
+    if !approval.plan_branch {
+        return;
+    }
+
+    // merging the branch approves the plan
+    let plan_branch = format!("{}{}", PLAN_BRANCH_PREFIX, plan.run_id);
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            error!("Failed to read {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let relative = |path: &Path| path.strip_prefix(config_folder_path).unwrap_or(path).to_path_buf();
+    let files = [
+        (relative(&path), contents),
+        (
+            relative(&Plan::approval_file(config_folder_path, &plan.run_id)),
+            format!("approved_by: merging {}\n", plan_branch),
+        ),
+    ];
+    let message = with_run_id(&format!("Plan the changes of run {}\n\n{}", plan.run_id, plan), &plan.run_id);
+    if let Err(e) = commit_to_branch(repo, &plan_branch, &files, &message) {
+        error!("Failed to commit {} to branch {}: {}", plan, plan_branch, e);
+        return;
+    }
+    // a new branch, pulling would merge into the checked out one
+    match push_changes(repo, &plan_branch, auth_method) {
+        Ok(()) => info!("Pushed {} to branch {}, merge it into {} to approve it", plan, plan_branch, branch),
+        Err(e) => error!("Failed to push branch {}: {}", plan_branch, e),
+    }
+}
+
+/// Record a file that does not parse, an error under strict parsing and a warning otherwise.
+fn note_parse_failure(path: &Path, failure: &ConversionError, strict_parsing: bool, report: &mut SyncReport) {
+    if !report.record_parse_failure(path) {
+        return;
+    }
+    if strict_parsing {
+        error!("{}", failure);
+    } else {
+        warn_repeated("Skipping file that does not parse", path.display(), format_args!("Skipping {}", failure));
+    }
+}
+
+/// Record the files among `errors` that do not parse.
+fn record_parse_failures(errors: &[anyhow::Error], strict_parsing: bool, report: &mut SyncReport) {
+    for failure in errors.iter().filter_map(parse_error) {
+        if let Some(path) = failure.path() {
+            note_parse_failure(path, failure, strict_parsing, report);
+        }
+    }
+}
+
+/// What to do with the local config folder before the first run.
+#[derive(Debug)]
+enum DownloadDecision {
+    /// The remote holds no configuration yet, download it from Rancher
+    Bootstrap,
+    /// The folder holds a checkout of the remote, use it as is
+    UseExisting,
+    /// The folder cannot be used, startup must abort
+    Error(GitError),
+}
+
+    /// Decides how to start from the local config folder. An empty (or missing) folder is
+    /// cloned from the remote, if the remote turns out to be empty the configuration is
+    /// bootstrapped from Rancher. A folder holding a repository is used as is.
+    ///
+    /// Failing to clone, for example because the credentials were rejected or the remote is
+    /// unreachable, and a folder that is neither empty nor a repository are errors: starting
+    /// from a fresh repository would later push over the remote history.
+    ///
+    /// # Arguments
+    /// * `config_folder_path` - Path to the local config folder
+    /// * `remote_url` - URL of the remote repository
+    /// * `auth_method` - Authentication method to use when cloning the repository
+    /// * `clone_depth` - How many commits of history to clone, `None` for all of them
+    ///
+    /// # Returns
+    /// * `DownloadDecision` - Whether to bootstrap, use the existing checkout or abort
+async fn download_required(
+    config_folder_path: &Path,
+    remote_url: &str,
+    auth_method: &GitAuth,
+    clone_depth: Option<u32>,
+) -> DownloadDecision {
+    if !config_folder_path.exists() {
+        if let Err(e) = tokio::fs::create_dir_all(config_folder_path).await {
+            return DownloadDecision::Error(e.into());
+        }
+    }
+
+    match is_directory_empty(config_folder_path).await {
+        Ok(true) => {
+            info!("Directory is empty: {}", config_folder_path.display());
+            // Clone the remote repository into the empty directory
+            let repo = match safe_clone_repository(config_folder_path, remote_url, auth_method, clone_depth).await {
+                Ok(repo) => repo,
+                Err(e) => return DownloadDecision::Error(e),
+            };
+            info!("Repository cloned successfully: {}", repo.path().display());
+
+            match is_repo_effectively_empty(&repo).await {
+                Ok(true) => {
+                    info!("Repository is empty after cloning (ignoring .git and .gitignored files)");
+                    DownloadDecision::Bootstrap
+                }
+                Ok(false) => DownloadDecision::UseExisting,
+                Err(e) => DownloadDecision::Error(e),
+            }
+        }
+        Ok(false) => {
+            info!("Directory is not empty: {}", config_folder_path.display());
+            match Repository::open(config_folder_path) {
+                Ok(_) => DownloadDecision::UseExisting,
+                Err(_) => DownloadDecision::Error(GitError::NotARepository(
+                    config_folder_path.display().to_string(),
+                )),
+            }
+        }
+        Err(e) => DownloadDecision::Error(GitError::Other(format!("Failed to check directory: {:#}", e))),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+
+    //Setup logging
+
+    init_tracing();
+    info!("Starting shepherd {}", version_info());
+
+    #[cfg(feature = "schema")]
+    if let Some(Command::Schema { out_dir }) = &cli.command {
+        for path in shepherd::schema::write_schemas(out_dir)? {
+            info!("Wrote {}", path.display());
+        }
+        return Ok(());
+    }
+
+    // get home path and concatenate with .config/shepherd/config.toml
+    let home_path = std::env::var("HOME")
+        .map_err(|_| AppError::Other("HOME environment variable not set".to_string()))?;
+    let app_config_path = format!("{}/.config/shepherd/config.toml", home_path);
+    let app_config = ShepherdConfig::from_file(&app_config_path).map_err(|e| {
+        error!("Failed to load config: {}", e);
+        AppError::Other("Failed to load config".to_string())
+    });
+
+    // if we have an error spit out the error and exit
+    let app_config = match app_config {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    debug!("App config: {}", app_config);
+
+
+    let auth_method = app_config.auth_method;
+    let branch = app_config.branch;
+    let include_local_cluster = app_config.manage_local_cluster
+        || app_config
+            .cluster_names
+            .as_ref()
+            .is_some_and(|names| names.iter().any(|name| name == LOCAL_CLUSTER_ID));
+    let clusters = match (app_config.cluster_names, app_config.cluster_selector) {
+        (Some(names), selector) => {
+            if selector.is_some() {
+                info!("cluster_names is set, ignoring cluster_selector");
+            }
+            ClusterScope::Names(names)
+        }
+        (None, Some(selector)) => ClusterScope::Selector(selector),
+        (None, None) => {
+            error!("Set cluster_names or cluster_selector to pick the clusters to synchronize");
+            std::process::exit(1);
+        }
+    };
+    if app_config.prune && app_config.management_mode == ManagementMode::RemoteIsSource {
+        warn!("prune only applies with management_mode = \"repo-is-source\", ignoring it");
+    }
+    let config_folder_path = app_config.rancher_config_path;
+    let endpoint_url = app_config.endpoint_url;
+    let file_format = app_config.file_format;
+    let insecure = app_config.insecure;
     // in seconds
     let loop_interval = app_config.loop_interval;
-    let remote_url = app_config.remote_git_url.unwrap();
+    let remote_url = match app_config.remote_git_url {
+        Some(remote_url) => remote_url,
+        None if !app_config.git_enabled => String::new(),
+        None => {
+            error!("Set remote_git_url, or git_enabled = false to sync from a folder another tool keeps");
+            std::process::exit(1);
+        }
+    };
     // in milliseconds
     let retry_delay = app_config.retry_delay;
     let token = app_config.token;
-    
-    let client = ShepherdClient::new(&endpoint_url, &token, insecure);
+    let update_strategy = app_config.update_strategy;
+    let metadata_filter = MetadataFilter::new(&app_config.ignored_metadata_patterns);
+    let principal_rules = PrincipalRules::new(&app_config.principal_normalization);
+    let role_template_filter = RoleTemplateFilter {
+        include_hidden: app_config.download_hidden_role_templates,
+        include_builtin: app_config.download_builtin_role_templates,
+    };
+    let project_readiness = ProjectReadiness {
+        conditions: app_config.project_ready_conditions,
+        timeout: Duration::from_secs(app_config.project_ready_timeout),
+    };
+    let readiness = app_config.readiness.readiness();
+
+    let circuit_breaker = CircuitBreaker::new(
+        app_config.circuit_breaker_threshold,
+        Duration::from_secs(app_config.circuit_breaker_cooldown),
+        Duration::from_secs(app_config.circuit_breaker_max_cooldown),
+        app_config.circuit_breaker_sync_git,
+    );
+
+    let strict_parsing = cli.strict_parsing || app_config.strict_parsing;
+    set_strict_parsing(strict_parsing);
+    set_repeated_warning_window(Duration::from_secs(app_config.repeated_warning_window));
+    set_file_limits(FileLimits {
+        max_file_size: app_config.max_file_size,
+        max_files_per_project: app_config.max_files_per_project,
+    });
+    if let Some(client_name) = app_config.client_name.as_deref() {
+        set_client_name(client_name);
+    }
+    set_git_author(
+        app_config.git_author_name.as_deref().unwrap_or(shepherd::FULL_CLIENT_ID),
+        &app_config
+            .git_author_email
+            .clone()
+            .unwrap_or_else(default_git_author_email),
+    );
+    let phase_timeouts = app_config.phase_timeouts.phase_timeouts();
+    if let Some(timeout) = phase_timeouts.git {
+        if let Err(e) = set_network_timeout(timeout) {
+            warn!("Cannot limit how long git waits on the remote: {}", e);
+        }
+    }
+
+    let state_dir = app_config
+        .state_dir
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(&home_path).join(".local/state/shepherd"));
+    set_error_bodies(ErrorBodies {
+        max_len: app_config.max_error_body,
+        spool_dir: cli.verbose.then(|| state_dir.join(ERROR_BODIES_FOLDER)),
+    });
+
+    match &cli.command {
+        Some(Command::Status { format }) => {
+            let status = Status::load(&state_dir)?;
+            match format {
+                OutputFormat::Text => print!("{}", status),
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&status)?),
+            }
+            return Ok(());
+        }
+        Some(Command::Unquarantine { id }) => {
+            let mut state = SyncState::load(&state_dir)?;
+            let released = state.unquarantine(id);
+            if released.is_empty() {
+                return Err(format!("No quarantined object `{}`", id).into());
+            }
+            state.save(&state_dir)?;
+            for path in released {
+                println!("Released {}", path.display());
+            }
+            return Ok(());
+        }
+        Some(Command::Approve { run_id }) => {
+            let approver = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+            let marker = Plan::approve(&config_folder_path, run_id, &approver, chrono::Utc::now())?;
+            commit_changes(&config_folder_path, &[marker], &format!("Approve the changes planned by run {}", run_id))?;
+            println!("{}", Plan::load(&config_folder_path, run_id)?);
+            println!("Approved and committed, the next run applies it");
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let client = match &app_config.auth {
+        Some(auth) => {
+            let (client, credentials) = ShepherdClient::from_kubeconfig(auth, insecure)?;
+            info!("Using Rancher credentials of {}, server {}", auth, credentials.server);
+            client
+        }
+        None => ShepherdClient::new(&endpoint_url, &token, insecure),
+    };
     let client_config = client.config.clone();
 
+    if let Some(Command::ConvertFormat { to }) = &cli.command {
+        let endpoint_folder = endpoint_folder(&config_folder_path, &client_config.base_path);
+        let ignore = ShepherdIgnore::load(&config_folder_path);
+        let converted = convert_format(&config_folder_path, &endpoint_folder, to, &ignore)?;
+        if converted.is_empty() {
+            println!("Every object file in {} is already {}", endpoint_folder.display(), to);
+            return Ok(());
+        }
+        commit_changes(
+            &config_folder_path,
+            std::slice::from_ref(&endpoint_folder),
+            &format!("Convert {} object files to {}", converted.len(), to),
+        )?;
+        println!("Converted and committed {} files, the next run pushes them", converted.len());
+        if *to != file_format {
+            println!("Set file_format = \"{}\" in the configuration before the next run", to);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Check) = &cli.command {
+        let mut report = CheckReport::default();
+        let cluster_ids = match &clusters {
+            ClusterScope::Names(names) => names.clone(),
+            ClusterScope::Selector(selector) => match selector.select(&client_config).await {
+                Ok(selected) => selected
+                    .into_iter()
+                    .map(|cluster| cluster.id)
+                    .filter(|cluster_id| include_local_cluster || cluster_id != LOCAL_CLUSTER_ID)
+                    .collect(),
+                Err(e) => {
+                    report.record("Select clusters", Err::<String, _>(e));
+                    Vec::new()
+                }
+            },
+        };
+        let checks = run_checks(&client_config, &cluster_ids, &remote_url, &auth_method, &config_folder_path).await;
+        report.probes.extend(checks.probes);
+        print!("{}", report);
+        if !report.passed() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Report { report: ReportCommand::Rbac { cluster, format, live } }) = &cli.command {
+        let correct = app_config.correct_misplaced_objects;
+        let Some(config) = load_configuration(&config_folder_path, &client_config.base_path, cluster, &file_format, correct).await? else {
+            return Err(format!("No folder for cluster `{}` in the repository", cluster).into());
+        };
+        let report = if *live {
+            let retry_policy = RetryPolicy {
+                delay: Duration::from_millis(retry_delay),
+                ..RetryPolicy::default()
+            };
+            let rancher = load_configuration_from_rancher(&client_config, cluster, &RemoteCache::default(), &retry_policy).await?;
+            let rancher = live_config(rancher)?;
+            let mut report = RbacReport::build(&config, &rancher.role_templates);
+            report.cross_check(&RbacReport::build(&rancher, &[]));
+            report
+        } else {
+            RbacReport::build(&config, &[])
+        };
+        match format {
+            ReportFormat::Md => print!("{}", report.to_markdown()),
+            ReportFormat::Csv => print!("{}", report.to_csv()),
+            ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Diff { clusters: cluster_ids, format }) = &cli.command {
+        let cluster_ids = match (cluster_ids.is_empty(), &clusters) {
+            (false, _) => cluster_ids.clone(),
+            (true, ClusterScope::Names(names)) => names.clone(),
+            (true, ClusterScope::Selector(_)) => {
+                cluster_folders(&endpoint_folder(&config_folder_path, &client_config.base_path), &file_format).await?
+            }
+        };
+        let retry_policy = RetryPolicy {
+            delay: Duration::from_millis(retry_delay),
+            ..RetryPolicy::default()
+        };
+        let remote_cache = RemoteCache::default();
+        let mut diffs = BTreeMap::new();
+        for cluster_id in cluster_ids {
+            let comparison = compare_cluster(
+                &client_config,
+                &config_folder_path,
+                &cluster_id,
+                &file_format,
+                &metadata_filter,
+                &principal_rules,
+                &role_template_filter,
+                &retry_policy,
+                &remote_cache,
+                app_config.management_mode,
+                app_config.correct_misplaced_objects,
+            )
+            .await?;
+            match comparison {
+                Some(comparison) => {
+                    for violation in comparison.quota_violations().values() {
+                        warn!("{}", violation);
+                    }
+                    diffs.insert(cluster_id, comparison.diff);
+                }
+                None => warn!("No folder for cluster `{}` in the repository", cluster_id),
+            }
+        }
+        match format {
+            OutputFormat::Text => {
+                for (cluster_id, diff) in &diffs {
+                    println!("Cluster `{}`:\n{}", cluster_id, diff);
+                }
+            }
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&diffs)?),
+        }
+        return Ok(());
+    }
+    let task_dumper = Arc::new(TaskDumper::new(Duration::from_secs(app_config.task_dump_min_interval)));
+    #[cfg(unix)]
+    if let Err(e) = dump_on_sigusr1(task_dumper.clone()) {
+        warn!("Cannot dump the running tasks on SIGUSR1: {}", e);
+    }
+    let stuck_run_after = (app_config.stuck_run_intervals > 0)
+        .then(|| Duration::from_secs(loop_interval) * app_config.stuck_run_intervals);
+    let notifier = app_config.notifications.map(Notifier::new);
+    let events = app_config.events.as_ref().map(EventSink::start);
+
     run_sync(
         client_config,
         &config_folder_path,
         &remote_url,
         file_format,
-        cluster_ids,
+        clusters,
+        include_local_cluster,
+        app_config.cluster_folder_naming,
+        app_config.match_projects_by,
+        app_config.prtb_listing,
+        DownloadOptions {
+            bundle_projects: app_config.bundle_projects,
+            cluster_redact_paths: app_config.cluster_redact_paths,
+        },
         loop_interval,
         retry_delay,
         &branch,
         auth_method,
+        app_config.clone_depth,
+        app_config.sparse_paths,
+        app_config.git_enabled,
+        update_strategy,
+        app_config.management_mode,
+        app_config.mode,
+        app_config.approval.map(ApprovalPolicy::from),
+        app_config.prune,
+        project_readiness,
+        readiness,
+        app_config.skip_cascaded_deletes,
+        app_config.deletion_options.deletion_options(),
+        app_config.deletable_types.clone(),
+        app_config.repo_doctor,
+        app_config.duplicate_project_display_names,
+        app_config.binding_policy_violations,
+        app_config.quota_below_usage,
+        metadata_filter,
+        principal_rules,
+        role_template_filter,
+        circuit_breaker,
+        app_config.commit_granularity,
+        app_config.rollback_threshold,
+        state_dir,
+        app_config.quarantine_after,
+        app_config.escalate_terminating_after.map(|minutes| Duration::from_secs(minutes * 60)),
+        app_config.server_version_interval,
+        Some(Duration::from_secs(app_config.max_clock_skew)).filter(|skew| !skew.is_zero()),
+        notifier,
+        events,
+        app_config.stamp_git_commit,
+        app_config.record_last_applied,
+        app_config.correct_misplaced_objects,
+        strict_parsing,
+        cli.once,
+        cli.dry_run,
+        task_dumper,
+        stuck_run_after,
+        phase_timeouts,
+        app_config.max_changes_per_run,
     )
     .await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A repository at `path` with a single committed object file.
+    fn remote_with_commit(path: &Path) {
+        let repo = Repository::init(path).unwrap();
+        std::fs::write(path.join("c-123.cluster.yaml"), "id: c-123\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("c-123.cluster.yaml")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "initial", &tree, &[]).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_discovery_downloads_new_and_prunes_unmatched_clusters() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let list = |items: serde_json::Value| serde_json::json!({ "metadata": {}, "items": items });
+        let mocks = [
+            (
+                "/apis/management.cattle.io/v3/clusters",
+                list(serde_json::json!([
+                    { "metadata": { "name": "c-123" }, "spec": { "displayName": "prod" } },
+                    { "metadata": { "name": "local" }, "spec": { "displayName": "local" } },
+                ])),
+            ),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects", list(serde_json::json!([]))),
+            (
+                "/apis/management.cattle.io/v3/namespaces/c-123/clusterroletemplatebindings",
+                list(serde_json::json!([])),
+            ),
+        ];
+        for (mock_path, body) in mocks {
+            Mock::given(method("GET"))
+                .and(path(mock_path))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&server)
+                .await;
+        }
+        let client_config = Configuration {
+            base_path: server.uri(),
+            ..Default::default()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let endpoint = endpoint_folder(&root, &client_config.base_path);
+        std::fs::create_dir_all(endpoint.join("c-old")).unwrap();
+        std::fs::write(endpoint.join("c-old/c-old.cluster.yaml"), "id: c-old\n").unwrap();
+        std::fs::create_dir_all(endpoint.join("local")).unwrap();
+        std::fs::write(endpoint.join("local/local.cluster.yaml"), "id: local\n").unwrap();
+        remote_with_commit(&root);
+        let repo = Repository::open(&root).unwrap();
+        commit_changes(&root, std::slice::from_ref(&endpoint), "initial").unwrap();
+
+        let selector = ClusterSelector {
+            missing: MissingClusters::Prune,
+            ..Default::default()
+        };
+        let mut index = RepoIndex::build(&root, &endpoint, &ShepherdIgnore::default()).await;
+        let cluster_ids = discover_clusters(
+            &client_config,
+            &root,
+            &endpoint,
+            &mut index,
+            &selector,
+            false,
+            &FileFormat::Yaml,
+            ClusterFolderNaming::Id,
+            &MetadataFilter::default(),
+            &RetryPolicy::default(),
+            PrtbListing::PerProject,
+            &DownloadOptions::default(),
+            "run-1",
+            None,
+            &mut SyncReport::new("run-1"),
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(cluster_ids, ["c-123"]);
+        assert!(endpoint.join("c-123/c-123.cluster.yaml").exists());
+        assert!(!endpoint.join("c-old").exists());
+        assert!(endpoint.join("local/local.cluster.yaml").exists());
+        assert!(repo.statuses(None).unwrap().is_empty());
+        assert!(index.path(ObjectType::Cluster, None, "c-123").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_discovery_does_not_prune_a_cluster_mapped_outside_the_endpoint_folder() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/clusters"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "metadata": {}, "items": [] })))
+            .mount(&server)
+            .await;
+        let client_config = Configuration {
+            base_path: server.uri(),
+            ..Default::default()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let endpoint = endpoint_folder(&root, &client_config.base_path);
+        std::fs::create_dir_all(endpoint.join("c-old")).unwrap();
+        std::fs::write(endpoint.join("c-old/c-old.cluster.yaml"), "id: c-old\n").unwrap();
+        // the map sends the cluster's folder out of the endpoint folder
+        std::fs::write(ClusterFolderMap::path(&endpoint, &FileFormat::Yaml), "../outside: c-old\n").unwrap();
+        let outside = endpoint.join("../outside");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("keep.txt"), "keep").unwrap();
+        remote_with_commit(&root);
+
+        let selector = ClusterSelector {
+            missing: MissingClusters::Prune,
+            ..Default::default()
+        };
+        let mut index = RepoIndex::build(&root, &endpoint, &ShepherdIgnore::default()).await;
+        let cluster_ids = discover_clusters(
+            &client_config,
+            &root,
+            &endpoint,
+            &mut index,
+            &selector,
+            false,
+            &FileFormat::Yaml,
+            ClusterFolderNaming::Id,
+            &MetadataFilter::default(),
+            &RetryPolicy::default(),
+            PrtbListing::PerProject,
+            &DownloadOptions::default(),
+            "run-1",
+            None,
+            &mut SyncReport::new("run-1"),
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert!(cluster_ids.is_empty());
+        assert!(outside.join("keep.txt").exists());
+        assert!(endpoint.join("c-old/c-old.cluster.yaml").exists());
+    }
+
+    #[tokio::test]
+    async fn test_empty_remote_bootstraps() {
+        let remote = tempfile::tempdir().unwrap();
+        Repository::init_bare(remote.path()).unwrap();
+        let local = tempfile::tempdir().unwrap();
+
+        let decision = download_required(local.path(), remote.path().to_str().unwrap(), &GitAuth::SshAgent, None).await;
+
+        assert!(matches!(decision, DownloadDecision::Bootstrap), "{:?}", decision);
+    }
+
+    #[tokio::test]
+    async fn test_populated_remote_and_existing_checkout_are_used() {
+        let remote = tempfile::tempdir().unwrap();
+        remote_with_commit(remote.path());
+        let local = tempfile::tempdir().unwrap();
+        let folder = local.path().join("config");
+
+        let cloned = download_required(&folder, remote.path().to_str().unwrap(), &GitAuth::SshAgent, None).await;
+        let reopened = download_required(&folder, remote.path().to_str().unwrap(), &GitAuth::SshAgent, None).await;
+
+        assert!(matches!(cloned, DownloadDecision::UseExisting), "{:?}", cloned);
+        assert!(folder.join("c-123.cluster.yaml").exists());
+        assert!(matches!(reopened, DownloadDecision::UseExisting), "{:?}", reopened);
+    }
+
+    #[tokio::test]
+    async fn test_clone_failure_is_an_error() {
+        let missing = tempfile::tempdir().unwrap();
+        let remote_url = missing.path().join("missing.git");
+        let local = tempfile::tempdir().unwrap();
+
+        let decision = download_required(local.path(), remote_url.to_str().unwrap(), &GitAuth::SshAgent, None).await;
+
+        assert!(matches!(decision, DownloadDecision::Error(GitError::Git(_))), "{:?}", decision);
+        assert!(Repository::open(local.path()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_folder_that_is_not_a_repository_is_an_error() {
+        let remote = tempfile::tempdir().unwrap();
+        remote_with_commit(remote.path());
+        let local = tempfile::tempdir().unwrap();
+        std::fs::write(local.path().join("notes.txt"), "not a checkout").unwrap();
+
+        let decision = download_required(local.path(), remote.path().to_str().unwrap(), &GitAuth::SshAgent, None).await;
+
+        assert!(matches!(decision, DownloadDecision::Error(GitError::NotARepository(_))), "{:?}", decision);
+    }
+
+    #[tokio::test]
+    async fn test_objects_not_written_back_are_adopted_instead_of_created_again() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-generated"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "metadata": { "name": "p-generated", "namespace": "c-123", "resourceVersion": "7" },
+                "spec": { "clusterName": "c-123", "displayName": "web" }
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/p-abc/projectroletemplatebindings/prtb-gone"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "kind": "Status",
+                "reason": "NotFound",
+                "message": "projectroletemplatebindings.management.cattle.io \"prtb-gone\" not found"
+            })))
+            .mount(&server)
+            .await;
+        let client_config = Configuration {
+            base_path: server.uri(),
+            ..Default::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("web.project.yaml");
+        std::fs::write(&project, "display_name: web\ncluster_name: c-123\nnamespace: c-123\n").unwrap();
+        let binding = dir.path().join("reader.prtb.yaml");
+        let creation = |object_type: ObjectType, namespace: &str, path: &Path| UnpersistedCreation {
+            object_type,
+            namespace: Some(namespace.to_string()),
+            path: path.to_path_buf(),
+            created_at: chrono::Utc::now(),
+            error: "No space left on device".to_string(),
+        };
+        let mut state = SyncState::default();
+        state.record_write_back(
+            &[],
+            vec![
+                ("p-generated".to_string(), creation(ObjectType::Project, "c-123", &project)),
+                ("prtb-gone".to_string(), creation(ObjectType::ProjectRoleTemplateBinding, "p-abc", &binding)),
+            ],
+        );
+        let mut new_files = vec![
+            (ObjectType::Project, project.clone()),
+            (ObjectType::ProjectRoleTemplateBinding, binding.clone()),
+        ];
+        let mut report = SyncReport::new("run-1");
+
+        let adopted =
+            adopt_unpersisted(&client_config, &mut state, &mut new_files, dir.path(), FileFormat::Yaml, false, &mut report).await;
+
+        assert_eq!(adopted, vec![project.clone()]);
+        assert!(std::fs::read_to_string(&project).unwrap().starts_with("id: p-generated\n"));
+        // the binding Rancher no longer has is created again
+        assert_eq!(new_files, vec![(ObjectType::ProjectRoleTemplateBinding, binding)]);
+        assert!(state.unpersisted.is_empty());
+        assert_eq!(report.adopted, 1);
+        assert!(report.unpersisted.is_empty());
+    }
+
+    /// A single run without git of the folder `config_folder_path` against `server`.
+    async fn sync_once_without_git(
+        server: &wiremock::MockServer,
+        config_folder_path: &Path,
+        state_dir: &Path,
+        max_changes_per_run: ChangeLimits,
+    ) {
+        let client_config = Arc::new(Configuration {
+            base_path: server.uri(),
+            ..Default::default()
+        });
+        run_sync(
+            client_config,
+            config_folder_path,
+            "",
+            FileFormat::Yaml,
+            ClusterScope::Names(vec!["c-123".to_string()]),
+            false,
+            ClusterFolderNaming::Id,
+            ProjectMatch::Id,
+            PrtbListing::PerProject,
+            DownloadOptions::default(),
+            1,
+            10,
+            "main",
+            GitAuth::SshAgent,
+            None,
+            None,
+            false,
+            UpdateStrategy::default(),
+            ManagementMode::default(),
+            SyncMode::default(),
+            None,
+            false,
+            ProjectReadiness::default(),
+            Readiness::default(),
+            false,
+            DeletionOptions::default(),
+            vec![ObjectType::ProjectRoleTemplateBinding],
+            RepoDoctorConfig::default(),
+            Severity::Warning,
+            Severity::Warning,
+            Severity::Warning,
+            MetadataFilter::default(),
+            PrincipalRules::default(),
+            RoleTemplateFilter::default(),
+            CircuitBreaker::new(0, Duration::from_secs(1), Duration::from_secs(1), false),
+            CommitGranularity::Run,
+            None,
+            state_dir.to_path_buf(),
+            0,
+            None,
+            0,
+            None,
+            None,
+            None,
+            false,
+            false,
+            false,
+            false,
+            true,
+            false,
+            Arc::new(TaskDumper::new(Duration::from_secs(60))),
+            None,
+            PhaseTimeouts::default(),
+            max_changes_per_run,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sync_without_git_creates_and_deletes_the_objects_changed_since_the_last_run() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let list = |items: Vec<serde_json::Value>| serde_json::json!({ "metadata": {}, "items": items });
+        let project = serde_json::json!({
+            "metadata": { "name": "p-abc", "namespace": "c-123", "resourceVersion": "1" },
+            "spec": { "clusterName": "c-123", "displayName": "web" }
+        });
+        let server = MockServer::start().await;
+        let mocks = [
+            ("/apis/management.cattle.io/v3/clusters", list(vec![serde_json::json!({ "metadata": { "name": "c-123" }, "spec": { "displayName": "prod" } })])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects", list(vec![project.clone()])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc", project),
+            ("/apis/management.cattle.io/v3/roletemplates", list(vec![serde_json::json!({ "metadata": { "name": "project-member" }, "context": "project" })])),
+        ];
+        for (mock_path, body) in mocks {
+            Mock::given(method("GET"))
+                .and(path(mock_path))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&server)
+                .await;
+        }
+        // every other listing is empty
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(list(vec![])))
+            .with_priority(10)
+            .mount(&server)
+            .await;
+        let bindings = "/apis/management.cattle.io/v3/namespaces/p-abc/projectroletemplatebindings";
+        Mock::given(method("POST"))
+            .and(path(bindings))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                "metadata": { "name": "prtb-generated", "namespace": "p-abc", "resourceVersion": "2" },
+                "projectName": "c-123:p-abc",
+                "roleTemplateName": "project-member",
+                "userName": "u-abc"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path(format!("{}/prtb-generated", bindings)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config_folder_path = dir.path().join("rancher_config");
+        let state_dir = dir.path().join("state");
+        let project_folder = endpoint_folder(&config_folder_path, &server.uri()).join("c-123/p-abc");
+        std::fs::create_dir_all(&project_folder).unwrap();
+        std::fs::write(project_folder.join("p-abc.project.yaml"), "id: p-abc\ncluster_name: c-123\ndisplay_name: web\nnamespace: c-123\n").unwrap();
+        let binding = project_folder.join("reader.prtb.yaml");
+        std::fs::write(&binding, "id: ''\nnamespace: p-abc\nproject_name: c-123:p-abc\nrole_template_name: project-member\nuser_name: u-abc\n").unwrap();
+
+        // the new binding is created and written back
+        sync_once_without_git(&server, &config_folder_path, &state_dir, ChangeLimits::default()).await;
+        assert!(std::fs::read_to_string(&binding).unwrap().contains("prtb-generated"));
+        let snapshot = SyncState::load(&state_dir).unwrap().snapshot.unwrap();
+        assert_eq!(snapshot.files.len(), 2);
+
+        // nothing changed, nothing is created again
+        sync_once_without_git(&server, &config_folder_path, &state_dir, ChangeLimits::default()).await;
+
+        // the removed file deletes its binding
+        std::fs::remove_file(&binding).unwrap();
+        sync_once_without_git(&server, &config_folder_path, &state_dir, ChangeLimits::default()).await;
+        let snapshot = SyncState::load(&state_dir).unwrap().snapshot.unwrap();
+        assert_eq!(snapshot.files.keys().collect::<Vec<_>>(), [&project_folder.join("p-abc.project.yaml")]);
+
+        assert!(!config_folder_path.join(".git").exists());
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_changes_over_the_limit_are_left_to_the_next_run() {
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let list = |items: Vec<serde_json::Value>| serde_json::json!({ "metadata": {}, "items": items });
+        let project = serde_json::json!({
+            "metadata": { "name": "p-abc", "namespace": "c-123", "resourceVersion": "1" },
+            "spec": { "clusterName": "c-123", "displayName": "web" }
+        });
+        let server = MockServer::start().await;
+        let mocks = [
+            ("/apis/management.cattle.io/v3/clusters", list(vec![serde_json::json!({ "metadata": { "name": "c-123" }, "spec": { "displayName": "prod" } })])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects", list(vec![project.clone()])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc", project),
+            ("/apis/management.cattle.io/v3/roletemplates", list(vec![serde_json::json!({ "metadata": { "name": "project-member" }, "context": "project" })])),
+        ];
+        for (mock_path, body) in mocks {
+            Mock::given(method("GET"))
+                .and(path(mock_path))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(list(vec![])))
+            .with_priority(10)
+            .mount(&server)
+            .await;
+        for user in ["u-a", "u-b"] {
+            Mock::given(method("POST"))
+                .and(path("/apis/management.cattle.io/v3/namespaces/p-abc/projectroletemplatebindings"))
+                .and(body_partial_json(serde_json::json!({ "userName": user })))
+                .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                    "metadata": { "name": format!("prtb-{}", user), "namespace": "p-abc", "resourceVersion": "2" },
+                    "projectName": "c-123:p-abc",
+                    "roleTemplateName": "project-member",
+                    "userName": user
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let config_folder_path = dir.path().join("rancher_config");
+        let state_dir = dir.path().join("state");
+        let project_folder = endpoint_folder(&config_folder_path, &server.uri()).join("c-123/p-abc");
+        std::fs::create_dir_all(&project_folder).unwrap();
+        std::fs::write(project_folder.join("p-abc.project.yaml"), "id: p-abc\ncluster_name: c-123\ndisplay_name: web\nnamespace: c-123\n").unwrap();
+        let bindings = [project_folder.join("a.prtb.yaml"), project_folder.join("b.prtb.yaml")];
+        for (binding, user) in bindings.iter().zip(["u-a", "u-b"]) {
+            let contents = format!("id: ''\nnamespace: p-abc\nproject_name: c-123:p-abc\nrole_template_name: project-member\nuser_name: {}\n", user);
+            std::fs::write(binding, contents).unwrap();
+        }
+        let limits = ChangeLimits { creates: Some(1), ..Default::default() };
+
+        // the first file in path order is created, the other one waits
+        sync_once_without_git(&server, &config_folder_path, &state_dir, limits).await;
+        assert!(std::fs::read_to_string(&bindings[0]).unwrap().contains("prtb-u-a"));
+        assert!(!std::fs::read_to_string(&bindings[1]).unwrap().contains("prtb-u-b"));
+        let backlog = SyncState::load(&state_dir).unwrap().backlog.unwrap();
+        assert_eq!((backlog.applied, backlog.remaining), (1, 1));
+        assert_eq!(backlog.to_string(), "1 of 2 changes applied");
+        assert_eq!(SyncReport::load(&state_dir).unwrap().unwrap().deferred, 1);
+
+        // the next run picks up where the last one stopped
+        sync_once_without_git(&server, &config_folder_path, &state_dir, limits).await;
+        assert!(std::fs::read_to_string(&bindings[1]).unwrap().contains("prtb-u-b"));
+        assert_eq!(SyncState::load(&state_dir).unwrap().backlog, None);
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_sync_without_git_removes_a_field_dropped_since_the_last_run() {
+        use wiremock::matchers::{body_string_contains, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let list = |items: Vec<serde_json::Value>| serde_json::json!({ "metadata": {}, "items": items });
+        let project = serde_json::json!({
+            "metadata": { "name": "p-abc", "namespace": "c-123", "resourceVersion": "1" },
+            "spec": { "clusterName": "c-123", "displayName": "web" }
+        });
+        let binding = serde_json::json!({
+            "metadata": { "name": "prtb-abc", "namespace": "p-abc", "resourceVersion": "2" },
+            "projectName": "c-123:p-abc",
+            "roleTemplateName": "project-member",
+            "userName": "u-abc",
+            "userPrincipalName": "local://u-abc"
+        });
+        let bindings = "/apis/management.cattle.io/v3/namespaces/p-abc/projectroletemplatebindings";
+        let server = MockServer::start().await;
+        let mocks = [
+            ("/apis/management.cattle.io/v3/clusters", list(vec![serde_json::json!({ "metadata": { "name": "c-123" }, "spec": { "displayName": "prod" } })])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects", list(vec![project.clone()])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc", project),
+            ("/apis/management.cattle.io/v3/roletemplates", list(vec![serde_json::json!({ "metadata": { "name": "project-member" }, "context": "project" })])),
+            (bindings, list(vec![binding.clone()])),
+        ];
+        for (mock_path, body) in mocks {
+            Mock::given(method("GET"))
+                .and(path(mock_path))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(list(vec![])))
+            .with_priority(10)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path(format!("{}/prtb-abc", bindings)))
+            .and(body_string_contains("/userPrincipalName"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(binding))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config_folder_path = dir.path().join("rancher_config");
+        let state_dir = dir.path().join("state");
+        let project_folder = endpoint_folder(&config_folder_path, &server.uri()).join("c-123/p-abc");
+        std::fs::create_dir_all(&project_folder).unwrap();
+        std::fs::create_dir_all(endpoint_folder(&config_folder_path, &server.uri()).join("roles")).unwrap();
+        std::fs::write(project_folder.join("../c-123.cluster.yaml"), "id: c-123\ndisplay_name: prod\n").unwrap();
+        std::fs::write(project_folder.join("p-abc.project.yaml"), "id: p-abc\ncluster_name: c-123\ndisplay_name: web\nnamespace: c-123\n").unwrap();
+        let binding_file = project_folder.join("reader.prtb.yaml");
+        let contents = "id: prtb-abc\nnamespace: p-abc\nproject_name: c-123:p-abc\nrole_template_name: project-member\nuser_name: u-abc\n";
+        std::fs::write(&binding_file, format!("{}user_principal_name: local://u-abc\n", contents)).unwrap();
+
+        // in sync, the snapshot keeps the file as it was
+        sync_once_without_git(&server, &config_folder_path, &state_dir, ChangeLimits::default()).await;
+
+        // the field the last run saw is removed, without git to back the removal
+        std::fs::write(&binding_file, contents).unwrap();
+        sync_once_without_git(&server, &config_folder_path, &state_dir, ChangeLimits::default()).await;
+
+        assert!(!config_folder_path.join(".git").exists());
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_sync_without_git_deletes_a_deferred_deletion_on_a_later_run() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let list = |items: Vec<serde_json::Value>| serde_json::json!({ "metadata": {}, "items": items });
+        let project = |id: &str, name: &str| {
+            serde_json::json!({
+                "metadata": { "name": id, "namespace": "c-123", "resourceVersion": "1" },
+                "spec": { "clusterName": "c-123", "displayName": name }
+            })
+        };
+        // both projects have a binding of the same ID
+        let binding = |namespace: &str| {
+            serde_json::json!({
+                "metadata": { "name": "prtb-same", "namespace": namespace, "resourceVersion": "2" },
+                "projectName": format!("c-123:{}", namespace),
+                "roleTemplateName": "project-member",
+                "userName": "u-abc"
+            })
+        };
+        let bindings = |namespace: &str| format!("/apis/management.cattle.io/v3/namespaces/{}/projectroletemplatebindings", namespace);
+        let server = MockServer::start().await;
+        let mocks = [
+            ("/apis/management.cattle.io/v3/clusters".to_string(), list(vec![serde_json::json!({ "metadata": { "name": "c-123" }, "spec": { "displayName": "prod" } })])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects".to_string(), list(vec![project("p-abc", "web"), project("p-def", "db")])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc".to_string(), project("p-abc", "web")),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-def".to_string(), project("p-def", "db")),
+            ("/apis/management.cattle.io/v3/roletemplates".to_string(), list(vec![serde_json::json!({ "metadata": { "name": "project-member" }, "context": "project" })])),
+            (bindings("p-abc"), list(vec![binding("p-abc")])),
+            (bindings("p-def"), list(vec![binding("p-def")])),
+        ];
+        for (mock_path, body) in mocks {
+            Mock::given(method("GET"))
+                .and(path(mock_path))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(list(vec![])))
+            .with_priority(10)
+            .mount(&server)
+            .await;
+        for namespace in ["p-abc", "p-def"] {
+            Mock::given(method("DELETE"))
+                .and(path(format!("{}/prtb-same", bindings(namespace))))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let config_folder_path = dir.path().join("rancher_config");
+        let state_dir = dir.path().join("state");
+        let cluster_folder = endpoint_folder(&config_folder_path, &server.uri()).join("c-123");
+        std::fs::create_dir_all(endpoint_folder(&config_folder_path, &server.uri()).join("roles")).unwrap();
+        std::fs::create_dir_all(&cluster_folder).unwrap();
+        std::fs::write(cluster_folder.join("c-123.cluster.yaml"), "id: c-123\ndisplay_name: prod\n").unwrap();
+        let mut binding_files = Vec::new();
+        for (id, name) in [("p-abc", "web"), ("p-def", "db")] {
+            let project_folder = cluster_folder.join(id);
+            std::fs::create_dir_all(&project_folder).unwrap();
+            let contents = format!("id: {}\ncluster_name: c-123\ndisplay_name: {}\nnamespace: c-123\n", id, name);
+            std::fs::write(project_folder.join(format!("{}.project.yaml", id)), contents).unwrap();
+            let binding_file = project_folder.join("reader.prtb.yaml");
+            let contents = format!(
+                "id: prtb-same\nnamespace: {}\nproject_name: c-123:{}\nrole_template_name: project-member\nuser_name: u-abc\n",
+                id, id
+            );
+            std::fs::write(&binding_file, contents).unwrap();
+            binding_files.push(binding_file);
+        }
+        let limits = ChangeLimits { deletes: Some(1), ..Default::default() };
+
+        // in sync, the snapshot keeps both bindings
+        sync_once_without_git(&server, &config_folder_path, &state_dir, limits).await;
+
+        // one deletion is applied, the other one waits with its own file
+        for binding_file in &binding_files {
+            std::fs::remove_file(binding_file).unwrap();
+        }
+        sync_once_without_git(&server, &config_folder_path, &state_dir, limits).await;
+        let snapshot = SyncState::load(&state_dir).unwrap().snapshot.unwrap();
+        assert!(!snapshot.files.contains_key(&binding_files[0]));
+        assert!(snapshot.files.contains_key(&binding_files[1]));
+        assert_eq!(SyncReport::load(&state_dir).unwrap().unwrap().deferred, 1);
+
+        // the next run deletes the binding left over
+        sync_once_without_git(&server, &config_folder_path, &state_dir, limits).await;
+        let state = SyncState::load(&state_dir).unwrap();
+        assert!(!state.snapshot.unwrap().files.contains_key(&binding_files[1]));
+        assert_eq!(state.backlog, None);
+
+        assert!(!config_folder_path.join(".git").exists());
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_sync_without_git_keeps_only_the_failed_one_of_two_deletions_with_the_same_id() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let list = |items: Vec<serde_json::Value>| serde_json::json!({ "metadata": {}, "items": items });
+        let project = |id: &str, name: &str| {
+            serde_json::json!({
+                "metadata": { "name": id, "namespace": "c-123", "resourceVersion": "1" },
+                "spec": { "clusterName": "c-123", "displayName": name }
+            })
+        };
+        // both projects have a binding of the same ID
+        let binding = |namespace: &str| {
+            serde_json::json!({
+                "metadata": { "name": "prtb-same", "namespace": namespace, "resourceVersion": "2" },
+                "projectName": format!("c-123:{}", namespace),
+                "roleTemplateName": "project-member",
+                "userName": "u-abc"
+            })
+        };
+        let bindings = |namespace: &str| format!("/apis/management.cattle.io/v3/namespaces/{}/projectroletemplatebindings", namespace);
+        let server = MockServer::start().await;
+        let mocks = [
+            ("/apis/management.cattle.io/v3/clusters".to_string(), list(vec![serde_json::json!({ "metadata": { "name": "c-123" }, "spec": { "displayName": "prod" } })])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects".to_string(), list(vec![project("p-abc", "web"), project("p-def", "db")])),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc".to_string(), project("p-abc", "web")),
+            ("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-def".to_string(), project("p-def", "db")),
+            ("/apis/management.cattle.io/v3/roletemplates".to_string(), list(vec![serde_json::json!({ "metadata": { "name": "project-member" }, "context": "project" })])),
+            (bindings("p-abc"), list(vec![binding("p-abc")])),
+            (bindings("p-def"), list(vec![binding("p-def")])),
+        ];
+        for (mock_path, body) in mocks {
+            Mock::given(method("GET"))
+                .and(path(mock_path))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(list(vec![])))
+            .with_priority(10)
+            .mount(&server)
+            .await;
+        // only the binding of the first project fails to delete
+        for (namespace, status) in [("p-abc", 409), ("p-def", 200)] {
+            Mock::given(method("DELETE"))
+                .and(path(format!("{}/prtb-same", bindings(namespace))))
+                .respond_with(ResponseTemplate::new(status).set_body_json(serde_json::json!({})))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let config_folder_path = dir.path().join("rancher_config");
+        let state_dir = dir.path().join("state");
+        let cluster_folder = endpoint_folder(&config_folder_path, &server.uri()).join("c-123");
+        std::fs::create_dir_all(endpoint_folder(&config_folder_path, &server.uri()).join("roles")).unwrap();
+        std::fs::create_dir_all(&cluster_folder).unwrap();
+        std::fs::write(cluster_folder.join("c-123.cluster.yaml"), "id: c-123\ndisplay_name: prod\n").unwrap();
+        let mut binding_files = Vec::new();
+        for (id, name) in [("p-abc", "web"), ("p-def", "db")] {
+            let project_folder = cluster_folder.join(id);
+            std::fs::create_dir_all(&project_folder).unwrap();
+            let contents = format!("id: {}\ncluster_name: c-123\ndisplay_name: {}\nnamespace: c-123\n", id, name);
+            std::fs::write(project_folder.join(format!("{}.project.yaml", id)), contents).unwrap();
+            let binding_file = project_folder.join("reader.prtb.yaml");
+            let contents = format!(
+                "id: prtb-same\nnamespace: {}\nproject_name: c-123:{}\nrole_template_name: project-member\nuser_name: u-abc\n",
+                id, id
+            );
+            std::fs::write(&binding_file, contents).unwrap();
+            binding_files.push(binding_file);
+        }
+
+        // in sync, the snapshot keeps both bindings
+        sync_once_without_git(&server, &config_folder_path, &state_dir, ChangeLimits::default()).await;
+
+        for binding_file in &binding_files {
+            std::fs::remove_file(binding_file).unwrap();
+        }
+        sync_once_without_git(&server, &config_folder_path, &state_dir, ChangeLimits::default()).await;
+
+        // the failed deletion is tried again next run, the other one is done
+        let state = SyncState::load(&state_dir).unwrap();
+        let snapshot = state.snapshot.unwrap();
+        assert!(snapshot.files.contains_key(&binding_files[0]));
+        assert!(!snapshot.files.contains_key(&binding_files[1]));
+        let pending: Vec<_> = state
+            .pending_deletions
+            .iter()
+            .map(|pending| (pending.object_id.as_str(), pending.namespace.as_deref()))
+            .collect();
+        assert_eq!(pending, [("prtb-same", Some("p-def"))]);
+        assert_eq!(SyncReport::load(&state_dir).unwrap().unwrap().pending_drift, [binding_files[0].clone()]);
+
+        server.verify().await;
+    }
+}
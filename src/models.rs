@@ -1,12 +1,12 @@
-use std::{borrow::Cow, path::Path};
+use std::{borrow::Cow, path::{Path, PathBuf}, time::Duration};
 
 use anyhow::Result;
 
-use rancher_client::models::{IoCattleManagementv3Project, IoCattleManagementv3ProjectRoleTemplateBinding, IoCattleManagementv3RoleTemplate, IoK8sApimachineryPkgApisMetaV1Status};
+use rancher_client::models::{IoCattleManagementv3ClusterRoleTemplateBinding, IoCattleManagementv3Project, IoCattleManagementv3ProjectRoleTemplateBinding, IoCattleManagementv3RoleTemplate, IoK8sApimachineryPkgApisMetaV1ObjectMeta, IoK8sApimachineryPkgApisMetaV1Status};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{resources::project::Project, resources::prtb::ProjectRoleTemplateBinding, resources::rt::RoleTemplate};
+use crate::{events::Action, utils::file::FileFormat, resources::cluster::Cluster, resources::project::Project, resources::prtb::ProjectRoleTemplateBinding, resources::rt::RoleTemplate};
 
 #[derive(Debug, Error, PartialEq, Clone)]
 pub enum ConversionError {
@@ -24,6 +24,71 @@ pub enum ConversionError {
 
     #[error("Other conversion error: {0}")]
     Other(Cow<'static, str>),
+
+    /// The contents of a file, or of a string when `path` is `None`, do not deserialize
+    #[error("{}: {message}", describe_location(*format, path.as_deref(), *line, *column))]
+    Deserialize {
+        format: FileFormat,
+        path: Option<PathBuf>,
+        /// 1-based, as reported by the parser
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+
+    /// A file, or a folder holding too many files, exceeds the [`FileLimits`] and was not read
+    #[error("{}: {reason}", path.display())]
+    OverLimit {
+        path: PathBuf,
+        reason: String,
+    },
+
+    /// The object of a file names another cluster than the cluster folder holding the file
+    #[error("{}: {reason}", path.display())]
+    Misplaced {
+        path: PathBuf,
+        reason: String,
+    },
+}
+
+impl ConversionError {
+    /// The file that failed to deserialize, if the error names one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ConversionError::Deserialize { path, .. } => path.as_deref(),
+            ConversionError::OverLimit { path, .. } | ConversionError::Misplaced { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Name `path` as the file a deserialization error was raised for.
+    pub fn at(self, file: &Path) -> Self {
+        match self {
+            ConversionError::Deserialize { format, line, column, message, .. } => ConversionError::Deserialize {
+                format,
+                path: Some(file.to_path_buf()),
+                line,
+                column,
+                message,
+            },
+            other => other,
+        }
+    }
+}
+
+/// `path:line:column`, or the format in place of a path, leaving out what the parser did not report.
+fn describe_location(format: FileFormat, path: Option<&Path>, line: Option<usize>, column: Option<usize>) -> String {
+    let mut location = match path {
+        Some(path) => path.display().to_string(),
+        None => format!("<{}>", format),
+    };
+    if let Some(line) = line {
+        location.push_str(&format!(":{}", line));
+        if let Some(column) = column {
+            location.push_str(&format!(":{}", column));
+        }
+    }
+    location
 }
 
 impl From<serde_json::Error> for ConversionError {
@@ -189,6 +254,461 @@ impl TryFrom<RoleTemplate> for MinimalObject {
     }
 }
 
+// Clusters are cluster-scoped and their files carry no resource version
+impl From<&Cluster> for MinimalObject {
+    fn from(value: &Cluster) -> Self {
+        MinimalObject {
+            object_id: Some(value.id.clone()),
+            resource_version_match: ResourceVersionMatch::NotOlderThan,
+            resource_version: None,
+            namespace: None,
+        }
+    }
+}
+
+
+
+/// The namespaced object a patch is sent to.
+///
+/// The generated client takes `name` and `namespace` as two positional `&str`
+/// arguments, which makes them easy to swap without the compiler noticing.
+/// Wrappers take this struct instead so call sites have to name both fields.
+///
+/// For projects the namespace is the cluster ID, for project role template
+/// bindings it is the project ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateTarget<'a> {
+    pub namespace: &'a str,
+    pub name: &'a str,
+}
+
+impl std::fmt::Display for UpdateTarget<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.namespace, self.name)
+    }
+}
+
+/// What Kubernetes' garbage collector does with the dependents of a deleted object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PropagationPolicy {
+    /// Leave the dependents in place
+    Orphan,
+    /// Delete the object right away and its dependents afterwards
+    Background,
+    /// Keep the object until every dependent is deleted
+    Foreground,
+}
+
+impl PropagationPolicy {
+    /// The value of the `propagationPolicy` query parameter.
+    pub fn as_param(&self) -> &'static str {
+        match self {
+            PropagationPolicy::Orphan => "Orphan",
+            PropagationPolicy::Background => "Background",
+            PropagationPolicy::Foreground => "Foreground",
+        }
+    }
+}
+
+impl std::fmt::Display for PropagationPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PropagationPolicy::Orphan => write!(f, "orphan"),
+            PropagationPolicy::Background => write!(f, "background"),
+            PropagationPolicy::Foreground => write!(f, "foreground"),
+        }
+    }
+}
+
+/// The optional query parameters of a delete call, unset ones are left to the server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeleteParams {
+    pub propagation_policy: Option<PropagationPolicy>,
+    pub grace_period_seconds: Option<i32>,
+}
+
+impl DeleteParams {
+    /// The `propagationPolicy` query parameter.
+    pub fn propagation_param(&self) -> Option<&'static str> {
+        self.propagation_policy.as_ref().map(PropagationPolicy::as_param)
+    }
+}
+
+/// How objects whose files were removed are deleted.
+///
+/// With `wait_for_deletion`, a deletion only completes once the object is gone,
+/// which with foreground propagation is after its dependents are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeletionOptions {
+    pub role_template: Option<PropagationPolicy>,
+    pub project: Option<PropagationPolicy>,
+    pub project_role_template_binding: Option<PropagationPolicy>,
+    pub cluster_role_template_binding: Option<PropagationPolicy>,
+    pub grace_period_seconds: Option<i32>,
+    pub wait_for_deletion: Option<ReadyPolicy>,
+}
+
+impl DeletionOptions {
+    /// The query parameters of the delete call for an object of `object_type`.
+    pub fn params(&self, object_type: ObjectType) -> DeleteParams {
+        let propagation_policy = match object_type {
+            ObjectType::RoleTemplate => self.role_template,
+            ObjectType::Project => self.project,
+            ObjectType::ProjectRoleTemplateBinding => self.project_role_template_binding,
+            ObjectType::ClusterRoleTemplateBinding => self.cluster_role_template_binding,
+            ObjectType::Cluster => None,
+        };
+        DeleteParams {
+            propagation_policy,
+            grace_period_seconds: self.grace_period_seconds,
+        }
+    }
+}
+
+
+/// How objects that drifted from their files are brought back in line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateStrategy {
+    /// Send a JSON patch containing only the differing fields
+    #[default]
+    Patch,
+    /// PUT the object from the file, keeping server-managed fields from the remote object
+    Replace,
+}
+
+impl std::fmt::Display for UpdateStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateStrategy::Patch => write!(f, "patch"),
+            UpdateStrategy::Replace => write!(f, "replace"),
+        }
+    }
+}
+
+/// Which objects in Rancher a sync takes care of.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ManagementMode {
+    /// Every object in a synced cluster is compared, those without a file are reported as
+    /// removed from the repository and left alone
+    #[default]
+    RemoteIsSource,
+    /// Only objects with a file are compared, those without one are out of scope. Bindings
+    /// without a file in the projects and clusters of the repository are deleted with `prune`
+    RepoIsSource,
+}
+
+impl std::fmt::Display for ManagementMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManagementMode::RemoteIsSource => write!(f, "remote-is-source"),
+            ManagementMode::RepoIsSource => write!(f, "repo-is-source"),
+        }
+    }
+}
+
+/// What Shepherd does with Rancher every run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyncMode {
+    /// Apply the changes of the repository to Rancher
+    #[default]
+    Gitops,
+    /// Only download Rancher into the repository and commit it, never changing Rancher
+    Mirror,
+}
+
+impl std::fmt::Display for SyncMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncMode::Gitops => write!(f, "gitops"),
+            SyncMode::Mirror => write!(f, "mirror"),
+        }
+    }
+}
+
+
+/// Status conditions that must be `True` before a newly created project is used.
+pub const DEFAULT_PROJECT_READY_CONDITIONS: &[&str] = &["BackingNamespaceCreated", "InitialRolesPopulated"];
+
+/// How the waits between polls grow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backoff {
+    /// Every wait is the interval
+    #[default]
+    Fixed,
+    /// Each wait doubles the one before, up to `max_interval`
+    Exponential { max_interval: Duration },
+}
+
+/// How a newly created or deleted object is polled until it is ready or gone.
+///
+/// The object is fetched, then again after every wait, as long as the waits add up to less
+/// than `max_wait`. It is always fetched at least once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadyPolicy {
+    pub max_wait: Duration,
+    /// The first wait between two polls
+    pub interval: Duration,
+    pub backoff: Backoff,
+    /// Stop at the first poll refused with `401` or `403` instead of polling until `max_wait`
+    pub treat_forbidden_as_fatal: bool,
+}
+
+impl ReadyPolicy {
+    /// The wait after `previous`, the interval after the first poll.
+    pub fn next_interval(&self, previous: Option<Duration>) -> Duration {
+        match (self.backoff, previous) {
+            (_, None) | (Backoff::Fixed, _) => self.interval,
+            (Backoff::Exponential { max_interval }, Some(previous)) => (previous * 2).min(max_interval.max(self.interval)),
+        }
+    }
+
+    /// The waits between the polls, whose number is one less than the polls.
+    pub fn intervals(&self) -> Vec<Duration> {
+        let mut intervals = Vec::new();
+        let mut waited = Duration::ZERO;
+        let mut previous = None;
+        loop {
+            let next = self.next_interval(previous);
+            if next.is_zero() || waited + next >= self.max_wait {
+                return intervals;
+            }
+            waited += next;
+            intervals.push(next);
+            previous = Some(next);
+        }
+    }
+}
+
+impl Default for ReadyPolicy {
+    fn default() -> Self {
+        Self {
+            max_wait: Duration::from_secs(10),
+            interval: Duration::from_secs(1),
+            backoff: Backoff::Fixed,
+            treat_forbidden_as_fatal: false,
+        }
+    }
+}
+
+impl std::fmt::Display for ReadyPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "up to {:?} every {:?}", self.max_wait, self.interval)?;
+        if let Backoff::Exponential { max_interval } = self.backoff {
+            write!(f, " doubling to {:?}", max_interval)?;
+        }
+        if self.treat_forbidden_as_fatal {
+            write!(f, ", stopping when forbidden")?;
+        }
+        Ok(())
+    }
+}
+
+/// The [`ReadyPolicy`] of every object type that is polled after it is created.
+///
+/// Project role template bindings are not polled unless their `max_wait` is set,
+/// they are usable as soon as Rancher accepts them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Readiness {
+    pub role_template: ReadyPolicy,
+    pub project: ReadyPolicy,
+    pub project_role_template_binding: ReadyPolicy,
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self {
+            role_template: ReadyPolicy::default(),
+            project: ReadyPolicy::default(),
+            project_role_template_binding: ReadyPolicy {
+                max_wait: Duration::ZERO,
+                ..ReadyPolicy::default()
+            },
+        }
+    }
+}
+
+/// How long each phase of a run may take before it is cut short, `None` never cuts it.
+///
+/// `git` is not a limit of the whole fetch or push but of every wait on the remote to
+/// answer, it is enforced by libgit2 as the git calls cannot be cancelled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseTimeouts {
+    pub download: Option<Duration>,
+    pub git: Option<Duration>,
+    pub compare: Option<Duration>,
+    pub create: Option<Duration>,
+    pub delete: Option<Duration>,
+}
+
+/// The `[max_changes_per_run]` table, the most objects of each kind a run creates, updates or
+/// deletes. Kinds left out are not limited.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChangeLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creates: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updates: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deletes: Option<usize>,
+}
+
+impl std::fmt::Display for ChangeLimits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kinds = [("creates", self.creates), ("updates", self.updates), ("deletes", self.deletes)];
+        let limited: Vec<String> = kinds
+            .iter()
+            .filter_map(|(kind, limit)| limit.map(|limit| format!("{} {}", limit, kind)))
+            .collect();
+        match limited.is_empty() {
+            true => write!(f, "unlimited"),
+            false => write!(f, "{}", limited.join(", ")),
+        }
+    }
+}
+
+/// The changes a run may still send under its [`ChangeLimits`], shared by the clusters of the run.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeBudget {
+    limits: ChangeLimits,
+    created: usize,
+    updated: usize,
+    deleted: usize,
+    deferred: usize,
+}
+
+impl ChangeBudget {
+    pub fn new(limits: ChangeLimits) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    /// Splits `changes`, in the order they are applied, into the prefix the run still sends
+    /// for `action` and the rest, left to the next runs.
+    pub fn take<T>(&mut self, action: Action, mut changes: Vec<T>) -> (Vec<T>, Vec<T>) {
+        let (limit, taken) = match action {
+            Action::Create => (self.limits.creates, &mut self.created),
+            Action::Update => (self.limits.updates, &mut self.updated),
+            Action::Delete => (self.limits.deletes, &mut self.deleted),
+        };
+        let allowed = limit.map_or(changes.len(), |limit| limit.saturating_sub(*taken));
+        let deferred = changes.split_off(allowed.min(changes.len()));
+        *taken += changes.len();
+        self.deferred += deferred.len();
+        (changes, deferred)
+    }
+
+    /// The changes taken so far, including those that failed.
+    pub fn applied(&self) -> usize {
+        self.created + self.updated + self.deleted
+    }
+
+    /// The changes left to the next runs.
+    pub fn deferred(&self) -> usize {
+        self.deferred
+    }
+}
+
+/// When a newly created project counts as ready for bindings to be created in it.
+///
+/// Once the project exists, it is polled at the project's poll interval until every
+/// condition in `conditions` is `True`. If that does not happen within `timeout`, the
+/// project is accepted as soon as it exists, so a Rancher version that never sets a
+/// condition does not block the sync.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectReadiness {
+    pub conditions: Vec<String>,
+    pub timeout: Duration,
+}
+
+impl Default for ProjectReadiness {
+    fn default() -> Self {
+        Self {
+            conditions: DEFAULT_PROJECT_READY_CONDITIONS.iter().map(|c| c.to_string()).collect(),
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+
+/// How often an API call that failed with a transient error is attempted.
+///
+/// Only calls that are safe to repeat are retried: reads, and deletes of a
+/// named object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: usize,
+    pub delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            delay: Duration::from_millis(200),
+        }
+    }
+}
+
+
+/// Bounds on the object files read from the repository, so a generated file too large to
+/// hold in memory is skipped instead of read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileLimits {
+    /// Largest object file read, in bytes
+    pub max_file_size: u64,
+    /// Most files a project folder may hold, none of them are read beyond that
+    pub max_files_per_project: usize,
+}
+
+impl Default for FileLimits {
+    fn default() -> Self {
+        Self {
+            max_file_size: 5 * 1024 * 1024,
+            max_files_per_project: 1000,
+        }
+    }
+}
+
+/// How downloads write the objects of Rancher to the repository.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DownloadOptions {
+    /// Write each project and its bindings to one `<project-id>.bundle.yaml` instead of a
+    /// project folder
+    pub bundle_projects: bool,
+    /// The dot-separated paths of the fields left out of cluster files, see
+    /// [`crate::resources::cluster::Cluster::redacted`]
+    pub cluster_redact_paths: Vec<String>,
+}
+
+impl FileLimits {
+    /// Fails if the file at `path` with `size` bytes is too large to read.
+    pub fn check_size(&self, path: &Path, size: u64) -> Result<(), ConversionError> {
+        if size <= self.max_file_size {
+            return Ok(());
+        }
+        Err(ConversionError::OverLimit {
+            path: path.to_path_buf(),
+            reason: format!("file is {} bytes, over the limit of {} bytes", size, self.max_file_size),
+        })
+    }
+
+    /// Fails if the project folder at `path` holds too many files to read.
+    pub fn check_count(&self, path: &Path, count: usize) -> Result<(), ConversionError> {
+        if count <= self.max_files_per_project {
+            return Ok(());
+        }
+        Err(ConversionError::OverLimit {
+            path: path.to_path_buf(),
+            reason: format!("folder holds {} files, over the limit of {}", count, self.max_files_per_project),
+        })
+    }
+}
 
 
 /// The type of object to be updated in Rancher.
@@ -198,6 +718,7 @@ impl TryFrom<RoleTemplate> for MinimalObject {
 /// - `Project`: Represents a project object.
 /// - `RoleTemplate`: Represents a role template object.
 /// - `ProjectRoleTemplateBinding`: Represents a project-role-template binding object.
+/// - `ClusterRoleTemplateBinding`: Represents a cluster-role-template binding object.
 ///
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Ord, PartialOrd)]
 pub enum ObjectType {
@@ -205,17 +726,54 @@ pub enum ObjectType {
     Project,
     ProjectRoleTemplateBinding,
     Cluster,
+    ClusterRoleTemplateBinding,
 }
 
 impl ObjectType {
+    /// Every object type.
+    pub const ALL: [ObjectType; 5] = [
+        ObjectType::RoleTemplate,
+        ObjectType::Project,
+        ObjectType::ProjectRoleTemplateBinding,
+        ObjectType::Cluster,
+        ObjectType::ClusterRoleTemplateBinding,
+    ];
+
     pub fn priority(&self) -> u8 {
         match self {
             ObjectType::RoleTemplate => 0,
             ObjectType::Project => 1,
             ObjectType::ProjectRoleTemplateBinding => 2,
+            ObjectType::ClusterRoleTemplateBinding => 2,
             ObjectType::Cluster => 3,
         }
     }
+
+    /// Whether objects of the type are ever deleted from Rancher, clusters are only read.
+    pub fn deletable(&self) -> bool {
+        match self {
+            ObjectType::RoleTemplate
+            | ObjectType::Project
+            | ObjectType::ProjectRoleTemplateBinding
+            | ObjectType::ClusterRoleTemplateBinding => true,
+            ObjectType::Cluster => false,
+        }
+    }
+
+    /// The types deleted when their files are removed, unless `deletable_types` says otherwise.
+    pub const DEFAULT_DELETABLE: [ObjectType; 3] =
+        [ObjectType::Project, ObjectType::ProjectRoleTemplateBinding, ObjectType::RoleTemplate];
+
+    /// The plural name of the type in the `management.cattle.io/v3` API, such as `projects`.
+    pub fn resource(&self) -> &'static str {
+        match self {
+            ObjectType::RoleTemplate => "roletemplates",
+            ObjectType::Project => "projects",
+            ObjectType::ProjectRoleTemplateBinding => "projectroletemplatebindings",
+            ObjectType::ClusterRoleTemplateBinding => "clusterroletemplatebindings",
+            ObjectType::Cluster => "clusters",
+        }
+    }
     
     pub fn from_path(_path: &Path) -> Option<Self> {
         // Logic to determine object type from path
@@ -228,11 +786,34 @@ impl ObjectType {
 pub enum CreatedObject {
     // Cluster(Cluster),
     Status(IoK8sApimachineryPkgApisMetaV1Status),
+    /// A child object that Rancher removed together with its deleted parent project
+    CascadeDeleted(ObjectType, MinimalObject),
+    /// An object left alone because its file carries the ignore directive
+    Ignored(ObjectType, MinimalObject),
+    /// An object left in Rancher because objects of its type are never deleted
+    Skipped(ObjectType, MinimalObject),
     Project(IoCattleManagementv3Project),
     RoleTemplate(IoCattleManagementv3RoleTemplate),
     ProjectRoleTemplateBinding(IoCattleManagementv3ProjectRoleTemplateBinding),
+    ClusterRoleTemplateBinding(IoCattleManagementv3ClusterRoleTemplateBinding),
 }
 
-
-
-
+impl CreatedObject {
+    /// The type and metadata of an object returned by Rancher, `None` for statuses and skipped objects.
+    pub fn metadata(&self) -> Option<(ObjectType, &IoK8sApimachineryPkgApisMetaV1ObjectMeta)> {
+        match self {
+            CreatedObject::Project(object) => Some((ObjectType::Project, object.metadata.as_ref()?)),
+            CreatedObject::RoleTemplate(object) => Some((ObjectType::RoleTemplate, object.metadata.as_ref()?)),
+            CreatedObject::ProjectRoleTemplateBinding(object) => {
+                Some((ObjectType::ProjectRoleTemplateBinding, object.metadata.as_ref()?))
+            }
+            CreatedObject::ClusterRoleTemplateBinding(object) => {
+                Some((ObjectType::ClusterRoleTemplateBinding, object.metadata.as_deref()?))
+            }
+            CreatedObject::Status(_)
+            | CreatedObject::CascadeDeleted(..)
+            | CreatedObject::Ignored(..)
+            | CreatedObject::Skipped(..) => None,
+        }
+    }
+}
@@ -1,15 +1,49 @@
-use crate::api::config::RancherClusterConfig;
+use crate::api::config::{ClusterConfig, RancherClusterConfig};
+use crate::error::FailedObject;
+use crate::events::{Action, EventKind, ObjectRef, RunEvents};
+use crate::plan::PlannedChange;
+use crate::state::SyncState;
+use crate::transaction::{Mutation, Transaction};
 use crate::traits::RancherResource;
-use crate::utils::diff::compute_cluster_diff;
-use crate::utils::file::FileFormat;
-use crate::models::{CreatedObject, MinimalObject};
-use crate::resources::project::{create_project, update_project};
-use crate::resources::prtb::update_project_role_template_binding;
-use crate::resources::rt::update_role_template;
+use crate::utils::diff::{
+    diff_cluster_config, guard_removals, has_guarded_removal, unbacked_removals, last_applied, recorded_last_applied, three_way_merge,
+    ClusterDiff, MetadataFilter, ObjectDiff, LAST_APPLIED_ANNOTATION,
+};
+use crate::utils::file::{endpoint_folder, get_file_name_for_object, get_minimal_object_from_path, FileFormat};
+use crate::utils::index::RepoIndex;
+use crate::utils::principal::PrincipalRules;
+use crate::utils::snapshot::DirectorySnapshot;
+use crate::utils::quota::quota_violation;
+use crate::utils::rbac::{role_template_errors, InheritanceGraph};
+use crate::utils::config_validator::{check_cluster_placement, folder_cluster_id, ClusterScoped, Severity, Violation};
+use crate::utils::ignore::{file_has_ignore_directive, has_ignore_directive, strip_directives, DIRECTIVE_PREFIX};
+use crate::utils::git::{
+    file_revision, read_file_at_previous_revision, GIT_COMMIT_ANNOTATION, GIT_PATH_ANNOTATION,
+};
+use crate::models::{
+    ChangeBudget, ConversionError, CreatedObject, DeleteParams, DeletionOptions, ManagementMode, MinimalObject, ProjectReadiness, Readiness, ReadyPolicy,
+    ResourceVersionMatch, RetryPolicy, UpdateStrategy, UpdateTarget,
+};
+use crate::resources::cluster::cluster_folder;
+use crate::resources::project::{create_project, find_project, replace_project, update_project, PROJECT_EXCLUDE_PATHS};
+use crate::resources::prtb::{
+    find_project_role_template_binding, replace_project_role_template_binding, update_project_role_template_binding,
+    PRTB_EXCLUDE_PATHS,
+};
+use crate::resources::crtb::{
+    find_cluster_role_template_binding, replace_cluster_role_template_binding, update_cluster_role_template_binding,
+    ClusterRoleTemplateBinding, CRTB_EXCLUDE_PATHS,
+};
+use crate::resources::rt::{
+    find_role_template, replace_role_template, update_role_template, RoleTemplateFilter, RT_EXCLUDE_PATHS,
+};
+use crate::{
+    await_handles, copy_paths, CRTB_FOLDER, deserialize_object, load_configuration, load_configuration_from_rancher, load_object, ObjectType, RemoteCache, StoredConfigs,
+};
 use crate::{
-    await_handles, load_configuration, load_configuration_from_rancher, load_object, ObjectType,
+    poll_project_ready, poll_project_role_template_binding_ready, poll_role_template_ready, retry_async, retry_transient,
+    wait_for_object_ready, RoleTemplate,
 };
-use crate::{poll_project_ready, poll_role_template_ready, retry_async, RoleTemplate};
 
 use rancher_client::apis::configuration::Configuration;
 use rancher_client::apis::management_cattle_io_v3_api::CreateManagementCattleIoV3NamespacedProjectRoleTemplateBindingError;
@@ -20,14 +54,15 @@ use rancher_client::models::{
 use reqwest::StatusCode;
 
 use futures::{stream, FutureExt, StreamExt};
-use tracing::{debug, error, info, trace};
+use tracing::{debug, error, info, trace, warn, Instrument};
 
 use crate::resources::project::Project;
 use crate::resources::prtb::{create_project_role_template_binding, ProjectRoleTemplateBinding};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
 use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
@@ -37,61 +72,324 @@ use std::time::Duration;
 /// # Arguments
 /// * `configuration`: The configuration object to use for connecting to Rancher
 /// * `config_folder_path`: The path to the folder containing the stored configuration
+/// * `stored_configs`: The stored configuration of the clusters of the run, the one of
+///   `cluster_id` is taken from it
 /// * `cluster_id`: The ID of the cluster to load the stored configuration from
 /// * `file_format`: The file format to load the stored configuration from
+/// * `update_strategy`: Whether drifted objects are patched or replaced
+/// * `metadata_filter`: Annotation and label keys managed by Rancher, never compared or patched
+/// * `principal_rules`: How the principal names of bindings are compared
+/// * `role_template_filter`: The role templates checked for drift
+/// * `retry_policy`: How reads of the live configuration failing with a transient error are retried
+/// * `remote_cache`: The role templates of the endpoint, listed once per run
+/// * `management_mode`: Whether objects without a file are compared
+/// * `quota_below_usage`: Whether projects lowering their resource quota below what is used
+///   only warn or are not updated
+/// * `out_of_scope`: Receives the bindings without a file in the projects and clusters of the
+///   repository when objects without a file are not compared, for pruning
+/// * `index`: The object files of the endpoint folder, to find the file of each object
+/// * `state`: Objects whose files are quarantined are left out
+/// * `previous_snapshot`: Without git, the files as the last run saw them, the previous revision
+///   a whole section must have had to be removed
+/// * `transaction`: Records the live state of every updated object, so the update can be undone
+/// * `plan`: Receives the updates instead of them being sent, to be applied once approved
+/// * `budget`: The updates beyond it are left to the next runs, unless they are planned
+/// * `events`: Receives an `object_planned` event per update before it is sent or planned
+/// * `stamp_git_commit`: Annotate updated objects with the commit and path of their file
+/// * `record_last_applied`: Record the configuration applied to updated objects for three-way merges
+/// * `dry_run`: Send the updates as server-side dry runs
 ///
 /// # Returns
 /// `Vec<Result<CreatedObject, Box<dyn std::error::Error + Send + Sync>>>`: A vector of results containing the created objects
+#[allow(clippy::too_many_arguments)]
 pub async fn compare_and_update_configurations(
     configuration: Arc<Configuration>,
     config_folder_path: &Path,
+    stored_configs: &mut StoredConfigs,
     cluster_id: &str,
     file_format: &FileFormat,
+    update_strategy: UpdateStrategy,
+    metadata_filter: &MetadataFilter,
+    principal_rules: &PrincipalRules,
+    role_template_filter: &RoleTemplateFilter,
+    retry_policy: &RetryPolicy,
+    remote_cache: &RemoteCache,
+    management_mode: ManagementMode,
+    quota_below_usage: Severity,
+    out_of_scope: &mut Vec<(ObjectType, MinimalObject)>,
+    index: &RepoIndex,
+    state: &SyncState,
+    previous_snapshot: Option<&DirectorySnapshot>,
+    mut transaction: Option<&mut Transaction>,
+    mut plan: Option<&mut Vec<PlannedChange>>,
+    budget: &mut ChangeBudget,
+    events: Option<&RunEvents>,
+    stamp_git_commit: bool,
+    record_last_applied: bool,
+    dry_run: bool,
 ) -> Vec<Result<CreatedObject>> {
-    // Load the stored configuration
-    let stored_config = load_configuration(
-        config_folder_path,
-        &configuration.base_path,
-        cluster_id,
-        file_format,
-    )
-    .await
-    .unwrap()
-    .unwrap();
+    let comparison = match stored_configs.take(cluster_id).await {
+        Ok(Some(stored)) => compare_stored(
+            &configuration,
+            stored,
+            cluster_id,
+            metadata_filter,
+            principal_rules,
+            role_template_filter,
+            retry_policy,
+            remote_cache,
+            management_mode,
+        )
+        .await
+        .map(Some),
+        Ok(None) => Ok(None),
+        Err(e) => Err(e),
+    };
+    let comparison = match comparison {
+        Ok(Some(comparison)) => comparison,
+        Ok(None) => return Vec::new(),
+        Err(e) => {
+            error!("Failed to compare cluster `{}`: {:#}", cluster_id, e);
+            return vec![Err(e)];
+        }
+    };
     debug!(
         "Loaded stored configuration for cluster `{}`: {} ",
-        cluster_id, stored_config
+        cluster_id, comparison.stored
     );
+    out_of_scope.extend(comparison.prunable_bindings());
+    let quota_violations = comparison.quota_violations();
+    let mut invalid_role_templates: HashMap<String, Vec<String>> = comparison
+        .stored
+        .role_templates
+        .iter()
+        .map(|rt| (rt.id.clone(), role_template_errors(rt)))
+        .filter(|(_, errors)| !errors.is_empty())
+        .collect();
+    let mut graph = InheritanceGraph::default();
+    for rt in &comparison.live.role_templates {
+        if let Some(name) = rt.metadata.as_ref().and_then(|m| m.name.as_deref()) {
+            graph.insert(name, rt.role_template_names.as_deref().unwrap_or_default());
+        }
+    }
+    for rt in &comparison.stored.role_templates {
+        graph.insert(rt.id.as_str(), rt.role_template_names.as_deref().unwrap_or_default());
+    }
+    for cycle in graph.cycles() {
+        for id in cycle.iter().skip(1) {
+            if comparison.stored.role_templates.iter().any(|rt| rt.id == *id) {
+                let error = format!("role templates inherit from each other: {}", cycle.join(" -> "));
+                invalid_role_templates.entry(id.to_string()).or_default().push(error);
+            }
+        }
+    }
+    // the objects of these files are left out of the comparison
+    let malformed: Vec<Result<CreatedObject>> = comparison
+        .stored
+        .malformed
+        .iter()
+        .cloned()
+        .map(|e| Err(e.into()))
+        .collect();
+    let live_config = comparison.live;
     let stored_config: RancherClusterConfig =
-        RancherClusterConfig::try_from(stored_config).unwrap();
+        RancherClusterConfig::try_from(comparison.stored).unwrap();
 
-    // Load the live Rancher configuration
-    let live_config = load_configuration_from_rancher(&configuration, cluster_id)
-        .await
-        .unwrap();
+    let ignored = ignored_objects(&stored_config);
 
-    // Compute the differences
-    let diffs = compute_cluster_diff(
-        &serde_json::to_value(&live_config).unwrap(),
-        &serde_json::to_value(&stored_config).unwrap(),
-    );
+    let mut diffs = comparison.diff.patches();
+    diffs.retain(|(object_type, object_id, _), _| {
+        !ignored
+            .iter()
+            .any(|(ignored_type, object)| ignored_type == object_type && object.object_id.as_ref() == Some(object_id))
+    });
     debug!(
         "Generated diffs for cluster `{}`: {:#?} ",
         cluster_id, diffs
     );
 
-    let mut results: Vec<Result<CreatedObject>> = Vec::new();
+    let endpoint_path = endpoint_folder(config_folder_path, &configuration.base_path);
+    diffs.retain(|key, _| {
+        let quarantined = object_file(index, &endpoint_path, cluster_id, key, file_format)
+            .is_some_and(|path| state.is_quarantined(&path));
+        if quarantined {
+            debug!("Not updating quarantined {:?} `{}`", key.0, key.1);
+        }
+        !quarantined
+    });
+
+    // Rancher only refuses invalid rules and a quota below the usage of the namespaces once they are sent
+    let mut refused: Vec<Result<CreatedObject>> = Vec::new();
+    diffs.retain(|key, _| {
+        let failed = || FailedObject {
+            object_type: key.0,
+            object_id: key.1.clone(),
+            namespace: key.2.clone(),
+            path: object_file(index, &endpoint_path, cluster_id, key, file_format),
+        };
+        if let Some(errors) = invalid_role_templates.get(&key.1).filter(|_| key.0 == ObjectType::RoleTemplate) {
+            error!("Not updating role template `{}`: {}", key.1, errors.join(", "));
+            refused.push(Err(anyhow::anyhow!("Invalid role template: {}", errors.join(", ")).context(failed())));
+            return false;
+        }
+        let Some(violation) = quota_violations.get(&key.1).filter(|_| key.0 == ObjectType::Project) else {
+            return true;
+        };
+        match quota_below_usage {
+            Severity::Warning => {
+                warn!("{}", violation);
+                true
+            }
+            Severity::Error => {
+                error!("{}, not updating it", violation);
+                refused.push(Err(anyhow::anyhow!("{}", violation).context(failed())));
+                false
+            }
+        }
+    });
+
+    // Removing a whole section is only allowed if the previous revision of the file had it
+    let mut unbacked: HashMap<_, Vec<String>> = HashMap::new();
+    let diffs: HashMap<_, Value> = diffs
+        .into_iter()
+        .filter_map(|(key, patch)| {
+            let previous = if has_guarded_removal(&patch) {
+                object_file(index, &endpoint_path, cluster_id, &key, file_format)
+                    .and_then(|path| previous_revision_value(&endpoint_path, &path, key.0, file_format, previous_snapshot))
+            } else {
+                None
+            };
+            let removals = unbacked_removals(&patch, previous.as_ref());
+            if !removals.is_empty() {
+                unbacked.insert(key.clone(), removals);
+            }
+            guard_removals(patch, previous.as_ref()).map(|patch| (key, patch))
+        })
+        .collect();
+
+    let mut updates: Vec<(_, Value)> = match update_strategy {
+        UpdateStrategy::Patch => diffs.into_iter().collect(),
+        UpdateStrategy::Replace => diffs
+            .into_keys()
+            .filter_map(|key| {
+                let kept = unbacked.get(&key).map_or(&[][..], Vec::as_slice);
+                let body = replacement_body(&live_config, &stored_config, &key, metadata_filter, kept);
+                if body.is_none() {
+                    error!("Could not build replacement for {:?} `{}`", key.0, key.1);
+                }
+                body.map(|body| (key, body))
+            })
+            .collect(),
+    };
+    // in the same order each run, so the updates left to the next runs are the same ones
+    updates.sort_by(|(a, _), (b, _)| (a.0.priority(), a).cmp(&(b.0.priority(), b)));
+    if plan.is_none() {
+        let (applied, deferred) = budget.take(Action::Update, updates);
+        for ((object_type, object_id, _), _) in &deferred {
+            debug!("Leaving the update of {:?} `{}` to the next runs", object_type, object_id);
+        }
+        updates = applied;
+    }
+
+    let mut results: Vec<Result<CreatedObject>> = ignored
+        .into_iter()
+        .map(|(object_type, object)| {
+            info!("Ignoring {:?} `{}`", object_type, object.object_id.as_deref().unwrap_or_default());
+            Ok(CreatedObject::Ignored(object_type, object))
+        })
+        .collect();
+    results.extend(malformed);
+    results.extend(refused);
 
     // Iterate through the differences and handle them use tokio to do them in parallel
-    let mut handles = Vec::with_capacity(diffs.len());
-    for ((object_type, object_id, namespace), diff_value) in diffs {
-        let handle = tokio::spawn(handle_diff(
+    let mut handles = Vec::with_capacity(updates.len());
+    for (key, mut diff_value) in updates {
+        let path = object_file(index, &endpoint_path, cluster_id, &key, file_format);
+        let revision = path.as_deref().and_then(|path| match file_revision(path) {
+            Ok(revision) => revision,
+            Err(e) => {
+                debug!("No git revision for {:?}: {}", path, e);
+                None
+            }
+        });
+        let live = find_object(&live_config, &key);
+        let applied = live
+            .as_ref()
+            .and_then(|live| live.get("metadata")?.get("annotations")?.get(GIT_COMMIT_ANNOTATION)?.as_str());
+        info!(
+            "{:?} `{}` drifted from its file, applied commit: {}, file commit: {}",
+            key.0,
+            key.1,
+            applied.unwrap_or("<none>"),
+            revision.as_ref().map_or("<uncommitted>", |revision| revision.commit.as_str())
+        );
+        let mut stamps = Vec::new();
+        if let (true, Some(revision)) = (stamp_git_commit, &revision) {
+            stamps.push((GIT_COMMIT_ANNOTATION, revision.commit.clone()));
+            stamps.push((GIT_PATH_ANNOTATION, revision.path.clone()));
+        }
+        if record_last_applied {
+            let applied = find_object(&stored_config, &key)
+                .zip(type_exclude_paths(key.0))
+                .map(|(stored, exclude_paths)| last_applied(&stored, exclude_paths));
+            stamps.extend(applied.map(|applied| (LAST_APPLIED_ANNOTATION, applied)));
+        }
+        if !stamps.is_empty() {
+            match update_strategy {
+                UpdateStrategy::Patch => stamp_patch(&mut diff_value, live.as_ref(), &stamps),
+                UpdateStrategy::Replace => stamp_value(&mut diff_value, &stamps),
+            }
+        }
+
+        if let Some(events) = events {
+            events.emit(EventKind::ObjectPlanned {
+                action: Action::Update,
+                object: ObjectRef {
+                    object_type: key.0,
+                    cluster_id: cluster_id.to_string(),
+                    object_id: Some(key.1.clone()),
+                    namespace: key.2.clone(),
+                    path: path.clone(),
+                },
+            });
+        }
+
+        if let Some(plan) = plan.as_deref_mut() {
+            let (object_type, object_id, namespace) = key;
+            let resource_version = live
+                .as_ref()
+                .and_then(|live| live.get("metadata")?.get("resourceVersion")?.as_str())
+                .map(str::to_string);
+            plan.push(PlannedChange::Update {
+                object_type,
+                object_id,
+                namespace,
+                update_strategy,
+                body: diff_value,
+                resource_version,
+                path,
+            });
+            continue;
+        }
+
+        let failed = FailedObject {
+            object_type: key.0,
+            object_id: key.1.clone(),
+            namespace: key.2.clone(),
+            path,
+        };
+        let (object_type, object_id, namespace) = key;
+        let update = handle_diff(
             configuration.clone(),
             object_type,
             object_id,
             namespace,
             diff_value,
-        ));
+            update_strategy,
+            dry_run,
+        );
+        let task = async move { update.await.map_err(|e| e.context(failed)) };
+        let handle = tokio::spawn(task.in_current_span());
         handles.push(handle);
     }
     for result in stream::iter(handles)
@@ -100,7 +398,12 @@ pub async fn compare_and_update_configurations(
         .await
     {
         match result {
-            Ok(object) => results.push(object),
+            Ok(object) => {
+                if let (Some(transaction), Ok(updated)) = (transaction.as_deref_mut(), &object) {
+                    record_update(transaction, &live_config, updated);
+                }
+                results.push(object)
+            }
             Err(e) => results.push(Err(e.into())),
         }
     }
@@ -108,12 +411,266 @@ pub async fn compare_and_update_configurations(
     results
 }
 
-async fn handle_diff(
+/// The configuration of a cluster in the repository and in Rancher, and how they differ.
+pub struct ClusterComparison {
+    pub stored: ClusterConfig,
+    pub live: RancherClusterConfig,
+    pub diff: ClusterDiff,
+    /// The objects without a file, left out of `diff` with [`ManagementMode::RepoIsSource`]
+    pub out_of_scope: Vec<ObjectDiff>,
+}
+
+impl ClusterComparison {
+    /// The changed projects whose file lowers their resource quota below what Rancher reports
+    /// as used, keyed by project ID.
+    pub fn quota_violations(&self) -> HashMap<String, Violation> {
+        self.diff
+            .changes()
+            .filter(|object| object.object_type == ObjectType::Project)
+            .filter_map(|object| {
+                let (stored, _) = self.stored.projects.get(&object.object_id)?;
+                let (live, _) = self.live.projects.get(&object.object_id)?;
+                quota_violation(stored, live).map(|violation| (object.object_id.clone(), violation))
+            })
+            .collect()
+    }
+
+    /// The out of scope bindings in projects that have a file and in the cluster.
+    ///
+    /// Role templates and projects are never pruned, Rancher ships role templates of its own
+    /// and deleting a project deletes its namespaces.
+    pub fn prunable_bindings(&self) -> Vec<(ObjectType, MinimalObject)> {
+        self.out_of_scope
+            .iter()
+            .filter(|object| match object.object_type {
+                ObjectType::ProjectRoleTemplateBinding => object
+                    .namespace
+                    .as_ref()
+                    .is_some_and(|project_id| self.stored.projects.contains_key(project_id)),
+                ObjectType::ClusterRoleTemplateBinding => true,
+                _ => false,
+            })
+            .map(|object| {
+                (
+                    object.object_type,
+                    MinimalObject {
+                        object_id: Some(object.object_id.clone()),
+                        resource_version_match: ResourceVersionMatch::NotOlderThan,
+                        resource_version: None,
+                        namespace: object.namespace.clone(),
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+/// Load the stored and the live configuration of a cluster and diff them.
+///
+/// Sync and `shepherd diff` both compare through this function, so they always agree
+/// on what is added, removed and changed.
+///
+/// # Arguments
+/// * `configuration`: The configuration object to use for connecting to Rancher
+/// * `config_folder_path`: The path to the folder containing the stored configuration
+/// * `cluster_id`: The ID of the cluster to compare
+/// * `file_format`: The file format to load the stored configuration from
+/// * `metadata_filter`: Annotation and label keys managed by Rancher, never compared
+/// * `principal_rules`: How the principal names of bindings are compared
+/// * `role_template_filter`: The live role templates that are compared
+/// * `retry_policy`: How reads of the live configuration failing with a transient error are retried
+/// * `remote_cache`: The role templates of the endpoint, listed once per run
+/// * `management_mode`: With [`ManagementMode::RepoIsSource`] objects without a file are moved
+///   out of the diff into [`ClusterComparison::out_of_scope`]
+/// * `correct_misplaced_objects`: Move projects and bindings naming another cluster than their
+///   folder to the cluster of the folder instead of failing them
+///
+/// # Returns
+/// `None` if the repository has no folder for the cluster
+#[allow(clippy::too_many_arguments)]
+pub async fn compare_cluster(
+    configuration: &Configuration,
+    config_folder_path: &Path,
+    cluster_id: &str,
+    file_format: &FileFormat,
+    metadata_filter: &MetadataFilter,
+    principal_rules: &PrincipalRules,
+    role_template_filter: &RoleTemplateFilter,
+    retry_policy: &RetryPolicy,
+    remote_cache: &RemoteCache,
+    management_mode: ManagementMode,
+    correct_misplaced_objects: bool,
+) -> Result<Option<ClusterComparison>> {
+    let Some(stored) = load_configuration(config_folder_path, &configuration.base_path, cluster_id, file_format, correct_misplaced_objects)
+        .await
+        .context("Failed to load stored configuration")?
+    else {
+        return Ok(None);
+    };
+    compare_stored(
+        configuration,
+        stored,
+        cluster_id,
+        metadata_filter,
+        principal_rules,
+        role_template_filter,
+        retry_policy,
+        remote_cache,
+        management_mode,
+    )
+    .await
+    .map(Some)
+}
+
+/// Diff the stored configuration `stored` of a cluster, already loaded, with its live configuration.
+///
+/// See [`compare_cluster`] for the arguments.
+#[allow(clippy::too_many_arguments)]
+pub async fn compare_stored(
+    configuration: &Configuration,
+    stored: ClusterConfig,
+    cluster_id: &str,
+    metadata_filter: &MetadataFilter,
+    principal_rules: &PrincipalRules,
+    role_template_filter: &RoleTemplateFilter,
+    retry_policy: &RetryPolicy,
+    remote_cache: &RemoteCache,
+    management_mode: ManagementMode,
+) -> Result<ClusterComparison> {
+    let mut live = load_configuration_from_rancher(configuration, cluster_id, remote_cache, retry_policy)
+        .await
+        .context("Failed to load live configuration")?;
+    live.role_templates.retain(|rt| role_template_filter.includes(rt));
+    let mut diff = diff_cluster_config(&stored, &live, metadata_filter, principal_rules)?;
+    let out_of_scope = match management_mode {
+        ManagementMode::RemoteIsSource => Vec::new(),
+        ManagementMode::RepoIsSource => diff.take_removed(),
+    };
+    Ok(ClusterComparison { stored, live, diff, out_of_scope })
+}
+
+/// Append operations setting the annotations `stamps` to the JSON patch `patch`.
+///
+/// Annotations already holding the values are left out, the map is added whole if
+/// `live` has none once `patch` is applied.
+fn stamp_patch(patch: &mut Value, live: Option<&Value>, stamps: &[(&str, String)]) {
+    let Some(operations) = patch.as_array_mut() else {
+        return;
+    };
+    let mut patched = live.cloned().unwrap_or(Value::Null);
+    if let Ok(ops) = serde_json::from_value::<json_patch::Patch>(Value::Array(operations.clone())) {
+        if json_patch::patch(&mut patched, &ops).is_err() {
+            patched = live.cloned().unwrap_or(Value::Null);
+        }
+    }
+    match patched.pointer("/metadata/annotations").and_then(Value::as_object) {
+        Some(annotations) => {
+            for (key, value) in stamps {
+                if annotations.get(*key).and_then(Value::as_str) != Some(value.as_str()) {
+                    operations.push(serde_json::json!({
+                        "op": "add",
+                        "path": format!("/metadata/annotations/{}", key.replace('~', "~0").replace('/', "~1")),
+                        "value": value,
+                    }));
+                }
+            }
+        }
+        None => operations.push(serde_json::json!({
+            "op": "add",
+            "path": "/metadata/annotations",
+            "value": stamps
+                .iter()
+                .map(|(key, value)| (key.to_string(), Value::String(value.clone())))
+                .collect::<serde_json::Map<_, _>>(),
+        })),
+    }
+}
+
+/// Set the annotations `stamps` on the API object `value`.
+fn stamp_value(value: &mut Value, stamps: &[(&str, String)]) {
+    let Some(metadata) = value.get_mut("metadata").and_then(Value::as_object_mut) else {
+        return;
+    };
+    let annotations = metadata
+        .entry("annotations")
+        .or_insert_with(|| Value::Object(serde_json::Map::new()));
+    if let Some(annotations) = annotations.as_object_mut() {
+        for (key, value) in stamps {
+            annotations.insert(key.to_string(), Value::String(value.clone()));
+        }
+    }
+}
+
+/// Record the update of `updated` in `transaction` with the state it had in `live_config`.
+fn record_update(transaction: &mut Transaction, live_config: &RancherClusterConfig, updated: &CreatedObject) {
+    let Some((object_type, metadata)) = updated.metadata() else {
+        return;
+    };
+    let Some(object_id) = metadata.name.clone() else {
+        return;
+    };
+    let key = (object_type, object_id, metadata.namespace.clone());
+    let Some(before) = find_object(live_config, &key) else {
+        error!("No live state of updated {:?} `{}` to roll it back to", key.0, key.1);
+        return;
+    };
+    let (object_type, object_id, namespace) = key;
+    let mutation = Mutation::Updated {
+        object_type,
+        object_id,
+        namespace,
+        before,
+        resource_version: metadata.resource_version.clone(),
+    };
+    if let Err(e) = transaction.record(mutation) {
+        error!("Failed to back up an update: {:#}", e);
+    }
+}
+
+/// The stored objects whose files carry the ignore directive.
+fn ignored_objects(stored_config: &RancherClusterConfig) -> Vec<(ObjectType, MinimalObject)> {
+    let ignored = |object_type: ObjectType, metadata: Option<&IoK8sApimachineryPkgApisMetaV1ObjectMeta>| {
+        let metadata = metadata?;
+        has_ignore_directive(metadata.annotations.as_ref()).then(|| {
+            (
+                object_type,
+                MinimalObject {
+                    object_id: metadata.name.clone(),
+                    resource_version_match: ResourceVersionMatch::Exact,
+                    resource_version: metadata.resource_version.clone(),
+                    namespace: metadata.namespace.clone(),
+                },
+            )
+        })
+    };
+
+    let role_templates = stored_config
+        .role_templates
+        .iter()
+        .filter_map(|rt| ignored(ObjectType::RoleTemplate, rt.metadata.as_ref()));
+    let projects = stored_config.projects.values().flat_map(|(project, prtbs)| {
+        ignored(ObjectType::Project, project.metadata.as_ref()).into_iter().chain(
+            prtbs
+                .iter()
+                .filter_map(|prtb| ignored(ObjectType::ProjectRoleTemplateBinding, prtb.metadata.as_ref())),
+        )
+    });
+    let crtbs = stored_config
+        .crtbs
+        .iter()
+        .filter_map(|crtb| ignored(ObjectType::ClusterRoleTemplateBinding, crtb.metadata.as_deref()));
+
+    role_templates.chain(projects).chain(crtbs).collect()
+}
+
+pub(crate) async fn handle_diff(
     configuration: Arc<Configuration>,
     object_type: ObjectType,
     object_id: String,
     namespace: Option<String>,
     diff_value: Value,
+    update_strategy: UpdateStrategy,
+    dry_run: bool,
 ) -> Result<CreatedObject> {
     match object_type {
         ObjectType::Project => {
@@ -122,7 +679,16 @@ async fn handle_diff(
                 "Updating project `{}` in cluster `{} with diff: {:#?}`",
                 object_id, ns, diff_value
             );
-            let object = update_project(&configuration, ns, &object_id, diff_value).await;
+            let target = UpdateTarget { namespace: ns, name: &object_id };
+            let object = match update_strategy {
+                UpdateStrategy::Patch => {
+                    update_project(&configuration, target, diff_value, dry_run).await
+                }
+                UpdateStrategy::Replace => {
+                    let body = serde_json::from_value(diff_value)?;
+                    replace_project(&configuration, target, body, dry_run).await
+                }
+            };
             match object {
                 Ok(object) => {
                     info!(
@@ -143,7 +709,15 @@ async fn handle_diff(
                 "Update role-template `{}` with diff: {:#?} ",
                 object_id, diff_value
             );
-            let object = update_role_template(&configuration, &object_id, diff_value).await;
+            let object = match update_strategy {
+                UpdateStrategy::Patch => {
+                    update_role_template(&configuration, &object_id, diff_value, dry_run).await
+                }
+                UpdateStrategy::Replace => {
+                    let body = serde_json::from_value(diff_value)?;
+                    replace_role_template(&configuration, &object_id, body, dry_run).await
+                }
+            };
             match object {
                 Ok(object) => Ok(CreatedObject::RoleTemplate(object)),
                 Err(e) => Err(e),
@@ -157,44 +731,340 @@ async fn handle_diff(
                 "Updated prtb `{}` in namespace `{}` with diff: {:#?} ",
                 object_id, ns, diff_value
             );
-            let object =
-                update_project_role_template_binding(&configuration, ns, &object_id, diff_value)
-                    .await;
+            let target = UpdateTarget { namespace: ns, name: &object_id };
+            let object = match update_strategy {
+                UpdateStrategy::Patch => {
+                    update_project_role_template_binding(&configuration, target, diff_value, dry_run)
+                        .await
+                }
+                UpdateStrategy::Replace => {
+                    let body = serde_json::from_value(diff_value)?;
+                    replace_project_role_template_binding(&configuration, target, body, dry_run)
+                        .await
+                }
+            };
             match object {
                 Ok(object) => Ok(CreatedObject::ProjectRoleTemplateBinding(object)),
                 Err(e) => Err(e),
             }
         }
 
+        ObjectType::ClusterRoleTemplateBinding => {
+            let ns = namespace.as_deref().unwrap_or("<no-namespace>");
+            info!("Updated crtb `{}` in cluster `{}`", object_id, ns);
+            debug!(
+                "Updated crtb `{}` in cluster `{}` with diff: {:#?} ",
+                object_id, ns, diff_value
+            );
+            let target = UpdateTarget { namespace: ns, name: &object_id };
+            let object = match update_strategy {
+                UpdateStrategy::Patch => {
+                    update_cluster_role_template_binding(&configuration, target, diff_value, dry_run)
+                        .await
+                }
+                UpdateStrategy::Replace => {
+                    let body = serde_json::from_value(diff_value)?;
+                    replace_cluster_role_template_binding(&configuration, target, body, dry_run)
+                        .await
+                }
+            };
+            object.map(CreatedObject::ClusterRoleTemplateBinding)
+        }
+
         _ => panic!("Unsupported object type: {:?}", object_type),
     }
 }
 
+/// Loads the previous revision of an object's file in API form.
+///
+/// # Arguments
+/// * `endpoint_path` - The folder holding the configuration of the Rancher endpoint
+/// * `file_path` - The file of the object
+/// * `object_type` - The type of the object
+/// * `file_format` - The file format of the configuration files
+/// * `previous_snapshot` - Without git, the files as the last run saw them, which hold the
+///   previous revision instead of git
+///
+/// # Returns
+/// * `Option<Value>` - The previous revision, or `None` if there is none or it cannot be read
+fn previous_revision_value(
+    endpoint_path: &Path,
+    file_path: &Path,
+    object_type: ObjectType,
+    file_format: &FileFormat,
+    previous_snapshot: Option<&DirectorySnapshot>,
+) -> Option<Value> {
+    let contents = match previous_snapshot {
+        Some(snapshot) => snapshot.files.get(file_path)?.contents.clone()?,
+        None => match read_file_at_previous_revision(endpoint_path, file_path) {
+            Ok(contents) => contents?,
+            Err(e) => {
+                warn!("Failed to read previous revision of {:?}: {}", file_path, e);
+                return None;
+            }
+        },
+    };
+
+    match object_type {
+        ObjectType::RoleTemplate => api_value::<RoleTemplate>(&contents, file_format),
+        ObjectType::Project => api_value::<Project>(&contents, file_format),
+        ObjectType::ProjectRoleTemplateBinding => {
+            api_value::<ProjectRoleTemplateBinding>(&contents, file_format)
+        }
+        ObjectType::ClusterRoleTemplateBinding => {
+            api_value::<ClusterRoleTemplateBinding>(&contents, file_format)
+        }
+        ObjectType::Cluster => None,
+    }
+}
+
+/// The file of the object with the diff key `key` as found by `index`, or where
+/// the object would be written by a download if the index does not know it.
+fn object_file(
+    index: &RepoIndex,
+    endpoint_path: &Path,
+    cluster_id: &str,
+    key: &(ObjectType, String, Option<String>),
+    file_format: &FileFormat,
+) -> Option<PathBuf> {
+    let (object_type, object_id, namespace) = key;
+    index
+        .path(*object_type, namespace.as_deref(), object_id)
+        .map(Path::to_path_buf)
+        .or_else(|| object_file_path(endpoint_path, cluster_id, key, file_format))
+}
+
+/// The path of the file of the object with the diff key `key`, `None` for clusters.
+fn object_file_path(
+    endpoint_path: &Path,
+    cluster_id: &str,
+    (object_type, object_id, namespace): &(ObjectType, String, Option<String>),
+    file_format: &FileFormat,
+) -> Option<PathBuf> {
+    let file_name = get_file_name_for_object(object_id, object_type, file_format);
+    let cluster_path = cluster_folder(endpoint_path, cluster_id, file_format);
+    match object_type {
+        ObjectType::RoleTemplate => Some(endpoint_path.join("roles").join(file_name)),
+        ObjectType::Project => Some(cluster_path.join(object_id).join(file_name)),
+        ObjectType::ProjectRoleTemplateBinding => Some(cluster_path.join(namespace.as_ref()?).join(file_name)),
+        ObjectType::ClusterRoleTemplateBinding => Some(cluster_path.join(CRTB_FOLDER).join(file_name)),
+        ObjectType::Cluster => None,
+    }
+}
+
+fn api_value<T: RancherResource>(contents: &str, file_format: &FileFormat) -> Option<Value> {
+    let object: T = deserialize_object(contents, file_format, None).ok()?;
+    serde_json::to_value(object.try_into_api().ok()?).ok()
+}
+
+/// Builds the body used to replace a drifted object.
+///
+/// Starts from the object as stored in the repository and copies every
+/// excluded path from the live object, so server-managed fields survive the
+/// PUT and the live `metadata.resourceVersion` is sent for optimistic
+/// concurrency. Fields added in Rancher since the configuration last applied
+/// to the object are kept, as the patch computed by the diff keeps them.
+///
+/// # Arguments
+/// * `live_config` - The configuration currently in Rancher
+/// * `stored_config` - The configuration stored in the repository
+/// * `key` - The diff key of the object to replace
+/// * `metadata_filter` - Annotation and label keys kept as they are on the remote object
+///
+/// # Returns
+/// * `Option<Value>` - The replacement body, or `None` if the object is missing on either side
+fn replacement_body(
+    live_config: &RancherClusterConfig,
+    stored_config: &RancherClusterConfig,
+    key: &(ObjectType, String, Option<String>),
+    metadata_filter: &MetadataFilter,
+    kept: &[String],
+) -> Option<Value> {
+    let exclude_paths = type_exclude_paths(key.0)?;
+    let live = find_object(live_config, key)?;
+
+    let mut body = find_object(stored_config, key)?;
+    copy_paths(&live, &mut body, exclude_paths);
+    metadata_filter.strip_value(&mut body);
+    metadata_filter.copy_ignored(&live, &mut body);
+    if let Some(mut last_applied) = recorded_last_applied(&live) {
+        metadata_filter.strip_value(&mut last_applied);
+        body = three_way_merge(&live, &body, &last_applied);
+    }
+    for pointer in kept {
+        copy_pointer(&live, &mut body, pointer);
+    }
+    Some(body)
+}
+
+/// Set the value at the JSON `pointer` of `target` to the one of `source`, removing it if
+/// `source` has none.
+fn copy_pointer(source: &Value, target: &mut Value, pointer: &str) {
+    let Some((parent, key)) = pointer.rsplit_once('/') else {
+        return;
+    };
+    let key = key.replace("~1", "/").replace("~0", "~");
+    let Some(parent) = target.pointer_mut(parent).and_then(Value::as_object_mut) else {
+        return;
+    };
+    match source.pointer(pointer) {
+        Some(value) => parent.insert(key, value.clone()),
+        None => parent.remove(&key),
+    };
+}
+
+/// The paths of the API objects of `object_type` Rancher manages, `None` for clusters.
+fn type_exclude_paths(object_type: ObjectType) -> Option<&'static [&'static str]> {
+    match object_type {
+        ObjectType::RoleTemplate => Some(RT_EXCLUDE_PATHS),
+        ObjectType::Project => Some(PROJECT_EXCLUDE_PATHS),
+        ObjectType::ProjectRoleTemplateBinding => Some(PRTB_EXCLUDE_PATHS),
+        ObjectType::ClusterRoleTemplateBinding => Some(CRTB_EXCLUDE_PATHS),
+        ObjectType::Cluster => None,
+    }
+}
+
+/// The object of `config` with the diff key `key`, in API form.
+fn find_object(
+    config: &RancherClusterConfig,
+    (object_type, object_id, namespace): &(ObjectType, String, Option<String>),
+) -> Option<Value> {
+    let named = |metadata: Option<&IoK8sApimachineryPkgApisMetaV1ObjectMeta>| {
+        metadata.and_then(|m| m.name.as_ref()) == Some(object_id)
+    };
+    match object_type {
+        ObjectType::RoleTemplate => config
+            .role_templates
+            .iter()
+            .find(|rt| named(rt.metadata.as_ref()))
+            .and_then(|rt| serde_json::to_value(rt).ok()),
+        ObjectType::Project => config
+            .projects
+            .get(object_id)
+            .and_then(|(project, _)| serde_json::to_value(project).ok()),
+        ObjectType::ProjectRoleTemplateBinding => config
+            .projects
+            .get(namespace.as_ref()?)?
+            .1
+            .iter()
+            .find(|prtb| named(prtb.metadata.as_ref()))
+            .and_then(|prtb| serde_json::to_value(prtb).ok()),
+        ObjectType::ClusterRoleTemplateBinding => config
+            .crtbs
+            .iter()
+            .find(|crtb| named(crtb.metadata.as_deref()))
+            .and_then(|crtb| serde_json::to_value(crtb).ok()),
+        ObjectType::Cluster => None,
+    }
+}
+
 /// Deletes objects from the cluster
+///
+/// Deletions are grouped by project, with each project deleted after its bindings and
+/// role templates deleted last. Rancher removes the bindings of a deleted project on its
+/// own, so a binding that is already gone when its project is deleted in the same batch
+/// is reported as `CreatedObject::CascadeDeleted` instead of an error.
+///
+/// With `deletion_options.wait_for_deletion`, a deletion only succeeds once Rancher
+/// answers `404 Not Found` for the object.
+///
+/// Objects of a type that is never deleted, such as clusters, are reported as
+/// `CreatedObject::Skipped` without any API call.
+///
 /// # Arguments
 /// * `configuration` - The configuration object
 /// * `deleted_files` - A vector of tuples containing the object type and the minimal object
+/// * `skip_cascaded_deletes` - Do not delete bindings of deleted projects, leave them to Rancher
+/// * `deletion_options` - The propagation policy and grace period of the deletions, and how long to wait for them
+/// * `retry_policy` - How deletions failing with a transient error are retried
+/// * `dry_run` - Send the deletions as server-side dry runs
 /// # Returns
 /// * `Vec<Result<CreatedObject, Box<dyn std::error::Error + Send + Sync>>>`
 pub async fn delete_objects(
     configuration: Arc<Configuration>,
     deleted_files: Vec<(ObjectType, MinimalObject)>,
+    skip_cascaded_deletes: bool,
+    deletion_options: &DeletionOptions,
+    retry_policy: &RetryPolicy,
+    dry_run: bool,
 ) -> Vec<Result<CreatedObject>> {
     let mut results = Vec::with_capacity(deleted_files.len());
 
-    // sort the deleted files by object type backwards
+    let deleted_projects: HashSet<String> = deleted_files
+        .iter()
+        .filter(|(object_type, _)| *object_type == ObjectType::Project)
+        .filter_map(|(_, minimal_object)| minimal_object.object_id.clone())
+        .collect();
+
+    // group by project, children before their project, role templates last, then by ID
     let mut deleted_files = deleted_files;
-    deleted_files.sort_by(|a, b| b.0.priority().cmp(&a.0.priority()));
+    deleted_files.sort_by_key(|(object_type, minimal_object)| {
+        let project_id = match object_type {
+            ObjectType::Project => minimal_object.object_id.clone(),
+            ObjectType::ProjectRoleTemplateBinding => minimal_object.namespace.clone(),
+            _ => None,
+        };
+        (
+            *object_type == ObjectType::RoleTemplate,
+            project_id,
+            std::cmp::Reverse(object_type.priority()),
+            minimal_object.object_id.clone(),
+        )
+    });
 
     for (object_type, minimal_object) in deleted_files {
-        match delete_object(&configuration, &object_type, &minimal_object).await {
+        if !object_type.deletable() {
+            warn!(
+                "Not deleting {:?} `{}`, {:?} objects are never deleted",
+                object_type,
+                minimal_object.object_id.as_deref().unwrap_or_default(),
+                object_type
+            );
+            results.push(Ok(CreatedObject::Skipped(object_type, minimal_object)));
+            continue;
+        }
+        let cascaded = object_type == ObjectType::ProjectRoleTemplateBinding
+            && minimal_object
+                .namespace
+                .as_ref()
+                .is_some_and(|project_id| deleted_projects.contains(project_id));
+
+        if cascaded && skip_cascaded_deletes {
+            debug!("Leaving {:?} to be removed with its project", minimal_object);
+            results.push(Ok(CreatedObject::CascadeDeleted(object_type, minimal_object)));
+            continue;
+        }
+
+        let params = deletion_options.params(object_type);
+        let deleted = retry_transient("delete_object", retry_policy, || {
+            delete_object(&configuration, &object_type, &minimal_object, dry_run, params)
+        })
+        .await;
+        // Dry-run deletions leave the object in place
+        let deleted = match (deleted, deletion_options.wait_for_deletion) {
+            (Ok(object), Some(wait)) if !dry_run => wait_until_deleted(&configuration, object_type, &minimal_object, &wait)
+                .await
+                .map(|()| object),
+            (deleted, _) => deleted,
+        };
+        match deleted {
             Ok(object) => {
                 trace!("Deleted object: {:#?}", minimal_object);
                 results.push(Ok(object))
             }
+            Err(e) if cascaded && e.to_string().contains("not found") => {
+                debug!("{:?} was already removed with its project", minimal_object);
+                results.push(Ok(CreatedObject::CascadeDeleted(object_type, minimal_object)))
+            }
             Err(e) => {
                 error!("Error deleting {:?} object: {}", minimal_object, e);
-                results.push(Err(e))
+                let failed = FailedObject {
+                    object_type,
+                    object_id: minimal_object.object_id.clone().unwrap_or_default(),
+                    namespace: minimal_object.namespace.clone(),
+                    path: None,
+                };
+                results.push(Err(e.context(failed)))
             }
         }
     }
@@ -206,30 +1076,317 @@ async fn delete_object(
     configuration: &Arc<Configuration>,
     object_type: &ObjectType,
     minimal_object: &MinimalObject,
+    dry_run: bool,
+    params: DeleteParams,
 ) -> Result<CreatedObject> {
     let name = minimal_object.object_id.as_ref()
         .ok_or_else(|| anyhow::anyhow!("Object ID is required for deletion"))?;
     
-    let namespace = minimal_object.namespace.as_ref()
-        .ok_or_else(|| anyhow::anyhow!("Namespace is required for deletion"))?;
-    
+    // role templates are not namespaced
+    let namespace = match (object_type, minimal_object.namespace.as_ref()) {
+        (ObjectType::RoleTemplate, namespace) => namespace.cloned().unwrap_or_default(),
+        (_, Some(namespace)) => namespace.clone(),
+        (_, None) => anyhow::bail!("Namespace is required for deletion"),
+    };
+    let namespace = &namespace;
+
     match object_type {
         ObjectType::Project => {
-            Project::delete(configuration, name, namespace).await
+            Project::delete(configuration, name, namespace, dry_run, params).await
         },
         ObjectType::ProjectRoleTemplateBinding => {
-            ProjectRoleTemplateBinding::delete(configuration, name, namespace).await
+            ProjectRoleTemplateBinding::delete(configuration, name, namespace, dry_run, params).await
             // ProjectRoleTemplateBinding::delete(configuration, name, namespace).await?;
         },
         ObjectType::RoleTemplate => {
-            RoleTemplate::delete(configuration, name, namespace).await
+            RoleTemplate::delete(configuration, name, namespace, dry_run, params).await
             // RoleTemplate::delete(configuration, name, namespace).await?;
         },
-        _ => return Err(anyhow::anyhow!("Unsupported object type: {:?}", object_type)),
+        ObjectType::ClusterRoleTemplateBinding => {
+            ClusterRoleTemplateBinding::delete(configuration, name, namespace, dry_run, params).await
+        },
+        _ => Err(anyhow::anyhow!("Unsupported object type: {:?}", object_type)),
     }
     
 }
 
+/// Whether Rancher still has the object of `object_type` with `object_id` in `namespace`.
+///
+/// An object being deleted counts as existing until Rancher answers `404 Not Found` for it.
+pub async fn object_exists(
+    configuration: &Configuration,
+    object_type: ObjectType,
+    namespace: &str,
+    object_id: &str,
+) -> Result<bool> {
+    let found = match object_type {
+        ObjectType::Project => find_project(configuration, namespace, object_id, None).await.map(drop),
+        ObjectType::ProjectRoleTemplateBinding => {
+            find_project_role_template_binding(configuration, namespace, object_id, None).await.map(drop)
+        }
+        ObjectType::RoleTemplate => find_role_template(configuration, object_id, None).await.map(drop),
+        ObjectType::ClusterRoleTemplateBinding => {
+            find_cluster_role_template_binding(configuration, namespace, object_id).await.map(drop)
+        }
+        ObjectType::Cluster => anyhow::bail!("Looking up clusters by ID is unsupported"),
+    };
+    match found {
+        Ok(()) => Ok(true),
+        Err(e) if e.to_string().contains("not found") => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Polls a deleted object until Rancher answers `404 Not Found` for it.
+///
+/// Objects with finalizers or deleted with foreground propagation stay around,
+/// marked as terminating, until their dependents are gone.
+async fn wait_until_deleted(
+    configuration: &Configuration,
+    object_type: ObjectType,
+    minimal_object: &MinimalObject,
+    wait: &ReadyPolicy,
+) -> Result<()> {
+    let name = minimal_object.object_id.as_deref().unwrap_or_default();
+    let namespace = minimal_object.namespace.as_deref().unwrap_or_default();
+    let gone = wait_for_object_ready(
+        name,
+        wait,
+        || async move {
+            match object_exists(configuration, object_type, namespace, name).await? {
+                true => Err(anyhow::anyhow!("{:?} `{}` still exists", object_type, name)),
+                false => Ok(()),
+            }
+        },
+        "deletion",
+    )
+    .await;
+    gone.with_context(|| format!("{:?} `{}` was deleted but is still there after {:?}", object_type, name, wait.max_wait))
+}
+
+
+/// Replaces an object with a snapshot of its earlier state, undoing an update.
+///
+/// # Arguments
+/// * `configuration` - The configuration object
+/// * `object_type` - The type of the object
+/// * `object_id` - The ID of the object
+/// * `namespace` - The namespace of the object, if it has one
+/// * `before` - The object in API form as it was before the update
+/// * `resource_version` - The version the update left, the replacement fails if the object changed since
+///
+/// # Returns
+/// * `Result<CreatedObject>` - The restored object
+pub async fn restore_object(
+    configuration: Arc<Configuration>,
+    object_type: ObjectType,
+    object_id: &str,
+    namespace: Option<String>,
+    before: Value,
+    resource_version: Option<String>,
+) -> Result<CreatedObject> {
+    let mut body = before;
+    if let Some(metadata) = body.get_mut("metadata").and_then(Value::as_object_mut) {
+        match resource_version {
+            Some(resource_version) => metadata.insert("resourceVersion".to_string(), Value::String(resource_version)),
+            None => metadata.remove("resourceVersion"),
+        };
+    }
+    handle_diff(
+        configuration,
+        object_type,
+        object_id.to_string(),
+        namespace,
+        body,
+        UpdateStrategy::Replace,
+        false,
+    )
+    .await
+}
+
+/// Creates an object again from the contents of its deleted file, undoing a deletion.
+///
+/// The directives and the server-assigned metadata in the file are left out.
+///
+/// # Arguments
+/// * `configuration` - The configuration object
+/// * `object_type` - The type of the object
+/// * `path` - The path the file had, to locate parse errors
+/// * `contents` - The contents of the file
+/// * `file_format` - The format of the file
+///
+/// # Returns
+/// * `Result<CreatedObject>` - The created object
+pub async fn recreate_object(
+    configuration: &Configuration,
+    object_type: ObjectType,
+    path: &Path,
+    contents: &str,
+    file_format: &FileFormat,
+) -> Result<CreatedObject> {
+    match object_type {
+        ObjectType::RoleTemplate => recreate::<RoleTemplate>(configuration, path, contents, file_format).await,
+        ObjectType::Project => recreate::<Project>(configuration, path, contents, file_format).await,
+        ObjectType::ProjectRoleTemplateBinding => {
+            recreate::<ProjectRoleTemplateBinding>(configuration, path, contents, file_format).await
+        }
+        ObjectType::ClusterRoleTemplateBinding => {
+            recreate::<ClusterRoleTemplateBinding>(configuration, path, contents, file_format).await
+        }
+        ObjectType::Cluster => Err(anyhow::anyhow!("Unsupported object type: {:?}", object_type)),
+    }
+}
+
+/// Get the object of `object_type` with `object_id` in `namespace` from Rancher, in the form
+/// a creation returns it, to write it back to the file it was created from.
+///
+/// # Arguments
+/// * `configuration` - The configuration object
+/// * `object_type` - The type of the object
+/// * `object_id` - The ID Rancher assigned to the object
+/// * `namespace` - The namespace of the object, `None` for role templates
+///
+/// # Returns
+/// * `Result<CreatedObject>` - The object, an error mentioning `not found` if it is gone
+pub async fn fetch_created_object(
+    configuration: &Configuration,
+    object_type: ObjectType,
+    object_id: &str,
+    namespace: Option<&str>,
+) -> Result<CreatedObject> {
+    let namespace = namespace.unwrap_or_default();
+    match object_type {
+        ObjectType::RoleTemplate => Ok(CreatedObject::RoleTemplate(
+            RoleTemplate::get(configuration, object_id, namespace).await?.try_into_api()?,
+        )),
+        ObjectType::Project => Ok(CreatedObject::Project(
+            Project::get(configuration, object_id, namespace).await?.try_into_api()?,
+        )),
+        ObjectType::ProjectRoleTemplateBinding => Ok(CreatedObject::ProjectRoleTemplateBinding(
+            ProjectRoleTemplateBinding::get(configuration, object_id, namespace).await?.try_into_api()?,
+        )),
+        ObjectType::ClusterRoleTemplateBinding => Ok(CreatedObject::ClusterRoleTemplateBinding(
+            ClusterRoleTemplateBinding::get(configuration, object_id, namespace).await?.try_into_api()?,
+        )),
+        ObjectType::Cluster => Err(anyhow::anyhow!("Unsupported object type: {:?}", object_type)),
+    }
+}
+
+/// Set the git annotations of the revision of `file_path` in `annotations`, if the file is committed.
+fn stamp_from_file(file_path: &Path, annotations: &mut Option<HashMap<String, String>>) {
+    match file_revision(file_path) {
+        Ok(Some(revision)) => revision.stamp(annotations),
+        Ok(None) => debug!("Not stamping the object of {:?}, the file is not committed", file_path),
+        Err(e) => debug!("No git revision for {:?}: {}", file_path, e),
+    }
+}
+
+/// Record the API form of `object` as the configuration last applied to it, see [`last_applied`].
+fn stamp_last_applied<T: RancherResource>(object: &mut T) {
+    let applied = object
+        .clone()
+        .try_into_api()
+        .ok()
+        .and_then(|api| serde_json::to_value(api).ok())
+        .map(|value| last_applied(&value, T::exclude_paths()));
+    if let Some(applied) = applied {
+        object.metadata_mut().0.get_or_insert_with(HashMap::new).insert(LAST_APPLIED_ANNOTATION.to_string(), applied);
+    }
+}
+
+/// Create the role template of `file_path`, see [`create_objects`].
+async fn create_role_template(
+    config: Arc<Configuration>,
+    file_path: PathBuf,
+    record_last_applied: bool,
+    stamp_git_commit: bool,
+    dry_run: bool,
+) -> Result<(PathBuf, CreatedObject)> {
+    info!(path = %file_path.display(), "Creating role-template from file");
+    let mut role_template = load_object::<RoleTemplate>(&file_path).await?;
+    let errors = role_template_errors(&role_template);
+    if !errors.is_empty() {
+        anyhow::bail!("Invalid role template in {}: {}", file_path.display(), errors.join(", "));
+    }
+    // a file recreating a deleted object may still carry its version, refused on create
+    role_template.resource_version = None;
+    strip_directives(&mut role_template.annotations);
+    if record_last_applied {
+        stamp_last_applied(&mut role_template);
+    }
+    if stamp_git_commit {
+        stamp_from_file(&file_path, &mut role_template.annotations);
+    }
+    let created = role_template
+        .create(&config, dry_run)
+        .await
+        .with_context(|| format!("Failed to create role template from {}", file_path.display()))?;
+    match created {
+        CreatedObject::RoleTemplate(ref object) => {
+            info!( "Created role-template: {}", object.metadata.as_ref().and_then(|m| m.name.as_deref()).unwrap_or_default() );
+            Ok((file_path, created))
+        }
+        other => {
+            error!( "Failed to create role-template: {:#?}", other );
+            Err(anyhow::anyhow!("Failed to create role-template"))
+        },
+    }
+}
+
+/// The role template files `files` grouped so the ones inheriting from others of `files` come
+/// after them, see [`InheritanceGraph::levels`].
+///
+/// Files that cannot be read come first, creating them reports the error.
+async fn role_template_levels(files: Vec<PathBuf>) -> Vec<Vec<PathBuf>> {
+    let mut graph = InheritanceGraph::default();
+    let mut unreadable = Vec::new();
+    let mut by_id: HashMap<String, PathBuf> = HashMap::new();
+    for path in files {
+        match load_object::<RoleTemplate>(&path).await {
+            Ok(role_template) => {
+                graph.insert(role_template.id.as_str(), role_template.role_template_names.as_deref().unwrap_or_default());
+                by_id.insert(role_template.id, path);
+            }
+            Err(_) => unreadable.push(path),
+        }
+    }
+    let mut ids: Vec<&str> = by_id.keys().map(String::as_str).collect();
+    ids.sort_unstable();
+    let levels = graph
+        .levels(&ids)
+        .into_iter()
+        .map(|level| level.iter().filter_map(|id| by_id.get(id).cloned()).collect());
+    std::iter::once(unreadable).filter(|files| !files.is_empty()).chain(levels).collect()
+}
+
+async fn recreate<T: RancherResource>(
+    configuration: &Configuration,
+    path: &Path,
+    contents: &str,
+    file_format: &FileFormat,
+) -> Result<CreatedObject> {
+    let object: T = deserialize_object(contents, file_format, Some(path))?;
+    let mut value = serde_json::to_value(object.try_into_api()?)?;
+    if let Some(metadata) = value.get_mut("metadata").and_then(Value::as_object_mut) {
+        for field in ["resourceVersion", "uid", "creationTimestamp"] {
+            metadata.remove(field);
+        }
+        if let Some(annotations) = metadata.get_mut("annotations").and_then(Value::as_object_mut) {
+            annotations.retain(|key, _| !key.starts_with(DIRECTIVE_PREFIX));
+        }
+    }
+    let object = T::try_from_api(serde_json::from_value(value)?)?;
+    object.create(configuration, false).await
+}
+
+/// Check that the object of the file at `path` names the cluster of its folder before it is
+/// created, or move it to that cluster with `correct`, see [`check_cluster_placement`].
+fn check_placement<T: ClusterScoped>(path: &Path, object: &mut T, correct: bool) -> Result<(), ConversionError> {
+    match folder_cluster_id(path) {
+        Some(cluster_id) => check_cluster_placement(path, &cluster_id, object, correct),
+        None => Ok(()),
+    }
+}
 
 /// Creates objects from files in the given directory
 ///
@@ -237,37 +1394,37 @@ async fn delete_object(
 /// * `configuration` - The configuration object
 /// * `new_files` - A vector of tuples containing the object type and the path to the file
 /// * `file_format` - The format of the files
+/// * `project_readiness` - The status conditions a new project must report before its bindings are created
+/// * `readiness` - How long new objects of each type are polled for before they count as not ready
+/// * `stamp_git_commit` - Annotate created objects with the commit and path of their file
+/// * `record_last_applied` - Record the configuration applied to created objects for three-way merges
+/// * `correct_misplaced_objects` - Create projects and bindings naming another cluster than their
+///   folder in the cluster of the folder instead of failing them
+/// * `dry_run` - Send the creations as server-side dry runs; nothing is polled for readiness
 ///
 /// # Returns
 /// * `Vec<Result<(PathBuf, CreatedObject)>>`
+#[allow(clippy::too_many_arguments)]
 pub async fn create_objects(
     configuration: Arc<Configuration>,
     new_files: Vec<(ObjectType, PathBuf)>,
-    concurrency: usize, max_retries: usize, retry_delay: Duration
+    concurrency: usize, max_retries: usize, retry_delay: Duration,
+    project_readiness: &ProjectReadiness,
+    readiness: &Readiness,
+    stamp_git_commit: bool,
+    record_last_applied: bool,
+    correct_misplaced_objects: bool,
+    dry_run: bool,
 ) -> Vec<Result<(PathBuf, CreatedObject)>> {
     // Mutable vector for file processing results
     let mut new_files = new_files;
     let mut results = Vec::with_capacity(new_files.len());
 
-    // Sort the files based on object type priority
-    new_files.sort_by(|a, b| {
-        let a_priority = match a.0 {
-            ObjectType::RoleTemplate => 0,
-            ObjectType::Project => 1,
-            ObjectType::ProjectRoleTemplateBinding => 2,
-            ObjectType::Cluster => 3,
-        };
-        let b_priority = match b.0 {
-            ObjectType::RoleTemplate => 0,
-            ObjectType::Project => 1,
-            ObjectType::ProjectRoleTemplateBinding => 2,
-            ObjectType::Cluster => 3,
-        };
-        a_priority.cmp(&b_priority)
-    });
+    // Sort the files based on object type priority, then path, so results come in the same order every run
+    new_files.sort_by(|(a_type, a_path), (b_type, b_path)| (a_type.priority(), a_path).cmp(&(b_type.priority(), b_path)));
 
     // Create vectors to store tasks for different object types
-    let mut handles_role_templates = Vec::with_capacity(
+    let mut role_template_files = Vec::with_capacity(
         new_files
             .iter()
             .filter(|(object_type, _)| *object_type == ObjectType::RoleTemplate)
@@ -285,34 +1442,46 @@ pub async fn create_objects(
             .filter(|(object_type, _)| *object_type == ObjectType::ProjectRoleTemplateBinding)
             .count(),
     );
+    let mut handles_crtbs = Vec::with_capacity(
+        new_files
+            .iter()
+            .filter(|(object_type, _)| *object_type == ObjectType::ClusterRoleTemplateBinding)
+            .count(),
+    );
 
     // Iterate through each file and create tasks based on object type
     for (object_type, file_path) in new_files {
+        if file_has_ignore_directive(&file_path).await {
+            info!(path = %file_path.display(), "Not creating object, its file carries the ignore directive");
+            results.push(
+                get_minimal_object_from_path(object_type, &file_path)
+                    .await
+                    .map(|object| (file_path, CreatedObject::Ignored(object_type, object))),
+            );
+            continue;
+        }
+
         let config = configuration.clone();
         match object_type {
             ObjectType::RoleTemplate => {
-                // Spawn task to create role template
-                handles_role_templates.push(tokio::spawn(async move {
-                    info!(path = %file_path.display(), "Creating role-template from file");
-                    let role_template = load_object::<RoleTemplate>(&file_path).await?;
-                    let created = role_template.create(&config).await?;
-                    match created {
-                        CreatedObject::RoleTemplate(ref object) => {
-                            info!( "Created role-template: {}", object.metadata.as_ref().unwrap().name.as_ref().unwrap() );
-                            Ok((file_path, created))
-                        }
-                        other => {
-                            error!( "Failed to create role-template: {:#?}", other );
-                            Err(anyhow::anyhow!("Failed to create role-template"))
-                        },
-                    }
-                }));
+                // Created once the role templates they inherit from exist
+                role_template_files.push(file_path);
             }
             ObjectType::Project => {
                 // Spawn task to create project
-                handles_projects.push(tokio::spawn(async move {
+                let task = async move {
                     info!(path = %file_path.display(), "Creating project from file");
-                    let project = load_object::<Project>(&file_path).await?;
+                    let mut project = load_object::<Project>(&file_path).await?;
+                    check_placement(&file_path, &mut project, correct_misplaced_objects)?;
+                    project.resource_version = None;
+                    project.uid = None;
+                    strip_directives(&mut project.annotations);
+                    if record_last_applied {
+                        stamp_last_applied(&mut project);
+                    }
+                    if stamp_git_commit {
+                        stamp_from_file(&file_path, &mut project.annotations);
+                    }
                     let mut rancher_p = IoCattleManagementv3Project::try_from(project)?;
                     let cluster_name = rancher_p
                             .spec
@@ -322,16 +1491,16 @@ pub async fn create_objects(
                             .clone();
 
                     // Ensure project has a name or generate one
-                    if rancher_p.metadata.is_none()
-                        || rancher_p.metadata.as_ref().unwrap().name.is_none()
-                    {
+                    if rancher_p.metadata.as_ref().and_then(|m| m.name.as_ref()).is_none() {
                         let mut metadata = rancher_p
                             .metadata
                             .unwrap_or(IoK8sApimachineryPkgApisMetaV1ObjectMeta::default());
                         metadata.generate_name = Some("p-".to_string());
                         rancher_p.metadata = Some(metadata);
                     }
-                    let created = create_project(&config, &cluster_name, rancher_p).await?;
+                    let created = create_project(&config, &cluster_name, rancher_p, dry_run)
+                        .await
+                        .with_context(|| format!("Failed to create project from {}", file_path.display()))?;
                     let display_name = created
                         .metadata
                         .as_ref()
@@ -340,107 +1509,139 @@ pub async fn create_objects(
 
                     info!("Created project: {}", display_name);
                     Ok((file_path, CreatedObject::Project(created)))
-                }));
+                };
+                handles_projects.push(tokio::spawn(task.in_current_span()));
             }
             ObjectType::ProjectRoleTemplateBinding => {
                 // Collect files for ProjectRoleTemplateBinding
                 handles_prtbs.push(file_path);
             }
+            ObjectType::ClusterRoleTemplateBinding => {
+                // Created once the role templates they may reference exist
+                handles_crtbs.push(file_path);
+            }
             _ => unreachable!(),
         }
     }
 
-    // Process role template tasks and poll for readiness
-    let rts = await_handles(handles_role_templates).await;
-    let poll_tasks = rts.into_iter().filter_map(|res| {
-        match res {
-            Ok((path, CreatedObject::RoleTemplate(rt))) => {
-                let configuration = configuration.clone();
-                let fut = async move {
-                    info!(
-                        "Polling role-template {} for readiness",
-                        rt.metadata.as_ref().unwrap().name.as_ref().unwrap()
-                    );
-                    let poll_result = poll_role_template_ready(configuration, &rt).await;
-                    match poll_result {
-                        Ok(_) => {
-                            info!(
-                                "Role-template {} is ready",
-                                rt.metadata.as_ref().unwrap().name.as_ref().unwrap()
-                            );
-                            Ok((path, CreatedObject::RoleTemplate(rt)))
+    // Process role template tasks and poll for readiness, level by level so the role
+    // templates inherited from are ready before the ones inheriting them are created
+    for level in role_template_levels(role_template_files).await {
+        let handles_role_templates: Vec<_> = level
+            .into_iter()
+            .map(|file_path| {
+                let task = create_role_template(configuration.clone(), file_path, record_last_applied, stamp_git_commit, dry_run);
+                tokio::spawn(task.in_current_span())
+            })
+            .collect();
+        let rts = await_handles(handles_role_templates).await;
+        let poll_tasks = rts.into_iter().map(|res| {
+            match res {
+                // Dry-run creations are never persisted, so there is nothing to wait for
+                Ok((path, CreatedObject::RoleTemplate(rt))) if !dry_run => {
+                    let configuration = configuration.clone();
+                    let wait = readiness.role_template;
+                    let fut = async move {
+                        info!(
+                            "Polling role-template {} for readiness",
+                            rt.metadata.as_ref().and_then(|m| m.name.as_deref()).unwrap_or_default()
+                        );
+                        let poll_result = poll_role_template_ready(configuration, &rt, &wait).await;
+                        match poll_result {
+                            Ok(_) => {
+                                info!(
+                                    "Role-template {} is ready",
+                                    rt.metadata.as_ref().and_then(|m| m.name.as_deref()).unwrap_or_default()
+                                );
+                                Ok((path, CreatedObject::RoleTemplate(rt)))
+                            }
+                            Err(e) => Err(e),
                         }
-                        Err(e) => Err(e),
-                    }
-                };
-                Some(fut.boxed())
-            }
-            other => {
-                // Wrap the already-evaluated result into a ready future
-                let fut = async move { other }.boxed();
-                Some(fut)
+                    };
+                    fut.boxed()
+                }
+                other => {
+                    // Wrap the already-evaluated result into a ready future
+                    async move { other }.boxed()
+                }
             }
-        }
-    });
+        });
 
-    // Run polling with a bounded number of concurrent futures
-    let polled_rts: Vec<_> = stream::iter(poll_tasks)
-        .buffer_unordered(concurrency) // Adjust concurrency level here
-        .collect()
-        .await;
+        // Run polling with a bounded number of concurrent futures
+        let polled_rts: Vec<_> = stream::iter(poll_tasks)
+            .buffered(concurrency) // Adjust concurrency level here, results keep the order of the files
+            .collect()
+            .await;
 
-    // Append `polled_rts` to the final results
-    results.extend(polled_rts);
+        // Append `polled_rts` to the final results
+        results.extend(polled_rts);
+    }
 
     // Process project tasks and poll for readiness
     let projects = await_handles(handles_projects).await;
-    let poll_tasks = projects.into_iter().filter_map(|res| {
+    let poll_tasks = projects.into_iter().map(|res| {
         match res {
-            Ok((path, CreatedObject::Project(p))) => {
+            Ok((path, CreatedObject::Project(p))) if !dry_run => {
                 let configuration = configuration.clone();
+                let project_readiness = project_readiness.clone();
+                let wait = readiness.project;
                 let fut = async move {
                     info!(
                         "Polling project {} for readiness",
-                        p.metadata.as_ref().unwrap().name.as_ref().unwrap()
+                        p.metadata.as_ref().and_then(|m| m.name.as_deref()).unwrap_or_default()
                     );
-                    let poll_result = poll_project_ready(configuration, &p).await;
+                    let poll_result = poll_project_ready(configuration, &p, &project_readiness, &wait).await;
                     match poll_result {
                         Ok(_) => {
                             info!(
                                 "Project {} is ready",
-                                p.metadata.as_ref().unwrap().name.as_ref().unwrap()
+                                p.metadata.as_ref().and_then(|m| m.name.as_deref()).unwrap_or_default()
                             );
                             Ok((path, CreatedObject::Project(p)))
                         }
                         Err(e) => Err(e),
                     }
                 };
-                Some(fut.boxed())
+                fut.boxed()
             }
             other => {
                 // Wrap the already-evaluated result into a ready future
-                let fut = async move { other }.boxed();
-                Some(fut)
+                async move { other }.boxed()
             }
         }
     });
 
     // Run polling with a bounded number of concurrent futures
     let polled_projects: Vec<_> = stream::iter(poll_tasks)
-        .buffer_unordered(concurrency) // Adjust concurrency level here
+        .buffered(concurrency) // Adjust concurrency level here, results keep the order of the files
         .collect()
         .await;
 
     // Append `polled_projects` to the final results
     results.extend(polled_projects);
 
+    // A dry-run binding in a project that was only dry-run created can never succeed
+    let max_retries = if dry_run { 1 } else { max_retries };
+
     // Process ProjectRoleTemplateBinding files
     let mut prtb_handles = Vec::with_capacity(handles_prtbs.len());
+    // Bindings are only polled when a wait is configured for them
+    let prtb_wait = Some(readiness.project_role_template_binding).filter(|wait| !wait.max_wait.is_zero() && !dry_run);
     for file_path in handles_prtbs {
         let config = configuration.clone();
-        prtb_handles.push(tokio::spawn(async move {
+        let task = async move {
             info!(path = %file_path.display(), "Creating project-role-template-binding from file");
-            let prtb = load_object::<ProjectRoleTemplateBinding>(&file_path).await?;
+            let mut prtb = load_object::<ProjectRoleTemplateBinding>(&file_path).await?;
+            check_placement(&file_path, &mut prtb, correct_misplaced_objects)?;
+            prtb.resource_version = None;
+            prtb.uid = None;
+            strip_directives(&mut prtb.annotations);
+            if record_last_applied {
+                stamp_last_applied(&mut prtb);
+            }
+            if stamp_git_commit {
+                stamp_from_file(&file_path, &mut prtb.annotations);
+            }
             let display_name = prtb.id.clone();
             let mut rancher_prtb = IoCattleManagementv3ProjectRoleTemplateBinding::try_from(prtb)?;
             let project_id = rancher_prtb
@@ -450,7 +1651,7 @@ pub async fn create_objects(
                 .ok_or_else(|| anyhow::anyhow!("Missing metadata.namespace in PRTB"))?;
 
             // Ensure PRTB has a name or generate one
-            if rancher_prtb.metadata.is_none() || rancher_prtb.metadata.as_ref().unwrap().name.is_none() {
+            if rancher_prtb.metadata.as_ref().and_then(|m| m.name.as_ref()).is_none() {
                 let mut metadata = rancher_prtb.metadata.unwrap_or(IoK8sApimachineryPkgApisMetaV1ObjectMeta::default());
                 metadata.generate_name = Some("prtb-".to_string());
                 rancher_prtb.metadata = Some(metadata);
@@ -468,7 +1669,7 @@ let result = retry_async(
         let rancher_prtb = rancher_prtb.clone();
         let project_id = project_id.clone();
         async move {
-            create_project_role_template_binding(&config, &project_id, rancher_prtb.clone()).await
+            create_project_role_template_binding(&config, &project_id, rancher_prtb.clone(), dry_run).await
         }
     },
     |err| {
@@ -488,14 +1689,817 @@ let result = retry_async(
 match result {
     Ok(created) => {
         info!("Created PRTB: {}", display_name);
+        if let Some(wait) = prtb_wait {
+            poll_project_role_template_binding_ready(config.clone(), &created, &wait)
+                .await
+                .with_context(|| format!("Project role template binding from {} did not become ready", file_path.display()))?;
+        }
         Ok((file_path, CreatedObject::ProjectRoleTemplateBinding(created)))
     }
-    Err(e) => Err(e), // Already an anyhow::Error
+    Err(e) => Err(e.context(format!(
+        "Failed to create project role template binding from {}",
+        file_path.display()
+    ))),
 }
-        }));
+        };
+        prtb_handles.push(tokio::spawn(task.in_current_span()));
     }
 
     // Append the results of PRTB tasks
     results.extend(await_handles(prtb_handles).await);
+
+    // Process ClusterRoleTemplateBinding files
+    let mut crtb_handles = Vec::with_capacity(handles_crtbs.len());
+    for file_path in handles_crtbs {
+        let config = configuration.clone();
+        let task = async move {
+            info!(path = %file_path.display(), "Creating cluster-role-template-binding from file");
+            let mut crtb = load_object::<ClusterRoleTemplateBinding>(&file_path).await?;
+            check_placement(&file_path, &mut crtb, correct_misplaced_objects)?;
+            crtb.resource_version = None;
+            crtb.uid = None;
+            strip_directives(&mut crtb.annotations);
+            if record_last_applied {
+                stamp_last_applied(&mut crtb);
+            }
+            if stamp_git_commit {
+                stamp_from_file(&file_path, &mut crtb.annotations);
+            }
+            let created = crtb
+                .create(&config, dry_run)
+                .await
+                .with_context(|| format!("Failed to create cluster role template binding from {}", file_path.display()))?;
+            info!("Created CRTB: {}", crtb.id);
+            Ok((file_path, created))
+        };
+        crtb_handles.push(tokio::spawn(task.in_current_span()));
+    }
+    results.extend(await_handles(crtb_handles).await);
     results
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{failed_object, AppError};
+    use crate::models::PropagationPolicy;
+    use crate::utils::ignore::IGNORE_ANNOTATION;
+    use crate::utils::diff::ObjectChange;
+    use crate::report::SyncReport;
+    use crate::utils::file::{get_minimal_object_from_contents, write_back_objects};
+    use crate::utils::git::FileRevision;
+    use rancher_client::models::{IoCattleManagementv3Cluster, IoCattleManagementv3ProjectSpec};
+    use wiremock::matchers::{body_json, body_partial_json, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn sample_project(description: &str, resource_version: Option<&str>) -> IoCattleManagementv3Project {
+        IoCattleManagementv3Project {
+            metadata: Some(IoK8sApimachineryPkgApisMetaV1ObjectMeta {
+                name: Some("p-abc".to_string()),
+                namespace: Some("c-123".to_string()),
+                resource_version: resource_version.map(str::to_string),
+                ..Default::default()
+            }),
+            spec: Some(IoCattleManagementv3ProjectSpec {
+                description: Some(description.to_string()),
+                ..IoCattleManagementv3ProjectSpec::new("c-123".to_string(), "Project".to_string())
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn cluster_config(project: IoCattleManagementv3Project) -> RancherClusterConfig {
+        RancherClusterConfig {
+            cluster: IoCattleManagementv3Cluster::default(),
+            role_templates: vec![],
+            projects: HashMap::from([("p-abc".to_string(), (project, vec![]))]),
+            crtbs: vec![],
+        }
+    }
+
+    fn project_key() -> (ObjectType, String, Option<String>) {
+        (ObjectType::Project, "p-abc".to_string(), Some("c-123".to_string()))
+    }
+
+    async fn mock_config(server: &MockServer) -> Arc<Configuration> {
+        let mut config = Configuration::new();
+        config.base_path = server.uri();
+        Arc::new(config)
+    }
+
+    #[test]
+    fn test_only_bindings_in_stored_projects_and_the_cluster_are_prunable() {
+        let stored: ClusterConfig = serde_json::from_value(serde_json::json!({
+            "cluster": { "id": "c-123", "display_name": "prod" },
+            "role_templates": [],
+            "projects": {
+                "p-abc": [{ "id": "p-abc", "cluster_name": "c-123", "namespace": "c-123", "display_name": "abc" }, []]
+            },
+        }))
+        .unwrap();
+        let out_of_scope = |object_type, object_id: &str, namespace: Option<&str>| ObjectDiff {
+            object_type,
+            object_id: object_id.to_string(),
+            namespace: namespace.map(str::to_string),
+            change: ObjectChange::Removed,
+        };
+        let comparison = ClusterComparison {
+            stored,
+            live: cluster_config(sample_project("live", None)),
+            diff: ClusterDiff::default(),
+            out_of_scope: vec![
+                out_of_scope(ObjectType::RoleTemplate, "project-member", None),
+                out_of_scope(ObjectType::Project, "p-remote", Some("c-123")),
+                out_of_scope(ObjectType::ProjectRoleTemplateBinding, "prtb-stray", Some("p-abc")),
+                out_of_scope(ObjectType::ProjectRoleTemplateBinding, "prtb-remote", Some("p-remote")),
+                out_of_scope(ObjectType::ClusterRoleTemplateBinding, "crtb-stray", Some("c-123")),
+            ],
+        };
+
+        let prunable: Vec<(ObjectType, Option<String>)> = comparison
+            .prunable_bindings()
+            .into_iter()
+            .map(|(object_type, object)| (object_type, object.object_id))
+            .collect();
+
+        assert_eq!(
+            prunable,
+            [
+                (ObjectType::ProjectRoleTemplateBinding, Some("prtb-stray".to_string())),
+                (ObjectType::ClusterRoleTemplateBinding, Some("crtb-stray".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replacement_body_keeps_server_managed_fields() {
+        let live = cluster_config(sample_project("old", Some("42")));
+        let stored = cluster_config(sample_project("new", None));
+
+        let body = replacement_body(&live, &stored, &project_key(), &MetadataFilter::default(), &[]).unwrap();
+
+        assert_eq!(body["spec"]["description"], "new");
+        assert_eq!(body["metadata"]["resourceVersion"], "42");
+    }
+
+    #[test]
+    fn test_replacement_keeps_the_live_value_of_a_removal_the_guard_held_back() {
+        let mut live_project = sample_project("old", Some("42"));
+        live_project.metadata.as_mut().unwrap().labels = Some(HashMap::from([("team".to_string(), "a".to_string())]));
+        let live = cluster_config(live_project);
+        let stored = cluster_config(sample_project("new", None));
+        // the labels were never in the file, removing them is not backed by the previous revision
+        let patch = serde_json::json!([
+            { "op": "replace", "path": "/spec/description", "value": "new" },
+            { "op": "remove", "path": "/metadata/labels" }
+        ]);
+        let unbacked = unbacked_removals(&patch, None);
+        assert_eq!(unbacked, ["/metadata/labels"]);
+        assert!(guard_removals(patch, None).is_some());
+
+        let body = replacement_body(&live, &stored, &project_key(), &MetadataFilter::default(), &unbacked).unwrap();
+
+        assert_eq!(body["spec"]["description"], "new");
+        assert_eq!(body["metadata"]["labels"], serde_json::json!({ "team": "a" }));
+    }
+
+    #[test]
+    fn test_ignored_objects_come_from_annotated_files() {
+        let mut project = sample_project("frozen", None);
+        project.metadata.as_mut().unwrap().annotations =
+            Some(HashMap::from([(IGNORE_ANNOTATION.to_string(), "true".to_string())]));
+
+        let ignored = ignored_objects(&cluster_config(project));
+        let not_ignored = ignored_objects(&cluster_config(sample_project("live", None)));
+
+        assert_eq!(ignored.len(), 1);
+        assert_eq!(ignored[0].0, ObjectType::Project);
+        assert_eq!(ignored[0].1.object_id.as_deref(), Some("p-abc"));
+        assert!(not_ignored.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_objects_skips_new_file_with_ignore_directive() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("p-abc.project.yaml");
+        let mut project = Project::try_from(sample_project("frozen", None)).unwrap();
+        project.annotations = Some(HashMap::from([(IGNORE_ANNOTATION.to_string(), "true".to_string())]));
+        std::fs::write(&file_path, serde_yaml::to_string(&project).unwrap()).unwrap();
+
+        let results = create_objects(
+            mock_config(&server).await,
+            vec![(ObjectType::Project, file_path)],
+            1,
+            1,
+            Duration::from_millis(1),
+            &ProjectReadiness::default(),
+            &Readiness::default(),
+            true,
+            true,
+            false,
+            false,
+        )
+        .await;
+
+        assert!(
+            matches!(results.as_slice(), [Ok((_, CreatedObject::Ignored(ObjectType::Project, _)))]),
+            "{:?}",
+            results
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_objects_refuses_objects_naming_another_cluster_than_their_folder() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let cluster_path = dir.path().join("c-aaa");
+        std::fs::create_dir_all(cluster_path.join("new")).unwrap();
+        std::fs::write(cluster_path.join("c-aaa.cluster.yaml"), "id: c-aaa\ndisplay_name: prod\n").unwrap();
+        let project_file = cluster_path.join("new").join("new.project.yaml");
+        std::fs::write(&project_file, "display_name: web\ncluster_name: c-bbb\nnamespace: c-bbb\n").unwrap();
+
+        let results = create_objects(
+            mock_config(&server).await,
+            vec![(ObjectType::Project, project_file.clone())],
+            1,
+            1,
+            Duration::from_millis(1),
+            &ProjectReadiness::default(),
+            &Readiness::default(),
+            false,
+            false,
+            false,
+            false,
+        )
+        .await;
+
+        let [Err(e)] = results.as_slice() else {
+            panic!("{:?}", results);
+        };
+        assert!(
+            matches!(crate::error::parse_error(e), Some(ConversionError::Misplaced { path, .. }) if *path == project_file),
+            "{:#}",
+            e
+        );
+        assert!(format!("{:#}", e).contains("cluster_name names cluster `c-bbb`"), "{:#}", e);
+    }
+
+    #[test]
+    fn test_stamp_patch_adds_only_changed_annotations() {
+        let revision = FileRevision { commit: "abc1234".to_string(), path: "c-123/p-abc/p-abc.project.yaml".to_string() };
+        let stamps = [(GIT_COMMIT_ANNOTATION, revision.commit.clone()), (GIT_PATH_ANNOTATION, revision.path.clone())];
+        let mut stamped = sample_project("old", None);
+        stamped.metadata.as_mut().unwrap().annotations = Some(HashMap::from([
+            (GIT_COMMIT_ANNOTATION.to_string(), "0000000".to_string()),
+            (GIT_PATH_ANNOTATION.to_string(), revision.path.clone()),
+        ]));
+        let live = serde_json::to_value(stamped).unwrap();
+
+        let mut patch = serde_json::json!([{ "op": "replace", "path": "/spec/description", "value": "new" }]);
+        stamp_patch(&mut patch, Some(&live), &stamps);
+        assert_eq!(
+            patch[1],
+            serde_json::json!({ "op": "add", "path": "/metadata/annotations/shepherd.cattle.io~1git-commit", "value": "abc1234" })
+        );
+        assert_eq!(patch.as_array().unwrap().len(), 2);
+
+        let live = serde_json::to_value(sample_project("old", None)).unwrap();
+        let mut patch = serde_json::json!([]);
+        stamp_patch(&mut patch, Some(&live), &stamps);
+        assert_eq!(patch[0]["path"], "/metadata/annotations");
+        assert_eq!(patch[0]["value"][GIT_COMMIT_ANNOTATION], "abc1234");
+    }
+
+    #[test]
+    fn test_stamp_last_applied_records_the_object_without_managed_fields() {
+        let mut project = Project::try_from(sample_project("web", Some("123"))).unwrap();
+        project.annotations = Some(HashMap::from([
+            ("team".to_string(), "a".to_string()),
+            (GIT_COMMIT_ANNOTATION.to_string(), "abc1234".to_string()),
+        ]));
+
+        stamp_last_applied(&mut project);
+
+        let annotations = project.annotations.as_ref().unwrap();
+        let recorded: Value = serde_json::from_str(&annotations[LAST_APPLIED_ANNOTATION]).unwrap();
+        assert_eq!(recorded.pointer("/spec/description"), Some(&serde_json::json!("web")));
+        assert_eq!(recorded.pointer("/metadata/annotations"), Some(&serde_json::json!({ "team": "a" })));
+        assert_eq!(recorded.pointer("/metadata/resourceVersion"), None);
+        assert_eq!(recorded_last_applied(&serde_json::to_value(project.try_into_api().unwrap()).unwrap()), Some(recorded));
+    }
+
+    #[tokio::test]
+    async fn test_bindings_created_side_by_side_are_written_back_to_their_own_files() {
+        let created = |namespace: &str| {
+            let mut prtb = IoCattleManagementv3ProjectRoleTemplateBinding::new(
+                format!("c-123:{}", namespace),
+                "project-member".to_string(),
+            );
+            prtb.metadata = Some(IoK8sApimachineryPkgApisMetaV1ObjectMeta {
+                name: Some("prtb-generated".to_string()),
+                namespace: Some(namespace.to_string()),
+                ..Default::default()
+            });
+            prtb.user_name = Some("u-abc".to_string());
+            prtb
+        };
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/p-a/projectroletemplatebindings"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(created("p-a")))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/p-b/projectroletemplatebindings"))
+            .respond_with(ResponseTemplate::new(403))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = |namespace: &str| {
+            let folder = dir.path().join("c-123").join(namespace);
+            std::fs::create_dir_all(&folder).unwrap();
+            let path = folder.join("new-member.prtb.yaml");
+            let contents = format!(
+                "id: ''\nnamespace: {0}\nproject_name: c-123:{0}\nrole_template_name: project-member\nuser_name: u-abc\n",
+                namespace
+            );
+            std::fs::write(&path, &contents).unwrap();
+            (path, contents)
+        };
+        let (file_a, _) = file("p-a");
+        let (file_b, contents_b) = file("p-b");
+
+        let results = create_objects(
+            mock_config(&server).await,
+            vec![
+                (ObjectType::ProjectRoleTemplateBinding, file_b.clone()),
+                (ObjectType::ProjectRoleTemplateBinding, file_a.clone()),
+            ],
+            2,
+            1,
+            Duration::from_millis(1),
+            &ProjectReadiness::default(),
+            &Readiness::default(),
+            false,
+            false,
+            false,
+            false,
+        )
+        .await;
+        let mut successes: Vec<_> = results.into_iter().filter_map(Result::ok).collect();
+        assert_eq!(successes.len(), 1);
+        let Some((path, CreatedObject::ProjectRoleTemplateBinding(object))) = successes.pop() else {
+            panic!("expected a created binding");
+        };
+        assert_eq!(path, file_a);
+
+        // a binding paired with the file of another project is never written
+        let written = crate::utils::file::write_back_objects(
+            vec![
+                (file_b.clone(), CreatedObject::ProjectRoleTemplateBinding(object.clone())),
+                (file_a.clone(), CreatedObject::ProjectRoleTemplateBinding(object)),
+            ],
+            dir.path(),
+            FileFormat::Yaml,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(written.written, vec![file_a.clone()]);
+        // the binding exists, its own file is written and the other one is remembered
+        assert_eq!(written.failed.len(), 1);
+        assert_eq!(written.failed[0].0, "prtb-generated");
+        assert_eq!(written.failed[0].1.path, file_b);
+        assert!(std::fs::read_to_string(&file_a).unwrap().contains("id: prtb-generated"));
+        assert_eq!(std::fs::read_to_string(&file_b).unwrap(), contents_b);
+    }
+
+    #[tokio::test]
+    async fn test_created_bindings_keep_the_principal_name_as_written() {
+        let principal = "activedirectory_group://cn=Ops-Team,ou=groups,DC=example,DC=com";
+        let server = MockServer::start().await;
+        let mut created = IoCattleManagementv3ProjectRoleTemplateBinding::new("c-123:p-a".to_string(), "project-member".to_string());
+        created.metadata = Some(IoK8sApimachineryPkgApisMetaV1ObjectMeta {
+            name: Some("prtb-generated".to_string()),
+            namespace: Some("p-a".to_string()),
+            ..Default::default()
+        });
+        created.group_principal_name = Some(principal.to_uppercase());
+        Mock::given(method("POST"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/p-a/projectroletemplatebindings"))
+            .and(body_partial_json(serde_json::json!({ "groupPrincipalName": principal })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(created))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("c-123/p-a");
+        std::fs::create_dir_all(&folder).unwrap();
+        let file = folder.join("ops.prtb.yaml");
+        std::fs::write(
+            &file,
+            format!("id: ''\nnamespace: p-a\nproject_name: c-123:p-a\nrole_template_name: project-member\ngroup_principal_name: {}\n", principal),
+        )
+        .unwrap();
+
+        let results = create_objects(
+            mock_config(&server).await,
+            vec![(ObjectType::ProjectRoleTemplateBinding, file)],
+            1,
+            1,
+            Duration::from_millis(1),
+            &ProjectReadiness::default(),
+            &Readiness::default(),
+            false,
+            false,
+            false,
+            false,
+        )
+        .await;
+
+        assert!(matches!(results.as_slice(), [Ok((_, CreatedObject::ProjectRoleTemplateBinding(_)))]), "{:?}", results);
+    }
+
+    #[test]
+    fn test_replacement_body_missing_remote_object() {
+        let live = RancherClusterConfig {
+            projects: HashMap::new(),
+            ..cluster_config(sample_project("old", Some("42")))
+        };
+        let stored = cluster_config(sample_project("new", None));
+
+        assert!(replacement_body(&live, &stored, &project_key(), &MetadataFilter::default(), &[]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_handle_diff_patch_strategy_sends_patch() {
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(sample_project("new", Some("43"))))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let patch = serde_json::json!([
+            { "op": "replace", "path": "/spec/description", "value": "new" }
+        ]);
+        let (object_type, object_id, namespace) = project_key();
+        let result = handle_diff(
+            mock_config(&server).await,
+            object_type,
+            object_id,
+            namespace,
+            patch,
+            UpdateStrategy::Patch,
+            false,
+        )
+        .await;
+
+        assert!(matches!(result, Ok(CreatedObject::Project(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_handle_diff_replace_strategy_sends_put_with_resource_version() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc"))
+            .and(body_partial_json(serde_json::json!({
+                "metadata": { "resourceVersion": "42" },
+                "spec": { "description": "new" }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(sample_project("new", Some("43"))))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let live = cluster_config(sample_project("old", Some("42")));
+        let stored = cluster_config(sample_project("new", None));
+        let body = replacement_body(&live, &stored, &project_key(), &MetadataFilter::default(), &[]).unwrap();
+
+        let (object_type, object_id, namespace) = project_key();
+        let result = handle_diff(
+            mock_config(&server).await,
+            object_type,
+            object_id,
+            namespace,
+            body,
+            UpdateStrategy::Replace,
+            false,
+        )
+        .await;
+
+        assert!(matches!(result, Ok(CreatedObject::Project(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_handle_diff_sends_remove_for_deleted_annotation() {
+        let mut updated = sample_project("new", Some("43"));
+        updated.metadata.as_mut().unwrap().annotations = Some(HashMap::new());
+
+        let patch = serde_json::json!([
+            { "op": "remove", "path": "/metadata/annotations/example.com~1owner" }
+        ]);
+
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc"))
+            .and(body_json(&patch))
+            .respond_with(ResponseTemplate::new(200).set_body_json(updated))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let (object_type, object_id, namespace) = project_key();
+        let result = handle_diff(
+            mock_config(&server).await,
+            object_type,
+            object_id,
+            namespace,
+            patch,
+            UpdateStrategy::Patch,
+            false,
+        )
+        .await;
+
+        match result {
+            Ok(CreatedObject::Project(project)) => {
+                let annotations = project.metadata.unwrap().annotations.unwrap();
+                assert!(!annotations.contains_key("example.com/owner"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    fn project_deletion() -> Vec<(ObjectType, MinimalObject)> {
+        let minimal = |object_id: &str, namespace: &str| MinimalObject {
+            object_id: Some(object_id.to_string()),
+            resource_version_match: crate::models::ResourceVersionMatch::Exact,
+            resource_version: None,
+            namespace: Some(namespace.to_string()),
+        };
+        vec![
+            (ObjectType::Project, minimal("p-abc", "c-123")),
+            (ObjectType::ProjectRoleTemplateBinding, minimal("prtb-xyz", "p-abc")),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_delete_objects_treats_missing_child_of_deleted_project_as_cascaded() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/p-abc/projectroletemplatebindings/prtb-xyz"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(sample_project("old", None)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let results = delete_objects(mock_config(&server).await, project_deletion(), false, &DeletionOptions::default(), &RetryPolicy::default(), false).await;
+
+        assert!(matches!(
+            results[0],
+            Ok(CreatedObject::CascadeDeleted(ObjectType::ProjectRoleTemplateBinding, _))
+        ));
+        assert!(matches!(results[1], Ok(CreatedObject::Project(_))));
+    }
+
+    #[tokio::test]
+    async fn test_delete_objects_skips_cascaded_children() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/p-abc/projectroletemplatebindings/prtb-xyz"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(sample_project("old", None)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let results = delete_objects(mock_config(&server).await, project_deletion(), true, &DeletionOptions::default(), &RetryPolicy::default(), false).await;
+
+        assert!(results.iter().all(Result::is_ok));
+        assert!(matches!(
+            results[0],
+            Ok(CreatedObject::CascadeDeleted(ObjectType::ProjectRoleTemplateBinding, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_delete_objects_never_deletes_a_cluster() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        // the contents of a deleted cluster file
+        let cluster = get_minimal_object_from_contents(ObjectType::Cluster, "id: c-123\ndisplay_name: prod\n", &FileFormat::Yaml)
+            .await
+            .unwrap();
+
+        let results = delete_objects(
+            mock_config(&server).await,
+            vec![(ObjectType::Cluster, cluster)],
+            false,
+            &DeletionOptions::default(),
+            &RetryPolicy::default(),
+            false,
+        )
+        .await;
+
+        assert!(matches!(results.as_slice(), [Ok(CreatedObject::Skipped(ObjectType::Cluster, _))]));
+        assert!(server.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_the_same_creations_report_the_same_way_in_any_order() {
+        let server = MockServer::start().await;
+        // the earlier their file sorts, the later they are ready
+        for (name, delay_ms) in [("rt-a", 300), ("rt-b", 150), ("rt-c", 0)] {
+            let created = serde_json::json!({ "metadata": { "name": name, "resourceVersion": "1" }, "context": "project" });
+            Mock::given(method("POST"))
+                .and(path("/apis/management.cattle.io/v3/roletemplates"))
+                .and(body_partial_json(serde_json::json!({ "metadata": { "name": name } })))
+                .respond_with(ResponseTemplate::new(201).set_body_json(&created))
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path(format!("/apis/management.cattle.io/v3/roletemplates/{}", name)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(&created).set_delay(Duration::from_millis(delay_ms)))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({ "metadata": { "name": "rt-d" } })))
+            .respond_with(ResponseTemplate::new(422))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<(ObjectType, PathBuf)> = ["rt-c", "rt-a", "rt-d", "rt-b"]
+            .into_iter()
+            .map(|id| {
+                let path = dir.path().join(format!("{}.rt.yaml", id));
+                let contents = format!("id: {}\ncontext: project\nrules:\n- apiGroups:\n  - ''\n  resources:\n  - pods\n  verbs:\n  - get\n", id);
+                std::fs::write(&path, contents).unwrap();
+                (ObjectType::RoleTemplate, path)
+            })
+            .collect();
+
+        let mut runs = Vec::new();
+        for files in [files.clone(), files.into_iter().rev().collect()] {
+            let results = create_objects(
+                mock_config(&server).await,
+                files.clone(),
+                10,
+                1,
+                Duration::ZERO,
+                &ProjectReadiness::default(),
+                &Readiness::default(),
+                false,
+                false,
+                false,
+                false,
+            )
+            .await;
+            let order: Vec<PathBuf> = results
+                .iter()
+                .map(|result| match result {
+                    Ok((path, _)) => path.clone(),
+                    Err(_) => PathBuf::from("failed"),
+                })
+                .collect();
+            let (successes, errors) = crate::error::handle_result_collection(results);
+            let mut report = SyncReport { run_id: "run-1".to_string(), ..SyncReport::default() };
+            report.created = successes.len();
+            report.failed = errors.len();
+            report.pending_drift = files
+                .iter()
+                .filter(|(_, path)| !successes.iter().any(|(created, _)| created == path))
+                .map(|(_, path)| path.clone())
+                .collect();
+            let write_back = write_back_objects(successes, dir.path(), FileFormat::Yaml).await.unwrap();
+            report.sort();
+            runs.push((order, write_back.written, serde_json::to_string(&report).unwrap()));
+        }
+
+        let name = |id: &str| dir.path().join(format!("{}.rt.yaml", id));
+        assert_eq!(runs[0].0, [name("rt-a"), name("rt-b"), name("rt-c"), PathBuf::from("failed")]);
+        assert_eq!(runs[0].1, [name("rt-a"), name("rt-b"), name("rt-c")]);
+        assert_eq!(runs[0], runs[1]);
+    }
+
+    fn foreground_project_deletion(max_wait_ms: u64) -> DeletionOptions {
+        DeletionOptions {
+            project: Some(PropagationPolicy::Foreground),
+            grace_period_seconds: Some(30),
+            wait_for_deletion: Some(ReadyPolicy {
+                max_wait: Duration::from_millis(max_wait_ms),
+                interval: Duration::from_millis(10),
+                ..ReadyPolicy::default()
+            }),
+            ..DeletionOptions::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_objects_waits_until_the_project_is_gone() {
+        let server = MockServer::start().await;
+        let project_path = "/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc";
+        Mock::given(method("DELETE"))
+            .and(path(project_path))
+            .and(query_param("propagationPolicy", "Foreground"))
+            .and(query_param("gracePeriodSeconds", "30"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(sample_project("old", None)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // terminating while its namespace is cleaned up, then gone
+        Mock::given(method("GET"))
+            .and(path(project_path))
+            .respond_with(ResponseTemplate::new(200).set_body_json(sample_project("old", None)))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(project_path))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let deletion = vec![project_deletion().remove(0)];
+        let results = delete_objects(
+            mock_config(&server).await,
+            deletion,
+            false,
+            &foreground_project_deletion(1000),
+            &RetryPolicy::default(),
+            false,
+        )
+        .await;
+
+        assert!(matches!(results.as_slice(), [Ok(CreatedObject::Project(_))]), "{:?}", results);
+    }
+
+    #[tokio::test]
+    async fn test_delete_objects_fails_when_the_project_stays() {
+        let server = MockServer::start().await;
+        let project_path = "/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc";
+        Mock::given(method("DELETE"))
+            .and(path(project_path))
+            .respond_with(ResponseTemplate::new(200).set_body_json(sample_project("old", None)))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(project_path))
+            .respond_with(ResponseTemplate::new(200).set_body_json(sample_project("old", None)))
+            .mount(&server)
+            .await;
+
+        let deletion = vec![project_deletion().remove(0)];
+        let results = delete_objects(
+            mock_config(&server).await,
+            deletion,
+            false,
+            &foreground_project_deletion(50),
+            &RetryPolicy::default(),
+            false,
+        )
+        .await;
+
+        let [Err(e)] = results.as_slice() else {
+            panic!("expected the deletion to fail, got {:?}", results);
+        };
+        assert!(failed_object(e).is_some_and(|failed| failed.object_id == "p-abc"));
+        assert!(matches!(e.downcast_ref::<AppError>(), Some(AppError::TimedOutWaitingReady { .. })), "{:#}", e);
+    }
+}
@@ -0,0 +1,291 @@
+//! Webhook notifications about the outcome of runs.
+
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, warn};
+
+/// Something a run did that is worth a message.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    /// The run ended with failures
+    RunFailed,
+    /// Objects were deleted from Rancher
+    Deletions,
+    /// Objects in Rancher differed from their files and were updated
+    Drift,
+    /// The endpoint kept failing and API calls are paused
+    CircuitOpened,
+    /// A deleted object was still terminating past `escalate_terminating_after`
+    StuckTerminating,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::RunFailed => write!(f, "run_failed"),
+            Event::Deletions => write!(f, "deletions"),
+            Event::Drift => write!(f, "drift"),
+            Event::CircuitOpened => write!(f, "circuit_opened"),
+            Event::StuckTerminating => write!(f, "stuck_terminating"),
+        }
+    }
+}
+
+/// The payload sent when no `template` is configured.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    /// A JSON object with the `event`, `run_id`, `endpoint` and `message` fields
+    #[default]
+    Generic,
+    /// A `text` message, as taken by Slack, Mattermost and Teams incoming webhooks
+    Slack,
+}
+
+impl Preset {
+    fn template(self) -> &'static str {
+        match self {
+            Preset::Generic => {
+                r#"{"event": "{{event}}", "run_id": "{{run_id}}", "endpoint": "{{endpoint}}", "message": "{{message}}"}"#
+            }
+            Preset::Slack => r#"{"text": "Shepherd {{event}} on {{endpoint}} (run {{run_id}}): {{message}}"}"#,
+        }
+    }
+}
+
+/// The `[notifications]` section of the config.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NotificationConfig {
+    /// The webhook the payloads are posted to
+    pub url: String,
+    #[serde(default)]
+    pub preset: Preset,
+    /// The JSON payload with `{{event}}`, `{{run_id}}`, `{{endpoint}}` and `{{message}}`
+    /// placeholders, which are inserted JSON-escaped. Overrides the preset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// The events notified about
+    #[serde(default = "default_events")]
+    pub events: Vec<Event>,
+    /// Seconds to wait for the webhook before giving up on a notification
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+fn default_events() -> Vec<Event> {
+    vec![Event::RunFailed, Event::Deletions, Event::CircuitOpened, Event::StuckTerminating]
+}
+
+fn default_timeout() -> u64 {
+    5
+}
+
+/// A notification about one event of a run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification<'a> {
+    pub event: Event,
+    pub run_id: &'a str,
+    pub endpoint: &'a str,
+    pub message: &'a str,
+}
+
+impl NotificationConfig {
+    /// The payload for `notification`, the template with its placeholders filled in.
+    ///
+    /// # Errors
+    /// If the filled in template is not JSON
+    pub fn render(&self, notification: &Notification) -> Result<String> {
+        let template = self.template.as_deref().unwrap_or_else(|| self.preset.template());
+        let escape = |value: &str| {
+            let quoted = Value::String(value.to_string()).to_string();
+            quoted[1..quoted.len() - 1].to_string()
+        };
+        let payload = template
+            .replace("{{event}}", &escape(&notification.event.to_string()))
+            .replace("{{run_id}}", &escape(notification.run_id))
+            .replace("{{endpoint}}", &escape(notification.endpoint))
+            .replace("{{message}}", &escape(notification.message));
+        serde_json::from_str::<Value>(&payload).context("The notification template does not render to JSON")?;
+        Ok(payload)
+    }
+}
+
+/// Posts notifications to the configured webhook without ever holding up or failing a run.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    client: reqwest::Client,
+    config: NotificationConfig,
+}
+
+impl Notifier {
+    /// A notifier posting with a client of its own, so no Rancher credential reaches the webhook.
+    pub fn new(config: NotificationConfig) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            config,
+        }
+    }
+
+    /// Post `notification` in the background if its event is configured.
+    ///
+    /// Failures are logged as warnings, the returned handle only matters to tests.
+    pub fn notify(&self, notification: &Notification) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.config.events.contains(&notification.event) {
+            return None;
+        }
+        let payload = match self.config.render(notification) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Not sending the {} notification: {:#}", notification.event, e);
+                return None;
+            }
+        };
+
+        let request = self
+            .client
+            .post(&self.config.url)
+            .header(CONTENT_TYPE, "application/json")
+            .timeout(Duration::from_secs(self.config.timeout))
+            .body(payload);
+        let event = notification.event;
+        Some(tokio::spawn(async move {
+            match request.send().await.map(|response| response.error_for_status()) {
+                Ok(Ok(_)) => debug!("Sent the {} notification", event),
+                Ok(Err(e)) => warn!("Failed to send the {} notification: {}", event, e),
+                Err(e) => warn!("Failed to send the {} notification: {}", event, e),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn config(preset: Preset) -> NotificationConfig {
+        NotificationConfig {
+            url: "http://hooks.example.com/shepherd".to_string(),
+            preset,
+            template: None,
+            events: default_events(),
+            timeout: default_timeout(),
+        }
+    }
+
+    fn notification(event: Event, message: &str) -> Notification<'_> {
+        Notification {
+            event,
+            run_id: "20261016T100000.000Z",
+            endpoint: "https://rancher.example.com",
+            message,
+        }
+    }
+
+    #[test]
+    fn test_generic_payloads_of_every_event() {
+        let cases = [
+            (Event::RunFailed, "3 objects failed", "run_failed"),
+            (Event::Deletions, "Deleted 2 objects", "deletions"),
+            (Event::Drift, "Updated 1 drifted object", "drift"),
+            (Event::CircuitOpened, "Pausing API calls for 60s", "circuit_opened"),
+        ];
+
+        for (event, message, name) in cases {
+            assert_eq!(
+                config(Preset::Generic).render(&notification(event, message)).unwrap(),
+                format!(
+                    r#"{{"event": "{}", "run_id": "20261016T100000.000Z", "endpoint": "https://rancher.example.com", "message": "{}"}}"#,
+                    name, message
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_slack_payloads_of_every_event() {
+        let render = |event| config(Preset::Slack).render(&notification(event, "details")).unwrap();
+
+        assert_eq!(
+            render(Event::RunFailed),
+            r#"{"text": "Shepherd run_failed on https://rancher.example.com (run 20261016T100000.000Z): details"}"#
+        );
+        assert_eq!(
+            render(Event::Deletions),
+            r#"{"text": "Shepherd deletions on https://rancher.example.com (run 20261016T100000.000Z): details"}"#
+        );
+        assert_eq!(
+            render(Event::Drift),
+            r#"{"text": "Shepherd drift on https://rancher.example.com (run 20261016T100000.000Z): details"}"#
+        );
+        assert_eq!(
+            render(Event::CircuitOpened),
+            r#"{"text": "Shepherd circuit_opened on https://rancher.example.com (run 20261016T100000.000Z): details"}"#
+        );
+    }
+
+    #[test]
+    fn test_templates_escape_values_and_must_render_json() {
+        let mut custom = config(Preset::Generic);
+        custom.template = Some(r#"{"summary": "{{message}}"}"#.to_string());
+
+        assert_eq!(
+            custom.render(&notification(Event::RunFailed, "quote \" and\nnewline")).unwrap(),
+            r#"{"summary": "quote \" and\nnewline"}"#
+        );
+        custom.template = Some("{{message}}".to_string());
+        assert!(custom.render(&notification(Event::RunFailed, "not json")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_only_configured_events_are_posted() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(body_json(serde_json::json!({"text": "Shepherd deletions on https://rancher.example.com (run 20261016T100000.000Z): Deleted 2 objects"})))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut config = config(Preset::Slack);
+        config.url = format!("{}/hook", server.uri());
+        let notifier = Notifier::new(config);
+
+        assert!(notifier.notify(&notification(Event::Drift, "Updated 1 object")).is_none());
+        notifier
+            .notify(&notification(Event::Deletions, "Deleted 2 objects"))
+            .unwrap()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_webhook_requests_carry_no_authorization() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut config = config(Preset::Generic);
+        config.url = format!("{}/hook", server.uri());
+        let notifier = Notifier::new(config);
+
+        notifier
+            .notify(&notification(Event::RunFailed, "3 objects failed"))
+            .unwrap()
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert!(!requests[0].headers.contains_key(reqwest::header::AUTHORIZATION));
+    }
+}
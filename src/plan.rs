@@ -0,0 +1,540 @@
+//! The changes a run would make to Rancher, written to a plan in [`PENDING_FOLDER`] and only
+//! applied once a person approved them.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use rancher_client::apis::configuration::Configuration;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::models::{
+    CreatedObject, DeletionOptions, MinimalObject, ObjectType, ProjectReadiness, Readiness, ResourceVersionMatch, RetryPolicy,
+    UpdateStrategy,
+};
+use crate::modify::{delete_objects, fetch_created_object, handle_diff, recreate_object};
+use crate::{poll_project_ready, poll_project_role_template_binding_ready, poll_role_template_ready};
+use crate::state::write_atomically;
+use crate::utils::file::FileFormat;
+
+/// The folder of the repository holding the plans waiting for approval, `<run-id>.json` each.
+pub const PENDING_FOLDER: &str = ".shepherd/pending";
+
+/// The folder of the repository holding the approval markers, `<run-id>` each.
+pub const APPROVED_FOLDER: &str = ".shepherd/approved";
+
+/// The branch a plan is committed to with `plan_branch`, followed by the run ID.
+pub const PLAN_BRANCH_PREFIX: &str = "shepherd/plans/";
+
+/// How the changes of a run wait for approval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApprovalPolicy {
+    /// How long a plan waits for its approval before it expires
+    pub ttl: Duration,
+    /// Commit each plan with its approval marker to a branch of its own, approved by merging it
+    pub plan_branch: bool,
+}
+
+/// A change to Rancher a run planned.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum PlannedChange {
+    /// The patch or replacement of an object that drifted from its file
+    Update {
+        object_type: ObjectType,
+        object_id: String,
+        namespace: Option<String>,
+        update_strategy: UpdateStrategy,
+        /// The JSON patch or the replacement, sent as it is
+        body: Value,
+        /// The version of the live object the change was planned against
+        resource_version: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<PathBuf>,
+    },
+    /// The creation of an object from a new file
+    Create {
+        object_type: ObjectType,
+        path: PathBuf,
+        /// The contents of the file when the change was planned, created as they are
+        contents: String,
+    },
+    /// The deletion of an object whose file was deleted, or of a pruned binding
+    Delete {
+        object_type: ObjectType,
+        object_id: String,
+        namespace: Option<String>,
+        /// The version of the live object the change was planned against
+        resource_version: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<PathBuf>,
+    },
+}
+
+impl fmt::Display for PlannedChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlannedChange::Update { object_type, object_id, .. } => write!(f, "update {:?} `{}`", object_type, object_id),
+            PlannedChange::Create { object_type, path, .. } => write!(f, "create {:?} from {}", object_type, path.display()),
+            PlannedChange::Delete { object_type, object_id, .. } => write!(f, "delete {:?} `{}`", object_type, object_id),
+        }
+    }
+}
+
+/// The changes a run planned, waiting in [`PENDING_FOLDER`] until approved or expired.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Plan {
+    pub run_id: String,
+    /// The URL of the Rancher endpoint the changes are for
+    pub endpoint: String,
+    /// The format the files of the creations are written back in
+    pub file_format: FileFormat,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub changes: Vec<PlannedChange>,
+}
+
+impl Plan {
+    pub fn new(run_id: &str, endpoint: &str, file_format: FileFormat, ttl: Duration, changes: Vec<PlannedChange>) -> Self {
+        let created_at = Utc::now();
+        Self {
+            run_id: run_id.to_string(),
+            endpoint: endpoint.to_string(),
+            file_format,
+            created_at,
+            expires_at: created_at + chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX),
+            changes,
+        }
+    }
+
+    /// The file of the plan of `run_id` in the repository at `repo_root`.
+    pub fn file(repo_root: &Path, run_id: &str) -> PathBuf {
+        repo_root.join(PENDING_FOLDER).join(format!("{}.json", run_id))
+    }
+
+    /// The marker approving the plan of `run_id` in the repository at `repo_root`.
+    pub fn approval_file(repo_root: &Path, run_id: &str) -> PathBuf {
+        repo_root.join(APPROVED_FOLDER).join(run_id)
+    }
+
+    /// Write the plan to [`PENDING_FOLDER`] in `repo_root`, returning its file.
+    pub fn save(&self, repo_root: &Path) -> Result<PathBuf> {
+        let path = Self::file(repo_root, &self.run_id);
+        write_atomically(&path, &(serde_json::to_string_pretty(self)? + "\n"))?;
+        Ok(path)
+    }
+
+    /// Read the plan of `run_id` from `repo_root`.
+    ///
+    /// # Errors
+    /// If there is no such plan or it cannot be parsed
+    pub fn load(repo_root: &Path, run_id: &str) -> Result<Self> {
+        let path = Self::file(repo_root, run_id);
+        let contents = std::fs::read_to_string(&path).with_context(|| format!("No plan {} in {}", run_id, path.display()))?;
+        serde_json::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// The plans waiting in `repo_root`, the oldest first.
+    ///
+    /// # Errors
+    /// If a plan cannot be read or parsed
+    pub fn pending(repo_root: &Path) -> Result<Vec<Self>> {
+        let folder = repo_root.join(PENDING_FOLDER);
+        let entries = match std::fs::read_dir(&folder) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", folder.display())),
+        };
+        let mut plans = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let Some(run_id) = path.file_name().and_then(|name| name.to_str()?.strip_suffix(".json")) else {
+                continue;
+            };
+            plans.push(Self::load(repo_root, run_id)?);
+        }
+        plans.sort_by_key(|plan| plan.created_at);
+        Ok(plans)
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Whether the approval marker of the plan is in `repo_root`.
+    pub fn is_approved(&self, repo_root: &Path) -> bool {
+        Self::approval_file(repo_root, &self.run_id).is_file()
+    }
+
+    /// Approve the plan of `run_id` in `repo_root` on behalf of `approver`, returning the marker.
+    ///
+    /// # Errors
+    /// If there is no such plan, it expired, or the marker cannot be written
+    pub fn approve(repo_root: &Path, run_id: &str, approver: &str, now: DateTime<Utc>) -> Result<PathBuf> {
+        let plan = Self::load(repo_root, run_id)?;
+        if plan.is_expired(now) {
+            bail!("Plan {} expired at {}", run_id, plan.expires_at.format("%Y-%m-%d %H:%M:%S UTC"));
+        }
+        let path = Self::approval_file(repo_root, run_id);
+        write_atomically(&path, &format!("approved_by: {}\napproved_at: {}\n", approver, now.to_rfc3339()))?;
+        Ok(path)
+    }
+
+    /// Remove the plan and its approval marker from `repo_root`, returning the removed files.
+    pub fn discard(&self, repo_root: &Path) -> Vec<PathBuf> {
+        [Self::file(repo_root, &self.run_id), Self::approval_file(repo_root, &self.run_id)]
+            .into_iter()
+            .filter(|path| std::fs::remove_file(path).is_ok())
+            .collect()
+    }
+}
+
+/// A summary such as `plan 20261017T100000.000Z: 2 updates, 1 creations, 0 deletions, expires ...`.
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = |kind: fn(&PlannedChange) -> bool| self.changes.iter().filter(|change| kind(change)).count();
+        write!(
+            f,
+            "plan {}: {} updates, {} creations, {} deletions, expires {}",
+            self.run_id,
+            count(|change| matches!(change, PlannedChange::Update { .. })),
+            count(|change| matches!(change, PlannedChange::Create { .. })),
+            count(|change| matches!(change, PlannedChange::Delete { .. })),
+            self.expires_at.format("%Y-%m-%d %H:%M:%S UTC")
+        )
+    }
+}
+
+/// The version of the live object of `object_type` with `object_id` in `namespace`, `None` if it
+/// has none.
+///
+/// # Errors
+/// If the object cannot be read, such as when it is gone
+pub async fn live_resource_version(
+    configuration: &Configuration,
+    object_type: ObjectType,
+    object_id: &str,
+    namespace: Option<&str>,
+) -> Result<Option<String>> {
+    let live = fetch_created_object(configuration, object_type, object_id, namespace).await?;
+    Ok(live.metadata().and_then(|(_, metadata)| metadata.resource_version.clone()))
+}
+
+/// The changes of `plan` whose object changed in Rancher since it was planned, described.
+///
+/// Creations are not checked, the objects they create do not exist yet.
+pub async fn drifted(configuration: &Configuration, plan: &Plan) -> Vec<String> {
+    let mut drifted = Vec::new();
+    for change in &plan.changes {
+        let (object_type, object_id, namespace, planned) = match change {
+            PlannedChange::Update { object_type, object_id, namespace, resource_version, .. }
+            | PlannedChange::Delete { object_type, object_id, namespace, resource_version, .. } => {
+                (*object_type, object_id, namespace, resource_version)
+            }
+            PlannedChange::Create { .. } => continue,
+        };
+        match live_resource_version(configuration, object_type, object_id, namespace.as_deref()).await {
+            Ok(live) if live == *planned => {}
+            Ok(live) => drifted.push(format!(
+                "{} was planned against version {}, Rancher has {}",
+                change,
+                planned.as_deref().unwrap_or("<none>"),
+                live.as_deref().unwrap_or("<none>")
+            )),
+            Err(e) => drifted.push(format!("{} cannot be checked: {:#}", change, e)),
+        }
+    }
+    drifted
+}
+
+/// What applying a plan did.
+#[derive(Debug, Default)]
+pub struct Applied {
+    /// The created objects and the files they were created from, to write back
+    pub created: Vec<(PathBuf, CreatedObject)>,
+    pub updated: Vec<CreatedObject>,
+    pub deleted: Vec<CreatedObject>,
+    pub errors: Vec<anyhow::Error>,
+}
+
+/// Wait for `created` to be ready as a sync does after creating it, so the bindings created
+/// after a project or role template find it.
+async fn wait_until_ready(
+    configuration: Arc<Configuration>,
+    created: &CreatedObject,
+    project_readiness: &ProjectReadiness,
+    readiness: &Readiness,
+) -> Result<()> {
+    match created {
+        CreatedObject::RoleTemplate(rt) => {
+            poll_role_template_ready(configuration, rt, &readiness.role_template).await?;
+        }
+        CreatedObject::Project(project) => {
+            poll_project_ready(configuration, project, project_readiness, &readiness.project).await?;
+        }
+        // bindings are only polled when a wait is configured for them
+        CreatedObject::ProjectRoleTemplateBinding(prtb) if !readiness.project_role_template_binding.max_wait.is_zero() => {
+            poll_project_role_template_binding_ready(configuration, prtb, &readiness.project_role_template_binding).await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Apply the changes of `plan` as they were recorded: the creations first, roles and projects
+/// before their bindings and each polled for readiness, then the updates and the deletions.
+///
+/// Check the plan with [`drifted`] first, the updates and deletions are sent without looking
+/// at the live objects again.
+pub async fn apply_plan(
+    configuration: Arc<Configuration>,
+    plan: &Plan,
+    project_readiness: &ProjectReadiness,
+    readiness: &Readiness,
+    deletion_options: &DeletionOptions,
+    retry_policy: &RetryPolicy,
+) -> Applied {
+    let mut applied = Applied::default();
+    let mut deletions = Vec::new();
+    let mut changes: Vec<&PlannedChange> = plan.changes.iter().collect();
+    // roles and projects before their bindings, whatever order the plan lists them in
+    changes.sort_by_key(|change| match change {
+        PlannedChange::Create { object_type, .. } => (0, object_type.priority()),
+        _ => (1, 0),
+    });
+    for change in changes {
+        info!("Applying {} of plan {}", change, plan.run_id);
+        match change {
+            PlannedChange::Create { object_type, path, contents } => {
+                let created = match recreate_object(&configuration, *object_type, path, contents, &plan.file_format).await {
+                    Ok(created) => created,
+                    Err(e) => {
+                        applied.errors.push(e.context(format!("Failed to {}", change)));
+                        continue;
+                    }
+                };
+                match wait_until_ready(configuration.clone(), &created, project_readiness, readiness).await {
+                    Ok(()) => applied.created.push((path.clone(), created)),
+                    Err(e) => applied.errors.push(e.context(format!("{} did not become ready", change))),
+                }
+            }
+            PlannedChange::Update { object_type, object_id, namespace, update_strategy, body, .. } => {
+                let updated = handle_diff(
+                    configuration.clone(),
+                    *object_type,
+                    object_id.clone(),
+                    namespace.clone(),
+                    body.clone(),
+                    *update_strategy,
+                    false,
+                )
+                .await;
+                match updated {
+                    Ok(updated) => applied.updated.push(updated),
+                    Err(e) => applied.errors.push(e.context(format!("Failed to {}", change))),
+                }
+            }
+            PlannedChange::Delete { object_type, object_id, namespace, resource_version, .. } => {
+                let object = MinimalObject {
+                    object_id: Some(object_id.clone()),
+                    resource_version_match: ResourceVersionMatch::Exact,
+                    resource_version: resource_version.clone(),
+                    namespace: namespace.clone(),
+                };
+                deletions.push((*object_type, object));
+            }
+        }
+    }
+    if !deletions.is_empty() {
+        debug!("Deleting {} objects of plan {}", deletions.len(), plan.run_id);
+        for deleted in delete_objects(configuration, deletions, false, deletion_options, retry_policy, false).await {
+            match deleted {
+                Ok(deleted) => applied.deleted.push(deleted),
+                Err(e) => applied.errors.push(e),
+            }
+        }
+    }
+    applied
+}
+
+/// The `changes` not already waiting in one of the `pending` plans that has not expired at `now`.
+pub fn unplanned(changes: Vec<PlannedChange>, pending: &[Plan], now: DateTime<Utc>) -> Vec<PlannedChange> {
+    changes
+        .into_iter()
+        .filter(|change| {
+            !pending
+                .iter()
+                .filter(|plan| !plan.is_expired(now))
+                .any(|plan| plan.changes.contains(change))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn update(resource_version: &str) -> PlannedChange {
+        PlannedChange::Update {
+            object_type: ObjectType::Project,
+            object_id: "p-abc".to_string(),
+            namespace: Some("c-123".to_string()),
+            update_strategy: UpdateStrategy::Patch,
+            body: json!([{"op": "replace", "path": "/spec/displayName", "value": "web"}]),
+            resource_version: Some(resource_version.to_string()),
+            path: None,
+        }
+    }
+
+    fn creation() -> PlannedChange {
+        PlannedChange::Create {
+            object_type: ObjectType::ClusterRoleTemplateBinding,
+            path: PathBuf::from("/repo/c-123/new.crtb.yaml"),
+            contents: "cluster_name: c-123\nrole_template_name: cluster-member\nuser_name: u-abc\n".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_plans_are_approved_until_they_expire() {
+        let repo = tempfile::tempdir().unwrap();
+        let plan = Plan::new("run-1", "https://rancher.example.com", FileFormat::Yaml, Duration::from_secs(3600), vec![update("7"), creation()]);
+        plan.save(repo.path()).unwrap();
+
+        let pending = Plan::pending(repo.path()).unwrap();
+        assert_eq!(pending, std::slice::from_ref(&plan));
+        assert_eq!(pending[0].to_string(), format!("plan run-1: 1 updates, 1 creations, 0 deletions, expires {}", plan.expires_at.format("%Y-%m-%d %H:%M:%S UTC")));
+        assert!(!plan.is_approved(repo.path()));
+        let too_late = plan.expires_at;
+        assert!(Plan::approve(repo.path(), "run-1", "alice", too_late).is_err());
+        assert!(Plan::approve(repo.path(), "run-2", "alice", plan.created_at).is_err());
+
+        let marker = Plan::approve(repo.path(), "run-1", "alice", plan.created_at).unwrap();
+        assert_eq!(marker, repo.path().join(".shepherd/approved/run-1"));
+        assert!(plan.is_approved(repo.path()));
+        assert_eq!(plan.discard(repo.path()), [Plan::file(repo.path(), "run-1"), marker]);
+        assert!(Plan::pending(repo.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_changes_waiting_in_a_plan_are_not_planned_again() {
+        let plan = Plan::new("run-1", "https://rancher.example.com", FileFormat::Yaml, Duration::from_secs(3600), vec![update("7")]);
+        let now = plan.created_at;
+
+        assert_eq!(unplanned(vec![update("7"), creation()], std::slice::from_ref(&plan), now), [creation()]);
+        // the object changed since, planned against its new version
+        assert_eq!(unplanned(vec![update("8")], std::slice::from_ref(&plan), now), [update("8")]);
+        assert_eq!(unplanned(vec![update("7")], std::slice::from_ref(&plan), plan.expires_at), [update("7")]);
+    }
+
+    #[tokio::test]
+    async fn test_changes_to_objects_changed_since_the_plan_are_drifted() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "apiVersion": "management.cattle.io/v3",
+                "kind": "Project",
+                "metadata": {"name": "p-abc", "namespace": "c-123", "resourceVersion": "8"},
+                "spec": {"clusterName": "c-123", "displayName": "team-a"},
+            })))
+            .mount(&server)
+            .await;
+        let mut configuration = Configuration::new();
+        configuration.base_path = server.uri();
+        let planned = |resource_version| {
+            Plan::new("run-1", &server.uri(), FileFormat::Yaml, Duration::from_secs(3600), vec![update(resource_version), creation()])
+        };
+
+        assert!(drifted(&configuration, &planned("8")).await.is_empty());
+        let drift = drifted(&configuration, &planned("7")).await;
+        assert_eq!(drift, ["update Project `p-abc` was planned against version 7, Rancher has 8"]);
+    }
+
+    #[tokio::test]
+    async fn test_planned_projects_are_created_and_ready_before_their_bindings() {
+        let server = MockServer::start().await;
+        let project = json!({
+            "metadata": {"name": "p-abc", "namespace": "c-123", "resourceVersion": "1"},
+            "spec": {"clusterName": "c-123", "displayName": "web"},
+        });
+        Mock::given(method("POST"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/c-123/projects"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(project.clone()))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(project))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/p-abc/projectroletemplatebindings"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "metadata": {"name": "prtb-abc", "namespace": "p-abc", "resourceVersion": "2"},
+                "projectName": "c-123:p-abc",
+                "roleTemplateName": "project-member",
+                "userName": "u-abc",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut configuration = Configuration::new();
+        configuration.base_path = server.uri();
+        // the binding is listed first
+        let changes = vec![
+            PlannedChange::Create {
+                object_type: ObjectType::ProjectRoleTemplateBinding,
+                path: PathBuf::from("/repo/c-123/p-abc/reader.prtb.yaml"),
+                contents: "id: ''\nnamespace: p-abc\nproject_name: c-123:p-abc\nrole_template_name: project-member\nuser_name: u-abc\n".to_string(),
+            },
+            PlannedChange::Create {
+                object_type: ObjectType::Project,
+                path: PathBuf::from("/repo/c-123/p-abc/p-abc.project.yaml"),
+                contents: "id: p-abc\ncluster_name: c-123\ndisplay_name: web\nnamespace: c-123\n".to_string(),
+            },
+        ];
+        let plan = Plan::new("run-1", &server.uri(), FileFormat::Yaml, Duration::from_secs(3600), changes);
+        let project_readiness = ProjectReadiness {
+            conditions: Vec::new(),
+            ..Default::default()
+        };
+
+        let applied = apply_plan(
+            Arc::new(configuration),
+            &plan,
+            &project_readiness,
+            &Readiness::default(),
+            &DeletionOptions::default(),
+            &RetryPolicy::default(),
+        )
+        .await;
+
+        assert!(applied.errors.is_empty(), "{:?}", applied.errors);
+        assert_eq!(applied.created.len(), 2);
+        let requests: Vec<_> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| format!("{} {}", request.method, request.url.path()))
+            .collect();
+        assert_eq!(
+            requests,
+            [
+                "POST /apis/management.cattle.io/v3/namespaces/c-123/projects",
+                "GET /apis/management.cattle.io/v3/namespaces/c-123/projects/p-abc",
+                "POST /apis/management.cattle.io/v3/namespaces/p-abc/projectroletemplatebindings",
+            ]
+        );
+    }
+}
@@ -0,0 +1,647 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::{permission_error, Access};
+use crate::models::{CreatedObject, ObjectType, SyncMode};
+use crate::state::write_atomically;
+
+/// The file in the state folder holding the report of the last run.
+pub const LAST_REPORT_FILE: &str = "last_report.json";
+
+/// What a single sync run did and how long each phase took.
+///
+/// Phases recorded more than once, such as the per-cluster create phase, are
+/// added up so the summary has one duration per phase.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SyncReport {
+    pub run_id: String,
+    #[serde(default)]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(with = "phase_millis")]
+    pub phases: Vec<(String, Duration)>,
+    pub updated: usize,
+    pub created: usize,
+    pub deleted: usize,
+    pub cascade_deleted: usize,
+    pub ignored: usize,
+    pub failed: usize,
+    /// Files written by a download from Rancher committed in this run
+    pub downloaded: usize,
+    /// Object files that could not be parsed, each listed once
+    pub parse_failures: Vec<PathBuf>,
+    /// Objects skipped after failing too many runs in a row
+    pub quarantined: usize,
+    pub status: RunStatus,
+    /// What the run did per cluster, keyed by cluster ID
+    #[serde(default)]
+    pub clusters: BTreeMap<String, ObjectCounts>,
+    /// The files whose changes failed to reach Rancher
+    #[serde(default)]
+    pub pending_drift: Vec<PathBuf>,
+    /// The commit the branch pointed to after the last successful push of the run
+    #[serde(default)]
+    pub pushed_commit: Option<String>,
+    /// Objects deleted in an earlier run that Rancher still has
+    #[serde(default)]
+    pub stuck_terminating: Vec<StuckObject>,
+    /// API calls refused with `401` or `403`, summed up by what they needed access to
+    #[serde(default)]
+    pub permission_denials: Vec<PermissionDenial>,
+    /// The phases cut short by their timeout, such as `compare_c-abc`
+    #[serde(default)]
+    pub timed_out: Vec<String>,
+    /// The files whose object was created in Rancher but could not be written back
+    #[serde(default)]
+    pub unpersisted: Vec<PathBuf>,
+    /// Objects created by an earlier run whose file this run wrote back
+    #[serde(default)]
+    pub adopted: usize,
+    /// Changes over `max_changes_per_run` left to the next runs
+    #[serde(default)]
+    pub deferred: usize,
+    /// Whether the run applied the repository to Rancher or mirrored Rancher into it
+    #[serde(default)]
+    pub mode: SyncMode,
+    /// The object files a mirror run changed, what changed in Rancher since the last commit
+    #[serde(default)]
+    pub mirrored: MirroredChanges,
+    /// The version of Rancher the run talked to, as last probed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_version: Option<String>,
+}
+
+/// The object files a mirror run added, changed and removed.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct MirroredChanges {
+    pub added: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+impl MirroredChanges {
+    pub fn len(&self) -> usize {
+        self.added.len() + self.modified.len() + self.removed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Display for MirroredChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} changed, {} removed",
+            self.added.len(),
+            self.modified.len(),
+            self.removed.len()
+        )
+    }
+}
+
+/// An object still terminating a run after it was deleted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StuckObject {
+    pub object_type: ObjectType,
+    pub object_id: String,
+    pub minutes: u64,
+}
+
+impl fmt::Display for StuckObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} `{}` stuck terminating for {} minutes",
+            self.object_type, self.object_id, self.minutes
+        )
+    }
+}
+
+/// The API calls of a run refused with the same status for the same access to the objects
+/// of a type in a namespace.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PermissionDenial {
+    /// `401` or `403`
+    pub status: u16,
+    pub access: Access,
+    pub object_type: ObjectType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    pub calls: usize,
+}
+
+/// A diagnosis of the refusals, such as "token appears to lack write permission to
+/// management.cattle.io/v3 projects in cluster c-abc".
+impl fmt::Display for PermissionDenial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scope = match (self.object_type, &self.namespace) {
+            (ObjectType::ProjectRoleTemplateBinding, Some(project_id)) => format!(" in project {}", project_id),
+            (_, Some(cluster_id)) => format!(" in cluster {}", cluster_id),
+            (_, None) => String::new(),
+        };
+        if self.status == 401 {
+            write!(
+                f,
+                "token was rejected trying to {} management.cattle.io/v3 {}{} ({} calls unauthorized), it may be expired or revoked",
+                self.access,
+                self.object_type.resource(),
+                scope,
+                self.calls
+            )
+        } else {
+            write!(
+                f,
+                "token appears to lack {} permission to management.cattle.io/v3 {}{} ({} calls forbidden)",
+                self.access,
+                self.object_type.resource(),
+                scope,
+                self.calls
+            )
+        }
+    }
+}
+
+/// The objects a run changed and failed to change.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ObjectCounts {
+    pub updated: usize,
+    pub created: usize,
+    pub deleted: usize,
+    pub failed: usize,
+}
+
+/// Whether the changes of a run were kept.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    #[default]
+    Completed,
+    /// Failed too often, every change was undone
+    RolledBack,
+    /// Failed too often and some changes could not be undone
+    RollbackFailed,
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunStatus::Completed => write!(f, "completed"),
+            RunStatus::RolledBack => write!(f, "rolled_back"),
+            RunStatus::RollbackFailed => write!(f, "rollback_failed"),
+        }
+    }
+}
+
+impl SyncReport {
+    pub fn new(run_id: impl Into<String>) -> Self {
+        Self {
+            run_id: run_id.into(),
+            started_at: Some(Utc::now()),
+            ..Default::default()
+        }
+    }
+
+    /// Read the report of the last run from `state_dir`, `None` if no run finished yet.
+    ///
+    /// # Errors
+    /// If the report exists but cannot be read or parsed
+    pub fn load(state_dir: &Path) -> Result<Option<Self>> {
+        let path = state_dir.join(LAST_REPORT_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map(Some)
+                .with_context(|| format!("Failed to parse {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    /// Write the report to `state_dir` as the report of the last run.
+    pub fn save(&self, state_dir: &Path) -> Result<()> {
+        write_atomically(&state_dir.join(LAST_REPORT_FILE), &(serde_json::to_string_pretty(self)? + "\n"))
+    }
+
+    /// How long the run took, once it finished.
+    pub fn duration(&self) -> Option<Duration> {
+        (self.finished_at? - self.started_at?).to_std().ok()
+    }
+
+    /// The objects changed and failed so far.
+    pub fn counts(&self) -> ObjectCounts {
+        ObjectCounts {
+            updated: self.updated,
+            created: self.created,
+            deleted: self.deleted,
+            failed: self.failed,
+        }
+    }
+
+    /// Attribute the objects changed and failed since the counts were `before` to `cluster_id`.
+    pub fn record_cluster(&mut self, cluster_id: &str, before: ObjectCounts) {
+        let counts = self.clusters.entry(cluster_id.to_string()).or_default();
+        counts.updated += self.updated - before.updated;
+        counts.created += self.created - before.created;
+        counts.deleted += self.deleted - before.deleted;
+        counts.failed += self.failed - before.failed;
+    }
+
+    /// Sort the lists of the report, filled in the order tasks finished, so two runs doing the
+    /// same work have the same report. Phases stay in the order they ran.
+    pub fn sort(&mut self) {
+        self.parse_failures.sort();
+        self.pending_drift.sort();
+        self.unpersisted.sort();
+        self.timed_out.sort();
+        self.stuck_terminating
+            .sort_by(|a, b| (a.object_type, &a.object_id).cmp(&(b.object_type, &b.object_id)));
+        self.permission_denials.sort_by(|a, b| {
+            (a.object_type, &a.namespace, a.status, a.access.to_string())
+                .cmp(&(b.object_type, &b.namespace, b.status, b.access.to_string()))
+        });
+        self.mirrored.added.sort();
+        self.mirrored.modified.sort();
+        self.mirrored.removed.sort();
+    }
+
+    /// Add `elapsed` to the duration of `phase`.
+    pub fn record_phase(&mut self, phase: &str, elapsed: Duration) {
+        match self.phases.iter_mut().find(|(name, _)| name == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => self.phases.push((phase.to_string(), elapsed)),
+        }
+    }
+
+    /// The total duration recorded for `phase`, if it ran.
+    pub fn phase(&self, phase: &str) -> Option<Duration> {
+        self.phases
+            .iter()
+            .find(|(name, _)| name == phase)
+            .map(|(_, elapsed)| *elapsed)
+    }
+
+    /// Record that `phase` was cut short by its timeout.
+    pub fn record_timeout(&mut self, phase: &str) {
+        self.timed_out.push(phase.to_string());
+    }
+
+    /// Count the outcome of updates, telling objects skipped for the ignore directive apart.
+    pub fn record_updated(&mut self, updated: &[CreatedObject]) {
+        let ignored = count_ignored(updated.iter());
+        self.ignored += ignored;
+        self.updated += updated.len() - ignored;
+    }
+
+    /// Count the outcome of deletions, telling cascaded deletions and skipped objects apart.
+    pub fn record_deleted(&mut self, deleted: &[CreatedObject]) {
+        let cascade_deleted = deleted
+            .iter()
+            .filter(|object| matches!(object, CreatedObject::CascadeDeleted(..)))
+            .count();
+        let skipped = deleted
+            .iter()
+            .filter(|object| matches!(object, CreatedObject::Skipped(..)))
+            .count();
+        self.cascade_deleted += cascade_deleted;
+        self.ignored += skipped;
+        self.deleted += deleted.len() - cascade_deleted - skipped;
+    }
+
+    /// Record a file that could not be parsed, returning whether it is new to this run.
+    /// Sum up the `errors` raised for API calls the token was refused, other errors are skipped.
+    pub fn record_permission_errors<'a>(&mut self, errors: impl IntoIterator<Item = &'a anyhow::Error>) {
+        for refused in errors.into_iter().filter_map(permission_error) {
+            let existing = self.permission_denials.iter_mut().find(|denial| {
+                denial.status == refused.status
+                    && denial.access == refused.access
+                    && denial.object_type == refused.object_type
+                    && denial.namespace == refused.namespace
+            });
+            match existing {
+                Some(denial) => denial.calls += 1,
+                None => self.permission_denials.push(PermissionDenial {
+                    status: refused.status,
+                    access: refused.access,
+                    object_type: refused.object_type,
+                    namespace: refused.namespace.clone(),
+                    calls: 1,
+                }),
+            }
+        }
+    }
+
+    pub fn record_parse_failure(&mut self, path: &Path) -> bool {
+        if self.parse_failures.iter().any(|failed| failed == path) {
+            return false;
+        }
+        self.parse_failures.push(path.to_path_buf());
+        true
+    }
+}
+
+/// A single `key=value` line, durations in milliseconds.
+impl fmt::Display for SyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "run_id={} version={}", self.run_id, crate::CLIENT_VERSION)?;
+        if let Some(server_version) = &self.server_version {
+            write!(f, " rancher_version={}", server_version)?;
+        }
+        for (phase, elapsed) in &self.phases {
+            write!(f, " {}_ms={}", phase, elapsed.as_millis())?;
+        }
+        write!(
+            f,
+            " updated={} created={} deleted={} cascade_deleted={} ignored={} failed={} downloaded={} parse_failures={} quarantined={} stuck_terminating={} permission_denials={} timed_out={} unpersisted={} adopted={} deferred={} mode={} mirrored={} status={}",
+            self.updated,
+            self.created,
+            self.deleted,
+            self.cascade_deleted,
+            self.ignored,
+            self.failed,
+            self.downloaded,
+            self.parse_failures.len(),
+            self.quarantined,
+            self.stuck_terminating.len(),
+            self.permission_denials.iter().map(|denial| denial.calls).sum::<usize>(),
+            self.timed_out.len(),
+            self.unpersisted.len(),
+            self.adopted,
+            self.deferred,
+            self.mode,
+            self.mirrored.len(),
+            self.status
+        )
+    }
+}
+
+/// The phase durations as a list of `{"phase": ..., "ms": ...}` objects, in the order they ran.
+mod phase_millis {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct PhaseMillis {
+        phase: String,
+        ms: u64,
+    }
+
+    pub fn serialize<S: Serializer>(phases: &[(String, Duration)], serializer: S) -> Result<S::Ok, S::Error> {
+        let phases: Vec<PhaseMillis> = phases
+            .iter()
+            .map(|(phase, elapsed)| PhaseMillis {
+                phase: phase.clone(),
+                ms: elapsed.as_millis() as u64,
+            })
+            .collect();
+        phases.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, Duration)>, D::Error> {
+        let phases = Vec::<PhaseMillis>::deserialize(deserializer)?;
+        Ok(phases
+            .into_iter()
+            .map(|PhaseMillis { phase, ms }| (phase, Duration::from_millis(ms)))
+            .collect())
+    }
+}
+
+/// How many files of one folder a download wrote and skipped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DownloadCounts {
+    pub written: usize,
+    /// Files left alone because they carry the ignore directive
+    pub skipped: usize,
+}
+
+/// What a download from Rancher wrote, per cluster.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DownloadReport {
+    /// The paths of the files that were written
+    pub written: Vec<PathBuf>,
+    pub role_templates: DownloadCounts,
+    /// Keyed by cluster ID
+    pub clusters: BTreeMap<String, DownloadCounts>,
+    /// The cluster folders moved after their display name and the cluster folder map, committed with the files
+    pub moved: Vec<PathBuf>,
+    /// The paths of the files left alone because they carry the ignore directive
+    pub kept: Vec<PathBuf>,
+    /// The paths of the files removed because Rancher no longer has their object, see [`crate::prune_download`]
+    pub pruned: Vec<PathBuf>,
+}
+
+impl DownloadReport {
+    /// Record the file at `path`, written unless `skipped`, for `cluster_id` or the role templates.
+    pub fn record(&mut self, cluster_id: Option<&str>, path: &Path, skipped: bool) {
+        let counts = match cluster_id {
+            Some(cluster_id) => self.clusters.entry(cluster_id.to_string()).or_default(),
+            None => &mut self.role_templates,
+        };
+        if skipped {
+            counts.skipped += 1;
+            self.kept.push(path.to_path_buf());
+        } else {
+            counts.written += 1;
+            self.written.push(path.to_path_buf());
+        }
+    }
+
+    /// The number of files skipped for the ignore directive.
+    pub fn skipped(&self) -> usize {
+        self.role_templates.skipped + self.clusters.values().map(|counts| counts.skipped).sum::<usize>()
+    }
+}
+
+/// A summary line followed by one line per folder, used as the body of the download commit.
+impl fmt::Display for DownloadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Downloaded {} files", self.written.len())?;
+        if self.skipped() > 0 {
+            write!(f, ", skipped {} carrying the ignore directive", self.skipped())?;
+        }
+        if !self.pruned.is_empty() {
+            write!(f, ", removed {} of objects Rancher no longer has", self.pruned.len())?;
+        }
+        let folders = std::iter::once(("role templates", &self.role_templates))
+            .chain(self.clusters.iter().map(|(id, counts)| (id.as_str(), counts)));
+        for (folder, counts) in folders {
+            write!(f, "\n- {}: {} written", folder, counts.written)?;
+            if counts.skipped > 0 {
+                write!(f, ", {} skipped", counts.skipped)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The number of objects skipped because their files carry the ignore directive.
+pub fn count_ignored<'a>(objects: impl Iterator<Item = &'a CreatedObject>) -> usize {
+    objects
+        .filter(|object| matches!(object, CreatedObject::Ignored(..)))
+        .count()
+}
+
+/// A new identifier for a sync run, derived from the current time.
+pub fn new_run_id() -> String {
+    chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{MinimalObject, ObjectType, ResourceVersionMatch};
+    use rancher_client::models::IoK8sApimachineryPkgApisMetaV1Status;
+
+    #[test]
+    fn test_phases_accumulate_and_render_in_order() {
+        let mut report = SyncReport::new("run-1");
+        report.record_phase("pull", Duration::from_millis(120));
+        report.record_phase("create", Duration::from_millis(30));
+        report.record_phase("create", Duration::from_millis(20));
+        report.created = 2;
+
+        assert_eq!(report.phase("create"), Some(Duration::from_millis(50)));
+        assert_eq!(report.phase("push"), None);
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "run_id=run-1 version={} pull_ms=120 create_ms=50 updated=0 created=2 deleted=0 cascade_deleted=0 ignored=0 failed=0 downloaded=0 parse_failures=0 quarantined=0 stuck_terminating=0 permission_denials=0 timed_out=0 unpersisted=0 adopted=0 deferred=0 mode=gitops mirrored=0 status=completed",
+                crate::CLIENT_VERSION
+            )
+        );
+
+        report.server_version = Some("v2.8.5".to_string());
+        assert!(report
+            .to_string()
+            .starts_with(&format!("run_id=run-1 version={} rancher_version=v2.8.5 pull_ms=120", crate::CLIENT_VERSION)));
+    }
+
+    #[test]
+    fn test_record_deleted_counts_cascades_separately() {
+        let mut report = SyncReport::new("run-1");
+        let cascaded = CreatedObject::CascadeDeleted(
+            ObjectType::ProjectRoleTemplateBinding,
+            MinimalObject {
+                object_id: Some("prtb-xyz".to_string()),
+                resource_version_match: ResourceVersionMatch::Exact,
+                resource_version: None,
+                namespace: Some("p-abc".to_string()),
+            },
+        );
+        let deleted = CreatedObject::Status(IoK8sApimachineryPkgApisMetaV1Status::default());
+        let skipped = CreatedObject::Skipped(
+            ObjectType::Cluster,
+            MinimalObject {
+                object_id: Some("c-123".to_string()),
+                resource_version_match: ResourceVersionMatch::Exact,
+                resource_version: None,
+                namespace: None,
+            },
+        );
+
+        report.record_deleted(&[cascaded, deleted, skipped]);
+
+        assert_eq!((report.deleted, report.cascade_deleted, report.ignored), (1, 1, 1));
+    }
+
+    #[test]
+    fn test_permission_errors_are_summed_up_per_access_type_and_namespace() {
+        use crate::error::{status_error, FailedObject};
+        use reqwest::StatusCode;
+
+        let refused = |status, access, object_type, namespace| {
+            status_error(status, access, object_type, namespace, "refused".to_string())
+        };
+        let errors = vec![
+            refused(StatusCode::FORBIDDEN, Access::Write, ObjectType::Project, Some("c-abc")).context(FailedObject {
+                object_type: ObjectType::Project,
+                object_id: "p-1".to_string(),
+                namespace: Some("c-abc".to_string()),
+                path: None,
+            }),
+            refused(StatusCode::FORBIDDEN, Access::Write, ObjectType::Project, Some("c-abc")),
+            refused(StatusCode::FORBIDDEN, Access::Read, ObjectType::Project, Some("c-abc")),
+            refused(StatusCode::UNAUTHORIZED, Access::Write, ObjectType::RoleTemplate, None),
+            refused(StatusCode::FORBIDDEN, Access::Write, ObjectType::ProjectRoleTemplateBinding, Some("p-1")),
+            refused(StatusCode::CONFLICT, Access::Write, ObjectType::Project, Some("c-abc")),
+            anyhow::anyhow!("Forbidden, but not raised for a refused call"),
+        ];
+        let mut report = SyncReport::new("run-1");
+
+        report.record_permission_errors(&errors);
+
+        let diagnoses: Vec<String> = report.permission_denials.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            diagnoses,
+            [
+                "token appears to lack write permission to management.cattle.io/v3 projects in cluster c-abc (2 calls forbidden)",
+                "token appears to lack read permission to management.cattle.io/v3 projects in cluster c-abc (1 calls forbidden)",
+                "token was rejected trying to write management.cattle.io/v3 roletemplates (1 calls unauthorized), it may be expired or revoked",
+                "token appears to lack write permission to management.cattle.io/v3 projectroletemplatebindings in project p-1 (1 calls forbidden)",
+            ]
+        );
+        assert!(report.to_string().contains(" permission_denials=5 "));
+    }
+
+    #[test]
+    fn test_parse_failures_are_listed_once() {
+        let mut report = SyncReport::new("run-1");
+
+        assert!(report.record_parse_failure(Path::new("c-123/p-abc/p-abc.project.yaml")));
+        assert!(!report.record_parse_failure(Path::new("c-123/p-abc/p-abc.project.yaml")));
+        assert!(report.record_parse_failure(Path::new("roles/admin.rt.yaml")));
+
+        assert_eq!(report.parse_failures.len(), 2);
+    }
+
+    #[test]
+    fn test_last_report_is_saved_with_per_cluster_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut report = SyncReport::new("run-1");
+        report.record_phase("pull", Duration::from_millis(120));
+        let before = report.counts();
+        report.updated += 2;
+        report.failed += 1;
+        report.record_cluster("c-123", before);
+        report.finished_at = report.started_at.map(|started| started + chrono::Duration::seconds(3));
+
+        report.save(dir.path()).unwrap();
+        let saved = SyncReport::load(dir.path()).unwrap().unwrap();
+
+        assert_eq!(saved, report);
+        assert_eq!(saved.duration(), Some(Duration::from_secs(3)));
+        assert_eq!(
+            saved.clusters["c-123"],
+            ObjectCounts { updated: 2, failed: 1, ..ObjectCounts::default() }
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.path().join(LAST_REPORT_FILE)).unwrap()).unwrap();
+        assert_eq!(json["phases"], serde_json::json!([{"phase": "pull", "ms": 120}]));
+        assert_eq!(SyncReport::load(&dir.path().join("missing")).unwrap(), None);
+    }
+
+    #[test]
+    fn test_download_report_counts_per_cluster() {
+        let mut report = DownloadReport::default();
+        report.record(None, Path::new("roles/admin.rt.yaml"), false);
+        report.record(Some("c-456"), Path::new("c-456/c-456.cluster.yaml"), false);
+        report.record(Some("c-123"), Path::new("c-123/c-123.cluster.yaml"), false);
+        report.record(Some("c-123"), Path::new("c-123/p-abc/p-abc.project.yaml"), true);
+
+        assert_eq!(report.written.len(), 3);
+        assert_eq!(report.clusters["c-123"], DownloadCounts { written: 1, skipped: 1 });
+        assert_eq!(
+            report.to_string(),
+            "Downloaded 3 files, skipped 1 carrying the ignore directive\n\
+             - role templates: 1 written\n\
+             - c-123: 1 written, 1 skipped\n\
+             - c-456: 1 written"
+        );
+    }
+}
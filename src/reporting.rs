@@ -0,0 +1,483 @@
+//! Reports for people auditing the configuration, such as who can do what in a cluster.
+//!
+//! Reports are built from a [`ClusterConfig`], so the same report can be made of the files in
+//! the repository and of the live objects, and the two compared.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+
+use anyhow::Result;
+use rancher_client::models::IoCattleManagementv3GlobalRoleRulesInner;
+use serde::Serialize;
+
+use crate::api::config::{ClusterConfig, RancherClusterConfig};
+use crate::resources::cluster::Cluster;
+use crate::resources::crtb::ClusterRoleTemplateBinding;
+use crate::resources::project::Project;
+use crate::resources::prtb::ProjectRoleTemplateBinding;
+use crate::resources::rt::RoleTemplate;
+
+/// The verbs that only read, left out of the notable verbs of a row.
+const READ_VERBS: &[&str] = &["get", "list", "watch"];
+
+/// Who a binding grants its role template to.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum SubjectKind {
+    User,
+    Group,
+    ServiceAccount,
+}
+
+impl SubjectKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            SubjectKind::User => "user",
+            SubjectKind::Group => "group",
+            SubjectKind::ServiceAccount => "service account",
+        }
+    }
+}
+
+/// How a row of a report checked against Rancher compares.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum LiveState {
+    InBoth,
+    OnlyInRepository,
+    OnlyInRancher,
+}
+
+impl LiveState {
+    fn as_str(self) -> &'static str {
+        match self {
+            LiveState::InBoth => "",
+            LiveState::OnlyInRepository => "only in the repository",
+            LiveState::OnlyInRancher => "only in Rancher",
+        }
+    }
+}
+
+/// The project a binding is scoped to.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ProjectRef {
+    pub id: String,
+    pub display_name: String,
+}
+
+/// What one binding grants to one subject.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RbacRow {
+    pub subject: String,
+    pub kind: SubjectKind,
+    /// The project of a project binding, `None` for a cluster binding
+    pub project: Option<ProjectRef>,
+    pub role_template: String,
+    /// The display name of the role template, if it is known
+    pub role_template_name: Option<String>,
+    /// Every role template the role template inherits from, directly or not
+    pub inherits: Vec<String>,
+    /// The verbs granted beyond reading, `*` alone if every verb is
+    pub notable_verbs: Vec<String>,
+    /// The role templates granted whose rules are unknown
+    pub unresolved: Vec<String>,
+    /// Set once the report is checked against Rancher
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub live: Option<LiveState>,
+}
+
+impl RbacRow {
+    fn key(&self) -> (SubjectKind, &str, Option<&str>, &str) {
+        (self.kind, &self.subject, self.project.as_ref().map(|project| project.id.as_str()), &self.role_template)
+    }
+
+    fn scope(&self) -> String {
+        match &self.project {
+            Some(project) => format!("project `{}` ({})", project.display_name, project.id),
+            None => "cluster".to_string(),
+        }
+    }
+
+    fn role(&self) -> String {
+        match &self.role_template_name {
+            Some(name) if name != &self.role_template => format!("{} ({})", name, self.role_template),
+            _ => self.role_template.clone(),
+        }
+    }
+
+    fn verbs(&self) -> String {
+        let mut verbs = if self.notable_verbs.is_empty() {
+            "read only".to_string()
+        } else {
+            self.notable_verbs.join(", ")
+        };
+        if !self.unresolved.is_empty() {
+            write!(verbs, ", and the unknown rules of {}", self.unresolved.join(", ")).unwrap();
+        }
+        verbs
+    }
+
+    fn columns(&self, live: bool) -> Vec<String> {
+        let mut columns = vec![
+            self.subject.clone(),
+            self.kind.as_str().to_string(),
+            self.scope(),
+            self.role(),
+            self.inherits.join(", "),
+            self.verbs(),
+        ];
+        if live {
+            columns.push(self.live.map(LiveState::as_str).unwrap_or_default().to_string());
+        }
+        columns
+    }
+}
+
+/// Who can do what in a cluster, a row per binding.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct RbacReport {
+    pub cluster_id: String,
+    pub cluster_name: String,
+    pub rows: Vec<RbacRow>,
+}
+
+const HEADERS: &[&str] = &["Subject", "Kind", "Scope", "Role", "Inherits", "Notable verbs"];
+
+impl RbacReport {
+    /// The report of the bindings of `config`.
+    ///
+    /// Role templates are looked up in `config` first, then in `extra_role_templates`, such as
+    /// the builtin ones of Rancher.
+    pub fn build(config: &ClusterConfig, extra_role_templates: &[RoleTemplate]) -> Self {
+        let role_templates: BTreeMap<&str, &RoleTemplate> = extra_role_templates
+            .iter()
+            .chain(&config.role_templates)
+            .map(|rt| (rt.id.as_str(), rt))
+            .collect();
+
+        let mut rows = Vec::new();
+        for (project, prtbs) in config.projects.values() {
+            let project_ref = ProjectRef {
+                id: project.id.clone().unwrap_or_default(),
+                display_name: project.display_name.clone(),
+            };
+            for prtb in prtbs {
+                let subject = subject(&[
+                    (SubjectKind::User, &prtb.user_name),
+                    (SubjectKind::User, &prtb.user_principal_name),
+                    (SubjectKind::Group, &prtb.group_name),
+                    (SubjectKind::Group, &prtb.group_principal_name),
+                    (SubjectKind::ServiceAccount, &prtb.service_account),
+                ]);
+                rows.push(row(subject, Some(project_ref.clone()), &prtb.role_template_name, &role_templates));
+            }
+        }
+        for crtb in &config.crtbs {
+            let subject = subject(&[
+                (SubjectKind::User, &crtb.user_name),
+                (SubjectKind::User, &crtb.user_principal_name),
+                (SubjectKind::Group, &crtb.group_name),
+                (SubjectKind::Group, &crtb.group_principal_name),
+            ]);
+            rows.push(row(subject, None, &crtb.role_template_name, &role_templates));
+        }
+        rows.sort();
+        Self {
+            cluster_id: config.cluster.id.clone(),
+            cluster_name: config.cluster.display_name.clone(),
+            rows,
+        }
+    }
+
+    /// Mark every row as in both or only in the repository, and add the rows only `live` has.
+    pub fn cross_check(&mut self, live: &RbacReport) {
+        let live_keys: BTreeSet<_> = live.rows.iter().map(RbacRow::key).collect();
+        let own_keys: BTreeSet<_> = self.rows.iter().map(RbacRow::key).collect();
+        let only_live: Vec<RbacRow> = live
+            .rows
+            .iter()
+            .filter(|row| !own_keys.contains(&row.key()))
+            .map(|row| RbacRow { live: Some(LiveState::OnlyInRancher), ..row.clone() })
+            .collect();
+        for row in &mut self.rows {
+            let state = if live_keys.contains(&row.key()) { LiveState::InBoth } else { LiveState::OnlyInRepository };
+            row.live = Some(state);
+        }
+        self.rows.extend(only_live);
+        self.rows.sort();
+    }
+
+    fn checked(&self) -> bool {
+        self.rows.iter().any(|row| row.live.is_some())
+    }
+
+    fn headers(&self) -> Vec<&'static str> {
+        let mut headers = HEADERS.to_vec();
+        if self.checked() {
+            headers.push("Rancher");
+        }
+        headers
+    }
+
+    /// The report as a Markdown table under a heading naming the cluster.
+    pub fn to_markdown(&self) -> String {
+        let headers = self.headers();
+        let mut out = format!("# Access to cluster `{}` ({})\n\n", self.cluster_name, self.cluster_id);
+        if self.rows.is_empty() {
+            out.push_str("No bindings.\n");
+            return out;
+        }
+        writeln!(out, "| {} |", headers.join(" | ")).unwrap();
+        writeln!(out, "|{}", "---|".repeat(headers.len())).unwrap();
+        for row in &self.rows {
+            let columns: Vec<String> = row.columns(self.checked()).iter().map(|column| column.replace('|', "\\|")).collect();
+            writeln!(out, "| {} |", columns.join(" | ")).unwrap();
+        }
+        out
+    }
+
+    /// The report as CSV with a header line.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::new();
+        let headers: Vec<String> = self.headers().iter().map(|header| csv_field(header)).collect();
+        writeln!(out, "{}", headers.join(",")).unwrap();
+        for row in &self.rows {
+            let columns: Vec<String> = row.columns(self.checked()).iter().map(|column| csv_field(column)).collect();
+            writeln!(out, "{}", columns.join(",")).unwrap();
+        }
+        out
+    }
+}
+
+/// The configuration `live` of a cluster in the form of its files, to report on it.
+///
+/// # Errors
+/// If an object cannot be converted
+pub fn live_config(live: RancherClusterConfig) -> Result<ClusterConfig> {
+    Ok(ClusterConfig {
+        cluster: Cluster::try_from(live.cluster)?,
+        role_templates: live.role_templates.into_iter().map(RoleTemplate::try_from).collect::<Result<_, _>>()?,
+        projects: live
+            .projects
+            .into_iter()
+            .map(|(id, (project, prtbs))| {
+                let prtbs = prtbs.into_iter().map(ProjectRoleTemplateBinding::try_from).collect::<Result<_, _>>()?;
+                Ok((id, (Project::try_from(project)?, prtbs)))
+            })
+            .collect::<Result<_>>()?,
+        crtbs: live.crtbs.into_iter().map(ClusterRoleTemplateBinding::try_from).collect::<Result<_, _>>()?,
+        malformed: Vec::new(),
+    })
+}
+
+/// The first subject set of `subjects`, a binding naming none has an empty one.
+fn subject(subjects: &[(SubjectKind, &Option<String>)]) -> (SubjectKind, String) {
+    subjects
+        .iter()
+        .find_map(|(kind, name)| Some((*kind, (*name).clone()?)))
+        .unwrap_or((SubjectKind::User, String::new()))
+}
+
+fn row(
+    (kind, subject): (SubjectKind, String),
+    project: Option<ProjectRef>,
+    role_template: &str,
+    role_templates: &BTreeMap<&str, &RoleTemplate>,
+) -> RbacRow {
+    let (inherits, rules, unresolved) = resolve(role_template, role_templates);
+    RbacRow {
+        subject,
+        kind,
+        project,
+        role_template: role_template.to_string(),
+        role_template_name: role_templates.get(role_template).and_then(|rt| rt.display_name.clone()),
+        inherits,
+        notable_verbs: notable_verbs(&rules),
+        unresolved,
+        live: None,
+    }
+}
+
+/// The role templates `id` inherits from, the rules of all of them and the ones that are unknown.
+fn resolve<'a>(
+    id: &str,
+    role_templates: &BTreeMap<&str, &'a RoleTemplate>,
+) -> (Vec<String>, Vec<&'a IoCattleManagementv3GlobalRoleRulesInner>, Vec<String>) {
+    let mut seen = BTreeSet::from([id.to_string()]);
+    let mut pending = vec![id.to_string()];
+    let (mut inherits, mut rules, mut unresolved) = (Vec::new(), Vec::new(), Vec::new());
+    while let Some(current) = pending.pop() {
+        let Some(role_template) = role_templates.get(current.as_str()) else {
+            unresolved.push(current);
+            continue;
+        };
+        rules.extend(role_template.rules.iter().flatten());
+        for name in role_template.role_template_names.iter().flatten() {
+            // a cycle is reported by the validation, not followed
+            if seen.insert(name.clone()) {
+                inherits.push(name.clone());
+                pending.push(name.clone());
+            }
+        }
+    }
+    inherits.sort();
+    unresolved.sort();
+    (inherits, rules, unresolved)
+}
+
+fn notable_verbs(rules: &[&IoCattleManagementv3GlobalRoleRulesInner]) -> Vec<String> {
+    let verbs: BTreeSet<&str> = rules
+        .iter()
+        .flat_map(|rule| rule.verbs.iter().map(String::as_str))
+        .filter(|verb| !READ_VERBS.contains(verb))
+        .collect();
+    if verbs.contains("*") {
+        return vec!["*".to_string()];
+    }
+    verbs.into_iter().map(str::to_string).collect()
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A cluster with a team project, a role template inheriting from a builtin one and
+    /// bindings of a user, a group and a service account.
+    fn fixture() -> ClusterConfig {
+        serde_json::from_value(json!({
+            "cluster": { "id": "c-123", "display_name": "prod" },
+            "role_templates": [
+                {
+                    "id": "rt-deployer",
+                    "display_name": "Deployer",
+                    "context": "project",
+                    "role_template_names": ["read-only"],
+                    "rules": [
+                        { "apiGroups": ["apps"], "resources": ["deployments"], "verbs": ["get", "update", "patch"] }
+                    ]
+                },
+                {
+                    "id": "rt-auditor",
+                    "display_name": "Auditor",
+                    "context": "cluster",
+                    "rules": [{ "apiGroups": ["*"], "resources": ["*"], "verbs": ["get", "list", "watch"] }]
+                }
+            ],
+            "projects": {
+                "p-abc": [
+                    { "id": "p-abc", "cluster_name": "c-123", "namespace": "c-123", "display_name": "web" },
+                    [
+                        { "id": "prtb-1", "namespace": "p-abc", "project_name": "c-123:p-abc", "role_template_name": "rt-deployer", "user_name": "u-dev" },
+                        { "id": "prtb-2", "namespace": "p-abc", "project_name": "c-123:p-abc", "role_template_name": "project-owner", "group_principal_name": "github_team://42" },
+                        { "id": "prtb-3", "namespace": "p-abc", "project_name": "c-123:p-abc", "role_template_name": "rt-deployer", "service_account": "ci:deployer" }
+                    ]
+                ]
+            },
+            "crtbs": [
+                { "id": "crtb-1", "cluster_name": "c-123", "namespace": "c-123", "role_template_name": "rt-auditor", "user_principal_name": "local://u-audit" }
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn builtin() -> Vec<RoleTemplate> {
+        serde_json::from_value(json!([
+            {
+                "id": "read-only",
+                "display_name": "Read-only",
+                "rules": [{ "apiGroups": [""], "resources": ["pods"], "verbs": ["get", "list"] }]
+            },
+            {
+                "id": "project-owner",
+                "display_name": "Project Owner",
+                "rules": [{ "apiGroups": ["*"], "resources": ["*"], "verbs": ["*"] }]
+            }
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rows_resolve_inheritance_and_notable_verbs() {
+        let report = RbacReport::build(&fixture(), &builtin());
+
+        #[allow(clippy::type_complexity)]
+        let rows: Vec<(&str, Option<&str>, &str, Vec<String>, Vec<String>)> = report
+            .rows
+            .iter()
+            .map(|row| {
+                let project = row.project.as_ref().map(|project| project.id.as_str());
+                (row.subject.as_str(), project, row.role_template.as_str(), row.inherits.clone(), row.notable_verbs.clone())
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("ci:deployer", Some("p-abc"), "rt-deployer", vec!["read-only".to_string()], vec!["patch".to_string(), "update".to_string()]),
+                ("github_team://42", Some("p-abc"), "project-owner", vec![], vec!["*".to_string()]),
+                ("local://u-audit", None, "rt-auditor", vec![], vec![]),
+                ("u-dev", Some("p-abc"), "rt-deployer", vec!["read-only".to_string()], vec!["patch".to_string(), "update".to_string()]),
+            ]
+        );
+
+        // without the builtin role templates their rules are unknown
+        let report = RbacReport::build(&fixture(), &[]);
+        let owner = report.rows.iter().find(|row| row.role_template == "project-owner").unwrap();
+        assert_eq!(owner.unresolved, ["project-owner"]);
+        assert_eq!(owner.verbs(), "read only, and the unknown rules of project-owner");
+    }
+
+    #[test]
+    fn test_markdown_and_csv() {
+        let report = RbacReport::build(&fixture(), &builtin());
+
+        let markdown = report.to_markdown();
+        assert!(markdown.starts_with("# Access to cluster `prod` (c-123)\n\n| Subject | Kind | Scope | Role | Inherits | Notable verbs |\n|---|---|---|---|---|---|\n"));
+        assert!(markdown.contains("| u-dev | user | project `web` (p-abc) | Deployer (rt-deployer) | read-only | patch, update |\n"), "{}", markdown);
+        assert!(markdown.contains("| local://u-audit | user | cluster | Auditor (rt-auditor) |  | read only |\n"), "{}", markdown);
+
+        let csv = report.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("Subject,Kind,Scope,Role,Inherits,Notable verbs"));
+        assert_eq!(
+            lines.next(),
+            Some("ci:deployer,service account,project `web` (p-abc),Deployer (rt-deployer),read-only,\"patch, update\"")
+        );
+        assert_eq!(csv.lines().count(), 5);
+    }
+
+    #[test]
+    fn test_cross_check_marks_discrepancies() {
+        let mut report = RbacReport::build(&fixture(), &builtin());
+        let mut live = fixture();
+        live.projects.get_mut("p-abc").unwrap().1.retain(|prtb| prtb.id != "prtb-3");
+        live.crtbs[0].role_template_name = "cluster-owner".to_string();
+
+        report.cross_check(&RbacReport::build(&live, &builtin()));
+
+        let states: Vec<(&str, &str, Option<LiveState>)> = report
+            .rows
+            .iter()
+            .map(|row| (row.subject.as_str(), row.role_template.as_str(), row.live))
+            .collect();
+        assert_eq!(
+            states,
+            [
+                ("ci:deployer", "rt-deployer", Some(LiveState::OnlyInRepository)),
+                ("github_team://42", "project-owner", Some(LiveState::InBoth)),
+                ("local://u-audit", "cluster-owner", Some(LiveState::OnlyInRancher)),
+                ("local://u-audit", "rt-auditor", Some(LiveState::OnlyInRepository)),
+                ("u-dev", "rt-deployer", Some(LiveState::InBoth)),
+            ]
+        );
+        assert!(report.to_markdown().contains("| Notable verbs | Rancher |"));
+    }
+}
@@ -1,3 +1,5 @@
+use anyhow::{Context, Result};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 use rancher_client::apis::{configuration::Configuration, Error, ResponseContent};
@@ -13,13 +15,31 @@ use rancher_client::{
     },
 };
 
-use crate::models::ConversionError;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use tracing::{info, warn};
+
+use crate::api::client::with_server_version;
+use crate::error::bounded_body;
+use crate::models::{ConversionError, ResourceVersionMatch};
+use crate::{clean_up_value, serialize_object};
+use crate::utils::file::{file_extension_from_format, FileFormat};
+use crate::utils::safe_path::ensure_within;
 
 /// Get all clusters from an endpoint using the provided configuration
 ///
+/// Every page of the listing is fetched, following the continue token of each response.
+///
 /// # Arguments
 ///
 /// * `configuration` - The configuration to use for the request
+/// * `field_selector` - The field selector to apply to the cluster list
+/// * `label_selector` - The label selector to apply to the cluster list
+/// * `limit` - The number of clusters to request per page
+/// * `resource_version` - The resource version to use for the first request
+/// * `resource_version_match` - The resource version match to use for the first request
+/// * `continue_` - The continue token to start the listing from
 ///
 /// # Returns
 ///
@@ -29,20 +49,76 @@ use crate::models::ConversionError;
 ///
 /// * `Error<ListManagementCattleIoV3ClustersError>` - The error that occurred while trying to get the clusters
 ///
+#[allow(clippy::too_many_arguments)]
 #[async_backtrace::framed]
 pub async fn get_clusters(
     configuration: &Configuration,
+    field_selector: Option<&str>,
+    label_selector: Option<&str>,
+    limit: Option<i32>,
+    resource_version: Option<&str>,
+    resource_version_match: Option<ResourceVersionMatch>,
+    continue_: Option<&str>,
+) -> Result<IoCattleManagementv3ClusterList, Error<ListManagementCattleIoV3ClustersError>> {
+    let mut clusters = get_cluster_page(
+        configuration,
+        field_selector,
+        label_selector,
+        limit,
+        resource_version,
+        resource_version_match,
+        continue_,
+    )
+    .await?;
+
+    while let Some(token) = next_page_token(&clusters) {
+        // the resource version only applies to the first page, the token pins the rest
+        let page = get_cluster_page(configuration, field_selector, label_selector, limit, None, None, Some(&token)).await?;
+        clusters.items.extend(page.items);
+        clusters.metadata = page.metadata;
+    }
+    Ok(clusters)
+}
+
+/// Check that the token can list the clusters of the endpoint, returning how many it sees.
+///
+/// Used by `shepherd check`, nothing is changed.
+pub async fn probe_clusters(configuration: &Configuration) -> Result<usize> {
+    let clusters = get_clusters(configuration, None, None, None, None, None, None)
+        .await
+        .context("Failed to list clusters")?;
+    Ok(clusters.items.len())
+}
+
+/// The continue token of `list`, if there are more pages.
+fn next_page_token(list: &IoCattleManagementv3ClusterList) -> Option<String> {
+    list.metadata
+        .as_ref()
+        .and_then(|metadata| metadata.r#continue.clone())
+        .filter(|token| !token.is_empty())
+}
+
+/// Get a single page of clusters.
+#[async_backtrace::framed]
+async fn get_cluster_page(
+    configuration: &Configuration,
+    field_selector: Option<&str>,
+    label_selector: Option<&str>,
+    limit: Option<i32>,
+    resource_version: Option<&str>,
+    resource_version_match: Option<ResourceVersionMatch>,
+    continue_: Option<&str>,
 ) -> Result<IoCattleManagementv3ClusterList, Error<ListManagementCattleIoV3ClustersError>> {
     let result = list_management_cattle_io_v3_clusters(
         configuration,
         None,
+        continue_,
+        field_selector,
+        label_selector,
+        limit,
         None,
-        None,
-        None,
-        None,
-        None,
-        None,
-        None,
+        resource_version,
+        resource_version_match.map(|v| v.as_str()),
         None,
         None,
         None,
@@ -58,7 +134,7 @@ pub async fn get_clusters(
                     // Try to deserialize the content into IoCattleManagementv3ClusterList (Status200 case)
                     match serde_json::from_str(&response_content.content) {
                         Ok(data) => Ok(data),
-                        Err(deserialize_err) => Err(Error::Serde(deserialize_err)),
+                        Err(deserialize_err) => Err(Error::Serde(serde::de::Error::custom(with_server_version(configuration, deserialize_err)))),
                     }
                 }
                 _ => {
@@ -68,7 +144,7 @@ pub async fn get_clusters(
                             // Handle the unknown response
                             Err(Error::ResponseError(ResponseContent {
                                 status: response_content.status,
-                                content: response_content.content,
+                                content: bounded_body(&response_content.content).into_owned(),
                                 entity: Some(ListManagementCattleIoV3ClustersError::UnknownValue(
                                     unknown_data,
                                 )),
@@ -82,22 +158,124 @@ pub async fn get_clusters(
     }
 }
 
+/// ID of the cluster Rancher itself runs in.
+pub const LOCAL_CLUSTER_ID: &str = "local";
+
+/// What happens to the folder of a cluster that no longer matches the selector.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MissingClusters {
+    /// Keep the folder and log a warning
+    #[default]
+    Warn,
+    /// Remove the folder from the repository, leaving the cluster in Rancher alone
+    Prune,
+}
+
+/// Picks the clusters to synchronize from the ones Rancher knows, used when no
+/// `cluster_names` are configured.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ClusterSelector {
+    /// Kubernetes label selector sent with the cluster list request, such as `env=prod`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<String>,
+    /// Regular expression the cluster ID or display name has to match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name_pattern: Option<String>,
+    #[serde(default)]
+    pub missing: MissingClusters,
+}
+
+impl ClusterSelector {
+    /// The compiled `name_pattern`.
+    ///
+    /// # Errors
+    /// Returns an error if the pattern is not a valid regular expression
+    pub fn name_regex(&self) -> Result<Option<Regex>> {
+        self.name_pattern
+            .as_deref()
+            .map(|pattern| Regex::new(pattern).with_context(|| format!("Invalid cluster name pattern `{}`", pattern)))
+            .transpose()
+    }
+
+    /// The clusters labelled as selected whose ID or display name matches the name pattern.
+    ///
+    /// # Errors
+    /// Returns an error if the pattern is invalid or the clusters cannot be listed
+    #[async_backtrace::framed]
+    pub async fn select(&self, configuration: &Configuration) -> Result<Vec<Cluster>> {
+        let name_regex = self.name_regex()?;
+        let clusters = get_clusters(configuration, None, self.labels.as_deref(), None, None, None, None)
+            .await
+            .context("Failed to get clusters")?;
+
+        clusters
+            .items
+            .into_iter()
+            .map(|item| Cluster::try_from(item).context("Failed to convert cluster"))
+            .filter(|cluster| match (cluster, &name_regex) {
+                (Ok(cluster), Some(regex)) => regex.is_match(&cluster.id) || regex.is_match(&cluster.display_name),
+                _ => true,
+            })
+            .collect()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Cluster {
     pub id: String,
     pub display_name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    /// Only ever the [`REDACTED_ANNOTATION`] of the file, the annotations of the cluster in
+    /// Rancher are not downloaded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<HashMap<String, String>>,
 }
 
+/// The annotation of a cluster file listing the fields left out of it, comma separated.
+pub const REDACTED_ANNOTATION: &str = "shepherd.cattle.io/redacted";
+
+/// The fields a cluster file cannot do without, never redacted.
+pub const UNREDACTABLE_FIELDS: &[&str] = &["id", "display_name", "annotations"];
+
 impl Cluster {
     pub fn new(id: String, name: String, description: Option<String>) -> Self {
         Cluster {
             id,
             display_name: name,
             description,
+            annotations: None,
         }
     }
+
+    /// The cluster without the fields at the dot-separated `paths` of its file, such as
+    /// `description`, and the paths it had a field at listed in its [`REDACTED_ANNOTATION`].
+    ///
+    /// # Errors
+    /// If a path is one of the [`UNREDACTABLE_FIELDS`]
+    pub fn redacted(&self, paths: &[String]) -> Result<Self> {
+        let mut value = serde_json::to_value(self)?;
+        let mut redacted = Vec::new();
+        for path in paths {
+            if UNREDACTABLE_FIELDS.contains(&path.split('.').next().unwrap_or_default()) {
+                anyhow::bail!("Cannot redact `{}` from cluster files", path);
+            }
+            if value.pointer(&format!("/{}", path.replace('.', "/"))).is_some() {
+                clean_up_value(&mut value, &[path.as_str()]);
+                redacted.push(path.as_str());
+            }
+        }
+        let mut cluster: Cluster = serde_json::from_value(value)?;
+        if !redacted.is_empty() {
+            cluster
+                .annotations
+                .get_or_insert_with(HashMap::new)
+                .insert(REDACTED_ANNOTATION.to_string(), redacted.join(","));
+        }
+        Ok(cluster)
+    }
 }
 
 // impl From<IoCattleManagementv3Cluster> for Cluster {
@@ -122,6 +300,7 @@ impl TryFrom<IoCattleManagementv3Cluster> for Cluster {
             id: metadata.name.ok_or(ConversionError::MissingField("missing metadata.name".into()))?,
             display_name: spec.display_name,
             description: spec.description,
+            annotations: None,
         })
     }
 }
@@ -176,15 +355,179 @@ impl PartialEq<IoCattleManagementv3Cluster> for Cluster {
 
 
 
+/// How the folders of clusters in the endpoint folder are named.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClusterFolderNaming {
+    /// After the cluster ID, such as `c-m-abc123`
+    #[default]
+    Id,
+    /// After the display name, mapped back to the ID by the `clusters.map` file
+    DisplayName,
+}
+
+impl std::fmt::Display for ClusterFolderNaming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClusterFolderNaming::Id => write!(f, "id"),
+            ClusterFolderNaming::DisplayName => write!(f, "display-name"),
+        }
+    }
+}
+
+/// The stem of the file at the endpoint root mapping cluster folders to cluster IDs.
+pub const CLUSTER_MAP_FILE_STEM: &str = "clusters.map";
+
+/// The cluster ID of every cluster folder not named after its cluster, keyed by folder name.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct ClusterFolderMap {
+    folders: BTreeMap<String, String>,
+}
+
+impl ClusterFolderMap {
+    /// The path of the map file in `endpoint_path`.
+    pub fn path(endpoint_path: &Path, file_format: &FileFormat) -> PathBuf {
+        endpoint_path.join(format!("{}.{}", CLUSTER_MAP_FILE_STEM, file_extension_from_format(file_format)))
+    }
+
+    /// Read the map file of `endpoint_path`, empty if there is none.
+    ///
+    /// # Errors
+    /// Returns an error if the file exists but cannot be read or parsed
+    pub fn load(endpoint_path: &Path, file_format: &FileFormat) -> Result<Self> {
+        let path = Self::path(endpoint_path, file_format);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        file_format
+            .deserialize(&contents)
+            .with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    /// Write the map file of `endpoint_path`, returning its path.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be written
+    pub fn save(&self, endpoint_path: &Path, file_format: &FileFormat) -> Result<PathBuf> {
+        let path = Self::path(endpoint_path, file_format);
+        std::fs::write(&path, serialize_object(self, file_format)?).with_context(|| format!("Failed to write {:?}", path))?;
+        Ok(path)
+    }
+
+    /// The ID of the cluster in `folder`.
+    pub fn cluster_id(&self, folder: &str) -> Option<&str> {
+        self.folders.get(folder).map(String::as_str)
+    }
+
+    /// The folder of the cluster with `cluster_id`, if it is mapped.
+    pub fn folder(&self, cluster_id: &str) -> Option<&str> {
+        self.folders
+            .iter()
+            .find(|(_, id)| *id == cluster_id)
+            .map(|(folder, _)| folder.as_str())
+    }
+
+    /// Map `folder` to `cluster_id`, dropping the cluster's previous folder.
+    pub fn insert(&mut self, folder: &str, cluster_id: &str) {
+        self.remove(cluster_id);
+        self.folders.insert(folder.to_string(), cluster_id.to_string());
+    }
+
+    /// Drop the folder of the cluster with `cluster_id`.
+    pub fn remove(&mut self, cluster_id: &str) {
+        self.folders.retain(|_, id| id != cluster_id);
+    }
+}
+
+/// The folder of the cluster with `cluster_id` in `endpoint_path`, the mapped one or the one named after the ID.
+pub fn cluster_folder(endpoint_path: &Path, cluster_id: &str, file_format: &FileFormat) -> PathBuf {
+    let map = ClusterFolderMap::load(endpoint_path, file_format).unwrap_or_else(|e| {
+        warn!("Ignoring the cluster folder map: {:#}", e);
+        ClusterFolderMap::default()
+    });
+    endpoint_path.join(map.folder(cluster_id).unwrap_or(cluster_id))
+}
+
+/// `display_name` reduced to a folder name: runs of anything but ASCII letters, digits,
+/// `.`, `-` and `_` become a single `-`, leading dots and dashes are dropped.
+pub fn cluster_folder_name(display_name: &str) -> String {
+    let mut name = String::with_capacity(display_name.len());
+    for c in display_name.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+            name.push(c);
+        } else if !name.ends_with('-') {
+            name.push('-');
+        }
+    }
+    name.trim_start_matches(['.', '-']).trim_end_matches('-').to_string()
+}
+
+/// Give `cluster` the folder named after its display name, moving the folder it has.
+///
+/// The current folder is the mapped one, or the one named after the ID. A cluster renamed
+/// in Rancher has its folder moved to the new name, so its files keep their history. The
+/// ID is used instead when the display name makes no folder name or the folder belongs to
+/// another cluster, and the choice is recorded in the map file.
+///
+/// # Returns
+/// The folder of the cluster, and the moved folders and map file to commit
+///
+/// # Errors
+/// Returns an error if the map file cannot be read or written, or the folder cannot be moved,
+/// and an [`crate::error::UnsafePathError`] if the ID of the cluster puts its folder outside of `endpoint_path`
+pub fn place_cluster_folder(
+    endpoint_path: &Path,
+    cluster: &Cluster,
+    file_format: &FileFormat,
+) -> Result<(PathBuf, Vec<PathBuf>)> {
+    let mut map = ClusterFolderMap::load(endpoint_path, file_format)?;
+    let current = map.folder(&cluster.id).unwrap_or(&cluster.id).to_string();
+    let taken = |folder: &str| {
+        folder == "roles"
+            || map.cluster_id(folder).is_some_and(|id| id != cluster.id)
+            || (folder != current && endpoint_path.join(folder).exists())
+    };
+    let name = cluster_folder_name(&cluster.display_name);
+    let folder = [name.clone(), format!("{}-{}", name, cluster.id)]
+        .into_iter()
+        .find(|folder| !name.is_empty() && !taken(folder))
+        .unwrap_or_else(|| cluster.id.clone());
+
+    let mut changed = Vec::new();
+    let current_path = endpoint_path.join(&current);
+    let folder_path = endpoint_path.join(&folder);
+    ensure_within(endpoint_path, &folder_path)?;
+    if folder != current && current_path.exists() {
+        info!(
+            "Cluster `{}` is named `{}`, moving {:?} to {:?}",
+            cluster.id, cluster.display_name, current_path, folder_path
+        );
+        std::fs::rename(&current_path, &folder_path)
+            .with_context(|| format!("Failed to move {:?} to {:?}", current_path, folder_path))?;
+        changed.push(current_path);
+        changed.push(folder_path.clone());
+    }
+    if map.folder(&cluster.id) != Some(folder.as_str()) {
+        map.insert(&folder, &cluster.id);
+        changed.push(map.save(endpoint_path, file_format)?);
+    }
+    Ok((folder_path, changed))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
     fn sample_cluster() -> Cluster {
         Cluster {
             id: "cluster-id".to_string(),
             display_name: "Test Cluster".to_string(),
             description: Some("A test cluster".to_string()),
+            annotations: None,
         }
     }
 
@@ -339,7 +682,161 @@ mod tests {
         assert_eq!(ioc.spec.description, None);
     }
 
+    #[tokio::test]
+    async fn test_selector_filters_labelled_clusters_by_name() {
+        let cluster = |id: &str, display_name: &str| {
+            serde_json::to_value(IoCattleManagementv3Cluster::try_from(Cluster::new(
+                id.to_string(),
+                display_name.to_string(),
+                None,
+            )).unwrap())
+            .unwrap()
+        };
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/clusters"))
+            .and(query_param("labelSelector", "env=prod"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "metadata": {},
+                "items": [cluster("c-123", "prod-eu"), cluster("c-456", "prod-us"), cluster("c-789", "staging")]
+            })))
+            .mount(&server)
+            .await;
+        let configuration = Configuration {
+            base_path: server.uri(),
+            ..Default::default()
+        };
+
+        let selector = ClusterSelector {
+            labels: Some("env=prod".to_string()),
+            name_pattern: Some("^(prod-eu|c-789)$".to_string()),
+            missing: MissingClusters::Warn,
+        };
+        let selected = selector.select(&configuration).await.unwrap();
+
+        let ids: Vec<_> = selected.iter().map(|cluster| cluster.id.as_str()).collect();
+        assert_eq!(ids, ["c-123", "c-789"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_clusters_follows_continue_tokens() {
+        let cluster = |id: &str| {
+            serde_json::to_value(IoCattleManagementv3Cluster::try_from(Cluster::new(id.to_string(), id.to_string(), None)).unwrap())
+                .unwrap()
+        };
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/clusters"))
+            .and(query_param("labelSelector", "env=prod"))
+            .and(query_param("fieldSelector", "metadata.name!=local"))
+            .and(query_param("limit", "2"))
+            .and(query_param("resourceVersion", "100"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "metadata": { "continue": "page-2", "remainingItemCount": 1 },
+                "items": [cluster("c-1"), cluster("c-2")]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/clusters"))
+            .and(query_param("labelSelector", "env=prod"))
+            .and(query_param("continue", "page-2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "metadata": { "resourceVersion": "100" },
+                "items": [cluster("c-3")]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let configuration = Configuration {
+            base_path: server.uri(),
+            ..Default::default()
+        };
+
+        let clusters = get_clusters(
+            &configuration,
+            Some("metadata.name!=local"),
+            Some("env=prod"),
+            Some(2),
+            Some("100"),
+            Some(ResourceVersionMatch::NotOlderThan),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let ids: Vec<_> = clusters
+            .items
+            .iter()
+            .filter_map(|cluster| cluster.metadata.as_ref()?.name.as_deref())
+            .collect();
+        assert_eq!(ids, ["c-1", "c-2", "c-3"]);
+        assert_eq!(next_page_token(&clusters), None);
+    }
+
+    #[test]
+    fn test_invalid_name_pattern_is_an_error() {
+        let selector = ClusterSelector {
+            name_pattern: Some("prod-(".to_string()),
+            ..Default::default()
+        };
 
+        assert!(selector.name_regex().is_err());
+    }
 
+    #[test]
+    fn test_cluster_folder_name_is_sanitized() {
+        assert_eq!(cluster_folder_name("Prod EU (west)"), "Prod-EU-west");
+        assert_eq!(cluster_folder_name("../etc"), "etc");
+        assert_eq!(cluster_folder_name("dev_2.1"), "dev_2.1");
+        assert_eq!(cluster_folder_name("日本"), "");
+    }
+
+    #[test]
+    fn test_renamed_cluster_folder_is_moved_and_mapped() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = dir.path();
+        let format = FileFormat::Yaml;
+        std::fs::create_dir_all(endpoint.join("c-123/p-abc")).unwrap();
+        std::fs::write(endpoint.join("c-123/c-123.cluster.yaml"), "id: c-123\ndisplay_name: prod\n").unwrap();
+        let mut cluster = Cluster::new("c-123".to_string(), "prod".to_string(), None);
+
+        // switching to display names moves the folder named after the ID
+        let (folder, changed) = place_cluster_folder(endpoint, &cluster, &format).unwrap();
+        assert_eq!(folder, endpoint.join("prod"));
+        assert_eq!(changed, [endpoint.join("c-123"), endpoint.join("prod"), endpoint.join("clusters.map.yaml")]);
+        assert!(endpoint.join("prod/p-abc").is_dir());
+        assert_eq!(cluster_folder(endpoint, "c-123", &format), endpoint.join("prod"));
+
+        let (_, changed) = place_cluster_folder(endpoint, &cluster, &format).unwrap();
+        assert!(changed.is_empty());
+
+        // another cluster holds the name, the ID is appended
+        std::fs::create_dir_all(endpoint.join("staging")).unwrap();
+        let mut map = ClusterFolderMap::load(endpoint, &format).unwrap();
+        map.insert("staging", "c-456");
+        map.save(endpoint, &format).unwrap();
+        cluster.display_name = "staging".to_string();
+        let (folder, _) = place_cluster_folder(endpoint, &cluster, &format).unwrap();
+        assert_eq!(folder, endpoint.join("staging-c-123"));
+        assert!(!endpoint.join("prod").exists());
+
+        let map = ClusterFolderMap::load(endpoint, &format).unwrap();
+        assert_eq!(map.cluster_id("staging-c-123"), Some("c-123"));
+        assert_eq!(map.cluster_id("staging"), Some("c-456"));
+        assert_eq!(map.cluster_id("prod"), None);
+    }
 
+    #[test]
+    fn test_redacted_fields_are_left_out_and_listed() {
+        let redacted = sample_cluster().redacted(&["description".to_string(), "spec.agentEnvVars".to_string()]).unwrap();
+
+        assert_eq!(redacted.description, None);
+        assert_eq!(redacted.annotations.unwrap()[REDACTED_ANNOTATION], "description");
+        // nothing to leave out, nothing to mark
+        let plain = Cluster { description: None, ..sample_cluster() };
+        assert_eq!(plain.redacted(&["description".to_string()]).unwrap(), plain);
+        assert!(sample_cluster().redacted(&["display_name".to_string()]).is_err());
+    }
 }
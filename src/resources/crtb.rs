@@ -0,0 +1,854 @@
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::client::{dry_run_param, with_server_version};
+use crate::api::client_info::field_manager;
+use crate::error::{api_error, bounded_body, status_error, Access, BoundedResponse};
+use crate::{models::{CreatedObject, DeleteParams, ObjectType, UpdateTarget}, traits::{Metadata, RancherResource}, utils::logging::log_api_error};
+use anyhow::Result;
+
+use reqwest::StatusCode;
+
+use rancher_client::{
+    apis::{
+        configuration::Configuration,
+        management_cattle_io_v3_api::{
+            create_management_cattle_io_v3_namespaced_cluster_role_template_binding,
+            delete_management_cattle_io_v3_namespaced_cluster_role_template_binding,
+            list_management_cattle_io_v3_namespaced_cluster_role_template_binding,
+            patch_management_cattle_io_v3_namespaced_cluster_role_template_binding,
+            read_management_cattle_io_v3_namespaced_cluster_role_template_binding,
+            replace_management_cattle_io_v3_namespaced_cluster_role_template_binding
+        },
+        Error,
+    },
+    models::{
+        IoCattleManagementv3ClusterRoleTemplateBinding,
+        IoCattleManagementv3ClusterRoleTemplateBindingList,
+        IoK8sApimachineryPkgApisMetaV1ObjectMeta, IoK8sApimachineryPkgApisMetaV1Patch, IoK8sApimachineryPkgApisMetaV1Status,
+    },
+};
+use serde_json::Value;
+use tracing::{debug, error, info, trace};
+
+pub const CRTB_EXCLUDE_PATHS: &[&str] = &[
+    "metadata.creationTimestamp",
+    "metadata.finalizers",
+    "metadata.generateName",
+    "metadata.generation",
+    "metadata.managedFields",
+    "metadata.resourceVersion",
+    "metadata.selfLink",
+    "metadata.uid",
+];
+
+impl RancherResource for ClusterRoleTemplateBinding {
+    type ApiType = IoCattleManagementv3ClusterRoleTemplateBinding;
+
+    async fn list(config: &Configuration, namespace: Option<&str>) -> Result<Vec<Self::ApiType>> {
+        let ns = namespace.ok_or_else(|| anyhow::anyhow!("Namespace is required for listing cluster role template bindings"))?;
+        let bindings_list = get_namespaced_cluster_role_template_bindings(config, ns, None, None, None, None, None, None).await?;
+        Ok(bindings_list.items)
+    }
+
+    async fn get(config: &Configuration, name: &str, namespace: &str) -> Result<Self> {
+        let bindings = Self::list(config, Some(namespace)).await?;
+        let binding = bindings.iter()
+            .find(|b| b.metadata.as_ref().and_then(|m| m.name.as_ref()) == Some(&name.to_string()))
+            .ok_or_else(|| anyhow::anyhow!("Cluster role template binding not found: {}", name))?;
+
+        ClusterRoleTemplateBinding::try_from(binding.clone())
+    }
+
+    async fn create(&self, config: &Configuration, dry_run: bool) -> Result<CreatedObject> {
+        let crtb_api = self.clone().try_into_api()?;
+        let result = create_cluster_role_template_binding(config, &self.namespace, crtb_api, dry_run).await?;
+        Ok(CreatedObject::ClusterRoleTemplateBinding(result))
+    }
+
+    async fn update(&self, config: &Configuration, patch: Value, dry_run: bool) -> Result<CreatedObject> {
+        let target = UpdateTarget { namespace: &self.namespace, name: &self.id };
+        let result = update_cluster_role_template_binding(config, target, patch, dry_run).await?;
+        Ok(CreatedObject::ClusterRoleTemplateBinding(result))
+    }
+
+    async fn delete(config: &Configuration, name: &str, namespace: &str, dry_run: bool, params: DeleteParams) -> Result<CreatedObject> {
+        let result = delete_cluster_role_template_binding(config, namespace, name, dry_run, params).await?;
+        Ok(CreatedObject::Status(result))
+    }
+
+    fn resource_type() -> ObjectType {
+        ObjectType::ClusterRoleTemplateBinding
+    }
+
+    fn exclude_paths() -> &'static [&'static str] {
+        CRTB_EXCLUDE_PATHS
+    }
+
+    fn try_from_api(value: Self::ApiType) -> Result<Self> {
+        ClusterRoleTemplateBinding::try_from(value)
+    }
+
+    fn try_into_api(self) -> Result<Self::ApiType> {
+        IoCattleManagementv3ClusterRoleTemplateBinding::try_from(self)
+    }
+
+    fn id(&self) -> Option<String> {
+        Some(self.id.clone())
+    }
+
+    fn namespace(&self) -> Option<String> {
+        Some(self.namespace.clone())
+    }
+
+    fn resource_version(&self) -> Option<String> {
+        self.resource_version.clone()
+    }
+
+    fn metadata_mut(&mut self) -> (&mut Metadata, &mut Metadata) {
+        (&mut self.annotations, &mut self.labels)
+    }
+}
+
+/// Create a cluster role template binding
+///
+/// # Arguments
+///
+/// * `configuration` - The configuration to use for the request
+/// * `cluster_id` - The cluster ID (namespace) to create the binding in
+/// * `body` - The cluster role template binding to create
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
+/// # Returns
+///
+/// * `IoCattleManagementv3ClusterRoleTemplateBinding` - The created cluster role template binding
+/// # Errors
+///
+/// * `anyhow::Error` - The error that occurred while trying to create the cluster role template binding
+///
+#[async_backtrace::framed]
+pub async fn create_cluster_role_template_binding(
+    configuration: &Configuration,
+    cluster_id: &str,
+    body: IoCattleManagementv3ClusterRoleTemplateBinding,
+    dry_run: bool,
+) -> Result<IoCattleManagementv3ClusterRoleTemplateBinding> {
+    let crtb_id = body.metadata.as_ref().and_then(|m| m.name.clone()).unwrap_or_default();
+
+    let api_result = create_management_cattle_io_v3_namespaced_cluster_role_template_binding(
+        configuration,
+        cluster_id,
+        body,
+        None,
+        dry_run_param(dry_run),
+        Some(field_manager()),
+        None,
+    )
+    .await;
+
+    trace!(api_result = ?api_result, "Received API response");
+
+    match api_result {
+        Ok(response_content) => {
+            match response_content.status {
+                StatusCode::CREATED | StatusCode::OK => {
+                    match serde_json::from_str::<IoCattleManagementv3ClusterRoleTemplateBinding>(&response_content.content) {
+                        Ok(data) => {
+                            info!("Successfully created cluster role template binding with ID: {} in cluster: {}",
+                                crtb_id, cluster_id);
+                            Ok(data)
+                        }
+                        Err(deserialize_err) => {
+                            let err = anyhow::anyhow!(
+                                "Failed to deserialize cluster role template binding creation response: {}",
+                                deserialize_err
+                            );
+                            log_api_error("create_cluster_role_template_binding:deserialize", &err);
+                            Err(err)
+                        }
+                    }
+                }
+                status => {
+                    let err = anyhow::anyhow!(
+                        "Unexpected status code {} when creating cluster role template binding in cluster: {}: {}",
+                        status,
+                        cluster_id,
+                        bounded_body(&response_content.content)
+                    );
+                    log_api_error("create_cluster_role_template_binding:unexpected_status", &err);
+                    Err(err)
+                }
+            }
+        },
+        Err(e) => {
+            match e {
+                Error::ResponseError(response_content) => {
+                    let msg = match response_content.status {
+                        StatusCode::NOT_FOUND => format!("Cluster with ID: {} not found", cluster_id),
+                        StatusCode::UNAUTHORIZED => format!("Unauthorized access while trying to create cluster role template binding with ID: {} in cluster: {}", crtb_id, cluster_id),
+                        StatusCode::BAD_REQUEST => format!("Bad request when creating cluster role template binding with ID: {} in cluster: {}. Request body was: {}", crtb_id, cluster_id, bounded_body(&response_content.content)),
+                        StatusCode::FORBIDDEN => format!("Forbidden while trying to create cluster role template binding with ID: {} in cluster: {}", crtb_id, cluster_id),
+                        StatusCode::CONFLICT => format!("Cluster role template binding with ID: {} in cluster {} already exists", crtb_id, cluster_id),
+                        _ => format!("Failed to create cluster role template binding with ID: {} in cluster {}. Response: {:#?}", crtb_id, cluster_id, BoundedResponse(&response_content)),
+                    };
+                    error!(msg);
+                    Err(status_error(response_content.status, Access::Write, ObjectType::ClusterRoleTemplateBinding, Some(cluster_id), msg))
+                }
+                _ => {
+                    let msg = format!("Failed to create cluster role template binding with ID: {} in cluster: {}. Error: {:#?}", crtb_id, cluster_id, e);
+                    error!(msg);
+                    Err(anyhow::anyhow!(msg))
+                }
+            }
+        }
+    }
+}
+
+/// Find a cluster role template binding by its ID
+///
+/// # Arguments
+///
+/// * `configuration` - The configuration to use for the request
+/// * `cluster_id` - The ID of the cluster (namespace) holding the binding
+/// * `crtb_id` - The ID of the binding to get
+/// # Returns
+///
+/// * `IoCattleManagementv3ClusterRoleTemplateBinding` - The cluster role template binding
+/// # Errors
+///
+/// * `anyhow::Error` - The error that occurred while trying to get the cluster role template binding
+///
+#[async_backtrace::framed]
+pub async fn find_cluster_role_template_binding(
+    configuration: &Configuration,
+    cluster_id: &str,
+    crtb_id: &str,
+) -> Result<IoCattleManagementv3ClusterRoleTemplateBinding> {
+    let api_result =
+        read_management_cattle_io_v3_namespaced_cluster_role_template_binding(configuration, crtb_id, cluster_id, None, None)
+            .await;
+
+    trace!(api_result = ?api_result, "Received API response");
+
+    match api_result {
+        Ok(response_content) => match response_content.status {
+            StatusCode::OK => {
+                match serde_json::from_str::<IoCattleManagementv3ClusterRoleTemplateBinding>(&response_content.content) {
+                    Ok(data) => Ok(data),
+                    Err(deserialize_err) => {
+                        let err = anyhow::anyhow!(
+                            "Failed to deserialize cluster role template binding response: {}",
+                            deserialize_err
+                        );
+                        log_api_error("find_cluster_role_template_binding:deserialize", &err);
+                        Err(err)
+                    }
+                }
+            }
+            status => {
+                let err = anyhow::anyhow!(
+                    "Unexpected status code {} when finding cluster role template binding with ID: {}: {}",
+                    status,
+                    crtb_id,
+                    bounded_body(&response_content.content)
+                );
+                log_api_error("find_cluster_role_template_binding:unexpected_status", &err);
+                Err(err)
+            }
+        },
+        Err(e) => match e {
+            Error::ResponseError(response_content) => {
+                let msg = match response_content.status {
+                    StatusCode::NOT_FOUND => format!("Cluster role template binding with ID: {} in cluster: {} not found", crtb_id, cluster_id),
+                    _ => format!("Failed to get cluster role template binding with ID: {} in cluster: {}. Response: {:#?}", crtb_id, cluster_id, BoundedResponse(&response_content)),
+                };
+                debug!("{}", msg);
+                Err(status_error(response_content.status, Access::Read, ObjectType::ClusterRoleTemplateBinding, Some(cluster_id), msg))
+            }
+            _ => {
+                let msg = format!("Failed to get cluster role template binding with ID: {} in cluster: {}. Error: {:#?}", crtb_id, cluster_id, e);
+                error!("{}", msg);
+                Err(api_error(&e, msg))
+            }
+        },
+    }
+}
+
+/// Get the cluster role template bindings of a cluster
+///
+/// # Arguments
+///
+/// * `configuration` - The configuration to use for the request
+/// * `cluster_id` - The ID of the cluster (namespace) to get the role template bindings for
+/// * `field_selector` - If specified, selects only the specified fields of the bindings
+/// * `label_selector` - If specified, selects only the bindings with the specified labels
+/// * `limit` - If specified, limits the number of bindings returned
+/// * `resource_version` - If specified, only returns bindings with a resource version greater than the specified version
+/// * `resource_version_match` - If specified, only returns bindings with a resource version that matches the specified version
+/// * `continue_` - If specified, continues the listing from the last binding returned in the previous response
+///
+/// # Returns
+///
+/// * `IoCattleManagementv3ClusterRoleTemplateBindingList` - The list of cluster role template bindings
+///
+/// # Errors
+///
+/// * `anyhow::Error` - The error that occurred while trying to get the bindings
+#[allow(clippy::too_many_arguments)]
+#[async_backtrace::framed]
+pub async fn get_namespaced_cluster_role_template_bindings(
+    configuration: &Configuration,
+    cluster_id: &str,
+    field_selector: Option<&str>,
+    label_selector: Option<&str>,
+    limit: Option<i32>,
+    resource_version: Option<&str>,
+    resource_version_match: Option<&str>,
+    continue_: Option<&str>,
+) -> Result<IoCattleManagementv3ClusterRoleTemplateBindingList> {
+    let api_result = list_management_cattle_io_v3_namespaced_cluster_role_template_binding(
+        configuration,
+        cluster_id,
+        None,
+        None,
+        continue_,
+        field_selector,
+        label_selector,
+        limit,
+        resource_version,
+        resource_version_match,
+        None,
+        None,
+        None,
+    )
+    .await;
+
+    trace!(api_result = ?api_result, "Received API response");
+
+    match api_result {
+        Ok(response_content) => {
+            match response_content.status {
+                StatusCode::OK => match serde_json::from_str(&response_content.content) {
+                    Ok(data) => {
+                        debug!("Successfully deserialized crtb response content");
+                        Ok(data)
+                    }
+                    Err(deserialize_err) => {
+                        let msg = with_server_version(configuration, format!("Failed to deserialize cluster role template bindings: {}", deserialize_err));
+                        error!(msg);
+                        Err(anyhow::anyhow!(msg))
+                    }
+                },
+                status => {
+                    let err = anyhow::anyhow!(
+                        "Unexpected status code {} when getting cluster role template bindings: {}",
+                        status, bounded_body(&response_content.content)
+                    );
+                    log_api_error("get_namespaced_cluster_role_template_bindings:unexpected_status", &err);
+                    Err(err)
+                }
+            }
+        }
+        Err(e) => {
+            match e {
+                Error::ResponseError(response_content) => {
+                    let msg = match response_content.status {
+                        StatusCode::NOT_FOUND => format!("Cluster with ID: {} not found", cluster_id),
+                        StatusCode::FORBIDDEN => format!("Forbidden access while trying to get cluster role template bindings for cluster: {}", cluster_id),
+                        _ => format!("Failed to get cluster role template bindings for cluster: {}. Response: {:#?}", cluster_id, BoundedResponse(&response_content)),
+                    };
+                    error!("{}", msg);
+                    Err(status_error(response_content.status, Access::Read, ObjectType::ClusterRoleTemplateBinding, Some(cluster_id), msg))
+                },
+                _ => {
+                    let msg = format!("Failed to get cluster role template bindings for cluster: {}. Error: {:#?}", cluster_id, e);
+                    error!("{}", msg);
+                    Err(api_error(&e, msg))
+                }
+            }
+        }
+    }
+}
+
+/// Update a cluster role template binding
+///
+/// # Arguments
+///
+/// * `configuration` - The configuration to use for the request
+/// * `target` - The binding to patch; `namespace` is the cluster ID and `name` the binding ID
+/// * `patch_value` - The JSON patch to apply
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
+/// # Returns
+///
+/// * `IoCattleManagementv3ClusterRoleTemplateBinding` - The updated cluster role template binding
+/// # Errors
+///
+/// * `anyhow::Error` - The error that occurred while trying to update the cluster role template binding
+///
+#[async_backtrace::framed]
+pub async fn update_cluster_role_template_binding(
+    configuration: &Configuration,
+    target: UpdateTarget<'_>,
+    patch_value: Value,
+    dry_run: bool,
+) -> Result<IoCattleManagementv3ClusterRoleTemplateBinding> {
+    let UpdateTarget { namespace: cluster_id, name: crtb_id } = target;
+
+    let patch_array = match patch_value {
+        Value::Array(arr) => arr,
+        _ => {
+            error!(
+                "Expected patch to serialize to a JSON array, but got: {:?}",
+                patch_value
+            );
+            return Err(anyhow::anyhow!(
+                "Expected patch to serialize to a JSON array"
+            ));
+        }
+    };
+
+    let k8s_patch = IoK8sApimachineryPkgApisMetaV1Patch::Array(patch_array);
+
+    let api_result = patch_management_cattle_io_v3_namespaced_cluster_role_template_binding(
+        configuration,
+        target.name,
+        target.namespace,
+        Some(k8s_patch),
+        None,
+        dry_run_param(dry_run),
+        Some(field_manager()),
+        None,
+        None
+    )
+    .await;
+
+    trace!(result = ?api_result, "Received API response");
+
+    match api_result {
+        Ok(response_content) => {
+            match response_content.status {
+                StatusCode::OK => {
+                    match serde_json::from_str::<IoCattleManagementv3ClusterRoleTemplateBinding>(&response_content.content) {
+                        Ok(data) => {
+                            info!("Successfully updated cluster role template binding with ID: {}", crtb_id);
+                            Ok(data)
+                        }
+                        Err(deserialize_err) => {
+                            let err = anyhow::anyhow!(
+                                "Failed to deserialize cluster role template binding update response: {}",
+                                deserialize_err
+                            );
+                            log_api_error("update_cluster_role_template_binding:deserialize", &err);
+                            Err(err)
+                        }
+                    }
+                }
+                status => {
+                    let err = anyhow::anyhow!(
+                        "Unexpected status code {} when updating cluster role template binding with ID: {}: {}",
+                        status,
+                        crtb_id,
+                        bounded_body(&response_content.content)
+                    );
+                    log_api_error("update_cluster_role_template_binding:unexpected_status", &err);
+                    Err(err)
+                }
+            }
+        }
+        Err(e) => {
+            match e {
+                Error::ResponseError(response_error) => {
+                    let msg = match response_error.status {
+                        StatusCode::NOT_FOUND => format!("Cluster role template binding with ID: {} in cluster: {} not found", crtb_id, cluster_id),
+                        StatusCode::UNAUTHORIZED => format!("Unauthorized access while trying to update cluster role template binding with ID: {} in cluster: {}", crtb_id, cluster_id),
+                        StatusCode::BAD_REQUEST => format!("Bad request when updating cluster role template binding with ID: {} in cluster: {}. Request body was: {}", crtb_id, cluster_id, bounded_body(&response_error.content)),
+                        _ => format!("Failed to update cluster role template binding with ID: {} in cluster: {}. Response: {:#?}", crtb_id, cluster_id, BoundedResponse(&response_error)),
+                    };
+                    error!(msg);
+                    Err(status_error(response_error.status, Access::Write, ObjectType::ClusterRoleTemplateBinding, Some(target.namespace), msg))
+                }
+                _ => {
+                    let msg = format!("Failed to update cluster role template binding with ID: {} in cluster: {}. Error: {:#?}", crtb_id, cluster_id, e);
+                    error!(msg);
+                    Err(anyhow::anyhow!(msg))
+                }
+            }
+        }
+    }
+}
+
+/// Replace a cluster role template binding with the given body (PUT)
+/// # Arguments
+///
+/// * `configuration` - The configuration to use for the request
+/// * `target` - The binding to replace; `namespace` is the cluster ID and `name` the binding ID
+/// * `body` - The full binding, carrying the current `metadata.resourceVersion`
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
+/// # Returns
+///
+/// * `IoCattleManagementv3ClusterRoleTemplateBinding` - The replaced cluster role template binding
+/// # Errors
+///
+/// * `anyhow::Error` - The error that occurred while trying to replace the cluster role template binding
+///
+#[async_backtrace::framed]
+pub async fn replace_cluster_role_template_binding(
+    configuration: &Configuration,
+    target: UpdateTarget<'_>,
+    body: IoCattleManagementv3ClusterRoleTemplateBinding,
+    dry_run: bool,
+) -> Result<IoCattleManagementv3ClusterRoleTemplateBinding> {
+    let UpdateTarget { namespace: cluster_id, name: crtb_id } = target;
+
+    let api_result = replace_management_cattle_io_v3_namespaced_cluster_role_template_binding(
+        configuration,
+        target.name,
+        target.namespace,
+        body,
+        None,
+        dry_run_param(dry_run),
+        Some(field_manager()),
+        None,
+    )
+    .await;
+
+    trace!(api_result = ?api_result, "Received API response");
+
+    match api_result {
+        Ok(response_content) => {
+            match response_content.status {
+                StatusCode::OK | StatusCode::CREATED => {
+                    match serde_json::from_str::<IoCattleManagementv3ClusterRoleTemplateBinding>(&response_content.content) {
+                        Ok(data) => {
+                            info!("Successfully replaced cluster role template binding with ID: {}", crtb_id);
+                            Ok(data)
+                        }
+                        Err(deserialize_err) => {
+                            let err = anyhow::anyhow!(
+                                "Failed to deserialize cluster role template binding replace response: {}",
+                                deserialize_err
+                            );
+                            log_api_error("replace_cluster_role_template_binding:deserialize", &err);
+                            Err(err)
+                        }
+                    }
+                }
+                status => {
+                    let err = anyhow::anyhow!(
+                        "Unexpected status code {} when replacing cluster role template binding with ID: {}: {}",
+                        status,
+                        crtb_id,
+                        bounded_body(&response_content.content)
+                    );
+                    log_api_error("replace_cluster_role_template_binding:unexpected_status", &err);
+                    Err(err)
+                }
+            }
+        }
+        Err(e) => {
+            match e {
+                Error::ResponseError(response_error) => {
+                    let msg = match response_error.status {
+                        StatusCode::NOT_FOUND => format!("Cluster role template binding with ID: {} in cluster: {} not found", crtb_id, cluster_id),
+                        StatusCode::CONFLICT => format!("Conflict when replacing cluster role template binding with ID: {} in cluster: {}, it changed since it was read. Response: {}", crtb_id, cluster_id, bounded_body(&response_error.content)),
+                        _ => format!("Failed to replace cluster role template binding with ID: {} in cluster: {}. Response: {:#?}", crtb_id, cluster_id, BoundedResponse(&response_error)),
+                    };
+                    error!(msg);
+                    Err(status_error(response_error.status, Access::Write, ObjectType::ClusterRoleTemplateBinding, Some(target.namespace), msg))
+                }
+                _ => {
+                    let msg = format!("Failed to replace cluster role template binding with ID: {} in cluster: {}. Error: {:#?}", crtb_id, cluster_id, e);
+                    error!(msg);
+                    Err(anyhow::anyhow!(msg))
+                }
+            }
+        }
+    }
+}
+
+/// Delete a cluster role template binding
+///
+/// # Arguments
+///
+/// * `configuration` - The configuration to use for the request
+/// * `cluster_id` - The cluster ID
+/// * `crtb_id` - The cluster role template binding ID
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
+/// * `params` - The propagation policy and grace period of the deletion
+/// # Returns
+///
+/// * `IoK8sApimachineryPkgApisMetaV1Status` - The status of the deletion
+/// # Errors
+///
+/// * `anyhow::Error` - The error that occurred while trying to delete the cluster role template binding
+///
+#[async_backtrace::framed]
+pub async fn delete_cluster_role_template_binding(
+    configuration: &Configuration,
+    cluster_id: &str,
+    crtb_id: &str,
+    dry_run: bool,
+    params: DeleteParams,
+) -> Result<IoK8sApimachineryPkgApisMetaV1Status> {
+    let api_result = delete_management_cattle_io_v3_namespaced_cluster_role_template_binding(
+        configuration,
+        crtb_id,
+        cluster_id,
+        None,
+        dry_run_param(dry_run),
+        params.grace_period_seconds,
+        None,
+        params.propagation_param(),
+        None,
+    )
+    .await;
+
+    trace!(api_result = ?api_result, "Received API response");
+
+    match api_result {
+        Ok(response_content) => {
+            match response_content.status {
+                StatusCode::OK => {
+                    match serde_json::from_str::<IoK8sApimachineryPkgApisMetaV1Status>(&response_content.content) {
+                        Ok(data) => {
+                            info!("Successfully deleted cluster role template binding with ID: {}", crtb_id);
+                            Ok(data)
+                        }
+                        Err(deserialize_err) => {
+                            let err = anyhow::anyhow!(
+                                "Failed to deserialize cluster role template binding deletion response: {}",
+                                deserialize_err
+                            );
+                            log_api_error("delete_cluster_role_template_binding:deserialize", &err);
+                            Err(err)
+                        }
+                    }
+                }
+                status => {
+                    let err = anyhow::anyhow!(
+                        "Unexpected status code {} when deleting cluster role template binding with ID: {}: {}",
+                        status,
+                        crtb_id,
+                        bounded_body(&response_content.content)
+                    );
+                    log_api_error("delete_cluster_role_template_binding:unexpected_status", &err);
+                    Err(err)
+                }
+            }
+        }
+        Err(e) => {
+            match e {
+                Error::ResponseError(response_error) => {
+                    let msg = match response_error.status {
+                        StatusCode::NOT_FOUND => format!("Cluster role template binding with ID: {} in cluster: {} not found", crtb_id, cluster_id),
+                        StatusCode::UNAUTHORIZED => format!("Unauthorized access while trying to delete cluster role template binding with ID: {} in cluster: {}", crtb_id, cluster_id),
+                        _ => format!("Failed to delete cluster role template binding with ID: {} in cluster: {}. Response: {:#?}", crtb_id, cluster_id, BoundedResponse(&response_error)),
+                    };
+                    error!(msg);
+                    Err(status_error(response_error.status, Access::Write, ObjectType::ClusterRoleTemplateBinding, Some(cluster_id), msg))
+                }
+                _ => {
+                    let msg = format!("Failed to delete cluster role template binding with ID: {} in cluster: {}. Error: {:#?}", crtb_id, cluster_id, e);
+                    error!(msg);
+                    Err(api_error(&e, msg))
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ClusterRoleTemplateBinding {
+    /// Annotations applied to the cluster role template binding.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<std::collections::HashMap<String, String>>,
+
+    /// The name of the cluster the role template is bound to. Must match the namespace.
+    pub cluster_name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_principal_name: Option<String>,
+
+    /// The name of the cluster role template binding (typically the Kubernetes metadata.name).
+    pub id: String,
+
+    /// Labels applied to the cluster role template binding
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<std::collections::HashMap<String, String>>,
+
+    /// The cluster (namespace) the cluster role template binding exists in
+    pub namespace: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_version: Option<String>,
+
+    pub role_template_name: String,
+
+    /// The UID of the binding. Rancher will set this value when the binding is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_principal_name: Option<String>,
+}
+
+impl TryFrom<IoCattleManagementv3ClusterRoleTemplateBinding> for ClusterRoleTemplateBinding {
+    type Error = anyhow::Error;
+
+    fn try_from(
+        value: IoCattleManagementv3ClusterRoleTemplateBinding,
+    ) -> Result<Self, Self::Error> {
+        let metadata: IoK8sApimachineryPkgApisMetaV1ObjectMeta = value.metadata.map(|m| *m).ok_or_else(|| anyhow::anyhow!("Missing metadata field"))?;
+
+        let id = metadata.name.ok_or_else(|| anyhow::anyhow!("Missing metadata.name"))?;
+
+        Ok(ClusterRoleTemplateBinding {
+            annotations: metadata.annotations,
+            cluster_name: value.cluster_name,
+            group_name: value.group_name,
+            group_principal_name: value.group_principal_name,
+            id,
+            labels: metadata.labels,
+            namespace: metadata.namespace.unwrap_or_default(),
+            resource_version: metadata.resource_version,
+            role_template_name: value.role_template_name,
+            uid: metadata.uid,
+            user_name: value.user_name,
+            user_principal_name: value.user_principal_name,
+        })
+    }
+}
+
+impl TryFrom<ClusterRoleTemplateBinding> for IoCattleManagementv3ClusterRoleTemplateBinding {
+    type Error = anyhow::Error;
+
+    fn try_from(value: ClusterRoleTemplateBinding) -> Result<Self, Self::Error> {
+        let metadata = IoK8sApimachineryPkgApisMetaV1ObjectMeta {
+            annotations: value.annotations,
+            labels: value.labels,
+            namespace: Some(value.namespace),
+            name: Some(value.id),
+            resource_version: value.resource_version,
+            uid: value.uid,
+            ..Default::default()
+        };
+
+        Ok(IoCattleManagementv3ClusterRoleTemplateBinding {
+            api_version: Some("management.cattle.io/v3".to_string()),
+            cluster_name: value.cluster_name,
+            group_name: value.group_name,
+            group_principal_name: value.group_principal_name,
+            kind: Some("ClusterRoleTemplateBinding".to_string()),
+            metadata: Some(Box::new(metadata)),
+            role_template_name: value.role_template_name,
+            user_name: value.user_name,
+            user_principal_name: value.user_principal_name,
+        })
+    }
+}
+
+impl PartialEq<ClusterRoleTemplateBinding> for IoCattleManagementv3ClusterRoleTemplateBinding {
+    fn eq(&self, other: &ClusterRoleTemplateBinding) -> bool {
+        let lhs = self.metadata.as_ref().and_then(|m| m.name.clone());
+        let rhs = Some(other.id.clone());
+
+        lhs == rhs
+            && self.cluster_name == other.cluster_name
+            && self.group_name == other.group_name
+            && self.group_principal_name == other.group_principal_name
+            && self.role_template_name == other.role_template_name
+            && self.user_name == other.user_name
+            && self.user_principal_name == other.user_principal_name
+    }
+}
+
+impl PartialEq<IoCattleManagementv3ClusterRoleTemplateBinding> for ClusterRoleTemplateBinding {
+    fn eq(&self, other: &IoCattleManagementv3ClusterRoleTemplateBinding) -> bool {
+        other == self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_binding() -> ClusterRoleTemplateBinding {
+        ClusterRoleTemplateBinding {
+            annotations: None,
+            cluster_name: "c-123".to_string(),
+            group_name: None,
+            group_principal_name: None,
+            id: "crtb-abc".to_string(),
+            labels: None,
+            namespace: "c-123".to_string(),
+            resource_version: Some("42".to_string()),
+            role_template_name: "cluster-owner".to_string(),
+            uid: None,
+            user_name: Some("u-xyz".to_string()),
+            user_principal_name: Some("local://u-xyz".to_string()),
+        }
+    }
+
+    fn sample_iocattle_binding() -> IoCattleManagementv3ClusterRoleTemplateBinding {
+        IoCattleManagementv3ClusterRoleTemplateBinding {
+            metadata: Some(Box::new(IoK8sApimachineryPkgApisMetaV1ObjectMeta {
+                name: Some("crtb-abc".to_string()),
+                namespace: Some("c-123".to_string()),
+                ..Default::default()
+            })),
+            user_name: Some("u-xyz".to_string()),
+            user_principal_name: Some("local://u-xyz".to_string()),
+            ..IoCattleManagementv3ClusterRoleTemplateBinding::new("c-123".to_string(), "cluster-owner".to_string())
+        }
+    }
+
+    #[test]
+    fn test_conversion_round_trip() {
+        let binding = ClusterRoleTemplateBinding::try_from(sample_iocattle_binding()).unwrap();
+        assert_eq!(binding.namespace, "c-123");
+        assert_eq!(binding, sample_iocattle_binding());
+
+        let ioc = IoCattleManagementv3ClusterRoleTemplateBinding::try_from(sample_binding()).unwrap();
+        assert_eq!(ioc, sample_binding());
+        assert_eq!(ioc.kind.as_deref(), Some("ClusterRoleTemplateBinding"));
+    }
+
+    #[test]
+    fn test_missing_metadata_name() {
+        let mut ioc = sample_iocattle_binding();
+        ioc.metadata.as_mut().unwrap().name = None;
+
+        assert!(ClusterRoleTemplateBinding::try_from(ioc).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_reads_cluster_namespace() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/c-123/clusterroletemplatebindings"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "apiVersion": "management.cattle.io/v3",
+                "kind": "ClusterRoleTemplateBindingList",
+                "metadata": {},
+                "items": [sample_iocattle_binding()]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut config = Configuration::new();
+        config.base_path = server.uri();
+
+        let bindings = ClusterRoleTemplateBinding::list(&config, Some("c-123")).await.unwrap();
+
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(bindings[0], sample_binding());
+    }
+}
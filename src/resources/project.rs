@@ -1,12 +1,13 @@
-use std::path::Path;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_diff::SerdeDiff;
 use similar::{ChangeTag, TextDiff};
 use serde_json::Value;
-use tokio::fs::{metadata, read_to_string};
-use tracing::{error, info, trace};
+use tokio::fs::{metadata, read_dir, read_to_string, remove_file, rename, write};
+use tracing::{error, info, trace, warn};
 use reqwest::{ StatusCode};
 
 
@@ -18,6 +19,7 @@ use rancher_client::{
             delete_management_cattle_io_v3_namespaced_project,
             list_management_cattle_io_v3_namespaced_project,
             patch_management_cattle_io_v3_namespaced_project,
+            replace_management_cattle_io_v3_namespaced_project,
             read_management_cattle_io_v3_namespaced_project,
         },
         Error,
@@ -35,13 +37,19 @@ use rancher_client::{
 
 
 use crate::{
-    deserialize_object,
-    utils::file::{file_extension_from_format, FileFormat},
+    deserialize_object, serialize_object,
+    utils::file::{endpoint_folder, file_extension_from_format, get_file_name_for_object, FileFormat},
 };
+use crate::resources::prtb::ProjectRoleTemplateBinding;
+use crate::resources::cluster::cluster_folder;
+use crate::api::client::{dry_run_param, with_server_version};
+use crate::api::client_info::field_manager;
+use crate::error::{api_error, bounded_body, status_error, Access, BoundedResponse};
 use crate::utils::logging::log_api_error;
+use crate::utils::safe_path::ensure_within;
 use crate::utils::diff::diff_boxed_hashmap_string_string;
-use crate::traits::RancherResource;
-use crate::models::{CreatedObject, ObjectType, ResourceVersionMatch};
+use crate::traits::{Metadata, RancherResource};
+use crate::models::{ConversionError, CreatedObject, DeleteParams, ObjectType, ResourceVersionMatch, UpdateTarget};
 
 pub const PROJECT_EXCLUDE_PATHS: &[&str] = &[
     "metadata.creationTimestamp",
@@ -72,7 +80,7 @@ impl RancherResource for Project {
     }
     
     fn try_into_api(self) -> Result<Self::ApiType> {
-        Ok(IoCattleManagementv3Project::try_from(self)?)
+        IoCattleManagementv3Project::try_from(self)
     }
     
     fn id(&self) -> Option<String> {
@@ -86,6 +94,10 @@ impl RancherResource for Project {
     fn resource_version(&self) -> Option<String> {
         self.resource_version.clone()
     }
+
+    fn metadata_mut(&mut self) -> (&mut Metadata, &mut Metadata) {
+        (&mut self.annotations, &mut self.labels)
+    }
     
     async fn list(config: &Configuration, namespace: Option<&str>) -> Result<Vec<Self::ApiType>> {
         let ns = namespace.ok_or_else(|| anyhow::anyhow!("Namespace is required for listing projects"))?;
@@ -102,37 +114,41 @@ impl RancherResource for Project {
         Self::try_from_api(project)
     }
     
-    async fn create(&self, config: &Configuration) -> Result<CreatedObject> {
+    async fn create(&self, config: &Configuration, dry_run: bool) -> Result<CreatedObject> {
         let ns = self.namespace().ok_or_else(|| anyhow::anyhow!("Namespace is required for creating projects"))?;
         // Convert to API type
         let project_api = self.clone().try_into_api()?;
         
         // Call API
-        let result = create_project( config, &ns, project_api ).await?;
+        let result = create_project( config, &ns, project_api, dry_run ).await?;
         
         // Convert response to CreatedObject
         Ok(CreatedObject::Project(result))
     }
     
 
-    async fn update(&self, config: &Configuration, patch_value: Value) -> Result<CreatedObject> {
+    async fn update(&self, config: &Configuration, patch_value: Value, dry_run: bool) -> Result<CreatedObject> {
         let ns = self.namespace().ok_or_else(|| anyhow::anyhow!("Namespace is required for updating projects"))?;
     
+        let id = self.id().ok_or_else(|| anyhow::anyhow!("Project ID is required for updating projects"))?;
+
         let result = update_project(
             config,
-            &self.id().unwrap_or_default(),
-            &ns,
-            patch_value
+            UpdateTarget { namespace: &ns, name: &id },
+            patch_value,
+            dry_run,
         ).await?;
         Ok(CreatedObject::Project(result))
     }
     
-    async fn delete(config: &Configuration, name: &str, namespace: &str) -> Result<CreatedObject> {
+    async fn delete(config: &Configuration, name: &str, namespace: &str, dry_run: bool, params: DeleteParams) -> Result<CreatedObject> {
         // Call API, return of Result<Result<IoCattleManagementv3Project, IoK8sApimachineryPkgApisMetaV1Status>, Error>
         let result = delete_project(
             config,
             namespace,
             name,
+            dry_run,
+            params,
         ).await?;
         
         match result {
@@ -155,6 +171,7 @@ impl RancherResource for Project {
 /// * `configuration` - The configuration for the request
 /// * `cluster_id` - The ID of the cluster to create the project in
 /// * `body` - The project to create
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
 /// # Returns
 /// * `IoCattleManagementv3Project` - The project that was created
 /// # Errors
@@ -165,8 +182,18 @@ pub async fn create_project(
     configuration: &Configuration,
     cluster_id: &str,
     body: IoCattleManagementv3Project,
+    dry_run: bool,
 ) -> Result<IoCattleManagementv3Project> {
-    let project_id = body.metadata.as_ref().unwrap().name.clone().unwrap();
+    let metadata = body
+        .metadata
+        .as_ref()
+        .ok_or(ConversionError::MissingField("metadata".into()))?;
+    // new projects usually only carry a generateName prefix
+    let project_id = metadata
+        .name
+        .clone()
+        .or_else(|| metadata.generate_name.clone())
+        .ok_or(ConversionError::MissingField("metadata.name".into()))?;
     info!(
         "Creating project in cluster: {} with ID: {}",
         cluster_id, project_id
@@ -177,8 +204,8 @@ pub async fn create_project(
         cluster_id,
         body,
         None,
-        None,
-        Some(crate::FULL_CLIENT_ID),
+        dry_run_param(dry_run),
+        Some(field_manager()),
         None,
     )
     .await;
@@ -217,13 +244,13 @@ pub async fn create_project(
                         Ok(error_obj) => {
                             anyhow::anyhow!(
                                 "Unexpected status code {} when creating project with ID: {} in cluster {}: {}", 
-                                status, project_id, cluster_id, serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone())
+                                status, project_id, cluster_id, bounded_body(&serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone()))
                             )
                         }
                         Err(_) => {
                             anyhow::anyhow!(
                                 "Unexpected status code {} when creating project with ID: {} in cluster {}: {}", 
-                                status, project_id, cluster_id, response_content.content
+                                status, project_id, cluster_id, bounded_body(&response_content.content)
                             )
                         }
                     };
@@ -248,7 +275,7 @@ pub async fn create_project(
                         StatusCode::BAD_REQUEST => {
                             format!(
                                 "Bad request when creating project with ID: {} in cluster: {}. Request body was: {}",
-                                project_id, cluster_id, response_content.content
+                                project_id, cluster_id, bounded_body(&response_content.content)
                             )
                         }
                         StatusCode::FORBIDDEN => {
@@ -264,11 +291,11 @@ pub async fn create_project(
                             )
                         }
                         _ => {
-                            format!("Failed to create project with ID: {} in cluster {}. Response: {:#?}", project_id, cluster_id, response_content)
+                            format!("Failed to create project with ID: {} in cluster {}. Response: {:#?}", project_id, cluster_id, BoundedResponse(&response_content))
                         }
                     };
                     error!("{}", msg);
-                    Err(anyhow::anyhow!("{}", msg))
+                    Err(status_error(response_content.status, Access::Write, ObjectType::Project, Some(cluster_id), msg))
                 }
                 _ => {
                     let msg = format!(
@@ -284,6 +311,14 @@ pub async fn create_project(
 }
 
 
+/// Check that the token can list the projects of `cluster_id`, returning how many it sees.
+///
+/// Used by `shepherd check`, nothing is changed.
+pub async fn probe_projects(configuration: &Configuration, cluster_id: &str) -> Result<usize> {
+    let projects = get_projects(configuration, cluster_id, None, None, None, None, None, None).await?;
+    Ok(projects.items.len())
+}
+
 /// Get the list of projects for a cluster
 ///
 /// # Arguments
@@ -304,6 +339,7 @@ pub async fn create_project(
 /// # Errors
 ///
 /// * `anyhow::Error` - The error that occurred while trying to get the project list
+#[allow(clippy::too_many_arguments)]
 #[async_backtrace::framed]
 pub async fn get_projects(
     configuration: &Configuration,
@@ -350,10 +386,10 @@ pub async fn get_projects(
                             Ok(data)
                         }
                         Err(deserialize_err) => {
-                            let msg = format!(
+                            let msg = with_server_version(configuration, format!(
                                 "Failed to deserialize projects response: {}",
                                 deserialize_err
-                            );
+                            ));
                             error!(msg);
                             Err(anyhow::anyhow!(msg))
                         }
@@ -368,13 +404,13 @@ pub async fn get_projects(
                         Ok(error_obj) => {
                             anyhow::anyhow!(
                                 "Unexpected status code {} when getting projects for cluster {}: {}", 
-                                status, cluster_id, serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone())
+                                status, cluster_id, bounded_body(&serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone()))
                             )
                         }
                         Err(_) => {
                             anyhow::anyhow!(
                                 "Unexpected status code {} when getting projects for cluster {}: {}", 
-                                status, cluster_id, response_content.content
+                                status, cluster_id, bounded_body(&response_content.content)
                             )
                         }
                     };
@@ -394,7 +430,7 @@ pub async fn get_projects(
                         ),
                         StatusCode::BAD_REQUEST => format!(
                             "Bad request while trying to get project list for cluster: {}. Request body was: {}",
-                            cluster_id, response_content.content
+                            cluster_id, bounded_body(&response_content.content)
                         ),
                         StatusCode::FORBIDDEN => format!(
                             "Forbidden access while trying to get project list for cluster: {}",
@@ -402,11 +438,11 @@ pub async fn get_projects(
                         ),
                         _ => format!(
                             "Failed to get project list for cluster: {}. Response: {:#?}",
-                            cluster_id, response_content
+                            cluster_id, BoundedResponse(&response_content)
                         ),
                     };
                     error!("{}", msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(status_error(response_content.status, Access::Read, ObjectType::Project, Some(cluster_id), msg))
                 },
                 _ => {
                     let msg = format!(
@@ -414,7 +450,7 @@ pub async fn get_projects(
                         cluster_id, e
                     );
                     error!("{}", msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(api_error(&e, msg))
                 }
             }
         }
@@ -482,8 +518,8 @@ pub async fn find_project(
                             "Unexpected status code {} when finding project with ID: {}: {}",
                             status,
                             project_id,
-                            serde_json::to_string_pretty(&error_obj)
-                                .unwrap_or_else(|_| response_content.content.clone())
+                            bounded_body(&serde_json::to_string_pretty(&error_obj)
+                                .unwrap_or_else(|_| response_content.content.clone()))
                         )
                     }
                     Err(_) => {
@@ -491,7 +527,7 @@ pub async fn find_project(
                             "Unexpected status code {} when finding project with ID: {}: {}",
                             status,
                             project_id,
-                            response_content.content
+                            bounded_body(&response_content.content)
                         )
                     }
                 };
@@ -519,15 +555,15 @@ pub async fn find_project(
                         StatusCode::BAD_REQUEST => {
                             format!(
                         "Bad request while trying to get project with ID: {} for cluster: {}. Request body was: {}",
-                            project_id, cluster_id, response_content.content
+                            project_id, cluster_id, bounded_body(&response_content.content)
                         )
                         }
                         _ => {
-                            format!("Failed to get project with ID: {} for cluster: {}. Response: {:#?}", project_id, cluster_id, response_content)
+                            format!("Failed to get project with ID: {} for cluster: {}. Response: {:#?}", project_id, cluster_id, BoundedResponse(&response_content))
                         }
                     };
                     error!("{}", msg);
-                    Err(anyhow::anyhow!("{}", msg))
+                    Err(status_error(response_content.status, Access::Read, ObjectType::Project, Some(cluster_id), msg))
                 }
                 _ => {
                     let msg = format!(
@@ -535,7 +571,7 @@ pub async fn find_project(
                         project_id, cluster_id, e
                     );
                     error!("{}", msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(api_error(&e, msg))
                 }
             }
         }
@@ -545,9 +581,9 @@ pub async fn find_project(
 /// Update a project by its ID
 /// # Arguments
 /// * `configuration` - The configuration to use for the request
-/// * `cluster_id` - The ID of the cluster (namespace) to get the project for
-/// * `project_id` - The ID of the project to get
+/// * `target` - The project to patch; `namespace` is the cluster ID and `name` the project ID
 /// * `body` - The Kubernetes patch body to apply
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
 /// # Returns
 /// * `IoCattleManagementv3Project` - The project
 /// # Errors
@@ -556,10 +592,12 @@ pub async fn find_project(
 #[async_backtrace::framed]
 pub async fn update_project(
     configuration: &Configuration,
-    cluster_id: &str,
-    project_id: &str,
+    target: UpdateTarget<'_>,
     patch_value: Value,
+    dry_run: bool,
 ) -> Result<IoCattleManagementv3Project> {
+    let UpdateTarget { namespace: cluster_id, name: project_id } = target;
+
     let patch_array = match patch_value {
         Value::Array(arr) => arr,
         Value::Null => {
@@ -583,12 +621,12 @@ pub async fn update_project(
 
     let api_result = patch_management_cattle_io_v3_namespaced_project(
         configuration,
-        project_id,
-        cluster_id,
+        target.name,
+        target.namespace,
         Some(k8s_patch),
         None,
-        None,
-        Some(crate::FULL_CLIENT_ID),
+        dry_run_param(dry_run),
+        Some(field_manager()),
         None,
         None,
     )
@@ -627,7 +665,7 @@ pub async fn update_project(
                         StatusCode::UNPROCESSABLE_ENTITY => {
                             format!(
                                 "Unprocessable entity when updating project with ID: {} in cluster: {}. Response: {:#?}",
-                                project_id, cluster_id, response_content.content
+                                project_id, cluster_id, bounded_body(&response_content.content)
                             )
                         }
                         StatusCode::NOT_FOUND => {
@@ -645,18 +683,18 @@ pub async fn update_project(
                         StatusCode::BAD_REQUEST => {
                             format!(
                                 "Bad request when updating project with ID: {} in cluster: {}. Request body was: {}",
-                                project_id, cluster_id, response_content.content
+                                project_id, cluster_id, bounded_body(&response_content.content)
                             )
                         }
                         _ => {
                             format!(
                                 "Failed to patch project with ID: {} in cluster: {}. Response: {:#?}",
-                                project_id, cluster_id, response_content
+                                project_id, cluster_id, BoundedResponse(&response_content)
                             )
                         }
                     };
                     error!(msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(status_error(response_content.status, Access::Write, ObjectType::Project, Some(target.namespace), msg))
                 }
                 _ => {
                     let msg = format!(
@@ -671,11 +709,116 @@ pub async fn update_project(
     }
 }
 
+/// Replace a project with the given body (PUT)
+/// # Arguments
+/// * `configuration` - The configuration to use for the request
+/// * `target` - The project to replace; `namespace` is the cluster ID and `name` the project ID
+/// * `body` - The full project, carrying the current `metadata.resourceVersion`
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
+/// # Returns
+/// * `IoCattleManagementv3Project` - The replaced project
+/// # Errors
+/// * `anyhow::Error` - The error that occurred while trying to replace the project
+///
+#[async_backtrace::framed]
+pub async fn replace_project(
+    configuration: &Configuration,
+    target: UpdateTarget<'_>,
+    body: IoCattleManagementv3Project,
+    dry_run: bool,
+) -> Result<IoCattleManagementv3Project> {
+    let UpdateTarget { namespace: cluster_id, name: project_id } = target;
+
+    let api_result = replace_management_cattle_io_v3_namespaced_project(
+        configuration,
+        target.name,
+        target.namespace,
+        body,
+        None,
+        dry_run_param(dry_run),
+        Some(field_manager()),
+        None,
+    )
+    .await;
+
+    trace!(api_result = ?api_result, "Received API response");
+
+    match api_result {
+        Ok(response_content) => match response_content.status {
+            StatusCode::OK | StatusCode::CREATED => {
+                info!(
+                    "Successfully replaced project with ID: {} in cluster: {}",
+                    project_id, cluster_id
+                );
+                match serde_json::from_str(&response_content.content) {
+                    Ok(data) => Ok(data),
+                    Err(deserialize_err) => {
+                        error!("Failed to deserialize response: {}", deserialize_err);
+                        Err(anyhow::anyhow!(deserialize_err))
+                    }
+                }
+            }
+            _ => {
+                let msg = format!(
+                        "Received unexpected status code: {} when replacing project with ID: {} in cluster: {}",
+                        response_content.status, project_id, cluster_id
+                    );
+                error!(msg);
+                Err(anyhow::anyhow!(msg))
+            }
+        },
+        Err(e) => {
+            match e {
+                Error::ResponseError(response_content) => {
+                    let msg = match response_content.status {
+                        StatusCode::CONFLICT => {
+                            format!(
+                                "Conflict when replacing project with ID: {} in cluster: {}, it changed since it was read. Response: {}",
+                                project_id, cluster_id, bounded_body(&response_content.content)
+                            )
+                        }
+                        StatusCode::NOT_FOUND => {
+                            format!(
+                                "Project with ID: {} in cluster: {} not found",
+                                project_id, cluster_id
+                            )
+                        }
+                        StatusCode::UNAUTHORIZED => {
+                            format!(
+                                "Unauthorized access while trying to replace project with ID: {} in cluster: {}",
+                                project_id, cluster_id
+                            )
+                        }
+                        _ => {
+                            format!(
+                                "Failed to replace project with ID: {} in cluster: {}. Response: {:#?}",
+                                project_id, cluster_id, BoundedResponse(&response_content)
+                            )
+                        }
+                    };
+                    error!(msg);
+                    Err(status_error(response_content.status, Access::Write, ObjectType::Project, Some(target.namespace), msg))
+                }
+                _ => {
+                    let msg = format!(
+                        "Failed to replace project with ID: {} in cluster: {}. Error: {:#?}",
+                        project_id, cluster_id, e
+                    );
+                    error!(msg);
+                    Err(anyhow::anyhow!(msg))
+                }
+            }
+        }
+    }
+}
+
 /// Delete a project by its ID  
 /// # Arguments  
 /// * `configuration` - The configuration to use for the request  
 /// * `cluster_id` - The ID of the cluster (namespace) containing the project  
 /// * `project_id` - The ID of the project to delete  
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
+/// * `params` - The propagation policy and grace period of the deletion
 /// # Returns  
 /// * `IoCattleManagementv3Project` - The deleted project  
 /// # Errors  
@@ -685,6 +828,8 @@ pub async fn delete_project(
     configuration: &Configuration,
     cluster_id: &str,
     project_id: &str,
+    dry_run: bool,
+    params: DeleteParams,
 ) -> Result<Result<IoCattleManagementv3Project, IoK8sApimachineryPkgApisMetaV1Status>> {
     // info!( "Deleting project with ID: {} in cluster: {}", project_id, cluster_id );
     let api_result = delete_management_cattle_io_v3_namespaced_project(
@@ -692,10 +837,10 @@ pub async fn delete_project(
         project_id,
         cluster_id,
         None, // pretty
-        None, // dry_run
-        None, // grace_period_seconds
+        dry_run_param(dry_run),
+        params.grace_period_seconds,
         None, // orphan_dependents
-        None, // propagation_policy
+        params.propagation_param(),
         None, // body
     )
     .await;
@@ -704,7 +849,7 @@ pub async fn delete_project(
 
     match api_result {
         Ok(response_content) => {
-            trace!("Response: {}", response_content.content);
+            trace!("Response: {}", bounded_body(&response_content.content));
             match response_content.status {
                 StatusCode::OK => {
                     info!("Successfully deleted project with ID: {}", project_id);
@@ -745,11 +890,11 @@ pub async fn delete_project(
                     let msg = match response_content.status {
                         StatusCode::NOT_FOUND => format!( "Project with ID: {} in cluster {} not found", project_id, cluster_id ),
                         StatusCode::UNAUTHORIZED => format!( "Unauthorized access while trying to delete project with ID: {} in cluster {}", project_id, cluster_id ),
-                        StatusCode::BAD_REQUEST => format!( "Bad request when deleting project with ID: {} in cluster {}. Request body was: {}", project_id, cluster_id, response_content.content ),
-                        _ => format!( "Failed to delete project with ID: {} in cluster {}. Response: {:#?}", project_id, cluster_id, response_content ),
+                        StatusCode::BAD_REQUEST => format!( "Bad request when deleting project with ID: {} in cluster {}. Request body was: {}", project_id, cluster_id, bounded_body(&response_content.content) ),
+                        _ => format!( "Failed to delete project with ID: {} in cluster {}. Response: {:#?}", project_id, cluster_id, BoundedResponse(&response_content) ),
                     };
                     error!("{}", msg);
-                    Err(anyhow::anyhow!("{}", msg))
+                    Err(status_error(response_content.status, Access::Write, ObjectType::Project, Some(cluster_id), msg))
                 }
                 _ => {
                     let msg = format!(
@@ -757,7 +902,7 @@ pub async fn delete_project(
                         project_id, cluster_id, e
                     );
                     error!(msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(api_error(&e, msg))
                 }
             }
         }
@@ -784,10 +929,8 @@ pub async fn load_project(
     file_format: FileFormat,
 ) -> Result<Project, Box<dyn std::error::Error>> {
     // create the path to the project
-    let project_path = base_path
-        .join(endpoint_url.replace("https://", "").replace("/", "_"))
-        .join(cluster_id)
-        .join(project_name);
+    let endpoint_path = endpoint_folder(base_path, endpoint_url);
+    let project_path = cluster_folder(&endpoint_path, cluster_id, &file_format).join(project_name);
     // check if the path exists
 
     metadata(&project_path)
@@ -817,10 +960,11 @@ pub async fn load_project(
         .await
         .map_err(|e| format!("Failed to read file {:?}: {}", project_file, e))?;
 
-    Ok(deserialize_object(&content, &file_format)?)
+    Ok(deserialize_object(&content, &file_format, Some(&project_file))?)
 }
 
 #[derive(Serialize, Deserialize, SerdeDiff, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Project {
     /// Name of the Kubernetes cluster this project belongs to.
     pub cluster_name: String,
@@ -843,6 +987,7 @@ pub struct Project {
 
     /// Default container resource limits applied within the project namespaces.
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<serde_json::Map<String, serde_json::Value>>"))]
     pub container_default_resource_limit:
         Option<IoCattleManagementv3ProjectSpecContainerDefaultResourceLimit>,
 
@@ -866,15 +1011,18 @@ pub struct Project {
 
     /// Default resource quotas applied at the namespace level.
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<serde_json::Map<String, serde_json::Value>>"))]
     pub namespace_default_resource_quota:
         Option<IoCattleManagementv3ProjectSpecNamespaceDefaultResourceQuota>,
 
     /// Resource quota limits applied at the project level.
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<serde_json::Map<String, serde_json::Value>>"))]
     pub resource_quota: Option<IoCattleManagementv3ProjectSpecResourceQuotaLimit>,
 }
 
 impl Project {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         annotations: Option<std::collections::HashMap<String, String>>,
         cluster_name: String,
@@ -1129,6 +1277,131 @@ pub fn show_project_diff(project_a: &Project, project_b: &IoCattleManagementv3Pr
     );
 }
 
+/// How project files are matched to the projects in Rancher.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProjectMatch {
+    /// By project ID only
+    #[default]
+    Id,
+    /// By ID, then by display name for projects whose ID is gone from Rancher
+    DisplayName,
+}
+
+impl std::fmt::Display for ProjectMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProjectMatch::Id => write!(f, "id"),
+            ProjectMatch::DisplayName => write!(f, "display-name"),
+        }
+    }
+}
+
+/// Pair the stored projects whose ID is gone from Rancher with the project Rancher recreated
+/// them as, one with the same display name and an ID no file has.
+///
+/// A display name shared by more than one such stored or live project is ambiguous, those
+/// projects are left alone with a warning.
+///
+/// # Returns
+/// The old and the new ID of every recreated project
+pub fn match_regenerated_projects(stored: &[Project], live: &[Project]) -> Vec<(String, String)> {
+    let stored_ids: HashSet<&str> = stored.iter().filter_map(|project| project.id.as_deref()).collect();
+    let live_ids: HashSet<&str> = live.iter().filter_map(|project| project.id.as_deref()).collect();
+    let gone: Vec<(&str, &str)> = stored
+        .iter()
+        .filter_map(|project| Some((project.id.as_deref().filter(|id| !id.is_empty())?, project.display_name.as_str())))
+        .filter(|(id, _)| !live_ids.contains(id))
+        .collect();
+    let fresh: Vec<(&str, &str)> = live
+        .iter()
+        .filter_map(|project| Some((project.id.as_deref()?, project.display_name.as_str())))
+        .filter(|(id, _)| !stored_ids.contains(id))
+        .collect();
+
+    let mut matches = Vec::new();
+    for (old_id, display_name) in &gone {
+        let namesakes = gone.iter().filter(|(_, name)| name == display_name).count();
+        let candidates: Vec<&str> = fresh
+            .iter()
+            .filter(|(_, name)| name == display_name)
+            .map(|(id, _)| *id)
+            .collect();
+        match (namesakes, candidates.as_slice()) {
+            (_, []) => {}
+            (1, [new_id]) => {
+                info!("Project `{}` was recreated as `{}` with the display name `{}`", old_id, new_id, display_name);
+                matches.push((old_id.to_string(), new_id.to_string()));
+            }
+            _ => warn!(
+                "Not matching project `{}` by its display name `{}`, {} stored and {} live projects share it",
+                old_id,
+                display_name,
+                namesakes,
+                candidates.len()
+            ),
+        }
+    }
+    matches
+}
+
+/// Point the project file at `project_file` and the bindings next to it to the project `new_id`.
+///
+/// The project file is renamed after the new ID and its folder, when named after the old ID,
+/// moved. The `id` of the project and the `namespace` and `project_name` of its bindings are
+/// rewritten, the server-set fields of the old project are dropped.
+///
+/// # Returns
+/// The old and the new folder of the project, to commit
+///
+/// # Errors
+/// Returns an error if a file cannot be read, parsed or written, the new folder exists, or
+/// `new_id` leads outside of the cluster folder, an [`crate::error::UnsafePathError`]
+pub async fn move_project(project_file: &Path, new_id: &str, file_format: &FileFormat) -> Result<Vec<PathBuf>> {
+    let mut project: Project = crate::load_object(project_file).await?;
+    let old_id = project.id.clone().unwrap_or_default();
+    let folder = project_file.parent().context("Project file has no folder")?;
+    let new_folder = match folder.file_name() {
+        Some(name) if name == old_id.as_str() => folder.with_file_name(new_id),
+        _ => folder.to_path_buf(),
+    };
+    if new_folder != folder && new_folder.exists() {
+        bail!("Cannot move project `{}` to {:?}, the folder exists", old_id, new_folder);
+    }
+    let renamed = folder.join(get_file_name_for_object(new_id, &ObjectType::Project, file_format));
+    let cluster_folder = folder.parent().context("Project folder has no cluster folder")?;
+    ensure_within(cluster_folder, &new_folder)?;
+    ensure_within(cluster_folder, &renamed)?;
+
+    project.id = Some(new_id.to_string());
+    project.resource_version = None;
+    project.uid = None;
+    remove_file(project_file).await?;
+    write(&renamed, serialize_object(&project, file_format)?).await?;
+
+    let mut entries = read_dir(folder).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let is_binding = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.contains(".prtb."));
+        if !is_binding {
+            continue;
+        }
+        let mut prtb: ProjectRoleTemplateBinding = crate::load_object(&path).await?;
+        prtb.namespace = new_id.to_string();
+        prtb.project_name = format!("{}:{}", project.cluster_name, new_id);
+        write(&path, serialize_object(&prtb, file_format)?).await?;
+    }
+
+    if new_folder != folder {
+        rename(folder, &new_folder).await?;
+    }
+    info!("Moved project `{}` to `{}` in {:?}", old_id, new_id, new_folder);
+    Ok(vec![folder.to_path_buf(), new_folder])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1194,4 +1467,216 @@ mod tests {
         assert_ne!(rancher_project, project);
         assert_ne!(project, rancher_project);
     }
+
+    #[tokio::test]
+    async fn test_update_patches_namespace_then_project_name() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/cluster-1/projects/proj-1"))
+            .and(query_param("fieldManager", field_manager()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(sample_iocattle_project()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut config = Configuration::new();
+        config.base_path = server.uri();
+
+        let patch = serde_json::json!([
+            { "op": "replace", "path": "/spec/description", "value": "Test project" }
+        ]);
+        let result = sample_project().update(&config, patch, false).await;
+
+        assert!(matches!(result, Ok(CreatedObject::Project(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_create_dry_run_sends_dry_run_all() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/cluster-1/projects"))
+            .and(query_param("dryRun", "All"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(sample_iocattle_project()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut config = Configuration::new();
+        config.base_path = server.uri();
+
+        let result = sample_project().create(&config, true).await;
+
+        assert!(matches!(result, Ok(CreatedObject::Project(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_forbidden_create_is_a_permission_error() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/cluster-1/projects"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(serde_json::json!({ "reason": "Forbidden" })))
+            .mount(&server)
+            .await;
+
+        let mut config = Configuration::new();
+        config.base_path = server.uri();
+
+        let error = sample_project().create(&config, false).await.unwrap_err();
+
+        let refused = crate::error::permission_error(&error).expect("a permission error");
+        assert_eq!(
+            (refused.status, refused.access, refused.object_type, refused.namespace.as_deref()),
+            (403, Access::Write, ObjectType::Project, Some("cluster-1"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_without_metadata_is_an_error() {
+        let config = Configuration::new();
+        let body = IoCattleManagementv3Project {
+            metadata: None,
+            ..sample_iocattle_project()
+        };
+
+        let result = create_project(&config, "cluster-1", body, false).await;
+
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ConversionError>(),
+            Some(&ConversionError::MissingField("metadata".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_with_generate_name_only() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/cluster-1/projects"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(sample_iocattle_project()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut config = Configuration::new();
+        config.base_path = server.uri();
+        let mut body = sample_iocattle_project();
+        let metadata = body.metadata.as_mut().unwrap();
+        metadata.name = None;
+        metadata.generate_name = Some("p-".to_string());
+
+        let result = create_project(&config, "cluster-1", body, false).await;
+
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    fn named_project(id: &str, display_name: &str) -> Project {
+        Project {
+            id: Some(id.to_string()),
+            display_name: display_name.to_string(),
+            ..sample_project()
+        }
+    }
+
+    #[test]
+    fn test_match_regenerated_projects_pairs_unique_display_names() {
+        let stored = [
+            named_project("p-kept", "kept"),
+            named_project("p-old", "recreated"),
+            named_project("p-gone", "deleted"),
+        ];
+        let live = [named_project("p-kept", "kept"), named_project("p-new", "recreated")];
+
+        assert_eq!(
+            match_regenerated_projects(&stored, &live),
+            [("p-old".to_string(), "p-new".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_match_regenerated_projects_skips_ambiguous_display_names() {
+        // two stored projects lost their ID under the same name
+        let stored = [named_project("p-old-1", "shared"), named_project("p-old-2", "shared")];
+        let live = [named_project("p-new", "shared")];
+        assert!(match_regenerated_projects(&stored, &live).is_empty());
+
+        // two live projects could be the recreated one
+        let stored = [named_project("p-old", "shared")];
+        let live = [named_project("p-new-1", "shared"), named_project("p-new-2", "shared")];
+        assert!(match_regenerated_projects(&stored, &live).is_empty());
+
+        // a live project already stored under its ID is no candidate
+        let stored = [named_project("p-old", "shared"), named_project("p-kept", "shared")];
+        let live = [named_project("p-kept", "shared"), named_project("p-new", "shared")];
+        assert_eq!(
+            match_regenerated_projects(&stored, &live),
+            [("p-old".to_string(), "p-new".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_move_project_renames_folder_and_rewrites_bindings() {
+        let dir = tempfile::tempdir().unwrap();
+        let old_folder = dir.path().join("cluster-1/p-old");
+        std::fs::create_dir_all(&old_folder).unwrap();
+        let project_file = old_folder.join("p-old.project.yaml");
+        std::fs::write(&project_file, serde_yaml::to_string(&named_project("p-old", "team")).unwrap()).unwrap();
+        std::fs::write(
+            old_folder.join("member.prtb.yaml"),
+            "id: member\nnamespace: p-old\nproject_name: cluster-1:p-old\nrole_template_name: project-member\nuser_name: u-abc\n",
+        )
+        .unwrap();
+
+        let moved = move_project(&project_file, "p-new", &FileFormat::Yaml).await.unwrap();
+
+        let new_folder = dir.path().join("cluster-1/p-new");
+        assert_eq!(moved, [old_folder.clone(), new_folder.clone()]);
+        assert!(!old_folder.exists());
+        let project: Project = crate::load_object(&new_folder.join("p-new.project.yaml")).await.unwrap();
+        assert_eq!(project.id.as_deref(), Some("p-new"));
+        assert_eq!(project.display_name, "team");
+        assert!(project.resource_version.is_none() && project.uid.is_none());
+        let prtb: ProjectRoleTemplateBinding = crate::load_object(&new_folder.join("member.prtb.yaml")).await.unwrap();
+        assert_eq!(prtb.namespace, "p-new");
+        assert_eq!(prtb.project_name, "cluster-1:p-new");
+    }
+
+    #[tokio::test]
+    async fn test_move_project_refuses_an_existing_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let old_folder = dir.path().join("cluster-1/p-old");
+        std::fs::create_dir_all(&old_folder).unwrap();
+        std::fs::create_dir_all(dir.path().join("cluster-1/p-new")).unwrap();
+        let project_file = old_folder.join("p-old.project.yaml");
+        std::fs::write(&project_file, serde_yaml::to_string(&named_project("p-old", "team")).unwrap()).unwrap();
+
+        assert!(move_project(&project_file, "p-new", &FileFormat::Yaml).await.is_err());
+        assert!(project_file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_move_project_refuses_an_id_leading_out_of_the_cluster_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let old_folder = dir.path().join("cluster-1/p-old");
+        std::fs::create_dir_all(&old_folder).unwrap();
+        let project_file = old_folder.join("p-old.project.yaml");
+        std::fs::write(&project_file, serde_yaml::to_string(&named_project("p-old", "team")).unwrap()).unwrap();
+
+        let error = move_project(&project_file, "../../etc/passwd", &FileFormat::Yaml).await.unwrap_err();
+
+        assert!(crate::error::is_unsafe_path(&error), "{:#}", error);
+        assert!(project_file.exists());
+        assert!(!dir.path().join("etc").exists());
+    }
 }
@@ -1,7 +1,10 @@
 
 use serde::{Deserialize, Serialize};
 
-use crate::{models::{CreatedObject, ObjectType, ResourceVersionMatch}, traits::RancherResource, utils::logging::log_api_error};
+use crate::api::client::{dry_run_param, with_server_version};
+use crate::api::client_info::field_manager;
+use crate::error::{api_error, bounded_body, status_error, Access, BoundedResponse, ForbiddenError};
+use crate::{models::{ConversionError, CreatedObject, DeleteParams, ObjectType, ResourceVersionMatch, UpdateTarget}, traits::{Metadata, RancherResource}, utils::logging::log_api_error, utils::principal::PrincipalRules};
 use anyhow::Result;
 
 use reqwest::StatusCode;
@@ -14,7 +17,9 @@ use rancher_client::{
             delete_management_cattle_io_v3_namespaced_project_role_template_binding,
             list_management_cattle_io_v3_namespaced_project_role_template_binding,
             list_management_cattle_io_v3_project_role_template_binding_for_all_namespaces,
-            patch_management_cattle_io_v3_namespaced_project_role_template_binding
+            patch_management_cattle_io_v3_namespaced_project_role_template_binding,
+            read_management_cattle_io_v3_namespaced_project_role_template_binding,
+            replace_management_cattle_io_v3_namespaced_project_role_template_binding
         },
         Error,
     },
@@ -25,7 +30,7 @@ use rancher_client::{
     },
 };
 use serde_json::Value;
-use tracing::{debug, error, info, trace};
+use tracing::{debug, error, info, trace, warn};
 
 pub const PRTB_EXCLUDE_PATHS: &[&str] = &[
     "metadata.creationTimestamp",
@@ -33,9 +38,11 @@ pub const PRTB_EXCLUDE_PATHS: &[&str] = &[
     "metadata.generateName",
     "metadata.generation",
     "metadata.managedFields",
+    "metadata.ownerReferences",
     "metadata.resourceVersion",
     "metadata.selfLink",
     "metadata.uid",
+    "status",
 ];
 
 
@@ -59,23 +66,24 @@ impl RancherResource for ProjectRoleTemplateBinding {
         ProjectRoleTemplateBinding::try_from(binding.clone())
     }
 
-    async fn create(&self, config: &Configuration) -> Result<CreatedObject> {
+    async fn create(&self, config: &Configuration, dry_run: bool) -> Result<CreatedObject> {
         let prtb_api = self.clone().try_into_api()?;
 
         let ns = self.namespace().ok_or_else(|| anyhow::anyhow!("Namespace is required for creating project role template bindings"))?;
 
-        let result = create_project_role_template_binding(config, &ns, prtb_api).await?;
+        let result = create_project_role_template_binding(config, &ns, prtb_api, dry_run).await?;
         Ok(CreatedObject::ProjectRoleTemplateBinding(result))
         
     }
 
-    async fn update(&self, config: &Configuration, patch: Value) -> Result<CreatedObject> {
-        let result = update_project_role_template_binding(config, &self.namespace, &self.id, patch).await?;
+    async fn update(&self, config: &Configuration, patch: Value, dry_run: bool) -> Result<CreatedObject> {
+        let target = UpdateTarget { namespace: &self.namespace, name: &self.id };
+        let result = update_project_role_template_binding(config, target, patch, dry_run).await?;
         Ok(CreatedObject::ProjectRoleTemplateBinding(result))
     }
 
-    async fn delete(config: &Configuration, name: &str, namespace: &str) -> Result<CreatedObject> {
-        let result = delete_project_role_template_binding(config, namespace, name).await?;
+    async fn delete(config: &Configuration, name: &str, namespace: &str, dry_run: bool, params: DeleteParams) -> Result<CreatedObject> {
+        let result = delete_project_role_template_binding(config, namespace, name, dry_run, params).await?;
         Ok(CreatedObject::Status(result))
     }
     
@@ -86,13 +94,17 @@ impl RancherResource for ProjectRoleTemplateBinding {
     fn exclude_paths() -> &'static [&'static str] {
         PRTB_EXCLUDE_PATHS
     }
+
+    fn file_exclude_paths() -> &'static [&'static str] {
+        PRTB_EXCLUDE_PATHS
+    }
     
     fn try_from_api(value: Self::ApiType) -> Result<Self> {
         ProjectRoleTemplateBinding::try_from(value)
     }
     
     fn try_into_api(self) -> Result<Self::ApiType> {
-        Ok(IoCattleManagementv3ProjectRoleTemplateBinding::try_from(self)?)
+        IoCattleManagementv3ProjectRoleTemplateBinding::try_from(self)
     }
     
     fn id(&self) -> Option<String> {
@@ -106,6 +118,10 @@ impl RancherResource for ProjectRoleTemplateBinding {
     fn resource_version(&self) -> Option<String> {
         self.resource_version.clone()
     }
+
+    fn metadata_mut(&mut self) -> (&mut Metadata, &mut Metadata) {
+        (&mut self.annotations, &mut self.labels)
+    }
 }
 
 
@@ -117,6 +133,7 @@ impl RancherResource for ProjectRoleTemplateBinding {
 /// * `configuration` - The configuration to use for the request
 /// * `project_id` - The cluster ID
 /// * `body` - The project role template binding to create
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
 /// # Returns
 ///
 /// * `IoCattleManagementv3ProjectRoleTemplateBinding` - The created project role template binding
@@ -129,8 +146,15 @@ pub async fn create_project_role_template_binding(
     configuration: &Configuration,
     project_id: &str,
     body: IoCattleManagementv3ProjectRoleTemplateBinding,
+    dry_run: bool,
 ) -> Result<IoCattleManagementv3ProjectRoleTemplateBinding> {
-    let prtb_id = body.metadata.as_ref().unwrap().name.clone().unwrap_or_default();
+    let prtb_id = body
+        .metadata
+        .as_ref()
+        .ok_or(ConversionError::MissingField("metadata".into()))?
+        .name
+        .clone()
+        .unwrap_or_default();
     
     // info!("Creating project role template binding with ID: {} for project: {}", prtb_id, project_id);
 
@@ -139,8 +163,8 @@ pub async fn create_project_role_template_binding(
         project_id,
         body,
         None,
-        None,
-        Some(crate::FULL_CLIENT_ID),
+        dry_run_param(dry_run),
+        Some(field_manager()),
         None,
     )
     .await;
@@ -174,7 +198,7 @@ pub async fn create_project_role_template_binding(
                                 "Unexpected status code {} when creating project role template binding for project: {}: {}", 
                                 status, 
                                 project_id,
-                                serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone())
+                                bounded_body(&serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone()))
                             )
                         }
                         Err(_) => {
@@ -182,7 +206,7 @@ pub async fn create_project_role_template_binding(
                                 "Unexpected status code {} when creating project role template binding for project: {}: {}", 
                                 status, 
                                 project_id,
-                                response_content.content
+                                bounded_body(&response_content.content)
                             )
                         }
                     };
@@ -197,15 +221,15 @@ pub async fn create_project_role_template_binding(
                     let msg = match response_content.status {
                         StatusCode::NOT_FOUND => format!("Cluster with ID: {} not found", project_id) ,
                         StatusCode::UNAUTHORIZED => format!( "Unauthorized access while trying to create project role template binding with ID: {} in cluster: {}", prtb_id, project_id ) ,
-                        StatusCode::BAD_REQUEST => format!( "Bad request when creating project role template binding with ID: {} in cluster: {}. Request body was: {}", prtb_id, project_id, response_content.content ) ,
+                        StatusCode::BAD_REQUEST => format!( "Bad request when creating project role template binding with ID: {} in cluster: {}. Request body was: {}", prtb_id, project_id, bounded_body(&response_content.content) ) ,
                         StatusCode::FORBIDDEN => format!( "Forbidden while trying to create project role template binding with ID: {} in cluster: {}", prtb_id, project_id ) ,
                         StatusCode::CONFLICT => format!( "Project role template binding with ID: {} in cluster {} already exists", prtb_id, project_id ) ,
                         _ => {
-                            format!("Failed to create project role template binding with ID: {} in cluster {}. Response: {:#?}", prtb_id, project_id, response_content)
+                            format!("Failed to create project role template binding with ID: {} in cluster {}. Response: {:#?}", prtb_id, project_id, BoundedResponse(&response_content))
                         }
                     };
                     error!(msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(status_error(response_content.status, Access::Write, ObjectType::ProjectRoleTemplateBinding, Some(project_id), msg))
                 }
                 _ => {
                     let msg = format!("Failed to create project role template binding with ID: {} in cluster: {}. Error: {:#?}", prtb_id, project_id, e);
@@ -218,6 +242,120 @@ pub async fn create_project_role_template_binding(
 }
 
 
+/// How a download lists the project role template bindings of an endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PrtbListing {
+    /// One list call per project
+    #[default]
+    PerProject,
+    /// A few paginated list calls across all namespaces, grouped by project. Falls back to
+    /// listing per project when the account may not list them across all namespaces
+    AllNamespaces,
+}
+
+impl std::fmt::Display for PrtbListing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrtbListing::PerProject => write!(f, "per-project"),
+            PrtbListing::AllNamespaces => write!(f, "all-namespaces"),
+        }
+    }
+}
+
+/// Find a project role template binding by its ID
+///
+/// # Arguments
+///
+/// * `configuration` - The configuration to use for the request
+/// * `project_id` - The ID of the project (namespace) holding the binding
+/// * `prtb_id` - The ID of the binding to get
+/// * `resource_version` - The resource version to use for the request
+/// # Returns
+///
+/// * `IoCattleManagementv3ProjectRoleTemplateBinding` - The project role template binding
+/// # Errors
+///
+/// * `anyhow::Error` - The error that occurred while trying to get the project role template binding
+///
+#[async_backtrace::framed]
+pub async fn find_project_role_template_binding(
+    configuration: &Configuration,
+    project_id: &str,
+    prtb_id: &str,
+    resource_version: Option<&str>,
+) -> Result<IoCattleManagementv3ProjectRoleTemplateBinding> {
+    let api_result = read_management_cattle_io_v3_namespaced_project_role_template_binding(
+        configuration,
+        prtb_id,
+        project_id,
+        None,
+        resource_version,
+    )
+    .await;
+
+    trace!(api_result = ?api_result, "Received API response");
+
+    match api_result {
+        Ok(response_content) => match response_content.status {
+            StatusCode::OK => {
+                match serde_json::from_str::<IoCattleManagementv3ProjectRoleTemplateBinding>(&response_content.content) {
+                    Ok(data) => {
+                        info!("Successfully found project role template binding with ID: {}", prtb_id);
+                        Ok(data)
+                    }
+                    Err(deserialize_err) => {
+                        let err = anyhow::anyhow!(
+                            "Failed to deserialize project role template binding response: {}",
+                            deserialize_err
+                        );
+                        log_api_error("find_project_role_template_binding:deserialize", &err);
+                        Err(err)
+                    }
+                }
+            }
+            status => {
+                let err = anyhow::anyhow!(
+                    "Unexpected status code {} when finding project role template binding with ID: {}: {}",
+                    status,
+                    prtb_id,
+                    bounded_body(&response_content.content)
+                );
+                log_api_error("find_project_role_template_binding:unexpected_status", &err);
+                Err(err)
+            }
+        },
+        Err(e) => match e {
+            Error::ResponseError(response_content) => {
+                let msg = match response_content.status {
+                    StatusCode::NOT_FOUND => format!(
+                        "Project role template binding with ID: {} not found in project: {}",
+                        prtb_id, project_id
+                    ),
+                    StatusCode::FORBIDDEN => format!(
+                        "Forbidden access while trying to get project role template binding with ID: {} in project: {}",
+                        prtb_id, project_id
+                    ),
+                    _ => format!(
+                        "Failed to get project role template binding with ID: {} in project: {}. Response: {:#?}",
+                        prtb_id, project_id, BoundedResponse(&response_content)
+                    ),
+                };
+                debug!("{}", msg);
+                Err(status_error(response_content.status, Access::Read, ObjectType::ProjectRoleTemplateBinding, Some(project_id), msg))
+            }
+            _ => {
+                let msg = format!(
+                    "Failed to get project role template binding with ID: {} in project: {}. Error: {:#?}",
+                    prtb_id, project_id, e
+                );
+                error!("{}", msg);
+                Err(api_error(&e, msg))
+            }
+        },
+    }
+}
+
 /// Get all project role template bindings for all projects on an endpoint
 ///
 /// # Arguments
@@ -255,11 +393,11 @@ pub async fn get_all_project_role_template_bindings(
         field_selector,
         label_selector,
         limit,
+        None,
         resource_version,
         resource_version_match.as_ref().map(|rvm| rvm.as_str()),
         None,
         None,
-        None,
         None
     )
     .await;
@@ -278,7 +416,7 @@ pub async fn get_all_project_role_template_bindings(
                             Ok(data)
                         },
                                     Err(deserialize_err) => {
-                            let err = anyhow::anyhow!("Failed to deserialize project role template bindings response: {}", deserialize_err);
+                            let err = anyhow::anyhow!(with_server_version(configuration, format!("Failed to deserialize project role template bindings response: {}", deserialize_err)));
                             log_api_error("get_all_project_role_template_bindings:deserialize", &err);
                             Err(err)
                         }
@@ -290,14 +428,14 @@ pub async fn get_all_project_role_template_bindings(
                             anyhow::anyhow!(
                                 "Unexpected status code {} when getting all project role template bindings: {}", 
                                 status, 
-                                serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone())
+                                bounded_body(&serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone()))
                             )
                         }
                         Err(_) => {
                             anyhow::anyhow!(
                                 "Unexpected status code {} when getting all project role template bindings: {}", 
                                 status, 
-                                response_content.content
+                                bounded_body(&response_content.content)
                             )
                         }
                     };
@@ -310,10 +448,15 @@ pub async fn get_all_project_role_template_bindings(
             match e {
                 Error::ResponseError(response_content) => {
                     let msg = match response_content.status {
-                        StatusCode::NOT_FOUND => format!("Project role template bindings not found. Response: {}", response_content.content),
-                        StatusCode::UNAUTHORIZED => format!("Unauthorized to get all project role template bindings. Response: {}", response_content.content),
-                        StatusCode::FORBIDDEN => format!("Forbidden to get all project role template bindings. Response: {}", response_content.content),
-                        _ => format!("Failed to get all project role template bindings. Response: {:#?}", response_content),
+                        StatusCode::NOT_FOUND => format!("Project role template bindings not found. Response: {}", bounded_body(&response_content.content)),
+                        StatusCode::UNAUTHORIZED => format!("Unauthorized to get all project role template bindings. Response: {}", bounded_body(&response_content.content)),
+                        StatusCode::FORBIDDEN => {
+                            // callers fall back to listing the bindings per project
+                            let msg = format!("Forbidden to get all project role template bindings. Response: {}", bounded_body(&response_content.content));
+                            warn!("{}", msg);
+                            return Err(ForbiddenError(msg).into());
+                        }
+                        _ => format!("Failed to get all project role template bindings. Response: {:#?}", BoundedResponse(&response_content)),
                     };
                     error!("{}", msg);
                     Err(anyhow::anyhow!(msg))
@@ -321,7 +464,7 @@ pub async fn get_all_project_role_template_bindings(
                 _ => {
                     let msg = format!("Failed to get all project role template bindings. Error was: {:#?}", e);
                     error!("{}", msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(api_error(&e, msg))
                 }
             }
         }
@@ -349,6 +492,7 @@ pub async fn get_all_project_role_template_bindings(
     /// # Errors
     ///
     /// * `anyhow::Error` - The error that occurred while trying to get the role template bindings
+#[allow(clippy::too_many_arguments)]
 #[async_backtrace::framed]
 pub async fn get_project_role_template_bindings(
     configuration: &Configuration,
@@ -391,7 +535,7 @@ pub async fn get_project_role_template_bindings(
                             Ok(data)
                         },
                         Err(deserialize_err) => {
-                            let msg = format!("Failed to deserialize project role template bindings for cluster: {}. Response: {:#?}. Error: {:#?}", cluster_id, response_content, deserialize_err);
+                            let msg = with_server_version(configuration, format!("Failed to deserialize project role template bindings for cluster: {}. Response: {:#?}. Error: {:#?}", cluster_id, BoundedResponse(&response_content), deserialize_err));
                             error!("{}", msg);
                             Err(anyhow::anyhow!(msg))
                         }
@@ -404,7 +548,7 @@ pub async fn get_project_role_template_bindings(
                                 "Unexpected status code {} when getting project role template bindings for cluster: {}: {}", 
                                 status, 
                                 cluster_id,
-                                serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone())
+                                bounded_body(&serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone()))
                             )
                         }
                         Err(_) => {
@@ -412,7 +556,7 @@ pub async fn get_project_role_template_bindings(
                                 "Unexpected status code {} when getting project role template bindings for cluster: {}: {}", 
                                 status, 
                                 cluster_id,
-                                response_content.content
+                                bounded_body(&response_content.content)
                             )
                         }
                     };
@@ -427,9 +571,9 @@ pub async fn get_project_role_template_bindings(
                     let msg = match response_content.status {
                         StatusCode::NOT_FOUND => format!("Project role template bindings not found for cluster: {}", cluster_id),
                         StatusCode::UNAUTHORIZED => format!( "Unauthorized access while trying to get project role template bindings for cluster: {}", cluster_id ) ,
-                        StatusCode::BAD_REQUEST => format!( "Bad request while trying to get project role template bindings for cluster: {}. Request body was: {}", cluster_id, response_content.content ) ,
+                        StatusCode::BAD_REQUEST => format!( "Bad request while trying to get project role template bindings for cluster: {}. Request body was: {}", cluster_id, bounded_body(&response_content.content) ) ,
                         StatusCode::FORBIDDEN => format!( "Forbidden access while trying to get project role template bindings for cluster: {}", cluster_id ) ,
-                        _ => format!("Failed to get project role template bindings for cluster: {}. Response: {:#?}", cluster_id, response_content) ,
+                        _ => format!("Failed to get project role template bindings for cluster: {}. Response: {:#?}", cluster_id, BoundedResponse(&response_content)) ,
                     };
                     error!("{}", msg);
                     Err(anyhow::anyhow!(msg))
@@ -437,7 +581,7 @@ pub async fn get_project_role_template_bindings(
             _ => {
                 let msg = format!("Failed to get project role template bindings for cluster: {}. Error: {:#?}", cluster_id, e);
                 error!("{}", msg);
-                Err(anyhow::anyhow!(msg))
+                Err(api_error(&e, msg))
             }
         }
     }
@@ -465,6 +609,7 @@ pub async fn get_project_role_template_bindings(
 /// # Errors
 ///
 /// * `Error<ListManagementCattleIoV3NamespacedProjectRoleTemplateBindingError>` - The error that occurred while trying to get the bindings
+#[allow(clippy::too_many_arguments)]
 #[async_backtrace::framed]
 pub async fn get_namespaced_project_role_template_bindings(
     configuration: &Configuration,
@@ -510,7 +655,7 @@ pub async fn get_namespaced_project_role_template_bindings(
                         Ok(data)
                     }
                     Err(deserialize_err) => {
-                        let msg = format!("Failed to deserialize project role template bindings: {}", deserialize_err);
+                        let msg = with_server_version(configuration, format!("Failed to deserialize project role template bindings: {}", deserialize_err));
                         error!(msg);
                         Err(anyhow::anyhow!(msg))
                     }
@@ -521,7 +666,7 @@ pub async fn get_namespaced_project_role_template_bindings(
                     Ok(error_obj) => {
                         anyhow::anyhow!(
                             "Unexpected status code {} when getting project role template bindings: {}", 
-                            status, serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone()))
+                            status, bounded_body(&serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone())))
                     }
                     Err(deserialize_err) => {
                         anyhow::anyhow!("Failed to deserialize error object: {}", deserialize_err)
@@ -538,15 +683,15 @@ pub async fn get_namespaced_project_role_template_bindings(
                 let msg = match response_content.status {
                     StatusCode::NOT_FOUND => format!("Project with ID: {} not found", project_id) ,
                     StatusCode::FORBIDDEN => format!("Forbidden access while trying to get project role template bindings for project: {}", project_id) ,
-                    _ => format!("Failed to get project role template bindings for project: {}. Response: {:#?}", project_id, response_content), 
+                    _ => format!("Failed to get project role template bindings for project: {}. Response: {:#?}", project_id, BoundedResponse(&response_content)), 
                 };
                     error!("{}", msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(status_error(response_content.status, Access::Read, ObjectType::ProjectRoleTemplateBinding, Some(project_id), msg))
                 },            
                 _ => {
                     let msg = format!("Failed to get project role template bindings for project: {}. Error: {:#?}", project_id, e);
                     error!("{}", msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(api_error(&e, msg))
                 }
 
             }
@@ -559,9 +704,9 @@ pub async fn get_namespaced_project_role_template_bindings(
 /// # Arguments
 ///
 /// * `configuration` - The configuration to use for the request
-/// * `project_id` - The project ID
-/// * `prtb_id` - The project role template binding ID
+/// * `target` - The binding to patch; `namespace` is the project ID and `name` the binding ID
 /// * `patch_value` - The JSON patch to apply
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
 /// # Returns
 ///
 /// * `IoCattleManagementv3ProjectRoleTemplateBinding` - The updated project role template binding
@@ -572,10 +717,12 @@ pub async fn get_namespaced_project_role_template_bindings(
 #[async_backtrace::framed]
 pub async fn update_project_role_template_binding(
     configuration: &Configuration,
-    project_id: &str,
-    prtb_id: &str,
+    target: UpdateTarget<'_>,
     patch_value: Value,
+    dry_run: bool,
 ) -> Result<IoCattleManagementv3ProjectRoleTemplateBinding> {
+    let UpdateTarget { namespace: project_id, name: prtb_id } = target;
+
     // info!("Patching project role template binding with ID: {} in cluster: {}", prtb_id, cluster_id);
 
     let patch_array = match patch_value {
@@ -601,12 +748,12 @@ pub async fn update_project_role_template_binding(
 
     let api_result = patch_management_cattle_io_v3_namespaced_project_role_template_binding(
         configuration,
-        project_id,
-        prtb_id,
+        target.name,
+        target.namespace,
         Some(k8s_patch),
         None,
-        None,
-        None,
+        dry_run_param(dry_run),
+        Some(field_manager()),
         None,
         None
     )
@@ -642,7 +789,7 @@ pub async fn update_project_role_template_binding(
                                 "Unexpected status code {} when updating project role template binding with ID: {}: {}", 
                                 status, 
                                 prtb_id,
-                                serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone())
+                                bounded_body(&serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone()))
                             )
                         }
                         Err(_) => {
@@ -650,7 +797,7 @@ pub async fn update_project_role_template_binding(
                                 "Unexpected status code {} when updating project role template binding with ID: {}: {}", 
                                 status, 
                                 prtb_id,
-                                response_content.content
+                                bounded_body(&response_content.content)
                             )
                         }
                     };
@@ -678,19 +825,19 @@ pub async fn update_project_role_template_binding(
                         StatusCode::BAD_REQUEST => {
                             format!(
                                 "Bad request when updating project role template binding with ID: {} in project: {}. Request body was: {}",
-                                prtb_id, project_id, response_error.content
+                                prtb_id, project_id, bounded_body(&response_error.content)
                             )
                         }
                         _ => {
                             format!(
                                 "Failed to update project role template binding with ID: {} in project: {}. Response: {:#?}",
-                                prtb_id, project_id, response_error
+                                prtb_id, project_id, BoundedResponse(&response_error)
                             )
                         }
                         
                     };
                     error!(msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(status_error(response_error.status, Access::Write, ObjectType::ProjectRoleTemplateBinding, Some(target.namespace), msg))
                 }
             _ => {
                 let msg = format!("Failed to update project role template binding with ID: {} in project: {}. Error: {:#?}", prtb_id, project_id, e);
@@ -704,6 +851,113 @@ pub async fn update_project_role_template_binding(
 
 
 
+/// Replace a project role template binding with the given body (PUT)
+/// # Arguments
+///
+/// * `configuration` - The configuration to use for the request
+/// * `target` - The binding to replace; `namespace` is the project ID and `name` the binding ID
+/// * `body` - The full binding, carrying the current `metadata.resourceVersion`
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
+/// # Returns
+///
+/// * `IoCattleManagementv3ProjectRoleTemplateBinding` - The replaced project role template binding
+/// # Errors
+///
+/// * `anyhow::Error` - The error that occurred while trying to replace the project role template binding
+///
+#[async_backtrace::framed]
+pub async fn replace_project_role_template_binding(
+    configuration: &Configuration,
+    target: UpdateTarget<'_>,
+    body: IoCattleManagementv3ProjectRoleTemplateBinding,
+    dry_run: bool,
+) -> Result<IoCattleManagementv3ProjectRoleTemplateBinding> {
+    let UpdateTarget { namespace: project_id, name: prtb_id } = target;
+
+    let api_result = replace_management_cattle_io_v3_namespaced_project_role_template_binding(
+        configuration,
+        target.name,
+        target.namespace,
+        body,
+        None,
+        dry_run_param(dry_run),
+        Some(field_manager()),
+        None,
+    )
+    .await;
+
+    trace!(api_result = ?api_result, "Received API response");
+
+    match api_result {
+        Ok(response_content) => {
+            match response_content.status {
+                StatusCode::OK | StatusCode::CREATED => {
+                    match serde_json::from_str::<IoCattleManagementv3ProjectRoleTemplateBinding>(&response_content.content) {
+                        Ok(data) => {
+                            info!("Successfully replaced project role template binding with ID: {}", prtb_id);
+                            Ok(data)
+                        }
+                        Err(deserialize_err) => {
+                            let err = anyhow::anyhow!(
+                                "Failed to deserialize project role template binding replace response: {}",
+                                deserialize_err
+                            );
+                            log_api_error("replace_project_role_template_binding:deserialize", &err);
+                            Err(err)
+                        }
+                    }
+                }
+                status => {
+                    let err = anyhow::anyhow!(
+                        "Unexpected status code {} when replacing project role template binding with ID: {}: {}",
+                        status,
+                        prtb_id,
+                        bounded_body(&response_content.content)
+                    );
+                    log_api_error("replace_project_role_template_binding:unexpected_status", &err);
+                    Err(err)
+                }
+            }
+        }
+        Err(e) => {
+            match e {
+                Error::ResponseError(response_error) => {
+                    let msg = match response_error.status {
+                        StatusCode::NOT_FOUND => {
+                            format!(
+                                "Project role template binding with ID: {} in project: {} not found",
+                                prtb_id, project_id
+                            )
+                        }
+                        StatusCode::CONFLICT => {
+                            format!(
+                                "Conflict when replacing project role template binding with ID: {} in project: {}, it changed since it was read. Response: {}",
+                                prtb_id, project_id, bounded_body(&response_error.content)
+                            )
+                        }
+                        _ => {
+                            format!(
+                                "Failed to replace project role template binding with ID: {} in project: {}. Response: {:#?}",
+                                prtb_id, project_id, BoundedResponse(&response_error)
+                            )
+                        }
+                    };
+                    error!(msg);
+                    Err(status_error(response_error.status, Access::Write, ObjectType::ProjectRoleTemplateBinding, Some(target.namespace), msg))
+                }
+                _ => {
+                    let msg = format!(
+                        "Failed to replace project role template binding with ID: {} in project: {}. Error: {:#?}",
+                        prtb_id, project_id, e
+                    );
+                    error!(msg);
+                    Err(anyhow::anyhow!(msg))
+                }
+            }
+        }
+    }
+}
+
 /// Delete a project role template binding
 ///
 /// # Arguments
@@ -711,6 +965,8 @@ pub async fn update_project_role_template_binding(
 /// * `configuration` - The configuration to use for the request
 /// * `project_id` - The project ID
 /// * `prtb_id` - The project role template binding ID
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
+/// * `params` - The propagation policy and grace period of the deletion
 /// # Returns
 ///
 /// * `IoK8sApimachineryPkgApisMetaV1Status` - The status of the deletion
@@ -723,6 +979,8 @@ pub async fn delete_project_role_template_binding(
     configuration: &Configuration,
     project_id: &str,
     prtb_id: &str,
+    dry_run: bool,
+    params: DeleteParams,
 ) -> Result<IoK8sApimachineryPkgApisMetaV1Status> {
     // info!("Deleting project role template binding with ID: {} in project: {}", prtb_id, project_id);
 
@@ -731,10 +989,10 @@ pub async fn delete_project_role_template_binding(
         prtb_id,
         project_id,
         None,
+        dry_run_param(dry_run),
+        params.grace_period_seconds,
         None,
-        None,
-        None,
-        None,
+        params.propagation_param(),
         None,
     )
     .await;
@@ -770,7 +1028,7 @@ pub async fn delete_project_role_template_binding(
                                 "Unexpected status code {} when deleting project role template binding with ID: {}: {}", 
                                 status, 
                                 prtb_id,
-                                serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone())
+                                bounded_body(&serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone()))
                             )
                         }
                         Err(_) => {
@@ -778,7 +1036,7 @@ pub async fn delete_project_role_template_binding(
                                 "Unexpected status code {} when deleting project role template binding with ID: {}: {}", 
                                 status, 
                                 prtb_id,
-                                response_content.content
+                                bounded_body(&response_content.content)
                             )
                         }
                     };
@@ -793,17 +1051,17 @@ pub async fn delete_project_role_template_binding(
                     let msg = match response_error.status {
                         StatusCode::NOT_FOUND => format!( "Project role template binding with ID: {} in project: {} not found", prtb_id, project_id ),
                         StatusCode::UNAUTHORIZED => format!( "Unauthorized access while trying to delete project role template binding with ID: {} in project: {}", prtb_id, project_id ) ,
-                        StatusCode::BAD_REQUEST => format!( "Bad request when deleting project role template binding with ID: {} in project: {}. Request body was: {}", prtb_id, project_id, response_error.content ),
-                        _ => format!( "Failed to delete project role template binding with ID: {} in project: {}. Response: {:#?}", prtb_id, project_id, response_error )
+                        StatusCode::BAD_REQUEST => format!( "Bad request when deleting project role template binding with ID: {} in project: {}. Request body was: {}", prtb_id, project_id, bounded_body(&response_error.content) ),
+                        _ => format!( "Failed to delete project role template binding with ID: {} in project: {}. Response: {:#?}", prtb_id, project_id, BoundedResponse(&response_error) )
                         
                     };
                     error!(msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(status_error(response_error.status, Access::Write, ObjectType::ProjectRoleTemplateBinding, Some(project_id), msg))
                 }
                 _ => {
                     let msg = format!("Failed to delete project role template binding with ID: {} in project: {}. Error: {:#?}", prtb_id, project_id, e);
                     error!(msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(api_error(&e, msg))
                 }
             }
         }
@@ -815,6 +1073,7 @@ pub async fn delete_project_role_template_binding(
 
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ProjectRoleTemplateBinding {
     // annotations: Option<std::collections::HashMap<String, String>>,
     /// Annotations applied to the project role template binding.
@@ -857,6 +1116,7 @@ pub struct ProjectRoleTemplateBinding {
 }
 
 impl ProjectRoleTemplateBinding {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         annotations: Option<std::collections::HashMap<String, String>>,
         group_name: Option<String>,
@@ -888,6 +1148,18 @@ impl ProjectRoleTemplateBinding {
             user_principal_name,
         }
     }
+
+    /// Whether `other` binds the same subject to the same role template in the same project,
+    /// whatever their IDs. Principal names are compared by `principal_rules`.
+    pub fn same_binding(&self, other: &ProjectRoleTemplateBinding, principal_rules: &PrincipalRules) -> bool {
+        self.namespace == other.namespace
+            && self.role_template_name == other.role_template_name
+            && self.group_name == other.group_name
+            && principal_rules.same(self.group_principal_name.as_deref(), other.group_principal_name.as_deref())
+            && self.service_account == other.service_account
+            && self.user_name == other.user_name
+            && principal_rules.same(self.user_principal_name.as_deref(), other.user_principal_name.as_deref())
+    }
 }
 
 impl TryFrom<IoCattleManagementv3ProjectRoleTemplateBinding> for ProjectRoleTemplateBinding {
@@ -950,6 +1222,8 @@ impl TryFrom<ProjectRoleTemplateBinding> for IoCattleManagementv3ProjectRoleTemp
             labels: value.labels,
             namespace: Some(value.namespace),
             name: Some(value.id.clone()),
+            resource_version: value.resource_version,
+            uid: value.uid,
             ..Default::default()
         };
 
@@ -1039,6 +1313,27 @@ mod tests {
         assert_eq!(b, a);
     }
 
+    #[test]
+    fn test_directory_principals_are_the_same_binding_whatever_their_case() {
+        let rules = PrincipalRules::default();
+        let mut a = sample_binding();
+        a.group_principal_name = Some("activedirectory_group://cn=ops-team,ou=groups,dc=example,dc=com".to_string());
+        let mut new = ProjectRoleTemplateBinding { id: String::new(), ..sample_binding() };
+        new.group_principal_name = Some("activedirectory_group://CN=Ops-Team,OU=Groups,DC=example,DC=com".to_string());
+
+        assert!(a.same_binding(&new, &rules));
+        assert!(new.same_binding(&a, &rules));
+
+        new.group_principal_name = Some("activedirectory_group://CN=Dev-Team,OU=Groups,DC=example,DC=com".to_string());
+        assert!(!a.same_binding(&new, &rules));
+        let exact = PrincipalRules::new(&std::collections::BTreeMap::from([(
+            "activedirectory_group".to_string(),
+            crate::utils::principal::PrincipalNormalization::Exact,
+        )]));
+        new.group_principal_name = Some("activedirectory_group://CN=Ops-Team,OU=Groups,DC=example,DC=com".to_string());
+        assert!(!a.same_binding(&new, &exact));
+    }
+
     #[test]
     fn test_try_from_iocattle_to_binding() {
         let ioc = sample_iocattle_binding();
@@ -1090,4 +1385,80 @@ mod tests {
         assert_ne!(a, b);
         assert_ne!(b, a);
     }
+
+    #[tokio::test]
+    async fn test_update_sends_field_manager() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/namespace-id/projectroletemplatebindings/binding-id"))
+            .and(query_param("fieldManager", field_manager()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(sample_iocattle_binding()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut config = Configuration::new();
+        config.base_path = server.uri();
+
+        let patch = serde_json::json!([
+            { "op": "replace", "path": "/userName", "value": "user1" }
+        ]);
+        let result = sample_binding().update(&config, patch, false).await;
+
+        assert!(matches!(result, Ok(CreatedObject::ProjectRoleTemplateBinding(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_create_dry_run_sends_dry_run_all() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/apis/management.cattle.io/v3/namespaces/namespace-id/projectroletemplatebindings"))
+            .and(query_param("dryRun", "All"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(sample_iocattle_binding()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut config = Configuration::new();
+        config.base_path = server.uri();
+
+        let result = sample_binding().create(&config, true).await;
+
+        assert!(matches!(result, Ok(CreatedObject::ProjectRoleTemplateBinding(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_create_without_metadata_is_collected_as_an_error() {
+        use crate::error::handle_result_collection;
+
+        let config = Configuration::new();
+        let body = IoCattleManagementv3ProjectRoleTemplateBinding {
+            metadata: None,
+            ..sample_iocattle_binding()
+        };
+
+        let results = vec![create_project_role_template_binding(&config, "project-id", body, false).await];
+        let (created, errors) = handle_result_collection(results);
+
+        assert!(created.is_empty());
+        assert_eq!(
+            errors[0].downcast_ref::<ConversionError>(),
+            Some(&ConversionError::MissingField("metadata".into()))
+        );
+    }
+
+    #[test]
+    fn test_files_leave_out_the_server_managed_fields() {
+        let binding = sample_binding().for_file().unwrap();
+
+        assert_eq!(binding.resource_version, None);
+        assert_eq!(binding.uid, None);
+        assert_eq!(binding, ProjectRoleTemplateBinding { resource_version: None, uid: None, ..sample_binding() });
+    }
 }
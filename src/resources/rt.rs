@@ -1,7 +1,14 @@
-use crate::{models::{CreatedObject, ObjectType}, traits::RancherResource, utils::logging::log_api_error};
+use crate::api::client::{dry_run_param, with_server_version};
+use crate::api::client_info::field_manager;
+use crate::error::{api_error, bounded_body, status_error, Access, BoundedResponse};
+use crate::{models::{ConversionError, CreatedObject, DeleteParams, ObjectType}, traits::{Metadata, RancherResource}, utils::logging::log_api_error};
+use crate::state::write_atomically;
+use crate::serialize_object;
+use crate::utils::file::{role_template_index_name, FileFormat};
 use anyhow::Result;
 
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
@@ -12,6 +19,7 @@ use rancher_client::{
     apis::{Error,
         management_cattle_io_v3_api::{
         patch_management_cattle_io_v3_role_template,
+        replace_management_cattle_io_v3_role_template,
         list_management_cattle_io_v3_role_template,
         delete_management_cattle_io_v3_role_template,
         
@@ -32,12 +40,40 @@ pub const RT_EXCLUDE_PATHS: &[&str] = &[
     "metadata.generateName",
     "metadata.generation",
     "metadata.managedFields",
+    "metadata.ownerReferences",
     "metadata.resourceVersion",
     "metadata.selfLink",
     "metadata.uid",
+    "status",
 ];
 
 
+/// Which role templates are downloaded and checked for drift, decided on the API object's flags.
+///
+/// Excluded role templates can still be referenced by bindings, they are only
+/// left out of the `roles` folder and the comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoleTemplateFilter {
+    /// Include role templates with `hidden: true`
+    pub include_hidden: bool,
+    /// Include role templates with `builtin: true`
+    pub include_builtin: bool,
+}
+
+impl Default for RoleTemplateFilter {
+    fn default() -> Self {
+        Self { include_hidden: true, include_builtin: true }
+    }
+}
+
+impl RoleTemplateFilter {
+    pub fn includes(&self, role_template: &IoCattleManagementv3RoleTemplate) -> bool {
+        (self.include_hidden || role_template.hidden != Some(true))
+            && (self.include_builtin || role_template.builtin != Some(true))
+    }
+}
+
+
 impl RancherResource for RoleTemplate {
     type ApiType = IoCattleManagementv3RoleTemplate;
 
@@ -54,21 +90,21 @@ impl RancherResource for RoleTemplate {
         Self::try_from_api(rt)
     }
 
-    async fn create(&self, config: &Configuration) -> Result<CreatedObject> {
+    async fn create(&self, config: &Configuration, dry_run: bool) -> Result<CreatedObject> {
         let rt_api = self.clone().try_into_api()?;
 
-        let result = create_role_template(config, rt_api).await?;
+        let result = create_role_template(config, rt_api, dry_run).await?;
         Ok(CreatedObject::RoleTemplate(result))
         
     }
 
-    async fn update(&self, config: &Configuration, patch: Value) -> Result<CreatedObject> {
-        let result = update_role_template(config, &self.id, patch).await?;
+    async fn update(&self, config: &Configuration, patch: Value, dry_run: bool) -> Result<CreatedObject> {
+        let result = update_role_template(config, &self.id, patch, dry_run).await?;
         Ok(CreatedObject::RoleTemplate(result))
     }
 
-    async fn delete(config: &Configuration, name: &str, _: &str) -> Result<CreatedObject> {
-        let result = delete_role_template(config, name).await?;
+    async fn delete(config: &Configuration, name: &str, _: &str, dry_run: bool, params: DeleteParams) -> Result<CreatedObject> {
+        let result = delete_role_template(config, name, dry_run, params).await?;
         Ok(CreatedObject::Status(result))
     }
     
@@ -79,13 +115,17 @@ impl RancherResource for RoleTemplate {
     fn exclude_paths() -> &'static [&'static str] {
         RT_EXCLUDE_PATHS
     }
+
+    fn file_exclude_paths() -> &'static [&'static str] {
+        RT_EXCLUDE_PATHS
+    }
     
     fn try_from_api(value: Self::ApiType) -> Result<Self> {
         RoleTemplate::try_from(value)
     }
     
     fn try_into_api(self) -> Result<Self::ApiType> {
-        Ok(IoCattleManagementv3RoleTemplate::try_from(self)?)
+        IoCattleManagementv3RoleTemplate::try_from(self)
     }
     
     fn id(&self) -> Option<String> {
@@ -99,6 +139,10 @@ impl RancherResource for RoleTemplate {
     fn resource_version(&self) -> Option<String> {
         self.resource_version.clone()
     }
+
+    fn metadata_mut(&mut self) -> (&mut Metadata, &mut Metadata) {
+        (&mut self.annotations, &mut self.labels)
+    }
 }
 
 
@@ -108,6 +152,7 @@ impl RancherResource for RoleTemplate {
 ///
 /// * `configuration` - The configuration to use for the request
 /// * `body` - The role template to create
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
 /// # Returns
 ///
 /// * `IoCattleManagementv3RoleTemplate` - The created role template
@@ -119,15 +164,22 @@ impl RancherResource for RoleTemplate {
 pub async fn create_role_template(
     configuration: &Configuration,
     body: IoCattleManagementv3RoleTemplate,
+    dry_run: bool,
 ) -> Result<IoCattleManagementv3RoleTemplate> {
-    let role_template_id = body.metadata.as_ref().unwrap().name.clone().unwrap_or_default();
+    let role_template_id = body
+        .metadata
+        .as_ref()
+        .ok_or(ConversionError::MissingField("metadata".into()))?
+        .name
+        .clone()
+        .unwrap_or_default();
 
     let api_result = create_management_cattle_io_v3_role_template(
         configuration,
         body,
         None,
-        None,
-        None,
+        dry_run_param(dry_run),
+        Some(field_manager()),
         None,
     )
     .await;
@@ -160,7 +212,7 @@ pub async fn create_role_template(
                                 "Unexpected status code {} when creating role template with ID: {}: {}", 
                                 status, 
                                 role_template_id,
-                                serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone())
+                                bounded_body(&serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone()))
                             )
                         }
                         Err(_) => {
@@ -168,7 +220,7 @@ pub async fn create_role_template(
                                 "Unexpected status code {} when creating role template with ID: {}: {}", 
                                 status, 
                                 role_template_id,
-                                response_content.content
+                                bounded_body(&response_content.content)
                             )
                         }
                     };
@@ -184,11 +236,11 @@ pub async fn create_role_template(
                         StatusCode::UNAUTHORIZED => format!( "Unauthorized to create role template with ID: {}", role_template_id ),
                         StatusCode::FORBIDDEN => format!( "Forbidden to create role template with ID: {}", role_template_id),
                         StatusCode::CONFLICT => format!( "Conflict when creating role template with ID: {}", role_template_id ),
-                        _ => format!( "Failed to create role template with ID: {}. Response: {:#?}", role_template_id, response_error )
+                        _ => format!( "Failed to create role template with ID: {}. Response: {:#?}", role_template_id, BoundedResponse(&response_error) )
                         
                     };
                     error!(msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(status_error(response_error.status, Access::Write, ObjectType::RoleTemplate, None, msg))
                 }
                 _ => {
                     let msg = format!("Failed to create role template with ID: {}. Error: {:#?}", role_template_id, e);
@@ -258,13 +310,13 @@ pub async fn find_role_template(
                         Ok(error_obj) => {
                             anyhow::anyhow!(
                                 "Unexpected status code {} when finding role template with ID: {}: {}",
-                                status, role_template_id, serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone())
+                                status, role_template_id, bounded_body(&serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone()))
                             )
                         }
                         Err(_) => {
                             anyhow::anyhow!(
                                 "Unexpected status code {} when finding role template with ID: {}: {}",
-                                status, role_template_id, response_content.content
+                                status, role_template_id, bounded_body(&response_content.content)
                             )
                         }
                     };
@@ -294,11 +346,11 @@ pub async fn find_role_template(
                         }
                         _ => format!(
                             "Failed to find role template with ID: {}. Response: {:#?}",
-                            role_template_id, response_content
+                            role_template_id, BoundedResponse(&response_content)
                         ),
                     };
                     error!(msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(status_error(response_content.status, Access::Read, ObjectType::RoleTemplate, None, msg))
                 }
                 _ => {
                     let msg = format!(
@@ -306,7 +358,7 @@ pub async fn find_role_template(
                         role_template_id, e
                     );
                     error!(msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(api_error(&e, msg))
                 }
             }
         }
@@ -314,6 +366,14 @@ pub async fn find_role_template(
 }
 
 
+/// Check that the token can list role templates, returning how many it sees.
+///
+/// Used by `shepherd check`, nothing is changed.
+pub async fn probe_role_templates(configuration: &Configuration) -> Result<usize> {
+    let role_templates = get_role_templates(configuration, None, None, None, None, None, None).await?;
+    Ok(role_templates.items.len())
+}
+
 /// Get all role templates
 ///
 /// # Arguments
@@ -372,7 +432,7 @@ pub async fn get_role_templates(
                             Ok(data)
                         },
                         Err(deserialize_err) => {
-                            let err = anyhow::anyhow!("Failed to deserialize role templates response: {}", deserialize_err);
+                            let err = anyhow::anyhow!(with_server_version(configuration, format!("Failed to deserialize role templates response: {}", deserialize_err)));
                             log_api_error("get_role_templates:deserialize", &err);
                             Err(err)
                         }
@@ -385,14 +445,14 @@ pub async fn get_role_templates(
                             anyhow::anyhow!(
                                 "Unexpected status code {} when getting role templates: {}", 
                                 status, 
-                                serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone())
+                                bounded_body(&serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone()))
                             )
                         }
                         Err(_) => {
                             anyhow::anyhow!(
                                 "Unexpected status code {} when getting role templates: {}", 
                                 status, 
-                                response_content.content
+                                bounded_body(&response_content.content)
                             )
                         }
                     };
@@ -405,17 +465,17 @@ pub async fn get_role_templates(
             match e {
                 Error::ResponseError(response_content) => {
                     let msg = match response_content.status {
-                        StatusCode::NOT_FOUND => format!( "Role templates not found" ), 
-                        StatusCode::UNAUTHORIZED => format!( "Unauthorized access while trying to get role templates",  ) ,
-                        StatusCode::FORBIDDEN => format!( "Forbidden access while trying to get role templates." ) ,
-                        _ => format!( "Failed to get role templates. Response: {:#?}", response_content ), };
+                        StatusCode::NOT_FOUND => "Role templates not found".to_string(), 
+                        StatusCode::UNAUTHORIZED => "Unauthorized access while trying to get role templates".to_string() ,
+                        StatusCode::FORBIDDEN => "Forbidden access while trying to get role templates.".to_string() ,
+                        _ => format!( "Failed to get role templates. Response: {:#?}", BoundedResponse(&response_content) ), };
                     error!(msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(status_error(response_content.status, Access::Read, ObjectType::RoleTemplate, None, msg))
                     }
                 _ => {
                     let msg = format!("Failed to get role templates. Error: {:#?}", e);
                     error!(msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(api_error(&e, msg))
                 }
             }
         }
@@ -428,6 +488,7 @@ pub async fn get_role_templates(
 /// * `configuration` - The configuration to use for the request
 /// * `role_template_id` - The ID of the role template to update
 /// * `patch_value` - The JSON patch to apply
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
 /// # Returns
 /// * `IoCattleManagementv3RoleTemplate` - The updated role template
 /// # Errors
@@ -438,6 +499,7 @@ pub async fn update_role_template(
     configuration: &Configuration,
     role_template_id: &str,
     patch_value: Value,
+    dry_run: bool,
 ) -> Result<IoCattleManagementv3RoleTemplate> {
     // info!("Patching role template with ID: {}", role_template_id);
 
@@ -465,8 +527,8 @@ pub async fn update_role_template(
         role_template_id,
         Some(k8s_patch),
         None,
-        None,
-        None,
+        dry_run_param(dry_run),
+        Some(field_manager()),
         None,
         None
     )
@@ -500,7 +562,7 @@ pub async fn update_role_template(
                                 "Unexpected status code {} when updating role template with ID: {}: {}", 
                                 status, 
                                 role_template_id,
-                                serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone())
+                                bounded_body(&serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone()))
                             )
                         }
                         Err(_) => {
@@ -508,7 +570,7 @@ pub async fn update_role_template(
                                 "Unexpected status code {} when updating role template with ID: {}: {}", 
                                 status, 
                                 role_template_id,
-                                response_content.content
+                                bounded_body(&response_content.content)
                             )
                         }
                     };
@@ -521,14 +583,14 @@ pub async fn update_role_template(
             match e {
                 Error::ResponseError(response_content) => {
                     let msg = match response_content.status {
-                        StatusCode::NOT_FOUND => format!("Role template with ID: {} not found for update. Response: {}", role_template_id, response_content.content),
-                        StatusCode::UNAUTHORIZED => format!("Unauthorized to update role template with ID: {}. Response: {}", role_template_id, response_content.content),
-                        StatusCode::FORBIDDEN => format!("Forbidden to update role template with ID: {}. Response: {}", role_template_id, response_content.content),
-                        StatusCode::CONFLICT => format!("Conflict when updating role template with ID: {}. Response: {}", role_template_id, response_content.content),
-                        _ => format!("Failed to update role template with ID: {}. Response: {:#?}", role_template_id, response_content),
+                        StatusCode::NOT_FOUND => format!("Role template with ID: {} not found for update. Response: {}", role_template_id, bounded_body(&response_content.content)),
+                        StatusCode::UNAUTHORIZED => format!("Unauthorized to update role template with ID: {}. Response: {}", role_template_id, bounded_body(&response_content.content)),
+                        StatusCode::FORBIDDEN => format!("Forbidden to update role template with ID: {}. Response: {}", role_template_id, bounded_body(&response_content.content)),
+                        StatusCode::CONFLICT => format!("Conflict when updating role template with ID: {}. Response: {}", role_template_id, bounded_body(&response_content.content)),
+                        _ => format!("Failed to update role template with ID: {}. Response: {:#?}", role_template_id, BoundedResponse(&response_content)),
                     };
                     error!("{}", msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(status_error(response_content.status, Access::Write, ObjectType::RoleTemplate, None, msg))
                 },
                 _ => {
                     let msg = format!("Failed to update role template with ID: {}. Error was: {:#?}", role_template_id, e);
@@ -541,10 +603,98 @@ pub async fn update_role_template(
 }
 
 
+/// Replace a role template with the given body (PUT)
+/// # Arguments
+/// * `configuration` - The configuration to use for the request
+/// * `role_template_id` - The ID of the role template to replace
+/// * `body` - The full role template, carrying the current `metadata.resourceVersion`
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
+/// # Returns
+/// * `IoCattleManagementv3RoleTemplate` - The replaced role template
+/// # Errors
+/// * `anyhow::Error` - The error that occurred while trying to replace the role template
+///
+#[async_backtrace::framed]
+pub async fn replace_role_template(
+    configuration: &Configuration,
+    role_template_id: &str,
+    body: IoCattleManagementv3RoleTemplate,
+    dry_run: bool,
+) -> Result<IoCattleManagementv3RoleTemplate> {
+    let api_result = replace_management_cattle_io_v3_role_template(
+        configuration,
+        role_template_id,
+        body,
+        None,
+        dry_run_param(dry_run),
+        Some(field_manager()),
+        None,
+    )
+    .await;
+
+    trace!(api_result = ?api_result, "Received API response");
+
+    match api_result {
+        Ok(response_content) => {
+            match response_content.status {
+                StatusCode::OK | StatusCode::CREATED => {
+                    match serde_json::from_str::<IoCattleManagementv3RoleTemplate>(&response_content.content) {
+                        Ok(data) => {
+                            info!("Successfully replaced role template with ID: {}", role_template_id);
+                            Ok(data)
+                        }
+                        Err(deserialize_err) => {
+                            let err = anyhow::anyhow!(
+                                "Failed to deserialize role template replace response: {}",
+                                deserialize_err
+                            );
+                            log_api_error("replace_role_template:deserialize", &err);
+                            Err(err)
+                        }
+                    }
+                }
+                status => {
+                    let err = anyhow::anyhow!(
+                        "Unexpected status code {} when replacing role template with ID: {}: {}",
+                        status,
+                        role_template_id,
+                        bounded_body(&response_content.content)
+                    );
+                    log_api_error("replace_role_template:unexpected_status", &err);
+                    Err(err)
+                }
+            }
+        }
+        Err(e) => {
+            match e {
+                Error::ResponseError(response_content) => {
+                    let msg = match response_content.status {
+                        StatusCode::NOT_FOUND => format!("Role template with ID: {} not found for replace. Response: {}", role_template_id, bounded_body(&response_content.content)),
+                        StatusCode::UNAUTHORIZED => format!("Unauthorized to replace role template with ID: {}. Response: {}", role_template_id, bounded_body(&response_content.content)),
+                        StatusCode::FORBIDDEN => format!("Forbidden to replace role template with ID: {}. Response: {}", role_template_id, bounded_body(&response_content.content)),
+                        StatusCode::CONFLICT => format!("Conflict when replacing role template with ID: {}, it changed since it was read. Response: {}", role_template_id, bounded_body(&response_content.content)),
+                        _ => format!("Failed to replace role template with ID: {}. Response: {:#?}", role_template_id, BoundedResponse(&response_content)),
+                    };
+                    error!("{}", msg);
+                    Err(status_error(response_content.status, Access::Write, ObjectType::RoleTemplate, None, msg))
+                },
+                _ => {
+                    let msg = format!("Failed to replace role template with ID: {}. Error was: {:#?}", role_template_id, e);
+                    error!("{}", msg);
+                    Err(anyhow::anyhow!(msg))
+                }
+            }
+        }
+    }
+}
+
+
 /// Delete a role template by its ID
 /// # Arguments
 /// * `configuration` - The configuration to use for the request
 /// * `role_template_id` - The ID of the role template to delete
+/// * `dry_run` - Send the request with `dryRun=All` so the server validates it without persisting anything
+/// * `params` - The propagation policy and grace period of the deletion
 /// # Returns
 /// * `IoK8sApimachineryPkgApisMetaV1Status` - The status of the deletion
 /// # Errors
@@ -554,16 +704,18 @@ pub async fn update_role_template(
 pub async fn delete_role_template(
     configuration: &Configuration,
     role_template_id: &str,
+    dry_run: bool,
+    params: DeleteParams,
 ) -> Result<IoK8sApimachineryPkgApisMetaV1Status> {
 
     let api_result = delete_management_cattle_io_v3_role_template(
         configuration,
         role_template_id,
         None,
+        dry_run_param(dry_run),
+        params.grace_period_seconds,
         None,
-        None,
-        None,
-        None,
+        params.propagation_param(),
         None,
     )
     .await;
@@ -597,7 +749,7 @@ pub async fn delete_role_template(
                                 "Unexpected status code {} when deleting role template with ID: {}: {}", 
                                 status, 
                                 role_template_id,
-                                serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone())
+                                bounded_body(&serde_json::to_string_pretty(&error_obj).unwrap_or_else(|_| response_content.content.clone()))
                             )
                         }
                         Err(_) => {
@@ -605,7 +757,7 @@ pub async fn delete_role_template(
                                 "Unexpected status code {} when deleting role template with ID: {}: {}", 
                                 status, 
                                 role_template_id,
-                                response_content.content
+                                bounded_body(&response_content.content)
                             )
                         }
                     };
@@ -635,11 +787,11 @@ pub async fn delete_role_template(
                         }
                         _ => format!(
                             "Failed to delete role template with ID: {}. Response: {:#?}",
-                            role_template_id, response_content
+                            role_template_id, BoundedResponse(&response_content)
                         ),
                     };
                     error!(msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(status_error(response_content.status, Access::Write, ObjectType::RoleTemplate, None, msg))
                 }
                 _ => {
                     let msg = format!(
@@ -647,7 +799,7 @@ pub async fn delete_role_template(
                         role_template_id, e
                     );
                     error!(msg);
-                    Err(anyhow::anyhow!(msg))
+                    Err(api_error(&e, msg))
                 }
             }
         }
@@ -656,6 +808,7 @@ pub async fn delete_role_template(
 
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RoleTemplate {
 
     /// Administrative if true, this RoleTemplate is used to grant administrative privileges. Default to false.
@@ -669,6 +822,7 @@ pub struct RoleTemplate {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cluster_creator_default: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<crate::schema::ContextSchema>"))]
     pub context: Option<Context>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
@@ -690,10 +844,12 @@ pub struct RoleTemplate {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub role_template_names: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<Vec<crate::schema::PolicyRuleSchema>>"))]
     pub rules: Option<Vec<IoCattleManagementv3GlobalRoleRulesInner>>,
 }
 
 impl RoleTemplate {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         administrative: Option<bool>,
         annotations: Option<HashMap<String, String>>,
@@ -784,6 +940,7 @@ impl TryFrom<RoleTemplate> for IoCattleManagementv3RoleTemplate {
             annotations: value.annotations,
             labels: value.labels,
             name: Some(value.id.clone()),
+            resource_version: value.resource_version,
             ..Default::default()
         };
 
@@ -849,6 +1006,61 @@ impl PartialEq<IoCattleManagementv3RoleTemplate> for RoleTemplate {
     }
 }
 
+/// A role template as listed in the role template index.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RoleTemplateIndexEntry {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<Context>,
+    pub builtin: bool,
+    pub locked: bool,
+}
+
+impl From<&RoleTemplate> for RoleTemplateIndexEntry {
+    fn from(role_template: &RoleTemplate) -> Self {
+        Self {
+            id: role_template.id.clone(),
+            display_name: role_template.display_name.clone(),
+            context: role_template.context,
+            builtin: role_template.builtin.unwrap_or_default(),
+            locked: role_template.locked.unwrap_or_default(),
+        }
+    }
+}
+
+/// The overview of the role templates of an endpoint, written to the `roles` folder next to
+/// their files so a review sees role templates added and removed at a glance.
+///
+/// It is sorted by ID, so the file only changes when the role templates do.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RoleTemplateIndex {
+    pub role_templates: Vec<RoleTemplateIndexEntry>,
+}
+
+impl RoleTemplateIndex {
+    pub fn new<'a>(role_templates: impl IntoIterator<Item = &'a RoleTemplate>) -> Self {
+        let mut role_templates: Vec<RoleTemplateIndexEntry> = role_templates.into_iter().map(RoleTemplateIndexEntry::from).collect();
+        role_templates.sort_by(|a, b| a.id.cmp(&b.id));
+        Self { role_templates }
+    }
+
+    /// Write the index to the role template folder `role_template_path`, unless it is unchanged.
+    ///
+    /// # Returns
+    /// The path of the index if it was written
+    pub fn write(&self, role_template_path: &Path, file_format: &FileFormat) -> Result<Option<PathBuf>> {
+        let path = role_template_path.join(role_template_index_name(file_format));
+        let contents = serialize_object(self, file_format)?;
+        if std::fs::read_to_string(&path).is_ok_and(|existing| existing == contents) {
+            return Ok(None);
+        }
+        write_atomically(&path, &contents)?;
+        Ok(Some(path))
+    }
+}
+
 
 
 
@@ -992,6 +1204,104 @@ mod tests {
         assert_ne!(iort, rt);
     }
 
+    #[tokio::test]
+    async fn test_update_sends_field_manager() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/apis/management.cattle.io/v3/roletemplates/admin-template"))
+            .and(query_param("fieldManager", field_manager()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(sample_iocattle_role_template()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut config = Configuration::new();
+        config.base_path = server.uri();
+
+        let patch = serde_json::json!([
+            { "op": "replace", "path": "/description", "value": "A role template" }
+        ]);
+        let result = sample_role_template().update(&config, patch, false).await;
+
+        assert!(matches!(result, Ok(CreatedObject::RoleTemplate(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_delete_dry_run_sends_dry_run_all() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/apis/management.cattle.io/v3/roletemplates/admin-template"))
+            .and(query_param("dryRun", "All"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "apiVersion": "v1",
+                "kind": "Status",
+                "status": "Success"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut config = Configuration::new();
+        config.base_path = server.uri();
+
+        let result = RoleTemplate::delete(&config, "admin-template", "", true, DeleteParams::default()).await;
+
+        assert!(matches!(result, Ok(CreatedObject::Status(_))), "{:?}", result);
+    }
 
+    #[test]
+    fn test_role_template_filter_uses_api_flags() {
+        let mut hidden = sample_iocattle_role_template();
+        hidden.hidden = Some(true);
+        let mut builtin = sample_iocattle_role_template();
+        builtin.builtin = Some(true);
+        let plain = sample_iocattle_role_template();
+
+        let all = RoleTemplateFilter::default();
+        assert!(all.includes(&hidden) && all.includes(&builtin) && all.includes(&plain));
+
+        let custom_only = RoleTemplateFilter { include_hidden: false, include_builtin: false };
+        assert!(!custom_only.includes(&hidden));
+        assert!(!custom_only.includes(&builtin));
+        assert!(custom_only.includes(&plain));
+    }
 
+    #[test]
+    fn test_role_template_index_is_sorted_and_only_written_when_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let admin = sample_role_template();
+        let mut member = sample_role_template();
+        member.id = "project-member".to_string();
+        member.display_name = Some("Project Member".to_string());
+        member.context = Some(Context::Project);
+        member.builtin = Some(true);
+        member.locked = None;
+
+        let index = RoleTemplateIndex::new([&member, &admin]);
+        let written = index.write(dir.path(), &FileFormat::Yaml).unwrap().unwrap();
+        assert_eq!(written, dir.path().join("_index.yaml"));
+        assert_eq!(
+            std::fs::read_to_string(&written).unwrap(),
+            "role_templates:\n\
+             - builtin: false\n  context: cluster\n  display_name: Admin\n  id: admin-template\n  locked: false\n\
+             - builtin: true\n  context: project\n  display_name: Project Member\n  id: project-member\n  locked: false\n"
+        );
+
+        // the same role templates in another order leave the file alone
+        assert_eq!(RoleTemplateIndex::new([&admin, &member]).write(dir.path(), &FileFormat::Yaml).unwrap(), None);
+
+        member.locked = Some(true);
+        assert!(RoleTemplateIndex::new([&admin, &member]).write(dir.path(), &FileFormat::Yaml).unwrap().is_some());
+        for file_format in [FileFormat::Json, FileFormat::Toml] {
+            let index = RoleTemplateIndex::new([&admin, &member]);
+            let written = index.write(dir.path(), &file_format).unwrap().unwrap();
+            assert_eq!(index.write(dir.path(), &file_format).unwrap(), None, "{}", written.display());
+        }
+    }
 }
@@ -0,0 +1,192 @@
+//! JSON Schemas of the object files, for editors to validate and complete them.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use schemars::schema::RootSchema;
+use schemars::{schema_for, JsonSchema};
+use serde_json::{json, Value};
+
+use crate::models::ObjectType;
+use crate::resources::{
+    cluster::Cluster, crtb::ClusterRoleTemplateBinding, project::Project, prtb::ProjectRoleTemplateBinding,
+    rt::RoleTemplate,
+};
+use crate::utils::file::{get_file_name_for_object, FileFormat};
+
+/// The file listing every schema with the file names it applies to.
+pub const CATALOG_FILE: &str = "catalog.json";
+
+// stands in for the context of the generated client, which has no schema
+/// Whether the role template applies to projects or clusters.
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+pub(crate) enum ContextSchema {
+    #[serde(rename = "project")]
+    Project,
+    #[serde(rename = "cluster")]
+    Cluster,
+    #[serde(rename = "")]
+    Empty,
+}
+
+// stands in for the policy rule of the generated client, which has no schema
+/// The verbs allowed on the listed resources or non-resource URLs.
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+pub(crate) struct PolicyRuleSchema {
+    #[serde(rename = "apiGroups", skip_serializing_if = "Option::is_none")]
+    api_groups: Option<Vec<String>>,
+    #[serde(rename = "nonResourceURLs", skip_serializing_if = "Option::is_none")]
+    non_resource_urls: Option<Vec<String>>,
+    #[serde(rename = "resourceNames", skip_serializing_if = "Option::is_none")]
+    resource_names: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resources: Option<Vec<String>>,
+    verbs: Vec<String>,
+}
+
+/// The file name of the schema of `object_type`, such as `project.schema.json`.
+pub fn schema_file_name(object_type: ObjectType) -> String {
+    let kind = get_file_name_for_object("", &object_type, &FileFormat::Json);
+    format!("{}.schema.json", kind.trim_start_matches('.').trim_end_matches(".json"))
+}
+
+/// The schema of the files holding objects of `object_type`.
+pub fn object_schema(object_type: ObjectType) -> RootSchema {
+    match object_type {
+        ObjectType::Project => schema_for!(Project),
+        ObjectType::RoleTemplate => schema_for!(RoleTemplate),
+        ObjectType::ProjectRoleTemplateBinding => schema_for!(ProjectRoleTemplateBinding),
+        ObjectType::ClusterRoleTemplateBinding => schema_for!(ClusterRoleTemplateBinding),
+        ObjectType::Cluster => schema_for!(Cluster),
+    }
+}
+
+/// The glob patterns of the files holding objects of `object_type`, in every file format.
+pub fn file_patterns(object_type: ObjectType) -> Vec<String> {
+    let yaml = get_file_name_for_object("*", &object_type, &FileFormat::Yaml);
+    let mut patterns: Vec<String> = [FileFormat::Yaml, FileFormat::Json, FileFormat::Toml]
+        .iter()
+        .map(|file_format| get_file_name_for_object("*", &object_type, file_format))
+        .collect();
+    patterns.insert(1, format!("{}yml", yaml.trim_end_matches("yaml")));
+    patterns
+}
+
+/// A schemastore-style catalog mapping the file patterns of every object type to its schema.
+pub fn catalog() -> Value {
+    let schemas: Vec<Value> = ALL_OBJECT_TYPES
+        .iter()
+        .map(|object_type| {
+            json!({
+                "name": format!("Shepherd {:?}", object_type),
+                "fileMatch": file_patterns(*object_type),
+                "url": format!("./{}", schema_file_name(*object_type)),
+            })
+        })
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/schema-catalog.json",
+        "version": 1.0,
+        "schemas": schemas,
+    })
+}
+
+const ALL_OBJECT_TYPES: [ObjectType; 5] = [
+    ObjectType::Cluster,
+    ObjectType::Project,
+    ObjectType::ProjectRoleTemplateBinding,
+    ObjectType::ClusterRoleTemplateBinding,
+    ObjectType::RoleTemplate,
+];
+
+/// Write one schema per object type and the [`CATALOG_FILE`] to `out_dir`, creating it if needed.
+///
+/// # Returns
+/// The paths of the files written
+///
+/// # Errors
+/// If the folder cannot be created or a file cannot be written
+pub fn write_schemas(out_dir: &Path) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(out_dir).with_context(|| format!("Failed to create {}", out_dir.display()))?;
+
+    let mut files: Vec<(PathBuf, Value)> = ALL_OBJECT_TYPES
+        .iter()
+        .map(|object_type| {
+            let schema = serde_json::to_value(object_schema(*object_type))?;
+            Ok((out_dir.join(schema_file_name(*object_type)), schema))
+        })
+        .collect::<Result<_>>()?;
+    files.push((out_dir.join(CATALOG_FILE), catalog()));
+
+    for (path, contents) in &files {
+        std::fs::write(path, serde_json::to_string_pretty(contents)? + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(object_type: ObjectType, instance: &Value) -> Vec<String> {
+        let schema = serde_json::to_value(object_schema(object_type)).unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        validator.iter_errors(instance).map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn test_sample_files_validate_against_their_schemas() {
+        let project: Value = serde_yaml::from_str(
+            "cluster_name: c-123\n\
+             id: p-abc\n\
+             display_name: team-a\n\
+             namespace: c-123\n\
+             annotations:\n  team: platform\n\
+             resource_quota:\n  limitsCpu: 2000m\n",
+        )
+        .unwrap();
+        let role_template: Value = serde_yaml::from_str(
+            "id: rt-abc\n\
+             context: project\n\
+             rules:\n- apiGroups: ['']\n  resources: [pods]\n  verbs: [get, list]\n",
+        )
+        .unwrap();
+
+        assert_eq!(validate(ObjectType::Project, &project), Vec::<String>::new());
+        assert_eq!(validate(ObjectType::RoleTemplate, &role_template), Vec::<String>::new());
+
+        // a field named the way the API spells it is not the file's field
+        let mut misnamed = project.clone();
+        misnamed["displayName"] = misnamed.as_object_mut().unwrap().remove("display_name").unwrap();
+        assert!(!validate(ObjectType::Project, &misnamed).is_empty());
+        let mut bad_context = role_template.clone();
+        bad_context["context"] = json!("global");
+        assert!(!validate(ObjectType::RoleTemplate, &bad_context).is_empty());
+    }
+
+    #[test]
+    fn test_schemas_and_catalog_are_written() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let written = write_schemas(dir.path()).unwrap();
+
+        assert_eq!(written.len(), 6);
+        assert!(dir.path().join("project.schema.json").exists());
+        assert!(dir.path().join("rt.schema.json").exists());
+        let catalog: Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.path().join(CATALOG_FILE)).unwrap()).unwrap();
+        let project = catalog["schemas"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|schema| schema["url"] == "./project.schema.json")
+            .unwrap();
+        assert_eq!(
+            project["fileMatch"],
+            json!(["*.project.yaml", "*.project.yml", "*.project.json", "*.project.toml"])
+        );
+    }
+}
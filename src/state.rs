@@ -0,0 +1,789 @@
+//! What Shepherd remembers between runs, kept in [`STATE_FILE`] in the state folder.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{MinimalObject, ObjectType, SyncMode};
+use crate::report::{PermissionDenial, SyncReport};
+use crate::utils::snapshot::DirectorySnapshot;
+
+/// The file in the state folder holding the [`SyncState`].
+pub const STATE_FILE: &str = "state.json";
+
+/// The Prometheus text file in the state folder, for the node exporter's textfile collector.
+pub const METRICS_FILE: &str = "shepherd.prom";
+
+/// An object that failed in a run, identified by its file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectFailure {
+    pub path: PathBuf,
+    pub object_type: ObjectType,
+    pub object_id: Option<String>,
+    pub error: String,
+}
+
+/// An object failing in consecutive runs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ObjectState {
+    pub object_type: ObjectType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_id: Option<String>,
+    pub consecutive_failures: u32,
+    pub last_error: String,
+    /// The git blob ID of the file when it last failed, `None` if there was no file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob_id: Option<String>,
+    /// Set once the object is skipped by every run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantined_at: Option<DateTime<Utc>>,
+}
+
+impl fmt::Display for ObjectState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} `{}` failed {} runs in a row: {}",
+            self.object_type,
+            self.object_id.as_deref().unwrap_or("<new>"),
+            self.consecutive_failures,
+            self.last_error
+        )
+    }
+}
+
+/// An object Shepherd deleted that Rancher may still be terminating, such as a project
+/// whose finalizers have not run yet.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PendingDeletion {
+    pub object_type: ObjectType,
+    pub object_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    pub deleted_at: DateTime<Utc>,
+    /// Set once the object was escalated for terminating too long
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub escalated: bool,
+}
+
+impl PendingDeletion {
+    /// Whether this is the object of `object_type` with `object_id` in `namespace`.
+    ///
+    /// Role templates are not namespaced, their namespace is not compared.
+    pub fn is(&self, object_type: ObjectType, namespace: Option<&str>, object_id: &str) -> bool {
+        self.object_type == object_type
+            && self.object_id == object_id
+            && (matches!(object_type, ObjectType::RoleTemplate | ObjectType::Cluster) || self.namespace.as_deref() == namespace)
+    }
+
+    /// How long the object has been terminating at `now`.
+    pub fn terminating_for(&self, now: DateTime<Utc>) -> Duration {
+        (now - self.deleted_at).to_std().unwrap_or_default()
+    }
+}
+
+impl fmt::Display for PendingDeletion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.namespace {
+            Some(namespace) => write!(f, "{:?} `{}/{}`", self.object_type, namespace, self.object_id),
+            None => write!(f, "{:?} `{}`", self.object_type, self.object_id),
+        }
+    }
+}
+
+/// An object created in Rancher whose file could not be written back, so the file still
+/// looks new. The next run adopts the object into the file instead of creating it again.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UnpersistedCreation {
+    pub object_type: ObjectType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// The file the object was created from
+    pub path: PathBuf,
+    pub created_at: DateTime<Utc>,
+    /// Why the file could not be written
+    pub error: String,
+}
+
+impl fmt::Display for UnpersistedCreation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} of {}: {}", self.object_type, self.path.display(), self.error)
+    }
+}
+
+/// A change set larger than `max_changes_per_run`, applied over several runs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChangeBacklog {
+    /// The run that first left changes to the next runs
+    pub started_run_id: String,
+    /// The changes applied since, including those that failed
+    pub applied: usize,
+    /// The changes the last run left to the next runs
+    pub remaining: usize,
+    /// The files of the creations and deletions left, kept out of the commits until they are applied
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub held_back: Vec<PathBuf>,
+}
+
+impl ChangeBacklog {
+    /// The backlog after the run `run_id` applied `applied` changes and left `remaining`, with
+    /// the files of the latter `held_back`. `None` once no changes are left.
+    pub fn advance(previous: Option<Self>, run_id: &str, applied: usize, remaining: usize, held_back: Vec<PathBuf>) -> Option<Self> {
+        if remaining == 0 {
+            return None;
+        }
+        let (started_run_id, applied_before) = previous.map_or((run_id.to_string(), 0), |backlog| (backlog.started_run_id, backlog.applied));
+        Some(Self {
+            started_run_id,
+            applied: applied_before + applied,
+            remaining,
+            held_back,
+        })
+    }
+
+    /// All the changes of the backlog, applied or not.
+    pub fn total(&self) -> usize {
+        self.applied + self.remaining
+    }
+}
+
+impl fmt::Display for ChangeBacklog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} changes applied", self.applied, self.total())
+    }
+}
+
+/// The failing objects, keyed by the path of their file, the objects deleted but maybe not
+/// gone yet, the objects created but not written back, and the last run that counted them.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SyncState {
+    #[serde(default)]
+    pub objects: BTreeMap<PathBuf, ObjectState>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pending_deletions: Vec<PendingDeletion>,
+    /// The objects created without writing back their file, keyed by object ID
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub unpersisted: BTreeMap<String, UnpersistedCreation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run_id: Option<String>,
+    /// The API calls of the last run refused with `401` or `403`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub permission_denials: Vec<PermissionDenial>,
+    /// The mode of the last run
+    #[serde(default)]
+    pub mode: SyncMode,
+    /// The version of Rancher last probed, to tell when it changed between runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_version: Option<String>,
+    /// The object files at the end of the last run, with `git_enabled = false`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<DirectorySnapshot>,
+    /// The changes left to the next runs by `max_changes_per_run`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backlog: Option<ChangeBacklog>,
+}
+
+impl SyncState {
+    /// Read the state from `state_dir`, empty if there is none yet.
+    ///
+    /// # Errors
+    /// If the state file exists but cannot be read or parsed
+    pub fn load(state_dir: &Path) -> Result<Self> {
+        let path = state_dir.join(STATE_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    /// Write the state to `state_dir`, replacing the previous state in one step.
+    pub fn save(&self, state_dir: &Path) -> Result<()> {
+        write_atomically(&state_dir.join(STATE_FILE), &(serde_json::to_string_pretty(self)? + "\n"))
+    }
+
+    /// Whether the object of the file at `path` is skipped.
+    pub fn is_quarantined(&self, path: &Path) -> bool {
+        self.objects.get(path).is_some_and(|object| object.quarantined_at.is_some())
+    }
+
+    /// The quarantined objects by the path of their file.
+    pub fn quarantined(&self) -> impl Iterator<Item = (&PathBuf, &ObjectState)> {
+        self.objects.iter().filter(|(_, object)| object.quarantined_at.is_some())
+    }
+
+    /// Release the quarantined objects whose file changed since they last failed.
+    ///
+    /// # Returns
+    /// The paths of the released files
+    pub fn release_changed(&mut self) -> Vec<PathBuf> {
+        let changed: Vec<PathBuf> = self
+            .quarantined()
+            .filter(|(path, object)| blob_id(path) != object.blob_id)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &changed {
+            self.objects.remove(path);
+        }
+        changed
+    }
+
+    /// Release the quarantined objects with the ID `id` or whose file is `id`.
+    ///
+    /// # Returns
+    /// The paths of the released files
+    pub fn unquarantine(&mut self, id: &str) -> Vec<PathBuf> {
+        let matching: Vec<PathBuf> = self
+            .quarantined()
+            .filter(|(path, object)| object.object_id.as_deref() == Some(id) || path.as_path() == Path::new(id))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &matching {
+            self.objects.remove(path);
+        }
+        matching
+    }
+
+    /// Count the `failures` of a run, forgetting the objects that did not fail again.
+    ///
+    /// An object failing `quarantine_after` runs in a row is quarantined. Quarantined
+    /// objects are not attempted, so they stay as they are.
+    ///
+    /// # Returns
+    /// The paths of the newly quarantined files
+    pub fn record_run(&mut self, failures: &[ObjectFailure], quarantine_after: u32) -> Vec<PathBuf> {
+        self.objects.retain(|path, object| {
+            object.quarantined_at.is_some() || failures.iter().any(|failure| &failure.path == path)
+        });
+
+        let mut quarantined = Vec::new();
+        for failure in failures {
+            let object = self.objects.entry(failure.path.clone()).or_insert_with(|| ObjectState {
+                object_type: failure.object_type,
+                object_id: None,
+                consecutive_failures: 0,
+                last_error: String::new(),
+                blob_id: None,
+                quarantined_at: None,
+            });
+            if object.quarantined_at.is_some() {
+                continue;
+            }
+            object.consecutive_failures += 1;
+            object.object_id = failure.object_id.clone().or(object.object_id.take());
+            object.last_error = failure.error.clone();
+            object.blob_id = blob_id(&failure.path);
+            if object.consecutive_failures >= quarantine_after {
+                object.quarantined_at = Some(Utc::now());
+                quarantined.push(failure.path.clone());
+            }
+        }
+        quarantined
+    }
+
+    /// Remember the objects deleted at `deleted_at` until a later run finds them gone.
+    pub fn record_deletions(&mut self, deleted: &[(ObjectType, MinimalObject)], deleted_at: DateTime<Utc>) {
+        for (object_type, object) in deleted {
+            let Some(object_id) = object.object_id.as_deref() else {
+                continue;
+            };
+            let namespace = object.namespace.as_deref();
+            self.pending_deletions
+                .retain(|pending| !pending.is(*object_type, namespace, object_id));
+            self.pending_deletions.push(PendingDeletion {
+                object_type: *object_type,
+                object_id: object_id.to_string(),
+                namespace: object.namespace.clone(),
+                deleted_at,
+                escalated: false,
+            });
+        }
+    }
+
+    /// The deletion of the object of `object_type` with `object_id` in `namespace`, if it may
+    /// still be terminating.
+    pub fn pending_deletion(&self, object_type: ObjectType, namespace: Option<&str>, object_id: &str) -> Option<&PendingDeletion> {
+        self.pending_deletions
+            .iter()
+            .find(|pending| pending.is(object_type, namespace, object_id))
+    }
+
+    /// The ID and creation of the object created from the file at `path` but not written
+    /// back to it, if there is one.
+    pub fn unpersisted_at(&self, path: &Path) -> Option<(&String, &UnpersistedCreation)> {
+        self.unpersisted.iter().find(|(_, creation)| creation.path == path)
+    }
+
+    /// Forget the creations whose file was `written` and remember the `failed` ones, keyed by
+    /// object ID.
+    pub fn record_write_back(&mut self, written: &[PathBuf], failed: Vec<(String, UnpersistedCreation)>) {
+        self.unpersisted.retain(|_, creation| !written.contains(&creation.path));
+        self.unpersisted.extend(failed);
+    }
+
+    /// The metrics of the state in the Prometheus text format.
+    ///
+    /// The last run is labelled with its ID, which its commits and report carry as well.
+    pub fn metrics(&self) -> String {
+        let mut metrics = String::new();
+        if let Some(run_id) = &self.last_run_id {
+            let server_version = self
+                .server_version
+                .as_ref()
+                .map(|version| format!(",rancher_version=\"{}\"", escape_label(version)))
+                .unwrap_or_default();
+            metrics.push_str(&format!(
+                "# HELP shepherd_last_run_info The last run, labelled with its ID and the version of Rancher\n\
+                 # TYPE shepherd_last_run_info gauge\n\
+                 shepherd_last_run_info{{run_id=\"{}\"{}}} 1\n",
+                escape_label(run_id),
+                server_version
+            ));
+        }
+        metrics.push_str(&format!(
+            "# HELP shepherd_mode_info Whether the last run applied the repository to Rancher or mirrored Rancher into it\n\
+             # TYPE shepherd_mode_info gauge\n\
+             shepherd_mode_info{{mode=\"{}\"}} 1\n",
+            self.mode
+        ));
+        metrics.push_str(&format!(
+            "# HELP shepherd_quarantined_objects Objects skipped after failing too many runs in a row\n\
+             # TYPE shepherd_quarantined_objects gauge\n\
+             shepherd_quarantined_objects {}\n",
+            self.quarantined().count()
+        ));
+        metrics.push_str(&format!(
+            "# HELP shepherd_terminating_objects Objects deleted in an earlier run that Rancher still has\n\
+             # TYPE shepherd_terminating_objects gauge\n\
+             shepherd_terminating_objects {}\n",
+            self.pending_deletions.len()
+        ));
+        metrics.push_str(&format!(
+            "# HELP shepherd_unpersisted_objects Objects created in Rancher whose file could not be written back\n\
+             # TYPE shepherd_unpersisted_objects gauge\n\
+             shepherd_unpersisted_objects {}\n",
+            self.unpersisted.len()
+        ));
+        metrics.push_str(&format!(
+            "# HELP shepherd_deferred_changes Changes left to the next runs by max_changes_per_run\n\
+             # TYPE shepherd_deferred_changes gauge\n\
+             shepherd_deferred_changes {}\n",
+            self.backlog.as_ref().map_or(0, |backlog| backlog.remaining)
+        ));
+        if !self.pending_deletions.is_empty() {
+            metrics.push_str(
+                "# HELP shepherd_terminating_since_seconds When a terminating object was deleted, as a Unix timestamp\n\
+                 # TYPE shepherd_terminating_since_seconds gauge\n",
+            );
+            for pending in &self.pending_deletions {
+                metrics.push_str(&format!(
+                    "shepherd_terminating_since_seconds{{object_type=\"{:?}\",object_id=\"{}\"}} {}\n",
+                    pending.object_type,
+                    escape_label(&pending.object_id),
+                    pending.deleted_at.timestamp()
+                ));
+            }
+        }
+        if !self.permission_denials.is_empty() {
+            metrics.push_str(
+                "# HELP shepherd_permission_denied_calls API calls of the last run refused with 401 or 403\n\
+                 # TYPE shepherd_permission_denied_calls gauge\n",
+            );
+            for denial in &self.permission_denials {
+                metrics.push_str(&format!(
+                    "shepherd_permission_denied_calls{{status=\"{}\",access=\"{}\",resource=\"{}\",namespace=\"{}\"}} {}\n",
+                    denial.status,
+                    denial.access,
+                    denial.object_type.resource(),
+                    escape_label(denial.namespace.as_deref().unwrap_or_default()),
+                    denial.calls
+                ));
+            }
+        }
+        metrics
+    }
+}
+
+/// `value` as a label value of the Prometheus text format, with `\`, `"` and newlines escaped.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// What `shepherd status` shows: the last run and the quarantined objects.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Status {
+    pub last_run: Option<SyncReport>,
+    pub quarantined: BTreeMap<PathBuf, ObjectState>,
+}
+
+impl Status {
+    /// Read the report of the last run and the state from `state_dir`.
+    pub fn load(state_dir: &Path) -> Result<Self> {
+        let state = SyncState::load(state_dir)?;
+        Ok(Self {
+            last_run: SyncReport::load(state_dir)?,
+            quarantined: state
+                .quarantined()
+                .map(|(path, object)| (path.clone(), object.clone()))
+                .collect(),
+        })
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.last_run {
+            None => writeln!(f, "No run has finished yet")?,
+            Some(report) => {
+                write!(f, "Last run {}: {}", report.run_id, report.status.to_string().replace('_', " "))?;
+                if let Some(duration) = report.duration() {
+                    write!(f, " in {:.1}s", duration.as_secs_f64())?;
+                }
+                if let Some(finished_at) = report.finished_at {
+                    write!(f, ", finished {}", finished_at.format("%Y-%m-%d %H:%M:%S UTC"))?;
+                }
+                writeln!(f)?;
+                if report.mode == SyncMode::Mirror {
+                    writeln!(f, "  mirrored Rancher: {}", report.mirrored)?;
+                }
+                writeln!(
+                    f,
+                    "  {} updated, {} created, {} deleted, {} failed",
+                    report.updated, report.created, report.deleted, report.failed
+                )?;
+                for (cluster_id, counts) in &report.clusters {
+                    writeln!(
+                        f,
+                        "  {}: {} updated, {} created, {} deleted, {} failed",
+                        cluster_id, counts.updated, counts.created, counts.deleted, counts.failed
+                    )?;
+                }
+                if report.deferred > 0 {
+                    writeln!(f, "  {} changes left to the next runs by max_changes_per_run", report.deferred)?;
+                }
+                if !report.pending_drift.is_empty() {
+                    writeln!(f, "Pending drift:")?;
+                    for path in &report.pending_drift {
+                        writeln!(f, "  {}", path.display())?;
+                    }
+                }
+                if !report.unpersisted.is_empty() {
+                    writeln!(f, "Applied but not persisted locally:")?;
+                    for path in &report.unpersisted {
+                        writeln!(f, "  {}", path.display())?;
+                    }
+                }
+                if !report.stuck_terminating.is_empty() {
+                    writeln!(f, "Stuck terminating:")?;
+                    for stuck in &report.stuck_terminating {
+                        writeln!(f, "  {}", stuck)?;
+                    }
+                }
+                if !report.permission_denials.is_empty() {
+                    writeln!(f, "Permission problems:")?;
+                    for denial in &report.permission_denials {
+                        writeln!(f, "  {}", denial)?;
+                    }
+                }
+                if !report.timed_out.is_empty() {
+                    writeln!(f, "Timed out phases: {}", report.timed_out.join(", "))?;
+                }
+                writeln!(
+                    f,
+                    "Last pushed commit: {}",
+                    report.pushed_commit.as_deref().unwrap_or("<none>")
+                )?;
+            }
+        }
+        if self.quarantined.is_empty() {
+            writeln!(f, "No quarantined objects")
+        } else {
+            writeln!(f, "Quarantined objects:")?;
+            for (path, object) in &self.quarantined {
+                writeln!(f, "  {}: {}", path.display(), object)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Write `contents` to `path` through a temporary file, so readers never see a partial file.
+///
+/// The parent folder is created if needed.
+pub fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    std::fs::write(&partial, contents).with_context(|| format!("Failed to write {}", partial.display()))?;
+    std::fs::rename(&partial, path).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// The git blob ID of the file at `path`, `None` if it cannot be read.
+pub fn blob_id(path: &Path) -> Option<String> {
+    git2::Oid::hash_file(git2::ObjectType::Blob, path)
+        .ok()
+        .map(|oid| oid.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Access;
+
+    fn failure(path: &Path, error: &str) -> ObjectFailure {
+        ObjectFailure {
+            path: path.to_path_buf(),
+            object_type: ObjectType::ProjectRoleTemplateBinding,
+            object_id: Some("prtb-xyz".to_string()),
+            error: error.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_objects_failing_in_a_row_are_quarantined_until_their_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let failing = dir.path().join("prtb-xyz.prtb.yaml");
+        let flaky = dir.path().join("prtb-abc.prtb.yaml");
+        std::fs::write(&failing, "user_principal_name: nobody\n").unwrap();
+        let mut state = SyncState::default();
+
+        assert!(state.record_run(&[failure(&failing, "first"), failure(&flaky, "once")], 3).is_empty());
+        assert!(state.record_run(&[failure(&failing, "second")], 3).is_empty());
+        assert!(!state.objects.contains_key(&flaky));
+        assert_eq!(state.record_run(&[failure(&failing, "third")], 3), vec![failing.clone()]);
+
+        assert!(state.is_quarantined(&failing));
+        assert_eq!(state.objects[&failing].last_error, "third");
+        // quarantined objects are not attempted, a run without them keeps them
+        state.record_run(&[], 3);
+        assert!(state.release_changed().is_empty());
+        assert!(state.is_quarantined(&failing));
+
+        std::fs::write(&failing, "user_principal_name: somebody\n").unwrap();
+        assert_eq!(state.release_changed(), vec![failing.clone()]);
+        assert!(!state.is_quarantined(&failing));
+    }
+
+    #[test]
+    fn test_status_lists_the_last_run_and_quarantined_objects() {
+        let dir = tempfile::tempdir().unwrap();
+        let failing = PathBuf::from("/repo/c-123/p-abc/prtb-xyz.prtb.yaml");
+        let mut state = SyncState::default();
+        state.record_run(&[failure(&failing, "invalid subject")], 1);
+        state.save(dir.path()).unwrap();
+        let mut report = SyncReport::new("run-1");
+        report.started_at = "2026-10-16T10:00:00Z".parse().ok();
+        report.finished_at = "2026-10-16T10:00:03Z".parse().ok();
+        report.updated = 2;
+        report.failed = 1;
+        report.clusters.insert(
+            "c-123".to_string(),
+            crate::report::ObjectCounts { updated: 2, failed: 1, ..Default::default() },
+        );
+        report.pending_drift.push(PathBuf::from("/repo/c-123/p-abc/p-abc.project.yaml"));
+        report.unpersisted.push(PathBuf::from("/repo/c-123/p-abc/new.prtb.yaml"));
+        report.pushed_commit = Some("0123abc".to_string());
+        report.save(dir.path()).unwrap();
+
+        let status = Status::load(dir.path()).unwrap();
+
+        assert_eq!(
+            status.to_string(),
+            "Last run run-1: completed in 3.0s, finished 2026-10-16 10:00:03 UTC\n  \
+             2 updated, 0 created, 0 deleted, 1 failed\n  \
+             c-123: 2 updated, 0 created, 0 deleted, 1 failed\n\
+             Pending drift:\n  \
+             /repo/c-123/p-abc/p-abc.project.yaml\n\
+             Applied but not persisted locally:\n  \
+             /repo/c-123/p-abc/new.prtb.yaml\n\
+             Last pushed commit: 0123abc\n\
+             Quarantined objects:\n  \
+             /repo/c-123/p-abc/prtb-xyz.prtb.yaml: ProjectRoleTemplateBinding `prtb-xyz` failed 1 runs in a row: invalid subject\n"
+        );
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["last_run"]["status"], "completed");
+        assert_eq!(json["last_run"]["clusters"]["c-123"]["updated"], 2);
+        assert_eq!(
+            json["quarantined"]["/repo/c-123/p-abc/prtb-xyz.prtb.yaml"]["last_error"],
+            "invalid subject"
+        );
+        assert_eq!(Status::load(&dir.path().join("missing")).unwrap().to_string(), "No run has finished yet\nNo quarantined objects\n");
+    }
+
+    #[test]
+    fn test_a_backlog_counts_the_changes_of_every_run_until_none_are_left() {
+        let dir = tempfile::tempdir().unwrap();
+        let held_back = vec![PathBuf::from("/repo/c-123/p-abc/b.prtb.yaml")];
+        let backlog = ChangeBacklog::advance(None, "run-1", 500, 700, held_back.clone());
+        let state = SyncState { backlog, ..Default::default() };
+        state.save(dir.path()).unwrap();
+        assert!(state.metrics().contains("\nshepherd_deferred_changes 700\n"));
+
+        let state = SyncState::load(dir.path()).unwrap();
+        assert_eq!(state.backlog.as_ref().unwrap().held_back, held_back);
+        let backlog = ChangeBacklog::advance(state.backlog, "run-2", 500, 200, Vec::new()).unwrap();
+        assert_eq!(backlog.started_run_id, "run-1");
+        assert_eq!(backlog.to_string(), "1000 of 1200 changes applied");
+        assert!(backlog.held_back.is_empty());
+
+        assert_eq!(ChangeBacklog::advance(Some(backlog), "run-3", 200, 0, Vec::new()), None);
+    }
+
+    #[test]
+    fn test_the_mode_of_the_last_run_is_exported_and_shown() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = SyncState::default();
+        assert!(state.metrics().contains("\nshepherd_mode_info{mode=\"gitops\"} 1\n"));
+        state.mode = SyncMode::Mirror;
+        state.save(dir.path()).unwrap();
+        assert!(SyncState::load(dir.path()).unwrap().metrics().contains("\nshepherd_mode_info{mode=\"mirror\"} 1\n"));
+
+        let mut report = SyncReport::new("run-1");
+        report.mode = SyncMode::Mirror;
+        report.mirrored.added.push(PathBuf::from("/repo/c-123/p-abc/p-abc.project.yaml"));
+        report.mirrored.removed.push(PathBuf::from("/repo/c-123/crtb-old.crtb.yaml"));
+        report.save(dir.path()).unwrap();
+
+        let status = Status::load(dir.path()).unwrap().to_string();
+        assert!(status.contains("\n  mirrored Rancher: 1 added, 0 changed, 1 removed\n"), "{status}");
+    }
+
+    #[test]
+    fn test_quarantine_survives_a_restart_and_is_lifted_by_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let failing = dir.path().join("prtb-xyz.prtb.yaml");
+        let mut state = SyncState::default();
+        state.record_run(&[failure(&failing, "invalid subject")], 1);
+        state.save(dir.path()).unwrap();
+
+        let mut state = SyncState::load(dir.path()).unwrap();
+
+        assert!(state.is_quarantined(&failing));
+        assert!(state.metrics().contains("\nshepherd_quarantined_objects 1\n"));
+        assert!(!state.metrics().contains("shepherd_last_run_info"));
+        state.last_run_id = Some("20261016T100000.000Z".to_string());
+        assert!(state.metrics().contains("shepherd_last_run_info{run_id=\"20261016T100000.000Z\"} 1\n"));
+        state.server_version = Some("v2.8.5".to_string());
+        assert!(state
+            .metrics()
+            .contains("shepherd_last_run_info{run_id=\"20261016T100000.000Z\",rancher_version=\"v2.8.5\"} 1\n"));
+        assert!(state.unquarantine("prtb-other").is_empty());
+        assert_eq!(state.unquarantine("prtb-xyz"), [failing]);
+        assert_eq!(SyncState::load(&dir.path().join("missing")).unwrap(), SyncState::default());
+    }
+
+    #[test]
+    fn test_deleted_objects_are_tracked_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let minimal = |object_id: &str, namespace: &str| MinimalObject {
+            object_id: Some(object_id.to_string()),
+            resource_version_match: crate::models::ResourceVersionMatch::Exact,
+            resource_version: None,
+            namespace: Some(namespace.to_string()),
+        };
+        let deleted_at: DateTime<Utc> = "2026-10-16T10:00:00Z".parse().unwrap();
+        let mut state = SyncState::default();
+        state.record_deletions(
+            &[
+                (ObjectType::Project, minimal("p-abc", "c-123")),
+                (ObjectType::RoleTemplate, minimal("rt-abc", "")),
+            ],
+            deleted_at,
+        );
+        // deleted again, the later deletion replaces the earlier one
+        state.record_deletions(&[(ObjectType::Project, minimal("p-abc", "c-123"))], deleted_at);
+        state.save(dir.path()).unwrap();
+
+        let state = SyncState::load(dir.path()).unwrap();
+
+        assert_eq!(state.pending_deletions.len(), 2);
+        assert!(state.pending_deletion(ObjectType::Project, Some("c-123"), "p-abc").is_some());
+        assert!(state.pending_deletion(ObjectType::Project, Some("c-456"), "p-abc").is_none());
+        assert!(state.pending_deletion(ObjectType::RoleTemplate, None, "rt-abc").is_some());
+        let pending = state.pending_deletion(ObjectType::Project, Some("c-123"), "p-abc").unwrap();
+        assert_eq!(pending.to_string(), "Project `c-123/p-abc`");
+        assert_eq!(
+            pending.terminating_for("2026-10-16T11:30:00Z".parse().unwrap()),
+            Duration::from_secs(90 * 60)
+        );
+        let metrics = state.metrics();
+        assert!(metrics.contains("\nshepherd_terminating_objects 2\n"), "{}", metrics);
+        assert!(metrics.contains("shepherd_terminating_since_seconds{object_type=\"Project\",object_id=\"p-abc\"} 1792144800\n"), "{}", metrics);
+    }
+
+    #[test]
+    fn test_permission_denials_of_the_last_run_are_exported() {
+        let mut state = SyncState::default();
+        assert!(!state.metrics().contains("shepherd_permission_denied_calls"));
+
+        state.permission_denials.push(PermissionDenial {
+            status: 403,
+            access: Access::Write,
+            object_type: ObjectType::Project,
+            namespace: Some("c-abc".to_string()),
+            calls: 3,
+        });
+
+        assert!(state.metrics().ends_with(
+            "shepherd_permission_denied_calls{status=\"403\",access=\"write\",resource=\"projects\",namespace=\"c-abc\"} 3\n"
+        ));
+    }
+
+    #[test]
+    fn test_label_values_are_escaped() {
+        let mut state = SyncState {
+            last_run_id: Some("run-1".to_string()),
+            server_version: Some("v2.8.5 \"rc\"\nC:\\".to_string()),
+            ..SyncState::default()
+        };
+        state.permission_denials.push(PermissionDenial {
+            status: 403,
+            access: Access::Write,
+            object_type: ObjectType::Project,
+            namespace: Some("c-\"abc\"".to_string()),
+            calls: 1,
+        });
+
+        let metrics = state.metrics();
+
+        assert!(
+            metrics.contains("shepherd_last_run_info{run_id=\"run-1\",rancher_version=\"v2.8.5 \\\"rc\\\"\\nC:\\\\\"} 1\n"),
+            "{}",
+            metrics
+        );
+        assert!(metrics.contains("namespace=\"c-\\\"abc\\\"\"} 1\n"), "{}", metrics);
+    }
+
+    #[test]
+    fn test_creations_not_written_back_are_remembered_until_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = PathBuf::from("/repo/c-123/p-abc/new.prtb.yaml");
+        let creation = UnpersistedCreation {
+            object_type: ObjectType::ProjectRoleTemplateBinding,
+            namespace: Some("p-abc".to_string()),
+            path: path.clone(),
+            created_at: "2026-10-16T10:00:00Z".parse().unwrap(),
+            error: "No space left on device".to_string(),
+        };
+        let mut state = SyncState::default();
+        state.record_write_back(&[], vec![("prtb-generated".to_string(), creation.clone())]);
+        state.save(dir.path()).unwrap();
+
+        let mut state = SyncState::load(dir.path()).unwrap();
+
+        assert_eq!(state.unpersisted_at(&path), Some((&"prtb-generated".to_string(), &creation)));
+        assert!(state.unpersisted_at(Path::new("/repo/c-123/p-abc/other.prtb.yaml")).is_none());
+        assert!(state.metrics().contains("\nshepherd_unpersisted_objects 1\n"));
+        state.record_write_back(std::slice::from_ref(&path), Vec::new());
+        assert!(state.unpersisted.is_empty());
+        assert_eq!(creation.to_string(), "ProjectRoleTemplateBinding of /repo/c-123/p-abc/new.prtb.yaml: No space left on device");
+    }
+}
@@ -1,11 +1,36 @@
-use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
 use rancher_client::apis::configuration::Configuration;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::Value;
+use tokio::fs::create_dir_all;
+use tracing::error;
 
-use crate::models::{CreatedObject, MinimalObject, ObjectType, ResourceVersionMatch};
+use crate::models::{ConversionError, CreatedObject, DeleteParams, MinimalObject, ObjectType, ResourceVersionMatch, RetryPolicy};
+use crate::report::DownloadReport;
+use crate::utils::diff::MetadataFilter;
+use crate::utils::file::{get_file_name_for_object, FileFormat};
 use crate::utils::logging::log_api_error;
+use crate::utils::safe_path::ensure_within;
+use crate::{clean_up_value, retry_transient, serialize_object, write_downloaded};
+
+/// The annotations or labels of an object.
+pub type Metadata = Option<HashMap<String, String>>;
+
+/// Where a download writes the objects of a resource type.
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadTarget<'a> {
+    /// The folder the object files are written to
+    pub dir: &'a Path,
+    pub file_format: &'a FileFormat,
+    /// Annotations and labels left out of the written files
+    pub metadata_filter: &'a MetadataFilter,
+    /// The cluster the files are counted for in the report, `None` for the role templates
+    pub cluster_id: Option<&'a str>,
+}
 
 pub trait RancherResource: Sized + Clone + DeserializeOwned + Serialize {
     type ApiType: Clone + DeserializeOwned + Serialize;
@@ -13,6 +38,10 @@ pub trait RancherResource: Sized + Clone + DeserializeOwned + Serialize {
     // Resource metadata
     fn resource_type() -> ObjectType;
     fn exclude_paths() -> &'static [&'static str];
+    // The exclude paths also left out of the files the objects are written to
+    fn file_exclude_paths() -> &'static [&'static str] {
+        &[]
+    }
     
     // Conversion methods
     fn try_from_api(value: Self::ApiType) -> Result<Self>;
@@ -22,6 +51,9 @@ pub trait RancherResource: Sized + Clone + DeserializeOwned + Serialize {
     fn id(&self) -> Option<String>;
     fn namespace(&self) -> Option<String>;
     fn resource_version(&self) -> Option<String>;
+
+    // The annotations and labels of the object
+    fn metadata_mut(&mut self) -> (&mut Metadata, &mut Metadata);
     
     // Create a minimal object representation
     fn to_minimal_object(&self) -> MinimalObject {
@@ -33,6 +65,18 @@ pub trait RancherResource: Sized + Clone + DeserializeOwned + Serialize {
         }
     }
     
+    /// The object without the fields of its API form at [`RancherResource::file_exclude_paths`],
+    /// as it is written to its file.
+    fn for_file(&self) -> Result<Self> {
+        let paths = Self::file_exclude_paths();
+        if paths.is_empty() {
+            return Ok(self.clone());
+        }
+        let mut value = serde_json::to_value(self.clone().try_into_api()?)?;
+        clean_up_value(&mut value, paths);
+        Self::try_from_api(serde_json::from_value(value)?)
+    }
+
     // Common API operations with default implementations
     fn list(_config: &Configuration,_namespacee: Option<&str>) -> impl std::future::Future<Output = Result<Vec<Self::ApiType>>> + Send {async {
         // Default implementation would dispatch to the appropriate API call
@@ -44,18 +88,95 @@ pub trait RancherResource: Sized + Clone + DeserializeOwned + Serialize {
         unimplemented!("Get operation must be implemented by resource type")
     } }
     
-    fn create(&self, _config: &Configuration) -> impl std::future::Future<Output = Result<CreatedObject>> + Send {async {
+    fn create(&self, _config: &Configuration, _dry_run: bool) -> impl std::future::Future<Output = Result<CreatedObject>> + Send {async {
         unimplemented!("Create operation must be implemented by resource type")
     } }
     
-    fn update(&self, _config: &Configuration,_patch: Value) -> impl std::future::Future<Output = Result<CreatedObject>> + Send {async {
+    fn update(&self, _config: &Configuration,_patch: Value, _dry_run: bool) -> impl std::future::Future<Output = Result<CreatedObject>> + Send {async {
         unimplemented!("Update operation must be implemented by resource type")
     } }
     
-    fn delete(_config: &Configuration, _name: &str, _namespace: &str) -> impl std::future::Future<Output = Result<CreatedObject>> + Send {async {
+    fn delete(_config: &Configuration, _name: &str, _namespace: &str, _dry_run: bool, _params: DeleteParams) -> impl std::future::Future<Output = Result<CreatedObject>> + Send {async {
         unimplemented!("Delete operation must be implemented by resource type")
     } }
     
+    /// List the objects in `scope` the way [`RancherResource::list`] does, retrying transient
+    /// errors according to `retry_policy`, and convert those `keep` accepts.
+    fn fetch_all(
+        config: &Configuration,
+        scope: Option<&str>,
+        retry_policy: &RetryPolicy,
+        keep: impl Fn(&Self::ApiType) -> bool + Send + Sync,
+    ) -> impl std::future::Future<Output = Result<Vec<Self>>> + Send
+    where
+        Self: Send,
+        Self::ApiType: Send,
+    { async move {
+        let resource = Self::resource_type().resource();
+        let items = retry_transient(&format!("list {}", resource), retry_policy, || Self::list(config, scope))
+            .await
+            .with_context(|| format!("Failed to get {}", resource))?;
+        items
+            .into_iter()
+            .filter(|item| keep(item))
+            .map(|item| Self::try_from_api(item).with_context(|| format!("Failed to convert {}", resource)))
+            .collect()
+    } }
+
+    /// Write the object to its file in `target.dir`, named by [`get_file_name_for_object`].
+    ///
+    /// A file carrying the ignore directive is kept and counted as skipped in `report`. An
+    /// object whose ID would put its file outside of `target.dir` is logged and skipped.
+    fn save_to(&self, target: DownloadTarget<'_>, report: &mut DownloadReport) -> impl std::future::Future<Output = Result<()>> + Send
+    where
+        Self: Sync,
+    { async move {
+        let id = self
+            .id()
+            .ok_or(ConversionError::MissingField("metadata.name".into()))
+            .with_context(|| format!("Failed to read {} ID", Self::resource_type().resource()))?;
+        let file = target.dir.join(get_file_name_for_object(&id, &Self::resource_type(), target.file_format));
+        if let Err(e) = ensure_within(target.dir, &file) {
+            error!("Skipping {} `{}`: {}", Self::resource_type().resource(), id, e);
+            return Ok(());
+        }
+        let contents = serialize_object(&self.for_file()?, target.file_format)?;
+        write_downloaded(&file, contents, report, target.cluster_id)
+            .await
+            .with_context(|| format!("Failed to write file {:?}", file))
+    } }
+
+    /// Download the objects in `scope` that `keep` accepts into `target`, leaving out the
+    /// annotations and labels matched by its metadata filter.
+    ///
+    /// The folder is only created when there is an object to write to it.
+    fn download_all(
+        config: &Configuration,
+        scope: Option<&str>,
+        retry_policy: &RetryPolicy,
+        keep: impl Fn(&Self::ApiType) -> bool + Send + Sync,
+        target: DownloadTarget<'_>,
+        report: &mut DownloadReport,
+    ) -> impl std::future::Future<Output = Result<()>> + Send
+    where
+        Self: Send + Sync,
+        Self::ApiType: Send,
+    { async move {
+        let mut objects = Self::fetch_all(config, scope, retry_policy, keep).await?;
+        if !objects.is_empty() && !target.dir.exists() {
+            create_dir_all(target.dir)
+                .await
+                .with_context(|| format!("Failed to create {} folder", Self::resource_type().resource()))?;
+        }
+        for object in &mut objects {
+            let (annotations, labels) = object.metadata_mut();
+            target.metadata_filter.retain(annotations);
+            target.metadata_filter.retain(labels);
+            object.save_to(target, report).await?;
+        }
+        Ok(())
+    } }
+
     // Helper for handling API errors
     fn handle_api_error<T: std::fmt::Debug>(result: Result<T>, operation: &str) -> Result<T> {
         match result {
@@ -0,0 +1,318 @@
+//! The changes a run made to Rancher, kept so a failing run can be rolled back.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use rancher_client::apis::configuration::Configuration;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use crate::models::{DeletionOptions, MinimalObject, ObjectType, ResourceVersionMatch, RetryPolicy};
+use crate::modify::{delete_objects, recreate_object, restore_object};
+use crate::utils::file::FileFormat;
+
+/// A successful change to Rancher, with what it takes to undo it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "mutation", rename_all = "snake_case")]
+pub enum Mutation {
+    /// Undone by deleting the object and restoring the file it was created from
+    Created {
+        object_type: ObjectType,
+        object_id: String,
+        namespace: Option<String>,
+        /// The file the object was created from, rewritten with the ID Rancher assigned
+        path: PathBuf,
+        /// The contents of the file before the write-back
+        original: String,
+    },
+    /// Undone by replacing the object with its live state from before the update
+    Updated {
+        object_type: ObjectType,
+        object_id: String,
+        namespace: Option<String>,
+        /// The object in API form as it was before the update
+        before: Value,
+        /// The resource version the update left, the object must still have it to be restored
+        resource_version: Option<String>,
+    },
+    /// Undone by creating the object again from its last committed file
+    Deleted {
+        object_type: ObjectType,
+        path: PathBuf,
+        contents: String,
+        file_format: FileFormat,
+    },
+}
+
+impl Mutation {
+    fn kind(&self) -> &'static str {
+        match self {
+            Mutation::Created { .. } => "created",
+            Mutation::Updated { .. } => "updated",
+            Mutation::Deleted { .. } => "deleted",
+        }
+    }
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mutation::Created { object_type, object_id, .. } | Mutation::Updated { object_type, object_id, .. } => {
+                write!(f, "{} {:?} `{}`", self.kind(), object_type, object_id)
+            }
+            Mutation::Deleted { object_type, path, .. } => {
+                write!(f, "deleted {:?} of {}", object_type, path.display())
+            }
+        }
+    }
+}
+
+/// The mutations of a single run, each backed up to disk as soon as it is recorded.
+///
+/// The backups live in `<state_dir>/backups/<run_id>` and are removed once the run
+/// is committed or rolled back without errors.
+#[derive(Debug)]
+pub struct Transaction {
+    backup_dir: PathBuf,
+    mutations: Vec<Mutation>,
+}
+
+/// What rolling back a transaction restored and what it could not.
+#[derive(Debug, Default)]
+pub struct Rollback {
+    /// The files whose contents from before the write-back were restored
+    pub restored_files: Vec<PathBuf>,
+    pub errors: Vec<anyhow::Error>,
+}
+
+impl Transaction {
+    pub fn new(state_dir: &Path, run_id: &str) -> Self {
+        Self {
+            backup_dir: state_dir.join("backups").join(run_id),
+            mutations: Vec::new(),
+        }
+    }
+
+    /// The folder the mutations are backed up to.
+    pub fn backup_dir(&self) -> &Path {
+        &self.backup_dir
+    }
+
+    pub fn mutations(&self) -> &[Mutation] {
+        &self.mutations
+    }
+
+    /// Record `mutation`, writing its backup before it is kept.
+    ///
+    /// # Errors
+    /// If the backup cannot be written, the mutation is kept in memory regardless
+    pub fn record(&mut self, mutation: Mutation) -> Result<()> {
+        let path = self
+            .backup_dir
+            .join(format!("{:04}-{}.json", self.mutations.len() + 1, mutation.kind()));
+        debug!("Recording {} in {}", mutation, path.display());
+        self.mutations.push(mutation);
+
+        std::fs::create_dir_all(&self.backup_dir)
+            .with_context(|| format!("Failed to create {}", self.backup_dir.display()))?;
+        let contents = serde_json::to_string_pretty(self.mutations.last().unwrap())?;
+        std::fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Keep the mutations and remove their backups.
+    pub fn commit(self) -> Result<()> {
+        remove_backups(&self.backup_dir)
+    }
+
+    /// Undo every mutation, the last one first.
+    ///
+    /// Deleting an object that is already gone or creating one that already exists counts
+    /// as undone, as the object is where it was before the run. The backups are only
+    /// removed if every mutation was undone.
+    ///
+    /// # Arguments
+    /// * `configuration` - The configuration of the Rancher API client
+    /// * `retry_policy` - How deletions failing with a transient error are retried
+    pub async fn roll_back(self, configuration: Arc<Configuration>, retry_policy: &RetryPolicy) -> Rollback {
+        let mut rollback = Rollback::default();
+        for mutation in self.mutations.iter().rev() {
+            info!("Rolling back {}", mutation);
+            match undo(&configuration, mutation, retry_policy).await {
+                Ok(Some(path)) => rollback.restored_files.push(path),
+                Ok(None) => {}
+                Err(e) => rollback.errors.push(e.context(format!("Failed to roll back {}", mutation))),
+            }
+        }
+        if rollback.errors.is_empty() {
+            if let Err(e) = remove_backups(&self.backup_dir) {
+                warn!("{:#}", e);
+            }
+        }
+        rollback
+    }
+}
+
+/// Undo `mutation`, returning the file it restored, if any.
+async fn undo(configuration: &Arc<Configuration>, mutation: &Mutation, retry_policy: &RetryPolicy) -> Result<Option<PathBuf>> {
+    match mutation {
+        Mutation::Created { object_type, object_id, namespace, path, original } => {
+            let object = MinimalObject {
+                object_id: Some(object_id.clone()),
+                resource_version_match: ResourceVersionMatch::NotOlderThan,
+                resource_version: None,
+                namespace: namespace.clone(),
+            };
+            let deleted = delete_objects(
+                configuration.clone(),
+                vec![(*object_type, object)],
+                false,
+                &DeletionOptions::default(),
+                retry_policy,
+                false,
+            )
+                .await
+                .pop()
+                .unwrap_or_else(|| Err(anyhow::anyhow!("Nothing was deleted")));
+            match deleted {
+                Ok(_) => {}
+                Err(e) if is_not_found(&e) => debug!("{:?} `{}` is already gone", object_type, object_id),
+                Err(e) => return Err(e),
+            }
+            tokio::fs::write(path, original)
+                .await
+                .with_context(|| format!("Failed to restore {}", path.display()))?;
+            Ok(Some(path.clone()))
+        }
+        Mutation::Updated { object_type, object_id, namespace, before, resource_version } => {
+            restore_object(
+                configuration.clone(),
+                *object_type,
+                object_id,
+                namespace.clone(),
+                before.clone(),
+                resource_version.clone(),
+            )
+            .await?;
+            Ok(None)
+        }
+        Mutation::Deleted { object_type, path, contents, file_format } => {
+            match recreate_object(configuration, *object_type, path, contents, file_format).await {
+                Ok(_) => Ok(None),
+                Err(e) if format!("{:#}", e).contains("already exists") => {
+                    debug!("The {:?} of {} still exists", object_type, path.display());
+                    Ok(None)
+                }
+                Err(e) => Err(e),
+            }
+        }
+    }
+}
+
+fn is_not_found(e: &anyhow::Error) -> bool {
+    format!("{:#}", e).contains("not found")
+}
+
+fn remove_backups(backup_dir: &Path) -> Result<()> {
+    match std::fs::remove_dir_all(backup_dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove the backups in {}", backup_dir.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn project(resource_version: &str, display_name: &str) -> Value {
+        json!({
+            "apiVersion": "management.cattle.io/v3",
+            "kind": "Project",
+            "metadata": {"name": "p-abc", "namespace": "c-123", "resourceVersion": resource_version},
+            "spec": {"clusterName": "c-123", "displayName": display_name},
+        })
+    }
+
+    #[test]
+    fn test_mutations_are_backed_up_until_committed() {
+        let state_dir = tempfile::tempdir().unwrap();
+        let mut transaction = Transaction::new(state_dir.path(), "run-1");
+        let updated = Mutation::Updated {
+            object_type: ObjectType::Project,
+            object_id: "p-abc".to_string(),
+            namespace: Some("c-123".to_string()),
+            before: project("1", "team-a"),
+            resource_version: Some("2".to_string()),
+        };
+
+        transaction.record(updated.clone()).unwrap();
+
+        let backup = state_dir.path().join("backups/run-1/0001-updated.json");
+        let restored: Mutation = serde_json::from_str(&std::fs::read_to_string(&backup).unwrap()).unwrap();
+        assert_eq!(restored, updated);
+        transaction.commit().unwrap();
+        assert!(!state_dir.path().join("backups/run-1").exists());
+    }
+
+    #[tokio::test]
+    async fn test_roll_back_undoes_in_reverse_and_keeps_backups_on_failure() {
+        let server = MockServer::start().await;
+        let projects = "/apis/management.cattle.io/v3/namespaces/c-123/projects";
+        // the update is undone with the state before it and the version it left
+        Mock::given(method("PUT"))
+            .and(path(format!("{}/p-abc", projects)))
+            .and(body_partial_json(project("2", "team-a")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(project("3", "team-a")))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path(format!("{}/p-new", projects)))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&server)
+            .await;
+        let configuration = Arc::new(Configuration {
+            base_path: server.uri(),
+            ..Configuration::default()
+        });
+
+        let state_dir = tempfile::tempdir().unwrap();
+        let file = state_dir.path().join("new.project.yaml");
+        std::fs::write(&file, "id: p-new\n").unwrap();
+        let mut transaction = Transaction::new(state_dir.path(), "run-1");
+        transaction
+            .record(Mutation::Updated {
+                object_type: ObjectType::Project,
+                object_id: "p-abc".to_string(),
+                namespace: Some("c-123".to_string()),
+                before: project("1", "team-a"),
+                resource_version: Some("2".to_string()),
+            })
+            .unwrap();
+        transaction
+            .record(Mutation::Created {
+                object_type: ObjectType::Project,
+                object_id: "p-new".to_string(),
+                namespace: Some("c-123".to_string()),
+                path: file.clone(),
+                original: "display_name: team-b\n".to_string(),
+            })
+            .unwrap();
+
+        let rollback = transaction.roll_back(configuration, &RetryPolicy::default()).await;
+
+        assert_eq!(rollback.errors.len(), 1);
+        assert!(format!("{:#}", rollback.errors[0]).starts_with("Failed to roll back created Project `p-new`"));
+        // the file of the object that could not be deleted keeps the ID it was written back with
+        assert!(rollback.restored_files.is_empty());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "id: p-new\n");
+        assert!(state_dir.path().join("backups/run-1/0002-created.json").exists());
+    }
+}
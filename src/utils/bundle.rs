@@ -0,0 +1,263 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::models::ConversionError;
+use crate::resources::project::Project;
+use crate::resources::prtb::ProjectRoleTemplateBinding;
+use crate::utils::file::FileFormat;
+use crate::utils::yaml_edit::merge_preserving_comments;
+use crate::{deserialize_object, serialize_object};
+
+/// The suffix of a file holding a project and its bindings, `<project-id>.bundle.yaml`.
+pub const BUNDLE_SUFFIX: &str = ".bundle.yaml";
+
+/// The `kind` of the project document of a bundle.
+const PROJECT_KIND: &str = "Project";
+
+/// The `kind` of the binding documents of a bundle.
+const PRTB_KIND: &str = "ProjectRoleTemplateBinding";
+
+/// Whether `path` is the bundle of a project.
+pub fn is_bundle(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(BUNDLE_SUFFIX))
+}
+
+/// The file name of the bundle of the project with `project_id`.
+pub fn bundle_file_name(project_id: &str) -> String {
+    format!("{}{}", project_id, BUNDLE_SUFFIX)
+}
+
+/// The ID of the project of the bundle at `path`, taken from the file name.
+pub fn bundle_project_id(path: &Path) -> Option<&str> {
+    path.file_name()?.to_str()?.strip_suffix(BUNDLE_SUFFIX)
+}
+
+/// A document of a bundle.
+struct Document<'a> {
+    kind: Option<String>,
+    /// The text of the document with its `kind` line blanked, so it parses as the object
+    /// and its lines keep their numbers
+    text: String,
+    /// The number of lines of the bundle before the document
+    offset: usize,
+    /// The document as written, `kind` line included
+    raw: &'a str,
+}
+
+/// Split `contents` into its documents on `---` lines, leaving out empty documents.
+fn split(contents: &str) -> Vec<Document<'_>> {
+    let mut documents = Vec::new();
+    let (mut start, mut position, mut offset) = (0, 0, 0);
+    for (index, line) in contents.split_inclusive('\n').enumerate() {
+        if line.trim_end() == "---" {
+            documents.extend(parse_document(&contents[start..position], offset));
+            start = position + line.len();
+            offset = index + 1;
+        }
+        position += line.len();
+    }
+    documents.extend(parse_document(&contents[start..], offset));
+    documents
+}
+
+fn parse_document(raw: &str, offset: usize) -> Option<Document<'_>> {
+    if raw.lines().all(|line| line.trim().is_empty() || line.trim_start().starts_with('#')) {
+        return None;
+    }
+    // read from its line, a document that does not parse is still routed and its error located
+    let kind = raw
+        .lines()
+        .find_map(|line| line.strip_prefix("kind:"))
+        .map(|kind| kind.trim().trim_matches(|c| c == '"' || c == '\'').to_string());
+    let text = raw
+        .lines()
+        .map(|line| if line.starts_with("kind:") { "" } else { line })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(Document { kind, text, offset, raw })
+}
+
+/// Move the location of a deserialize error from its document to the bundle at `path`.
+fn in_bundle(error: ConversionError, path: &Path, offset: usize) -> ConversionError {
+    match error.at(path) {
+        ConversionError::Deserialize { format, path, line, column, message } => ConversionError::Deserialize {
+            format,
+            path,
+            line: line.map(|line| line + offset),
+            column,
+            message,
+        },
+        other => other,
+    }
+}
+
+/// Read the project and the bindings of the bundle at `path`, routing each document by its `kind`.
+///
+/// # Errors
+/// [`ConversionError::Deserialize`] at the line of the bundle the parser stopped at, or if
+/// the bundle does not hold exactly one project or a document has another `kind`
+pub fn read_bundle(contents: &str, path: &Path) -> Result<(Project, Vec<ProjectRoleTemplateBinding>), ConversionError> {
+    let invalid = |offset: usize, message: String| ConversionError::Deserialize {
+        format: FileFormat::Yaml,
+        path: Some(path.to_path_buf()),
+        line: Some(offset + 1),
+        column: None,
+        message,
+    };
+    let mut project = None;
+    let mut prtbs = Vec::new();
+    for document in split(contents) {
+        match document.kind.as_deref() {
+            Some(PROJECT_KIND) if project.is_some() => {
+                return Err(invalid(document.offset, "a bundle holds a single project".to_string()));
+            }
+            Some(PROJECT_KIND) => {
+                let parsed = deserialize_object::<Project>(&document.text, &FileFormat::Yaml, Some(path))
+                    .map_err(|e| in_bundle(e, path, document.offset))?;
+                project = Some(parsed);
+            }
+            Some(PRTB_KIND) => prtbs.push(
+                deserialize_object::<ProjectRoleTemplateBinding>(&document.text, &FileFormat::Yaml, Some(path))
+                    .map_err(|e| in_bundle(e, path, document.offset))?,
+            ),
+            Some(kind) => {
+                return Err(invalid(
+                    document.offset,
+                    format!("unknown kind `{}`, expected `{}` or `{}`", kind, PROJECT_KIND, PRTB_KIND),
+                ));
+            }
+            None => {
+                return Err(invalid(
+                    document.offset,
+                    format!("document without a kind, expected `{}` or `{}`", PROJECT_KIND, PRTB_KIND),
+                ));
+            }
+        }
+    }
+    let project = project.ok_or_else(|| invalid(0, "a bundle needs a project document".to_string()))?;
+    Ok((project, prtbs))
+}
+
+/// The project document of a bundle, without its `kind` line, to read like a project file.
+pub fn project_document(contents: &str) -> Option<String> {
+    split(contents)
+        .into_iter()
+        .find(|document| document.kind.as_deref() == Some(PROJECT_KIND))
+        .map(|document| document.text)
+}
+
+fn document(kind: &str, object: &impl serde::Serialize) -> Result<String> {
+    Ok(format!("kind: {}\n{}", kind, serialize_object(object, &FileFormat::Yaml)?))
+}
+
+/// Write a bundle of `project` followed by `prtbs` ordered by ID, so a download of the same
+/// objects always writes the same file.
+///
+/// # Errors
+/// If an object cannot be serialized
+pub fn write_bundle(project: &Project, prtbs: &[ProjectRoleTemplateBinding]) -> Result<String> {
+    let mut prtbs: Vec<&ProjectRoleTemplateBinding> = prtbs.iter().collect();
+    prtbs.sort_by(|a, b| a.id.cmp(&b.id));
+    let mut documents = vec![document(PROJECT_KIND, project)?];
+    for prtb in prtbs {
+        documents.push(document(PRTB_KIND, prtb)?);
+    }
+    Ok(documents.join("---\n"))
+}
+
+/// Replace the project document of the bundle `contents` with `project`, keeping its comments
+/// where it can, and leave the binding documents as they are.
+///
+/// # Errors
+/// If the bundle has no project document or `project` cannot be serialized
+pub fn replace_project(contents: &str, project: &Project) -> Result<String> {
+    let documents = split(contents);
+    if !documents.iter().any(|document| document.kind.as_deref() == Some(PROJECT_KIND)) {
+        return Err(anyhow!("The bundle has no project document"));
+    }
+    let documents = documents
+        .iter()
+        .map(|existing| match existing.kind.as_deref() {
+            Some(PROJECT_KIND) => {
+                let written = document(PROJECT_KIND, project)?;
+                Ok(merge_preserving_comments(existing.raw, &written).unwrap_or(written))
+            }
+            _ => Ok(existing.raw.to_string()),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(documents.join("---\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project() -> Project {
+        deserialize_object(
+            "id: p-abc\ncluster_name: c-123\ndisplay_name: web\nnamespace: c-123\n",
+            &FileFormat::Yaml,
+            None,
+        )
+        .unwrap()
+    }
+
+    fn prtb(id: &str) -> ProjectRoleTemplateBinding {
+        deserialize_object(
+            &format!("id: {id}\nnamespace: p-abc\nproject_name: c-123:p-abc\nrole_template_name: project-member\nuser_name: u-{id}\n"),
+            &FileFormat::Yaml,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_bundle_round_trips_in_a_deterministic_order() {
+        let written = write_bundle(&project(), &[prtb("prtb-b"), prtb("prtb-a")]).unwrap();
+        let path = Path::new("c-123/p-abc.bundle.yaml");
+
+        let (read_project, prtbs) = read_bundle(&written, path).unwrap();
+
+        assert!(written.starts_with("kind: Project\n"));
+        assert_eq!(written.matches("---\n").count(), 2);
+        assert_eq!(read_project, project());
+        assert_eq!(prtbs, [prtb("prtb-a"), prtb("prtb-b")]);
+        assert_eq!(write_bundle(&project(), &prtbs).unwrap(), written);
+        assert_eq!(bundle_project_id(path), Some("p-abc"));
+        assert!(is_bundle(path));
+    }
+
+    #[test]
+    fn test_documents_are_routed_by_kind_and_errors_point_into_the_bundle() {
+        let path = Path::new("p-abc.bundle.yaml");
+        let contents = format!(
+            "kind: ProjectRoleTemplateBinding\n{}---\nkind: Project\n{}",
+            serialize_object(&prtb("prtb-a"), &FileFormat::Yaml).unwrap(),
+            serialize_object(&project(), &FileFormat::Yaml).unwrap(),
+        );
+        let (read_project, prtbs) = read_bundle(&contents, path).unwrap();
+        assert_eq!(read_project.id.as_deref(), Some("p-abc"));
+        assert_eq!(prtbs.len(), 1);
+
+        let broken = "kind: Project\nid: p-abc\ncluster_name: c-123\ndisplay_name: web\nnamespace: c-123\n---\nkind: ProjectRoleTemplateBinding\nid: [\n";
+        let error = read_bundle(broken, path).unwrap_err();
+        assert_eq!(error.to_string().split(':').take(2).collect::<Vec<_>>(), ["p-abc.bundle.yaml", "8"], "{}", error);
+
+        let unknown = "kind: Project\nid: p-abc\ncluster_name: c-123\ndisplay_name: web\nnamespace: c-123\n---\nkind: Secret\nid: s\n";
+        assert!(read_bundle(unknown, path).unwrap_err().to_string().contains("unknown kind `Secret`"));
+    }
+
+    #[test]
+    fn test_replacing_the_project_keeps_the_bindings_as_written() {
+        let contents = "kind: Project\nid: '' # set by Rancher\ncluster_name: c-123\n---\n# hand written\nkind: ProjectRoleTemplateBinding\nid: prtb-a\n";
+
+        let replaced = replace_project(contents, &project()).unwrap();
+
+        assert!(replaced.starts_with("kind: Project\n"));
+        assert!(replaced.contains("id: p-abc # set by Rancher\n"), "{}", replaced);
+        assert!(replaced.ends_with("---\n# hand written\nkind: ProjectRoleTemplateBinding\nid: prtb-a\n"));
+        assert_eq!(project_document(&replaced).map(|text| text.contains("display_name: web")), Some(true));
+    }
+}
@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use serde_yaml::{Mapping, Value};
+
+/// The fields an object file starts with, in this order: its ID, then the fields naming it.
+const LEADING_FIELDS: &[&str] = &["id", "display_name", "cluster_name", "namespace", "project_name"];
+
+/// The fields an object file ends with, in this order: its metadata, then the bookkeeping
+/// Rancher sets on it.
+const TRAILING_FIELDS: &[&str] = &["annotations", "labels", "resource_version", "uid"];
+
+/// Where the top-level field `key` goes: the leading fields, the other fields in the order of
+/// the struct, then the trailing fields.
+fn rank(key: &Value) -> (u8, usize) {
+    let key = key.as_str().unwrap_or_default();
+    if let Some(index) = LEADING_FIELDS.iter().position(|field| *field == key) {
+        (0, index)
+    } else if let Some(index) = TRAILING_FIELDS.iter().position(|field| *field == key) {
+        (2, index)
+    } else {
+        (1, 0)
+    }
+}
+
+/// `value` with the keys of every mapping in it sorted.
+fn sorted(value: Value) -> Value {
+    match value {
+        Value::Mapping(mapping) => {
+            let mut entries: Vec<(Value, Value)> = mapping.into_iter().map(|(key, value)| (key, sorted(value))).collect();
+            entries.sort_by(|(a, _), (b, _)| a.as_str().cmp(&b.as_str()));
+            Value::Mapping(entries.into_iter().collect())
+        }
+        Value::Sequence(items) => Value::Sequence(items.into_iter().map(sorted).collect()),
+        other => other,
+    }
+}
+
+/// `object` in the fixed field order of object files, so writing the same object always
+/// gives the same file whatever order Rancher or a hash map hands its fields in.
+///
+/// The top-level fields are ordered `id`, `display_name`, `cluster_name`, `namespace`,
+/// `project_name`, then the other fields in the order of the struct, then `annotations`,
+/// `labels`, `resource_version` and `uid`. The keys of every nested mapping, such as the
+/// annotations, labels and the fields of role template rules, are sorted.
+///
+/// # Errors
+/// If `object` cannot be represented as YAML
+pub fn canonical_value<T: serde::Serialize>(object: &T) -> Result<Value> {
+    let value = serde_yaml::to_value(object).context("Failed to serialize object")?;
+    let Value::Mapping(fields) = value else {
+        return Ok(sorted(value));
+    };
+    let mut fields: Vec<(Value, Value)> = fields.into_iter().map(|(key, value)| (key, sorted(value))).collect();
+    // a stable sort keeps the struct order within each rank
+    fields.sort_by_key(|(key, _)| rank(key));
+    Ok(Value::Mapping(fields.into_iter().collect::<Mapping>()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resources::cluster::Cluster;
+    use crate::resources::crtb::ClusterRoleTemplateBinding;
+    use crate::resources::project::Project;
+    use crate::resources::prtb::ProjectRoleTemplateBinding;
+    use crate::resources::rt::RoleTemplate;
+    use crate::utils::file::FileFormat;
+    use crate::{deserialize_object, serialize_object};
+
+    /// Read `golden` and write it back in `file_format`, which must give `golden` again.
+    fn assert_golden<T: serde::Serialize + serde::de::DeserializeOwned>(golden: &str, file_format: FileFormat) {
+        let object: T = deserialize_object(golden, &file_format, None).unwrap();
+        assert_eq!(serialize_object(&object, &file_format).unwrap(), golden);
+    }
+
+    #[test]
+    fn test_project_golden_file() {
+        assert_golden::<Project>(include_str!("../../tests/golden/project.yaml"), FileFormat::Yaml);
+        assert_golden::<Project>(include_str!("../../tests/golden/project.json"), FileFormat::Json);
+    }
+
+    #[test]
+    fn test_prtb_golden_file() {
+        assert_golden::<ProjectRoleTemplateBinding>(include_str!("../../tests/golden/prtb.yaml"), FileFormat::Yaml);
+    }
+
+    #[test]
+    fn test_crtb_golden_file() {
+        assert_golden::<ClusterRoleTemplateBinding>(include_str!("../../tests/golden/crtb.yaml"), FileFormat::Yaml);
+    }
+
+    #[test]
+    fn test_role_template_golden_file() {
+        assert_golden::<RoleTemplate>(include_str!("../../tests/golden/rt.yaml"), FileFormat::Yaml);
+    }
+
+    #[test]
+    fn test_cluster_golden_file() {
+        assert_golden::<Cluster>(include_str!("../../tests/golden/cluster.yaml"), FileFormat::Yaml);
+    }
+
+    #[test]
+    fn test_maps_are_sorted_whatever_order_they_come_in() {
+        let project: Project = deserialize_object(include_str!("../../tests/golden/project.yaml"), &FileFormat::Yaml, None).unwrap();
+        for _ in 0..8 {
+            // a new hash map iterates in a new order
+            let mut shuffled = project.clone();
+            shuffled.labels = project.labels.clone().map(|labels| labels.into_iter().collect());
+            shuffled.annotations = project.annotations.clone().map(|annotations| annotations.into_iter().collect());
+            assert_eq!(
+                serialize_object(&shuffled, &FileFormat::Yaml).unwrap(),
+                include_str!("../../tests/golden/project.yaml")
+            );
+        }
+    }
+}
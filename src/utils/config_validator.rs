@@ -1,5 +1,387 @@
-pub fn validate_configuration(config_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    // loop through the config file
-    
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::models::{ConversionError, ObjectType};
+use crate::resources::crtb::ClusterRoleTemplateBinding;
+use crate::resources::project::Project;
+use crate::resources::prtb::ProjectRoleTemplateBinding;
+use crate::utils::file::parse_object_file_name;
+use crate::utils::principal::PrincipalRules;
+
+/// Annotation that lets a project share its display name with another project of its cluster.
+pub const ALLOW_DUPLICATE_DISPLAY_NAME_ANNOTATION: &str = "shepherd.cattle.io/allow-duplicate-display-name";
+
+/// How a broken validation rule is reported.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Log the violation and keep syncing
+    #[default]
+    Warning,
+    /// Log the violation and skip the cluster for the run
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A validation rule broken by the files in the repository.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub rule: &'static str,
+    pub message: String,
+    /// The files involved, so the objects they describe can be held back
+    pub paths: Vec<PathBuf>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.rule, self.message)
+    }
+}
+
+/// Projects of one cluster sharing a display name.
+///
+/// `files` are the project files of the cluster, `remote` the projects Rancher lists for it.
+/// A remote project with the ID of a file is the same project and counted once. Files
+/// carrying the [`ALLOW_DUPLICATE_DISPLAY_NAME_ANNOTATION`] are left out of the check.
+pub fn duplicate_project_display_names(files: &[(PathBuf, Project)], remote: &[Project]) -> Vec<Violation> {
+    // the file of each project, if any, and its ID
+    type Named<'a> = (Option<&'a PathBuf>, Option<&'a str>);
+    let mut by_display_name: BTreeMap<&str, Vec<Named>> = BTreeMap::new();
+
+    for (path, project) in files {
+        let allowed = project
+            .annotations
+            .as_ref()
+            .and_then(|annotations| annotations.get(ALLOW_DUPLICATE_DISPLAY_NAME_ANNOTATION))
+            .is_some_and(|value| value.eq_ignore_ascii_case("true"));
+        if !allowed {
+            by_display_name
+                .entry(&project.display_name)
+                .or_default()
+                .push((Some(path), project.id.as_deref()));
+        }
+    }
+    for project in remote {
+        let in_files = files
+            .iter()
+            .any(|(_, file)| file.id.is_some() && file.id == project.id);
+        if !in_files {
+            by_display_name
+                .entry(&project.display_name)
+                .or_default()
+                .push((None, project.id.as_deref()));
+        }
+    }
+
+    by_display_name
+        .into_iter()
+        .filter(|(_, projects)| projects.len() > 1)
+        .map(|(display_name, projects)| {
+            let described: Vec<String> = projects
+                .iter()
+                .map(|(path, id)| match path {
+                    Some(path) => format!("{} ({})", path.display(), id.unwrap_or("new")),
+                    None => format!("{} (only in Rancher)", id.unwrap_or_default()),
+                })
+                .collect();
+            Violation {
+                rule: "duplicate-project-display-name",
+                message: format!(
+                    "{} projects are named `{}`: {}",
+                    projects.len(),
+                    display_name,
+                    described.join(", ")
+                ),
+                paths: projects.iter().filter_map(|(path, _)| path.cloned()).collect(),
+            }
+        })
+        .collect()
+}
+
+/// New project role template bindings granting a subject a role template it already has in the
+/// project.
+///
+/// `files` are the new binding files, `remote` the bindings Rancher lists for their projects. A
+/// file duplicates a binding of `remote`, or a file before it, that is the same by
+/// [`ProjectRoleTemplateBinding::same_binding`] with `principal_rules`, such as one naming the
+/// same directory principal in another case.
+pub fn duplicate_bindings(
+    files: &[(PathBuf, ProjectRoleTemplateBinding)],
+    remote: &[ProjectRoleTemplateBinding],
+    principal_rules: &PrincipalRules,
+) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for (index, (path, prtb)) in files.iter().enumerate() {
+        let in_rancher = remote
+            .iter()
+            .find(|binding| binding.same_binding(prtb, principal_rules))
+            .map(|binding| format!("binding `{}`", binding.id));
+        let in_files = || {
+            files[..index]
+                .iter()
+                .find(|(_, file)| file.same_binding(prtb, principal_rules))
+                .map(|(path, _)| path.display().to_string())
+        };
+        let Some(duplicated) = in_rancher.or_else(in_files) else {
+            continue;
+        };
+        let subject = [&prtb.user_principal_name, &prtb.group_principal_name, &prtb.user_name, &prtb.group_name, &prtb.service_account]
+            .into_iter()
+            .flatten()
+            .next()
+            .map_or("no subject", String::as_str);
+        violations.push(Violation {
+            rule: "duplicate-binding",
+            message: format!(
+                "{} grants `{}` to `{}` in project `{}` like {}",
+                path.display(),
+                prtb.role_template_name,
+                subject,
+                prtb.namespace,
+                duplicated
+            ),
+            paths: vec![path.clone()],
+        });
+    }
+    violations
+}
+
+/// An object naming the cluster it belongs to, which must be the cluster of the folder holding its file.
+pub trait ClusterScoped {
+    /// The fields naming the cluster and the cluster each names.
+    fn named_clusters(&self) -> Vec<(&'static str, &str)>;
+
+    /// Name `cluster_id` in every field naming the cluster.
+    fn move_to_cluster(&mut self, cluster_id: &str);
+}
+
+impl ClusterScoped for Project {
+    fn named_clusters(&self) -> Vec<(&'static str, &str)> {
+        vec![("cluster_name", &self.cluster_name), ("namespace", &self.namespace)]
+    }
+
+    fn move_to_cluster(&mut self, cluster_id: &str) {
+        self.cluster_name = cluster_id.to_string();
+        self.namespace = cluster_id.to_string();
+    }
+}
+
+impl ClusterScoped for ProjectRoleTemplateBinding {
+    fn named_clusters(&self) -> Vec<(&'static str, &str)> {
+        // `<cluster id>:<project id>`, a name without a cluster is left to Rancher to reject
+        match self.project_name.split_once(':') {
+            Some((cluster_id, _)) => vec![("project_name", cluster_id)],
+            None => Vec::new(),
+        }
+    }
+
+    fn move_to_cluster(&mut self, cluster_id: &str) {
+        if let Some((_, project_id)) = self.project_name.split_once(':') {
+            self.project_name = format!("{}:{}", cluster_id, project_id);
+        }
+    }
+}
+
+impl ClusterScoped for ClusterRoleTemplateBinding {
+    fn named_clusters(&self) -> Vec<(&'static str, &str)> {
+        vec![("cluster_name", &self.cluster_name), ("namespace", &self.namespace)]
+    }
+
+    fn move_to_cluster(&mut self, cluster_id: &str) {
+        self.cluster_name = cluster_id.to_string();
+        self.namespace = cluster_id.to_string();
+    }
+}
+
+/// The ID of the cluster whose folder holds the object file at `path`, named by the cluster
+/// file of the project, bundle or binding folder above it.
+///
+/// `None` if neither the folder of the file nor its parent holds a cluster file.
+pub fn folder_cluster_id(path: &Path) -> Option<String> {
+    path.ancestors().skip(1).take(2).find_map(|folder| {
+        std::fs::read_dir(folder).ok()?.flatten().find_map(|entry| {
+            match parse_object_file_name(entry.file_name().to_str()?)? {
+                (ObjectType::Cluster, cluster_id, _) => Some(cluster_id),
+                _ => None,
+            }
+        })
+    })
+}
+
+/// Check that `object`, read from the file at `path` in the folder of `cluster_id`, names
+/// that cluster, such as a project copied from the folder of another cluster.
+///
+/// With `correct` a mismatch is logged and `object` moved to `cluster_id`.
+///
+/// # Errors
+/// [`ConversionError::Misplaced`] naming the first mismatching field, unless `correct`
+pub fn check_cluster_placement<T: ClusterScoped>(
+    path: &Path,
+    cluster_id: &str,
+    object: &mut T,
+    correct: bool,
+) -> Result<(), ConversionError> {
+    let Some((field, named)) = object
+        .named_clusters()
+        .into_iter()
+        .find(|(_, named)| *named != cluster_id)
+        .map(|(field, named)| (field, named.to_string()))
+    else {
+        return Ok(());
+    };
+    let reason = format!(
+        "{} names cluster `{}`, the file is in the folder of cluster `{}`",
+        field, named, cluster_id
+    );
+    if !correct {
+        return Err(ConversionError::Misplaced { path: path.to_path_buf(), reason });
+    }
+    warn!("{}: {}, using `{}`", path.display(), reason, cluster_id);
+    object.move_to_cluster(cluster_id);
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn project(id: Option<&str>, display_name: &str) -> Project {
+        Project::new(
+            None,
+            "c-123".to_string(),
+            None,
+            None,
+            display_name.to_string(),
+            None,
+            id.map(str::to_string),
+            None,
+            None,
+            "c-123".to_string(),
+            None,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_duplicate_display_names_include_remote_projects() {
+        let files = vec![
+            (PathBuf::from("c-123/p-abc/p-abc.project.yaml"), project(Some("p-abc"), "team-a")),
+            (PathBuf::from("c-123/new/new.project.yaml"), project(None, "team-b")),
+            (PathBuf::from("c-123/p-ghi/p-ghi.project.yaml"), project(Some("p-ghi"), "team-c")),
+        ];
+        let remote = vec![
+            project(Some("p-abc"), "team-a"),
+            project(Some("p-def"), "team-b"),
+            project(Some("p-ghi"), "team-c"),
+        ];
+
+        let violations = duplicate_project_display_names(&files, &remote);
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(
+            violations[0].message,
+            "2 projects are named `team-b`: c-123/new/new.project.yaml (new), p-def (only in Rancher)"
+        );
+        assert_eq!(violations[0].paths, [PathBuf::from("c-123/new/new.project.yaml")]);
+    }
+
+    #[test]
+    fn test_annotated_projects_may_share_a_display_name() {
+        let mut allowed = project(None, "team-a");
+        allowed.annotations = Some(HashMap::from([(
+            ALLOW_DUPLICATE_DISPLAY_NAME_ANNOTATION.to_string(),
+            "true".to_string(),
+        )]));
+        let files = vec![(PathBuf::from("c-123/new/new.project.yaml"), allowed)];
+
+        assert!(duplicate_project_display_names(&files, &[project(Some("p-abc"), "team-a")]).is_empty());
+    }
+
+    fn group_binding(id: &str, role_template_name: &str, group: &str) -> ProjectRoleTemplateBinding {
+        crate::deserialize_object(
+            &format!(
+                "id: '{}'\nnamespace: p-abc\nproject_name: c-123:p-abc\nrole_template_name: {}\ngroup_principal_name: activedirectory_group://{}\n",
+                id, role_template_name, group
+            ),
+            &crate::utils::file::FileFormat::Yaml,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_new_bindings_of_a_subject_in_another_case_are_duplicates() {
+        let files = vec![
+            (PathBuf::from("c-123/p-abc/ops.prtb.yaml"), group_binding("", "project-member", "cn=ops-team,dc=example,dc=com")),
+            (PathBuf::from("c-123/p-abc/dev.prtb.yaml"), group_binding("", "project-member", "CN=Dev-Team,DC=example,DC=com")),
+            (PathBuf::from("c-123/p-abc/dev-2.prtb.yaml"), group_binding("", "project-member", "cn=dev-team,dc=example,dc=com")),
+            (PathBuf::from("c-123/p-abc/owner.prtb.yaml"), group_binding("", "project-owner", "cn=ops-team,dc=example,dc=com")),
+        ];
+        let remote = vec![group_binding("prtb-ops", "project-member", "CN=Ops-Team,DC=example,DC=com")];
+
+        let violations = duplicate_bindings(&files, &remote, &PrincipalRules::default());
+
+        assert_eq!(violations.len(), 2, "{:?}", violations);
+        assert_eq!(violations[0].paths, [PathBuf::from("c-123/p-abc/ops.prtb.yaml")]);
+        assert!(violations[0].message.ends_with("like binding `prtb-ops`"), "{}", violations[0]);
+        assert_eq!(violations[1].paths, [PathBuf::from("c-123/p-abc/dev-2.prtb.yaml")]);
+        assert!(violations[1].message.ends_with("like c-123/p-abc/dev.prtb.yaml"), "{}", violations[1]);
+    }
+
+    #[test]
+    fn test_objects_naming_another_cluster_than_their_folder_fail_or_move() {
+        let path = Path::new("c-aaa/p-abc/p-abc.project.yaml");
+        let mut copied = project(Some("p-abc"), "team-a");
+        copied.move_to_cluster("c-bbb");
+
+        let error = check_cluster_placement(path, "c-aaa", &mut copied.clone(), false).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "c-aaa/p-abc/p-abc.project.yaml: cluster_name names cluster `c-bbb`, the file is in the folder of cluster `c-aaa`"
+        );
+        check_cluster_placement(path, "c-aaa", &mut copied, true).unwrap();
+        assert_eq!((copied.cluster_name.as_str(), copied.namespace.as_str()), ("c-aaa", "c-aaa"));
+
+        let mut prtb: ProjectRoleTemplateBinding = crate::deserialize_object(
+            "id: prtb-1\nnamespace: p-abc\nproject_name: c-bbb:p-abc\nrole_template_name: project-member\nuser_name: u-abc\n",
+            &crate::utils::file::FileFormat::Yaml,
+            None,
+        )
+        .unwrap();
+        let prtb_path = Path::new("c-aaa/p-abc/prtb-1.prtb.yaml");
+        assert!(check_cluster_placement(prtb_path, "c-bbb", &mut prtb.clone(), false).is_ok());
+        assert!(matches!(
+            check_cluster_placement(prtb_path, "c-aaa", &mut prtb.clone(), false),
+            Err(ConversionError::Misplaced { .. })
+        ));
+        check_cluster_placement(prtb_path, "c-aaa", &mut prtb, true).unwrap();
+        assert_eq!(prtb.project_name, "c-aaa:p-abc");
+    }
+
+    #[test]
+    fn test_the_cluster_of_a_file_is_named_by_the_cluster_file_above_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let cluster_path = dir.path().join("prod");
+        std::fs::create_dir_all(cluster_path.join("p-abc")).unwrap();
+        std::fs::write(cluster_path.join("c-aaa.cluster.yaml"), "id: c-aaa\n").unwrap();
+
+        assert_eq!(folder_cluster_id(&cluster_path.join("p-abc/prtb-1.prtb.yaml")).as_deref(), Some("c-aaa"));
+        assert_eq!(folder_cluster_id(&cluster_path.join("p-def.bundle.yaml")).as_deref(), Some("c-aaa"));
+        assert_eq!(folder_cluster_id(&dir.path().join("roles/rt-1.rt.yaml")), None);
+    }
+
+}
@@ -0,0 +1,275 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+use crate::models::ObjectType;
+use crate::resources::cluster::{Cluster, ClusterFolderMap, CLUSTER_MAP_FILE_STEM};
+use crate::resources::crtb::ClusterRoleTemplateBinding;
+use crate::resources::project::Project;
+use crate::resources::prtb::ProjectRoleTemplateBinding;
+use crate::resources::rt::RoleTemplate;
+use crate::utils::file::{file_extension_from_format, file_format_from_path, FileFormat};
+use crate::utils::ignore::ShepherdIgnore;
+use crate::utils::index::object_type_of;
+use crate::utils::safe_path::ensure_within;
+use crate::{deserialize_object, serialize_object};
+
+/// Files of an endpoint folder left in another format than `file_format`, usually after
+/// `file_format` was changed without converting the repository.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixedFormats {
+    pub file_format: FileFormat,
+    /// The object and cluster map files in another format, in path order
+    pub foreign: Vec<PathBuf>,
+    /// How many of them have a file in `file_format` next to them
+    pub duplicated: usize,
+}
+
+impl fmt::Display for MixedFormats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} files are not {}", self.foreign.len(), self.file_format)?;
+        if self.duplicated > 0 {
+            write!(f, ", {} of them next to a {} file of the same object", self.duplicated, self.file_format)?;
+        }
+        if let Some(first) = self.foreign.first() {
+            write!(f, ", such as {}", first.display())?;
+        }
+        write!(f, ". Run `shepherd convert-format --to {}` to convert them", self.file_format)
+    }
+}
+
+/// The object files and the cluster map file of `endpoint_path`, leaving out the paths
+/// `ignore` matches, in path order.
+fn managed_files(repo_root: &Path, endpoint_path: &Path, ignore: &ShepherdIgnore) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkDir::new(endpoint_path)
+        .into_iter()
+        .filter_entry(|entry| {
+            let ignored = entry
+                .path()
+                .strip_prefix(repo_root)
+                .is_ok_and(|rel| ignore.is_ignored(rel, entry.file_type().is_dir()));
+            entry.depth() == 0 || !ignored
+        })
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && object_type_of(entry.path()).is_some())
+        .map(|entry| entry.into_path())
+        .collect();
+    // the map is ignored by default so it is not taken for an object
+    files.extend(
+        ["yaml", "yml", "json", "toml"]
+            .iter()
+            .map(|extension| endpoint_path.join(format!("{}.{}", CLUSTER_MAP_FILE_STEM, extension)))
+            .filter(|path| path.is_file()),
+    );
+    files.sort();
+    files
+}
+
+/// `path` with the extension of `file_format`.
+fn path_in_format(path: &Path, file_format: &FileFormat) -> PathBuf {
+    path.with_extension(file_extension_from_format(file_format))
+}
+
+/// Look for managed files of `endpoint_path` whose extension is not the one of `file_format`.
+///
+/// The loader only reads files in `file_format`, the others would be taken for deleted
+/// objects and written again next to the old ones.
+pub fn find_mixed_formats(
+    repo_root: &Path,
+    endpoint_path: &Path,
+    file_format: &FileFormat,
+    ignore: &ShepherdIgnore,
+) -> Option<MixedFormats> {
+    let extension = file_extension_from_format(file_format);
+    let foreign: Vec<PathBuf> = managed_files(repo_root, endpoint_path, ignore)
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext != extension.as_str()))
+        .collect();
+    if foreign.is_empty() {
+        return None;
+    }
+    let duplicated = foreign
+        .iter()
+        .filter(|path| path_in_format(path, file_format).is_file())
+        .count();
+    Some(MixedFormats {
+        file_format: *file_format,
+        foreign,
+        duplicated,
+    })
+}
+
+fn reserialize<T: Serialize + DeserializeOwned>(contents: &str, path: &Path, to: &FileFormat) -> Result<String> {
+    let object: T = deserialize_object(contents, &file_format_from_path(path), Some(path))?;
+    serialize_object(&object, to)
+}
+
+/// The contents of the managed file at `path` written in `to`.
+fn convert_file(path: &Path, to: &FileFormat) -> Result<String> {
+    let contents = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    match object_type_of(path).map(|(object_type, _)| object_type) {
+        Some(ObjectType::Project) => reserialize::<Project>(&contents, path, to),
+        Some(ObjectType::ProjectRoleTemplateBinding) => reserialize::<ProjectRoleTemplateBinding>(&contents, path, to),
+        Some(ObjectType::ClusterRoleTemplateBinding) => reserialize::<ClusterRoleTemplateBinding>(&contents, path, to),
+        Some(ObjectType::RoleTemplate) => reserialize::<RoleTemplate>(&contents, path, to),
+        Some(ObjectType::Cluster) => reserialize::<Cluster>(&contents, path, to),
+        None => reserialize::<ClusterFolderMap>(&contents, path, to),
+    }
+}
+
+/// Write every managed file of `endpoint_path` in `to` and delete the file it was converted from.
+///
+/// A file whose converted copy already exists is only deleted when both hold the same
+/// object. Nothing is changed if any file does not parse or differs from its copy in `to`,
+/// so the repository is never left half converted.
+///
+/// # Returns
+/// The converted files and the files they were converted to
+///
+/// # Errors
+/// If a file cannot be read, parsed or written, or differs from its copy in `to`
+pub fn convert_format(
+    repo_root: &Path,
+    endpoint_path: &Path,
+    to: &FileFormat,
+    ignore: &ShepherdIgnore,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let extension = file_extension_from_format(to);
+    let mut planned: Vec<(PathBuf, PathBuf, Option<String>)> = Vec::new();
+    let mut problems: Vec<String> = Vec::new();
+    for from in managed_files(repo_root, endpoint_path, ignore) {
+        if from.extension().is_some_and(|ext| ext == extension.as_str()) {
+            continue;
+        }
+        let target = path_in_format(&from, to);
+        // a symbolic link committed to the repository may lead anywhere
+        if let Err(e) = ensure_within(endpoint_path, &from).and_then(|()| ensure_within(endpoint_path, &target)) {
+            problems.push(e.to_string());
+            continue;
+        }
+        let converted = match convert_file(&from, to) {
+            Ok(converted) => converted,
+            Err(e) => {
+                problems.push(format!("{:#}", e));
+                continue;
+            }
+        };
+        if !target.exists() {
+            planned.push((from, target, Some(converted)));
+            continue;
+        }
+        match convert_file(&target, to) {
+            Ok(existing) if existing == converted => planned.push((from, target, None)),
+            Ok(_) => problems.push(format!(
+                "{} and {} hold different objects, delete the one to drop",
+                from.display(),
+                target.display()
+            )),
+            Err(e) => problems.push(format!("{:#}", e)),
+        }
+    }
+    if !problems.is_empty() {
+        bail!("Nothing was converted, {} files need attention:\n  {}", problems.len(), problems.join("\n  "));
+    }
+
+    let mut converted = Vec::new();
+    for (from, target, contents) in planned {
+        match contents {
+            Some(contents) => {
+                std::fs::write(&target, contents).with_context(|| format!("Failed to write {}", target.display()))?
+            }
+            None => warn!("Dropping {}, {} holds the same object", from.display(), target.display()),
+        }
+        std::fs::remove_file(&from).with_context(|| format!("Failed to remove {}", from.display()))?;
+        info!("Converted {} to {}", from.display(), target.display());
+        converted.push((from, target));
+    }
+    Ok(converted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    const PROJECT: &str = "id: p-abc\ncluster_name: c-123\ndisplay_name: web\nnamespace: c-123\n";
+
+    #[test]
+    fn test_files_left_in_the_old_format_are_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = dir.path().join("rancher.example.com");
+        write(&endpoint.join("c-123/c-123.cluster.yaml"), "id: c-123\ndisplay_name: prod\n");
+        write(&endpoint.join("c-123/p-abc/p-abc.project.yaml"), PROJECT);
+        write(&endpoint.join("c-123/p-abc/p-abc.project.json"), "{}");
+        write(&endpoint.join("c-123/p-abc/README.md"), "notes");
+        write(&endpoint.join("docs/old.rt.yaml"), "id: old\n");
+        let ignore = ShepherdIgnore::new(["rancher.example.com/docs/"]);
+
+        let mixed = find_mixed_formats(dir.path(), &endpoint, &FileFormat::Json, &ignore).unwrap();
+
+        assert_eq!(
+            mixed.foreign,
+            [endpoint.join("c-123/c-123.cluster.yaml"), endpoint.join("c-123/p-abc/p-abc.project.yaml")]
+        );
+        assert_eq!(mixed.duplicated, 1);
+        assert!(mixed.to_string().contains("Run `shepherd convert-format --to json`"));
+        assert_eq!(find_mixed_formats(dir.path(), &endpoint, &FileFormat::Yaml, &ignore).map(|m| m.foreign.len()), Some(1));
+    }
+
+    #[test]
+    fn test_convert_format_rewrites_files_and_drops_identical_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = dir.path().join("rancher.example.com");
+        write(&endpoint.join("c-123/c-123.cluster.yaml"), "id: c-123\ndisplay_name: prod\n");
+        write(&endpoint.join("c-123/p-abc/p-abc.project.yaml"), PROJECT);
+        let project: Project = deserialize_object(PROJECT, &FileFormat::Yaml, None).unwrap();
+        write(
+            &endpoint.join("c-123/p-abc/p-abc.project.json"),
+            &serialize_object(&project, &FileFormat::Json).unwrap(),
+        );
+        write(&endpoint.join("clusters.map.yaml"), "prod: c-123\n");
+
+        let converted = convert_format(dir.path(), &endpoint, &FileFormat::Json, &ShepherdIgnore::default()).unwrap();
+
+        assert_eq!(converted.len(), 3);
+        assert!(find_mixed_formats(dir.path(), &endpoint, &FileFormat::Json, &ShepherdIgnore::default()).is_none());
+        let cluster: Cluster = deserialize_object(
+            &std::fs::read_to_string(endpoint.join("c-123/c-123.cluster.json")).unwrap(),
+            &FileFormat::Json,
+            None,
+        )
+        .unwrap();
+        assert_eq!(cluster.display_name, "prod");
+        let map = ClusterFolderMap::load(&endpoint, &FileFormat::Json).unwrap();
+        assert_eq!(map.cluster_id("prod"), Some("c-123"));
+    }
+
+    #[test]
+    fn test_convert_format_changes_nothing_when_a_duplicate_differs() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = dir.path().join("rancher.example.com");
+        let cluster = endpoint.join("c-123/c-123.cluster.yaml");
+        write(&cluster, "id: c-123\ndisplay_name: prod\n");
+        write(&endpoint.join("c-123/p-abc/p-abc.project.yaml"), PROJECT);
+        // written by a run after file_format was switched, and edited since
+        let project: Project = deserialize_object(&PROJECT.replace("web", "api"), &FileFormat::Yaml, None).unwrap();
+        write(
+            &endpoint.join("c-123/p-abc/p-abc.project.json"),
+            &serialize_object(&project, &FileFormat::Json).unwrap(),
+        );
+
+        let error = convert_format(dir.path(), &endpoint, &FileFormat::Json, &ShepherdIgnore::default()).unwrap_err();
+
+        assert!(error.to_string().contains("hold different objects"), "{:#}", error);
+        assert!(cluster.exists());
+        assert!(!endpoint.join("c-123/c-123.cluster.json").exists());
+    }
+}
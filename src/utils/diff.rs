@@ -1,84 +1,439 @@
 use std::collections::{BTreeSet, HashMap};
+use std::fmt::Display;
 
 use json_patch::diff;
-use rancher_client::models::{IoCattleManagementv3Project, IoCattleManagementv3ProjectRoleTemplateBinding, IoCattleManagementv3RoleTemplate};
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
-use crate::{clean_up_value, api::config::RancherClusterConfig, resources::project::PROJECT_EXCLUDE_PATHS, resources::prtb::PRTB_EXCLUDE_PATHS, resources::rt::RT_EXCLUDE_PATHS, models::ObjectType};
+use crate::{clean_up_value, api::config::{ClusterConfig, RancherClusterConfig}, resources::crtb::CRTB_EXCLUDE_PATHS, resources::project::PROJECT_EXCLUDE_PATHS, resources::prtb::PRTB_EXCLUDE_PATHS, resources::rt::RT_EXCLUDE_PATHS, models::ObjectType, utils::principal::PrincipalRules};
 
 
-/// compute the cluster diff between the current state and the desired state
-/// # Arguments
-/// * `current_state` - The current state of the cluster
-/// * `desired_state` - The desired state of the cluster
-/// # Returns
-/// * HashMap< (ObjectType, String, Option<String>), Value> - A HashMap containing the differences between the two states, key is the ObjectType, the String is the id of the object, and the Option<String> is the namespace of the object, and the Value is the difference between the two states
-pub fn compute_cluster_diff(
-    current_state: &Value,
-    desired_state: &Value,
-) -> HashMap< (ObjectType, String, Option<String>), Value> {
+/// Annotation and label keys Rancher adds to objects on its own after creation.
+pub const DEFAULT_IGNORED_METADATA_PATTERNS: &[&str] = &[
+    "lifecycle.cattle.io/*",
+    "field.cattle.io/creatorId",
+    "authz.management.cattle.io/*",
+    "cattle.io/creator",
+    "shepherd.cattle.io/*",
+];
+
+/// Annotation holding the configuration Shepherd last applied to an object, see [`last_applied`].
+pub const LAST_APPLIED_ANNOTATION: &str = "shepherd.cattle.io/last-applied";
+
+
+/// Annotation and label keys that are never compared, patched or written to files.
+///
+/// Patterns are matched against the whole key, `*` matches any run of characters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataFilter {
+    patterns: Vec<String>,
+}
+
+impl Default for MetadataFilter {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+impl MetadataFilter {
+    /// Create a filter with the default patterns plus `extra_patterns`.
+    pub fn new(extra_patterns: &[String]) -> Self {
+        let patterns = DEFAULT_IGNORED_METADATA_PATTERNS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .chain(extra_patterns.iter().cloned())
+            .collect();
+        Self { patterns }
+    }
+
+    pub fn is_ignored(&self, key: &str) -> bool {
+        self.patterns.iter().any(|pattern| glob_match(pattern, key))
+    }
+
+    /// Drop ignored keys from an annotation or label map, leaving `None` if nothing remains.
+    pub fn retain(&self, map: &mut Option<HashMap<String, String>>) {
+        if let Some(entries) = map {
+            entries.retain(|key, _| !self.is_ignored(key));
+            if entries.is_empty() {
+                *map = None;
+            }
+        }
+    }
+
+    /// Drop ignored keys from `metadata.annotations` and `metadata.labels` of an API object.
+    ///
+    /// Maps left empty are removed as well so an empty local map never
+    /// patches away the keys Rancher manages.
+    pub fn strip_value(&self, value: &mut Value) {
+        let Some(metadata) = value.get_mut("metadata").and_then(Value::as_object_mut) else {
+            return;
+        };
+        for field in ["annotations", "labels"] {
+            if let Some(entries) = metadata.get_mut(field).and_then(Value::as_object_mut) {
+                entries.retain(|key, _| !self.is_ignored(key));
+                if entries.is_empty() {
+                    metadata.remove(field);
+                }
+            }
+        }
+    }
+
+    /// Copy the ignored keys of `source` into `target`, so a full replace keeps them.
+    pub fn copy_ignored(&self, source: &Value, target: &mut Value) {
+        for field in ["annotations", "labels"] {
+            let Some(entries) = source
+                .pointer(&format!("/metadata/{}", field))
+                .and_then(Value::as_object)
+            else {
+                continue;
+            };
+            for (key, value) in entries.iter().filter(|(key, _)| self.is_ignored(key)) {
+                let Some(metadata) = target.get_mut("metadata").and_then(Value::as_object_mut) else {
+                    return;
+                };
+                if let Some(map) = metadata
+                    .entry(field)
+                    .or_insert_with(|| Value::Object(serde_json::Map::new()))
+                    .as_object_mut()
+                {
+                    map.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(remaining) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=remaining.len())
+                .filter(|&i| remaining.is_char_boundary(i))
+                .any(|i| glob_match(rest, &remaining[i..]))
+        }
+    }
+}
 
-    let current_state: RancherClusterConfig = serde_json::from_value(current_state.clone()).unwrap();
-    let desired_state: RancherClusterConfig = serde_json::from_value(desired_state.clone()).unwrap();
 
-    let c_role_template = current_state.role_templates.clone();
-    let c_project = current_state.projects.clone();
+/// How an object in the repository compares to the live one.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "change", content = "patch")]
+pub enum ObjectChange {
+    /// Only in the repository, Rancher is yet to create it
+    Added,
+    /// Only in Rancher, the repository has no file for it
+    Removed,
+    /// In both with differing fields, the JSON patch turning the live object into the stored one
+    Changed(Value),
+    Unchanged,
+}
 
-    let mut patches: HashMap<(ObjectType, String, Option<String>), Value> = HashMap::new();
+/// The comparison of one object, keyed like the patches applied to Rancher.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ObjectDiff {
+    pub object_type: ObjectType,
+    /// Empty for stored objects Rancher is yet to name
+    pub object_id: String,
+    /// The cluster of projects and cluster role template bindings, the project of project role template bindings
+    pub namespace: Option<String>,
+    #[serde(flatten)]
+    pub change: ObjectChange,
+}
 
-    for crt in &c_role_template {
-        if let Some(desired_rt) = desired_state
-            .role_templates
+impl ObjectDiff {
+    pub fn key(&self) -> (ObjectType, String, Option<String>) {
+        (self.object_type, self.object_id.clone(), self.namespace.clone())
+    }
+}
+
+/// The comparison of every role template, project and binding of a cluster between
+/// the repository and Rancher, ordered by type, namespace and ID.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ClusterDiff {
+    pub objects: Vec<ObjectDiff>,
+}
+
+impl ClusterDiff {
+    /// The objects that are added, removed or changed.
+    pub fn changes(&self) -> impl Iterator<Item = &ObjectDiff> {
+        self.objects.iter().filter(|object| object.change != ObjectChange::Unchanged)
+    }
+
+    /// The JSON patch of every changed object, keyed by type, ID and namespace.
+    pub fn patches(&self) -> HashMap<(ObjectType, String, Option<String>), Value> {
+        self.objects
             .iter()
-            .find(|drole_template| drole_template.metadata.as_ref().unwrap().name == crt.metadata.as_ref().unwrap().name) {
-
-            let mut crtv = serde_json::to_value(crt).unwrap();
-            let mut drtv = serde_json::to_value(desired_rt).unwrap();
-            clean_up_value(&mut crtv, RT_EXCLUDE_PATHS);
-            clean_up_value(&mut drtv, RT_EXCLUDE_PATHS);
-            let patch = calculate_json_patch::<IoCattleManagementv3RoleTemplate>(&crtv, &drtv);
-            let rt_id = crt.metadata.as_ref().unwrap().name.clone().unwrap();
-            if let Some(patch) = patch {
-                debug!("RoleTemplate `{}` diff computed and added to patches", rt_id);
-                patches.insert((ObjectType::RoleTemplate, rt_id, None), patch);
-            }
-        }
-    }
-
-    for (c_project_id, (c_project, cprtbs)) in &c_project {
-        if let Some((d_project, dprtbs)) = desired_state.projects.get(c_project_id) {
-
-            let mut cpv = serde_json::to_value(c_project).unwrap();
-            let mut dpv = serde_json::to_value(d_project).unwrap();
-            clean_up_value(&mut cpv, PROJECT_EXCLUDE_PATHS);
-            clean_up_value(&mut dpv, PROJECT_EXCLUDE_PATHS);
-            let patch = calculate_json_patch::<IoCattleManagementv3Project>(&cpv, &dpv);
-            let cluster_id = c_project.metadata.as_ref().unwrap().namespace.clone().unwrap();
-            if let Some(patch) = patch {
-                patches.insert((ObjectType::Project, c_project_id.to_string(), Some(cluster_id.clone())), patch);
-                debug!("Project `{}` diff computed and added to patches", c_project_id);
-            }
-
-            for cprtb in cprtbs {
-                if let Some(desired_prtb) = dprtbs.iter().find(|dprtb| dprtb.metadata.as_ref().unwrap().name == cprtb.metadata.as_ref().unwrap().name) {
-                    let mut cprtbv = serde_json::to_value(cprtb).unwrap();
-                    let mut dprtbv = serde_json::to_value(desired_prtb).unwrap();
-                    clean_up_value(&mut cprtbv, PRTB_EXCLUDE_PATHS);
-                    clean_up_value(&mut dprtbv, PRTB_EXCLUDE_PATHS);
-                    let patch = calculate_json_patch::<IoCattleManagementv3ProjectRoleTemplateBinding>(&cprtbv, &dprtbv);
-                    let prtb_id = cprtb.metadata.as_ref().unwrap().name.clone().unwrap();
-                    if let Some(patch) = patch {
-                        debug!("ProjectRoleTemplateBinding `{}` diff computed and added to patches", prtb_id);
-                        patches.insert((ObjectType::ProjectRoleTemplateBinding, prtb_id, Some(c_project_id.clone())), patch);
+            .filter_map(|object| match &object.change {
+                ObjectChange::Changed(patch) => Some((object.key(), patch.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// How many objects have the kind of change of `change`, patches are not compared.
+    pub fn count(&self, change: &ObjectChange) -> usize {
+        self.objects
+            .iter()
+            .filter(|object| std::mem::discriminant(&object.change) == std::mem::discriminant(change))
+            .count()
+    }
+
+    /// Take the objects only Rancher has out of the diff.
+    pub fn take_removed(&mut self) -> Vec<ObjectDiff> {
+        let (removed, kept) = std::mem::take(&mut self.objects)
+            .into_iter()
+            .partition(|object| object.change == ObjectChange::Removed);
+        self.objects = kept;
+        removed
+    }
+
+    pub fn is_unchanged(&self) -> bool {
+        self.changes().next().is_none()
+    }
+
+    fn push<T: Serialize + DeserializeOwned>(
+        &mut self,
+        (object_type, object_id, namespace): (ObjectType, String, Option<String>),
+        live: Option<&T>,
+        stored: Option<&T>,
+        exclude_paths: &[&str],
+        metadata_filter: &MetadataFilter,
+        principal_rules: &PrincipalRules,
+    ) {
+        let change = match (live, stored) {
+            (Some(live), Some(stored)) => {
+                let mut live = serde_json::to_value(live).unwrap();
+                let mut stored = serde_json::to_value(stored).unwrap();
+                let last_applied = recorded_last_applied(&live).map(|mut last_applied| {
+                    clean_up_value(&mut last_applied, exclude_paths);
+                    metadata_filter.strip_value(&mut last_applied);
+                    last_applied
+                });
+                clean_up_value(&mut live, exclude_paths);
+                clean_up_value(&mut stored, exclude_paths);
+                metadata_filter.strip_value(&mut live);
+                metadata_filter.strip_value(&mut stored);
+                if object_type == ObjectType::ProjectRoleTemplateBinding {
+                    align_principals(&live, &mut stored, principal_rules);
+                }
+                if let Some(last_applied) = &last_applied {
+                    stored = three_way_merge(&live, &stored, last_applied);
+                }
+                keep_metadata_maps(&live, &mut stored);
+                match calculate_json_patch::<T>(&live, &stored) {
+                    Some(patch) => {
+                        debug!("{:?} `{}` diff computed and added to patches", object_type, object_id);
+                        ObjectChange::Changed(patch)
                     }
+                    None => ObjectChange::Unchanged,
+                }
+            }
+            (None, Some(_)) => ObjectChange::Added,
+            (Some(_), None) => ObjectChange::Removed,
+            (None, None) => return,
+        };
+        self.objects.push(ObjectDiff { object_type, object_id, namespace, change });
+    }
+}
+
+impl Display for ClusterDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for object in self.changes() {
+            let namespace = object.namespace.as_deref().map(|namespace| format!(" ({})", namespace)).unwrap_or_default();
+            let id = if object.object_id.is_empty() { "<unnamed>" } else { &object.object_id };
+            match &object.change {
+                ObjectChange::Added => writeln!(f, "+ {:?} {}{}", object.object_type, id, namespace)?,
+                ObjectChange::Removed => writeln!(f, "- {:?} {}{}", object.object_type, id, namespace)?,
+                ObjectChange::Changed(patch) => {
+                    let paths: Vec<&str> = patch
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|operation| operation["path"].as_str())
+                        .collect();
+                    writeln!(f, "~ {:?} {}{}: {}", object.object_type, id, namespace, paths.join(", "))?
                 }
+                ObjectChange::Unchanged => {}
             }
         }
+        write!(
+            f,
+            "{} added, {} removed, {} changed, {} unchanged",
+            self.count(&ObjectChange::Added),
+            self.count(&ObjectChange::Removed),
+            self.count(&ObjectChange::Changed(Value::Null)),
+            self.count(&ObjectChange::Unchanged)
+        )
+    }
+}
+
+/// Give `stored` an empty annotation or label map where it has none and `live` still has keys
+/// of its own, so the keys are removed one by one instead of the whole map, which would take
+/// the keys Rancher manages along.
+fn keep_metadata_maps(live: &Value, stored: &mut Value) {
+    let Some(metadata) = stored.get_mut("metadata").and_then(Value::as_object_mut) else {
+        return;
+    };
+    for field in ["annotations", "labels"] {
+        let live_has_keys = live
+            .pointer(&format!("/metadata/{}", field))
+            .and_then(Value::as_object)
+            .is_some_and(|entries| !entries.is_empty());
+        if live_has_keys && metadata.get(field).is_none_or(Value::is_null) {
+            metadata.insert(field.to_string(), Value::Object(serde_json::Map::new()));
+        }
+    }
+}
+
+/// The principal name fields of bindings, as the API names them.
+const PRINCIPAL_FIELDS: &[&str] = &["userPrincipalName", "groupPrincipalName"];
+
+/// Take the principal names of `live` for the ones of `stored` naming the same principal by
+/// `principal_rules`, so a name written in another case is no change. Only the compared value
+/// is touched, the file and what is sent to Rancher keep the name as written.
+fn align_principals(live: &Value, stored: &mut Value, principal_rules: &PrincipalRules) {
+    for field in PRINCIPAL_FIELDS {
+        let live_name = live.get(*field).and_then(Value::as_str);
+        let Some(stored_name) = stored.get_mut(*field) else {
+            continue;
+        };
+        if let Some(live_name) = live_name.filter(|live_name| principal_rules.same(Some(live_name), stored_name.as_str())) {
+            *stored_name = Value::String(live_name.to_string());
+        }
+    }
+}
+
+/// Compare the configuration of a cluster stored in the repository with the live one.
+///
+/// Objects are matched by type, ID and namespace. Fields Rancher manages, the exclude
+/// paths of each type, and the annotation and label keys of `metadata_filter` are left
+/// out of the comparison. Stored objects without an ID are always added.
+///
+/// # Arguments
+/// * `local` - The configuration loaded from the repository
+/// * `remote` - The configuration loaded from Rancher
+/// * `metadata_filter` - Annotation and label keys left out of the comparison
+/// * `principal_rules` - How the principal names of bindings are compared
+///
+/// # Errors
+/// Returns an error if an object of `local` cannot be converted to its API form
+pub fn diff_cluster_config(
+    local: &ClusterConfig,
+    remote: &RancherClusterConfig,
+    metadata_filter: &MetadataFilter,
+    principal_rules: &PrincipalRules,
+) -> anyhow::Result<ClusterDiff> {
+    let local = RancherClusterConfig::try_from(local.clone()).map_err(anyhow::Error::msg)?;
+    let mut cluster_diff = ClusterDiff::default();
+
+    diff_named(
+        &mut cluster_diff,
+        ObjectType::RoleTemplate,
+        &remote.role_templates,
+        &local.role_templates,
+        |rt| rt.metadata.as_ref().and_then(|m| m.name.clone()),
+        |_| None,
+        RT_EXCLUDE_PATHS,
+        metadata_filter,
+        principal_rules,
+    );
+
+    let project_ids: BTreeSet<&String> = remote.projects.keys().chain(local.projects.keys()).collect();
+    for project_id in project_ids {
+        let live = remote.projects.get(project_id);
+        let stored = local.projects.get(project_id);
+        let cluster_id = live
+            .or(stored)
+            .and_then(|(project, _)| project.metadata.as_ref()?.namespace.clone());
+        cluster_diff.push(
+            (ObjectType::Project, project_id.clone(), cluster_id),
+            live.map(|(project, _)| project),
+            stored.map(|(project, _)| project),
+            PROJECT_EXCLUDE_PATHS,
+            metadata_filter,
+            principal_rules,
+        );
+        diff_named(
+            &mut cluster_diff,
+            ObjectType::ProjectRoleTemplateBinding,
+            live.map_or(&[][..], |(_, prtbs)| prtbs),
+            stored.map_or(&[][..], |(_, prtbs)| prtbs),
+            |prtb| prtb.metadata.as_ref().and_then(|m| m.name.clone()),
+            |_| Some(project_id.clone()),
+            PRTB_EXCLUDE_PATHS,
+            metadata_filter,
+            principal_rules,
+        );
+    }
+
+    diff_named(
+        &mut cluster_diff,
+        ObjectType::ClusterRoleTemplateBinding,
+        &remote.crtbs,
+        &local.crtbs,
+        |crtb| crtb.metadata.as_ref().and_then(|m| m.name.clone()),
+        |crtb| crtb.metadata.as_ref().and_then(|m| m.namespace.clone()),
+        CRTB_EXCLUDE_PATHS,
+        metadata_filter,
+        principal_rules,
+    );
+
+    cluster_diff
+        .objects
+        .sort_by(|a, b| (a.object_type, &a.namespace, &a.object_id).cmp(&(b.object_type, &b.namespace, &b.object_id)));
+    let changed = cluster_diff.count(&ObjectChange::Changed(Value::Null));
+    info!("Total patches computed: {}", changed);
+    Ok(cluster_diff)
+}
+
+/// Compare the live and stored objects of one type, matched by name.
+#[allow(clippy::too_many_arguments)]
+fn diff_named<T: Serialize + DeserializeOwned>(
+    cluster_diff: &mut ClusterDiff,
+    object_type: ObjectType,
+    live: &[T],
+    stored: &[T],
+    name: impl Fn(&T) -> Option<String>,
+    namespace: impl Fn(&T) -> Option<String>,
+    exclude_paths: &[&str],
+    metadata_filter: &MetadataFilter,
+    principal_rules: &PrincipalRules,
+) {
+    let named = |object: &T| name(object).filter(|name| !name.is_empty());
+    for live_object in live {
+        let Some(id) = named(live_object) else {
+            continue;
+        };
+        let stored_object = stored.iter().find(|object| named(object).as_ref() == Some(&id));
+        cluster_diff.push(
+            (object_type, id, namespace(live_object)),
+            Some(live_object),
+            stored_object,
+            exclude_paths,
+            metadata_filter,
+            principal_rules,
+        );
+    }
+    for stored_object in stored {
+        let id = named(stored_object);
+        let live_object = id
+            .as_ref()
+            .and_then(|id| live.iter().find(|object| named(object).as_ref() == Some(id)));
+        if live_object.is_none() {
+            cluster_diff.push(
+                (object_type, id.unwrap_or_default(), namespace(stored_object)),
+                None,
+                Some(stored_object),
+                exclude_paths,
+                metadata_filter,
+                principal_rules,
+            );
+        }
     }
-    info!("Total patches computed: {}", patches.len());
-    patches
 }
 
 
@@ -155,4 +510,651 @@ where
         return Some(serde_json::to_value(patch).unwrap())
     }
     None
-}
\ No newline at end of file
+}
+
+
+/// The value of the [`LAST_APPLIED_ANNOTATION`] recording `value`, an object in API form.
+///
+/// The object is recorded without `exclude_paths` and the annotations and labels of
+/// [`DEFAULT_IGNORED_METADATA_PATTERNS`], as compact JSON with sorted keys.
+pub fn last_applied(value: &Value, exclude_paths: &[&str]) -> String {
+    let mut value = value.clone();
+    clean_up_value(&mut value, exclude_paths);
+    MetadataFilter::default().strip_value(&mut value);
+    value.to_string()
+}
+
+/// The configuration last applied to the live object `live`, `None` if it has none or it does not parse.
+pub fn recorded_last_applied(live: &Value) -> Option<Value> {
+    let recorded = live
+        .pointer("/metadata/annotations")?
+        .get(LAST_APPLIED_ANNOTATION)?
+        .as_str()?;
+    match serde_json::from_str(recorded) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            warn!("Ignoring the unparsable {} annotation: {}", LAST_APPLIED_ANNOTATION, e);
+            None
+        }
+    }
+}
+
+/// The state `live` is brought to by applying `stored` when `last_applied` was applied before.
+///
+/// Maps are merged key by key: keys of `stored` take its value, keys only `live` has are
+/// kept if `last_applied` did not have them, as they were added in Rancher, and dropped if it
+/// did, as they were removed from the file. Any other value, arrays included, is taken from
+/// `stored` as a whole. A `null` counts as a missing key.
+pub fn three_way_merge(live: &Value, stored: &Value, last_applied: &Value) -> Value {
+    fn set<'a>(map: Option<&'a serde_json::Map<String, Value>>, key: &str) -> Option<&'a Value> {
+        map.and_then(|map| map.get(key)).filter(|value| !value.is_null())
+    }
+    let (Value::Object(live), Value::Object(stored)) = (live, stored) else {
+        return stored.clone();
+    };
+    let last_applied = last_applied.as_object();
+    let mut merged = serde_json::Map::new();
+    for (key, stored_value) in stored.iter().filter(|(_, value)| !value.is_null()) {
+        let value = match set(Some(live), key) {
+            Some(live_value) => three_way_merge(live_value, stored_value, set(last_applied, key).unwrap_or(&Value::Null)),
+            None => stored_value.clone(),
+        };
+        merged.insert(key.clone(), value);
+    }
+    for (key, live_value) in live.iter().filter(|(key, value)| !value.is_null() && set(Some(stored), key).is_none()) {
+        match (live_value, set(last_applied, key)) {
+            (_, None) => {
+                merged.insert(key.clone(), live_value.clone());
+            }
+            // keep what was added in Rancher below a map removed from the file
+            (Value::Object(_), Some(last_value @ Value::Object(_))) => {
+                let kept = three_way_merge(live_value, &Value::Object(serde_json::Map::new()), last_value);
+                if kept.as_object().is_some_and(|kept| !kept.is_empty()) {
+                    merged.insert(key.clone(), kept);
+                }
+            }
+            (_, Some(_)) => {}
+        }
+    }
+    Value::Object(merged)
+}
+
+/// Whether removing the value at `pointer` would wipe a whole section of an object.
+///
+/// Top-level keys such as `/spec` and the complete annotation and label maps
+/// count as whole sections; single entries inside them do not.
+pub fn is_guarded_removal(pointer: &str) -> bool {
+    pointer.matches('/').count() <= 1
+        || pointer == "/metadata/annotations"
+        || pointer == "/metadata/labels"
+}
+
+
+/// Drop guarded remove operations that are not backed by the previous revision.
+///
+/// A guarded removal (see [`is_guarded_removal`]) is only kept when the key
+/// existed in the previous git revision of the file, which shows it was
+/// deliberately deleted rather than never written.
+/// # Arguments
+/// * `patch` - The JSON patch as produced by [`calculate_json_patch`]
+/// * `previous_state` - The previous revision of the object in API form, if known
+/// # Returns
+/// * `Option<Value>` - The filtered patch, or `None` if no operations remain
+pub fn guard_removals(patch: Value, previous_state: Option<&Value>) -> Option<Value> {
+    let unbacked = unbacked_removals(&patch, previous_state);
+    let Value::Array(operations) = patch else {
+        return Some(patch);
+    };
+
+    let operations: Vec<Value> = operations
+        .into_iter()
+        .filter(|operation| {
+            let pointer = operation["path"].as_str().unwrap_or_default();
+            let skipped = operation["op"] == "remove" && unbacked.iter().any(|unbacked| unbacked == pointer);
+            if skipped {
+                warn!("Skipping removal of `{}`, it was not present in the previous revision", pointer);
+            }
+            !skipped
+        })
+        .collect();
+
+    if operations.is_empty() {
+        None
+    } else {
+        Some(Value::Array(operations))
+    }
+}
+
+
+/// The pointers of the guarded removals of `patch` that [`guard_removals`] drops, as
+/// `previous_state` does not have them.
+pub fn unbacked_removals(patch: &Value, previous_state: Option<&Value>) -> Vec<String> {
+    patch
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|operation| operation["op"] == "remove")
+        .filter_map(|operation| operation["path"].as_str())
+        .filter(|pointer| is_guarded_removal(pointer))
+        .filter(|pointer| previous_state.is_none_or(|previous| previous.pointer(pointer).is_none()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether a JSON patch contains a guarded removal.
+pub fn has_guarded_removal(patch: &Value) -> bool {
+    patch.as_array().is_some_and(|operations| {
+        operations.iter().any(|operation| {
+            operation["op"] == "remove"
+                && is_guarded_removal(operation["path"].as_str().unwrap_or_default())
+        })
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rancher_client::models::IoCattleManagementv3Project;
+    use serde_json::json;
+
+    fn project_value(annotations: Value) -> Value {
+        json!({
+            "metadata": { "name": "p-abc", "namespace": "c-123", "annotations": annotations },
+            "spec": { "clusterName": "c-123", "displayName": "Project", "description": "desc" }
+        })
+    }
+
+    fn cluster_config(role_templates: &[&str], projects: Value) -> ClusterConfig {
+        let role_templates: Vec<Value> = role_templates
+            .iter()
+            .map(|id| json!({ "id": id, "display_name": id, "context": "project" }))
+            .collect();
+        serde_json::from_value(json!({
+            "cluster": { "id": "c-123", "display_name": "prod" },
+            "role_templates": role_templates,
+            "projects": projects,
+        }))
+        .unwrap()
+    }
+
+    fn project(id: &str, prtbs: &[(&str, &str)]) -> Value {
+        let prtbs: Vec<Value> = prtbs
+            .iter()
+            .map(|(prtb_id, role)| {
+                json!({
+                    "id": prtb_id,
+                    "namespace": id,
+                    "project_name": format!("c-123:{}", id),
+                    "role_template_name": role,
+                    "user_name": "u-abc"
+                })
+            })
+            .collect();
+        json!([
+            { "id": id, "cluster_name": "c-123", "namespace": "c-123", "display_name": id },
+            prtbs
+        ])
+    }
+
+    fn change<'a>(diff: &'a ClusterDiff, object_type: ObjectType, object_id: &str) -> &'a ObjectChange {
+        &diff
+            .objects
+            .iter()
+            .find(|object| object.object_type == object_type && object.object_id == object_id)
+            .unwrap_or_else(|| panic!("no {:?} `{}` in {:#?}", object_type, object_id, diff))
+            .change
+    }
+
+    #[test]
+    fn test_cluster_diff_classifies_every_object() {
+        let local = cluster_config(
+            &["rt-keep", "rt-new"],
+            json!({
+                "p-abc": project("p-abc", &[("prtb-same", "project-member"), ("prtb-changed", "project-owner"), ("", "read-only")]),
+                "p-new": project("p-new", &[]),
+            }),
+        );
+        let remote = cluster_config(
+            &["rt-keep", "rt-gone"],
+            json!({
+                "p-abc": project("p-abc", &[("prtb-same", "project-member"), ("prtb-changed", "project-member"), ("prtb-gone", "project-member")]),
+                "p-old": project("p-old", &[("prtb-old", "project-member")]),
+            }),
+        );
+        let remote = RancherClusterConfig::try_from(remote).unwrap();
+
+        let diff = diff_cluster_config(&local, &remote, &MetadataFilter::default(), &PrincipalRules::default()).unwrap();
+
+        assert_eq!(change(&diff, ObjectType::RoleTemplate, "rt-keep"), &ObjectChange::Unchanged);
+        assert_eq!(change(&diff, ObjectType::RoleTemplate, "rt-new"), &ObjectChange::Added);
+        assert_eq!(change(&diff, ObjectType::RoleTemplate, "rt-gone"), &ObjectChange::Removed);
+        assert_eq!(change(&diff, ObjectType::Project, "p-abc"), &ObjectChange::Unchanged);
+        assert_eq!(change(&diff, ObjectType::Project, "p-new"), &ObjectChange::Added);
+        assert_eq!(change(&diff, ObjectType::Project, "p-old"), &ObjectChange::Removed);
+        assert_eq!(change(&diff, ObjectType::ProjectRoleTemplateBinding, "prtb-old"), &ObjectChange::Removed);
+        assert_eq!(change(&diff, ObjectType::ProjectRoleTemplateBinding, "prtb-same"), &ObjectChange::Unchanged);
+        assert_eq!(change(&diff, ObjectType::ProjectRoleTemplateBinding, "prtb-gone"), &ObjectChange::Removed);
+        assert_eq!(change(&diff, ObjectType::ProjectRoleTemplateBinding, ""), &ObjectChange::Added);
+        assert_eq!(
+            change(&diff, ObjectType::ProjectRoleTemplateBinding, "prtb-changed"),
+            &ObjectChange::Changed(json!([{ "op": "replace", "path": "/roleTemplateName", "value": "project-owner" }]))
+        );
+
+        let patches = diff.patches();
+        assert_eq!(patches.len(), 1);
+        assert!(patches.contains_key(&(
+            ObjectType::ProjectRoleTemplateBinding,
+            "prtb-changed".to_string(),
+            Some("p-abc".to_string())
+        )));
+        assert_eq!(diff.to_string().lines().last(), Some("3 added, 4 removed, 1 changed, 3 unchanged"));
+
+        let mut diff = diff;
+        let removed: Vec<String> = diff.take_removed().into_iter().map(|object| object.object_id).collect();
+        assert_eq!(removed, ["rt-gone", "p-old", "prtb-gone", "prtb-old"]);
+        assert_eq!(diff.count(&ObjectChange::Removed), 0);
+        assert_eq!(diff.to_string().lines().last(), Some("3 added, 0 removed, 1 changed, 3 unchanged"));
+    }
+
+    #[test]
+    fn test_principal_names_in_another_case_are_unchanged() {
+        let principal = |name: &str| {
+            let mut project = project("p-abc", &[("prtb-ops", "project-member")]);
+            let prtb = &mut project[1][0];
+            prtb.as_object_mut().unwrap().remove("user_name");
+            prtb["group_principal_name"] = json!(format!("activedirectory_group://{}", name));
+            project
+        };
+        let local = cluster_config(&[], json!({ "p-abc": principal("cn=ops-team,ou=groups,dc=example,dc=com") }));
+        let remote = cluster_config(&[], json!({ "p-abc": principal("CN=Ops-Team,OU=Groups,DC=example,DC=com") }));
+        let remote = RancherClusterConfig::try_from(remote).unwrap();
+
+        let diff = diff_cluster_config(&local, &remote, &MetadataFilter::default(), &PrincipalRules::default()).unwrap();
+        assert!(diff.is_unchanged(), "{}", diff);
+
+        let local = cluster_config(&[], json!({ "p-abc": principal("cn=dev-team,ou=groups,dc=example,dc=com") }));
+        let diff = diff_cluster_config(&local, &remote, &MetadataFilter::default(), &PrincipalRules::default()).unwrap();
+        assert_eq!(
+            change(&diff, ObjectType::ProjectRoleTemplateBinding, "prtb-ops"),
+            &ObjectChange::Changed(json!([{
+                "op": "replace",
+                "path": "/groupPrincipalName",
+                "value": "activedirectory_group://cn=dev-team,ou=groups,dc=example,dc=com"
+            }]))
+        );
+
+        // compared as written by the rules of the config
+        let exact = PrincipalRules::new(&std::collections::BTreeMap::from([(
+            "activedirectory_group".to_string(),
+            crate::utils::principal::PrincipalNormalization::Exact,
+        )]));
+        let local = cluster_config(&[], json!({ "p-abc": principal("cn=ops-team,ou=groups,dc=example,dc=com") }));
+        let diff = diff_cluster_config(&local, &remote, &MetadataFilter::default(), &exact).unwrap();
+        assert!(!diff.is_unchanged(), "{}", diff);
+    }
+
+    #[test]
+    fn test_cluster_diff_ignores_excluded_fields_and_rancher_metadata() {
+        let local = cluster_config(&[], json!({ "p-abc": project("p-abc", &[]) }));
+        let mut remote = RancherClusterConfig::try_from(local.clone()).unwrap();
+        let (live_project, _) = remote.projects.get_mut("p-abc").unwrap();
+        let metadata = live_project.metadata.as_mut().unwrap();
+        metadata.resource_version = Some("12345".to_string());
+        metadata.annotations = Some(HashMap::from([(
+            "field.cattle.io/creatorId".to_string(),
+            "user-abc".to_string(),
+        )]));
+
+        let diff = diff_cluster_config(&local, &remote, &MetadataFilter::default(), &PrincipalRules::default()).unwrap();
+
+        assert!(diff.is_unchanged());
+        assert_eq!(change(&diff, ObjectType::Project, "p-abc"), &ObjectChange::Unchanged);
+    }
+
+    #[test]
+    fn test_removed_annotation_yields_escaped_remove_op() {
+        let current = project_value(json!({ "team": "a", "example.com/owner": "me" }));
+        let desired = project_value(json!({ "team": "a" }));
+
+        let patch = calculate_json_patch::<IoCattleManagementv3Project>(&current, &desired).unwrap();
+
+        assert_eq!(
+            patch,
+            json!([{ "op": "remove", "path": "/metadata/annotations/example.com~1owner" }])
+        );
+    }
+
+    #[test]
+    fn test_guard_removals_keeps_entry_removals() {
+        let patch = json!([{ "op": "remove", "path": "/metadata/annotations/team" }]);
+
+        assert_eq!(guard_removals(patch.clone(), None), Some(patch));
+    }
+
+    #[test]
+    fn test_guard_removals_drops_unbacked_section_removal() {
+        let patch = json!([
+            { "op": "remove", "path": "/spec" },
+            { "op": "replace", "path": "/metadata/labels/team", "value": "b" }
+        ]);
+        let previous = json!({ "metadata": { "name": "p-abc" } });
+
+        assert_eq!(
+            guard_removals(patch, Some(&previous)),
+            Some(json!([{ "op": "replace", "path": "/metadata/labels/team", "value": "b" }]))
+        );
+        assert!(guard_removals(json!([{ "op": "remove", "path": "/spec" }]), None).is_none());
+    }
+
+    #[test]
+    fn test_guard_removals_keeps_removal_backed_by_previous_revision() {
+        let patch = json!([{ "op": "remove", "path": "/spec" }]);
+        let previous = json!({ "spec": { "displayName": "Project" } });
+
+        assert_eq!(guard_removals(patch.clone(), Some(&previous)), Some(patch));
+    }
+
+    #[test]
+    fn test_metadata_filter_matches_patterns() {
+        let filter = MetadataFilter::new(&["example.com/*-hash".to_string()]);
+
+        assert!(filter.is_ignored("lifecycle.cattle.io/create.mgmt-project-rbac-remove"));
+        assert!(filter.is_ignored("field.cattle.io/creatorId"));
+        assert!(filter.is_ignored("example.com/config-hash"));
+        assert!(!filter.is_ignored("field.cattle.io/projectId"));
+        assert!(!filter.is_ignored("example.com/owner"));
+    }
+
+    #[test]
+    fn test_metadata_filter_hides_rancher_annotations_from_diff() {
+        let filter = MetadataFilter::default();
+        let mut current = project_value(json!({
+            "team": "a",
+            "field.cattle.io/creatorId": "user-abc",
+            "lifecycle.cattle.io/create.project-precan-alert-controller": "true"
+        }));
+        let mut desired = project_value(json!({ "team": "a" }));
+        filter.strip_value(&mut current);
+        filter.strip_value(&mut desired);
+
+        assert!(calculate_json_patch::<IoCattleManagementv3Project>(&current, &desired).is_none());
+    }
+
+    #[test]
+    fn test_metadata_filter_never_removes_ignored_annotation_section() {
+        let filter = MetadataFilter::default();
+        let mut current = project_value(json!({ "field.cattle.io/creatorId": "user-abc" }));
+        let mut desired = project_value(json!({}));
+        filter.strip_value(&mut current);
+        filter.strip_value(&mut desired);
+
+        assert!(calculate_json_patch::<IoCattleManagementv3Project>(&current, &desired).is_none());
+
+        let mut body = desired.clone();
+        filter.copy_ignored(&project_value(json!({ "field.cattle.io/creatorId": "user-abc" })), &mut body);
+        assert_eq!(
+            body.pointer("/metadata/annotations"),
+            Some(&json!({ "field.cattle.io/creatorId": "user-abc" }))
+        );
+    }
+
+    #[test]
+    fn test_removing_the_last_user_annotation_keeps_the_rancher_ones() {
+        let live: IoCattleManagementv3Project = serde_json::from_value(project_value(json!({
+            "team": "a",
+            "field.cattle.io/creatorId": "user-abc"
+        })))
+        .unwrap();
+        let mut stored_value = project_value(json!({}));
+        stored_value["metadata"].as_object_mut().unwrap().remove("annotations");
+        let stored: IoCattleManagementv3Project = serde_json::from_value(stored_value).unwrap();
+        let mut diff = ClusterDiff::default();
+        diff.push(
+            (ObjectType::Project, "p-abc".to_string(), Some("c-123".to_string())),
+            Some(&live),
+            Some(&stored),
+            PROJECT_EXCLUDE_PATHS,
+            &MetadataFilter::default(),
+            &PrincipalRules::default(),
+        );
+
+        assert_eq!(
+            diff.objects[0].change,
+            ObjectChange::Changed(json!([{ "op": "remove", "path": "/metadata/annotations/team" }]))
+        );
+    }
+
+    /// The labels of the live project `live` once the change of `stored` over it is applied.
+    fn applied_labels(live: Value, stored: Value) -> Value {
+        let live: IoCattleManagementv3Project = serde_json::from_value(live).unwrap();
+        let stored: IoCattleManagementv3Project = serde_json::from_value(stored).unwrap();
+        let mut diff = ClusterDiff::default();
+        diff.push(
+            (ObjectType::Project, "p-abc".to_string(), Some("c-123".to_string())),
+            Some(&live),
+            Some(&stored),
+            PROJECT_EXCLUDE_PATHS,
+            &MetadataFilter::default(),
+            &PrincipalRules::default(),
+        );
+        let mut applied = serde_json::to_value(&live).unwrap();
+        if let ObjectChange::Changed(patch) = &diff.objects[0].change {
+            json_patch::patch(&mut applied, &serde_json::from_value::<json_patch::Patch>(patch.clone()).unwrap()).unwrap();
+        }
+        applied.pointer("/metadata/labels").cloned().unwrap_or_else(|| json!({}))
+    }
+
+    fn labelled_project(labels: Value, annotations: Value) -> Value {
+        let mut project = project_value(annotations);
+        project["metadata"]["labels"] = labels;
+        project
+    }
+
+    #[test]
+    fn test_three_way_merge_of_a_map_field() {
+        // description, label in the file, in Rancher and last applied, and the label after the update
+        #[allow(clippy::type_complexity)]
+        let cases: [(&str, Option<&str>, Option<&str>, Option<&str>, Option<&str>); 11] = [
+            ("unchanged", Some("a"), Some("a"), Some("a"), Some("a")),
+            ("applied before recording", Some("a"), Some("a"), None, Some("a")),
+            ("changed in Rancher", Some("a"), Some("b"), Some("a"), Some("a")),
+            ("changed in Rancher, never applied", Some("a"), Some("b"), None, Some("a")),
+            ("changed in the file", Some("b"), Some("a"), Some("a"), Some("b")),
+            ("removed in Rancher", Some("a"), None, Some("a"), Some("a")),
+            ("added to the file", Some("a"), None, None, Some("a")),
+            ("removed from the file", None, Some("a"), Some("a"), None),
+            ("removed from the file, changed in Rancher", None, Some("b"), Some("a"), None),
+            ("added in Rancher", None, Some("a"), None, Some("a")),
+            ("removed on both sides", None, None, Some("a"), None),
+        ];
+        for (description, file, rancher, last, expected) in cases {
+            let labels = |value: Option<&str>| {
+                let mut labels = json!({ "team": "platform" });
+                if let Some(value) = value {
+                    labels["tier"] = json!(value);
+                }
+                labels
+            };
+            let last_applied = last_applied(&labelled_project(labels(last), json!({})), PROJECT_EXCLUDE_PATHS);
+            let live = labelled_project(labels(rancher), json!({ LAST_APPLIED_ANNOTATION: last_applied }));
+            let stored = labelled_project(labels(file), json!({}));
+
+            assert_eq!(applied_labels(live, stored), labels(expected), "{}", description);
+        }
+    }
+
+    #[test]
+    fn test_three_way_merge_keeps_what_rancher_added_to_a_map_removed_from_the_file() {
+        let last = last_applied(&labelled_project(json!({ "team": "platform" }), json!({})), PROJECT_EXCLUDE_PATHS);
+        let live = labelled_project(
+            json!({ "team": "platform", "example.com/scanned": "true" }),
+            json!({ LAST_APPLIED_ANNOTATION: last }),
+        );
+        let stored = project_value(json!({}));
+
+        assert_eq!(applied_labels(live, stored), json!({ "example.com/scanned": "true" }));
+    }
+
+    #[test]
+    fn test_without_last_applied_the_file_wins() {
+        let live = labelled_project(json!({ "team": "platform", "example.com/scanned": "true" }), json!({}));
+        let stored = labelled_project(json!({ "team": "platform" }), json!({}));
+
+        assert_eq!(applied_labels(live, stored), json!({ "team": "platform" }));
+    }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        /// Object keys, including the characters JSON pointers escape and dots, which
+        /// `clean_up_value` paths cannot address.
+        fn key() -> impl Strategy<Value = String> {
+            prop::sample::select(vec!["a", "b", "spec", "0", "", "a.b", "x/y", "~1", "m~n"]).prop_map(str::to_string)
+        }
+
+        fn json_value() -> impl Strategy<Value = Value> {
+            let leaf = prop_oneof![
+                Just(Value::Null),
+                any::<bool>().prop_map(Value::from),
+                any::<i32>().prop_map(Value::from),
+                "[a-z]{0,3}".prop_map(Value::from),
+            ];
+            leaf.prop_recursive(4, 32, 4, |inner| {
+                prop_oneof![
+                    prop::collection::vec(inner.clone(), 0..4).prop_map(Value::Array),
+                    prop::collection::btree_map(key(), inner, 0..4).prop_map(|map| Value::Object(map.into_iter().collect())),
+                ]
+            })
+        }
+
+        /// `base` with `changes` laid over it: keys of objects are merged, `null` removes a
+        /// key and anything else replaces what is there.
+        fn overlay(base: &Value, changes: &Value) -> Value {
+            match (base, changes) {
+                (Value::Object(base), Value::Object(changes)) => {
+                    let mut merged = base.clone();
+                    for (key, change) in changes {
+                        match (change, base.get(key)) {
+                            (Value::Null, _) => {
+                                merged.remove(key);
+                            }
+                            (change, Some(existing)) => {
+                                merged.insert(key.clone(), overlay(existing, change));
+                            }
+                            (change, None) => {
+                                merged.insert(key.clone(), change.clone());
+                            }
+                        }
+                    }
+                    Value::Object(merged)
+                }
+                (_, changes) => changes.clone(),
+            }
+        }
+
+        /// A value and a changed copy of it, so the patch between them has nested operations.
+        fn changed_pair() -> impl Strategy<Value = (Value, Value)> {
+            (json_value(), json_value()).prop_map(|(base, changes)| {
+                let changed = overlay(&base, &changes);
+                (base, changed)
+            })
+        }
+
+        fn parse_pointer(pointer: &str) -> Vec<String> {
+            pointer.split('/').skip(1).map(|token| token.replace("~1", "/").replace("~0", "~")).collect()
+        }
+
+        /// Apply a JSON patch of `add`, `remove` and `replace` operations as RFC 6902 reads.
+        fn apply_reference(value: &mut Value, patch: &Value) {
+            for operation in patch.as_array().expect("a patch is an array") {
+                let op = operation["op"].as_str().expect("an operation has an op");
+                let tokens = parse_pointer(operation["path"].as_str().expect("an operation has a path"));
+                let Some((last, parents)) = tokens.split_last() else {
+                    assert_ne!(op, "remove", "removing the whole document");
+                    *value = operation["value"].clone();
+                    continue;
+                };
+                let parent = parents.iter().fold(&mut *value, |current, token| match current {
+                    Value::Object(map) => map.get_mut(token).expect("the parent of the operation exists"),
+                    Value::Array(items) => &mut items[token.parse::<usize>().expect("an array index")],
+                    other => panic!("`{}` does not hold `{}`", other, token),
+                });
+                match (op, parent) {
+                    ("add" | "replace", Value::Object(map)) => {
+                        assert!(op == "add" || map.contains_key(last), "replacing a missing key");
+                        map.insert(last.clone(), operation["value"].clone());
+                    }
+                    ("remove", Value::Object(map)) => {
+                        map.remove(last).expect("removing a missing key");
+                    }
+                    ("add", Value::Array(items)) if last == "-" => items.push(operation["value"].clone()),
+                    ("add", Value::Array(items)) => items.insert(last.parse().unwrap(), operation["value"].clone()),
+                    ("replace", Value::Array(items)) => items[last.parse::<usize>().unwrap()] = operation["value"].clone(),
+                    ("remove", Value::Array(items)) => {
+                        items.remove(last.parse().unwrap());
+                    }
+                    (op, parent) => panic!("cannot {} `{}` in `{}`", op, last, parent),
+                }
+            }
+        }
+
+        /// `value` without the keys at `paths`, walking objects only like `clean_up_value`.
+        fn remove_reference(mut value: Value, paths: &[String]) -> Value {
+            'paths: for path in paths {
+                let parts: Vec<&str> = path.split('.').collect();
+                let (last, parents) = parts.split_last().unwrap();
+                let mut current = &mut value;
+                for part in parents {
+                    match current.as_object_mut().and_then(|map| map.get_mut(*part)) {
+                        Some(next) => current = next,
+                        None => continue 'paths,
+                    }
+                }
+                if let Some(map) = current.as_object_mut() {
+                    map.remove(*last);
+                }
+            }
+            value
+        }
+
+        proptest! {
+            #[test]
+            fn prop_patch_turns_current_into_desired((current, desired) in changed_pair()) {
+                match calculate_json_patch::<Value>(&current, &desired) {
+                    Some(patch) => {
+                        let mut patched = current.clone();
+                        apply_reference(&mut patched, &patch);
+                        prop_assert_eq!(patched, desired, "patch {}", patch);
+                    }
+                    None => prop_assert_eq!(current, desired),
+                }
+            }
+
+            #[test]
+            fn prop_patch_between_unrelated_values_applies(current in json_value(), desired in json_value()) {
+                let mut patched = current.clone();
+                if let Some(patch) = calculate_json_patch::<Value>(&current, &desired) {
+                    apply_reference(&mut patched, &patch);
+                }
+                prop_assert_eq!(patched, desired);
+            }
+
+            #[test]
+            fn prop_clean_up_value_only_removes_addressed_keys(
+                value in json_value(),
+                paths in prop::collection::vec(
+                    prop::collection::vec(prop::sample::select(vec!["a", "b", "spec", "0", "", "x/y"]), 1..4)
+                        .prop_map(|parts| parts.join(".")),
+                    0..4,
+                ),
+            ) {
+                let mut cleaned = value.clone();
+                let exclude_paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+                clean_up_value(&mut cleaned, &exclude_paths);
+                prop_assert_eq!(cleaned, remove_reference(value, &paths));
+            }
+        }
+    }
+}
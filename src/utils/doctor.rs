@@ -0,0 +1,431 @@
+//! The repo doctor, run before every run touches the repository.
+//!
+//! A crash in the middle of a git operation leaves lock files and half-written state behind,
+//! and every later run fails with the same libgit2 error until someone cleans up by hand.
+//! The doctor removes locks old enough to be left behind, checks that the repository can be
+//! read and, if allowed, replaces a repository that cannot be read with a fresh clone.
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use git2::{ErrorCode, Repository, StatusOptions};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::utils::git::{probe_remote, safe_clone_repository, GitAuth, GitError};
+
+/// The file in the git folder a run holds a lock on while it uses the repository.
+pub const RUN_LOCK_FILE: &str = "shepherd.lock";
+
+/// The lock files git leaves in the git folder when it does not finish.
+const GIT_LOCK_FILES: &[&str] = &[
+    "index.lock",
+    "HEAD.lock",
+    "ORIG_HEAD.lock",
+    "FETCH_HEAD.lock",
+    "config.lock",
+    "packed-refs.lock",
+    "shallow.lock",
+];
+
+/// The `[repo_doctor]` section of the config.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepoDoctorConfig {
+    /// Seconds a git lock file must be old before it is taken for one a crash left behind
+    #[serde(default = "default_stale_lock_after")]
+    pub stale_lock_after: u64,
+    /// Replace a repository that cannot be read with a fresh clone of the remote
+    #[serde(default)]
+    pub reclone: bool,
+}
+
+fn default_stale_lock_after() -> u64 {
+    600
+}
+
+impl Default for RepoDoctorConfig {
+    fn default() -> Self {
+        Self {
+            stale_lock_after: default_stale_lock_after(),
+            reclone: false,
+        }
+    }
+}
+
+/// What the doctor did to the repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Healing {
+    StaleLockRemoved,
+    Recloned,
+}
+
+impl Healing {
+    fn label(&self) -> &'static str {
+        match self {
+            Healing::StaleLockRemoved => "stale_lock_removed",
+            Healing::Recloned => "recloned",
+        }
+    }
+}
+
+/// How often each healing happened since Shepherd started.
+static HEALINGS: Mutex<BTreeMap<Healing, u64>> = Mutex::new(BTreeMap::new());
+
+fn record_healing(healing: Healing) {
+    if let Ok(mut healings) = HEALINGS.lock() {
+        *healings.entry(healing).or_default() += 1;
+    }
+}
+
+/// The healings since Shepherd started in the Prometheus text format, empty if there were none.
+pub fn healing_metrics() -> String {
+    let healings = HEALINGS.lock().map(|healings| healings.clone()).unwrap_or_default();
+    if healings.is_empty() {
+        return String::new();
+    }
+    let mut metrics = String::from(
+        "# HELP shepherd_repo_healings_total Repairs of the repository by the repo doctor, by action\n\
+         # TYPE shepherd_repo_healings_total counter\n",
+    );
+    for (healing, count) in healings {
+        metrics.push_str(&format!("shepherd_repo_healings_total{{action=\"{}\"}} {}\n", healing.label(), count));
+    }
+    metrics
+}
+
+/// The lock of a run on a repository, released when dropped.
+///
+/// Git locks are only removed by the holder, a lock file of another Shepherd process may
+/// belong to a git operation it is in the middle of.
+#[derive(Debug)]
+pub struct RunLock {
+    _file: File,
+}
+
+impl RunLock {
+    /// Lock the repository of the git folder `git_dir`, `None` if another process holds it.
+    pub fn try_acquire(git_dir: &Path) -> std::io::Result<Option<Self>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(git_dir.join(RUN_LOCK_FILE))?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self { _file: file })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(e),
+        }
+    }
+}
+
+/// Remove the git lock files of `git_dir` last modified at least `older_than` ago.
+///
+/// # Returns
+/// The paths of the removed lock files
+pub fn remove_stale_locks(git_dir: &Path, older_than: Duration) -> Vec<PathBuf> {
+    let refs = walkdir::WalkDir::new(git_dir.join("refs"))
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() && entry.path().extension().is_some_and(|extension| extension == "lock"))
+        .map(|entry| entry.into_path());
+    let candidates = GIT_LOCK_FILES.iter().map(|name| git_dir.join(name)).chain(refs);
+
+    let now = SystemTime::now();
+    let mut removed = Vec::new();
+    for path in candidates {
+        let Ok(modified) = std::fs::metadata(&path).and_then(|metadata| metadata.modified()) else {
+            continue;
+        };
+        let age = now.duration_since(modified).unwrap_or_default();
+        if age < older_than {
+            continue;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => {
+                warn!(
+                    "Removed {}, left behind {}s ago by a git operation that did not finish",
+                    path.display(),
+                    age.as_secs()
+                );
+                record_healing(Healing::StaleLockRemoved);
+                removed.push(path);
+            }
+            Err(e) => error!("Failed to remove the stale git lock {}: {}", path.display(), e),
+        }
+    }
+    removed
+}
+
+/// Check that the repository can be read: HEAD resolves, unless there is no commit yet,
+/// and the status of the working tree can be listed.
+pub fn check_integrity(repo: &Repository) -> Result<(), GitError> {
+    match repo.head() {
+        Ok(head) => {
+            head.peel_to_commit()?;
+        }
+        Err(e) if e.code() == ErrorCode::UnbornBranch => {}
+        Err(e) => return Err(e.into()),
+    }
+    repo.statuses(Some(StatusOptions::new().include_untracked(false)))?;
+    Ok(())
+}
+
+/// Make sure the repository at `folder` can be used by a run.
+///
+/// Takes the run lock, removes the git locks older than `config.stale_lock_after` and checks
+/// the integrity of the repository. With `config.reclone`, a repository failing the check is
+/// replaced with a fresh clone of `remote_url`, the broken one is kept next to it.
+///
+/// # Returns
+/// The run lock, to hold for the rest of the run, `None` if there is no repository yet or
+/// another process holds the lock
+///
+/// # Errors
+/// `GitError::Other` if the repository cannot be read and is not, or cannot be, re-cloned
+pub async fn examine(
+    folder: &Path,
+    config: &RepoDoctorConfig,
+    remote_url: &str,
+    auth_method: &GitAuth,
+    clone_depth: Option<u32>,
+) -> Result<Option<RunLock>, GitError> {
+    let git_dir = folder.join(".git");
+    if !git_dir.is_dir() {
+        return Ok(None);
+    }
+    let lock = match RunLock::try_acquire(&git_dir) {
+        Ok(Some(lock)) => {
+            remove_stale_locks(&git_dir, Duration::from_secs(config.stale_lock_after));
+            Some(lock)
+        }
+        Ok(None) => {
+            warn!("Another Shepherd process holds {}, leaving its git locks alone", git_dir.join(RUN_LOCK_FILE).display());
+            None
+        }
+        Err(e) => {
+            warn!("Cannot lock {}, leaving the git locks alone: {}", git_dir.join(RUN_LOCK_FILE).display(), e);
+            None
+        }
+    };
+
+    let broken = match Repository::open(folder) {
+        Ok(repo) => match check_integrity(&repo) {
+            Ok(()) => return Ok(lock),
+            Err(e) => e,
+        },
+        Err(e) => e.into(),
+    };
+    error!("The repository in {} cannot be read: {}", folder.display(), broken);
+    if !config.reclone {
+        return Err(GitError::Other(format!(
+            "{} cannot be read ({}), clone it again or set repo_doctor.reclone = true",
+            folder.display(),
+            broken
+        )));
+    }
+    if lock.is_none() {
+        return Err(GitError::Other(format!(
+            "{} cannot be read ({}) and is not re-cloned while another process may be using it",
+            folder.display(),
+            broken
+        )));
+    }
+    // held until the clone is in place, so no other run uses the folder while it is swapped
+    let (kept, lock) = reclone(folder, remote_url, auth_method, clone_depth).await?;
+    error!(
+        "Replaced the repository in {} with a fresh clone of {}, the broken one is kept in {}",
+        folder.display(),
+        remote_url,
+        kept.display()
+    );
+    record_healing(Healing::Recloned);
+    Ok(Some(lock))
+}
+
+/// Clone `remote_url` next to `folder` and swap the clone in, moving `folder` aside.
+///
+/// # Returns
+/// Where the replaced repository was moved to, and the run lock of the clone, taken before
+/// it is swapped in
+async fn reclone(
+    folder: &Path,
+    remote_url: &str,
+    auth_method: &GitAuth,
+    clone_depth: Option<u32>,
+) -> Result<(PathBuf, RunLock), GitError> {
+    if remote_url.is_empty() {
+        return Err(GitError::Other(format!("{} cannot be re-cloned without a remote", folder.display())));
+    }
+    probe_remote(remote_url, auth_method)?;
+
+    let name = folder.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S");
+    let clone_path = folder.with_file_name(format!("{}.reclone-{}", name, stamp));
+    let kept = folder.with_file_name(format!("{}.broken-{}", name, stamp));
+    std::fs::create_dir_all(&clone_path)?;
+
+    let cloned = match safe_clone_repository(&clone_path, remote_url, auth_method, clone_depth).await {
+        // an empty remote is initialized instead, it has nothing to restore from
+        Ok(repo) if repo.head().is_err() => Err(GitError::Other(format!("{} has no commits to re-clone", remote_url))),
+        Ok(repo) => check_integrity(&repo),
+        Err(e) => Err(e),
+    };
+    let locked = cloned.and_then(|()| {
+        RunLock::try_acquire(&clone_path.join(".git"))?
+            .ok_or_else(|| GitError::Other(format!("The run lock of {} is held by another process", clone_path.display())))
+    });
+    let lock = match locked {
+        Ok(lock) => lock,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&clone_path);
+            return Err(e);
+        }
+    };
+
+    std::fs::rename(folder, &kept)?;
+    if let Err(e) = std::fs::rename(&clone_path, folder) {
+        // put the broken repository back rather than leave nothing in place
+        let _ = std::fs::rename(&kept, folder);
+        return Err(e.into());
+    }
+    Ok((kept, lock))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(repo: &Repository) {
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = repo.find_tree(repo.index().unwrap().write_tree().unwrap()).unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "initial", &tree, &[]).unwrap();
+    }
+
+    fn age(path: &Path, seconds: u64) {
+        let modified = SystemTime::now() - Duration::from_secs(seconds);
+        File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn test_only_old_git_locks_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit(&repo);
+        let git_dir = dir.path().join(".git");
+        let index_lock = git_dir.join("index.lock");
+        let ref_lock = git_dir.join("refs/heads/main.lock");
+        let fresh_lock = git_dir.join("HEAD.lock");
+        for path in [&index_lock, &ref_lock, &fresh_lock] {
+            std::fs::write(path, "").unwrap();
+        }
+        age(&index_lock, 3600);
+        age(&ref_lock, 3600);
+
+        let mut removed = remove_stale_locks(&git_dir, Duration::from_secs(600));
+        removed.sort();
+
+        assert_eq!(removed, [git_dir.join("index.lock"), git_dir.join("refs/heads/main.lock")]);
+        assert!(fresh_lock.exists());
+        assert!(healing_metrics().contains("shepherd_repo_healings_total{action=\"stale_lock_removed\"}"));
+    }
+
+    #[test]
+    fn test_run_lock_is_held_by_one_run() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let lock = RunLock::try_acquire(dir.path()).unwrap();
+        assert!(lock.is_some());
+        assert!(RunLock::try_acquire(dir.path()).unwrap().is_none());
+
+        drop(lock);
+        assert!(RunLock::try_acquire(dir.path()).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_locks_of_another_run_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit(&repo);
+        let git_dir = dir.path().join(".git");
+        let index_lock = git_dir.join("index.lock");
+        std::fs::write(&index_lock, "").unwrap();
+        age(&index_lock, 3600);
+        let _other_run = RunLock::try_acquire(&git_dir).unwrap().unwrap();
+
+        let lock = examine(dir.path(), &RepoDoctorConfig::default(), "", &GitAuth::GitCredentialHelper, None)
+            .await
+            .unwrap();
+
+        assert!(lock.is_none());
+        assert!(index_lock.exists());
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_repository_is_recloned_when_allowed() {
+        let dir = tempfile::tempdir().unwrap();
+        let remote_path = dir.path().join("remote");
+        let remote = Repository::init(&remote_path).unwrap();
+        std::fs::write(remote_path.join("README.md"), "remote\n").unwrap();
+        let mut index = remote.index().unwrap();
+        index.add_path(Path::new("README.md")).unwrap();
+        index.write().unwrap();
+        commit(&remote);
+        let remote_url = format!("file://{}", remote_path.display());
+
+        let folder = dir.path().join("checkout");
+        let checkout = Repository::clone(&remote_url, &folder).unwrap();
+        let head = checkout.head().unwrap().name().unwrap().to_string();
+        // HEAD pointing at a commit that is gone
+        std::fs::write(folder.join(".git").join(&head), "0123456789abcdef0123456789abcdef01234567\n").unwrap();
+
+        let refused = examine(&folder, &RepoDoctorConfig::default(), &remote_url, &GitAuth::GitCredentialHelper, None).await;
+        assert!(matches!(refused, Err(GitError::Other(message)) if message.contains("repo_doctor.reclone")));
+
+        let config = RepoDoctorConfig { reclone: true, ..RepoDoctorConfig::default() };
+        let lock = examine(&folder, &config, &remote_url, &GitAuth::GitCredentialHelper, None).await.unwrap();
+
+        assert!(lock.is_some());
+        check_integrity(&Repository::open(&folder).unwrap()).unwrap();
+        assert_eq!(std::fs::read_to_string(folder.join("README.md")).unwrap(), "remote\n");
+        let kept: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("checkout.broken-"))
+            .collect();
+        assert_eq!(kept.len(), 1);
+        assert!(healing_metrics().contains("shepherd_repo_healings_total{action=\"recloned\"}"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_run_lock_is_held_while_the_repository_is_recloned() {
+        // a remote that answers once the test is done looking at the lock
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let remote_url = format!("http://{}/config.git", listener.local_addr().unwrap());
+        let (connected, healing) = std::sync::mpsc::channel();
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        std::thread::spawn(move || {
+            let (_stream, _) = listener.accept().unwrap();
+            connected.send(()).unwrap();
+            let _ = released.recv();
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("checkout");
+        Repository::init(&folder).unwrap();
+        let git_dir = folder.join(".git");
+        // HEAD pointing at a commit that is gone
+        std::fs::write(git_dir.join("refs/heads/master"), "0123456789abcdef0123456789abcdef01234567\n").unwrap();
+        std::fs::write(git_dir.join("HEAD"), "ref: refs/heads/master\n").unwrap();
+
+        let config = RepoDoctorConfig { reclone: true, ..RepoDoctorConfig::default() };
+        let examined = tokio::spawn(async move { examine(&folder, &config, &remote_url, &GitAuth::GitCredentialHelper, None).await });
+        healing.recv().unwrap();
+
+        assert!(RunLock::try_acquire(&git_dir).unwrap().is_none());
+        release.send(()).unwrap();
+        assert!(examined.await.unwrap().is_err());
+        assert!(RunLock::try_acquire(&git_dir).unwrap().is_some());
+    }
+}
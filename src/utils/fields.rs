@@ -0,0 +1,137 @@
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+
+/// The field names of the struct `T` deserializes as, empty if `T` is not a struct.
+///
+/// Asks the derived `Deserialize` implementation for its fields without parsing any input.
+pub fn struct_fields<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// A deserializer that only records the fields a struct asks for.
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for FieldNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("fields recorded"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// The field in `fields` closest to the unknown `field`, if one is close enough to be a typo.
+///
+/// Case and underscores are ignored, so `displayName` and `displayname` both match `display_name`.
+pub fn closest_field<'a>(field: &str, fields: &[&'a str]) -> Option<&'a str> {
+    let normalize = |name: &str| name.to_lowercase().replace('_', "");
+    let field = normalize(field);
+    fields
+        .iter()
+        .map(|known| (levenshtein(&field, &normalize(known)), *known))
+        .filter(|(distance, _)| *distance <= (field.len() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known)
+}
+
+/// The number of single character insertions, deletions and substitutions turning `a` into `b`.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// `unknown field `x``, with a suggestion if `x` looks like a typo of a field in `fields`.
+///
+/// `field` is the dotted path serde reports, only top-level fields are matched against `fields`.
+pub fn describe_unknown_field(field: &str, fields: &[&str]) -> String {
+    let suggestion = (!field.contains('.'))
+        .then(|| closest_field(field, fields))
+        .flatten();
+    match suggestion {
+        Some(known) => format!("unknown field `{}`, did you mean `{}`?", field, known),
+        None => format!("unknown field `{}`", field),
+    }
+}
+
+/// The 1-based line and column of the top-level key `key` in YAML, JSON or TOML `contents`.
+pub fn key_location(contents: &str, key: &str) -> Option<(usize, usize)> {
+    let quoted = format!("\"{}\"", key);
+    contents.lines().enumerate().find_map(|(index, line)| {
+        let trimmed = line.trim_start();
+        let rest = trimmed
+            .strip_prefix(quoted.as_str())
+            .or_else(|| trimmed.strip_prefix(key))?;
+        let is_key = rest.trim_start().starts_with([':', '=']);
+        is_key.then(|| (index + 1, line.len() - trimmed.len() + 1))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::cluster::Cluster;
+    use crate::resources::project::Project;
+    use crate::resources::prtb::ProjectRoleTemplateBinding;
+    use crate::resources::rt::RoleTemplate;
+
+    #[test]
+    fn test_typos_suggest_the_field_of_each_struct() {
+        let cases = [
+            ("resourceQutoa", struct_fields::<Project>(), "resource_quota"),
+            ("displayname", struct_fields::<Cluster>(), "display_name"),
+            ("role_tempalte_name", struct_fields::<ProjectRoleTemplateBinding>(), "role_template_name"),
+            ("contxt", struct_fields::<RoleTemplate>(), "context"),
+        ];
+
+        for (typo, fields, expected) in cases {
+            assert_eq!(
+                describe_unknown_field(typo, fields),
+                format!("unknown field `{}`, did you mean `{}`?", typo, expected)
+            );
+        }
+    }
+
+    #[test]
+    fn test_unrelated_and_nested_fields_get_no_suggestion() {
+        let fields = struct_fields::<Project>();
+
+        assert_eq!(describe_unknown_field("owner", fields), "unknown field `owner`");
+        assert_eq!(
+            describe_unknown_field("resource_quota.limt", fields),
+            "unknown field `resource_quota.limt`"
+        );
+        assert!(struct_fields::<String>().is_empty());
+    }
+
+    #[test]
+    fn test_key_location_finds_top_level_keys() {
+        assert_eq!(key_location("id: p-abc\n  displayname: a\n", "displayname"), Some((2, 3)));
+        assert_eq!(key_location("{\n  \"displayname\": \"a\"\n}", "displayname"), Some((2, 3)));
+        assert_eq!(key_location("id = \"p\"\ndisplayname = \"a\"\n", "displayname"), Some((2, 1)));
+        assert_eq!(key_location("displayname_other: a\n", "displayname"), None);
+    }
+}
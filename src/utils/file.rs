@@ -1,17 +1,25 @@
 use std::path::{Path, PathBuf};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
+use chrono::Utc;
 
 use serde::{de::DeserializeOwned, Serialize, Deserialize};
 use tokio::{fs::OpenOptions, io::AsyncWriteExt, task::JoinHandle, fs::read_dir};
 use tracing::{debug, error};
+use reqwest::Url;
 
-use crate::{load_object, models::{CreatedObject, MinimalObject, ObjectType}, resources::project::Project, resources::prtb::ProjectRoleTemplateBinding, resources::rt::RoleTemplate, serialize_object};
+use crate::{deserialize_object, error::DeletionError, load_object, resources::cluster::Cluster, models::{ConversionError, CreatedObject, MinimalObject, ObjectType}, resources::project::Project, resources::crtb::ClusterRoleTemplateBinding, resources::prtb::ProjectRoleTemplateBinding, resources::rt::RoleTemplate, serialize_object, traits::RancherResource};
+use crate::state::UnpersistedCreation;
+use crate::utils::bundle::{is_bundle, project_document, replace_project};
+use crate::utils::git::strip_revision;
+use crate::utils::safe_path::ensure_within;
+use crate::utils::yaml_edit::keep_comments;
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Hash, clap::ValueEnum)]
 pub enum FileFormat {
     // allow uppercase and lowercase when deserializing
     #[serde(alias = "yaml", alias = "YAML", alias = "Yml", alias = "YML", alias = "yml")]
+    #[value(alias = "yml")]
     Yaml,
     #[serde(alias = "json", alias = "JSON", alias = "Json", alias = "json")]
     Json,
@@ -46,11 +54,7 @@ impl FileFormat {
     ///
     /// This function will return an error if the deserialization fails.
     pub fn deserialize<T: DeserializeOwned>(&self, data: &str) -> Result<T> {
-        match self {
-            FileFormat::Yaml => serde_yaml::from_str(data).map_err(|e| e.into()),
-            FileFormat::Json => serde_json::from_str(data).map_err(|e| e.into()),
-            FileFormat::Toml => toml::from_str(data).map_err(|e| e.into()),
-        }
+        Ok(deserialize_object(data, self, None)?)
     }
 }
 
@@ -67,19 +71,25 @@ impl FileFormat {
 pub async fn get_minimal_object_from_path(object_type: ObjectType, path: &Path) -> Result<MinimalObject> {
     match object_type {
         ObjectType::Project => {
-            let object: Project = load_object(path).await.unwrap();
+            let object: Project = load_object(path).await?;
             MinimalObject::try_from(object)
         },
         ObjectType::RoleTemplate => {
-            let object: RoleTemplate = load_object(path).await.unwrap();
+            let object: RoleTemplate = load_object(path).await?;
             MinimalObject::try_from(object)
         },
         ObjectType::ProjectRoleTemplateBinding => {
-            let object: ProjectRoleTemplateBinding = load_object(path).await.unwrap();
+            let object: ProjectRoleTemplateBinding = load_object(path).await?;
             MinimalObject::try_from(object)
         }
+        ObjectType::ClusterRoleTemplateBinding => {
+            let object: ClusterRoleTemplateBinding = load_object(path).await?;
+            Ok(object.to_minimal_object())
+        }
         ObjectType::Cluster => {
-            bail!("Mininal Object for Cluster unimplemented")
+            let contents = tokio::fs::read_to_string(path).await?;
+            let object: Cluster = file_format_from_path(path).deserialize(&contents)?;
+            Ok(MinimalObject::from(&object))
         }
     }
 }
@@ -108,48 +118,144 @@ pub async fn get_minimal_object_from_contents(
             let object: ProjectRoleTemplateBinding = file_format.deserialize(contents)?;
             MinimalObject::try_from(object)
         },
+        ObjectType::ClusterRoleTemplateBinding => {
+            debug!("Deserializing CRTB: {:#?}", contents);
+            let object: ClusterRoleTemplateBinding = file_format.deserialize(contents)?;
+            Ok(object.to_minimal_object())
+        },
         ObjectType::Cluster => {
-            bail!("Minimal Object for Cluster unimplemented")
+            let object: Cluster = file_format.deserialize(contents)?;
+            Ok(MinimalObject::from(&object))
         }
     }
 }
 
+/// Turn the stored contents of a deleted file into the object to delete from Rancher.
+///
+/// # Arguments
+/// * `object_type` - The type of object the file held
+/// * `path` - The path the file was deleted from, used in the error
+/// * `contents` - The contents of the file in the last commit
+/// * `file_format` - The format of the contents
+/// * `deletable_types` - The types whose objects are deleted with their files
+///
+/// # Errors
+/// * [`DeletionError::Unsupported`] for clusters, which are never deleted from the repository
+/// * [`DeletionError::NotAllowed`] for types missing from `deletable_types`
+/// * [`DeletionError::Malformed`] if the contents do not describe an object of `object_type`
+pub async fn get_object_to_delete(
+    object_type: ObjectType,
+    path: &Path,
+    contents: &str,
+    file_format: &FileFormat,
+    deletable_types: &[ObjectType],
+) -> std::result::Result<MinimalObject, DeletionError> {
+    if !object_type.deletable() {
+        return Err(DeletionError::Unsupported {
+            object_type,
+            path: path.to_path_buf(),
+        });
+    }
+    if !deletable_types.contains(&object_type) {
+        return Err(DeletionError::NotAllowed {
+            object_type,
+            path: path.to_path_buf(),
+        });
+    }
+    // a deleted bundle deletes its project, Rancher deletes the bindings with it
+    let project = is_bundle(path).then(|| project_document(contents)).flatten();
+    get_minimal_object_from_contents(object_type, project.as_deref().unwrap_or(contents), file_format)
+        .await
+        .map_err(|e| DeletionError::Malformed {
+            object_type,
+            source: match e.downcast::<ConversionError>() {
+                Ok(error) => error.at(path),
+                Err(e) => ConversionError::Other(format!("{}: {:#}", path.display(), e).into()),
+            },
+        })
+}
+
+
+/// The outcome of writing back created objects.
+#[derive(Debug, Default)]
+pub struct WriteBack {
+    /// The files written
+    pub written: Vec<PathBuf>,
+    /// The objects whose file could not be written, keyed by object ID
+    pub failed: Vec<(String, UnpersistedCreation)>,
+}
 
 /// Writes back successfully created objects to their respective files
 ///
+/// Each object is written to the file it was created from, never to a path derived from
+/// the name or namespace Rancher returned, and only once the file is confirmed to describe
+/// that object. Files are handled in the order of their paths, a file leading outside of
+/// `root` is never written.
+///
 /// # Arguments
 /// * `successes` - A vector of tuples containing the file path and created object
+/// * `root` - The config folder the files must stay in
 /// * `file_format` - The format to use for serialization
 ///
 /// # Returns
-/// A Result with a vector of file paths that were successfully written or error information
+/// A Result with the file paths that were successfully written and the objects whose file
+/// could not be written
 pub async fn write_back_objects(
     successes: Vec<(PathBuf, CreatedObject)>,
+    root: &Path,
     file_format: FileFormat,
-) -> anyhow::Result<Vec<PathBuf>> {
+) -> anyhow::Result<WriteBack> {
+    let mut successes = successes;
+    successes.sort_by(|(a, _), (b, _)| a.cmp(b));
     let mut handles: Vec<JoinHandle<anyhow::Result<PathBuf>>> = Vec::new();
-    let mut results = Vec::new();
+    // the file and the type, ID and namespace of the object of each task
+    let mut origins = Vec::new();
+    let mut results = WriteBack::default();
 
     // Spawn tasks to write back objects
     for (file_path, created_object) in successes {
         let format = file_format;
+        let identity = created_object.metadata().and_then(|(object_type, metadata)| {
+            Some((object_type, metadata.name.clone()?, metadata.namespace.clone()))
+        });
+        origins.push((file_path.clone(), identity));
+        let root = root.to_path_buf();
         handles.push(tokio::spawn(async move {
+            ensure_within(&root, &file_path)?;
+            check_origin(&file_path, &created_object).await?;
             match created_object {
                 CreatedObject::ProjectRoleTemplateBinding(created) => {
                     debug!("Writing PRTB: {:#?}", created);
-                    let convert = ProjectRoleTemplateBinding::try_from(created)?;
+                    let mut convert = ProjectRoleTemplateBinding::try_from(created)?.for_file()?;
+                    // the file itself is what the revision annotations point to
+                    strip_revision(&mut convert.annotations);
+                    write_object_to_file(&file_path, &format, &convert).await?;
+                    Ok(file_path)
+                }
+                CreatedObject::ClusterRoleTemplateBinding(created) => {
+                    debug!("Writing CRTB: {:#?}", created);
+                    let mut convert = ClusterRoleTemplateBinding::try_from(created)?;
+                    strip_revision(&mut convert.annotations);
                     write_object_to_file(&file_path, &format, &convert).await?;
                     Ok(file_path)
                 }
                 CreatedObject::Project(created) => {
                     debug!("Writing Project: {:#?}", created);
-                    let convert = Project::try_from(created)?;
-                    write_object_to_file(&file_path, &format, &convert).await?;
+                    let mut convert = Project::try_from(created)?;
+                    strip_revision(&mut convert.annotations);
+                    if is_bundle(&file_path) {
+                        // the bindings of the bundle stay as written
+                        let contents = tokio::fs::read_to_string(&file_path).await?;
+                        tokio::fs::write(&file_path, replace_project(&contents, &convert)?).await?;
+                    } else {
+                        write_object_to_file(&file_path, &format, &convert).await?;
+                    }
                     Ok(file_path)
                 }
                 CreatedObject::RoleTemplate(created) => {
                     debug!("Writing Role Template: {:#?}", created);
-                    let convert = RoleTemplate::try_from(created)?;
+                    let mut convert = RoleTemplate::try_from(created)?.for_file()?;
+                    strip_revision(&mut convert.annotations);
                     write_object_to_file(&file_path, &format, &convert).await?;
                     Ok(file_path)
                 }
@@ -161,26 +267,171 @@ pub async fn write_back_objects(
     }
 
     // Wait for all tasks to complete and collect results
-    for handle in handles {
-        match handle.await {
-            Ok(result) => match result {
-                Ok(path) => {
-                    debug!("Successfully wrote to file: {}", path.display());
-                    results.push(path);
-                }
-                Err(e) => {
-                    error!("Error writing object: {}", e);
-                }
-            },
+    for ((path, identity), handle) in origins.into_iter().zip(handles) {
+        let error = match handle.await {
+            Ok(Ok(path)) => {
+                debug!("Successfully wrote to file: {}", path.display());
+                results.written.push(path);
+                continue;
+            }
+            Ok(Err(e)) => {
+                error!("Error writing object: {}", e);
+                format!("{:#}", e)
+            }
             Err(join_err) => {
                 error!("Task panicked: {:?}", join_err);
+                join_err.to_string()
             }
+        };
+        // the object exists in Rancher while its file still looks new
+        if let Some((object_type, object_id, namespace)) = identity {
+            results.failed.push((
+                object_id,
+                UnpersistedCreation { object_type, namespace, path, created_at: Utc::now(), error },
+            ));
         }
     }
 
     Ok(results)
 }
 
+/// Fails unless `created` is the object of the file at `file_path`.
+///
+/// Rancher may assign the name of an object whose file leaves it empty, but the namespace
+/// and a name set in the file must come back unchanged.
+async fn check_origin(file_path: &Path, created: &CreatedObject) -> anyhow::Result<()> {
+    let Some((object_type, metadata)) = created.metadata() else {
+        return Ok(());
+    };
+    let origin = get_minimal_object_from_path(object_type, file_path).await?;
+    let namespace_matches = origin.namespace.is_none() || origin.namespace == metadata.namespace;
+    let name_matches = origin
+        .object_id
+        .as_deref()
+        .filter(|id| !id.is_empty())
+        .is_none_or(|id| metadata.name.as_deref() == Some(id));
+    if !namespace_matches || !name_matches {
+        anyhow::bail!(
+            "Not writing {:?} `{}` in `{}` back to {}, the file describes `{}` in `{}`",
+            object_type,
+            metadata.name.as_deref().unwrap_or_default(),
+            metadata.namespace.as_deref().unwrap_or_default(),
+            file_path.display(),
+            origin.object_id.as_deref().unwrap_or_default(),
+            origin.namespace.as_deref().unwrap_or_default(),
+        );
+    }
+    Ok(())
+}
+
+/// The name of the folder holding the objects of the Rancher endpoint at `endpoint_url`.
+///
+/// The name is `host[_port][__path]`: the port is only included when it is not the
+/// default of the scheme, and the path segments follow two underscores, separated by
+/// one. Any character other than ASCII letters, digits, `.` and `-` is percent-encoded,
+/// `_` included, so the name can be mapped back with [`endpoint_from_folder_name`].
+/// The scheme is left out, `http://` and `https://` endpoints share a folder.
+pub fn endpoint_folder_name(endpoint_url: &str) -> String {
+    let url = Url::parse(endpoint_url)
+        .ok()
+        .filter(|url| url.has_host())
+        .or_else(|| Url::parse(&format!("https://{}", endpoint_url)).ok());
+    let Some(url) = url else {
+        return escape_folder_component(endpoint_url);
+    };
+
+    let mut name = escape_folder_component(url.host_str().unwrap_or_default());
+    if let Some(port) = url.port() {
+        name.push_str(&format!("_{}", port));
+    }
+    let segments: Vec<String> = url
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .filter(|segment| !segment.is_empty())
+        .map(escape_folder_component)
+        .collect();
+    if !segments.is_empty() {
+        name.push_str("__");
+        name.push_str(&segments.join("_"));
+    }
+    name
+}
+
+/// The endpoint, `host[:port][/path]` without a scheme, whose folder is called `name`.
+///
+/// # Returns
+/// `None` if `name` was not produced by [`endpoint_folder_name`]
+pub fn endpoint_from_folder_name(name: &str) -> Option<String> {
+    let (authority, path) = match name.split_once("__") {
+        Some((authority, path)) => (authority, Some(path)),
+        None => (name, None),
+    };
+    let (host, port) = match authority.split_once('_') {
+        Some((host, port)) => (host, Some(port.parse::<u16>().ok()?)),
+        None => (authority, None),
+    };
+
+    let mut endpoint = unescape_folder_component(host)?;
+    if let Some(port) = port {
+        endpoint.push_str(&format!(":{}", port));
+    }
+    for segment in path.into_iter().flat_map(|path| path.split('_')) {
+        endpoint.push('/');
+        endpoint.push_str(&unescape_folder_component(segment)?);
+    }
+    Some(endpoint)
+}
+
+/// The endpoint folder for `endpoint_url` inside `config_folder_path`.
+///
+/// Folders named by older versions, with the `https://` prefix removed and every `/`
+/// replaced by `_`, are still found when the folder with the current name does not exist.
+pub fn endpoint_folder(config_folder_path: &Path, endpoint_url: &str) -> PathBuf {
+    let folder = config_folder_path.join(endpoint_folder_name(endpoint_url));
+    if folder.exists() {
+        return folder;
+    }
+
+    let legacy_folder = config_folder_path.join(
+        endpoint_url
+            .trim_end_matches('/')
+            .replace("https://", "")
+            .replace('/', "_"),
+    );
+    if legacy_folder.exists() {
+        debug!("Using endpoint folder {:?} named by an older version", legacy_folder);
+        return legacy_folder;
+    }
+    folder
+}
+
+fn escape_folder_component(component: &str) -> String {
+    component
+        .bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn unescape_folder_component(component: &str) -> Option<String> {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = component.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
 
 /// Get the file name for a specific object type
 pub fn get_file_name_for_object(
@@ -192,12 +443,51 @@ pub fn get_file_name_for_object(
     match object_type {
         ObjectType::Project => format!("{}.project.{}", object_id, extension),
         ObjectType::ProjectRoleTemplateBinding => format!("{}.prtb.{}", object_id, extension),
+        ObjectType::ClusterRoleTemplateBinding => format!("{}.crtb.{}", object_id, extension),
         ObjectType::RoleTemplate => format!("{}.rt.{}", object_id, extension),
         ObjectType::Cluster => format!("{}.cluster.{}", object_id, extension),
         // _ => format!("{}.{}", object_id, extension),
     }
 }
 
+/// The name of the generated list of role templates in the `roles` folder, without its extension.
+pub const ROLE_TEMPLATE_INDEX: &str = "_index";
+
+/// The file name of the role template index, see [`crate::resources::rt::RoleTemplateIndex`].
+pub fn role_template_index_name(file_format: &FileFormat) -> String {
+    format!("{}.{}", ROLE_TEMPLATE_INDEX, file_extension_from_format(file_format))
+}
+
+/// Whether `file_name` is the role template index, which is generated and holds no object.
+pub fn is_role_template_index(file_name: &str) -> bool {
+    file_name.rsplit_once('.').is_some_and(|(stem, _)| stem == ROLE_TEMPLATE_INDEX)
+}
+
+/// The object type, ID and format of a file named `<id>.<type>.<extension>`, the inverse of
+/// [`get_file_name_for_object`].
+///
+/// `yml` is read as YAML. A project bundle, `<project id>.bundle.yaml`, is its project.
+/// `None` if the name has no ID, no known type or no known extension.
+pub fn parse_object_file_name(name: &str) -> Option<(ObjectType, String, FileFormat)> {
+    let (stem, extension) = name.rsplit_once('.')?;
+    if !matches!(extension, "yaml" | "yml" | "json" | "toml") {
+        return None;
+    }
+    let (object_id, kind) = stem.rsplit_once('.')?;
+    let object_type = match kind {
+        "project" | "bundle" => ObjectType::Project,
+        "prtb" => ObjectType::ProjectRoleTemplateBinding,
+        "crtb" => ObjectType::ClusterRoleTemplateBinding,
+        "rt" => ObjectType::RoleTemplate,
+        "cluster" => ObjectType::Cluster,
+        _ => return None,
+    };
+    if object_id.is_empty() {
+        return None;
+    }
+    Some((object_type, object_id.to_string(), file_format_from_extension(extension)))
+}
+
 
 /// Generic function to write any type of object to a file in the given path (overwrites file content)
 /// `file_path` is the path to the directory where the file should be written
@@ -212,7 +502,7 @@ pub async fn write_object_to_file<T>(
 where
     T: serde::Serialize + Send + 'static,
 {
-    let serialized = serialize_object(object, file_format)?;
+    let serialized = keep_comments(file_path, serialize_object(object, file_format)?).await;
     let mut file = OpenOptions::new()
         .write(true)
         .truncate(true)
@@ -221,7 +511,9 @@ where
         .await?;
     file.write_all(serialized.as_bytes())
         .await
-        .context("Failed to write object to file")
+        .context("Failed to write object to file")?;
+    // a tokio file finishes writing in the background unless flushed
+    file.flush().await.context("Failed to write object to file")
 }
 
 
@@ -247,6 +539,20 @@ pub async fn is_directory_empty<P: AsRef<Path>>(dir_path: P) -> Result<bool> {
     Ok(is_empty)
 }
 
+/// Check that files can be written to `folder` by writing and removing a probe file.
+///
+/// Used by `shepherd check`, the folder must already exist.
+pub async fn probe_write_access(folder: &Path) -> Result<()> {
+    let probe = folder.join(format!(".shepherd-check-{}", std::process::id()));
+    tokio::fs::write(&probe, b"")
+        .await
+        .with_context(|| format!("Failed to write to {}", folder.display()))?;
+    tokio::fs::remove_file(&probe)
+        .await
+        .with_context(|| format!("Failed to remove {}", probe.display()))?;
+    Ok(())
+}
+
 pub fn file_format_from_extension(extension: &str) -> FileFormat {
     match extension {
         "yml" => FileFormat::Yaml,
@@ -281,3 +587,169 @@ pub fn file_format(file_format: &str) -> FileFormat {
         _ => FileFormat::Json,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_file_names_parse_back_to_their_object() {
+        for object_type in ObjectType::ALL {
+            for file_format in <FileFormat as clap::ValueEnum>::value_variants() {
+                for object_id in ["p-abc", "c-123:p-abc", "rt.with.dots", "u-abc"] {
+                    let name = get_file_name_for_object(object_id, &object_type, file_format);
+                    assert_eq!(
+                        parse_object_file_name(&name),
+                        Some((object_type, object_id.to_string(), *file_format)),
+                        "{}",
+                        name
+                    );
+                }
+            }
+        }
+
+        assert_eq!(
+            parse_object_file_name("p-abc.bundle.yaml"),
+            Some((ObjectType::Project, "p-abc".to_string(), FileFormat::Yaml))
+        );
+        assert_eq!(
+            parse_object_file_name("member.prtb.yml"),
+            Some((ObjectType::ProjectRoleTemplateBinding, "member".to_string(), FileFormat::Yaml))
+        );
+        for name in [".prtb.yaml", "p-abc.yaml", "p-abc.project.txt", "p-abc.projects.yaml", "README.md", "prtb", "_index.yaml"] {
+            assert_eq!(parse_object_file_name(name), None, "{}", name);
+        }
+        for file_format in <FileFormat as clap::ValueEnum>::value_variants() {
+            assert!(is_role_template_index(&role_template_index_name(file_format)));
+        }
+        assert!(!is_role_template_index("_index.rt.yaml"));
+    }
+
+    #[tokio::test]
+    async fn test_write_back_keeps_the_comments_of_yaml_files() {
+        use rancher_client::models::{IoCattleManagementv3Project, IoCattleManagementv3ProjectSpec, IoK8sApimachineryPkgApisMetaV1ObjectMeta};
+
+        let created = |resource_version: &str| IoCattleManagementv3Project {
+            metadata: Some(IoK8sApimachineryPkgApisMetaV1ObjectMeta {
+                name: Some("p-abc".to_string()),
+                namespace: Some("c-123".to_string()),
+                resource_version: Some(resource_version.to_string()),
+                ..Default::default()
+            }),
+            spec: Some(IoCattleManagementv3ProjectSpec::new("c-123".to_string(), "web".to_string())),
+            ..Default::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("p-abc.project.yaml");
+        let stored = serialize_object(&Project::try_from(created("42")).unwrap(), &FileFormat::Yaml).unwrap();
+        std::fs::write(&file_path, format!("# approved by security 2024-05\n{}", stored)).unwrap();
+
+        let written = write_back_objects(vec![(file_path.clone(), CreatedObject::Project(created("43")))], dir.path(), FileFormat::Yaml)
+            .await
+            .unwrap();
+
+        assert_eq!(written.written, vec![file_path.clone()]);
+        assert!(written.failed.is_empty());
+        let contents = std::fs::read_to_string(&file_path).unwrap();
+        assert!(contents.starts_with("# approved by security 2024-05\n"), "{}", contents);
+        assert_eq!(load_object::<Project>(&file_path).await.unwrap().resource_version.as_deref(), Some("43"));
+    }
+
+    #[test]
+    fn test_endpoint_folder_names_keep_ports_and_paths_apart() {
+        let cases = [
+            ("https://rancher.example.com", "rancher.example.com"),
+            ("https://rancher.example.com/", "rancher.example.com"),
+            ("http://rancher.example.com", "rancher.example.com"),
+            ("https://rancher.example.com:443", "rancher.example.com"),
+            ("https://rancher.example.com:8443/v3", "rancher.example.com_8443__v3"),
+            ("https://rancher.example.com:8443/v3/", "rancher.example.com_8443__v3"),
+            ("https://rancher.example.com/k8s/clusters", "rancher.example.com__k8s_clusters"),
+            ("https://portal.example.com/rancher/", "portal.example.com__rancher"),
+            ("https://portal.example.com//rancher", "portal.example.com__rancher"),
+            ("https://rancher.example.com_8443_v3", "rancher.example.com%5F8443%5Fv3"),
+            ("rancher.example.com/v3", "rancher.example.com__v3"),
+        ];
+
+        for (endpoint_url, name) in cases {
+            assert_eq!(endpoint_folder_name(endpoint_url), name, "{}", endpoint_url);
+        }
+    }
+
+    #[test]
+    fn test_endpoint_folder_names_round_trip() {
+        let endpoints = [
+            "rancher.example.com",
+            "rancher.example.com:8443",
+            "rancher.example.com/v3",
+            "rancher.example.com:8443/k8s/clusters/local",
+            "rancher.example.com/a_b/c%20d",
+            "rancher_example.com",
+        ];
+
+        for endpoint in endpoints {
+            let name = endpoint_folder_name(&format!("https://{}", endpoint));
+            assert_eq!(endpoint_from_folder_name(&name).as_deref(), Some(endpoint), "{}", name);
+        }
+        assert_eq!(endpoint_from_folder_name("host_notaport"), None);
+    }
+
+    #[test]
+    fn test_endpoint_folder_finds_folders_named_by_older_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint_url = "https://rancher.example.com:8443/v3";
+        assert_eq!(
+            endpoint_folder(dir.path(), endpoint_url),
+            dir.path().join("rancher.example.com_8443__v3")
+        );
+
+        std::fs::create_dir(dir.path().join("rancher.example.com:8443_v3")).unwrap();
+        assert_eq!(
+            endpoint_folder(dir.path(), endpoint_url),
+            dir.path().join("rancher.example.com:8443_v3")
+        );
+
+        std::fs::create_dir(dir.path().join("rancher.example.com_8443__v3")).unwrap();
+        assert_eq!(
+            endpoint_folder(dir.path(), endpoint_url),
+            dir.path().join("rancher.example.com_8443__v3")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deleted_files_name_an_object_or_a_typed_error() {
+        let path = Path::new("c-123/crtbs/crtb-abc.crtb.yaml");
+        let crtb = "id: crtb-abc\nnamespace: c-123\ncluster_name: c-123\nrole_template_name: cluster-owner\nresource_version: \"42\"\n";
+
+        let object = get_object_to_delete(ObjectType::ClusterRoleTemplateBinding, path, crtb, &FileFormat::Yaml, &ObjectType::ALL)
+            .await
+            .unwrap();
+        assert_eq!(object.object_id.as_deref(), Some("crtb-abc"));
+        assert_eq!(object.namespace.as_deref(), Some("c-123"));
+        let not_allowed =
+            get_object_to_delete(ObjectType::ClusterRoleTemplateBinding, path, crtb, &FileFormat::Yaml, &ObjectType::DEFAULT_DELETABLE).await;
+        assert!(matches!(not_allowed, Err(DeletionError::NotAllowed { object_type: ObjectType::ClusterRoleTemplateBinding, .. })));
+
+        let malformed = get_object_to_delete(ObjectType::Project, path, "id: [", &FileFormat::Yaml, &ObjectType::DEFAULT_DELETABLE).await;
+        assert!(matches!(malformed, Err(DeletionError::Malformed { object_type: ObjectType::Project, .. })));
+
+        // clusters are never deleted, even if listed
+        let cluster = "id: c-123\ndisplay_name: prod\n";
+        let unsupported = get_object_to_delete(ObjectType::Cluster, path, cluster, &FileFormat::Yaml, &ObjectType::ALL).await;
+        assert!(matches!(unsupported, Err(DeletionError::Unsupported { object_type: ObjectType::Cluster, .. })));
+        let minimal = get_minimal_object_from_contents(ObjectType::Cluster, cluster, &FileFormat::Yaml)
+            .await
+            .unwrap();
+        assert_eq!(minimal.object_id.as_deref(), Some("c-123"));
+    }
+
+    #[tokio::test]
+    async fn test_probe_write_access_leaves_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+
+        probe_write_access(dir.path()).await.unwrap();
+
+        assert!(is_directory_empty(dir.path()).await.unwrap());
+        assert!(probe_write_access(&dir.path().join("missing")).await.is_err());
+    }
+}
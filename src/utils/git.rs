@@ -1,6 +1,8 @@
 use std::{
+    collections::HashMap,
     error::Error,
     path::{Path, PathBuf},
+    sync::OnceLock,
     time::Duration,
 };
 
@@ -14,16 +16,90 @@ use serde::{Deserialize, Serialize};
 use tokio::{fs::read_dir, time::sleep};
 use tracing::{debug, error, info, warn};
 
+use crate::file_limits;
 use crate::models::ObjectType;
+use crate::utils::ignore::ShepherdIgnore;
+use crate::utils::bundle::is_bundle;
+use crate::utils::file::{is_role_template_index, parse_object_file_name};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum GitAuth {
     SshKey(PathBuf),
-    HttpsToken(String),
+    HttpsToken(TokenCredentials),
+    /// Run `command` for a fresh token every time the remote asks for credentials
+    Command {
+        command: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+    },
     SshAgent,
     GitCredentialHelper,
 }
 
+impl GitAuth {
+    /// The name of the method, as used by `GIT_AUTH_METHOD`.
+    pub fn method_name(&self) -> &'static str {
+        match self {
+            GitAuth::SshKey(_) => "ssh_key",
+            GitAuth::HttpsToken(_) => "https_token",
+            GitAuth::Command { .. } => "command",
+            GitAuth::SshAgent => "ssh_agent",
+            GitAuth::GitCredentialHelper => "git_credential_helper",
+        }
+    }
+}
+
+/// A token sent as the password of an HTTPS remote.
+///
+/// Configured either as the bare token, `HttpsToken = "<token>"`, or as a table with a
+/// `username` for hosts such as Azure DevOps that reject an empty one.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "TokenCredentialsConfig")]
+pub struct TokenCredentials {
+    pub token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+}
+
+impl TokenCredentials {
+    pub fn new(token: impl Into<String>, username: Option<String>) -> Self {
+        Self {
+            token: token.into(),
+            username,
+        }
+    }
+}
+
+/// Keeps the token out of logged configs.
+impl std::fmt::Debug for TokenCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenCredentials")
+            .field("token", &"<redacted>")
+            .field("username", &self.username)
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TokenCredentialsConfig {
+    Token(String),
+    WithUsername {
+        token: String,
+        #[serde(default)]
+        username: Option<String>,
+    },
+}
+
+impl From<TokenCredentialsConfig> for TokenCredentials {
+    fn from(config: TokenCredentialsConfig) -> Self {
+        match config {
+            TokenCredentialsConfig::Token(token) => Self::new(token, None),
+            TokenCredentialsConfig::WithUsername { token, username } => Self::new(token, username),
+        }
+    }
+}
+
 use thiserror::Error;
 
 use super::file::is_directory_empty;
@@ -38,14 +114,114 @@ pub enum GitError {
     EmptyDirectory(String),
     #[error("Directory already contains a git repository: {0}")]
     ExistingRepository(String),
+    #[error("Directory is not empty and not a git repository: {0}")]
+    NotARepository(String),
     #[error("Network error: {0}")]
     Network(String),
+    #[error("Push rejected: {0}")]
+    PushRejected(String),
+    #[error("Authentication with {method} failed for {url}")]
+    AuthenticationFailed { url: String, method: String },
     #[error("Other error: {0}")]
     Other(String),
 }
 
+impl GitError {
+    /// Whether the remote did not answer within the timeout of [`set_network_timeout`].
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, GitError::Git(e) if e.code() == git2::ErrorCode::Timeout)
+    }
+}
+
+/// How the changes of a run are split into commits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitGranularity {
+    /// One commit with every change of the run
+    #[default]
+    Run,
+    /// One commit per changed object
+    Object,
+}
+
+impl std::fmt::Display for CommitGranularity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommitGranularity::Run => write!(f, "run"),
+            CommitGranularity::Object => write!(f, "object"),
+        }
+    }
+}
+
+static GIT_AUTHOR: OnceLock<(String, String)> = OnceLock::new();
+
+/// Set the author and committer of the commits Shepherd creates.
+///
+/// Every commit of the process is signed with it, unless the repository's own config sets
+/// `user.name` and `user.email`. A second call changes nothing.
+///
+/// # Arguments
+/// * `name` - The author name
+/// * `email` - The author email address
+pub fn set_git_author(name: &str, email: &str) {
+    let _ = GIT_AUTHOR.set((name.to_string(), email.to_string()));
+}
+
+/// Give up on a fetch or push once the remote does not answer for `timeout`, in every
+/// repository of the process.
+///
+/// Sets both the connect and the read timeout of libgit2. Operations started after the call
+/// use it, a later call replaces it.
+///
+/// # Errors
+/// If libgit2 refuses the option
+pub fn set_network_timeout(timeout: Duration) -> Result<(), GitError> {
+    let millis = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+    // SAFETY: libgit2 options are global, they are only set at startup before any thread uses libgit2
+    unsafe {
+        git2::opts::set_server_connect_timeout_in_milliseconds(millis)?;
+        git2::opts::set_server_timeout_in_milliseconds(millis)?;
+    }
+    Ok(())
+}
+
+/// The author email used unless one is configured, `shepherd@<hostname>`.
+pub fn default_git_author_email() -> String {
+    let hostname = std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty())
+        .unwrap_or_else(|| "localhost".to_string());
+    format!("shepherd@{}", hostname)
+}
+
+/// The signature for a new commit in `repo`.
+///
+/// Uses `user.name` and `user.email` from the repository's own config when both are
+/// set there, the identity from [`set_git_author`] otherwise.
+fn signature(repo: &Repository) -> Result<Signature<'static>, Git2Error> {
+    let local = repo
+        .config()
+        .and_then(|config| config.open_level(git2::ConfigLevel::Local))
+        .ok()
+        .and_then(|config| Some((config.get_string("user.name").ok()?, config.get_string("user.email").ok()?)));
+    if let Some((name, email)) = local {
+        return Signature::now(&name, &email);
+    }
+
+    match GIT_AUTHOR.get() {
+        Some((name, email)) => Signature::now(name, email),
+        None => Signature::now(crate::FULL_CLIENT_ID, &default_git_author_email()),
+    }
+}
+
 const MAX_RETRIES: u32 = 3;
+/// Attempts at pushing before a push rejected as non-fast-forward is given up on.
+pub const MAX_PUSH_ATTEMPTS: u32 = 3;
 const RETRY_DELAY: Duration = Duration::from_secs(5);
+/// How long a token command may take to print a token before it is killed.
+const TOKEN_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// Initializes a new git repository in the given folder and commits all changes, retrying up to
 /// MAX_RETRIES times if a network error occurs.
@@ -83,12 +259,15 @@ pub async fn init_and_commit_git_repo(
     /// empty, it will initialize a new repository instead. If the local folder is not empty, it
     /// will try to open the existing repository.
     ///
+    /// With a `clone_depth` only that many commits of history are fetched.
+    ///
     /// The function will return an error if the local folder already contains a repository, or if
     /// there is an error cloning or opening the repository.
 pub async fn safe_clone_repository(
     config_folder_path: &Path,
     remote_url: &str,
     auth_method: &GitAuth,
+    clone_depth: Option<u32>,
 ) -> Result<Repository, GitError> {
     if is_directory_empty(config_folder_path)
         .await
@@ -98,42 +277,14 @@ pub async fn safe_clone_repository(
         info!("Cloning repository from {}", remote_url);
 
         let mut fetch_options = git2::FetchOptions::new();
+        if let Some(depth) = clone_depth {
+            fetch_options.depth(fetch_depth(depth));
+        }
 
         // Set up remote callbacks
         let mut remote_callbacks = git2::RemoteCallbacks::new();
         remote_callbacks
-            .credentials(|_url, username_from_url, allowed_types| {
-                match &auth_method {
-                    GitAuth::SshKey(key_path) => {
-                        if allowed_types.contains(git2::CredentialType::SSH_KEY) {
-                            let username = username_from_url.unwrap_or("git");
-                            git2::Cred::ssh_key(username, None, key_path, None)
-                        } else {
-                            Err(git2::Error::from_str("SSH key authentication not allowed"))
-                        }
-                    }
-                    GitAuth::HttpsToken(token) => {
-                        if allowed_types.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
-                            git2::Cred::userpass_plaintext(username_from_url.unwrap_or(""), token)
-                        } else {
-                            Err(git2::Error::from_str(
-                                "HTTPS token authentication not allowed",
-                            ))
-                        }
-                    }
-                    GitAuth::SshAgent => {
-                        if allowed_types.contains(git2::CredentialType::SSH_KEY) {
-                            git2::Cred::ssh_key_from_agent(username_from_url.unwrap_or("git"))
-                        } else {
-                            Err(git2::Error::from_str(
-                                "SSH agent authentication not allowed",
-                            ))
-                        }
-                    }
-                    _ => Err(git2::Error::from_str("Unsupported authentication method")),
-                }
-                .map_err(|e| e.into())
-            })
+            .credentials(credentials_callback(auth_method, None))
             .transfer_progress(|progress| {
                 debug!(
                     "Transferred {} bytes out of {} bytes",
@@ -175,7 +326,7 @@ pub async fn safe_clone_repository(
                     Ok(repo)
                 } else {
                     // For other errors, propagate them
-                    Err(GitError::Git(e))
+                    Err(remote_error(e, remote_url, auth_method))
                 }
             }
         }
@@ -183,13 +334,74 @@ pub async fn safe_clone_repository(
         // If the directory is not empty, try to open the repository
         match Repository::open(config_folder_path) {
             Ok(repo) => Ok(repo),
-            Err(_) => Err(GitError::ExistingRepository(
+            Err(_) => Err(GitError::NotARepository(
                 config_folder_path.display().to_string(),
             )),
         }
     }
 }
 
+/// Check that `remote_url` can be read with `auth_method`, like `git ls-remote`.
+///
+/// Connects for a fetch, which has the remote advertise its references, without
+/// touching any local repository.
+///
+/// # Errors
+/// `GitError::AuthenticationFailed` if the remote rejects the credentials
+pub fn probe_remote(remote_url: &str, auth_method: &GitAuth) -> Result<(), GitError> {
+    let mut proxy_options = ProxyOptions::new();
+    proxy_options.auto();
+
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(credentials_callback(auth_method, None));
+
+    let mut remote = git2::Remote::create_detached(remote_url)?;
+    remote
+        .connect_auth(git2::Direction::Fetch, Some(callbacks), Some(proxy_options))
+        .map_err(|e| remote_error(e, remote_url, auth_method))?;
+    Ok(())
+}
+
+/// Whether the working directory of `repo` has no content besides `.git` and ignored files.
+///
+/// Files hidden by a sparse checkout still count as content. The walk runs on a blocking
+/// thread so large checkouts do not stall the runtime.
+///
+/// # Errors
+/// Returns an error if the repository is bare or cannot be read
+pub async fn is_repo_effectively_empty(repo: &Repository) -> Result<bool, GitError> {
+    let root = repo
+        .workdir()
+        .ok_or_else(|| GitError::Other("Repository has no working directory (bare repo?)".to_string()))?
+        .to_path_buf();
+
+    tokio::task::spawn_blocking(move || {
+        let repo = Repository::open(&root)?;
+        let index = repo.index()?;
+        let sparse = index
+            .iter()
+            .any(|entry| entry.flags_extended & git2::IndexEntryExtendedFlag::SKIP_WORKTREE.bits() != 0);
+        if sparse {
+            return Ok(false);
+        }
+
+        for entry in walkdir::WalkDir::new(&root)
+            .into_iter()
+            .filter_entry(|e| e.file_name() != ".git")
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+        {
+            let rel_path = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+            if !repo.status_should_ignore(rel_path).unwrap_or(false) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    })
+    .await
+    .map_err(|e| GitError::Other(format!("Failed to check if the repository is empty: {}", e)))?
+}
+
     /// Initializes a new git repository in the given folder, commits all files in the folder,
     /// and pushes the changes to the given remote URL.
     ///
@@ -286,12 +498,13 @@ async fn init_and_commit_git_repo_inner(
 ///
 /// # Arguments
 /// * `folder_path` - The path of the folder to collect files from.
+/// * `ignore` - Paths that are not Rancher objects and are skipped
 ///
 /// # Returns
 /// A vector containing the absolute paths of all modified files
 /// in the specified folder and its subfolders.
 #[async_backtrace::framed]
-pub async fn get_modified_files(folder_path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+pub async fn get_modified_files(folder_path: &Path, ignore: &ShepherdIgnore) -> Result<Vec<PathBuf>, Box<dyn Error>> {
     let folder_path = folder_path.canonicalize().map_err(|e| {
         format!(
             "Failed to canonicalize folder path {}: {}",
@@ -341,6 +554,10 @@ pub async fn get_modified_files(folder_path: &Path) -> Result<Vec<PathBuf>, Box<
             }
         };
         debug!("Processing path: {:?}", path);
+        if ignore.is_ignored(path, false) {
+            debug!("Skipping ignored path: {:?}", path);
+            continue;
+        }
         if path.starts_with(rel_folder) {
             debug!("Path is under rel_folder: {:?}", path);
             modified_files.push(workdir.join(path));
@@ -350,13 +567,14 @@ pub async fn get_modified_files(folder_path: &Path) -> Result<Vec<PathBuf>, Box<
     Ok(modified_files)
 }
 
-/// Initialize a local git repository in the folder with main branch
+/// Initialize a local git repository in the folder with `branch_name` checked out
 /// # Arguments
 /// * `folder_path` - Folder path to initialize the git repository
 /// * `remote_url` - Remote URL to set up
+/// * `branch_name` - Branch to create, tracking the branch of the same name on `origin`
 ///
 /// # Returns
-/// * `Result<(), String>` - Result indicating success or failure
+/// * `Result<(), GitError>` - Result indicating success or failure
 ///
 pub fn init_git_repo_with_main_branch(
     folder_path: &Path,
@@ -380,10 +598,12 @@ pub fn init_git_repo_with_main_branch(
         "Initializing repository in folder: {}",
         folder_path.display()
     );
-    let repo = Repository::init(folder_path).map_err(GitError::Git)?;
+    let mut init_options = git2::RepositoryInitOptions::new();
+    init_options.initial_head(branch_name);
+    let repo = Repository::init_opts(folder_path, &init_options).map_err(GitError::Git)?;
 
     debug!("Creating an initial commit in repository");
-    let sig = Signature::now(crate::FULL_CLIENT_ID, "shepherd@test.com").map_err(GitError::Git)?;
+    let sig = signature(&repo).map_err(GitError::Git)?;
     let tree_id = {
         let mut index = repo.index().map_err(GitError::Git)?;
         index
@@ -416,20 +636,136 @@ pub fn init_git_repo_with_main_branch(
         repo.remote("origin", remote_url)?;
     }
 
-    debug!("Creating and checking out branch: {}", branch_name);
-    let mut branch = repo
-        .branch(branch_name, &repo.head()?.peel_to_commit()?, false)
-        .map_err(GitError::Git)?;
-    repo.set_head(branch.get().name().unwrap_or("refs/heads/master"))
-        .map_err(GitError::Git)?;
-    repo.checkout_head(Some(git2::build::CheckoutBuilder::new().safe()))
-        .map_err(GitError::Git)?;
+    // the initial commit already created the branch, HEAD points at it
+    set_upstream(&repo, branch_name)?;
 
-    // Set up branch to track remote
-    branch
-        .set_upstream(Some("origin/main"))
-        .map_err(GitError::Git)?;
+    Ok(())
+}
+
+/// Bring the repository back onto `branch` before a run touches it.
+///
+/// Repairs the states a person or an interrupted run can leave behind:
+/// * merges, reverts and cherry-picks in progress are aborted, discarding their changes
+/// * rebases in progress are aborted
+/// * a detached HEAD or another checked out branch is switched to `branch`, which is
+///   created from `origin/<branch>` or the current commit if it does not exist
+/// * an unborn HEAD is pointed at `branch`, checking it out from `origin/<branch>` when
+///   the remote already has it
+///
+/// # Errors
+/// `GitError::Other` describing what to do by hand for states that cannot be repaired,
+/// such as a bisect in progress or local changes blocking the switch to `branch`.
+pub fn repair_repository_state(repo: &Repository, branch: &str) -> Result<(), GitError> {
+    let workdir = repo
+        .workdir()
+        .map(|path| path.display().to_string())
+        .unwrap_or_default();
+
+    match repo.state() {
+        git2::RepositoryState::Clean => {}
+        git2::RepositoryState::Merge
+        | git2::RepositoryState::Revert
+        | git2::RepositoryState::RevertSequence
+        | git2::RepositoryState::CherryPick
+        | git2::RepositoryState::CherryPickSequence => {
+            warn!("Aborting {:?} left in progress in {}", repo.state(), workdir);
+            repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+            repo.cleanup_state()?;
+        }
+        git2::RepositoryState::Rebase
+        | git2::RepositoryState::RebaseInteractive
+        | git2::RepositoryState::RebaseMerge => {
+            warn!("Aborting rebase left in progress in {}", workdir);
+            repo.open_rebase(None)
+                .and_then(|mut rebase| rebase.abort())
+                .map_err(|e| {
+                    GitError::Other(format!(
+                        "Rebase in progress in {} could not be aborted ({}), run `git rebase --abort` there",
+                        workdir, e
+                    ))
+                })?;
+        }
+        state => {
+            return Err(GitError::Other(format!(
+                "Repository {} is in state {:?}, finish or abort it with git before Shepherd can sync",
+                workdir, state
+            )))
+        }
+    }
+
+    let refname = format!("refs/heads/{}", branch);
+    let remote_refname = format!("refs/remotes/origin/{}", branch);
+    let head = match repo.head() {
+        Ok(head) => head,
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => {
+            match repo.refname_to_id(&remote_refname) {
+                Ok(id) => {
+                    info!("HEAD is unborn, checking out {} from origin", branch);
+                    switch_to_branch(repo, branch, id)?;
+                }
+                Err(_) => {
+                    debug!("HEAD is unborn, the first commit creates {}", branch);
+                    repo.set_head(&refname)?;
+                }
+            }
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if head.name() == Some(refname.as_str()) {
+        return Ok(());
+    }
+
+    let head_commit = head.peel_to_commit()?.id();
+    let target = repo
+        .refname_to_id(&refname)
+        .or_else(|_| repo.refname_to_id(&remote_refname))
+        .unwrap_or(head_commit);
+    if repo.head_detached()? {
+        warn!("HEAD is detached at {}, checking out {}", head_commit, branch);
+        if target != head_commit && !repo.graph_descendant_of(target, head_commit)? {
+            warn!("Commit {} is not on {} and is left behind", head_commit, branch);
+        }
+    } else {
+        warn!("{} is checked out instead of {}, switching", head.shorthand().unwrap_or_default(), branch);
+    }
+    switch_to_branch(repo, branch, target)
+}
+
+/// Check out `branch`, creating it at `target` when it does not exist yet.
+fn switch_to_branch(repo: &Repository, branch: &str, target: Oid) -> Result<(), GitError> {
+    let refname = format!("refs/heads/{}", branch);
+    let target = repo.refname_to_id(&refname).unwrap_or(target);
+    let commit = repo.find_commit(target)?;
+
+    repo.checkout_tree(commit.as_object(), Some(git2::build::CheckoutBuilder::default().safe()))
+        .map_err(|e| {
+            GitError::Other(format!(
+                "Cannot check out {} ({}), commit or discard the local changes first",
+                branch, e
+            ))
+        })?;
+    if repo.find_reference(&refname).is_err() {
+        repo.reference(&refname, target, false, "Create branch")?;
+        set_upstream(repo, branch)?;
+    }
+    repo.set_head(&refname)?;
+    Ok(())
+}
 
+/// Make `branch_name` track the branch of the same name on `origin`.
+///
+/// Unlike `Branch::set_upstream` this works before the remote branch exists, the first
+/// push creates it.
+fn set_upstream(repo: &Repository, branch_name: &str) -> Result<(), GitError> {
+    debug!("Setting upstream of {} to origin/{}", branch_name, branch_name);
+    let mut config = repo.config()?;
+    config.set_str(&format!("branch.{}.remote", branch_name), "origin")?;
+    config.set_str(
+        &format!("branch.{}.merge", branch_name),
+        &format!("refs/heads/{}", branch_name),
+    )?;
     Ok(())
 }
 
@@ -513,7 +849,7 @@ fn create_merge_commit(
     let tree_id = index.write_tree()?;
     let tree = repo.find_tree(tree_id)?;
 
-    let signature = repo.signature()?;
+    let signature = signature(repo)?;
     let parent_commit = repo.head()?.peel_to_commit()?;
     let message = "Merge and resolve conflicts";
 
@@ -567,55 +903,11 @@ pub fn push_repo_to_remote(
 
     let repo =
         Repository::open(folder_path).map_err(|e| format!("Failed to open repository: {}", e))?;
-    let config = repo
-        .config()
-        .map_err(|e| format!("Failed to get repository config: {}", e))?;
 
     // Set up remote callbacks
     let mut remote_callbacks = git2::RemoteCallbacks::new();
     remote_callbacks
-        .credentials(|url, username_from_url, allowed_types| {
-            match &auth_method {
-                GitAuth::SshKey(key_path) => {
-                    if allowed_types.contains(git2::CredentialType::SSH_KEY) {
-                        let username = username_from_url.unwrap_or("git");
-                        git2::Cred::ssh_key(username, None, key_path, None)
-                    } else {
-                        Err(git2::Error::from_str("SSH key authentication not allowed"))
-                    }
-                }
-                GitAuth::HttpsToken(token) => {
-                    if allowed_types.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
-                        git2::Cred::userpass_plaintext(username_from_url.unwrap_or(""), token)
-                    } else {
-                        Err(git2::Error::from_str(
-                            "HTTPS token authentication not allowed",
-                        ))
-                    }
-                }
-                GitAuth::SshAgent => {
-                    if allowed_types.contains(git2::CredentialType::SSH_KEY) {
-                        debug!("Using SSH agent authentication");
-                        debug!("Auth method: {:#?}", auth_method);
-                        git2::Cred::ssh_key_from_agent(username_from_url.unwrap_or("git"))
-                    } else {
-                        Err(git2::Error::from_str(
-                            "SSH agent authentication not allowed",
-                        ))
-                    }
-                }
-                GitAuth::GitCredentialHelper => {
-                    if allowed_types.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
-                        git2::Cred::credential_helper(&config, url, username_from_url)
-                    } else {
-                        Err(git2::Error::from_str(
-                            "Git credential helper authentication not allowed",
-                        ))
-                    }
-                }
-            }
-            .map_err(|e| e.into())
-        })
+        .credentials(credentials_callback(&auth_method, Some(&repo)))
         .transfer_progress(|progress| {
             debug!(
                 "Transferred {} bytes out of {} bytes",
@@ -653,7 +945,7 @@ pub fn push_repo_to_remote(
             &["refs/heads/main:refs/heads/main"],
             Some(&mut push_options),
         )
-        .map_err(|e| format!("Failed to push to remote: {}", e))?;
+        .map_err(|e| format!("Failed to push to remote: {}", remote_error(e, remote_url, &auth_method)))?;
     debug!("Push completed successfully.");
     Ok(())
 }
@@ -665,6 +957,11 @@ pub fn push_repo_to_remote(
     /// * `repo` - The git repository to pull changes into
     /// * `branch` - The branch to pull changes from
     /// * `auth_method` - The authentication method to use when pulling from the remote
+    /// * `clone_depth` - How many commits to fetch, `None` for the whole history
+    ///
+    /// In a shallow repository the local branch may be older than the fetched history
+    /// reaches back, the fetch is then repeated with twice the depth until the two meet or
+    /// `MAX_FETCH_DEPTH` is reached.
     ///
     /// # Return
     ///
@@ -677,25 +974,25 @@ pub fn pull_changes(
     repo: &Repository,
     branch: &str,
     auth_method: &GitAuth,
+    clone_depth: Option<u32>,
 ) -> Result<(), GitError> {
     // Setup ProxyOptions
     let mut proxy_options = ProxyOptions::new();
     proxy_options.auto();
 
     let mut callbacks = RemoteCallbacks::new();
-    callbacks.credentials(|url, username_from_url, allowed_types| {
-        match_credentials(url, username_from_url, allowed_types, auth_method).map_err(|e| e.into())
-    });
+    callbacks.credentials(credentials_callback(auth_method, Some(repo)));
 
     let mut remote = repo.find_remote("origin")?;
-    remote.connect_auth(git2::Direction::Fetch, Some(callbacks), Some(proxy_options))?;
+    let remote_url = remote.url().unwrap_or_default().to_string();
+    remote
+        .connect_auth(git2::Direction::Fetch, Some(callbacks), Some(proxy_options))
+        .map_err(|e| remote_error(e, &remote_url, auth_method))?;
 
     let mut fetch_options = git2::FetchOptions::new();
 
     let mut callbacks = RemoteCallbacks::new();
-    callbacks.credentials(|url, username_from_url, allowed_types| {
-        match_credentials(url, username_from_url, allowed_types, auth_method).map_err(|e| e.into())
-    });
+    callbacks.credentials(credentials_callback(auth_method, Some(repo)));
 
     let mut proxy_options = ProxyOptions::new();
     proxy_options.auto();
@@ -703,39 +1000,235 @@ pub fn pull_changes(
     fetch_options.remote_callbacks(callbacks);
     fetch_options.proxy_options(proxy_options);
 
-    remote.fetch(&[branch], Some(&mut fetch_options), None)?;
+    let mut depth = clone_depth;
+    loop {
+        if let Some(depth) = depth {
+            fetch_options.depth(fetch_depth(depth));
+        }
+        remote
+            .fetch(&[branch], Some(&mut fetch_options), None)
+            .map_err(|e| remote_error(e, &remote_url, auth_method))?;
 
-    let fetch_head = repo.find_reference("FETCH_HEAD")?;
-    let fetch_commit = repo.reference_to_annotated_commit(&fetch_head)?;
-    let analysis = repo.merge_analysis(&[&fetch_commit])?;
+        let fetch_head = repo.find_reference("FETCH_HEAD")?;
+        let fetch_commit = repo.reference_to_annotated_commit(&fetch_head)?;
 
-    if analysis.0.is_up_to_date() {
-        Ok(())
-    } else if analysis.0.is_fast_forward() {
+        // a clone checks out the remote's default branch, start the configured one from origin
         let refname = format!("refs/heads/{}", branch);
-        let mut reference = repo.find_reference(&refname)?;
-        reference.set_target(fetch_commit.id(), "Fast-Forward")?;
-        repo.set_head(&refname)?;
-        repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
-        Ok(())
-    } else {
-        Err(GitError::Other("Merge analysis failed".to_string()))
+        if repo.find_reference(&refname).is_err() {
+            info!("Creating local branch {} from origin", branch);
+            let commit = repo.find_commit(fetch_commit.id())?;
+            repo.checkout_tree(commit.as_object(), Some(git2::build::CheckoutBuilder::default().safe()))?;
+            repo.reference(&refname, fetch_commit.id(), false, "Create branch from origin")?;
+            repo.set_head(&refname)?;
+            set_upstream(repo, branch)?;
+            return Ok(());
+        }
+
+        let analysis = repo.merge_analysis(&[&fetch_commit]);
+
+        match analysis {
+            Ok((analysis, _)) if analysis.is_up_to_date() => return Ok(()),
+            Ok((analysis, _)) if analysis.is_fast_forward() => {
+                let mut reference = repo.find_reference(&refname)?;
+                reference.set_target(fetch_commit.id(), "Fast-Forward")?;
+                repo.set_head(&refname)?;
+                repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+                return Ok(());
+            }
+            // both sides have new commits, merge unless the merge base was not fetched
+            Ok((analysis, _))
+                if analysis.is_normal()
+                    && repo
+                        .merge_base(repo.head()?.peel_to_commit()?.id(), fetch_commit.id())
+                        .is_ok() =>
+            {
+                info!("Local and remote branch diverged, merging");
+                repo.merge(&[&fetch_commit], None, None)?;
+                let mut index = repo.index()?;
+                if index.has_conflicts() {
+                    warn!("Merge conflicts detected, taking the remote version");
+                    resolve_index_conflicts(&mut index)?;
+                    index.write()?;
+                    repo.checkout_index(
+                        Some(&mut index),
+                        Some(git2::build::CheckoutBuilder::default().force()),
+                    )?;
+                }
+                create_merge_commit(repo, &mut index, branch)?;
+                repo.cleanup_state()?;
+                return Ok(());
+            }
+            // the merge base may lie beyond the fetched history, deepen and try again
+            _ if repo.is_shallow() && depth.is_some_and(|d| d < MAX_FETCH_DEPTH) => {
+                let deeper = depth.map(|d| d.saturating_mul(2).min(MAX_FETCH_DEPTH));
+                info!("Fetched history too shallow to merge, deepening to {:?} commits", deeper);
+                depth = deeper;
+            }
+            Ok(_) => return Err(GitError::Other("Merge analysis failed".to_string())),
+            Err(e) => return Err(GitError::Git(e)),
+        }
+    }
+}
+
+/// The deepest a shallow fetch is extended to while looking for a merge base.
+pub const MAX_FETCH_DEPTH: u32 = 4096;
+
+/// `depth` as git2 expects it.
+fn fetch_depth(depth: u32) -> i32 {
+    i32::try_from(depth).unwrap_or(i32::MAX)
+}
+
+/// Restrict the working tree to `sparse_paths`, or restore the full checkout when it is empty.
+///
+/// Works like `git sparse-checkout` in cone mode: files at the repository root and
+/// directly inside the parents of a listed folder stay checked out, along with
+/// everything below the listed folders. Tracked files outside are marked skip-worktree
+/// and removed from the working tree unless they carry local changes. Files hidden by a
+/// previous call are brought back once they are in the cone again, files deleted by
+/// hand are left deleted.
+///
+/// The git configuration and `info/sparse-checkout` are kept in line so the git
+/// command line sees the same layout.
+///
+/// # Arguments
+/// * `repo` - The repository to update
+/// * `sparse_paths` - Folders to check out, relative to the repository root
+pub fn apply_sparse_checkout(repo: &Repository, sparse_paths: &[String]) -> Result<(), GitError> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| GitError::Other("Repository has no working directory".to_string()))?
+        .to_path_buf();
+    let mut cone: Vec<String> = sparse_paths
+        .iter()
+        .map(|path| path.trim_matches('/').to_string())
+        .filter(|path| !path.is_empty())
+        .collect();
+    cone.sort();
+    cone.dedup();
+    let sparse = !cone.is_empty();
+
+    let mut config = repo.config()?;
+    config.set_bool("core.sparseCheckout", sparse)?;
+    let sparse_file = repo.path().join("info").join("sparse-checkout");
+    if sparse {
+        config.set_bool("core.sparseCheckoutCone", true)?;
+        std::fs::create_dir_all(repo.path().join("info"))?;
+        std::fs::write(&sparse_file, cone_patterns(&cone))?;
+    } else if sparse_file.exists() {
+        std::fs::remove_file(&sparse_file)?;
+    }
+
+    let mut index = repo.index()?;
+    // commits made through another handle of the repository leave this one stale
+    index.read(false)?;
+    let mut restored = Vec::new();
+    let mut hidden = 0;
+    let entries: Vec<git2::IndexEntry> = index.iter().collect();
+    for mut entry in entries {
+        let path = String::from_utf8_lossy(&entry.path).to_string();
+        let skipped = entry.flags_extended & git2::IndexEntryExtendedFlag::SKIP_WORKTREE.bits() != 0;
+        let skip = sparse && !in_sparse_cone(&path, &cone);
+
+        if skip && !skipped {
+            let file = workdir.join(&path);
+            if file.exists() {
+                match Oid::hash_file(git2::ObjectType::Blob, &file) {
+                    Ok(id) if id == entry.id => {
+                        std::fs::remove_file(&file)?;
+                        remove_empty_parents(&workdir, &file);
+                    }
+                    _ => {
+                        warn!("Keeping {} checked out, it has local changes", path);
+                        continue;
+                    }
+                }
+            }
+            entry.flags_extended |= git2::IndexEntryExtendedFlag::SKIP_WORKTREE.bits();
+            index.add(&entry)?;
+            hidden += 1;
+        } else if !skip && skipped {
+            entry.flags_extended &= !git2::IndexEntryExtendedFlag::SKIP_WORKTREE.bits();
+            index.add(&entry)?;
+            restored.push(path);
+        }
+    }
+    index.write()?;
+
+    if !restored.is_empty() {
+        let mut checkout = git2::build::CheckoutBuilder::new();
+        checkout.recreate_missing(true);
+        for path in &restored {
+            checkout.path(path);
+        }
+        repo.checkout_index(Some(&mut index), Some(&mut checkout))?;
+    }
+
+    debug!("Sparse checkout: {} files hidden, {} restored", hidden, restored.len());
+    Ok(())
+}
+
+/// Whether `path`, relative to the repository root, is checked out with cone mode `cone`.
+fn in_sparse_cone(path: &str, cone: &[String]) -> bool {
+    let Some((parent, _)) = path.rsplit_once('/') else {
+        return true;
+    };
+    cone.iter()
+        .any(|folder| Path::new(path).starts_with(folder) || Path::new(folder).starts_with(parent))
+}
+
+/// The `info/sparse-checkout` contents git writes for the cone `cone`.
+fn cone_patterns(cone: &[String]) -> String {
+    let mut patterns = vec!["/*".to_string(), "!/*/".to_string()];
+    for folder in cone {
+        let components: Vec<&str> = folder.split('/').collect();
+        for len in 1..components.len() {
+            let parent = components[..len].join("/");
+            for pattern in [format!("/{}/", parent), format!("!/{}/*/", parent)] {
+                if !patterns.contains(&pattern) {
+                    patterns.push(pattern);
+                }
+            }
+        }
+        patterns.push(format!("/{}/", folder));
+    }
+    patterns.join("\n") + "\n"
+}
+
+/// Remove the folders above `file` that became empty, up to `root`.
+fn remove_empty_parents(root: &Path, file: &Path) {
+    let mut dir = file.parent();
+    while let Some(current) = dir {
+        if current == root || std::fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
     }
 }
 
+/// Whether the index marks `rel_path` as outside the sparse checkout.
+fn is_skip_worktree(index: &Index, rel_path: &Path) -> bool {
+    index
+        .get_path(rel_path, 0)
+        .is_some_and(|entry| entry.flags_extended & git2::IndexEntryExtendedFlag::SKIP_WORKTREE.bits() != 0)
+}
+
 /// Match the given authentication method against the allowed types and return the appropriate credential.
 ///
 /// * If the authentication method is SSH key, return a SSH key credential if SSH key is allowed.
-/// * If the authentication method is HTTPS token, return a userpass_plaintext credential if userpass_plaintext is allowed.
+/// * If the authentication method is HTTPS token or command, return a userpass_plaintext credential if userpass_plaintext is allowed.
 /// * If the authentication method is SSH agent, return a SSH key from agent credential if SSH key is allowed.
+/// * If the authentication method is the git credential helper, ask the helper configured in
+///   `config`, the configuration of the repository, or the global and system one without a repository.
 /// * Otherwise, return an error.
 ///
-/// The username used for the credential is taken from the URL, or if not present, defaults to "git".
+/// SSH usernames are taken from the URL, or if not present, default to "git". Tokens
+/// use the configured username, then the one from the URL, then an empty one.
 fn match_credentials(
-    _url: &str,
+    url: &str,
     username_from_url: Option<&str>,
     allowed_types: git2::CredentialType,
     auth_method: &GitAuth,
+    config: Option<&git2::Config>,
 ) -> Result<git2::Cred, git2::Error> {
     match &auth_method {
         GitAuth::SshKey(key_path) => {
@@ -747,15 +1240,27 @@ fn match_credentials(
                 Err(git2::Error::from_str("SSH key authentication not allowed"))
             }
         }
-        GitAuth::HttpsToken(token) => {
+        GitAuth::HttpsToken(credentials) => {
             if allowed_types.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
-                git2::Cred::userpass_plaintext(username_from_url.unwrap_or(""), token)
+                let username = credentials.username.as_deref().or(username_from_url).unwrap_or("");
+                git2::Cred::userpass_plaintext(username, &credentials.token)
             } else {
                 Err(git2::Error::from_str(
                     "HTTPS token authentication not allowed",
                 ))
             }
         }
+        GitAuth::Command { command, username } => {
+            if allowed_types.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
+                let token = token_from_command(command, TOKEN_COMMAND_TIMEOUT)?;
+                let username = username.as_deref().or(username_from_url).unwrap_or("");
+                git2::Cred::userpass_plaintext(username, &token)
+            } else {
+                Err(git2::Error::from_str(
+                    "Command token authentication not allowed",
+                ))
+            }
+        }
         GitAuth::SshAgent => {
             if allowed_types.contains(git2::CredentialType::SSH_KEY) {
                 git2::Cred::ssh_key_from_agent(username_from_url.unwrap_or("git"))
@@ -765,40 +1270,270 @@ fn match_credentials(
                 ))
             }
         }
-        _ => Err(git2::Error::from_str("Unsupported authentication method")),
+        GitAuth::GitCredentialHelper => {
+            if allowed_types.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
+                match config {
+                    Some(config) => git2::Cred::credential_helper(config, url, username_from_url),
+                    None => git2::Cred::credential_helper(&git2::Config::open_default()?, url, username_from_url),
+                }
+            } else {
+                Err(git2::Error::from_str(
+                    "Git credential helper authentication not allowed",
+                ))
+            }
+        }
+    }
+}
+
+/// Most credentials handed to libgit2 for one operation.
+///
+/// libgit2 asks again every time the remote rejects a credential, returning the same
+/// one forever would never end the operation.
+const MAX_CREDENTIAL_ATTEMPTS: u32 = 3;
+
+/// A credentials callback for `auth_method` that gives up after `MAX_CREDENTIAL_ATTEMPTS`.
+///
+/// The credential helper is looked up in the configuration of `repo` like git does, so a
+/// helper set in the repository wins over the global one.
+fn credentials_callback<'a>(
+    auth_method: &'a GitAuth,
+    repo: Option<&Repository>,
+) -> impl FnMut(&str, Option<&str>, git2::CredentialType) -> Result<git2::Cred, git2::Error> + 'a {
+    let config = repo.and_then(|repo| match repo.config() {
+        Ok(config) => Some(config),
+        Err(e) => {
+            debug!("Asking the global credential helper, the repository configuration cannot be read: {}", e);
+            None
+        }
+    });
+    let mut attempts = 0;
+    move |url, username_from_url, allowed_types| {
+        attempts += 1;
+        if attempts > MAX_CREDENTIAL_ATTEMPTS {
+            return Err(git2::Error::new(
+                git2::ErrorCode::Auth,
+                git2::ErrorClass::Callback,
+                format!("Credentials for {} were rejected {} times", url, MAX_CREDENTIAL_ATTEMPTS),
+            ));
+        }
+        match_credentials(url, username_from_url, allowed_types, auth_method, config.as_ref())
+    }
+}
+
+/// `e` from talking to `remote_url`, telling failed authentication apart.
+fn remote_error(e: Git2Error, remote_url: &str, auth_method: &GitAuth) -> GitError {
+    if e.code() == git2::ErrorCode::Auth {
+        GitError::AuthenticationFailed {
+            url: remote_url.to_string(),
+            method: auth_method.method_name().to_string(),
+        }
+    } else {
+        GitError::Git(e)
     }
 }
 
+/// Run `command` and return what it prints as the token.
+///
+/// The command gets no stdin to prompt on and is killed once it runs longer than `timeout`,
+/// it runs inside a libgit2 callback that nothing else can interrupt.
+fn token_from_command(command: &[String], timeout: Duration) -> Result<String, git2::Error> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| git2::Error::from_str("Token command is empty"))?;
+    let mut child = std::process::Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map_err(|e| git2::Error::from_str(&format!("Failed to run token command {}: {}", program, e)))?;
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let output = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        std::io::Read::read_to_end(&mut stdout, &mut output).map(|_| output)
+    });
+
+    let started = std::time::Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() < timeout => std::thread::sleep(Duration::from_millis(10)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(git2::Error::new(
+                    git2::ErrorCode::Auth,
+                    git2::ErrorClass::Callback,
+                    format!("Token command {} printed no token within {:?}", program, timeout),
+                ));
+            }
+            Err(e) => return Err(git2::Error::from_str(&format!("Failed to wait for token command {}: {}", program, e))),
+        }
+    };
+    if !status.success() {
+        return Err(git2::Error::from_str(&format!(
+            "Token command {} failed with {}",
+            program, status
+        )));
+    }
+
+    let output = output
+        .join()
+        .ok()
+        .and_then(Result::ok)
+        .ok_or_else(|| git2::Error::from_str(&format!("Failed to read the output of token command {}", program)))?;
+    let token = String::from_utf8_lossy(&output).trim().to_string();
+    if token.is_empty() {
+        return Err(git2::Error::from_str(&format!(
+            "Token command {} printed no token",
+            program
+        )));
+    }
+    Ok(token)
+}
+
+/// Push `branch` to `origin`.
+///
+/// # Errors
+/// `GitError::PushRejected` if the remote branch has commits the local one lacks.
 pub fn push_changes(
     repo: &Repository,
     branch: &str,
     auth_method: &GitAuth,
 ) -> Result<(), GitError> {
+    let rejection = std::cell::RefCell::new(None);
     let mut remote_callbacks = RemoteCallbacks::new();
-    remote_callbacks.credentials(|url, username_from_url, allowed_types| {
-        match_credentials(url, username_from_url, allowed_types, auth_method).map_err(|e| e.into())
-    });
+    remote_callbacks.credentials(credentials_callback(auth_method, Some(repo)));
 
     let mut proxy_options = ProxyOptions::new();
     proxy_options.auto();
 
+    remote_callbacks.push_update_reference(|refname, status| {
+        if let Some(status) = status {
+            *rejection.borrow_mut() = Some(format!("{}: {}", refname, status));
+        }
+        Ok(())
+    });
+
     let mut push_options = PushOptions::new();
     push_options.remote_callbacks(remote_callbacks);
     push_options.proxy_options(proxy_options);
 
     let mut remote = repo.find_remote("origin")?;
     let refspec = format!("refs/heads/{}:refs/heads/{}", branch, branch);
-    remote.push(&[&refspec], Some(&mut push_options))?;
-    Ok(())
+    match remote.push(&[&refspec], Some(&mut push_options)) {
+        Ok(()) => {}
+        Err(e) if e.code() == git2::ErrorCode::NotFastForward || e.message().contains("fastforward") => {
+            return Err(GitError::PushRejected(e.message().to_string()))
+        }
+        Err(e) => return Err(remote_error(e, remote.url().unwrap_or_default(), auth_method)),
+    }
+    drop(push_options);
+
+    // remotes speaking the smart protocol report rejected refs instead of failing the push
+    match rejection.into_inner() {
+        Some(status) => Err(GitError::PushRejected(status)),
+        None => Ok(()),
+    }
+}
+
+/// Push `branch`, pulling and merging the remote branch whenever the push is rejected
+/// because someone else pushed first.
+///
+/// # Arguments
+/// * `repo` - The repository to push
+/// * `branch` - The branch to push
+/// * `auth_method` - The authentication method to use for the remote
+/// * `clone_depth` - How many commits to fetch when pulling, `None` for the whole history
+///
+/// # Errors
+/// `GitError::PushRejected` if the push is still rejected after `MAX_PUSH_ATTEMPTS`
+/// attempts, or any error pushing or pulling runs into.
+pub fn push_changes_with_retry(
+    repo: &Repository,
+    branch: &str,
+    auth_method: &GitAuth,
+    clone_depth: Option<u32>,
+) -> Result<(), GitError> {
+    let mut attempt = 1;
+    loop {
+        match push_changes(repo, branch, auth_method) {
+            Err(GitError::PushRejected(reason)) if attempt < MAX_PUSH_ATTEMPTS => {
+                warn!(
+                    "Push rejected ({}), pulling before attempt {} of {}",
+                    reason,
+                    attempt + 1,
+                    MAX_PUSH_ATTEMPTS
+                );
+                if let Err(e) = pull_changes(repo, branch, auth_method, clone_depth) {
+                    warn!("Failed to pull changes: {}", e);
+                    resolve_conflicts(repo, branch)?;
+                }
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The git trailer naming the sync run that made a commit.
+pub const RUN_ID_TRAILER: &str = "Shepherd-Run-Id";
+
+/// `message` with a [`RUN_ID_TRAILER`] for `run_id`, so the commits of a run can be found
+/// with `git log --grep`.
+pub fn with_run_id(message: &str, run_id: &str) -> String {
+    format!("{}\n\n{}: {}", message.trim_end(), RUN_ID_TRAILER, run_id)
+}
+
+/// Commit `files`, paths relative to the root of `repo` with their contents, on top of HEAD to
+/// `branch`, leaving HEAD, the index and the working tree alone.
+///
+/// The branch is created, or moved if it exists, so it can be pushed and merged like any other.
+pub fn commit_to_branch(repo: &Repository, branch: &str, files: &[(PathBuf, String)], message: &str) -> Result<Oid, Git2Error> {
+    let head = repo.head()?.peel_to_commit()?;
+    let mut index = Index::new()?;
+    index.read_tree(&head.tree()?)?;
+    for (path, contents) in files {
+        let entry = git2::IndexEntry {
+            ctime: git2::IndexTime::new(0, 0),
+            mtime: git2::IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100644,
+            uid: 0,
+            gid: 0,
+            file_size: contents.len() as u32,
+            id: repo.blob(contents.as_bytes())?,
+            flags: 0,
+            flags_extended: 0,
+            path: path.to_string_lossy().replace('\\', "/").into_bytes(),
+        };
+        index.add(&entry)?;
+    }
+    let tree = repo.find_tree(index.write_tree_to(repo)?)?;
+    let signature = signature(repo)?;
+    let commit = repo.commit(None, &signature, &signature, message, &tree, &[&head])?;
+    repo.reference(&format!("refs/heads/{}", branch), commit, true, "commit to branch")?;
+    Ok(commit)
 }
 
 /// Commits changes in a given folder path with the specified commit message.
+///
+/// Only changes below `managed` are staged, anything else in the repository and
+/// paths matched by `.gitignore` are left out of the commit.
 /// # Arguments
 /// * `folder_path` - The path of the folder containing the changes.
+/// * `managed` - The endpoint folders and files Shepherd writes, absolute or relative to `folder_path`.
 /// * `message` - The commit message.
 /// # Returns
 /// * `Result<(), String>` - A result indicating success or failure.
-pub fn commit_changes(folder_path: &Path, message: &str) -> Result<(), String> {
+pub fn commit_changes(folder_path: &Path, managed: &[PathBuf], message: &str) -> Result<(), String> {
+    commit_changes_except(folder_path, managed, &[], message)
+}
+
+/// Like [`commit_changes`], but the files in `held_back` are left out of the commit, such as
+/// the files of changes left to the next runs.
+pub fn commit_changes_except(folder_path: &Path, managed: &[PathBuf], held_back: &[PathBuf], message: &str) -> Result<(), String> {
     if !folder_path.exists() {
         warn!("Folder does not exist: {}", folder_path.display());
         return Err(format!("Folder does not exist: {}", folder_path.display()));
@@ -822,10 +1557,42 @@ pub fn commit_changes(folder_path: &Path, message: &str) -> Result<(), String> {
     let mut index = repo
         .index()
         .map_err(|e| format!("Failed to get index: {}", e))?;
-    debug!("Adding all files to index");
+    let pathspecs = managed_pathspecs(folder_path, managed);
+    if pathspecs.is_empty() {
+        // an empty pathspec would stage the whole repository
+        debug!("No managed paths, nothing to commit");
+        return Ok(());
+    }
+    let held_back = managed_pathspecs(folder_path, held_back);
+    let is_held_back = |path: &Path| held_back.iter().any(|held_back| Path::new(held_back) == path);
+    debug!("Adding managed paths to index: {:?}", pathspecs);
     index
-        .add_all(["*"], IndexAddOption::FORCE, None)
+        .add_all(
+            pathspecs.iter(),
+            IndexAddOption::DEFAULT,
+            Some(&mut |path: &Path, _: &[u8]| i32::from(is_held_back(path))),
+        )
         .map_err(|e| format!("Failed to add files to index: {}", e))?;
+    // add_all only picks up new and modified files, this stages the deleted ones,
+    // except those merely hidden by a sparse checkout
+    let skipped: Vec<PathBuf> = index
+        .iter()
+        .filter(|entry| entry.flags_extended & git2::IndexEntryExtendedFlag::SKIP_WORKTREE.bits() != 0)
+        .map(|entry| PathBuf::from(String::from_utf8_lossy(&entry.path).to_string()))
+        .collect();
+    index
+        .update_all(
+            pathspecs.iter(),
+            Some(&mut |path: &Path, _: &[u8]| i32::from(skipped.iter().any(|skipped| skipped == path) || is_held_back(path))),
+        )
+        .map_err(|e| format!("Failed to stage deleted files: {}", e))?;
+
+    commit_index(&repo, &mut index, message)?;
+    Ok(())
+}
+
+/// Write `index` and commit its tree on top of HEAD.
+fn commit_index(repo: &Repository, index: &mut Index, message: &str) -> Result<Oid, String> {
     index
         .write()
         .map_err(|e| format!("Failed to write index: {}", e))?;
@@ -839,10 +1606,7 @@ pub fn commit_changes(folder_path: &Path, message: &str) -> Result<(), String> {
         .map_err(|e| format!("Failed to find tree: {}", e))?;
 
     debug!("Creating commit signature");
-    let sig = repo
-        .signature()
-        .or_else(|_| Signature::now(crate::FULL_CLIENT_ID, "shepherd@test.com"))
-        .map_err(|e| format!("Failed to create signature: {}", e))?;
+    let sig = signature(repo).map_err(|e| format!("Failed to create signature: {}", e))?;
 
     debug!("Preparing parent commits");
     let parents: Vec<Commit> = match repo.head() {
@@ -867,13 +1631,200 @@ pub fn commit_changes(folder_path: &Path, message: &str) -> Result<(), String> {
         .map_err(|e| format!("Failed to create commit: {}", e))?;
 
     info!("Created commit with id: {}", commit_oid);
-    Ok(())
+    Ok(commit_oid)
+}
+
+/// Commit every changed object file on its own, so each commit describes a single object.
+///
+/// The message names the object and what happened to it, e.g.
+/// `project c-xyz/p-abc: update spec`. Changed files that are not objects are
+/// committed together afterwards with `fallback_message`. Every message ends with the
+/// [`RUN_ID_TRAILER`] of `run_id`.
+///
+/// # Arguments
+/// * `folder_path` - The root of the repository
+/// * `managed` - The endpoint folders and files Shepherd writes, absolute or relative to `folder_path`
+/// * `held_back` - Files left out of the commits, absolute or relative to `folder_path`
+/// * `ignore` - Paths that are not Rancher objects
+/// * `fallback_message` - Message for the commit of changed files that are not objects
+/// * `run_id` - The sync run the commits belong to
+///
+/// # Returns
+/// The number of commits created
+pub fn commit_each_object(
+    folder_path: &Path,
+    managed: &[PathBuf],
+    held_back: &[PathBuf],
+    ignore: &ShepherdIgnore,
+    fallback_message: &str,
+    run_id: &str,
+) -> Result<usize, String> {
+    let repo =
+        Repository::open(folder_path).map_err(|e| format!("Failed to open repository: {}", e))?;
+    let pathspecs = managed_pathspecs(folder_path, managed);
+    let held_back = managed_pathspecs(folder_path, held_back);
+    let index = repo
+        .index()
+        .map_err(|e| format!("Failed to get index: {}", e))?;
+
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+    let statuses = repo
+        .statuses(Some(&mut opts))
+        .map_err(|e| format!("Failed to get statuses: {}", e))?;
+
+    let mut changes: Vec<(PathBuf, Status)> = statuses
+        .iter()
+        .filter_map(|entry| entry.path().map(|path| (PathBuf::from(path), entry.status())))
+        .filter(|(path, _)| pathspecs.iter().any(|managed| path.starts_with(managed)))
+        .filter(|(path, _)| !held_back.iter().any(|held_back| path == Path::new(held_back)))
+        .filter(|(path, _)| !is_skip_worktree(&index, path))
+        .collect();
+    // one commit per object in a stable order
+    changes.sort();
+
+    let mut commits = 0;
+    let mut other_files = Vec::new();
+    for (path, status) in changes {
+        let Some(object_type) = determine_object_type(&path, ignore) else {
+            other_files.push(path);
+            continue;
+        };
+        let message = with_run_id(&describe_object_change(&repo, &path, object_type, status), run_id);
+        stage_and_commit(&repo, &[path], &message)?;
+        commits += 1;
+    }
+
+    if !other_files.is_empty() {
+        stage_and_commit(&repo, &other_files, &with_run_id(fallback_message, run_id))?;
+        commits += 1;
+    }
+
+    Ok(commits)
+}
+
+/// `managed` relative to the repository root at `folder_path`, for use as pathspecs.
+fn managed_pathspecs(folder_path: &Path, managed: &[PathBuf]) -> Vec<String> {
+    let mut pathspecs: Vec<String> = managed
+        .iter()
+        .map(|path| {
+            path.strip_prefix(folder_path)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace('\\', "/")
+        })
+        .filter(|path| !path.is_empty())
+        .collect();
+    pathspecs.sort();
+    pathspecs.dedup();
+    pathspecs
+}
+
+/// Stage exactly `paths`, relative to the repository root, and commit them.
+fn stage_and_commit(repo: &Repository, paths: &[PathBuf], message: &str) -> Result<Oid, String> {
+    let workdir = repo
+        .workdir()
+        .ok_or("Repository has no working directory")?;
+    let mut index = repo
+        .index()
+        .map_err(|e| format!("Failed to get index: {}", e))?;
+
+    for path in paths {
+        if workdir.join(path).exists() {
+            index.add_path(path)
+        } else {
+            index.remove_path(path)
+        }
+        .map_err(|e| format!("Failed to stage {}: {}", path.display(), e))?;
+    }
+
+    commit_index(repo, &mut index, message)
+}
+
+/// A one-line summary of what happened to the object stored at `path`.
+fn describe_object_change(repo: &Repository, path: &Path, object_type: ObjectType, status: Status) -> String {
+    let kind = match object_type {
+        ObjectType::Project => "project",
+        ObjectType::ProjectRoleTemplateBinding => "prtb",
+        ObjectType::ClusterRoleTemplateBinding => "crtb",
+        ObjectType::RoleTemplate => "roletemplate",
+        ObjectType::Cluster => "cluster",
+    };
+
+    let file_name = path.file_name().and_then(|f| f.to_str()).unwrap_or_default();
+    let id = parse_object_file_name(file_name).map_or_else(
+        || file_name.split('.').next().unwrap_or(file_name).to_string(),
+        |(_, object_id, _)| object_id,
+    );
+    let parents: Vec<&str> = path
+        .parent()
+        .map(|parent| parent.iter().filter_map(|c| c.to_str()).collect())
+        .unwrap_or_default();
+    // projects and crtbs sit one folder below their cluster, prtbs in their project folder,
+    // bundles in their cluster folder
+    let scope = match object_type {
+        ObjectType::Project if is_bundle(path) => parents.last(),
+        ObjectType::Project | ObjectType::ClusterRoleTemplateBinding => parents.iter().rev().nth(1),
+        ObjectType::ProjectRoleTemplateBinding => parents.last(),
+        ObjectType::RoleTemplate | ObjectType::Cluster => None,
+    };
+    let name = match scope {
+        Some(scope) => format!("{}/{}", scope, id),
+        None => id.to_string(),
+    };
+
+    let action = if status.intersects(Status::WT_NEW | Status::INDEX_NEW) {
+        "add".to_string()
+    } else if status.intersects(Status::WT_DELETED | Status::INDEX_DELETED) {
+        "delete".to_string()
+    } else {
+        match changed_fields(repo, path) {
+            Some(fields) if !fields.is_empty() => format!("update {}", fields.join(", ")),
+            _ => "update".to_string(),
+        }
+    };
+
+    format!("{} {}: {}", kind, name, action)
+}
+
+/// The top-level fields that differ between the file at HEAD and in the working directory.
+fn changed_fields(repo: &Repository, path: &Path) -> Option<Vec<String>> {
+    let format = crate::utils::file::file_format_from_path(path);
+    let head = repo
+        .head()
+        .ok()?
+        .peel_to_tree()
+        .ok()?
+        .get_path(path)
+        .ok()?
+        .to_object(repo)
+        .ok()?
+        .peel_to_blob()
+        .ok()?;
+    let before: serde_json::Value = format.deserialize(std::str::from_utf8(head.content()).ok()?).ok()?;
+    let after: serde_json::Value = format
+        .deserialize(&std::fs::read_to_string(repo.workdir()?.join(path)).ok()?)
+        .ok()?;
+
+    let (before, after) = (before.as_object()?, after.as_object()?);
+    let mut fields: Vec<String> = before
+        .keys()
+        .chain(after.keys())
+        .filter(|key| before.get(*key) != after.get(*key))
+        .cloned()
+        .collect();
+    fields.sort();
+    fields.dedup();
+    Some(fields)
 }
 
 /// Collect the new uncommitted (untracked) files from a given folder path
 ///
 /// # Arguments
 /// * `folder_path` - The path of the folder to collect files from.
+/// * `ignore` - Paths that are not Rancher objects and are skipped
 ///
 /// # Returns
 /// A vector containing the absolute paths of all uncommitted (untracked) files
@@ -882,6 +1833,7 @@ pub fn commit_changes(folder_path: &Path, message: &str) -> Result<(), String> {
 #[async_recursion]
 pub async fn get_new_uncommited_files(
     folder_path: &Path,
+    ignore: &ShepherdIgnore,
 ) -> Result<Vec<(ObjectType, PathBuf)>, Box<dyn Error>> {
     let repo =
         Repository::discover(folder_path).map_err(|e| format!("Failed to open Git repo: {}", e))?;
@@ -916,7 +1868,14 @@ pub async fn get_new_uncommited_files(
 
         if metadata.is_dir() {
             debug!("Directory: {:?}", path);
-            let mut child = get_new_uncommited_files(&path).await?;
+            if path
+                .strip_prefix(workdir)
+                .is_ok_and(|rel| ignore.is_ignored(rel, true))
+            {
+                debug!("Skipping ignored directory: {:?}", path);
+                continue;
+            }
+            let mut child = get_new_uncommited_files(&path, ignore).await?;
             new_files.append(&mut child);
         } else if metadata.is_file() {
             debug!("File: {:?}", path);
@@ -930,7 +1889,10 @@ pub async fn get_new_uncommited_files(
 
             if status.contains(Status::WT_NEW) {
                 // Determine object type from path
-                let object_type = determine_object_type(rel);
+                let Some(object_type) = determine_object_type(rel, ignore) else {
+                    debug!("Skipping ignored file: {:?}", rel);
+                    continue;
+                };
                 debug!("New file: {:?}, type: {:?}", rel, object_type);
                 new_files.push((object_type, path));
             }
@@ -941,6 +1903,7 @@ pub async fn get_new_uncommited_files(
         ObjectType::RoleTemplate => 0,
         ObjectType::Project => 1,
         ObjectType::ProjectRoleTemplateBinding => 2,
+        ObjectType::ClusterRoleTemplateBinding => 2,
         ObjectType::Cluster => 3, // optional: push clusters to the end
     });
 
@@ -953,6 +1916,7 @@ pub async fn get_new_uncommited_files(
 ///
 /// # Arguments
 /// * `folder_path` - The path of the folder to collect files from.
+/// * `ignore` - Paths that are not Rancher objects and are skipped
 ///
 /// # Returns
 /// A vector containing the absolute paths of all deleted files
@@ -963,6 +1927,7 @@ pub async fn get_new_uncommited_files(
 #[async_recursion]
 pub async fn get_deleted_files(
     folder_path: &Path,
+    ignore: &ShepherdIgnore,
 ) -> Result<Vec<(ObjectType, PathBuf)>, Box<dyn Error>> {
     debug!(
         "Discovering repository in folder: {}",
@@ -985,6 +1950,7 @@ pub async fn get_deleted_files(
 
     debug!("Fetching statuses for deleted files");
     let statuses = repo.statuses(Some(&mut opts))?;
+    let index = repo.index()?;
 
     for entry in statuses.iter() {
         let status = entry.status();
@@ -998,7 +1964,14 @@ pub async fn get_deleted_files(
 
         if status.contains(Status::WT_DELETED) {
             let full_path = workdir.join(rel_path);
-            let object_type = determine_object_type(Path::new(rel_path));
+            if is_skip_worktree(&index, Path::new(rel_path)) {
+                debug!("Skipping file outside the sparse checkout: {:?}", rel_path);
+                continue;
+            }
+            let Some(object_type) = determine_object_type(Path::new(rel_path), ignore) else {
+                debug!("Skipping ignored file: {:?}", rel_path);
+                continue;
+            };
             debug!("Deleted file: {:?}, type: {:?}", rel_path, object_type);
             deleted_files.push((object_type, full_path));
         } else {
@@ -1014,33 +1987,28 @@ pub async fn get_deleted_files(
 /// Determine the object type from a path.
 ///
 /// # Arguments
-/// * `path` - The path to determine the object type from.
+/// * `path` - The path to determine the object type from, relative to the repository root.
+/// * `ignore` - Paths that are not Rancher objects
 ///
 /// # Returns
-/// The object type determined from the path.
-fn determine_object_type(path: &Path) -> ObjectType {
+/// The object type determined from the path, `None` if the path is ignored or is the role template index.
+fn determine_object_type(path: &Path, ignore: &ShepherdIgnore) -> Option<ObjectType> {
+    if ignore.is_ignored(path, false) {
+        return None;
+    }
+
     let file_name = path
         .file_name()
         .and_then(|f| f.to_str())
         .unwrap_or_default();
 
-    let file_extension = if let Some(ext) = path.extension() {
-        ext.to_string_lossy().to_lowercase()
-    } else {
-        String::new()
-    };
-
-    match (
-        file_name.ends_with(&format!(".project.{}", file_extension)),
-        file_name.ends_with(&format!(".prtb.{}", file_extension)),
-        file_name.ends_with(&format!(".rt.{}", file_extension)),
-        file_name.ends_with(&format!(".cluster.{}", file_extension)),
-    ) {
-        (true, _, _, _) => ObjectType::Project,
-        (_, true, _, _) => ObjectType::ProjectRoleTemplateBinding,
-        (_, _, true, _) => ObjectType::RoleTemplate,
-        (_, _, _, true) => ObjectType::Cluster,
-        _ => {
+    if is_role_template_index(file_name) {
+        return None;
+    }
+    // a bundle stands for its project, changes to its bindings are found by comparing
+    let object_type = match parse_object_file_name(file_name) {
+        Some((object_type, _, _)) => object_type,
+        None => {
             if path.components().any(|c| c.as_os_str() == "roles") {
                 ObjectType::RoleTemplate
             } else if file_name.starts_with("prtb-") {
@@ -1049,13 +2017,147 @@ fn determine_object_type(path: &Path) -> ObjectType {
                 ObjectType::Project
             }
         }
+    };
+    Some(object_type)
+}
+
+/// Reads a file as it was before the last commit that changed it, following first parents
+/// from HEAD, so commits of other files since do not hide the previous revision.
+/// # Arguments
+/// * `repo_path` - A path inside the repository
+/// * `file_path` - The path of the file in the working directory
+///
+/// # Returns
+/// The file contents, or `None` if no earlier commit is reached or the file did not exist there.
+pub fn read_file_at_previous_revision(
+    repo_path: &Path,
+    file_path: &Path,
+) -> Result<Option<String>, GitError> {
+    let repo = Repository::discover(repo_path)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| GitError::Other("Repository has no working directory".to_string()))?;
+
+    let workdir = workdir.canonicalize().unwrap_or_else(|_| workdir.to_path_buf());
+    let file_path = file_path.canonicalize().unwrap_or_else(|_| file_path.to_path_buf());
+    let rel_path = file_path
+        .strip_prefix(&workdir)
+        .map_err(|_| GitError::Other(format!("{:?} is outside of the repository", file_path)))?;
+
+    let mut commit = repo.head()?.peel_to_commit()?;
+    let current = commit.tree()?.get_path(rel_path).ok().map(|entry| entry.id());
+    let parent = loop {
+        let Ok(parent) = commit.parent(0) else {
+            return Ok(None);
+        };
+        if parent.tree()?.get_path(rel_path).ok().map(|entry| entry.id()) != current {
+            break parent;
+        }
+        commit = parent;
+    };
+
+    let Ok(tree_entry) = parent.tree()?.get_path(rel_path) else {
+        return Ok(None);
+    };
+    let (size, _) = repo.odb()?.read_header(tree_entry.id())?;
+    if let Err(e) = file_limits().check_size(&file_path, size as u64) {
+        warn!("Not reading the previous revision, {}", e);
+        return Ok(None);
+    }
+    let blob = tree_entry.to_object(&repo)?.peel_to_blob()?;
+
+    String::from_utf8(blob.content().to_vec())
+        .map(Some)
+        .map_err(|e| GitError::Other(format!("Invalid UTF-8 in blob: {}", e)))
+}
+
+/// Annotation holding the short ID of the commit that last changed the file an object was applied from.
+pub const GIT_COMMIT_ANNOTATION: &str = "shepherd.cattle.io/git-commit";
+
+/// Annotation holding the path, relative to the repository root, of the file an object was applied from.
+pub const GIT_PATH_ANNOTATION: &str = "shepherd.cattle.io/git-path";
+
+/// The commit that last changed an object file, and the file's path in the repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileRevision {
+    pub commit: String,
+    pub path: String,
+}
+
+impl FileRevision {
+    /// Set the git commit and git path annotations in `annotations`.
+    pub fn stamp(&self, annotations: &mut Option<HashMap<String, String>>) {
+        let annotations = annotations.get_or_insert_with(HashMap::new);
+        annotations.insert(GIT_COMMIT_ANNOTATION.to_string(), self.commit.clone());
+        annotations.insert(GIT_PATH_ANNOTATION.to_string(), self.path.clone());
     }
 }
 
-/// Collects deleted files and their contents from a given folder path.
+/// Remove the git commit and git path annotations from `annotations`, dropping the map if it is left empty.
+pub fn strip_revision(annotations: &mut Option<HashMap<String, String>>) {
+    if let Some(map) = annotations {
+        map.remove(GIT_COMMIT_ANNOTATION);
+        map.remove(GIT_PATH_ANNOTATION);
+        if map.is_empty() {
+            *annotations = None;
+        }
+    }
+}
+
+/// Finds the commit that last changed a file, following first parents from HEAD.
+///
+/// The walk stops at the boundary of a shallow clone, the oldest commit reached
+/// with the same contents is reported then.
+///
+/// # Arguments
+/// * `file_path` - The path of the file in the working directory
+///
+/// # Returns
+/// The revision, or `None` if the file is not committed or differs from HEAD in the working directory.
+pub fn file_revision(file_path: &Path) -> Result<Option<FileRevision>, GitError> {
+    let repo = Repository::discover(file_path.parent().unwrap_or(file_path))?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| GitError::Other("Repository has no working directory".to_string()))?;
+
+    let workdir = workdir.canonicalize().unwrap_or_else(|_| workdir.to_path_buf());
+    let file_path = file_path.canonicalize().unwrap_or_else(|_| file_path.to_path_buf());
+    let rel_path = file_path
+        .strip_prefix(&workdir)
+        .map_err(|_| GitError::Other(format!("{:?} is outside of the repository", file_path)))?;
+
+    let Ok(head) = repo.head() else {
+        return Ok(None);
+    };
+    let mut last = head.peel_to_commit()?;
+    let Ok(entry) = last.tree()?.get_path(rel_path) else {
+        return Ok(None);
+    };
+    let blob = entry.id();
+    if Oid::hash_file(git2::ObjectType::Blob, &file_path)? != blob {
+        return Ok(None);
+    }
+
+    while let Ok(parent) = last.parent(0) {
+        match parent.tree()?.get_path(rel_path) {
+            Ok(entry) if entry.id() == blob => last = parent,
+            _ => break,
+        }
+    }
 
+    let commit = last.as_object().short_id()?;
+    Ok(Some(FileRevision {
+        commit: commit.as_str().unwrap_or_default().to_string(),
+        path: rel_path.to_string_lossy().replace('\\', "/"),
+    }))
+}
+
+/// Collects deleted files and their contents from a given folder path.
+///
+/// Files whose committed contents exceed the [`crate::set_file_limits`] size are logged and left out unread.
 /// # Arguments
 /// * `folder_path` - The path of the folder to collect deleted files from.
+/// * `ignore` - Paths that are not Rancher objects and are skipped
 ///
 /// # Returns
 /// A vector of tuples containing the object type, absolute path, and contents
@@ -1063,6 +2165,7 @@ fn determine_object_type(path: &Path) -> ObjectType {
 #[async_backtrace::framed]
 pub async fn get_deleted_files_and_contents(
     folder_path: &Path,
+    ignore: &ShepherdIgnore,
 ) -> Result<Vec<(ObjectType, PathBuf, String)>, Box<dyn Error>> {
     // Discover the Git repository at the given folder path
     let repo =
@@ -1083,6 +2186,7 @@ pub async fn get_deleted_files_and_contents(
 
     // Collect the statuses of files in the repository
     let statuses = repo.statuses(Some(&mut opts))?;
+    let index = repo.index()?;
     debug!("Collected statuses");
 
     // Retrieve the HEAD commit and its associated tree
@@ -1106,6 +2210,14 @@ pub async fn get_deleted_files_and_contents(
         if status.contains(Status::WT_DELETED) {
             let full_path = workdir.join(rel_path);
             let git_rel_path = Path::new(rel_path);
+            if is_skip_worktree(&index, git_rel_path) {
+                debug!("Skipping file outside the sparse checkout: {:?}", git_rel_path);
+                continue;
+            }
+            let Some(object_type) = determine_object_type(git_rel_path, ignore) else {
+                debug!("Skipping ignored file: {:?}", git_rel_path);
+                continue;
+            };
 
             debug!(
                 "Attempting to get blob from HEAD for deleted file: {:?}",
@@ -1114,13 +2226,16 @@ pub async fn get_deleted_files_and_contents(
             // Attempt to retrieve blob from the HEAD commit
             match tree.get_path(git_rel_path) {
                 Ok(tree_entry) => {
+                    let (size, _) = repo.odb()?.read_header(tree_entry.id())?;
+                    if let Err(e) = file_limits().check_size(&full_path, size as u64) {
+                        error!("Not deleting the object of {}", e);
+                        continue;
+                    }
                     let object = tree_entry.to_object(&repo)?;
                     let blob = object.peel_to_blob()?;
                     let contents = String::from_utf8(blob.content().to_vec())
                         .map_err(|e| format!("Invalid UTF-8 in blob: {}", e))?;
 
-                    // Determine the object type from the path
-                    let object_type = determine_object_type(git_rel_path);
                     debug!(
                         "Determined object type {:?} for deleted file {:?}",
                         object_type, git_rel_path
@@ -1136,3 +2251,669 @@ pub async fn get_deleted_files_and_contents(
 
     Ok(deleted_files)
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit_file(repo: &Repository, path: &Path, contents: &str, message: &str) {
+        std::fs::write(path, contents).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = Signature::now("test", "test@example.com").unwrap();
+        let parents: Vec<Commit> = repo.head().ok().and_then(|h| h.peel_to_commit().ok()).into_iter().collect();
+        let parents: Vec<&Commit> = parents.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents).unwrap();
+    }
+
+    /// Add `count` commits to the `main` branch of the bare repository `repo`.
+    fn commit_to_bare(repo: &Repository, count: usize) {
+        let sig = Signature::now("test", "test@example.com").unwrap();
+        for i in 0..count {
+            let blob = repo.blob(format!("revision {}", i).as_bytes()).unwrap();
+            let mut builder = repo.treebuilder(None).unwrap();
+            builder.insert("file.txt", blob, 0o100644).unwrap();
+            let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+            let parent = repo.refname_to_id("refs/heads/main").ok().map(|id| repo.find_commit(id).unwrap());
+            let parents: Vec<&Commit> = parent.iter().collect();
+            repo.commit(Some("refs/heads/main"), &sig, &sig, "commit", &tree, &parents).unwrap();
+        }
+        repo.set_head("refs/heads/main").unwrap();
+    }
+
+    fn history_len(repo: &Repository) -> usize {
+        let mut revwalk = repo.revwalk().unwrap();
+        revwalk.push_head().unwrap();
+        revwalk.count()
+    }
+
+    /// `git daemon` serving the repositories below a folder, killed on drop.
+    struct GitDaemon(std::process::Child);
+
+    impl GitDaemon {
+        /// Serve `base_path` over git://, `None` if git is not installed.
+        fn start(base_path: &Path) -> Option<(Self, u16)> {
+            // the local transport cannot fetch shallow, a smart protocol is needed
+            let port = std::net::TcpListener::bind("127.0.0.1:0").ok()?.local_addr().ok()?.port();
+            // run git-daemon itself, killing the `git daemon` wrapper would leave it running
+            let exec_path = std::process::Command::new("git").arg("--exec-path").output().ok()?;
+            let exec_path = String::from_utf8(exec_path.stdout).ok()?;
+            let child = std::process::Command::new(Path::new(exec_path.trim()).join("git-daemon"))
+                .arg("--reuseaddr")
+                .arg("--export-all")
+                .arg("--listen=127.0.0.1")
+                .arg(format!("--port={}", port))
+                .arg(format!("--base-path={}", base_path.display()))
+                .arg(base_path)
+                .stderr(std::process::Stdio::null())
+                .spawn()
+                .ok()?;
+            let daemon = GitDaemon(child);
+            for _ in 0..50 {
+                if std::net::TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                    return Some((daemon, port));
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            None
+        }
+    }
+
+    impl Drop for GitDaemon {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shallow_clone_and_deepening_pull() {
+        let dir = tempfile::tempdir().unwrap();
+        let bare = Repository::init_bare(dir.path().join("remote.git")).unwrap();
+        commit_to_bare(&bare, 20);
+        let Some((_daemon, port)) = GitDaemon::start(dir.path()) else {
+            eprintln!("git daemon unavailable, skipping");
+            return;
+        };
+        let remote_url = format!("git://127.0.0.1:{}/remote.git", port);
+        let local = dir.path().join("local");
+        std::fs::create_dir_all(&local).unwrap();
+
+        let repo = safe_clone_repository(&local, &remote_url, &GitAuth::SshAgent, Some(1))
+            .await
+            .unwrap();
+        assert!(repo.is_shallow());
+        assert_eq!(history_len(&repo), 1);
+
+        commit_to_bare(&bare, 3);
+        pull_changes(&repo, "main", &GitAuth::SshAgent, Some(1)).unwrap();
+
+        assert_eq!(
+            repo.head().unwrap().target(),
+            bare.refname_to_id("refs/heads/main").ok()
+        );
+        assert_eq!(std::fs::read_to_string(local.join("file.txt")).unwrap(), "revision 2");
+    }
+
+    #[tokio::test]
+    async fn test_sparse_checkout_hides_and_restores_unmanaged_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let repo = Repository::init(&root).unwrap();
+        std::fs::create_dir_all(root.join("rancher.example.com/c-123")).unwrap();
+        std::fs::create_dir_all(root.join("tooling/big")).unwrap();
+        std::fs::write(root.join("tooling/big/blob.rt.yaml"), "id: blob\n").unwrap();
+        std::fs::write(root.join("README.md"), "readme").unwrap();
+        let cluster = root.join("rancher.example.com/c-123/c-123.cluster.yaml");
+        commit_file(&repo, &cluster, "id: c-123\n", "initial");
+
+        apply_sparse_checkout(&repo, &["rancher.example.com".to_string()]).unwrap();
+
+        assert!(!root.join("tooling").exists());
+        assert!(root.join("README.md").exists());
+        assert!(cluster.exists());
+        assert!(get_deleted_files(&root, &ShepherdIgnore::default()).await.unwrap().is_empty());
+
+        std::fs::write(&cluster, "id: c-123\nname: changed\n").unwrap();
+        commit_changes(&root, &[root.join("rancher.example.com")], "sync").unwrap();
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        assert!(tree.get_path(Path::new("tooling/big/blob.rt.yaml")).is_ok());
+
+        apply_sparse_checkout(&repo, &[]).unwrap();
+
+        assert_eq!(std::fs::read_to_string(root.join("tooling/big/blob.rt.yaml")).unwrap(), "id: blob\n");
+        assert!(!repo.config().unwrap().get_bool("core.sparseCheckout").unwrap());
+        assert!(repo.statuses(None).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_repo_effectively_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let repo = Repository::init(&root).unwrap();
+
+        assert!(is_repo_effectively_empty(&repo).await.unwrap());
+
+        std::fs::write(root.join(".git/info/exclude"), "*.log\nbuild/\n").unwrap();
+        std::fs::create_dir_all(root.join("build")).unwrap();
+        std::fs::write(root.join("build/out.yaml"), "id: out\n").unwrap();
+        std::fs::write(root.join("shepherd.log"), "log").unwrap();
+        assert!(is_repo_effectively_empty(&repo).await.unwrap());
+
+        std::fs::create_dir_all(root.join("rancher.example.com/c-123")).unwrap();
+        let cluster = root.join("rancher.example.com/c-123/c-123.cluster.yaml");
+        commit_file(&repo, &cluster, "id: c-123\n", "initial");
+        assert!(!is_repo_effectively_empty(&repo).await.unwrap());
+
+        apply_sparse_checkout(&repo, &["tooling".to_string()]).unwrap();
+        assert!(!cluster.exists());
+        assert!(!is_repo_effectively_empty(&repo).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_push_pulls_and_retries() {
+        let dir = tempfile::tempdir().unwrap();
+        let bare = Repository::init_bare(dir.path().join("remote.git")).unwrap();
+        commit_to_bare(&bare, 1);
+        let remote_url = format!("file://{}", dir.path().join("remote.git").display());
+
+        let mut clones = Vec::new();
+        for name in ["first", "second"] {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(&path).unwrap();
+            let repo = safe_clone_repository(&path, &remote_url, &GitAuth::SshAgent, None)
+                .await
+                .unwrap();
+            commit_file(&repo, &path.join(format!("{}.txt", name)), name, name);
+            clones.push(repo);
+        }
+
+        push_changes(&clones[0], "main", &GitAuth::SshAgent).unwrap();
+        assert!(matches!(
+            push_changes(&clones[1], "main", &GitAuth::SshAgent),
+            Err(GitError::PushRejected(_))
+        ));
+
+        push_changes_with_retry(&clones[1], "main", &GitAuth::SshAgent, None).unwrap();
+
+        let tree = bare.head().unwrap().peel_to_tree().unwrap();
+        for file in ["file.txt", "first.txt", "second.txt"] {
+            assert!(tree.get_path(Path::new(file)).is_ok(), "{} missing", file);
+        }
+        assert_eq!(bare.head().unwrap().peel_to_commit().unwrap().parent_count(), 2);
+    }
+
+    #[test]
+    fn test_init_checks_out_and_tracks_the_configured_branch() {
+        for branch in ["gitops", "release/prod"] {
+            let dir = tempfile::tempdir().unwrap();
+            Repository::init_bare(dir.path().join("remote.git")).unwrap();
+            let remote_url = format!("file://{}", dir.path().join("remote.git").display());
+            let local = dir.path().join("local");
+            std::fs::create_dir_all(&local).unwrap();
+            std::fs::write(local.join("README.md"), "readme").unwrap();
+
+            init_git_repo_with_main_branch(&local, &remote_url, branch).unwrap();
+
+            let repo = Repository::open(&local).unwrap();
+            let refname = format!("refs/heads/{}", branch);
+            assert_eq!(repo.head().unwrap().name(), Some(refname.as_str()));
+            assert!(repo.find_branch("master", git2::BranchType::Local).is_err());
+            let config = repo.config().unwrap();
+            assert_eq!(config.get_string(&format!("branch.{}.remote", branch)).unwrap(), "origin");
+            assert_eq!(config.get_string(&format!("branch.{}.merge", branch)).unwrap(), refname);
+
+            // the first push creates the remote branch, pulls fetch it afterwards
+            push_changes(&repo, branch, &GitAuth::SshAgent).unwrap();
+            pull_changes(&repo, branch, &GitAuth::SshAgent, None).unwrap();
+            let bare = Repository::open_bare(dir.path().join("remote.git")).unwrap();
+            assert_eq!(
+                bare.refname_to_id(&refname).unwrap(),
+                repo.head().unwrap().target().unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pull_creates_configured_branch_missing_from_clone() {
+        let dir = tempfile::tempdir().unwrap();
+        let bare = Repository::init_bare(dir.path().join("remote.git")).unwrap();
+        commit_to_bare(&bare, 2);
+        let main = bare.find_commit(bare.refname_to_id("refs/heads/main").unwrap()).unwrap();
+        bare.branch("release/prod", &main.parent(0).unwrap(), false).unwrap();
+        let remote_url = format!("file://{}", dir.path().join("remote.git").display());
+        let local = dir.path().join("local");
+        std::fs::create_dir_all(&local).unwrap();
+        let repo = safe_clone_repository(&local, &remote_url, &GitAuth::SshAgent, None)
+            .await
+            .unwrap();
+
+        pull_changes(&repo, "release/prod", &GitAuth::SshAgent, None).unwrap();
+
+        assert_eq!(repo.head().unwrap().name(), Some("refs/heads/release/prod"));
+        assert_eq!(std::fs::read_to_string(local.join("file.txt")).unwrap(), "revision 0");
+        assert_eq!(
+            repo.config().unwrap().get_string("branch.release/prod.merge").unwrap(),
+            "refs/heads/release/prod"
+        );
+    }
+
+    #[test]
+    fn test_repair_detached_head() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let file = dir.path().join("p-abc.project.yaml");
+        commit_file(&repo, &file, "v1", "first");
+        let first = repo.head().unwrap().target().unwrap();
+        commit_file(&repo, &file, "v2", "second");
+        repo.branch("main", &repo.head().unwrap().peel_to_commit().unwrap(), false).unwrap();
+        repo.set_head_detached(first).unwrap();
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force())).unwrap();
+
+        repair_repository_state(&repo, "main").unwrap();
+
+        assert_eq!(repo.head().unwrap().name(), Some("refs/heads/main"));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "v2");
+    }
+
+    #[test]
+    fn test_repair_unborn_head() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+
+        repair_repository_state(&repo, "gitops").unwrap();
+        assert_eq!(repo.find_reference("HEAD").unwrap().symbolic_target(), Some("refs/heads/gitops"));
+
+        // with the branch on the remote it is checked out from there
+        let file = dir.path().join("p-abc.project.yaml");
+        commit_file(&repo, &file, "v1", "first");
+        let commit = repo.head().unwrap().target().unwrap();
+        repo.reference("refs/remotes/origin/main", commit, false, "test").unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        repair_repository_state(&repo, "main").unwrap();
+
+        assert_eq!(repo.head().unwrap().target(), Some(commit));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "v1");
+    }
+
+    #[test]
+    fn test_repair_aborts_stale_merge_and_rebase() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let file = dir.path().join("p-abc.project.yaml");
+        commit_file(&repo, &file, "base", "base");
+        let base = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.branch("other", &base, false).unwrap();
+        commit_file(&repo, &file, "ours", "ours");
+        let main = repo.head().unwrap().name().unwrap().to_string();
+        repo.set_head("refs/heads/other").unwrap();
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force())).unwrap();
+        commit_file(&repo, &file, "theirs", "theirs");
+        repo.set_head(&main).unwrap();
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force())).unwrap();
+        let branch = main.trim_start_matches("refs/heads/");
+
+        let other = repo
+            .reference_to_annotated_commit(&repo.find_reference("refs/heads/other").unwrap())
+            .unwrap();
+        repo.merge(&[&other], None, None).unwrap();
+        assert_eq!(repo.state(), git2::RepositoryState::Merge);
+
+        repair_repository_state(&repo, branch).unwrap();
+
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+        assert!(!repo.index().unwrap().has_conflicts());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "ours");
+
+        let head = repo.reference_to_annotated_commit(&repo.head().unwrap()).unwrap();
+        let mut rebase = repo.rebase(Some(&head), Some(&other), None, None).unwrap();
+        rebase.next().unwrap().unwrap();
+        assert_eq!(repo.state(), git2::RepositoryState::RebaseMerge);
+
+        repair_repository_state(&repo, branch).unwrap();
+
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+        assert_eq!(repo.head().unwrap().name(), Some(main.as_str()));
+    }
+
+    #[test]
+    fn test_commits_use_configured_author_unless_repository_sets_one() {
+        set_git_author("Shepherd Bot", "bot@example.com");
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        std::fs::write(dir.path().join("p-abc.project.yaml"), "v1").unwrap();
+
+        commit_changes(dir.path(), &[PathBuf::from("p-abc.project.yaml")], "sync").unwrap();
+
+        let commit = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(commit.author().name(), Some("Shepherd Bot"));
+        assert_eq!(commit.committer().email(), Some("bot@example.com"));
+
+        let mut config = repo.config().unwrap().open_level(git2::ConfigLevel::Local).unwrap();
+        config.set_str("user.name", "Operator").unwrap();
+        config.set_str("user.email", "operator@example.com").unwrap();
+        std::fs::write(dir.path().join("p-abc.project.yaml"), "v2").unwrap();
+
+        commit_changes(dir.path(), &[PathBuf::from("p-abc.project.yaml")], "sync").unwrap();
+
+        let commit = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(commit.author().email(), Some("operator@example.com"));
+    }
+
+    #[test]
+    fn test_https_token_config_with_and_without_username() {
+        let bare: GitAuth = toml::from_str("HttpsToken = \"secret\"").unwrap();
+        let with_username: GitAuth =
+            toml::from_str("[HttpsToken]\ntoken = \"secret\"\nusername = \"oauth2\"").unwrap();
+
+        assert_eq!(bare, GitAuth::HttpsToken(TokenCredentials::new("secret", None)));
+        assert_eq!(
+            with_username,
+            GitAuth::HttpsToken(TokenCredentials::new("secret", Some("oauth2".to_string())))
+        );
+        assert!(!format!("{:?}", with_username).contains("secret"));
+    }
+
+    #[test]
+    fn test_token_from_command() {
+        let echo = |args: &[&str]| {
+            let command: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            token_from_command(&command, TOKEN_COMMAND_TIMEOUT)
+        };
+
+        assert_eq!(echo(&["echo", "  fresh-token "]).unwrap(), "fresh-token");
+        assert!(echo(&["echo"]).is_err());
+        assert!(echo(&["false"]).is_err());
+        assert!(echo(&[]).is_err());
+    }
+
+    #[test]
+    fn test_credential_helper_of_the_repository_is_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        repo.config()
+            .unwrap()
+            .set_str("credential.helper", "!f() { echo username=shepherd; echo password=repo-secret; }; f")
+            .unwrap();
+        let mut callback = credentials_callback(&GitAuth::GitCredentialHelper, Some(&repo));
+
+        let credential = callback("https://git.example.com/config.git", None, git2::CredentialType::USER_PASS_PLAINTEXT);
+
+        assert!(credential.is_ok(), "{:?}", credential.err());
+    }
+
+    #[test]
+    fn test_token_command_is_killed_after_the_timeout() {
+        let command = ["sleep".to_string(), "5".to_string()];
+
+        let started = std::time::Instant::now();
+        let error = token_from_command(&command, Duration::from_millis(100)).unwrap_err();
+
+        assert_eq!(error.code(), git2::ErrorCode::Auth);
+        assert!(error.message().contains("printed no token within"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_token_command_reads_no_stdin() {
+        // `cat` ends at once on an empty stdin instead of waiting for input
+        let command = ["cat".to_string()];
+
+        let error = token_from_command(&command, Duration::from_secs(5)).unwrap_err();
+
+        assert!(error.message().ends_with("printed no token"), "{}", error);
+    }
+
+    #[test]
+    fn test_rejected_credentials_fail_within_bounded_attempts() {
+        // an HTTP remote answering every request with 401
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let mut buffer = [0; 4096];
+                let _ = std::io::Read::read(&mut stream, &mut buffer);
+                let _ = std::io::Write::write_all(
+                    &mut stream,
+                    b"HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"git\"\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                );
+            }
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let remote_url = format!("http://127.0.0.1:{}/config.git", port);
+        repo.remote("origin", &remote_url).unwrap();
+        let auth = GitAuth::HttpsToken(TokenCredentials::new("wrong", Some("git".to_string())));
+
+        let started = std::time::Instant::now();
+        let result = pull_changes(&repo, "main", &auth, None);
+
+        match result {
+            Err(GitError::AuthenticationFailed { url, method }) => {
+                assert_eq!(url, remote_url);
+                assert_eq!(method, "https_token");
+            }
+            other => panic!("expected an authentication failure, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(requests.load(std::sync::atomic::Ordering::SeqCst) <= MAX_CREDENTIAL_ATTEMPTS as usize + 1);
+    }
+
+    #[test]
+    fn test_probe_remote_connects_without_a_checkout() {
+        let remote = tempfile::tempdir().unwrap();
+        let repo = Repository::init_bare(remote.path()).unwrap();
+        probe_remote(remote.path().to_str().unwrap(), &GitAuth::SshAgent).unwrap();
+
+        commit_to_bare(&repo, 1);
+
+        probe_remote(remote.path().to_str().unwrap(), &GitAuth::SshAgent).unwrap();
+        let missing = remote.path().join("missing.git");
+        assert!(probe_remote(missing.to_str().unwrap(), &GitAuth::SshAgent).is_err());
+    }
+
+    #[test]
+    fn test_read_file_at_previous_revision() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let file = dir.path().join("p-abc.project.yaml");
+
+        commit_file(&repo, &file, "v1", "first");
+        assert_eq!(read_file_at_previous_revision(dir.path(), &file).unwrap(), None);
+
+        commit_file(&repo, &file, "v2", "second");
+        assert_eq!(
+            read_file_at_previous_revision(dir.path(), &file).unwrap(),
+            Some("v1".to_string())
+        );
+
+        // a commit of another file does not hide the revision before the last change
+        commit_file(&repo, &dir.path().join("p-xyz.project.yaml"), "other", "unrelated");
+        assert_eq!(
+            read_file_at_previous_revision(dir.path(), &file).unwrap(),
+            Some("v1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_file_revision_is_the_last_commit_changing_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let file = dir.path().join("p-abc.project.yaml");
+
+        commit_file(&repo, &file, "v1", "first");
+        commit_file(&repo, &file, "v2", "second");
+        let changed = repo.head().unwrap().peel_to_commit().unwrap();
+        commit_file(&repo, &dir.path().join("other.yaml"), "other", "third");
+
+        let revision = file_revision(&file).unwrap().unwrap();
+        assert_eq!(revision.path, "p-abc.project.yaml");
+        assert!(changed.id().to_string().starts_with(&revision.commit));
+
+        std::fs::write(&file, "v3").unwrap();
+        assert_eq!(file_revision(&file).unwrap(), None);
+    }
+
+    #[test]
+    fn test_commit_each_object_describes_every_object() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let repo = Repository::init(root).unwrap();
+        std::fs::create_dir_all(root.join("c-123/p-abc")).unwrap();
+        let project = root.join("c-123/p-abc/p-abc.project.yaml");
+        commit_file(&repo, &project, "id: p-abc\nspec:\n  displayName: a\n", "initial");
+
+        std::fs::write(&project, "id: p-abc\nspec:\n  displayName: b\n").unwrap();
+        std::fs::write(root.join("c-123/p-abc/prtb-1.prtb.yaml"), "id: prtb-1\n").unwrap();
+        std::fs::write(root.join("README.md"), "readme").unwrap();
+
+        std::fs::write(root.join("notes.txt"), "scratch").unwrap();
+
+        let managed = vec![root.join("c-123"), PathBuf::from("README.md")];
+        let commits = commit_each_object(root, &managed, &[], &ShepherdIgnore::default(), "other files", "run-1").unwrap();
+
+        let mut revwalk = repo.revwalk().unwrap();
+        revwalk.push_head().unwrap();
+        let messages: Vec<String> = revwalk
+            .map(|oid| repo.find_commit(oid.unwrap()).unwrap().message().unwrap().to_string())
+            .collect();
+        assert_eq!(commits, 3);
+        assert_eq!(
+            messages,
+            vec![
+                "other files\n\nShepherd-Run-Id: run-1",
+                "prtb p-abc/prtb-1: add\n\nShepherd-Run-Id: run-1",
+                "project c-123/p-abc: update spec\n\nShepherd-Run-Id: run-1",
+                "initial",
+            ]
+        );
+        assert_eq!(repo.statuses(None).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_bundles_stand_for_their_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let repo = Repository::init(&root).unwrap();
+        std::fs::create_dir_all(root.join("c-123")).unwrap();
+        let bundle = root.join("c-123/p-abc.bundle.yaml");
+        commit_file(&repo, &bundle, "kind: Project\nid: p-abc\n", "initial");
+        let new_bundle = root.join("c-123/p-new.bundle.yaml");
+        std::fs::write(&new_bundle, "kind: Project\nid: ''\n").unwrap();
+
+        let new_files = get_new_uncommited_files(&root, &ShepherdIgnore::default()).await.unwrap();
+        assert_eq!(new_files, vec![(ObjectType::Project, new_bundle.clone())]);
+
+        std::fs::remove_file(&new_bundle).unwrap();
+        std::fs::write(&bundle, "kind: Project\nid: p-abc\n---\nkind: ProjectRoleTemplateBinding\nid: prtb-1\n").unwrap();
+        commit_each_object(&root, &[root.join("c-123")], &[], &ShepherdIgnore::default(), "other files", "run-1").unwrap();
+
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.message(), Some("project c-123/p-abc: update\n\nShepherd-Run-Id: run-1"));
+    }
+
+    #[test]
+    fn test_commit_changes_only_stages_managed_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let repo = Repository::init(root).unwrap();
+        let endpoint = root.join("rancher.example.com");
+        std::fs::create_dir_all(endpoint.join("c-123")).unwrap();
+        let stale = endpoint.join("c-123/c-123.cluster.yaml");
+        commit_file(&repo, &stale, "id: c-123\n", "initial");
+
+        std::fs::remove_file(&stale).unwrap();
+        std::fs::write(endpoint.join("roles.rt.yaml"), "id: rt\n").unwrap();
+        std::fs::write(endpoint.join("c-123/scratch.swp"), "swap").unwrap();
+        std::fs::write(root.join(".gitignore"), "*.swp\n").unwrap();
+        std::fs::write(root.join("notes.txt"), "scratch").unwrap();
+
+        commit_changes(root, &[endpoint], "sync").unwrap();
+
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        assert!(tree.get_path(Path::new("rancher.example.com/roles.rt.yaml")).is_ok());
+        assert!(tree.get_path(Path::new("rancher.example.com/c-123/c-123.cluster.yaml")).is_err());
+        assert!(tree.get_path(Path::new("rancher.example.com/c-123/scratch.swp")).is_err());
+        assert!(tree.get_path(Path::new("notes.txt")).is_err());
+        assert!(tree.get_path(Path::new(".gitignore")).is_err());
+    }
+
+    #[test]
+    fn test_held_back_files_stay_uncommitted() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let repo = Repository::init(root).unwrap();
+        let endpoint = root.join("rancher.example.com");
+        std::fs::create_dir_all(endpoint.join("c-123/p-abc")).unwrap();
+        let kept = endpoint.join("c-123/p-abc/p-abc.project.yaml");
+        commit_file(&repo, &kept, "id: p-abc\n", "initial");
+
+        std::fs::remove_file(&kept).unwrap();
+        std::fs::write(endpoint.join("c-123/p-abc/a.prtb.yaml"), "role_template_name: view\n").unwrap();
+        std::fs::write(endpoint.join("c-123/p-abc/b.prtb.yaml"), "role_template_name: edit\n").unwrap();
+        let held_back = [kept.clone(), endpoint.join("c-123/p-abc/b.prtb.yaml")];
+
+        commit_changes_except(root, std::slice::from_ref(&endpoint), &held_back, "sync").unwrap();
+
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        assert!(tree.get_path(Path::new("rancher.example.com/c-123/p-abc/a.prtb.yaml")).is_ok());
+        assert!(tree.get_path(Path::new("rancher.example.com/c-123/p-abc/b.prtb.yaml")).is_err());
+        // the deletion is held back as well
+        assert!(tree.get_path(Path::new("rancher.example.com/c-123/p-abc/p-abc.project.yaml")).is_ok());
+
+        assert_eq!(commit_each_object(root, &[endpoint], &held_back, &ShepherdIgnore::default(), "other files", "run-1").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_commit_to_branch_leaves_head_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let repo = Repository::init(root).unwrap();
+        commit_file(&repo, &root.join("README.md"), "readme\n", "initial");
+        let head = repo.head().unwrap().target().unwrap();
+
+        let files = [(PathBuf::from(".shepherd/pending/run-1.json"), "{}\n".to_string())];
+        let commit = commit_to_branch(&repo, "shepherd/plans/run-1", &files, "Plan run-1").unwrap();
+
+        assert_eq!(repo.head().unwrap().target(), Some(head));
+        assert!(!root.join(".shepherd").exists());
+        let commit = repo.find_commit(commit).unwrap();
+        assert_eq!(commit.parent_id(0).unwrap(), head);
+        assert_eq!(repo.refname_to_id("refs/heads/shepherd/plans/run-1").unwrap(), commit.id());
+        let tree = commit.tree().unwrap();
+        assert!(tree.get_path(Path::new("README.md")).is_ok());
+        let blob = tree.get_path(Path::new(".shepherd/pending/run-1.json")).unwrap();
+        assert_eq!(repo.find_blob(blob.id()).unwrap().content(), b"{}\n");
+    }
+
+    #[tokio::test]
+    async fn test_new_files_skip_shepherdignore_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        Repository::init(&root).unwrap();
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::create_dir_all(root.join("c-123/p-abc")).unwrap();
+        std::fs::write(root.join(".shepherdignore"), "docs/\n").unwrap();
+        std::fs::write(root.join("README.md"), "readme").unwrap();
+        std::fs::write(root.join("docs/guide.yaml"), "guide").unwrap();
+        std::fs::write(root.join("c-123/p-abc/p-abc.project.yaml"), "id: p-abc").unwrap();
+
+        let ignore = ShepherdIgnore::load(&root);
+        let new_files = get_new_uncommited_files(&root, &ignore).await.unwrap();
+
+        assert_eq!(
+            new_files,
+            vec![(ObjectType::Project, root.join("c-123/p-abc/p-abc.project.yaml"))]
+        );
+    }
+}
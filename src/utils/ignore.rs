@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde_json::Value;
+use tracing::warn;
+
+use crate::utils::file::{file_format_from_path, FileFormat};
+
+/// File at the repository root listing paths Shepherd should not treat as objects.
+pub const SHEPHERD_IGNORE_FILE: &str = ".shepherdignore";
+
+/// Patterns that apply even without a `.shepherdignore`: documentation, dotfiles and the cluster folder map.
+pub const DEFAULT_IGNORE_PATTERNS: &[&str] = &["README*", "*.md", ".*", "clusters.map.*"];
+
+/// Annotation that freezes an object: while it is `"true"` Shepherd neither
+/// updates, creates nor deletes the object, and download leaves its file alone.
+pub const IGNORE_ANNOTATION: &str = "shepherd.cattle.io/ignore";
+
+/// Prefix of the annotations that only steer Shepherd and are never sent to Rancher.
+pub const DIRECTIVE_PREFIX: &str = "shepherd.cattle.io/";
+
+/// Whether `annotations` carry the ignore directive.
+pub fn has_ignore_directive(annotations: Option<&HashMap<String, String>>) -> bool {
+    annotations
+        .and_then(|annotations| annotations.get(IGNORE_ANNOTATION))
+        .is_some_and(|value| value.eq_ignore_ascii_case("true"))
+}
+
+/// Whether the object serialized in `contents` carries the ignore directive.
+///
+/// Contents that cannot be parsed are not ignored, so the usual error surfaces later.
+pub fn contents_have_ignore_directive(contents: &str, file_format: &FileFormat) -> bool {
+    let Ok(object) = file_format.deserialize::<Value>(contents) else {
+        return false;
+    };
+    object
+        .get("annotations")
+        .and_then(|annotations| annotations.get(IGNORE_ANNOTATION))
+        .and_then(Value::as_str)
+        .is_some_and(|value| value.eq_ignore_ascii_case("true"))
+}
+
+/// Whether the object file at `path` carries the ignore directive.
+pub async fn file_has_ignore_directive(path: &Path) -> bool {
+    match tokio::fs::read_to_string(path).await {
+        Ok(contents) => contents_have_ignore_directive(&contents, &file_format_from_path(path)),
+        Err(_) => false,
+    }
+}
+
+/// Remove every Shepherd directive from `annotations` before they are sent to Rancher.
+pub fn strip_directives(annotations: &mut Option<HashMap<String, String>>) {
+    if let Some(map) = annotations {
+        map.retain(|key, _| !key.starts_with(DIRECTIVE_PREFIX));
+    }
+}
+
+/// A single line of a `.shepherdignore` file.
+#[derive(Debug, Clone, PartialEq)]
+struct IgnorePattern {
+    glob: String,
+    negated: bool,
+    directory_only: bool,
+    /// Matched against the whole relative path instead of each path component
+    anchored: bool,
+}
+
+impl IgnorePattern {
+    fn parse(line: &str) -> Result<Option<Self>, String> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+        if line.contains('[') || line.contains(']') {
+            return Err("character classes are not supported".to_string());
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (directory_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let glob = line.trim_start_matches('/');
+        if glob.is_empty() {
+            return Err("pattern matches nothing".to_string());
+        }
+
+        Ok(Some(Self {
+            glob: glob.to_string(),
+            negated,
+            directory_only,
+            anchored,
+        }))
+    }
+
+    /// Whether the pattern matches `components[..len]`, the path itself or one of its parents.
+    fn matches(&self, components: &[&str], len: usize, is_dir: bool) -> bool {
+        if self.directory_only && len == components.len() && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            glob_path_match(&self.glob, &components[..len].join("/"))
+        } else {
+            glob_path_match(&self.glob, components[len - 1])
+        }
+    }
+}
+
+/// Gitignore-style patterns for paths in the repository that are not Rancher objects.
+///
+/// Supports `*`, `?`, `**`, a leading `/` or inner `/` to anchor a pattern at the
+/// repository root, a trailing `/` to only match directories and `!` to re-include a
+/// path. A path is ignored when it or one of its parent directories matches, the last
+/// matching pattern wins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShepherdIgnore {
+    patterns: Vec<IgnorePattern>,
+}
+
+impl Default for ShepherdIgnore {
+    fn default() -> Self {
+        Self::new(DEFAULT_IGNORE_PATTERNS.iter().copied())
+    }
+}
+
+impl ShepherdIgnore {
+    /// Compile `lines` on top of the default patterns, warning about and skipping invalid ones.
+    pub fn new<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let patterns = DEFAULT_IGNORE_PATTERNS
+            .iter()
+            .copied()
+            .chain(lines)
+            .filter_map(|line| match IgnorePattern::parse(line) {
+                Ok(pattern) => pattern,
+                Err(reason) => {
+                    warn!("Ignoring invalid {} pattern `{}`: {}", SHEPHERD_IGNORE_FILE, line, reason);
+                    None
+                }
+            })
+            .collect();
+        Self { patterns }
+    }
+
+    /// Add `line` after the patterns read so far, so it takes precedence over them.
+    pub fn add(&mut self, line: &str) {
+        match IgnorePattern::parse(line) {
+            Ok(pattern) => self.patterns.extend(pattern),
+            Err(reason) => warn!("Ignoring invalid pattern `{}`: {}", line, reason),
+        }
+    }
+
+    /// Read the `.shepherdignore` at `repo_root`, falling back to the defaults if there is none.
+    pub fn load(repo_root: &Path) -> Self {
+        match std::fs::read_to_string(repo_root.join(SHEPHERD_IGNORE_FILE)) {
+            Ok(contents) => Self::new(contents.lines()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                warn!("Failed to read {}: {}, using the defaults", SHEPHERD_IGNORE_FILE, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Whether `path`, relative to the repository root, is excluded from syncing.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let path = path.to_string_lossy().replace('\\', "/");
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+
+        let mut ignored = false;
+        for pattern in &self.patterns {
+            if (1..=components.len()).any(|len| pattern.matches(&components, len, is_dir)) {
+                ignored = !pattern.negated;
+            }
+        }
+        ignored
+    }
+}
+
+/// Match `text` against a glob where `*` and `?` stay within a path component
+/// and `**` spans any number of them.
+fn glob_path_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let text = text.as_bytes();
+
+    fn matches(p: &[u8], t: &[u8]) -> bool {
+        match p {
+            [] => t.is_empty(),
+            [b'*', b'*', b'/', rest @ ..] => {
+                // zero or more whole directories
+                matches(rest, t)
+                    || t.iter()
+                        .enumerate()
+                        .any(|(i, c)| *c == b'/' && matches(rest, &t[i + 1..]))
+            }
+            [b'*', b'*', rest @ ..] => (0..=t.len()).any(|i| matches(rest, &t[i..])),
+            [b'*', rest @ ..] => (0..=t.len())
+                .take_while(|&i| i == 0 || t[i - 1] != b'/')
+                .any(|i| matches(rest, &t[i..])),
+            [b'?', rest @ ..] => matches!(t, [c, ..] if *c != b'/') && matches(rest, &t[1..]),
+            [c, rest @ ..] => t.first() == Some(c) && matches(rest, &t[1..]),
+        }
+    }
+
+    matches(pattern, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contents_with_directive_are_ignored() {
+        let yaml = "id: p-abc\nannotations:\n  shepherd.cattle.io/ignore: \"true\"\n";
+        let json = r#"{"id": "p-abc", "annotations": {"shepherd.cattle.io/ignore": "false"}}"#;
+
+        assert!(contents_have_ignore_directive(yaml, &FileFormat::Yaml));
+        assert!(!contents_have_ignore_directive(json, &FileFormat::Json));
+        assert!(!contents_have_ignore_directive("id: p-abc\n", &FileFormat::Yaml));
+    }
+
+    #[test]
+    fn test_strip_directives_keeps_other_annotations() {
+        let mut annotations = Some(HashMap::from([
+            (IGNORE_ANNOTATION.to_string(), "false".to_string()),
+            ("team".to_string(), "platform".to_string()),
+        ]));
+
+        strip_directives(&mut annotations);
+
+        assert_eq!(
+            annotations,
+            Some(HashMap::from([("team".to_string(), "platform".to_string())]))
+        );
+    }
+
+    fn ignored(ignore: &ShepherdIgnore, path: &str) -> bool {
+        ignore.is_ignored(Path::new(path), false)
+    }
+
+    #[test]
+    fn test_default_patterns_skip_docs_and_dotfiles() {
+        let ignore = ShepherdIgnore::default();
+
+        assert!(ignored(&ignore, "README"));
+        assert!(ignored(&ignore, "rancher/c-123/NOTES.md"));
+        assert!(ignored(&ignore, ".shepherdignore"));
+        assert!(ignored(&ignore, ".github/workflows/ci.yaml"));
+        assert!(ignored(&ignore, "rancher/clusters.map.yaml"));
+        assert!(!ignored(&ignore, "rancher/c-123/p-abc/p-abc.project.yaml"));
+    }
+
+    #[test]
+    fn test_patterns_anchor_match_directories_and_negate() {
+        let ignore = ShepherdIgnore::new(["docs/", "/scripts", "**/tmp/*.yaml", "!docs/keep.project.yaml"]);
+
+        assert!(ignored(&ignore, "docs/guide.txt"));
+        assert!(ignored(&ignore, "a/docs/guide.txt"));
+        assert!(!ignored(&ignore, "docs"));
+        assert!(ignore.is_ignored(Path::new("docs"), true));
+        assert!(!ignored(&ignore, "docs/keep.project.yaml"));
+        assert!(ignored(&ignore, "scripts/run.sh"));
+        assert!(!ignored(&ignore, "a/scripts/run.sh"));
+        assert!(ignored(&ignore, "tmp/x.yaml"));
+        assert!(ignored(&ignore, "a/b/tmp/x.yaml"));
+        assert!(!ignored(&ignore, "a/b/tmp/x.json"));
+    }
+
+    #[test]
+    fn test_added_patterns_take_precedence() {
+        let mut ignore = ShepherdIgnore::new(["!rancher.example.com/local/"]);
+        ignore.add("/rancher.example.com/local/");
+
+        assert!(ignored(&ignore, "rancher.example.com/local/p-abc/p-abc.project.yaml"));
+        assert!(!ignored(&ignore, "rancher.example.com/c-123/c-123.cluster.yaml"));
+        assert!(!ignored(&ignore, "other.example.com/rancher.example.com/local/x.yaml"));
+    }
+
+    #[test]
+    fn test_invalid_patterns_are_skipped() {
+        let ignore = ShepherdIgnore::new(["[abc]", "/", "docs/"]);
+
+        assert_eq!(ignore, ShepherdIgnore::new(["docs/"]));
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use walkdir::WalkDir;
+
+use crate::file_limits;
+use crate::utils::bundle::{is_bundle, project_document};
+use crate::models::ObjectType;
+use crate::utils::file::{get_minimal_object_from_contents, parse_object_file_name, FileFormat};
+use crate::utils::ignore::ShepherdIgnore;
+
+/// An object in a [`RepoIndex`]: its type, namespace and ID.
+///
+/// The namespace is the cluster ID for projects and cluster role template bindings and
+/// the project ID for project role template bindings, role templates and clusters have none.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectKey {
+    pub object_type: ObjectType,
+    pub namespace: Option<String>,
+    pub object_id: String,
+}
+
+/// The file of every object in an endpoint folder, keyed by object type, namespace and ID.
+///
+/// Built once per run by a single pass over the folder that reads only the minimal fields of
+/// each file. Files written, renamed or removed during the run are passed to [`RepoIndex::refresh`],
+/// [`RepoIndex::rename`] or [`RepoIndex::remove_under`] to keep the index in line. Files without an
+/// ID yet, such as new objects Rancher is to name, are listed by [`RepoIndex::files`] but cannot be
+/// looked up by ID.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepoIndex {
+    objects: BTreeMap<ObjectKey, PathBuf>,
+    keys: HashMap<PathBuf, ObjectKey>,
+}
+
+impl RepoIndex {
+    /// Index the object files in `endpoint_path`.
+    ///
+    /// # Arguments
+    /// * `repo_root` - The root of the repository, the `.shepherdignore` patterns are relative to it
+    /// * `endpoint_path` - The folder holding the configuration of the Rancher endpoint
+    /// * `ignore` - Paths that are not Rancher objects and are skipped
+    pub async fn build(repo_root: &Path, endpoint_path: &Path, ignore: &ShepherdIgnore) -> Self {
+        let mut index = Self::default();
+        let entries = WalkDir::new(endpoint_path)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| {
+                let ignored = entry
+                    .path()
+                    .strip_prefix(repo_root)
+                    .is_ok_and(|rel| ignore.is_ignored(rel, entry.file_type().is_dir()));
+                entry.depth() == 0 || !ignored
+            });
+        for entry in entries {
+            match entry {
+                Ok(entry) if entry.file_type().is_file() => index.refresh(entry.path()).await,
+                Ok(_) => {}
+                Err(e) => warn!("Not indexing {:?}: {}", e.path(), e),
+            }
+        }
+        debug!("Indexed {} objects in {:?}", index.len(), endpoint_path);
+        index
+    }
+
+    /// Index the file at `path` again after it was written, or drop it if it no longer holds an object.
+    pub async fn refresh(&mut self, path: &Path) {
+        self.remove(path);
+        let Some((object_type, file_format)) = object_type_of(path) else {
+            return;
+        };
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return;
+        };
+        if let Err(e) = file_limits().check_size(path, metadata.len()) {
+            debug!("Not indexing {}", e);
+            return;
+        }
+        let contents = match std::fs::read_to_string(path) {
+            // a bundle is indexed as its project
+            Ok(contents) if is_bundle(path) => project_document(&contents).unwrap_or_default(),
+            Ok(contents) => contents,
+            Err(e) => {
+                debug!("Not indexing {:?}: {}", path, e);
+                return;
+            }
+        };
+        let object = match get_minimal_object_from_contents(object_type, &contents, &file_format).await {
+            Ok(object) => object,
+            Err(e) => {
+                debug!("Not indexing {:?}: {}", path, e);
+                return;
+            }
+        };
+        let key = ObjectKey {
+            object_type,
+            namespace: object.namespace.filter(|_| !matches!(object_type, ObjectType::RoleTemplate | ObjectType::Cluster)),
+            object_id: object.object_id.unwrap_or_default(),
+        };
+        if key.object_id.is_empty() {
+            self.keys.insert(path.to_path_buf(), key);
+            return;
+        }
+        match self.objects.get(&key) {
+            Some(existing) => warn!(
+                "{:?} `{}` is defined in both {:?} and {:?}, using the first",
+                key.object_type, key.object_id, existing, path
+            ),
+            None => {
+                self.objects.insert(key.clone(), path.to_path_buf());
+            }
+        }
+        self.keys.insert(path.to_path_buf(), key);
+    }
+
+    /// Forget the file at `path`.
+    pub fn remove(&mut self, path: &Path) {
+        if let Some(key) = self.keys.remove(path) {
+            if self.objects.get(&key).is_some_and(|indexed| indexed == path) {
+                self.objects.remove(&key);
+            }
+        }
+    }
+
+    /// Forget every file in `folder`, such as a pruned cluster folder.
+    pub fn remove_under(&mut self, folder: &Path) {
+        let removed: Vec<PathBuf> = self.keys.keys().filter(|path| path.starts_with(folder)).cloned().collect();
+        for path in removed {
+            self.remove(&path);
+        }
+    }
+
+    /// Move the entry of the file at `from` to `to`.
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        if let Some(key) = self.keys.remove(from) {
+            if let Some(indexed) = self.objects.get_mut(&key).filter(|indexed| *indexed == from) {
+                *indexed = to.to_path_buf();
+            }
+            self.keys.insert(to.to_path_buf(), key);
+        }
+    }
+
+    /// The file of the object of `object_type` with `object_id` in `namespace`.
+    pub fn path(&self, object_type: ObjectType, namespace: Option<&str>, object_id: &str) -> Option<&Path> {
+        let key = ObjectKey {
+            object_type,
+            namespace: namespace
+                .filter(|_| !matches!(object_type, ObjectType::RoleTemplate | ObjectType::Cluster))
+                .map(str::to_string),
+            object_id: object_id.to_string(),
+        };
+        self.objects.get(&key).map(PathBuf::as_path)
+    }
+
+    /// The object held by the file at `path`.
+    pub fn key(&self, path: &Path) -> Option<&ObjectKey> {
+        self.keys.get(path)
+    }
+
+    /// Every indexed object and its file, ordered by type, namespace and ID.
+    pub fn iter(&self) -> impl Iterator<Item = (&ObjectKey, &Path)> {
+        self.objects.iter().map(|(key, path)| (key, path.as_path()))
+    }
+
+    /// Every indexed file and its object, named or not, in path order.
+    pub fn entries(&self) -> impl Iterator<Item = (&Path, &ObjectKey)> {
+        let mut entries: Vec<_> = self.keys.iter().map(|(path, key)| (path.as_path(), key)).collect();
+        entries.sort();
+        entries.into_iter()
+    }
+
+    /// The indexed objects of `object_type` and their files.
+    pub fn of_type(&self, object_type: ObjectType) -> impl Iterator<Item = (&ObjectKey, &Path)> {
+        self.iter().filter(move |(key, _)| key.object_type == object_type)
+    }
+
+    /// The files of `object_type` in `namespace`, named or not, in path order.
+    pub fn files(&self, object_type: ObjectType, namespace: Option<&str>) -> Vec<&Path> {
+        let mut files: Vec<&Path> = self
+            .keys
+            .iter()
+            .filter(|(_, key)| key.object_type == object_type && key.namespace.as_deref() == namespace)
+            .map(|(path, _)| path.as_path())
+            .collect();
+        files.sort();
+        files
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+/// The object type and format of a file named `<id>.<type>.<extension>`, bundles are their project.
+pub(crate) fn object_type_of(path: &Path) -> Option<(ObjectType, FileFormat)> {
+    let (object_type, _, file_format) = parse_object_file_name(path.file_name()?.to_str()?)?;
+    Some((object_type, file_format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    fn prtb(id: &str, project_id: &str) -> String {
+        format!(
+            "id: {id}\nnamespace: {project_id}\nproject_name: c-123:{project_id}\nrole_template_name: project-member\nuser_name: u-abc\n"
+        )
+    }
+
+    #[tokio::test]
+    async fn test_index_finds_objects_by_type_namespace_and_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = dir.path().join("rancher.example.com");
+        write(&endpoint.join("c-123/c-123.cluster.yaml"), "id: c-123\ndisplay_name: prod\n");
+        write(&endpoint.join("c-123/p-a/member.prtb.yaml"), &prtb("prtb-1", "p-a"));
+        write(&endpoint.join("c-123/p-b/prtb-1.prtb.yaml"), &prtb("prtb-1", "p-b"));
+        write(&endpoint.join("c-123/p-b/new.prtb.yaml"), &prtb("''", "p-b"));
+        write(&endpoint.join("c-123/p-b/README.md"), "notes");
+        write(&endpoint.join("docs/c-9.cluster.yaml"), "id: c-9\ndisplay_name: docs\n");
+        let ignore = ShepherdIgnore::new(["rancher.example.com/docs/"]);
+
+        let index = RepoIndex::build(dir.path(), &endpoint, &ignore).await;
+
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.files(ObjectType::ProjectRoleTemplateBinding, Some("p-b")).len(), 2);
+        assert_eq!(
+            index.path(ObjectType::ProjectRoleTemplateBinding, Some("p-a"), "prtb-1"),
+            Some(endpoint.join("c-123/p-a/member.prtb.yaml").as_path())
+        );
+        assert_eq!(
+            index.path(ObjectType::ProjectRoleTemplateBinding, Some("p-b"), "prtb-1"),
+            Some(endpoint.join("c-123/p-b/prtb-1.prtb.yaml").as_path())
+        );
+        assert!(index.path(ObjectType::Cluster, None, "c-123").is_some());
+        assert!(index.path(ObjectType::Cluster, None, "c-9").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_index_follows_files_written_renamed_and_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = dir.path().join("rancher.example.com");
+        let new_file = endpoint.join("c-123/p-a/new.prtb.yaml");
+        write(&new_file, &prtb("''", "p-a"));
+        let mut index = RepoIndex::build(dir.path(), &endpoint, &ShepherdIgnore::default()).await;
+        assert!(index.is_empty());
+        assert_eq!(index.files(ObjectType::ProjectRoleTemplateBinding, Some("p-a")), [new_file.as_path()]);
+
+        // Rancher named the object, the write-back stores the name
+        write(&new_file, &prtb("prtb-generated", "p-a"));
+        index.refresh(&new_file).await;
+        assert_eq!(
+            index.key(&new_file).map(|key| key.object_id.as_str()),
+            Some("prtb-generated")
+        );
+
+        let renamed = endpoint.join("c-123/p-a/prtb-generated.prtb.yaml");
+        index.rename(&new_file, &renamed);
+        assert_eq!(
+            index.path(ObjectType::ProjectRoleTemplateBinding, Some("p-a"), "prtb-generated"),
+            Some(renamed.as_path())
+        );
+
+        index.remove_under(&endpoint.join("c-123"));
+        assert!(index.is_empty());
+        assert!(index.files(ObjectType::ProjectRoleTemplateBinding, Some("p-a")).is_empty());
+    }
+}
@@ -1,13 +1,216 @@
-use tracing::error;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, error, warn};
+
+use crate::error::bounded_body;
 
 
 pub fn log_api_error(context: &str, error: &impl std::fmt::Debug) {
-    // For anyhow::Error, this will include the entire error chain
-    error!(context = context, error = ?error, "API operation failed");
+    // For anyhow::Error, this will include the entire error chain, a response body in it is cut
+    let error = format!("{:?}", error);
+    error!(context = context, error = %bounded_body(&error), "API operation failed");
 }
 
 
 pub fn generate_operation_id() -> String {
     let id: u64 = fastrand::u64(..);
     format!("{:x}", id)
-}
\ No newline at end of file
+}
+
+/// The warnings repeated by the loop every run, see [`set_repeated_warning_window`].
+static REPEATED_WARNINGS: OnceLock<RepeatedWarnings> = OnceLock::new();
+
+/// Log a warning repeated within `window` once, counting the repeats instead, see [`warn_repeated`].
+/// A window of zero logs every warning.
+///
+/// The repeats are counted across every run of the process, in the window of the first call.
+pub fn set_repeated_warning_window(window: Duration) {
+    let _ = REPEATED_WARNINGS.set(RepeatedWarnings::new(window));
+}
+
+fn repeated_warnings() -> &'static RepeatedWarnings {
+    REPEATED_WARNINGS.get_or_init(|| RepeatedWarnings::new(Duration::ZERO))
+}
+
+/// Log `message` at warn unless the warning of `template` about `subject` was logged less
+/// than the window of [`set_repeated_warning_window`] ago, in which case it is counted.
+///
+/// `template` names the warning, such as `"Project file not found"`, `subject` the file or
+/// object it is about. The first repeat after the window logs how often it was suppressed.
+pub fn warn_repeated(template: &'static str, subject: impl Display, message: impl Display) {
+    let subject = subject.to_string();
+    match repeated_warnings().claim(template, &subject, Instant::now()) {
+        Claim::Log => warn!("{}", message),
+        Claim::LogAfter(suppressed) => {
+            warn!("{} (suppressed {} times since it was last logged)", message, suppressed)
+        }
+        Claim::Suppress => debug!("{}", message),
+    }
+}
+
+/// Log how often the warnings whose window is over were suppressed, and forget the ones not
+/// repeated in their window, so a warning coming back after it was resolved is logged again.
+///
+/// Meant to be called at the end of every run.
+pub fn summarize_repeated_warnings() {
+    for (template, subject, suppressed) in repeated_warnings().expire(Instant::now()) {
+        warn!("\"{}\" for {} suppressed {} times", template, subject, suppressed);
+    }
+}
+
+/// The suppressed warnings per template in the Prometheus text format, empty until a warning
+/// was suppressed.
+///
+/// The counters only go up, a warning that stops being logged is not necessarily resolved.
+pub fn repeated_warning_metrics() -> String {
+    let totals = repeated_warnings().totals();
+    if totals.is_empty() {
+        return String::new();
+    }
+    let mut metrics = String::from(
+        "# HELP shepherd_suppressed_warnings_total Repeated warnings not logged, by message\n\
+         # TYPE shepherd_suppressed_warnings_total counter\n",
+    );
+    for (template, suppressed) in totals {
+        metrics.push_str(&format!(
+            "shepherd_suppressed_warnings_total{{message=\"{}\"}} {}\n",
+            template.replace('\\', "\\\\").replace('"', "\\\""),
+            suppressed
+        ));
+    }
+    metrics
+}
+
+/// What to do with a warning, see [`RepeatedWarnings::claim`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Claim {
+    Log,
+    /// Log it with the number of repeats suppressed since it was last logged
+    LogAfter(u64),
+    Suppress,
+}
+
+#[derive(Debug)]
+struct Repeat {
+    logged_at: Instant,
+    suppressed: u64,
+}
+
+/// The warnings logged in the last `window`, keyed by their template and subject.
+#[derive(Debug)]
+struct RepeatedWarnings {
+    window: Duration,
+    repeats: Mutex<HashMap<(&'static str, String), Repeat>>,
+    totals: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl RepeatedWarnings {
+    fn new(window: Duration) -> Self {
+        Self { window, repeats: Mutex::default(), totals: Mutex::default() }
+    }
+
+    /// Claim the warning of `template` about `subject` at `now`.
+    fn claim(&self, template: &'static str, subject: &str, now: Instant) -> Claim {
+        if self.window.is_zero() {
+            return Claim::Log;
+        }
+        let mut repeats = self.repeats.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(repeat) = repeats.get_mut(&(template, subject.to_string())) else {
+            repeats.insert((template, subject.to_string()), Repeat { logged_at: now, suppressed: 0 });
+            return Claim::Log;
+        };
+        if now.duration_since(repeat.logged_at) < self.window {
+            repeat.suppressed += 1;
+            *self.totals.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).entry(template).or_default() += 1;
+            return Claim::Suppress;
+        }
+        let suppressed = std::mem::take(&mut repeat.suppressed);
+        repeat.logged_at = now;
+        match suppressed {
+            0 => Claim::Log,
+            suppressed => Claim::LogAfter(suppressed),
+        }
+    }
+
+    /// Take the warnings whose window is over at `now` and that were suppressed, with how
+    /// often, starting their window again. The ones not suppressed are forgotten.
+    fn expire(&self, now: Instant) -> Vec<(&'static str, String, u64)> {
+        let mut repeats = self.repeats.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut expired = Vec::new();
+        repeats.retain(|(template, subject), repeat| {
+            if now.duration_since(repeat.logged_at) < self.window {
+                return true;
+            }
+            if repeat.suppressed == 0 {
+                return false;
+            }
+            expired.push((*template, subject.clone(), std::mem::take(&mut repeat.suppressed)));
+            repeat.logged_at = now;
+            true
+        });
+        expired.sort();
+        expired
+    }
+
+    fn totals(&self) -> BTreeMap<&'static str, u64> {
+        self.totals.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOT_FOUND: &str = "Project file not found";
+
+    #[test]
+    fn test_repeats_within_the_window_are_counted_instead_of_logged() {
+        let warnings = RepeatedWarnings::new(Duration::from_secs(600));
+        let start = Instant::now();
+
+        assert_eq!(warnings.claim(NOT_FOUND, "c-123/p-abc", start), Claim::Log);
+        assert_eq!(warnings.claim(NOT_FOUND, "c-123/p-def", start), Claim::Log);
+        assert_eq!(warnings.claim("Skipping", "c-123/p-abc", start), Claim::Log);
+        for minute in 1..10 {
+            let now = start + Duration::from_secs(60 * minute);
+            assert_eq!(warnings.claim(NOT_FOUND, "c-123/p-abc", now), Claim::Suppress);
+        }
+        assert_eq!(warnings.claim(NOT_FOUND, "c-123/p-def", start + Duration::from_secs(60)), Claim::Suppress);
+
+        assert_eq!(warnings.claim(NOT_FOUND, "c-123/p-abc", start + Duration::from_secs(600)), Claim::LogAfter(9));
+        assert_eq!(warnings.claim(NOT_FOUND, "c-123/p-abc", start + Duration::from_secs(660)), Claim::Suppress);
+        assert_eq!(warnings.totals(), BTreeMap::from([(NOT_FOUND, 11)]));
+    }
+
+    #[test]
+    fn test_expired_warnings_are_summarized_or_forgotten() {
+        let warnings = RepeatedWarnings::new(Duration::from_secs(600));
+        let start = Instant::now();
+        warnings.claim(NOT_FOUND, "c-123/p-abc", start);
+        warnings.claim(NOT_FOUND, "c-123/p-def", start);
+        warnings.claim(NOT_FOUND, "c-123/p-abc", start + Duration::from_secs(60));
+
+        assert!(warnings.expire(start + Duration::from_secs(599)).is_empty());
+        let later = start + Duration::from_secs(600);
+        assert_eq!(warnings.expire(later), vec![(NOT_FOUND, "c-123/p-abc".to_string(), 1)]);
+
+        // the resolved warning is logged again when it comes back, the summarized one waits a window
+        assert_eq!(warnings.claim(NOT_FOUND, "c-123/p-def", later), Claim::Log);
+        assert_eq!(warnings.claim(NOT_FOUND, "c-123/p-abc", later), Claim::Suppress);
+        // the counters are not reset by a summary
+        assert_eq!(warnings.totals(), BTreeMap::from([(NOT_FOUND, 2)]));
+    }
+
+    #[test]
+    fn test_a_zero_window_logs_every_warning() {
+        let warnings = RepeatedWarnings::new(Duration::ZERO);
+        let now = Instant::now();
+
+        assert_eq!(warnings.claim(NOT_FOUND, "c-123/p-abc", now), Claim::Log);
+        assert_eq!(warnings.claim(NOT_FOUND, "c-123/p-abc", now), Claim::Log);
+        assert!(warnings.totals().is_empty());
+    }
+}
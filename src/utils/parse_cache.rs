@@ -0,0 +1,129 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use git2::Oid;
+
+use crate::models::ConversionError;
+use crate::utils::file::FileFormat;
+
+type CacheKey = (Oid, TypeId, FileFormat);
+
+/// The objects parsed from object files, keyed by the git blob OID of the file contents, so a
+/// file left unchanged is parsed once however many runs and clusters read it.
+///
+/// Only successful parses are kept, a file that fails to parse is parsed again to report
+/// the error with its path. [`ParseCache::start_run`] drops the entries the last run did not
+/// use, so the cache holds no more than the objects of the repository.
+#[derive(Debug, Default)]
+pub struct ParseCache {
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// Parsed or used in this run.
+    current: HashMap<CacheKey, Arc<dyn Any + Send + Sync>>,
+    /// Parsed or used in the last run, moved to `current` once used again.
+    previous: HashMap<CacheKey, Arc<dyn Any + Send + Sync>>,
+    parsed: usize,
+}
+
+impl ParseCache {
+    /// The object `parse` reads from `content`, parsed earlier when a file held the same
+    /// contents in `file_format`.
+    pub fn parse<T, F>(&self, content: &str, file_format: &FileFormat, parse: F) -> Result<T, ConversionError>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> Result<T, ConversionError>,
+    {
+        let Ok(oid) = Oid::hash_object(git2::ObjectType::Blob, content.as_bytes()) else {
+            return parse();
+        };
+        let key = (oid, TypeId::of::<T>(), *file_format);
+        {
+            let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let cached = match entries.previous.remove(&key) {
+                Some(object) => Some(entries.current.entry(key).or_insert(object).clone()),
+                None => entries.current.get(&key).cloned(),
+            };
+            if let Some(object) = cached.as_deref().and_then(|object| object.downcast_ref::<T>()) {
+                return Ok(object.clone());
+            }
+        }
+
+        let object = parse()?;
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries.parsed += 1;
+        entries.current.insert(key, Arc::new(object.clone()));
+        Ok(object)
+    }
+
+    /// Start a run, dropping the entries the last run did not use.
+    pub fn start_run(&self) {
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries.previous = std::mem::take(&mut entries.current);
+    }
+
+    /// How many times a file was parsed rather than taken from the cache.
+    pub fn parsed(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).parsed
+    }
+
+    pub fn len(&self) -> usize {
+        let entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries.current.len() + entries.previous.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deserialize_object;
+    use crate::resources::crtb::ClusterRoleTemplateBinding;
+
+    const CRTB: &str = "id: crtb-1\ncluster_name: c-123\nnamespace: c-123\nrole_template_name: cluster-member\nuser_name: u-abc\n";
+
+    fn parse(cache: &ParseCache, content: &str) -> Result<ClusterRoleTemplateBinding, ConversionError> {
+        cache.parse(content, &FileFormat::Yaml, || deserialize_object(content, &FileFormat::Yaml, None))
+    }
+
+    #[test]
+    fn test_unchanged_contents_are_parsed_once() {
+        let cache = ParseCache::default();
+
+        let first = parse(&cache, CRTB).unwrap();
+        let second = parse(&cache, CRTB).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(cache.parsed(), 1);
+
+        parse(&cache, &CRTB.replace("u-abc", "u-def")).unwrap();
+        assert_eq!(cache.parsed(), 2);
+        // failures are parsed again every time
+        assert!(parse(&cache, "id: [").is_err());
+        assert!(parse(&cache, "id: [").is_err());
+        assert_eq!(cache.parsed(), 2);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_entries_unused_by_a_run_are_dropped() {
+        let cache = ParseCache::default();
+        let changed = CRTB.replace("u-abc", "u-def");
+        parse(&cache, CRTB).unwrap();
+        parse(&cache, &changed).unwrap();
+
+        cache.start_run();
+        parse(&cache, CRTB).unwrap();
+        assert_eq!(cache.parsed(), 2);
+        cache.start_run();
+
+        assert_eq!(cache.len(), 1);
+        parse(&cache, &changed).unwrap();
+        assert_eq!(cache.parsed(), 3);
+    }
+}
@@ -0,0 +1,240 @@
+//! Which role templates the bindings of a project or cluster may grant, and to whom.
+//!
+//! The policy is read from [`POLICY_FILE`] at the root of the repository. Deciding about a
+//! binding only looks at the binding and the policy, so the rules can be reviewed and tested
+//! on their own.
+
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::diff::glob_match;
+
+/// The policy file, relative to the root of the repository.
+pub const POLICY_FILE: &str = ".shepherd/policy.toml";
+
+/// The rules of [`POLICY_FILE`].
+///
+/// A binding is allowed if one of the rules applying to it allows it. A binding no rule
+/// applies to is allowed, add a rule naming no project to restrict every binding.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct BindingPolicy {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<PolicyRule>,
+}
+
+/// A `[[rule]]` of the policy. Patterns may use `*` for any run of characters.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyRule {
+    /// Patterns of the IDs of the clusters the rule applies to, every cluster when empty
+    #[serde(default)]
+    pub clusters: Vec<String>,
+    /// Patterns of the display names of the projects the rule applies to
+    #[serde(default)]
+    pub projects: Vec<String>,
+    /// Patterns of the IDs of the projects the rule applies to
+    #[serde(default)]
+    pub project_ids: Vec<String>,
+    /// Patterns of the role templates the bindings may grant, any role template when empty
+    #[serde(default)]
+    pub role_templates: Vec<String>,
+    /// Patterns of the users, groups and service accounts the bindings may grant it to, anyone when empty
+    #[serde(default)]
+    pub subjects: Vec<String>,
+}
+
+/// A binding as the policy sees it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding<'a> {
+    pub cluster_id: &'a str,
+    /// The project of a project role template binding, `None` for a cluster role template binding
+    pub project: Option<BoundProject<'a>>,
+    pub role_template_name: &'a str,
+    /// Every user, group and service account field set on the binding
+    pub subjects: Vec<&'a str>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundProject<'a> {
+    pub id: &'a str,
+    pub display_name: &'a str,
+}
+
+impl fmt::Display for Binding<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "binding of `{}` to {:?}", self.role_template_name, self.subjects)?;
+        match self.project {
+            Some(project) => write!(f, " in project `{}` ({})", project.display_name, project.id),
+            None => write!(f, " in cluster `{}`", self.cluster_id),
+        }
+    }
+}
+
+/// Whether `text` matches one of `patterns`, or `patterns` is empty.
+fn matches_any(patterns: &[String], text: &str) -> bool {
+    patterns.is_empty() || patterns.iter().any(|pattern| glob_match(pattern, text))
+}
+
+impl PolicyRule {
+    /// Whether the rule applies to `binding`.
+    ///
+    /// A rule naming projects applies to the bindings of those projects only, a rule naming
+    /// none to every binding of its clusters.
+    pub fn applies_to(&self, binding: &Binding) -> bool {
+        if !matches_any(&self.clusters, binding.cluster_id) {
+            return false;
+        }
+        if self.projects.is_empty() && self.project_ids.is_empty() {
+            return true;
+        }
+        binding.project.is_some_and(|project| {
+            self.projects.iter().any(|pattern| glob_match(pattern, project.display_name))
+                || self.project_ids.iter().any(|pattern| glob_match(pattern, project.id))
+        })
+    }
+
+    /// Whether the rule allows the role template and every subject of `binding`.
+    pub fn allows(&self, binding: &Binding) -> bool {
+        matches_any(&self.role_templates, binding.role_template_name)
+            && binding.subjects.iter().all(|subject| matches_any(&self.subjects, subject))
+    }
+}
+
+impl BindingPolicy {
+    /// Read [`POLICY_FILE`] in the repository at `repo_root`, `None` if there is none.
+    ///
+    /// # Errors
+    /// If the file cannot be read or parsed
+    pub fn load(repo_root: &Path) -> Result<Option<Self>> {
+        let path = repo_root.join(POLICY_FILE);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))
+            .map(Some)
+    }
+
+    /// The numbers, counted from 1, of the rules applying to `binding` and whether each allows it.
+    pub fn decisions(&self, binding: &Binding) -> Vec<(usize, bool)> {
+        self.rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.applies_to(binding))
+            .map(|(index, rule)| (index + 1, rule.allows(binding)))
+            .collect()
+    }
+
+    /// Why `binding` breaks the policy, `None` if the policy allows it.
+    pub fn check(&self, binding: &Binding) -> Option<String> {
+        let decisions = self.decisions(binding);
+        if decisions.is_empty() || decisions.iter().any(|(_, allowed)| *allowed) {
+            return None;
+        }
+        let numbers: Vec<String> = decisions.iter().map(|(number, _)| number.to_string()).collect();
+        Some(format!("{} is not allowed, the rules applying to it are {}", binding, numbers.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r#"
+# platform admins may bind anything anywhere, everyone else only as a later rule allows
+[[rule]]
+subjects = ["u-admin*", "local://u-admin*"]
+
+[[rule]]
+projects = ["team-a-*"]
+role_templates = ["project-member", "read-only"]
+
+[[rule]]
+projects = ["team-a-*"]
+role_templates = ["project-owner"]
+subjects = ["u-lead", "local://u-lead"]
+
+[[rule]]
+clusters = ["c-sandbox"]
+
+[[rule]]
+clusters = ["c-prod"]
+project_ids = ["p-shared"]
+role_templates = ["read-only"]
+"#;
+
+    fn project<'a>(id: &'a str, display_name: &'a str) -> Option<BoundProject<'a>> {
+        Some(BoundProject { id, display_name })
+    }
+
+    #[test]
+    fn test_a_binding_is_allowed_by_any_rule_applying_to_it() {
+        let policy: BindingPolicy = toml::from_str(POLICY).unwrap();
+        let team_a = project("p-abc", "team-a-web");
+        let team_b = project("p-def", "team-b");
+        let shared = project("p-shared", "shared");
+
+        // description, cluster, project, role template, subjects, the rules applying and whether each allows it
+        #[allow(clippy::type_complexity)]
+        let cases: [(&str, &str, Option<BoundProject>, &str, &[&str], &[(usize, bool)]); 12] = [
+            ("member of an owned project", "c-prod", team_a, "project-member", &["u-dev"], &[(1, false), (2, true), (3, false)]),
+            ("owner granted to the lead", "c-prod", team_a, "project-owner", &["u-lead", "local://u-lead"], &[(1, false), (2, false), (3, true)]),
+            ("owner granted to someone else", "c-prod", team_a, "project-owner", &["u-dev"], &[(1, false), (2, false), (3, false)]),
+            ("every subject must be allowed", "c-prod", team_a, "project-owner", &["u-lead", "local://u-dev"], &[(1, false), (2, false), (3, false)]),
+            ("admins may bind in every project", "c-prod", team_a, "project-owner", &["u-admin-1"], &[(1, true), (2, false), (3, false)]),
+            ("project of no team", "c-prod", team_b, "project-member", &["u-dev"], &[(1, false)]),
+            ("cluster binding of an admin", "c-prod", None, "cluster-owner", &["u-admin-1"], &[(1, true)]),
+            ("cluster binding of someone else", "c-prod", None, "cluster-owner", &["u-dev"], &[(1, false)]),
+            ("anything goes in the sandbox", "c-sandbox", team_b, "project-owner", &["u-dev"], &[(1, false), (4, true)]),
+            ("shared project in the cluster named", "c-prod", shared, "read-only", &["u-dev"], &[(1, false), (5, true)]),
+            ("shared project elsewhere", "c-test", shared, "read-only", &["u-dev"], &[(1, false)]),
+            ("shared project, other role", "c-prod", shared, "project-member", &["u-dev"], &[(1, false), (5, false)]),
+        ];
+        for (description, cluster_id, project, role_template_name, subjects, decisions) in cases {
+            let binding = Binding { cluster_id, project, role_template_name, subjects: subjects.to_vec() };
+            assert_eq!(policy.decisions(&binding), decisions, "{}", description);
+            let allowed = decisions.iter().any(|(_, allowed)| *allowed);
+            assert_eq!(policy.check(&binding).is_none(), allowed, "{}", description);
+        }
+    }
+
+    #[test]
+    fn test_bindings_no_rule_applies_to_are_allowed() {
+        let policy: BindingPolicy = toml::from_str("[[rule]]\nprojects = [\"team-a-*\"]\nsubjects = [\"u-lead\"]\n").unwrap();
+        let binding = |project, cluster_id| Binding {
+            cluster_id,
+            project,
+            role_template_name: "project-owner",
+            subjects: vec!["u-dev"],
+        };
+
+        assert!(policy.check(&binding(project("p-def", "team-b"), "c-prod")).is_none());
+        // rules naming projects never apply to cluster bindings
+        assert!(policy.check(&binding(None, "c-prod")).is_none());
+        assert_eq!(
+            policy.check(&binding(project("p-abc", "team-a-web"), "c-prod")).unwrap(),
+            "binding of `project-owner` to [\"u-dev\"] in project `team-a-web` (p-abc) is not allowed, the rules applying to it are 1"
+        );
+        assert!(BindingPolicy::default().check(&binding(None, "c-prod")).is_none());
+    }
+
+    #[test]
+    fn test_the_policy_file_is_optional_but_must_parse() {
+        let repo = tempfile::tempdir().unwrap();
+        assert_eq!(BindingPolicy::load(repo.path()).unwrap(), None);
+
+        std::fs::create_dir_all(repo.path().join(".shepherd")).unwrap();
+        std::fs::write(repo.path().join(POLICY_FILE), POLICY).unwrap();
+        assert_eq!(BindingPolicy::load(repo.path()).unwrap().unwrap().rules.len(), 5);
+
+        // a misspelled key would silently widen the rule
+        std::fs::write(repo.path().join(POLICY_FILE), "[[rule]]\nrole_template = [\"read-only\"]\n").unwrap();
+        assert!(BindingPolicy::load(repo.path()).is_err());
+    }
+}
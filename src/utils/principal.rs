@@ -0,0 +1,150 @@
+//! Principal names of binding subjects compared the way their auth provider compares them.
+//!
+//! Active Directory and OpenLDAP ignore the case of distinguished names, so Rancher may hand
+//! back `activedirectory_group://CN=Ops-Team,OU=Groups,DC=example,DC=com` for a file naming
+//! `cn=ops-team,ou=groups,dc=example,dc=com`. Names are only normalized to be compared, files
+//! and the objects sent to Rancher keep the casing they were written with.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// How the principal names of an auth provider are compared.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PrincipalNormalization {
+    /// Compared as they are written
+    Exact,
+    /// The distinguished name after `://` is compared ignoring case and the spaces around the
+    /// `,` and `=` separating its components
+    LowercaseDn,
+}
+
+impl fmt::Display for PrincipalNormalization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrincipalNormalization::Exact => write!(f, "exact"),
+            PrincipalNormalization::LowercaseDn => write!(f, "lowercase_dn"),
+        }
+    }
+}
+
+/// The auth provider prefixes, the part of a principal name before `://`, normalized unless the
+/// config says otherwise.
+pub const DEFAULT_PRINCIPAL_NORMALIZATION: &[(&str, PrincipalNormalization)] = &[
+    ("activedirectory_user", PrincipalNormalization::LowercaseDn),
+    ("activedirectory_group", PrincipalNormalization::LowercaseDn),
+    ("openldap_user", PrincipalNormalization::LowercaseDn),
+    ("openldap_group", PrincipalNormalization::LowercaseDn),
+];
+
+/// The normalization of each auth provider prefix, principal names of other providers are
+/// compared as they are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrincipalRules {
+    rules: BTreeMap<String, PrincipalNormalization>,
+}
+
+impl Default for PrincipalRules {
+    fn default() -> Self {
+        Self::new(&BTreeMap::new())
+    }
+}
+
+impl PrincipalRules {
+    /// The default rules with the prefixes of `overrides` added or replaced.
+    pub fn new(overrides: &BTreeMap<String, PrincipalNormalization>) -> Self {
+        let rules = DEFAULT_PRINCIPAL_NORMALIZATION
+            .iter()
+            .map(|(prefix, normalization)| (prefix.to_string(), *normalization))
+            .chain(overrides.iter().map(|(prefix, normalization)| (prefix.clone(), *normalization)))
+            .collect();
+        Self { rules }
+    }
+
+    /// `principal` in the form it is compared in.
+    pub fn normalize<'a>(&self, principal: &'a str) -> Cow<'a, str> {
+        let Some((prefix, name)) = principal.split_once("://") else {
+            return Cow::Borrowed(principal);
+        };
+        match self.rules.get(prefix) {
+            Some(PrincipalNormalization::LowercaseDn) => Cow::Owned(format!("{}://{}", prefix, lowercase_dn(name))),
+            Some(PrincipalNormalization::Exact) | None => Cow::Borrowed(principal),
+        }
+    }
+
+    /// Whether `a` and `b` name the same principal.
+    pub fn same(&self, a: Option<&str>, b: Option<&str>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => a == b || self.normalize(a) == self.normalize(b),
+            (a, b) => a == b,
+        }
+    }
+}
+
+/// `dn` lowercased, without the spaces around its separators. Escaped separators, such as the
+/// `\,` of `CN=Doe\, Jane`, are part of the component.
+fn lowercase_dn(dn: &str) -> String {
+    let mut components = Vec::new();
+    let mut component = String::new();
+    let mut chars = dn.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                component.push(c);
+                component.extend(chars.next());
+            }
+            ',' => components.push(std::mem::take(&mut component)),
+            _ => component.push(c),
+        }
+    }
+    components.push(component);
+    components
+        .iter()
+        .map(|component| match component.split_once('=') {
+            Some((attribute, value)) => format!("{}={}", attribute.trim(), value.trim()),
+            None => component.trim().to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directory_principals_are_compared_ignoring_case() {
+        let rules = PrincipalRules::default();
+
+        assert!(rules.same(
+            Some("activedirectory_group://CN=Ops-Team,OU=Groups,DC=example,DC=com"),
+            Some("activedirectory_group://cn=ops-team, ou=groups, dc=example, dc=com"),
+        ));
+        assert!(rules.same(Some("openldap_user://UID=Jane,OU=People"), Some("openldap_user://uid=jane,ou=people")));
+        assert!(rules.same(Some("activedirectory_user://CN=Doe\\, Jane,OU=People"), Some("activedirectory_user://cn=doe\\, jane,ou=people")));
+        // another provider, or another component, is another principal
+        assert!(!rules.same(Some("github_user://Jane"), Some("github_user://jane")));
+        assert!(!rules.same(Some("activedirectory_user://CN=Jane,OU=People"), Some("activedirectory_group://CN=Jane,OU=People")));
+        assert!(!rules.same(Some("activedirectory_user://CN=Jane,OU=People"), Some("activedirectory_user://CN=Jane,OU=Admins")));
+        assert!(!rules.same(Some("activedirectory_user://CN=Jane"), None));
+        assert!(rules.same(None, None));
+    }
+
+    #[test]
+    fn test_rules_are_set_per_provider_prefix() {
+        let overrides = BTreeMap::from([
+            ("activedirectory_user".to_string(), PrincipalNormalization::Exact),
+            ("freeipa_group".to_string(), PrincipalNormalization::LowercaseDn),
+        ]);
+        let rules = PrincipalRules::new(&overrides);
+
+        assert!(!rules.same(Some("activedirectory_user://CN=Jane"), Some("activedirectory_user://cn=jane")));
+        assert!(rules.same(Some("activedirectory_group://CN=Ops"), Some("activedirectory_group://cn=ops")));
+        assert!(rules.same(Some("freeipa_group://CN=Ops,CN=Groups"), Some("freeipa_group://cn=ops,cn=groups")));
+        assert_eq!(rules.normalize("local://u-AbC"), "local://u-AbC");
+    }
+}
@@ -0,0 +1,181 @@
+//! Resource quotas of projects lowered below what their namespaces already use.
+//!
+//! Rancher refuses such a quota with a 422 only when it is applied, the live project reports
+//! the usage in `spec.resourceQuota.usedLimit`, which is never written to files nor patched.
+
+use rancher_client::models::IoCattleManagementv3Project;
+use serde_json::Value;
+
+use crate::resources::project::Project;
+use crate::utils::config_validator::Violation;
+
+/// The value of a Kubernetes quantity such as `1500m`, `4Gi` or `1e3`, `None` if it is none.
+pub fn parse_quantity(quantity: &str) -> Option<f64> {
+    let quantity = quantity.trim();
+    let split = quantity
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '+' | '-')))
+        .unwrap_or(quantity.len());
+    let (number, suffix) = quantity.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let factor = match suffix {
+        "" => 1.0,
+        "n" => 1e-9,
+        "u" => 1e-6,
+        "m" => 1e-3,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024f64,
+        "Mi" => 1024f64.powi(2),
+        "Gi" => 1024f64.powi(3),
+        "Ti" => 1024f64.powi(4),
+        "Pi" => 1024f64.powi(5),
+        "Ei" => 1024f64.powi(6),
+        exponent => 10f64.powi(exponent.strip_prefix(['e', 'E'])?.parse().ok()?),
+    };
+    Some(number * factor)
+}
+
+/// A limit of a project quota below the current usage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shortfall {
+    /// The resource as Rancher names it, such as `limitsCpu`
+    pub resource: String,
+    pub limit: String,
+    pub used: String,
+}
+
+/// The limits of `stored` below the usage `live` reports that `stored` changes.
+///
+/// Limits left as they are in Rancher are not reported, only lowering a limit is refused.
+pub fn shortfalls(stored: &Project, live: &IoCattleManagementv3Project) -> Vec<Shortfall> {
+    let quota = |field: &str| {
+        live.spec
+            .as_ref()
+            .and_then(|spec| spec.resource_quota.as_ref())
+            .and_then(|quota| serde_json::to_value(quota).ok())
+            .and_then(|mut quota| quota.get_mut(field).map(Value::take))
+            .unwrap_or(Value::Null)
+    };
+    let (live_limit, used) = (quota("limit"), quota("usedLimit"));
+    let Some(Value::Object(limit)) = stored.resource_quota.as_ref().and_then(|limit| serde_json::to_value(limit).ok()) else {
+        return Vec::new();
+    };
+    limit
+        .iter()
+        .filter_map(|(resource, limit)| {
+            let limit = limit.as_str()?;
+            if live_limit.get(resource).and_then(Value::as_str) == Some(limit) {
+                return None;
+            }
+            let used = used.get(resource)?.as_str()?;
+            (parse_quantity(limit)? < parse_quantity(used)?).then(|| Shortfall {
+                resource: resource.clone(),
+                limit: limit.to_string(),
+                used: used.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The violation of the quota of `stored` if it lowers a limit below the usage `live` reports.
+pub fn quota_violation(stored: &Project, live: &IoCattleManagementv3Project) -> Option<Violation> {
+    let shortfalls = shortfalls(stored, live);
+    if shortfalls.is_empty() {
+        return None;
+    }
+    let details: Vec<String> = shortfalls
+        .iter()
+        .map(|shortfall| format!("{} {} < {} used", shortfall.resource, shortfall.limit, shortfall.used))
+        .collect();
+    Some(Violation {
+        rule: "quota-below-usage",
+        message: format!(
+            "project `{}` ({}) lowers its resource quota below what its namespaces use: {}",
+            stored.display_name,
+            stored.id.as_deref().unwrap_or_default(),
+            details.join(", ")
+        ),
+        paths: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rancher_client::models::IoCattleManagementv3ProjectSpecResourceQuotaLimit;
+    use serde_json::json;
+
+    fn live_project(limit: Value, used: Value) -> IoCattleManagementv3Project {
+        serde_json::from_value(json!({
+            "metadata": { "name": "p-abc", "namespace": "c-123" },
+            "spec": {
+                "clusterName": "c-123",
+                "displayName": "web",
+                "resourceQuota": { "limit": limit, "usedLimit": used }
+            }
+        }))
+        .unwrap()
+    }
+
+    fn stored_project(limit: Value) -> Project {
+        let mut project = Project::try_from(live_project(json!({}), json!({}))).unwrap();
+        project.resource_quota = Some(serde_json::from_value::<IoCattleManagementv3ProjectSpecResourceQuotaLimit>(limit).unwrap());
+        project
+    }
+
+    #[test]
+    fn test_parse_quantity() {
+        let cases = [
+            ("1500m", Some(1.5)),
+            ("2", Some(2.0)),
+            ("0.5", Some(0.5)),
+            ("4Gi", Some(4.0 * 1024f64.powi(3))),
+            ("128Mi", Some(128.0 * 1024f64.powi(2))),
+            ("1G", Some(1e9)),
+            ("1e3", Some(1000.0)),
+            ("2E", Some(2e18)),
+            ("", None),
+            ("Gi", None),
+            ("1Xi", None),
+        ];
+        for (quantity, expected) in cases {
+            assert_eq!(parse_quantity(quantity), expected, "{}", quantity);
+        }
+    }
+
+    #[test]
+    fn test_lowering_a_limit_below_usage_is_a_violation() {
+        let live = live_project(
+            json!({ "limitsCpu": "4000m", "limitsMemory": "8Gi", "pods": "50" }),
+            json!({ "limitsCpu": "1500m", "limitsMemory": "6Gi", "pods": "40" }),
+        );
+
+        // lowered, but above what is used
+        assert_eq!(quota_violation(&stored_project(json!({ "limitsCpu": "2", "limitsMemory": "8Gi" })), &live), None);
+        // unchanged limits are never reported
+        assert_eq!(quota_violation(&stored_project(json!({ "limitsCpu": "4000m", "limitsMemory": "8Gi" })), &live), None);
+
+        let violation = quota_violation(
+            &stored_project(json!({ "limitsCpu": "1", "limitsMemory": "4096Mi", "pods": "40" })),
+            &live,
+        )
+        .unwrap();
+        assert_eq!(violation.rule, "quota-below-usage");
+        assert_eq!(
+            violation.message,
+            "project `web` (p-abc) lowers its resource quota below what its namespaces use: limitsCpu 1 < 1500m used, limitsMemory 4096Mi < 6Gi used"
+        );
+    }
+
+    #[test]
+    fn test_limits_without_usage_are_not_checked() {
+        let live = live_project(json!({}), json!({}));
+
+        assert!(shortfalls(&stored_project(json!({ "limitsCpu": "1" })), &live).is_empty());
+        assert!(shortfalls(&Project::try_from(live.clone()).unwrap(), &live).is_empty());
+    }
+}
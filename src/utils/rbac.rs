@@ -0,0 +1,296 @@
+//! The checks Kubernetes runs on the rules of a role, run on role template files before
+//! Rancher refuses them with a 422 in the middle of a run.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use rancher_client::models::io_cattle_managementv3_role_template::Context;
+use rancher_client::models::IoCattleManagementv3GlobalRoleRulesInner;
+
+use crate::resources::rt::RoleTemplate;
+
+/// The verbs of rules on resources: the ones of the Kubernetes API, the special verbs RBAC
+/// checks and the verbs Rancher checks on its own resources.
+pub const RESOURCE_VERBS: &[&str] = &[
+    "get",
+    "list",
+    "watch",
+    "create",
+    "update",
+    "patch",
+    "delete",
+    "deletecollection",
+    "proxy",
+    "use",
+    "bind",
+    "escalate",
+    "impersonate",
+    "approve",
+    "sign",
+    "own",
+    "manage-namespaces",
+    "updatepsa",
+];
+
+/// The verbs of rules on non-resource URLs, the lowercase HTTP methods.
+pub const NON_RESOURCE_VERBS: &[&str] = &["get", "post", "put", "patch", "delete", "head", "options"];
+
+/// Why `rule`, the rule at `index` of a role template, would be refused, empty if it would not.
+///
+/// `namespaced` rules, the ones of project role templates, cannot grant non-resource URLs.
+pub fn rule_errors(index: usize, rule: &IoCattleManagementv3GlobalRoleRulesInner, namespaced: bool) -> Vec<String> {
+    let set = |field: &Option<Vec<String>>| field.as_ref().is_some_and(|values| !values.is_empty());
+    let mut errors = Vec::new();
+    let non_resource = set(&rule.non_resource_urls);
+
+    if rule.verbs.is_empty() {
+        errors.push("verbs must not be empty".to_string());
+    }
+    let known = if non_resource { NON_RESOURCE_VERBS } else { RESOURCE_VERBS };
+    for verb in &rule.verbs {
+        if verb != "*" && !known.contains(&verb.as_str()) {
+            errors.push(format!("unknown verb `{}`", verb));
+        }
+    }
+    if non_resource {
+        if namespaced {
+            errors.push("project role templates cannot grant nonResourceURLs".to_string());
+        }
+        if set(&rule.api_groups) || set(&rule.resources) || set(&rule.resource_names) {
+            errors.push("a rule cannot grant both resources and nonResourceURLs".to_string());
+        }
+    } else {
+        // `""` is the core group, a missing list grants no group at all
+        if !set(&rule.api_groups) {
+            errors.push("apiGroups must not be empty, use \"\" for the core group".to_string());
+        }
+        if !set(&rule.resources) {
+            errors.push("resources must not be empty".to_string());
+        }
+    }
+    errors.into_iter().map(|error| format!("rules[{}]: {}", index, error)).collect()
+}
+
+/// Why the rules of `role_template` would be refused, one message per problem.
+pub fn role_template_errors(role_template: &RoleTemplate) -> Vec<String> {
+    let namespaced = role_template.context == Some(Context::Project);
+    role_template
+        .rules
+        .iter()
+        .flatten()
+        .enumerate()
+        .flat_map(|(index, rule)| rule_errors(index, rule, namespaced))
+        .collect()
+}
+
+/// Which role templates each role template inherits from through `role_template_names`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InheritanceGraph {
+    inherits: BTreeMap<String, Vec<String>>,
+}
+
+impl InheritanceGraph {
+    /// Add the role template `id` inheriting from `inherited`, replacing an earlier one with its ID.
+    pub fn insert(&mut self, id: impl Into<String>, inherited: &[String]) {
+        self.inherits.insert(id.into(), inherited.to_vec());
+    }
+
+    /// The role templates inherited from but missing from the graph, with the one inheriting them.
+    pub fn missing(&self) -> Vec<(&str, &str)> {
+        self.inherits
+            .iter()
+            .flat_map(|(id, inherited)| inherited.iter().map(move |name| (id.as_str(), name.as_str())))
+            .filter(|(_, name)| !self.inherits.contains_key(*name))
+            .collect()
+    }
+
+    /// Every cycle of the graph once, as the path from its smallest ID back to it.
+    pub fn cycles(&self) -> Vec<Vec<&str>> {
+        // 1 while the role template is on the path being walked, 2 once everything it inherits is walked
+        fn walk<'a>(
+            graph: &'a InheritanceGraph,
+            id: &'a str,
+            path: &mut Vec<&'a str>,
+            state: &mut HashMap<&'a str, u8>,
+            cycles: &mut BTreeSet<Vec<&'a str>>,
+        ) {
+            state.insert(id, 1);
+            path.push(id);
+            for name in graph.inherits.get(id).into_iter().flatten() {
+                match state.get(name.as_str()) {
+                    Some(1) => {
+                        let start = path.iter().position(|on_path| on_path == name).unwrap_or_default();
+                        let mut cycle = path[start..].to_vec();
+                        let smallest = (0..cycle.len()).min_by_key(|&index| cycle[index]).unwrap_or_default();
+                        cycle.rotate_left(smallest);
+                        cycle.push(cycle[0]);
+                        cycles.insert(cycle);
+                    }
+                    Some(_) => {}
+                    None if graph.inherits.contains_key(name) => walk(graph, name, path, state, cycles),
+                    None => {}
+                }
+            }
+            path.pop();
+            state.insert(id, 2);
+        }
+
+        let mut state = HashMap::new();
+        let mut cycles = BTreeSet::new();
+        for id in self.inherits.keys() {
+            if !state.contains_key(id.as_str()) {
+                walk(self, id, &mut Vec::new(), &mut state, &mut cycles);
+            }
+        }
+        cycles.into_iter().collect()
+    }
+
+    /// `ids` grouped so each role template comes after the ones of `ids` it inherits from.
+    ///
+    /// The role templates of a cycle, and the ones inheriting from them, come last together.
+    pub fn levels(&self, ids: &[&str]) -> Vec<Vec<String>> {
+        let mut remaining: Vec<&str> = ids.to_vec();
+        let mut levels: Vec<Vec<String>> = Vec::new();
+        while !remaining.is_empty() {
+            let (ready, waiting): (Vec<&str>, Vec<&str>) = remaining.iter().partition(|id| {
+                self.inherits
+                    .get(**id)
+                    .into_iter()
+                    .flatten()
+                    .all(|name| name == *id || !remaining.contains(&name.as_str()))
+            });
+            if ready.is_empty() {
+                levels.push(waiting.iter().map(|id| id.to_string()).collect());
+                break;
+            }
+            levels.push(ready.iter().map(|id| id.to_string()).collect());
+            remaining = waiting;
+        }
+        levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule(rule: serde_json::Value) -> IoCattleManagementv3GlobalRoleRulesInner {
+        serde_json::from_value(rule).unwrap()
+    }
+
+    #[test]
+    fn test_rule_errors() {
+        // description, rule, namespaced, errors
+        let cases = [
+            ("resource rule", json!({ "apiGroups": [""], "resources": ["pods"], "verbs": ["get", "list"] }), true, vec![]),
+            ("wildcards", json!({ "apiGroups": ["*"], "resources": ["*"], "verbs": ["*"] }), true, vec![]),
+            ("special verb", json!({ "apiGroups": ["rbac.authorization.k8s.io"], "resources": ["roles"], "verbs": ["bind", "escalate"] }), false, vec![]),
+            ("rancher verb", json!({ "apiGroups": ["management.cattle.io"], "resources": ["projects"], "verbs": ["own"] }), false, vec![]),
+            ("no verbs", json!({ "apiGroups": [""], "resources": ["pods"], "verbs": [] }), false, vec!["rules[2]: verbs must not be empty"]),
+            (
+                "unknown verbs",
+                json!({ "apiGroups": [""], "resources": ["pods"], "verbs": ["get", "read", "Get"] }),
+                false,
+                vec!["rules[2]: unknown verb `read`", "rules[2]: unknown verb `Get`"],
+            ),
+            (
+                "no api group",
+                json!({ "resources": ["pods"], "verbs": ["get"] }),
+                false,
+                vec!["rules[2]: apiGroups must not be empty, use \"\" for the core group"],
+            ),
+            ("no resources", json!({ "apiGroups": [""], "resources": [], "verbs": ["get"] }), false, vec!["rules[2]: resources must not be empty"]),
+            ("non-resource URLs", json!({ "nonResourceURLs": ["/healthz", "/metrics/*"], "verbs": ["get", "head"] }), false, vec![]),
+            ("resource verb on URLs", json!({ "nonResourceURLs": ["/healthz"], "verbs": ["list"] }), false, vec!["rules[2]: unknown verb `list`"]),
+            (
+                "URLs in a project",
+                json!({ "nonResourceURLs": ["/healthz"], "verbs": ["get"] }),
+                true,
+                vec!["rules[2]: project role templates cannot grant nonResourceURLs"],
+            ),
+            (
+                "resources and URLs",
+                json!({ "apiGroups": [""], "resources": ["pods"], "nonResourceURLs": ["/healthz"], "verbs": ["get"] }),
+                false,
+                vec!["rules[2]: a rule cannot grant both resources and nonResourceURLs"],
+            ),
+        ];
+        for (description, value, namespaced, errors) in cases {
+            assert_eq!(rule_errors(2, &rule(value), namespaced), errors, "{}", description);
+        }
+    }
+
+    #[test]
+    fn test_role_template_errors_are_indexed_by_rule() {
+        let role_template: RoleTemplate = serde_json::from_value(json!({
+            "id": "rt-abc",
+            "context": "project",
+            "rules": [
+                { "apiGroups": [""], "resources": ["pods"], "verbs": ["get"] },
+                { "apiGroups": [""], "resources": ["secrets"], "verbs": [] },
+                { "nonResourceURLs": ["/healthz"], "verbs": ["get"] }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            role_template_errors(&role_template),
+            [
+                "rules[1]: verbs must not be empty",
+                "rules[2]: project role templates cannot grant nonResourceURLs"
+            ]
+        );
+    }
+
+    fn graph(edges: &[(&str, &[&str])]) -> InheritanceGraph {
+        let mut graph = InheritanceGraph::default();
+        for (id, inherited) in edges {
+            graph.insert(*id, &inherited.iter().map(|name| name.to_string()).collect::<Vec<_>>());
+        }
+        graph
+    }
+
+    #[test]
+    fn test_inheritance_cycles_and_missing_role_templates() {
+        // description, the graph, its cycles and the missing role templates
+        #[allow(clippy::type_complexity)]
+        let cases: [(&str, &[(&str, &[&str])], &[&[&str]], &[(&str, &str)]); 6] = [
+            ("no inheritance", &[("a", &[]), ("b", &[])], &[], &[]),
+            ("chain", &[("a", &["b"]), ("b", &["c"]), ("c", &[])], &[], &[]),
+            ("diamond", &[("a", &["b", "c"]), ("b", &["d"]), ("c", &["d"]), ("d", &[])], &[], &[]),
+            ("two role templates", &[("b", &["a"]), ("a", &["b"])], &[&["a", "b", "a"]], &[]),
+            ("itself", &[("a", &["a"])], &[&["a", "a"]], &[]),
+            (
+                "behind a chain, with a missing one",
+                &[("x", &["c"]), ("c", &["d", "gone"]), ("d", &["e"]), ("e", &["c"])],
+                &[&["c", "d", "e", "c"]],
+                &[("c", "gone")],
+            ),
+        ];
+        for (description, edges, cycles, missing) in cases {
+            let graph = graph(edges);
+            assert_eq!(graph.cycles(), cycles, "{}", description);
+            assert_eq!(graph.missing(), missing, "{}", description);
+        }
+    }
+
+    #[test]
+    fn test_levels_put_inherited_role_templates_first() {
+        let graph = graph(&[
+            ("child", &["parent", "read-only"]),
+            ("parent", &["grandparent"]),
+            ("grandparent", &[]),
+            ("sibling", &[]),
+            ("loop-a", &["loop-b"]),
+            ("loop-b", &["loop-a"]),
+        ]);
+
+        assert_eq!(
+            graph.levels(&["child", "sibling", "parent", "grandparent"]),
+            [vec!["sibling", "grandparent"], vec!["parent"], vec!["child"]]
+        );
+        // only the role templates of `ids` count, the others already exist
+        assert_eq!(graph.levels(&["child"]), [vec!["child"]]);
+        assert_eq!(graph.levels(&["sibling", "loop-a", "loop-b"]), [vec!["sibling"], vec!["loop-a", "loop-b"]]);
+    }
+}
@@ -0,0 +1,108 @@
+//! Files written and removed at paths built from names Rancher or the repository hands us.
+//!
+//! An object ID such as `../../etc/passwd`, or a symbolic link committed to the repository,
+//! would otherwise have a run write or delete files anywhere the process may.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::UnsafePathError;
+
+/// Symbolic links followed while resolving a path before it counts as a loop, as in Linux.
+const MAX_LINKS: usize = 40;
+
+/// `path` made absolute with its symbolic links, `.` and `..` resolved, also the parts of it
+/// that do not exist yet and links to them.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    resolve_following(path, &mut 0)
+}
+
+fn resolve_following(path: &Path, links: &mut usize) -> Option<PathBuf> {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().ok()?.join(path)
+    };
+    let mut resolved = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => resolved.push(component),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => {
+                resolved.push(name);
+                // a link is followed before the next `..` climbs out of where it leads,
+                // also when it leads nowhere yet, writing to it creates its target
+                if resolved.symlink_metadata().is_ok_and(|metadata| metadata.is_symlink()) {
+                    *links += 1;
+                    if *links > MAX_LINKS {
+                        return None;
+                    }
+                    let target = std::fs::read_link(&resolved).ok()?;
+                    resolved.pop();
+                    resolved = resolve_following(&resolved.join(target), links)?;
+                }
+            }
+        }
+    }
+    Some(resolved)
+}
+
+/// Make sure `path` stays under `root` once it is resolved, before a file is written to or
+/// removed at it.
+///
+/// # Errors
+/// [`UnsafePathError`] if `path` leads outside of `root` or cannot be resolved
+pub fn ensure_within(root: &Path, path: &Path) -> Result<(), UnsafePathError> {
+    let outside = || UnsafePathError {
+        path: path.to_path_buf(),
+        root: root.to_path_buf(),
+    };
+    let (Some(root), Some(resolved)) = (resolve(root), resolve(path)) else {
+        return Err(outside());
+    };
+    if resolved.starts_with(&root) {
+        Ok(())
+    } else {
+        Err(outside())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths_leading_out_of_the_root_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("rancher_config");
+        std::fs::create_dir_all(root.join("roles")).unwrap();
+
+        for inside in ["roles/admin.rt.yaml", "c-123/p-abc/p-abc.project.yaml", "roles/./a/../b.rt.yaml", "new-folder/deep/file"] {
+            assert_eq!(ensure_within(&root, &root.join(inside)), Ok(()), "{}", inside);
+        }
+        for hostile in ["../../etc/passwd", "roles/../../outside.rt.yaml", "c-123/../../../etc/passwd.project.yaml", "/etc/passwd"] {
+            let error = ensure_within(&root, &root.join(hostile)).unwrap_err();
+            assert_eq!(error.path, root.join(hostile), "{}", hostile);
+            assert!(error.to_string().contains("malicious or corrupted"), "{}", error);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symbolic_links_out_of_the_root_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("rancher_config");
+        let outside = dir.path().join("outside");
+        std::fs::create_dir_all(root.join("roles")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("c-123")).unwrap();
+        std::os::unix::fs::symlink(outside.join("passwd"), root.join("roles/admin.rt.yaml")).unwrap();
+        std::os::unix::fs::symlink(root.join("roles"), root.join("also-roles")).unwrap();
+
+        assert!(ensure_within(&root, &root.join("c-123/p-abc/p-abc.project.yaml")).is_err());
+        assert!(ensure_within(&root, &root.join("roles/admin.rt.yaml")).is_err());
+        assert_eq!(ensure_within(&root, &root.join("also-roles/admin-2.rt.yaml")), Ok(()));
+    }
+}
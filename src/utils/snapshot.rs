@@ -0,0 +1,241 @@
+//! The object files of a config folder that is not a git repository, as of the last run.
+//!
+//! With `git_enabled = false` the folder is kept by another tool, the new, modified and deleted
+//! object files are found by comparing it to the [`DirectorySnapshot`] of the last run, kept in
+//! the state file, instead of asking git.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::error::DeletionError;
+use crate::models::{MinimalObject, ObjectType, ResourceVersionMatch};
+use crate::utils::bundle::{is_bundle, project_document};
+use crate::utils::file::file_format_from_path;
+use crate::utils::ignore::contents_have_ignore_directive;
+use crate::utils::index::{ObjectKey, RepoIndex};
+
+/// An object file of a [`DirectorySnapshot`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SnapshotFile {
+    /// The git blob ID of the contents, as [`crate::state::blob_id`] hashes them
+    pub hash: String,
+    /// The object of the file, its ID is empty until Rancher named it
+    pub object: ObjectKey,
+    /// Whether the file carried the ignore directive
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ignored: bool,
+    /// The contents, the previous revision of the file for the next run, `None` in snapshots
+    /// of older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contents: Option<String>,
+}
+
+/// The object files of an endpoint folder by path.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DirectorySnapshot {
+    pub files: BTreeMap<PathBuf, SnapshotFile>,
+}
+
+/// The object files added, changed and removed since a [`DirectorySnapshot`].
+#[derive(Debug, Default, PartialEq)]
+pub struct DirectoryChanges {
+    pub new: Vec<(ObjectType, PathBuf)>,
+    pub modified: Vec<PathBuf>,
+    /// The removed files and what the snapshot knew of them
+    pub deleted: Vec<(PathBuf, SnapshotFile)>,
+}
+
+impl DirectorySnapshot {
+    /// The files of `index` as they are on disk, files that cannot be read are left out.
+    pub fn of(index: &RepoIndex) -> Self {
+        let files = index
+            .entries()
+            .filter_map(|(path, object)| Some((path.to_path_buf(), snapshot_file(path, object)?)))
+            .collect();
+        Self { files }
+    }
+
+    /// Take the files of `index` under `moved`, the folders and files Shepherd moved itself,
+    /// for known files instead of new ones, and forget the files that were there before.
+    pub fn rebase(&mut self, index: &RepoIndex, moved: &[PathBuf]) {
+        let under = |path: &Path| moved.iter().any(|moved| path.starts_with(moved));
+        self.files.retain(|path, _| !under(path));
+        for (path, object) in index.entries().filter(|(path, _)| under(path)) {
+            if let Some(file) = snapshot_file(path, object) {
+                self.files.insert(path.to_path_buf(), file);
+            }
+        }
+    }
+
+    /// The changes of the files of `index` since `previous`, the snapshot of the last run.
+    ///
+    /// Without a snapshot, on the first run, only the files of objects Rancher is to name are
+    /// new, the others are taken for objects that exist. A file that is gone from the index
+    /// but not from the disk, such as one that no longer parses, is not deleted.
+    pub fn changes(previous: Option<&Self>, index: &RepoIndex) -> DirectoryChanges {
+        let current = Self::of(index);
+        let mut changes = DirectoryChanges::default();
+        for (path, file) in &current.files {
+            match previous.map(|previous| previous.files.get(path)) {
+                Some(Some(known)) if known.hash != file.hash => changes.modified.push(path.clone()),
+                Some(Some(_)) => {}
+                Some(None) => changes.new.push((file.object.object_type, path.clone())),
+                None if file.object.object_id.is_empty() => changes.new.push((file.object.object_type, path.clone())),
+                None => {}
+            }
+        }
+        if previous.is_none() {
+            debug!("No snapshot of an earlier run, taking the {} files with an ID for existing objects", current.files.len() - changes.new.len());
+        }
+        for (path, file) in previous.map(|previous| &previous.files).into_iter().flatten() {
+            if !current.files.contains_key(path) && !path.exists() {
+                changes.deleted.push((path.clone(), file.clone()));
+            }
+        }
+        changes
+    }
+
+    /// Prepare the snapshot of a run for the next one: the `uncreated` new files stay new and
+    /// the files removed from `previous` stay removed until their object was `deleted`.
+    pub fn carry_over(&mut self, previous: Option<&Self>, uncreated: &[PathBuf], deleted: &[PathBuf]) {
+        self.files.retain(|path, _| !uncreated.contains(path));
+        for (path, file) in previous.map(|previous| &previous.files).into_iter().flatten() {
+            if !self.files.contains_key(path) && !path.exists() && !deleted.contains(path) {
+                self.files.insert(path.clone(), file.clone());
+            }
+        }
+    }
+}
+
+/// The file at `path` holding `object` as it is on disk, `None` if it cannot be read.
+fn snapshot_file(path: &Path, object: &ObjectKey) -> Option<SnapshotFile> {
+    let contents = std::fs::read(path).ok()?;
+    let hash = git2::Oid::hash_object(git2::ObjectType::Blob, &contents).ok()?.to_string();
+    let contents = String::from_utf8_lossy(&contents).into_owned();
+    // a bundle is its project, like in the index
+    let document = if is_bundle(path) { project_document(&contents) } else { None };
+    let ignored = contents_have_ignore_directive(document.as_deref().unwrap_or(&contents), &file_format_from_path(path));
+    Some(SnapshotFile { hash, object: object.clone(), ignored, contents: Some(contents) })
+}
+
+/// The object to delete for the removed file at `path`, as a snapshot knew it.
+///
+/// # Errors
+/// The same [`DeletionError`]s as [`crate::utils::file::get_object_to_delete`], except that
+/// the contents of the file are never parsed
+pub fn snapshot_object_to_delete(
+    path: &Path,
+    file: &SnapshotFile,
+    deletable_types: &[ObjectType],
+) -> Result<MinimalObject, DeletionError> {
+    let object_type = file.object.object_type;
+    if !object_type.deletable() {
+        return Err(DeletionError::Unsupported { object_type, path: path.to_path_buf() });
+    }
+    if !deletable_types.contains(&object_type) {
+        return Err(DeletionError::NotAllowed { object_type, path: path.to_path_buf() });
+    }
+    Ok(MinimalObject {
+        object_id: Some(file.object.object_id.clone()).filter(|id| !id.is_empty()),
+        resource_version_match: ResourceVersionMatch::NotOlderThan,
+        resource_version: None,
+        namespace: file.object.namespace.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::ignore::ShepherdIgnore;
+
+    fn write(path: &Path, contents: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    fn prtb(id: &str) -> String {
+        format!("id: {id}\nnamespace: p-abc\nproject_name: c-123:p-abc\nrole_template_name: project-member\nuser_name: u-abc\n")
+    }
+
+    #[tokio::test]
+    async fn test_changes_since_the_last_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = dir.path().join("rancher.example.com");
+        let project_folder = endpoint.join("c-123/p-abc");
+        write(&project_folder.join("p-abc.project.yaml"), "id: p-abc\ncluster_name: c-123\ndisplay_name: web\nnamespace: c-123\n");
+        write(&project_folder.join("prtb-kept.prtb.yaml"), &prtb("prtb-kept"));
+        write(&project_folder.join("prtb-gone.prtb.yaml"), &prtb("prtb-gone"));
+        write(&project_folder.join("prtb-broken.prtb.yaml"), &prtb("prtb-broken"));
+        write(&project_folder.join("new.prtb.yaml"), &prtb("''"));
+        let ignore = ShepherdIgnore::default();
+
+        // the first run creates the new binding only
+        let index = RepoIndex::build(dir.path(), &endpoint, &ignore).await;
+        let first = DirectorySnapshot::changes(None, &index);
+        assert_eq!(first.new, [(ObjectType::ProjectRoleTemplateBinding, project_folder.join("new.prtb.yaml"))]);
+        assert!(first.modified.is_empty() && first.deleted.is_empty());
+        let snapshot = DirectorySnapshot::of(&index);
+        assert_eq!(snapshot.files.len(), 5);
+
+        write(&project_folder.join("p-abc.project.yaml"), "id: p-abc\ncluster_name: c-123\ndisplay_name: api\nnamespace: c-123\n");
+        std::fs::remove_file(project_folder.join("prtb-gone.prtb.yaml")).unwrap();
+        write(&project_folder.join("prtb-broken.prtb.yaml"), "id: [");
+        write(&project_folder.join("prtb-added.prtb.yaml"), &prtb("prtb-added"));
+        let index = RepoIndex::build(dir.path(), &endpoint, &ignore).await;
+        let changes = DirectorySnapshot::changes(Some(&snapshot), &index);
+
+        assert_eq!(changes.new, [(ObjectType::ProjectRoleTemplateBinding, project_folder.join("prtb-added.prtb.yaml"))]);
+        assert_eq!(changes.modified, [project_folder.join("p-abc.project.yaml")]);
+        assert_eq!(changes.deleted.len(), 1);
+        let (path, file) = &changes.deleted[0];
+        assert_eq!(path, &project_folder.join("prtb-gone.prtb.yaml"));
+        let object = snapshot_object_to_delete(path, file, &[ObjectType::ProjectRoleTemplateBinding]).unwrap();
+        assert_eq!(object.object_id.as_deref(), Some("prtb-gone"));
+        assert_eq!(object.namespace.as_deref(), Some("p-abc"));
+        assert!(matches!(
+            snapshot_object_to_delete(path, file, &[ObjectType::Project]),
+            Err(DeletionError::NotAllowed { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_files_not_created_or_deleted_are_carried_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = dir.path().join("rancher.example.com");
+        let folder = endpoint.join("c-123/p-abc");
+        for id in ["prtb-a", "prtb-b"] {
+            write(&folder.join(format!("{id}.prtb.yaml")), &prtb(id));
+        }
+        let ignore = ShepherdIgnore::default();
+        let previous = DirectorySnapshot::of(&RepoIndex::build(dir.path(), &endpoint, &ignore).await);
+        std::fs::remove_file(folder.join("prtb-a.prtb.yaml")).unwrap();
+        std::fs::remove_file(folder.join("prtb-b.prtb.yaml")).unwrap();
+        write(&folder.join("new.prtb.yaml"), &prtb("''"));
+
+        let mut next = DirectorySnapshot::of(&RepoIndex::build(dir.path(), &endpoint, &ignore).await);
+        next.carry_over(Some(&previous), &[folder.join("new.prtb.yaml")], &[folder.join("prtb-a.prtb.yaml")]);
+
+        // the binding that failed to delete is deleted again, the new file created again
+        assert_eq!(next.files.keys().collect::<Vec<_>>(), [&folder.join("prtb-b.prtb.yaml")]);
+    }
+
+    #[tokio::test]
+    async fn test_files_moved_by_shepherd_are_neither_new_nor_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = dir.path().join("rancher.example.com");
+        let (old_folder, new_folder) = (endpoint.join("c-123/p-old"), endpoint.join("c-123/p-new"));
+        write(&old_folder.join("prtb-a.prtb.yaml"), &prtb("prtb-a"));
+        let ignore = ShepherdIgnore::default();
+        let mut snapshot = DirectorySnapshot::of(&RepoIndex::build(dir.path(), &endpoint, &ignore).await);
+        std::fs::rename(&old_folder, &new_folder).unwrap();
+
+        let index = RepoIndex::build(dir.path(), &endpoint, &ignore).await;
+        snapshot.rebase(&index, &[old_folder, new_folder.clone()]);
+
+        assert_eq!(DirectorySnapshot::changes(Some(&snapshot), &index), DirectoryChanges::default());
+        assert!(snapshot.files.contains_key(&new_folder.join("prtb-a.prtb.yaml")));
+    }
+}
@@ -0,0 +1,115 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Logs the tree of running async tasks, built from the frames of
+/// `#[async_backtrace::framed]` functions, at most once per `min_interval`.
+///
+/// Shows whether a hanging run waits on a git fetch, an API call or something else.
+#[derive(Debug)]
+pub struct TaskDumper {
+    min_interval: Duration,
+    last_dump: Mutex<Option<Instant>>,
+}
+
+impl TaskDumper {
+    pub fn new(min_interval: Duration) -> Self {
+        Self { min_interval, last_dump: Mutex::new(None) }
+    }
+
+    /// Claim a dump at `now`, refused while the last one is less than `min_interval` ago.
+    fn claim(&self, now: Instant) -> bool {
+        let mut last_dump = self.last_dump.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if last_dump.is_some_and(|last| now.duration_since(last) < self.min_interval) {
+            return false;
+        }
+        *last_dump = Some(now);
+        true
+    }
+
+    /// Log the task tree for `reason`, returning whether it was logged or skipped for the rate limit.
+    pub fn dump(&self, reason: &str) -> bool {
+        if !self.claim(Instant::now()) {
+            info!("Skipping task dump for {}, the last one is less than {:?} ago", reason, self.min_interval);
+            return false;
+        }
+        // running tasks are shown as polling, waiting for them could deadlock on a held lock
+        warn!("Task dump for {}:\n{}", reason, async_backtrace::taskdump_tree(false));
+        true
+    }
+}
+
+/// Dump the tasks every time the process receives `SIGUSR1`.
+///
+/// # Errors
+/// If the signal handler cannot be installed
+#[cfg(unix)]
+pub fn dump_on_sigusr1(dumper: Arc<TaskDumper>) -> std::io::Result<JoinHandle<()>> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signals = signal(SignalKind::user_defined1())?;
+    Ok(tokio::spawn(async move {
+        while signals.recv().await.is_some() {
+            dumper.dump("SIGUSR1");
+        }
+    }))
+}
+
+/// Dumps the tasks when a run is still going after a time limit, until it is dropped at the
+/// end of the run.
+#[derive(Debug)]
+pub struct RunWatchdog(JoinHandle<()>);
+
+impl RunWatchdog {
+    /// Watch a run, dumping the tasks once it takes longer than `limit` and again every
+    /// `limit` after that, as far as the rate limit of `dumper` lets it.
+    pub fn start(dumper: Arc<TaskDumper>, limit: Duration) -> Self {
+        Self(tokio::spawn(async move {
+            let mut elapsed = Duration::ZERO;
+            loop {
+                tokio::time::sleep(limit).await;
+                elapsed += limit;
+                dumper.dump(&format!("a run still going after {:?}", elapsed));
+            }
+        }))
+    }
+}
+
+impl Drop for RunWatchdog {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dumps_are_rate_limited() {
+        let dumper = TaskDumper::new(Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(dumper.claim(start));
+        assert!(!dumper.claim(start + Duration::from_secs(59)));
+        assert!(dumper.claim(start + Duration::from_secs(60)));
+        assert!(!dumper.claim(start + Duration::from_secs(61)));
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_dumps_a_run_over_its_limit_until_dropped() {
+        let dumper = Arc::new(TaskDumper::new(Duration::ZERO));
+        let watchdog = RunWatchdog::start(dumper.clone(), Duration::from_millis(100));
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(dumper.last_dump.lock().unwrap().is_none());
+        tokio::time::sleep(Duration::from_millis(130)).await;
+        let first = dumper.last_dump.lock().unwrap().expect("dumped after the limit");
+
+        drop(watchdog);
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(*dumper.last_dump.lock().unwrap(), Some(first));
+    }
+}
@@ -0,0 +1,253 @@
+use std::path::Path;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::utils::file::{file_format_from_path, FileFormat};
+
+/// A change of the YAML file an object is written back to, at the path of mapping keys
+/// from the root of the document.
+#[derive(Debug)]
+enum Edit {
+    Replace(Vec<String>, Value),
+    Remove(Vec<String>),
+    Insert(Vec<String>, String, Value),
+}
+
+/// The changes turning the mapping `old` into `new`, `None` if a key is not a string.
+fn diff(old: &Mapping, new: &Mapping, path: &[String], edits: &mut Vec<Edit>) -> Option<()> {
+    for (key, old_value) in old {
+        let key = key.as_str()?;
+        let key_path = [path, &[key.to_string()]].concat();
+        match new.get(key) {
+            None => edits.push(Edit::Remove(key_path)),
+            Some(new_value) if new_value == old_value => {}
+            // an empty mapping is written `{}` on the line of its key, it has no lines to add to
+            Some(Value::Mapping(new_map)) if matches!(old_value, Value::Mapping(m) if !m.is_empty()) && !new_map.is_empty() => {
+                diff(old_value.as_mapping()?, new_map, &key_path, edits)?;
+            }
+            Some(new_value) => edits.push(Edit::Replace(key_path, new_value.clone())),
+        }
+    }
+    for (key, value) in new {
+        if !old.contains_key(key) {
+            edits.push(Edit::Insert(path.to_vec(), key.as_str()?.to_string(), value.clone()));
+        }
+    }
+    Some(())
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Whether `line` holds YAML content rather than a comment, a blank or a document marker.
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim();
+    !(trimmed.is_empty() || trimmed.starts_with('#') || trimmed == "---" || trimmed == "...")
+}
+
+/// The key of the mapping entry `content` starts with and the position of the colon after it.
+fn parse_key(content: &str) -> Option<(String, usize)> {
+    let colon = if content.starts_with(['"', '\'']) {
+        let quote = content.chars().next()?;
+        let mut escaped = false;
+        let mut end = None;
+        for (index, c) in content.char_indices().skip(1) {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if quote == '"' => escaped = true,
+                _ if c == quote => {
+                    end = Some(index);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let colon = end? + 1;
+        content[colon..].starts_with(':').then_some(colon)?
+    } else {
+        content
+            .char_indices()
+            .find(|&(index, c)| c == ':' && content[index + 1..].chars().next().is_none_or(|next| next == ' '))?
+            .0
+    };
+    let key: String = serde_yaml::from_str(content[..colon].trim()).ok()?;
+    Some((key, colon))
+}
+
+/// The end of the value of the entry at `line`: the lines after it indented deeper, and the
+/// items of a sequence at its own indentation, leaving out trailing blanks and comments.
+fn block_end(lines: &[String], line: usize) -> usize {
+    let indent = indent_of(&lines[line]);
+    let mut end = line + 1;
+    for (index, next) in lines.iter().enumerate().skip(line + 1) {
+        if !is_content(next) {
+            continue;
+        }
+        let next_indent = indent_of(next);
+        if next_indent > indent || (next_indent == indent && next.trim_start().starts_with('-')) {
+            end = index + 1;
+        } else {
+            break;
+        }
+    }
+    end
+}
+
+/// The range of lines of the entries of the mapping at `path`, and their indentation.
+fn mapping_lines(lines: &[String], path: &[String]) -> Option<(usize, usize, usize)> {
+    let (start, end) = match path.split_last() {
+        None => (0, lines.len()),
+        Some((key, parent)) => {
+            let line = find_key(lines, parent, key)?;
+            (line + 1, block_end(lines, line))
+        }
+    };
+    let indent = indent_of(lines[start..end].iter().find(|line| is_content(line))?);
+    Some((start, end, indent))
+}
+
+/// The line of the entry `key` of the mapping at `parent`.
+fn find_key(lines: &[String], parent: &[String], key: &str) -> Option<usize> {
+    let (start, end, indent) = mapping_lines(lines, parent)?;
+    (start..end).find(|&index| {
+        let line = &lines[index];
+        is_content(line) && indent_of(line) == indent && parse_key(&line[indent..]).is_some_and(|(found, _)| found == key)
+    })
+}
+
+/// `key: value` as serde_yaml writes it, indented by `indent`.
+fn render_entry(key: &str, value: &Value, indent: usize) -> Option<Vec<String>> {
+    let mut entry = Mapping::new();
+    entry.insert(Value::String(key.to_string()), value.clone());
+    let rendered = serde_yaml::to_string(&entry).ok()?;
+    Some(rendered.lines().map(|line| format!("{}{}", " ".repeat(indent), line)).collect())
+}
+
+/// The comment ending `value`, the text after a key, with the spaces before it.
+fn trailing_comment(value: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (index, c) in value.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '#') if previous == ' ' => return &value[value[..index].trim_end().len()..],
+            _ => {}
+        }
+        previous = c;
+    }
+    ""
+}
+
+fn apply(lines: &mut Vec<String>, edit: &Edit) -> Option<()> {
+    match edit {
+        Edit::Replace(path, value) => {
+            let (key, parent) = path.split_last()?;
+            let line = find_key(lines, parent, key)?;
+            let end = block_end(lines, line);
+            let indent = indent_of(&lines[line]);
+            let rendered = render_entry(key, value, indent)?;
+            let (_, colon) = parse_key(&lines[line][indent..])?;
+            let colon = indent + colon;
+            let old_value = lines[line][colon + 1..].trim();
+            // a scalar on the line of its key is swapped in place, keeping the comment after it
+            if end == line + 1 && rendered.len() == 1 && !value.is_mapping() && !value.is_sequence() && !old_value.is_empty() {
+                let comment = trailing_comment(&lines[line][colon + 1..]).to_string();
+                let (_, new_value) = rendered[0].split_at(indent + parse_key(&rendered[0][indent..])?.1 + 1);
+                lines[line] = format!("{}:{}{}", &lines[line][..colon], new_value, comment);
+            } else {
+                lines.splice(line..end, rendered);
+            }
+        }
+        Edit::Remove(path) => {
+            let (key, parent) = path.split_last()?;
+            let line = find_key(lines, parent, key)?;
+            let end = block_end(lines, line);
+            lines.drain(line..end);
+        }
+        Edit::Insert(path, key, value) => {
+            let (start, end, indent) = mapping_lines(lines, path)?;
+            // the root has no block to end, its last entry is followed by the closing comments
+            let after = match path.is_empty() {
+                true => (start..end).rev().find(|&index| is_content(&lines[index]))? + 1,
+                false => end,
+            };
+            lines.splice(after..after, render_entry(key, value, indent)?);
+        }
+    }
+    Some(())
+}
+
+/// `updated`, a YAML object as serde_yaml writes it, written over the YAML file `existing` by
+/// editing only the entries that changed, so the comments of the file are kept.
+///
+/// Returns `None` when a full rewrite is needed instead: `existing` has no comments, either
+/// side is not a single mapping, or the edited file would not read back as `updated`.
+pub fn merge_preserving_comments(existing: &str, updated: &str) -> Option<String> {
+    if !existing.contains('#') {
+        return None;
+    }
+    let old: Value = serde_yaml::from_str(existing).ok()?;
+    let new: Value = serde_yaml::from_str(updated).ok()?;
+    if old == new {
+        return Some(existing.to_string());
+    }
+    let mut edits = Vec::new();
+    diff(old.as_mapping()?, new.as_mapping()?, &[], &mut edits)?;
+    let mut lines: Vec<String> = existing.lines().map(str::to_string).collect();
+    for edit in &edits {
+        apply(&mut lines, edit)?;
+    }
+    let mut merged = lines.join("\n");
+    merged.push('\n');
+    let reread: Value = serde_yaml::from_str(&merged).ok()?;
+    (reread == new).then_some(merged)
+}
+
+/// `serialized` to write to `path`, merged into the YAML file already there to keep its comments.
+pub async fn keep_comments(path: &Path, serialized: String) -> String {
+    if file_format_from_path(path) != FileFormat::Yaml {
+        return serialized;
+    }
+    match tokio::fs::read_to_string(path).await {
+        Ok(existing) => merge_preserving_comments(&existing, &serialized).unwrap_or(serialized),
+        Err(_) => serialized,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comments_survive_a_resource_version_bump() {
+        let existing = "# approved by security 2024-05\nid: p-abc\ndisplay_name: web # owned by team web\nresource_version: '100'\nannotations:\n  # keep for the audit\n  team: web\n";
+        let updated = "id: p-abc\ndisplay_name: web\nresource_version: '101'\nannotations:\n  team: web\n";
+
+        let merged = merge_preserving_comments(existing, updated).unwrap();
+
+        assert_eq!(merged, existing.replace("'100'", "'101'"));
+    }
+
+    #[test]
+    fn test_added_removed_and_nested_entries_are_edited_in_place() {
+        let existing = "# approved by security 2024-05\nid: ''\ndisplay_name: web # owned by team web\ndescription: old\nannotations:\n  # keep for the audit\n  team: web\nlabels: {}\n";
+        let updated = "id: p-abc\ndisplay_name: web\nannotations:\n  team: api\n  tier: gold\nlabels:\n  env: prod\nuuid: 1234\n";
+
+        let merged = merge_preserving_comments(existing, updated).unwrap();
+
+        assert_eq!(
+            merged,
+            "# approved by security 2024-05\nid: p-abc\ndisplay_name: web # owned by team web\nannotations:\n  # keep for the audit\n  team: api\n  tier: gold\nlabels:\n  env: prod\nuuid: 1234\n"
+        );
+    }
+
+    #[test]
+    fn test_files_without_comments_or_that_cannot_be_edited_are_rewritten() {
+        assert_eq!(merge_preserving_comments("id: p-abc\n", "id: p-def\n"), None);
+        // a flow mapping has no lines to edit
+        assert_eq!(merge_preserving_comments("# note\nannotations: {team: web}\n", "annotations:\n  team: api\n"), None);
+        assert_eq!(merge_preserving_comments("# note\n- a\n", "id: p-abc\n"), None);
+    }
+}
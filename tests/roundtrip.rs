@@ -0,0 +1,243 @@
+//! Downloads a mocked endpoint in every file format, loads it back and checks that nothing
+//! was lost on the way: the objects read back match the fixtures served and comparing the
+//! repository with the endpoint finds nothing to change.
+//!
+//! The fixtures leave out `apiVersion` and `kind`, the files do not store them. Role templates
+//! and project role template bindings are written without the fields of their exclude paths,
+//! such as `resourceVersion` and `ownerReferences`.
+
+use serde_json::{json, Value};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use rancher_client::apis::configuration::Configuration;
+use shepherd::api::config::RancherClusterConfig;
+use shepherd::models::{DownloadOptions, ManagementMode, RetryPolicy};
+use shepherd::modify::compare_cluster;
+use shepherd::resources::cluster::ClusterFolderNaming;
+use shepherd::resources::prtb::PrtbListing;
+use shepherd::resources::rt::RoleTemplateFilter;
+use shepherd::utils::diff::MetadataFilter;
+use shepherd::utils::file::FileFormat;
+use shepherd::utils::principal::PrincipalRules;
+use shepherd::resources::prtb::PRTB_EXCLUDE_PATHS;
+use shepherd::resources::rt::RT_EXCLUDE_PATHS;
+use shepherd::{clean_up_value, download_current_configuration, load_configuration, RemoteCache};
+
+const CLUSTER_ID: &str = "c-123";
+
+fn cluster() -> Value {
+    json!({
+        "metadata": { "name": CLUSTER_ID },
+        "spec": { "displayName": "prod", "description": "Production" }
+    })
+}
+
+fn role_template() -> Value {
+    json!({
+        "metadata": {
+            "name": "rt-reader",
+            "labels": { "team": "platform", "env": "prod" },
+            "resourceVersion": "11",
+            "creationTimestamp": "2026-10-01T08:00:00Z"
+        },
+        "context": "project",
+        "displayName": "Reader",
+        "description": "Reads workloads",
+        "roleTemplateNames": ["view"],
+        "rules": [
+            { "apiGroups": [""], "resources": ["pods", "services"], "verbs": ["get", "list", "watch"] },
+            { "apiGroups": ["apps"], "resources": ["deployments"], "verbs": ["get"] }
+        ]
+    })
+}
+
+fn project() -> Value {
+    json!({
+        "metadata": {
+            "name": "p-abc",
+            "namespace": CLUSTER_ID,
+            "annotations": { "example.com/owner": "web", "example.com/cost-center": "42" },
+            "labels": { "tier": "gold" },
+            "resourceVersion": "21",
+            "uid": "3f6c-project"
+        },
+        "spec": {
+            "clusterName": CLUSTER_ID,
+            "displayName": "web",
+            "description": "Web team",
+            "containerDefaultResourceLimit": { "limitsCpu": "500m", "limitsMemory": "512Mi" },
+            "namespaceDefaultResourceQuota": { "limit": { "pods": "10" } },
+            "resourceQuota": { "limit": { "pods": "50", "configMaps": "20" } }
+        }
+    })
+}
+
+fn prtb() -> Value {
+    json!({
+        "metadata": {
+            "name": "prtb-web",
+            "namespace": "p-abc",
+            "labels": { "team": "web" },
+            "resourceVersion": "31",
+            "uid": "3f6c-prtb",
+            "ownerReferences": [{ "apiVersion": "management.cattle.io/v3", "kind": "Project", "name": "p-abc", "uid": "3f6c-project" }]
+        },
+        "projectName": "c-123:p-abc",
+        "roleTemplateName": "rt-reader",
+        "userName": "u-abc",
+        "userPrincipalName": "local://u-abc"
+    })
+}
+
+fn crtb() -> Value {
+    json!({
+        "metadata": {
+            "name": "crtb-ops",
+            "namespace": CLUSTER_ID,
+            "annotations": { "example.com/ticket": "OPS-1" },
+            "resourceVersion": "41",
+            "uid": "3f6c-crtb"
+        },
+        "clusterName": CLUSTER_ID,
+        "roleTemplateName": "cluster-member",
+        "groupPrincipalName": "github_team://1"
+    })
+}
+
+async fn mock_endpoint() -> MockServer {
+    let list = |items: Vec<Value>| json!({ "metadata": {}, "items": items });
+    let server = MockServer::start().await;
+    let mocks = [
+        ("/apis/management.cattle.io/v3/clusters".to_string(), list(vec![cluster()])),
+        ("/apis/management.cattle.io/v3/roletemplates".to_string(), list(vec![role_template()])),
+        (format!("/apis/management.cattle.io/v3/namespaces/{CLUSTER_ID}/projects"), list(vec![project()])),
+        ("/apis/management.cattle.io/v3/namespaces/p-abc/projectroletemplatebindings".to_string(), list(vec![prtb()])),
+        (format!("/apis/management.cattle.io/v3/namespaces/{CLUSTER_ID}/clusterroletemplatebindings"), list(vec![crtb()])),
+    ];
+    for (mock_path, body) in mocks {
+        Mock::given(method("GET"))
+            .and(path(mock_path))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&server)
+            .await;
+    }
+    server
+}
+
+/// Fails unless every field of `expected` is in `actual` with the same value, `actual` may
+/// hold more, such as fields Rancher defaults.
+fn assert_contains(actual: &Value, expected: &Value, at: &str) {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => {
+            for (key, value) in expected {
+                let field = format!("{at}/{key}");
+                let found = actual.get(key).unwrap_or_else(|| panic!("{field} is missing"));
+                assert_contains(found, value, &field);
+            }
+        }
+        (Value::Array(actual), Value::Array(expected)) => {
+            assert_eq!(actual.len(), expected.len(), "{at} has another length");
+            for (index, (found, value)) in actual.iter().zip(expected).enumerate() {
+                assert_contains(found, value, &format!("{at}/{index}"));
+            }
+        }
+        _ => assert_eq!(actual, expected, "{at} differs"),
+    }
+}
+
+/// `object` as its file stores it, without the fields at `exclude_paths`.
+fn as_stored(mut object: Value, exclude_paths: &[&str]) -> Value {
+    clean_up_value(&mut object, exclude_paths);
+    object
+}
+
+/// Fails if `object` has a field at one of `exclude_paths`.
+fn assert_excluded(object: &Value, exclude_paths: &[&str], at: &str) {
+    for path in exclude_paths {
+        let pointer = format!("/{}", path.replace('.', "/"));
+        assert!(object.pointer(&pointer).is_none(), "{at} stored {path}");
+    }
+}
+
+async fn round_trip(file_format: FileFormat) {
+    let server = mock_endpoint().await;
+    let mut configuration = Configuration::new();
+    configuration.base_path = server.uri();
+    let dir = tempfile::tempdir().unwrap();
+    let metadata_filter = MetadataFilter::default();
+    let role_template_filter = RoleTemplateFilter::default();
+    let retry_policy = RetryPolicy::default();
+
+    download_current_configuration(
+        &configuration,
+        dir.path(),
+        &file_format,
+        &metadata_filter,
+        &role_template_filter,
+        &retry_policy,
+        false,
+        ClusterFolderNaming::Id,
+        PrtbListing::PerProject,
+        &DownloadOptions::default(),
+    )
+    .await
+    .unwrap_or_else(|e| panic!("{file_format}: download failed: {e:#}"));
+
+    let stored = load_configuration(dir.path(), &configuration.base_path, CLUSTER_ID, &file_format, false)
+        .await
+        .unwrap_or_else(|e| panic!("{file_format}: load failed: {e:#}"))
+        .unwrap_or_else(|| panic!("{file_format}: no configuration was loaded"));
+    assert!(stored.malformed.is_empty(), "{file_format}: {:?}", stored.malformed);
+
+    let converted = RancherClusterConfig::try_from(stored).unwrap();
+    let at = |object: &str| format!("{file_format} {object}");
+    assert_contains(&serde_json::to_value(&converted.cluster).unwrap(), &cluster(), &at("cluster"));
+    assert_eq!(converted.role_templates.len(), 1, "{file_format}");
+    let role_template_object = serde_json::to_value(&converted.role_templates[0]).unwrap();
+    assert_contains(&role_template_object, &as_stored(role_template(), RT_EXCLUDE_PATHS), &at("role template"));
+    assert_excluded(&role_template_object, RT_EXCLUDE_PATHS, &at("role template"));
+    let (project_object, prtbs) = &converted.projects["p-abc"];
+    assert_contains(&serde_json::to_value(project_object).unwrap(), &project(), &at("project"));
+    assert_eq!(prtbs.len(), 1, "{file_format}");
+    let prtb_object = serde_json::to_value(&prtbs[0]).unwrap();
+    assert_contains(&prtb_object, &as_stored(prtb(), PRTB_EXCLUDE_PATHS), &at("prtb"));
+    assert_excluded(&prtb_object, PRTB_EXCLUDE_PATHS, &at("prtb"));
+    assert_eq!(converted.crtbs.len(), 1, "{file_format}");
+    assert_contains(&serde_json::to_value(&converted.crtbs[0]).unwrap(), &crtb(), &at("crtb"));
+
+    let comparison = compare_cluster(
+        &configuration,
+        dir.path(),
+        CLUSTER_ID,
+        &file_format,
+        &metadata_filter,
+        &PrincipalRules::default(),
+        &role_template_filter,
+        &retry_policy,
+        &RemoteCache::default(),
+        ManagementMode::RemoteIsSource,
+        false,
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    let changes: Vec<_> = comparison.diff.changes().collect();
+    assert!(changes.is_empty(), "{file_format}: {changes:#?}");
+    assert!(comparison.diff.patches().is_empty(), "{file_format}");
+}
+
+#[tokio::test]
+async fn test_yaml_round_trip_is_lossless() {
+    round_trip(FileFormat::Yaml).await;
+}
+
+#[tokio::test]
+async fn test_json_round_trip_is_lossless() {
+    round_trip(FileFormat::Json).await;
+}
+
+#[tokio::test]
+async fn test_toml_round_trip_is_lossless() {
+    round_trip(FileFormat::Toml).await;
+}